@@ -1,3 +1,4 @@
+use bstr::BString;
 use mlua::prelude::*;
 
 use rbx_dom_weak::types::{Variant as DomValue, VariantType as DomType};
@@ -115,6 +116,13 @@ impl<'lua> LuaToDomValue<'lua> for LuaValue<'lua> {
                     dom::PhysicalProperties::Default,
                 )),
 
+                // NOTE: Binary payloads such as animation curves are
+                // easier to build using buffers, so we accept those too
+                (LuaValue::UserData(_), DomType::BinaryString) if self.is_buffer() => {
+                    let bytes = BString::from_lua(self.clone(), lua)?;
+                    Ok(DomValue::BinaryString(Vec::from(bytes).into()))
+                }
+
                 (LuaValue::UserData(u), d) => u.lua_to_dom_value(lua, Some(d)),
 
                 (v, d) => Err(DomConversionError::ToDomValue {
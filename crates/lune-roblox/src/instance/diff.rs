@@ -0,0 +1,288 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use mlua::prelude::*;
+use rbx_dom_weak::{
+    types::{Ref as DomRef, Variant as DomValue},
+    WeakDom,
+};
+
+use lune_utils::TableBuilder;
+
+use crate::datatypes::conversion::DomValueToLua;
+
+use super::{Instance, INTERNAL_DOM};
+
+/**
+    A difference between the values of a property of two instances.
+
+    Properties that are not set use the default value for the class,
+    if there is one, and are otherwise treated as having no value.
+*/
+#[derive(Debug, Clone)]
+pub struct PropertyDiff {
+    pub name: String,
+    pub old: Option<DomValue>,
+    pub new: Option<DomValue>,
+}
+
+/**
+    A difference between two trees of instances.
+*/
+#[derive(Debug, Clone)]
+pub enum InstanceDiff {
+    Added {
+        path: String,
+        instance: Instance,
+    },
+    Removed {
+        path: String,
+        instance: Instance,
+    },
+    Changed {
+        path: String,
+        old: Instance,
+        new: Instance,
+        properties: Vec<PropertyDiff>,
+    },
+}
+
+enum Entry {
+    Added(String, DomRef),
+    Removed(String, DomRef),
+    Matched(String, DomRef, DomRef, Vec<PropertyDiff>),
+}
+
+/**
+    Diffs two trees of instances, returning all instances that were added, removed,
+    or that had their properties changed, in the same order as they are in the trees.
+
+    Instances are matched by name and class name, with siblings that share both being
+    matched in order. Instances that were added or removed are returned without any of
+    their descendants, and references between instances are compared using the matches,
+    meaning that two references are the same if they refer to matching instances.
+*/
+#[must_use]
+pub fn diff_instances(old: &[Instance], new: &[Instance]) -> Vec<InstanceDiff> {
+    let dom = INTERNAL_DOM.lock().expect("Failed to lock document");
+
+    let old_refs = old.iter().map(|i| i.dom_ref).collect::<Vec<_>>();
+    let new_refs = new.iter().map(|i| i.dom_ref).collect::<Vec<_>>();
+
+    let mut entries = Vec::new();
+    let mut matches = HashMap::new();
+    match_instances(&dom, None, &old_refs, &new_refs, &mut entries, &mut matches);
+
+    // NOTE: References may point to instances that are matched after the instances
+    // that refer to them, so properties can only be diffed once matching is done
+    for entry in &mut entries {
+        if let Entry::Matched(_, old_ref, new_ref, properties) = entry {
+            *properties = diff_properties(&dom, *old_ref, *new_ref, &matches);
+        }
+    }
+
+    drop(dom); // Instance::new needs mutex handle, drop it first
+    entries
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Entry::Added(path, dom_ref) => Some(InstanceDiff::Added {
+                path,
+                instance: Instance::new(dom_ref),
+            }),
+            Entry::Removed(path, dom_ref) => Some(InstanceDiff::Removed {
+                path,
+                instance: Instance::new(dom_ref),
+            }),
+            Entry::Matched(_, _, _, properties) if properties.is_empty() => None,
+            Entry::Matched(path, old_ref, new_ref, properties) => Some(InstanceDiff::Changed {
+                path,
+                old: Instance::new(old_ref),
+                new: Instance::new(new_ref),
+                properties,
+            }),
+        })
+        .collect()
+}
+
+fn match_instances(
+    dom: &WeakDom,
+    parent_path: Option<&str>,
+    old: &[DomRef],
+    new: &[DomRef],
+    entries: &mut Vec<Entry>,
+    matches: &mut HashMap<DomRef, DomRef>,
+) {
+    let get = |dom_ref: DomRef| {
+        dom.get_by_ref(dom_ref)
+            .expect("Failed to find instance in document")
+    };
+    let path_of = |dom_ref: DomRef| match parent_path {
+        Some(parent_path) => format!("{parent_path}.{}", get(dom_ref).name),
+        None => get(dom_ref).name.clone(),
+    };
+
+    let mut candidates = HashMap::<(&str, &str), VecDeque<DomRef>>::new();
+    for &new_ref in new {
+        let inst = get(new_ref);
+        candidates
+            .entry((inst.name.as_str(), inst.class.as_str()))
+            .or_default()
+            .push_back(new_ref);
+    }
+
+    let mut matched = HashSet::new();
+    for &old_ref in old {
+        let inst = get(old_ref);
+        let path = path_of(old_ref);
+        let candidate = candidates
+            .get_mut(&(inst.name.as_str(), inst.class.as_str()))
+            .and_then(VecDeque::pop_front);
+        if let Some(new_ref) = candidate {
+            matched.insert(new_ref);
+            matches.insert(old_ref, new_ref);
+            entries.push(Entry::Matched(path.clone(), old_ref, new_ref, Vec::new()));
+            match_instances(
+                dom,
+                Some(&path),
+                inst.children(),
+                get(new_ref).children(),
+                entries,
+                matches,
+            );
+        } else {
+            entries.push(Entry::Removed(path, old_ref));
+        }
+    }
+
+    for &new_ref in new {
+        if !matched.contains(&new_ref) {
+            entries.push(Entry::Added(path_of(new_ref), new_ref));
+        }
+    }
+}
+
+fn diff_properties(
+    dom: &WeakDom,
+    old_ref: DomRef,
+    new_ref: DomRef,
+    matches: &HashMap<DomRef, DomRef>,
+) -> Vec<PropertyDiff> {
+    let old = dom.get_by_ref(old_ref).expect("Failed to find instance");
+    let new = dom.get_by_ref(new_ref).expect("Failed to find instance");
+
+    let prop_names = old
+        .properties
+        .keys()
+        .chain(new.properties.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut diffs = Vec::new();
+    for prop_name in prop_names {
+        let old_value = old
+            .properties
+            .get(prop_name)
+            .or_else(|| find_property_default(&old.class, prop_name));
+        let new_value = new
+            .properties
+            .get(prop_name)
+            .or_else(|| find_property_default(&new.class, prop_name));
+        let (old_value, new_value) = (non_empty(old_value), non_empty(new_value));
+        let same = match (old_value, new_value) {
+            (Some(DomValue::Ref(old_ref)), Some(DomValue::Ref(new_ref))) => {
+                old_ref == new_ref || matches.get(old_ref) == Some(new_ref)
+            }
+            (old_value, new_value) => old_value == new_value,
+        };
+        if !same {
+            diffs.push(PropertyDiff {
+                name: prop_name.clone(),
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            });
+        }
+    }
+    diffs
+}
+
+// NOTE: Instances get empty attributes and tags when the last ones are removed,
+// those are the same as not having any attributes or tags set in the first place
+fn non_empty(value: Option<&DomValue>) -> Option<&DomValue> {
+    match value {
+        Some(DomValue::Attributes(attributes)) if attributes.is_empty() => None,
+        Some(DomValue::Tags(tags)) if tags.iter().next().is_none() => None,
+        value => value,
+    }
+}
+
+fn find_property_default(class_name: &str, prop_name: &str) -> Option<&'static DomValue> {
+    let db = rbx_reflection_database::get();
+
+    let mut class_name = class_name;
+    while let Some(class) = db.classes.get(class_name) {
+        if let Some(default) = class.default_properties.get(prop_name) {
+            return Some(default);
+        }
+        class_name = class.superclass.as_deref()?;
+    }
+    None
+}
+
+// NOTE: Attributes and tags can not be converted like other values, but we still want
+// them to be readable in diffs, and values that can not be represented at all are nil
+fn property_value_to_lua<'lua>(
+    lua: &'lua Lua,
+    value: Option<&DomValue>,
+) -> LuaResult<LuaValue<'lua>> {
+    match value {
+        None => Ok(LuaValue::Nil),
+        Some(DomValue::Attributes(attributes)) => {
+            let tab = lua.create_table()?;
+            for (name, value) in attributes {
+                tab.set(name.as_str(), property_value_to_lua(lua, Some(value))?)?;
+            }
+            Ok(LuaValue::Table(tab))
+        }
+        Some(DomValue::Tags(tags)) => lua
+            .create_sequence_from(tags.iter().map(ToString::to_string))
+            .map(LuaValue::Table),
+        Some(value) => Ok(LuaValue::dom_value_to_lua(lua, value).unwrap_or(LuaValue::Nil)),
+    }
+}
+
+impl<'lua> IntoLua<'lua> for PropertyDiff {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("name", self.name)?
+            .with_value("old", property_value_to_lua(lua, self.old.as_ref())?)?
+            .with_value("new", property_value_to_lua(lua, self.new.as_ref())?)?
+            .build_readonly()?
+            .into_lua(lua)
+    }
+}
+
+impl<'lua> IntoLua<'lua> for InstanceDiff {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let builder = TableBuilder::new(lua)?;
+        let builder = match self {
+            Self::Added { path, instance } => builder
+                .with_value("kind", "added")?
+                .with_value("path", path)?
+                .with_value("instance", instance)?,
+            Self::Removed { path, instance } => builder
+                .with_value("kind", "removed")?
+                .with_value("path", path)?
+                .with_value("instance", instance)?,
+            Self::Changed {
+                path,
+                old,
+                new,
+                properties,
+            } => builder
+                .with_value("kind", "changed")?
+                .with_value("path", path)?
+                .with_value("old", old)?
+                .with_value("new", new)?
+                .with_value("properties", properties)?,
+        };
+        builder.build_readonly()?.into_lua(lua)
+    }
+}
@@ -0,0 +1,102 @@
+use mlua::prelude::*;
+
+use crate::shared::classes::{
+    add_child_of_class, add_class_restricted_method, get_children_of_class, remove_child,
+};
+
+use super::Instance;
+
+pub const CLASS_NAME: &str = "Keyframe";
+
+pub fn add_methods<'lua, M: LuaUserDataMethods<'lua, Instance>>(m: &mut M) {
+    add_class_restricted_method(m, CLASS_NAME, "GetPoses", keyframe_get_poses);
+    add_class_restricted_method(m, CLASS_NAME, "AddPose", keyframe_add_pose);
+    add_class_restricted_method(m, CLASS_NAME, "RemovePose", keyframe_remove_pose);
+    add_class_restricted_method(m, CLASS_NAME, "GetMarkers", keyframe_get_markers);
+    add_class_restricted_method(m, CLASS_NAME, "AddMarker", keyframe_add_marker);
+    add_class_restricted_method(m, CLASS_NAME, "RemoveMarker", keyframe_remove_marker);
+}
+
+/**
+    Gets all of the poses that have been added to this `Keyframe`.
+
+    ### See Also
+    * [`GetPoses`](https://create.roblox.com/docs/reference/engine/classes/Keyframe#GetPoses)
+    on the Roblox Developer Hub
+*/
+fn keyframe_get_poses(_: &Lua, this: &Instance, (): ()) -> LuaResult<Vec<Instance>> {
+    Ok(get_children_of_class(this, "PoseBase"))
+}
+
+/**
+    Adds a pose to this `Keyframe` by parenting it to the keyframe.
+
+    ### See Also
+    * [`AddPose`](https://create.roblox.com/docs/reference/engine/classes/Keyframe#AddPose)
+    on the Roblox Developer Hub
+*/
+fn keyframe_add_pose<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    pose: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    add_child_of_class(this, &pose, "PoseBase")
+}
+
+/**
+    Removes a pose from this `Keyframe` by unparenting it from the keyframe.
+
+    ### See Also
+    * [`RemovePose`](https://create.roblox.com/docs/reference/engine/classes/Keyframe#RemovePose)
+    on the Roblox Developer Hub
+*/
+fn keyframe_remove_pose<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    pose: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    remove_child(this, &pose);
+    Ok(())
+}
+
+/**
+    Gets all of the markers that have been added to this `Keyframe`.
+
+    ### See Also
+    * [`GetMarkers`](https://create.roblox.com/docs/reference/engine/classes/Keyframe#GetMarkers)
+    on the Roblox Developer Hub
+*/
+fn keyframe_get_markers(_: &Lua, this: &Instance, (): ()) -> LuaResult<Vec<Instance>> {
+    Ok(get_children_of_class(this, "KeyframeMarker"))
+}
+
+/**
+    Adds a marker to this `Keyframe` by parenting it to the keyframe.
+
+    ### See Also
+    * [`AddMarker`](https://create.roblox.com/docs/reference/engine/classes/Keyframe#AddMarker)
+    on the Roblox Developer Hub
+*/
+fn keyframe_add_marker<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    marker: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    add_child_of_class(this, &marker, "KeyframeMarker")
+}
+
+/**
+    Removes a marker from this `Keyframe` by unparenting it from the keyframe.
+
+    ### See Also
+    * [`RemoveMarker`](https://create.roblox.com/docs/reference/engine/classes/Keyframe#RemoveMarker)
+    on the Roblox Developer Hub
+*/
+fn keyframe_remove_marker<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    marker: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    remove_child(this, &marker);
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use mlua::prelude::*;
+
+use crate::shared::classes::{
+    add_child_of_class, add_class_restricted_method, get_children_of_class, remove_child,
+};
+
+use super::Instance;
+
+pub const CLASS_NAME: &str = "KeyframeSequence";
+
+pub fn add_methods<'lua, M: LuaUserDataMethods<'lua, Instance>>(m: &mut M) {
+    add_class_restricted_method(
+        m,
+        CLASS_NAME,
+        "GetKeyframes",
+        keyframe_sequence_get_keyframes,
+    );
+    add_class_restricted_method(m, CLASS_NAME, "AddKeyframe", keyframe_sequence_add_keyframe);
+    add_class_restricted_method(
+        m,
+        CLASS_NAME,
+        "RemoveKeyframe",
+        keyframe_sequence_remove_keyframe,
+    );
+}
+
+/**
+    Gets all of the keyframes that have been added to this `KeyframeSequence`.
+
+    ### See Also
+    * [`GetKeyframes`](https://create.roblox.com/docs/reference/engine/classes/KeyframeSequence#GetKeyframes)
+    on the Roblox Developer Hub
+*/
+fn keyframe_sequence_get_keyframes(_: &Lua, this: &Instance, (): ()) -> LuaResult<Vec<Instance>> {
+    Ok(get_children_of_class(this, "Keyframe"))
+}
+
+/**
+    Adds a keyframe to this `KeyframeSequence` by parenting it to the sequence.
+
+    ### See Also
+    * [`AddKeyframe`](https://create.roblox.com/docs/reference/engine/classes/KeyframeSequence#AddKeyframe)
+    on the Roblox Developer Hub
+*/
+fn keyframe_sequence_add_keyframe<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    keyframe: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    add_child_of_class(this, &keyframe, "Keyframe")
+}
+
+/**
+    Removes a keyframe from this `KeyframeSequence` by unparenting it from the sequence.
+
+    ### See Also
+    * [`RemoveKeyframe`](https://create.roblox.com/docs/reference/engine/classes/KeyframeSequence#RemoveKeyframe)
+    on the Roblox Developer Hub
+*/
+fn keyframe_sequence_remove_keyframe<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    keyframe: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    remove_child(this, &keyframe);
+    Ok(())
+}
@@ -23,10 +23,15 @@ use crate::{
 
 pub(crate) mod base;
 pub(crate) mod data_model;
+pub(crate) mod keyframe;
+pub(crate) mod keyframe_sequence;
+pub(crate) mod pose;
 pub(crate) mod terrain;
 pub(crate) mod workspace;
 
+pub mod diff;
 pub mod registry;
+pub mod upgrade;
 
 const PROPERTY_NAME_ATTRIBUTES: &str = "Attributes";
 const PROPERTY_NAME_TAGS: &str = "Tags";
@@ -771,6 +776,9 @@ impl LuaUserData for Instance {
     fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
         base::add_methods(methods);
         data_model::add_methods(methods);
+        keyframe::add_methods(methods);
+        keyframe_sequence::add_methods(methods);
+        pose::add_methods(methods);
         terrain::add_methods(methods);
     }
 }
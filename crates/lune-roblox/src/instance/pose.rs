@@ -0,0 +1,57 @@
+use mlua::prelude::*;
+
+use crate::shared::classes::{
+    add_child_of_class, add_class_restricted_method, get_children_of_class, remove_child,
+};
+
+use super::Instance;
+
+pub const CLASS_NAME: &str = "Pose";
+
+pub fn add_methods<'lua, M: LuaUserDataMethods<'lua, Instance>>(m: &mut M) {
+    add_class_restricted_method(m, CLASS_NAME, "GetSubPoses", pose_get_sub_poses);
+    add_class_restricted_method(m, CLASS_NAME, "AddSubPose", pose_add_sub_pose);
+    add_class_restricted_method(m, CLASS_NAME, "RemoveSubPose", pose_remove_sub_pose);
+}
+
+/**
+    Gets all of the sub-poses that have been added to this `Pose`.
+
+    ### See Also
+    * [`GetSubPoses`](https://create.roblox.com/docs/reference/engine/classes/Pose#GetSubPoses)
+    on the Roblox Developer Hub
+*/
+fn pose_get_sub_poses(_: &Lua, this: &Instance, (): ()) -> LuaResult<Vec<Instance>> {
+    Ok(get_children_of_class(this, "Pose"))
+}
+
+/**
+    Adds a sub-pose to this `Pose` by parenting it to the pose.
+
+    ### See Also
+    * [`AddSubPose`](https://create.roblox.com/docs/reference/engine/classes/Pose#AddSubPose)
+    on the Roblox Developer Hub
+*/
+fn pose_add_sub_pose<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    pose: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    add_child_of_class(this, &pose, "Pose")
+}
+
+/**
+    Removes a sub-pose from this `Pose` by unparenting it from the pose.
+
+    ### See Also
+    * [`RemoveSubPose`](https://create.roblox.com/docs/reference/engine/classes/Pose#RemoveSubPose)
+    on the Roblox Developer Hub
+*/
+fn pose_remove_sub_pose<'lua>(
+    _: &'lua Lua,
+    this: &Instance,
+    pose: LuaUserDataRef<'lua, Instance>,
+) -> LuaResult<()> {
+    remove_child(this, &pose);
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use rbx_reflection::{ClassTag, PropertyDescriptor, PropertyKind, PropertySerialization};
+
+use super::{Instance, INTERNAL_DOM};
+
+/**
+    A change made, or a problem found, while upgrading an instance.
+*/
+#[derive(Debug, Clone)]
+pub enum InstanceUpgrade {
+    /**
+        A property was renamed or migrated to its canonical property.
+
+        If the canonical property was already set, it is kept as is,
+        and the old property is removed without being migrated.
+    */
+    Migrated {
+        instance: Instance,
+        from: String,
+        to: String,
+    },
+    /**
+        A property could not be migrated, and was left as it was.
+    */
+    Failed {
+        instance: Instance,
+        property: String,
+        message: String,
+    },
+    /**
+        The instance is of a deprecated class.
+
+        The reflection database does not know of any replacements
+        for deprecated classes, so these instances are only reported.
+    */
+    Deprecated { instance: Instance },
+}
+
+/**
+    Upgrades the given instance and all of its descendants, using the migrations
+    in the reflection database to move data from renamed and legacy properties
+    to the properties that they have been replaced by.
+
+    Properties that are not in the reflection database are left as they are.
+*/
+#[must_use]
+pub fn upgrade_instance(instance: &Instance) -> Vec<InstanceUpgrade> {
+    let mut upgrades = Vec::new();
+    upgrade_single(instance, &mut upgrades);
+    for descendant in instance.get_descendants() {
+        upgrade_single(&descendant, &mut upgrades);
+    }
+    upgrades
+}
+
+fn upgrade_single(instance: &Instance, upgrades: &mut Vec<InstanceUpgrade>) {
+    let db = rbx_reflection_database::get();
+
+    let mut dom = INTERNAL_DOM.lock().expect("Failed to lock document");
+    let Some(inst) = dom.get_by_ref_mut(instance.dom_ref) else {
+        return;
+    };
+
+    if db
+        .classes
+        .get(inst.class.as_str())
+        .is_some_and(|class| class.tags.contains(&ClassTag::Deprecated))
+    {
+        upgrades.push(InstanceUpgrade::Deprecated {
+            instance: instance.clone(),
+        });
+    }
+
+    // NOTE: Property names are sorted to keep the order of upgrades stable
+    let mut prop_names = inst.properties.keys().cloned().collect::<Vec<_>>();
+    prop_names.sort();
+
+    for prop_name in prop_names {
+        let Some(descriptor) = find_property_descriptor(&inst.class, &prop_name) else {
+            continue;
+        };
+        let (to, migration) = match &descriptor.kind {
+            PropertyKind::Alias { alias_for } => (alias_for.to_string(), None),
+            PropertyKind::Canonical {
+                serialization: PropertySerialization::Migrate(migration),
+            } => (migration.new_property_name.clone(), Some(migration)),
+            _ => continue,
+        };
+
+        if !inst.properties.contains_key(&to) {
+            let value = &inst.properties[&prop_name];
+            let migrated = match migration {
+                Some(migration) => match migration.perform(value) {
+                    Ok(migrated) => migrated,
+                    Err(e) => {
+                        upgrades.push(InstanceUpgrade::Failed {
+                            instance: instance.clone(),
+                            property: prop_name,
+                            message: e.to_string(),
+                        });
+                        continue;
+                    }
+                },
+                None => value.clone(),
+            };
+            inst.properties.insert(to.clone(), migrated);
+        }
+
+        inst.properties.remove(&prop_name);
+        upgrades.push(InstanceUpgrade::Migrated {
+            instance: instance.clone(),
+            from: prop_name,
+            to,
+        });
+    }
+}
+
+fn find_property_descriptor(
+    class_name: &str,
+    prop_name: &str,
+) -> Option<&'static PropertyDescriptor<'static>> {
+    let db = rbx_reflection_database::get();
+
+    let mut class_name = class_name;
+    while let Some(class) = db.classes.get(class_name) {
+        if let Some(descriptor) = class.properties.get(prop_name) {
+            return Some(descriptor);
+        }
+        class_name = class.superclass.as_deref()?;
+    }
+    None
+}
@@ -127,3 +127,45 @@ pub(crate) fn get_or_create_property_ref_instance(
         Ok(inst)
     }
 }
+
+/**
+    Gets all children of the given instance that are of the given class, checked using `IsA`.
+*/
+pub(crate) fn get_children_of_class(this: &Instance, class_name: &'static str) -> Vec<Instance> {
+    this.get_children()
+        .into_iter()
+        .filter(|child| child.is_a(class_name))
+        .collect()
+}
+
+/**
+    Parents the given child to the instance, erroring if the
+    child is not of the given class, checked using `IsA`.
+*/
+pub(crate) fn add_child_of_class(
+    this: &Instance,
+    child: &Instance,
+    class_name: &'static str,
+) -> LuaResult<()> {
+    if child.is_a(class_name) {
+        child.set_parent(Some(this.clone()));
+        Ok(())
+    } else {
+        Err(LuaError::RuntimeError(format!(
+            "Expected {class_name}, got {}",
+            child.get_class_name()
+        )))
+    }
+}
+
+/**
+    Unparents the given child from the instance, if it is a child of it.
+*/
+pub(crate) fn remove_child(this: &Instance, child: &Instance) {
+    if child
+        .get_parent()
+        .is_some_and(|parent| parent.dom_ref == this.dom_ref)
+    {
+        child.set_parent(None);
+    }
+}
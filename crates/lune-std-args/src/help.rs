@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use crate::spec::{CommandSpec, PositionalSpec, ValueSpec};
+
+/**
+    Formats the version line for a command, such as `my-tool 1.0.0`.
+*/
+pub fn format_version(spec: &CommandSpec) -> String {
+    match &spec.version {
+        Some(version) => format!("{} {version}", spec.name),
+        None => spec.name.clone(),
+    }
+}
+
+/**
+    Formats the usage line for a command, given the full path
+    to the command, such as `my-tool build` for a subcommand.
+*/
+pub fn format_usage(spec: &CommandSpec, path: &str) -> String {
+    let mut usage = format!("Usage: {path} [options]");
+    if !spec.subcommands.is_empty() {
+        usage.push_str(" [command]");
+    }
+    for positional in &spec.positionals {
+        usage.push(' ');
+        usage.push_str(&positional_placeholder(positional));
+    }
+    usage
+}
+
+/**
+    Formats the full help text for a command, with all of its arguments, options and subcommands.
+*/
+pub fn format_help(spec: &CommandSpec, path: &str) -> String {
+    let arguments = spec
+        .positionals
+        .iter()
+        .map(|positional| {
+            let description = describe(positional.description.as_deref(), &positional.value, false);
+            (positional_placeholder(positional), description)
+        })
+        .collect::<Vec<_>>();
+
+    let mut options = Vec::new();
+    for flag in &spec.flags {
+        let description = flag.description.clone().unwrap_or_default();
+        options.push((option_names(flag.short, &flag.name), description));
+    }
+    for option in &spec.options {
+        let mut names = option_names(option.short, &option.name);
+        names.push(' ');
+        names.push_str(&option.value.placeholder());
+        if option.value.multiple {
+            names.push_str("...");
+        }
+        options.push((
+            names,
+            describe(option.description.as_deref(), &option.value, true),
+        ));
+    }
+    options.push((option_names(Some('h'), "help"), String::from("Print help")));
+    if spec.version.is_some() {
+        options.push((
+            option_names(Some('V'), "version"),
+            String::from("Print version"),
+        ));
+    }
+
+    let commands = spec
+        .subcommands
+        .iter()
+        .map(|command| {
+            let description = command.description.clone().unwrap_or_default();
+            (command.name.clone(), description)
+        })
+        .collect::<Vec<_>>();
+
+    let width = arguments
+        .iter()
+        .chain(&options)
+        .chain(&commands)
+        .map(|(left, _)| left.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    let mut help = String::new();
+    if path == spec.name {
+        help.push_str(&format_version(spec));
+    } else {
+        help.push_str(path);
+    }
+    help.push('\n');
+    if let Some(description) = &spec.description {
+        help.push_str(description);
+        help.push('\n');
+    }
+    help.push('\n');
+    help.push_str(&format_usage(spec, path));
+    help.push('\n');
+
+    for (title, entries) in [
+        ("Arguments", &arguments),
+        ("Options", &options),
+        ("Commands", &commands),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        write!(help, "\n{title}:\n").unwrap();
+        for (left, right) in entries {
+            let line = format!("  {left:<width$}  {right}");
+            help.push_str(line.trim_end());
+            help.push('\n');
+        }
+    }
+
+    help.truncate(help.trim_end().len());
+    help
+}
+
+fn positional_placeholder(positional: &PositionalSpec) -> String {
+    let dots = if positional.value.multiple { "..." } else { "" };
+    if positional.value.required {
+        format!("<{}>{dots}", positional.name)
+    } else {
+        format!("[{}]{dots}", positional.name)
+    }
+}
+
+// Options without a short name are indented so that all long names line up
+fn option_names(short: Option<char>, long: &str) -> String {
+    match short {
+        Some(short) => format!("-{short}, --{long}"),
+        None => format!("    --{long}"),
+    }
+}
+
+// Required positional arguments are already shown using <angle brackets>, so they
+// are only marked as required for options, which are optional unless noted
+fn describe(description: Option<&str>, value: &ValueSpec, note_required: bool) -> String {
+    let mut described = description.unwrap_or_default().to_string();
+    let mut push_note = |note: &str| {
+        if !described.is_empty() {
+            described.push(' ');
+        }
+        described.push_str(note);
+    };
+    if let Some(default) = &value.default {
+        push_note(&format!("(default: {default})"));
+    } else if value.required && note_required {
+        push_note("(required)");
+    }
+    described
+}
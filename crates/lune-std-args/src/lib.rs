@@ -0,0 +1,33 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+mod help;
+mod parse;
+mod parser;
+mod spec;
+
+pub use self::parser::ArgsParser;
+use self::spec::CommandSpec;
+
+/**
+    Creates the `args` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("parser", create_parser)?
+        .build_readonly()
+}
+
+fn create_parser(_: &Lua, spec: LuaTable) -> LuaResult<ArgsParser> {
+    let name = spec
+        .get::<_, Option<String>>("name")?
+        .ok_or_else(|| LuaError::runtime("Missing name for args parser"))?;
+    Ok(ArgsParser::new(CommandSpec::from_table(name, &spec)?))
+}
@@ -0,0 +1,276 @@
+use crate::help::{format_help, format_usage, format_version};
+use crate::spec::{ArgValue, CommandSpec, ValueSpec};
+
+/**
+    The value of an option or positional argument after parsing.
+*/
+#[derive(Debug, Clone)]
+pub enum ParsedValue {
+    None,
+    Single(ArgValue),
+    Multiple(Vec<ArgValue>),
+}
+
+/**
+    The arguments that were given to a command, or subcommand.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ParsedArgs {
+    pub flags: Vec<(String, bool)>,
+    pub options: Vec<(String, ParsedValue)>,
+    pub positionals: Vec<(String, ParsedValue)>,
+    pub rest: Vec<String>,
+    pub subcommand: Option<(String, Box<ParsedArgs>)>,
+}
+
+/**
+    The result of parsing arguments.
+*/
+#[derive(Debug, Clone)]
+pub enum ParseOutcome {
+    /// The arguments were parsed successfully.
+    Parsed(ParsedArgs),
+    /// Help or version text was requested, and should be printed instead of running the command.
+    Message(String),
+}
+
+/**
+    An error for arguments that do not match the command.
+*/
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    /// The usage line for the command, or subcommand, that the arguments were given to.
+    pub usage: String,
+}
+
+/**
+    Parses the given arguments, not including the program name, using the given command.
+*/
+pub fn parse_args(spec: &CommandSpec, args: &[String]) -> Result<ParseOutcome, ParseError> {
+    parse_command(spec, &spec.name, args)
+}
+
+fn parse_command(
+    spec: &CommandSpec,
+    path: &str,
+    args: &[String],
+) -> Result<ParseOutcome, ParseError> {
+    let fail = |message: String| ParseError {
+        message,
+        usage: format_usage(spec, path),
+    };
+
+    let mut flags = vec![false; spec.flags.len()];
+    let mut options: Vec<Vec<ArgValue>> = vec![Vec::new(); spec.options.len()];
+    let mut positional_args: Vec<&String> = Vec::new();
+    let mut trailing_args: Vec<&String> = Vec::new();
+
+    let mut index = 0;
+    while index < args.len() {
+        let arg = &args[index];
+        index += 1;
+
+        if arg == "--" {
+            trailing_args.extend(&args[index..]);
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            if name == "help" {
+                return Ok(ParseOutcome::Message(format_help(spec, path)));
+            }
+            if name == "version" && spec.version.is_some() {
+                return Ok(ParseOutcome::Message(format_version(spec)));
+            }
+            if let Some(position) = spec.flags.iter().position(|f| f.name == name) {
+                if inline_value.is_some() {
+                    return Err(fail(format!("Flag '--{name}' does not take a value")));
+                }
+                flags[position] = true;
+            } else if let Some(position) = spec.options.iter().position(|o| o.name == name) {
+                let value = match inline_value {
+                    Some(value) => value,
+                    None => next_value(args, &mut index)
+                        .ok_or_else(|| fail(format!("Option '--{name}' expects a value")))?,
+                };
+                push_option_value(spec, &mut options, position, value).map_err(fail)?;
+            } else {
+                return Err(fail(format!("Unknown option '--{name}'")));
+            }
+            continue;
+        }
+
+        if arg.len() > 1 && arg.starts_with('-') && !is_negative_number(spec, arg) {
+            let shorts = &arg[1..];
+            for (offset, short) in shorts.char_indices() {
+                if short == 'h' {
+                    return Ok(ParseOutcome::Message(format_help(spec, path)));
+                }
+                if short == 'V' && spec.version.is_some() {
+                    return Ok(ParseOutcome::Message(format_version(spec)));
+                }
+                if let Some(position) = spec.flags.iter().position(|f| f.short == Some(short)) {
+                    flags[position] = true;
+                } else if let Some(position) =
+                    spec.options.iter().position(|o| o.short == Some(short))
+                {
+                    // Anything after the option in the same argument is its value,
+                    // so both `-ovalue` and `-o=value` work, as well as `-o value`
+                    let attached = &shorts[offset + short.len_utf8()..];
+                    let value = if attached.is_empty() {
+                        next_value(args, &mut index)
+                            .ok_or_else(|| fail(format!("Option '-{short}' expects a value")))?
+                    } else {
+                        attached.strip_prefix('=').unwrap_or(attached)
+                    };
+                    push_option_value(spec, &mut options, position, value).map_err(fail)?;
+                    break;
+                } else {
+                    return Err(fail(format!("Unknown option '-{short}'")));
+                }
+            }
+            continue;
+        }
+
+        if positional_args.is_empty() && !spec.subcommands.is_empty() {
+            if let Some(subcommand) = spec.find_subcommand(arg) {
+                let sub_path = format!("{path} {}", subcommand.name);
+                return match parse_command(subcommand, &sub_path, &args[index..])? {
+                    ParseOutcome::Parsed(sub_args) => {
+                        let mut parsed = finish(spec, flags, options, &[], &[]).map_err(fail)?;
+                        parsed.subcommand = Some((subcommand.name.clone(), Box::new(sub_args)));
+                        Ok(ParseOutcome::Parsed(parsed))
+                    }
+                    message @ ParseOutcome::Message(_) => Ok(message),
+                };
+            } else if spec.positionals.is_empty() {
+                return Err(fail(format!("Unknown command '{arg}'")));
+            }
+        }
+
+        positional_args.push(arg);
+    }
+
+    let parsed = finish(spec, flags, options, &positional_args, &trailing_args).map_err(fail)?;
+    Ok(ParseOutcome::Parsed(parsed))
+}
+
+fn finish(
+    spec: &CommandSpec,
+    flags: Vec<bool>,
+    options: Vec<Vec<ArgValue>>,
+    positional_args: &[&String],
+    trailing_args: &[&String],
+) -> Result<ParsedArgs, String> {
+    let flags = spec
+        .flags
+        .iter()
+        .zip(flags)
+        .map(|(flag, set)| (flag.name.clone(), set))
+        .collect();
+
+    let mut parsed_options = Vec::with_capacity(spec.options.len());
+    for (option, values) in spec.options.iter().zip(options) {
+        if values.is_empty() && option.value.required {
+            return Err(format!("Missing required option '--{}'", option.name));
+        }
+        parsed_options.push((option.name.clone(), into_parsed(&option.value, values)));
+    }
+
+    // Arguments after `--` can fill positional arguments too, and any
+    // that are left over are given as the rest instead of failing
+    let all_args: Vec<&String> = positional_args
+        .iter()
+        .chain(trailing_args)
+        .copied()
+        .collect();
+    let mut consumed = 0;
+    let mut parsed_positionals = Vec::with_capacity(spec.positionals.len());
+    for positional in &spec.positionals {
+        let what = format!("argument <{}>", positional.name);
+        let mut values = Vec::new();
+        while let Some(arg) = all_args.get(consumed) {
+            values.push(positional.value.parse(arg, &what)?);
+            consumed += 1;
+            if !positional.value.multiple {
+                break;
+            }
+        }
+        if values.is_empty() && positional.value.required {
+            return Err(format!("Missing required argument <{}>", positional.name));
+        }
+        parsed_positionals.push((
+            positional.name.clone(),
+            into_parsed(&positional.value, values),
+        ));
+    }
+
+    if let Some(unexpected) = positional_args.get(consumed) {
+        return Err(format!("Unexpected argument '{unexpected}'"));
+    }
+
+    Ok(ParsedArgs {
+        flags,
+        options: parsed_options,
+        positionals: parsed_positionals,
+        rest: all_args[consumed..]
+            .iter()
+            .map(|arg| (*arg).clone())
+            .collect(),
+        subcommand: None,
+    })
+}
+
+fn into_parsed(spec: &ValueSpec, mut values: Vec<ArgValue>) -> ParsedValue {
+    if spec.multiple {
+        ParsedValue::Multiple(values)
+    } else if let Some(value) = values.pop() {
+        ParsedValue::Single(value)
+    } else if let Some(default) = &spec.default {
+        ParsedValue::Single(default.clone())
+    } else {
+        ParsedValue::None
+    }
+}
+
+fn next_value<'a>(args: &'a [String], index: &mut usize) -> Option<&'a str> {
+    let value = args.get(*index)?;
+    *index += 1;
+    Some(value.as_str())
+}
+
+fn push_option_value(
+    spec: &CommandSpec,
+    options: &mut [Vec<ArgValue>],
+    position: usize,
+    value: &str,
+) -> Result<(), String> {
+    let option = &spec.options[position];
+    let values = &mut options[position];
+    if !values.is_empty() && !option.value.multiple {
+        return Err(format!("Option '--{}' can only be given once", option.name));
+    }
+    values.push(
+        option
+            .value
+            .parse(value, &format!("option '--{}'", option.name))?,
+    );
+    Ok(())
+}
+
+// Negative numbers such as `-5` are positional arguments, unless
+// the command has short names that are digits, which would be ambiguous
+fn is_negative_number(spec: &CommandSpec, arg: &str) -> bool {
+    let has_digit_shorts = spec
+        .flags
+        .iter()
+        .filter_map(|f| f.short)
+        .chain(spec.options.iter().filter_map(|o| o.short))
+        .any(|c| c.is_ascii_digit());
+    !has_digit_shorts && arg.parse::<f64>().is_ok()
+}
@@ -0,0 +1,142 @@
+use std::process::ExitCode;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSchedulerExt;
+
+use lune_utils::{
+    output::{write_output, OutputStream},
+    TableBuilder,
+};
+
+use crate::help::format_help;
+use crate::parse::{parse_args, ParseError, ParseOutcome, ParsedArgs, ParsedValue};
+use crate::spec::CommandSpec;
+
+// Exit code for invalid arguments, same as most other command line tools
+const USAGE_EXIT_CODE: u8 = 2;
+
+/**
+    A parser for command line arguments, created from a declarative spec.
+*/
+pub struct ArgsParser {
+    spec: CommandSpec,
+}
+
+impl ArgsParser {
+    #[must_use]
+    pub fn new(spec: CommandSpec) -> Self {
+        Self { spec }
+    }
+
+    fn parse(&self, lua: &Lua, args: Option<Vec<String>>) -> Result<ParseOutcome, ParseError> {
+        let args = args.unwrap_or_else(|| {
+            lua.app_data_ref::<Vec<String>>()
+                .map(|args| args.clone())
+                .unwrap_or_default()
+        });
+        parse_args(&self.spec, &args)
+    }
+}
+
+impl LuaUserData for ArgsParser {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ArgsParser");
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("help", |_, this, ()| {
+            Ok(format_help(&this.spec, &this.spec.name))
+        });
+        methods.add_method(
+            "tryParse",
+            |lua, this, args: Option<Vec<String>>| match this.parse(lua, args) {
+                Ok(ParseOutcome::Parsed(parsed)) => create_result(lua, &parsed, None),
+                Ok(ParseOutcome::Message(message)) => {
+                    create_result(lua, &ParsedArgs::default(), Some(message))
+                }
+                Err(e) => Err(LuaError::RuntimeError(e.message)),
+            },
+        );
+        methods.add_async_method("parse", |lua, this, args: Option<Vec<String>>| {
+            let outcome = this.parse(lua, args);
+            async move {
+                let code = match outcome {
+                    Ok(ParseOutcome::Parsed(parsed)) => return create_result(lua, &parsed, None),
+                    Ok(ParseOutcome::Message(message)) => {
+                        let message = format!("{message}\n");
+                        write_output(lua, OutputStream::Stdout, message.as_bytes())?;
+                        ExitCode::SUCCESS
+                    }
+                    Err(e) => {
+                        let message = format!(
+                            "error: {}\n\n{}\n\nFor more information, try '--help'\n",
+                            e.message, e.usage
+                        );
+                        write_output(lua, OutputStream::Stderr, message.as_bytes())?;
+                        ExitCode::from(USAGE_EXIT_CODE)
+                    }
+                };
+                // The scheduler stops running as soon as an exit code
+                // is set, so the calling thread never resumes after this
+                lua.set_exit_code(code);
+                std::future::pending().await
+            }
+        });
+    }
+}
+
+fn create_result<'lua>(
+    lua: &'lua Lua,
+    parsed: &ParsedArgs,
+    help: Option<String>,
+) -> LuaResult<LuaTable<'lua>> {
+    let flags = TableBuilder::new(lua)?
+        .with_values(
+            parsed
+                .flags
+                .iter()
+                .map(|(name, set)| (name.as_str(), *set))
+                .collect(),
+        )?
+        .build_readonly()?;
+    let options = create_values(lua, &parsed.options)?;
+    let positionals = create_values(lua, &parsed.positionals)?;
+    let rest = TableBuilder::new(lua)?
+        .with_sequential_values(parsed.rest.iter().map(String::as_str).collect())?
+        .build_readonly()?;
+
+    let mut result = TableBuilder::new(lua)?
+        .with_value("flags", flags)?
+        .with_value("options", options)?
+        .with_value("positionals", positionals)?
+        .with_value("rest", rest)?;
+    if let Some((name, subcommand)) = &parsed.subcommand {
+        result = result
+            .with_value("command", name.as_str())?
+            .with_value("subcommand", create_result(lua, subcommand, None)?)?;
+    }
+    if let Some(help) = help {
+        result = result.with_value("help", help)?;
+    }
+    result.build_readonly()
+}
+
+fn create_values<'lua>(
+    lua: &'lua Lua,
+    values: &[(String, ParsedValue)],
+) -> LuaResult<LuaTable<'lua>> {
+    let mut builder = TableBuilder::new(lua)?;
+    for (name, value) in values {
+        builder = match value {
+            ParsedValue::None => builder,
+            ParsedValue::Single(value) => builder.with_value(name.as_str(), value)?,
+            ParsedValue::Multiple(values) => {
+                let list = TableBuilder::new(lua)?
+                    .with_sequential_values(values.iter().collect())?
+                    .build_readonly()?;
+                builder.with_value(name.as_str(), list)?
+            }
+        };
+    }
+    builder.build_readonly()
+}
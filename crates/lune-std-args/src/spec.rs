@@ -0,0 +1,405 @@
+use std::fmt;
+
+use mlua::prelude::*;
+
+use lune_utils::units::{parse_duration, parse_size};
+
+/**
+    The kind of value that an option or positional argument accepts.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Number,
+    Integer,
+    Duration,
+    Size,
+}
+
+impl ValueKind {
+    fn from_name(name: &str) -> LuaResult<Self> {
+        match name {
+            "string" => Ok(Self::String),
+            "number" => Ok(Self::Number),
+            "integer" => Ok(Self::Integer),
+            "duration" => Ok(Self::Duration),
+            "size" => Ok(Self::Size),
+            _ => Err(LuaError::RuntimeError(format!(
+                "Invalid value type '{name}', expected one of \
+                'string', 'number', 'integer', 'duration', 'size'"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Integer => "integer",
+            Self::Duration => "duration",
+            Self::Size => "size",
+        })
+    }
+}
+
+/**
+    A value that was parsed from an argument, or given as a default.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    String(String),
+    Number(f64),
+    Integer(i64),
+}
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) => f.write_str(s),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Integer(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for &ArgValue {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            ArgValue::String(s) => s.as_str().into_lua(lua),
+            ArgValue::Number(n) => Ok(LuaValue::Number(*n)),
+            ArgValue::Integer(n) => Ok(LuaValue::Number(*n as f64)),
+        }
+    }
+}
+
+/**
+    The accepted values for an option or positional argument.
+*/
+#[derive(Debug, Clone)]
+pub struct ValueSpec {
+    pub kind: ValueKind,
+    pub choices: Vec<String>,
+    pub default: Option<ArgValue>,
+    pub required: bool,
+    pub multiple: bool,
+}
+
+impl ValueSpec {
+    fn from_table(table: &LuaTable, what: &str, required_by_default: bool) -> LuaResult<Self> {
+        let kind = match table.get::<_, Option<String>>("type")? {
+            Some(name) => ValueKind::from_name(&name)?,
+            None => ValueKind::String,
+        };
+
+        let choices = table
+            .get::<_, Option<Vec<String>>>("choices")?
+            .unwrap_or_default();
+        if !choices.is_empty() && kind != ValueKind::String {
+            return Err(LuaError::RuntimeError(format!(
+                "Choices for {what} can only be given for values of type 'string'"
+            )));
+        }
+
+        let multiple = table.get::<_, Option<bool>>("multiple")?.unwrap_or(false);
+        let default = match table.get::<_, LuaValue>("default")? {
+            LuaValue::Nil => None,
+            _ if multiple => {
+                return Err(LuaError::RuntimeError(format!(
+                    "A default value can not be given for {what}, since it accepts multiple values"
+                )))
+            }
+            LuaValue::String(s) if kind == ValueKind::String => {
+                Some(ArgValue::String(s.to_str()?.to_string()))
+            }
+            LuaValue::Integer(n) if kind == ValueKind::Integer => Some(ArgValue::Integer(n as i64)),
+            LuaValue::Number(n) if kind == ValueKind::Integer && n.fract() == 0.0 => {
+                Some(ArgValue::Integer(n as i64))
+            }
+            LuaValue::Integer(n) if kind == ValueKind::Number => Some(ArgValue::Number(n as f64)),
+            LuaValue::Number(n) if kind == ValueKind::Number => Some(ArgValue::Number(n)),
+            // Durations and sizes may be given as strings, the same as on the command line
+            LuaValue::Integer(n) if kind == ValueKind::Duration => Some(ArgValue::Number(n as f64)),
+            LuaValue::Number(n) if kind == ValueKind::Duration => Some(ArgValue::Number(n)),
+            LuaValue::Integer(n) if kind == ValueKind::Size => Some(ArgValue::Integer(n as i64)),
+            LuaValue::Number(n) if kind == ValueKind::Size && n.fract() == 0.0 => {
+                Some(ArgValue::Integer(n as i64))
+            }
+            LuaValue::String(s) if matches!(kind, ValueKind::Duration | ValueKind::Size) => {
+                Some(parse_value(kind, s.to_str()?).map_err(|e| {
+                    LuaError::RuntimeError(format!("Invalid default value for {what} - {e}"))
+                })?)
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid default value for {what} - expected {kind}, got {}",
+                    value.type_name()
+                )))
+            }
+        };
+        if let Some(ArgValue::String(default)) = &default {
+            if !choices.is_empty() && !choices.contains(default) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Default value '{default}' for {what} is not one of its choices"
+                )));
+            }
+        }
+
+        let required = table
+            .get::<_, Option<bool>>("required")?
+            .unwrap_or(required_by_default && default.is_none());
+
+        Ok(Self {
+            kind,
+            choices,
+            default,
+            required,
+            multiple,
+        })
+    }
+
+    /**
+        Parses the given argument into a value of this kind.
+    */
+    pub fn parse(&self, arg: &str, what: &str) -> Result<ArgValue, String> {
+        match self.kind {
+            ValueKind::String => {
+                if self.choices.is_empty() || self.choices.iter().any(|c| c == arg) {
+                    Ok(ArgValue::String(arg.to_string()))
+                } else {
+                    Err(format!(
+                        "Invalid value '{arg}' for {what}, expected one of: {}",
+                        self.choices.join(", ")
+                    ))
+                }
+            }
+            ValueKind::Number => arg
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(ArgValue::Number)
+                .ok_or_else(|| format!("Invalid value '{arg}' for {what}, expected a number")),
+            ValueKind::Integer => arg
+                .trim()
+                .parse::<i64>()
+                .map(ArgValue::Integer)
+                .map_err(|_| format!("Invalid value '{arg}' for {what}, expected an integer")),
+            ValueKind::Duration | ValueKind::Size => parse_value(self.kind, arg)
+                .map_err(|e| format!("Invalid value '{arg}' for {what} - {e}")),
+        }
+    }
+
+    /**
+        Gets the placeholder to show for the value in help text, such as `<string>`.
+    */
+    pub fn placeholder(&self) -> String {
+        if self.choices.is_empty() {
+            format!("<{}>", self.kind)
+        } else {
+            format!("<{}>", self.choices.join("|"))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FlagSpec {
+    pub name: String,
+    pub short: Option<char>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OptionSpec {
+    pub name: String,
+    pub short: Option<char>,
+    pub description: Option<String>,
+    pub value: ValueSpec,
+}
+
+#[derive(Debug, Clone)]
+pub struct PositionalSpec {
+    pub name: String,
+    pub description: Option<String>,
+    pub value: ValueSpec,
+}
+
+/**
+    A command, or subcommand, with all of the arguments that it accepts.
+*/
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: String,
+    pub description: Option<String>,
+    pub version: Option<String>,
+    pub flags: Vec<FlagSpec>,
+    pub options: Vec<OptionSpec>,
+    pub positionals: Vec<PositionalSpec>,
+    pub subcommands: Vec<CommandSpec>,
+}
+
+impl CommandSpec {
+    /**
+        Creates a command from a table, as given to `args.parser`.
+    */
+    pub fn from_table(name: String, table: &LuaTable) -> LuaResult<Self> {
+        let mut this = Self {
+            name,
+            description: table.get("description")?,
+            version: table.get("version")?,
+            flags: Vec::new(),
+            options: Vec::new(),
+            positionals: Vec::new(),
+            subcommands: Vec::new(),
+        };
+
+        if let Some(flags) = table.get::<_, Option<LuaTable>>("flags")? {
+            for pair in flags.pairs::<String, LuaTable>() {
+                let (name, flag) = pair?;
+                validate_name(&name, "flag")?;
+                this.flags.push(FlagSpec {
+                    short: get_short(&flag, &name)?,
+                    description: flag.get("description")?,
+                    name,
+                });
+            }
+        }
+
+        if let Some(options) = table.get::<_, Option<LuaTable>>("options")? {
+            for pair in options.pairs::<String, LuaTable>() {
+                let (name, option) = pair?;
+                validate_name(&name, "option")?;
+                this.options.push(OptionSpec {
+                    short: get_short(&option, &name)?,
+                    description: option.get("description")?,
+                    value: ValueSpec::from_table(&option, &format!("option '--{name}'"), false)?,
+                    name,
+                });
+            }
+        }
+
+        if let Some(positionals) = table.get::<_, Option<LuaTable>>("positionals")? {
+            for positional in positionals.sequence_values::<LuaTable>() {
+                let positional = positional?;
+                let name: String = positional
+                    .get("name")
+                    .map_err(|_| LuaError::runtime("Positional arguments must have a name"))?;
+                let value =
+                    ValueSpec::from_table(&positional, &format!("argument <{name}>"), true)?;
+                if let Some(previous) = this.positionals.last() {
+                    if previous.value.multiple {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Argument <{}> accepts multiple values, so it must be the last argument",
+                            previous.name
+                        )));
+                    }
+                    if value.required && !previous.value.required {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Required argument <{name}> can not come after optional argument <{}>",
+                            previous.name
+                        )));
+                    }
+                }
+                this.positionals.push(PositionalSpec {
+                    description: positional.get("description")?,
+                    name,
+                    value,
+                });
+            }
+        }
+
+        if let Some(subcommands) = table.get::<_, Option<LuaTable>>("subcommands")? {
+            for pair in subcommands.pairs::<String, LuaTable>() {
+                let (name, subcommand) = pair?;
+                validate_name(&name, "subcommand")?;
+                this.subcommands
+                    .push(CommandSpec::from_table(name, &subcommand)?);
+            }
+        }
+
+        // NOTE: Tables are iterated in an unspecified order, so
+        // we sort everything to always give the same help text
+        this.flags.sort_by(|a, b| a.name.cmp(&b.name));
+        this.options.sort_by(|a, b| a.name.cmp(&b.name));
+        this.subcommands.sort_by(|a, b| a.name.cmp(&b.name));
+
+        this.validate_unique()?;
+
+        Ok(this)
+    }
+
+    pub fn find_subcommand(&self, name: &str) -> Option<&CommandSpec> {
+        self.subcommands.iter().find(|c| c.name == name)
+    }
+
+    fn validate_unique(&self) -> LuaResult<()> {
+        let mut longs = vec!["help".to_string()];
+        let mut shorts = vec!['h'];
+        if self.version.is_some() {
+            longs.push("version".to_string());
+            shorts.push('V');
+        }
+        let all = self
+            .flags
+            .iter()
+            .map(|f| (&f.name, f.short))
+            .chain(self.options.iter().map(|o| (&o.name, o.short)));
+        for (name, short) in all {
+            if longs.contains(name) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Flag or option '--{name}' is defined more than once"
+                )));
+            }
+            longs.push(name.clone());
+            if let Some(short) = short {
+                if shorts.contains(&short) {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Short name '-{short}' is used by more than one flag or option"
+                    )));
+                }
+                shorts.push(short);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str, what: &str) -> LuaResult<()> {
+    if name.is_empty()
+        || name.starts_with('-')
+        || name.contains(|c: char| c.is_whitespace() || c == '=')
+    {
+        Err(LuaError::RuntimeError(format!(
+            "Invalid {what} name '{name}' - names must not be empty, start with '-', or contain spaces or '='"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn get_short(table: &LuaTable, name: &str) -> LuaResult<Option<char>> {
+    let Some(short) = table.get::<_, Option<String>>("short")? else {
+        return Ok(None);
+    };
+    let mut chars = short.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphanumeric() => Ok(Some(c)),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid short name '{short}' for '--{name}' - expected a single letter or digit"
+        ))),
+    }
+}
+
+// Durations are given in seconds, and sizes in bytes
+fn parse_value(kind: ValueKind, arg: &str) -> Result<ArgValue, String> {
+    match kind {
+        ValueKind::Duration => parse_duration(arg).map(|d| ArgValue::Number(d.as_secs_f64())),
+        ValueKind::Size => parse_size(arg).and_then(|bytes| {
+            i64::try_from(bytes)
+                .map(ArgValue::Integer)
+                .map_err(|_| String::from("size is too large"))
+        }),
+        _ => unreachable!("only durations and sizes are parsed using units"),
+    }
+}
@@ -0,0 +1,84 @@
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::schema::{parse_str_like, Schema};
+
+/**
+    Overrides values in the config using environment variables that start with the given prefix.
+
+    The rest of each variable name is split into keys using the separator, and
+    keys are matched against existing keys and the schema case-insensitively,
+    ignoring underscores and dashes, so that `APP_SERVER__MAX_CONNECTIONS`
+    overrides `server.maxConnections` when using the prefix `APP_`.
+*/
+pub fn apply_env(
+    config: &mut JsonValue,
+    vars: impl Iterator<Item = (String, String)>,
+    prefix: &str,
+    separator: &str,
+    schema: Option<&Schema>,
+) -> Result<(), String> {
+    let mut vars = vars
+        .filter(|(name, _)| name.len() > prefix.len() && name.starts_with(prefix))
+        .collect::<Vec<_>>();
+    // NOTE: Sorted so that overlapping variables always override in the same order
+    vars.sort();
+
+    for (name, raw) in vars {
+        let keys = name[prefix.len()..].split(separator).collect::<Vec<_>>();
+        if keys.iter().any(|key| key.is_empty()) {
+            continue;
+        }
+        apply_var(config, &keys, schema, &name, &raw)?;
+    }
+
+    Ok(())
+}
+
+fn apply_var(
+    target: &mut JsonValue,
+    keys: &[&str],
+    schema: Option<&Schema>,
+    name: &str,
+    raw: &str,
+) -> Result<(), String> {
+    if target.is_null() {
+        *target = JsonValue::Object(JsonMap::new());
+    }
+    let JsonValue::Object(map) = target else {
+        return Err(format!(
+            "Environment variable {name} can not override a value that is not a table"
+        ));
+    };
+
+    let key = find_key(map, schema, keys[0]);
+    let field_schema = schema.and_then(|s| s.field(&key));
+
+    if keys.len() == 1 {
+        let value = match field_schema {
+            Some(schema) => schema.parse_str(raw),
+            None => parse_str_like(map.get(&key), raw),
+        }
+        .map_err(|e| format!("Invalid value for environment variable {name} - {e}"))?;
+        map.insert(key, value);
+        Ok(())
+    } else {
+        let inner = map.entry(key).or_insert(JsonValue::Null);
+        apply_var(inner, &keys[1..], field_schema, name, raw)
+    }
+}
+
+fn find_key(map: &JsonMap<String, JsonValue>, schema: Option<&Schema>, key: &str) -> String {
+    let normalized = normalize(key);
+    map.keys()
+        .map(String::as_str)
+        .chain(schema.into_iter().flat_map(Schema::field_names))
+        .find(|existing| normalize(existing) == normalized)
+        .map_or_else(|| key.to_ascii_lowercase(), str::to_string)
+}
+
+fn normalize(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
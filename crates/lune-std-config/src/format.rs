@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+
+/**
+    A format that config files can be written in.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /**
+        Gets the format for the given path from its extension, if it has a known extension.
+    */
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /**
+        Parses the given contents in this format.
+    */
+    pub fn parse(self, contents: &str) -> Result<JsonValue, String> {
+        match self {
+            Self::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+            Self::Toml => toml::from_str(contents).map_err(|e| e.to_string()),
+            Self::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+        }
+    }
+
+    /**
+        Parses the given contents in whichever format they are written in.
+
+        Formats are tried from strictest to least strict, since
+        most JSON is also valid YAML, but not the other way around.
+    */
+    pub fn parse_any(contents: &str) -> Result<JsonValue, String> {
+        Self::Json
+            .parse(contents)
+            .or_else(|_| Self::Toml.parse(contents))
+            .or_else(|_| Self::Yaml.parse(contents))
+            .map_err(|_| String::from("Contents are not valid JSON, TOML or YAML"))
+    }
+}
+
+impl<'lua> FromLua<'lua> for ConfigFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "json" => Ok(Self::Json),
+                "toml" => Ok(Self::Toml),
+                "yaml" => Ok(Self::Yaml),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ConfigFormat",
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are: json, toml, yaml"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ConfigFormat",
+                message: None,
+            })
+        }
+    }
+}
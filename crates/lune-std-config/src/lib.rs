@@ -0,0 +1,109 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use std::io::ErrorKind;
+
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+use tokio::fs;
+
+use lune_utils::TableBuilder;
+
+mod env;
+mod format;
+mod options;
+mod schema;
+
+use self::env::apply_env;
+use self::format::ConfigFormat;
+use self::options::LoadOptions;
+
+// NOTE: Same as the options used by `@lune/serde` for decoding
+const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(false)
+    .serialize_unit_to_null(false);
+
+/**
+    Creates the `config` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("load", config_load)?
+        .build_readonly()
+}
+
+async fn config_load(lua: &Lua, options: LoadOptions) -> LuaResult<LuaValue> {
+    let mut config = options.defaults;
+
+    for file in &options.files {
+        let path = file.path.display();
+        let contents = match fs::read_to_string(&file.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound && !file.required => continue,
+            Err(e) => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Failed to read config file '{path}' - {e}"
+                )))
+            }
+        };
+        let format = file.format.or_else(|| ConfigFormat::from_path(&file.path));
+        let value = match format {
+            Some(format) => format.parse(&contents),
+            None => ConfigFormat::parse_any(&contents),
+        }
+        .map_err(|e| {
+            LuaError::RuntimeError(format!("Failed to parse config file '{path}' - {e}"))
+        })?;
+        match value {
+            // NOTE: Empty YAML files contain nothing at all, which is fine
+            JsonValue::Null => {}
+            JsonValue::Object(_) => merge(&mut config, value),
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Config file '{path}' must contain a table"
+                )))
+            }
+        }
+    }
+
+    if let Some(prefix) = &options.env_prefix {
+        apply_env(
+            &mut config,
+            std::env::vars(),
+            prefix,
+            &options.env_separator,
+            options.schema.as_ref(),
+        )
+        .map_err(LuaError::RuntimeError)?;
+    }
+
+    if let Some(schema) = &options.schema {
+        schema
+            .validate("", Some(&mut config))
+            .map_err(LuaError::RuntimeError)?;
+    }
+
+    lua.to_value_with(&config, LUA_SERIALIZE_OPTIONS)
+}
+
+// Tables are merged recursively, everything else, including
+// arrays, is replaced by the value from the later source
+fn merge(base: &mut JsonValue, overlay: JsonValue) {
+    match (base, overlay) {
+        (JsonValue::Object(base), JsonValue::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use mlua::prelude::*;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::format::ConfigFormat;
+use crate::schema::Schema;
+
+const DEFAULT_ENV_SEPARATOR: &str = "__";
+
+/**
+    A config file to load, and how to load it.
+*/
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub format: Option<ConfigFormat>,
+    pub required: bool,
+}
+
+impl<'lua> FromLua<'lua> for ConfigFile {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => Ok(Self {
+                path: PathBuf::from(s.to_str()?),
+                format: None,
+                required: false,
+            }),
+            LuaValue::Table(t) => Ok(Self {
+                path: PathBuf::from(t.get::<_, String>("path")?),
+                format: t.get("format")?,
+                required: t.get::<_, Option<bool>>("required")?.unwrap_or(false),
+            }),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ConfigFile",
+                message: Some(format!(
+                    "Invalid config file - expected string or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Options for loading config using `config.load`.
+*/
+#[derive(Debug, Clone)]
+pub struct LoadOptions {
+    pub defaults: JsonValue,
+    pub files: Vec<ConfigFile>,
+    pub env_prefix: Option<String>,
+    pub env_separator: String,
+    pub schema: Option<Schema>,
+}
+
+impl<'lua> FromLua<'lua> for LoadOptions {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(t) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "LoadOptions",
+                message: Some(format!(
+                    "Invalid options - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let defaults = match t.get::<_, Option<LuaTable>>("defaults")? {
+            None => JsonValue::Object(JsonMap::new()),
+            Some(defaults) => match lua.from_value(LuaValue::Table(defaults))? {
+                // NOTE: Empty tables may be deserialized as empty arrays
+                JsonValue::Array(a) if a.is_empty() => JsonValue::Object(JsonMap::new()),
+                JsonValue::Object(map) => JsonValue::Object(map),
+                _ => {
+                    return Err(LuaError::runtime(
+                        "Invalid defaults - expected a table with string keys",
+                    ))
+                }
+            },
+        };
+
+        let env_separator = t
+            .get::<_, Option<String>>("envSeparator")?
+            .unwrap_or_else(|| DEFAULT_ENV_SEPARATOR.to_string());
+        if env_separator.is_empty() {
+            return Err(LuaError::runtime(
+                "Invalid envSeparator - separator must not be empty",
+            ));
+        }
+
+        Ok(Self {
+            defaults,
+            files: t
+                .get::<_, Option<Vec<ConfigFile>>>("files")?
+                .unwrap_or_default(),
+            env_prefix: t.get("envPrefix")?,
+            env_separator,
+            schema: t.get("schema")?,
+        })
+    }
+}
@@ -0,0 +1,248 @@
+use mlua::prelude::*;
+use serde_json::{Map as JsonMap, Number as JsonNumber, Value as JsonValue};
+
+use lune_utils::units::{parse_duration, parse_size};
+
+#[derive(Debug, Clone)]
+pub enum SchemaKind {
+    Any,
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Duration,
+    Size,
+    Array,
+    Table(Vec<(String, Schema)>),
+}
+
+/**
+    The expected type of a config value, and if it must be given.
+*/
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub kind: SchemaKind,
+    pub optional: bool,
+}
+
+impl Schema {
+    fn type_name(&self) -> &'static str {
+        match self.kind {
+            SchemaKind::Any => "any",
+            SchemaKind::String => "string",
+            SchemaKind::Number => "number",
+            SchemaKind::Integer => "integer",
+            SchemaKind::Boolean => "boolean",
+            SchemaKind::Duration => "duration",
+            SchemaKind::Size => "size",
+            SchemaKind::Array => "array",
+            SchemaKind::Table(_) => "table",
+        }
+    }
+
+    /**
+        Gets the schema for the field with the given name, if this is the schema for a table.
+    */
+    pub fn field(&self, name: &str) -> Option<&Schema> {
+        match &self.kind {
+            SchemaKind::Table(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, s)| s),
+            _ => None,
+        }
+    }
+
+    /**
+        Gets the names of all fields, if this is the schema for a table.
+    */
+    pub fn field_names(&self) -> impl Iterator<Item = &str> {
+        let fields = match &self.kind {
+            SchemaKind::Table(fields) => fields.as_slice(),
+            _ => &[],
+        };
+        fields.iter().map(|(name, _)| name.as_str())
+    }
+
+    /**
+        Parses a value given as a string, such as from an environment variable, into this type.
+    */
+    pub fn parse_str(&self, raw: &str) -> Result<JsonValue, String> {
+        parse_str_as(self.type_name(), raw)
+    }
+
+    /**
+        Validates the given value against this schema, with `path` being the full key of the value.
+
+        Missing tables that are optional are validated as if they were empty,
+        so that errors point at the values inside of them that are required.
+
+        Durations and sizes given as strings, such as `"1h30m"` or `"512MiB"`,
+        are replaced with their number of seconds or bytes while validating.
+    */
+    pub fn validate(&self, path: &str, value: Option<&mut JsonValue>) -> Result<(), String> {
+        let value = match value {
+            None | Some(JsonValue::Null) => {
+                return match &self.kind {
+                    SchemaKind::Table(_) if self.optional => {
+                        self.validate(path, Some(&mut JsonValue::Object(JsonMap::new())))
+                    }
+                    _ if self.optional => Ok(()),
+                    _ => Err(format!("Missing required config value '{path}'")),
+                };
+            }
+            Some(value) => value,
+        };
+        let valid = match (&self.kind, &mut *value) {
+            (SchemaKind::Any, _)
+            | (SchemaKind::String, JsonValue::String(_))
+            | (SchemaKind::Number, JsonValue::Number(_))
+            | (SchemaKind::Boolean, JsonValue::Bool(_))
+            | (SchemaKind::Array, JsonValue::Array(_)) => true,
+            (SchemaKind::Integer, JsonValue::Number(n)) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            (SchemaKind::Duration, JsonValue::Number(n)) => n.as_f64().is_some_and(|f| f >= 0.0),
+            (SchemaKind::Size, JsonValue::Number(n)) => n.is_u64(),
+            (SchemaKind::Duration, JsonValue::String(raw)) => {
+                let parsed = parse_duration_value(raw)
+                    .map_err(|e| format!("Invalid config value '{path}' - {e}"))?;
+                *value = parsed;
+                true
+            }
+            (SchemaKind::Size, JsonValue::String(raw)) => {
+                let parsed = parse_size_value(raw)
+                    .map_err(|e| format!("Invalid config value '{path}' - {e}"))?;
+                *value = parsed;
+                true
+            }
+            (SchemaKind::Table(fields), JsonValue::Object(map)) => {
+                for (name, schema) in fields {
+                    schema.validate(&join_path(path, name), map.get_mut(name))?;
+                }
+                true
+            }
+            _ => false,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!(
+                "Invalid config value '{path}' - expected {}, got {}",
+                self.type_name(),
+                json_type_name(value)
+            ))
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for Schema {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => {
+                let s = s.to_str()?;
+                let (name, optional) = match s.strip_suffix('?') {
+                    Some(name) => (name, true),
+                    None => (s, false),
+                };
+                let kind = match name {
+                    "any" => SchemaKind::Any,
+                    "string" => SchemaKind::String,
+                    "number" => SchemaKind::Number,
+                    "integer" => SchemaKind::Integer,
+                    "boolean" => SchemaKind::Boolean,
+                    "duration" => SchemaKind::Duration,
+                    "size" => SchemaKind::Size,
+                    "array" => SchemaKind::Array,
+                    "table" => SchemaKind::Table(Vec::new()),
+                    _ => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid schema type '{name}', valid types are: \
+                            any, string, number, integer, boolean, duration, size, array, table"
+                        )))
+                    }
+                };
+                Ok(Self { kind, optional })
+            }
+            LuaValue::Table(t) => {
+                let mut fields = Vec::new();
+                for pair in t.pairs::<String, Schema>() {
+                    fields.push(pair?);
+                }
+                // NOTE: Sorted so that errors for missing values are always given in the same order
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                Ok(Self {
+                    kind: SchemaKind::Table(fields),
+                    optional: true,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Schema",
+                message: Some(String::from("expected a type name or a table of types")),
+            }),
+        }
+    }
+}
+
+/**
+    Parses a value given as a string into the same type as an existing value,
+    or into a string if there is no existing value to get the type from.
+*/
+pub fn parse_str_like(existing: Option<&JsonValue>, raw: &str) -> Result<JsonValue, String> {
+    match existing {
+        None | Some(JsonValue::Null | JsonValue::String(_)) => {
+            Ok(JsonValue::String(raw.to_string()))
+        }
+        Some(existing) => parse_str_as(json_type_name(existing), raw),
+    }
+}
+
+fn parse_str_as(type_name: &str, raw: &str) -> Result<JsonValue, String> {
+    let parsed = match type_name {
+        "number" => raw
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(JsonNumber::from_f64)
+            .map(JsonValue::Number),
+        "integer" => raw.trim().parse::<i64>().ok().map(JsonValue::from),
+        "duration" => parse_duration_value(raw).ok(),
+        "size" => parse_size_value(raw).ok(),
+        "boolean" => match raw.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Some(JsonValue::Bool(true)),
+            "false" | "0" | "no" | "off" => Some(JsonValue::Bool(false)),
+            _ => None,
+        },
+        // Tables and arrays can not be written as plain strings, so they are given as JSON
+        "array" => serde_json::from_str(raw).ok().filter(JsonValue::is_array),
+        "table" => serde_json::from_str(raw).ok().filter(JsonValue::is_object),
+        _ => Some(JsonValue::String(raw.to_string())),
+    };
+    parsed.ok_or_else(|| format!("expected {type_name}, got '{raw}'"))
+}
+
+// Durations are given as a number of seconds, and sizes as a number of bytes
+fn parse_duration_value(raw: &str) -> Result<JsonValue, String> {
+    parse_duration(raw).map(|duration| JsonValue::from(duration.as_secs_f64()))
+}
+
+fn parse_size_value(raw: &str) -> Result<JsonValue, String> {
+    parse_size(raw).map(JsonValue::from)
+}
+
+pub fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn json_type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "nil",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "table",
+    }
+}
@@ -2,12 +2,13 @@ use std::cmp::Ordering;
 
 use mlua::prelude::*;
 
+use chrono::format::{parse, Parsed, StrftimeItems};
 use chrono::prelude::*;
-use chrono::DateTime as ChronoDateTime;
-use chrono_lc::LocaleDate;
+use chrono::{DateTime as ChronoDateTime, Days, Months, TimeDelta};
 
 use crate::result::{DateTimeError, DateTimeResult};
-use crate::values::DateTimeValues;
+use crate::time_zone::DateTimeZone;
+use crate::values::{DateTimeDuration, DateTimeValues};
 
 const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 const DEFAULT_LOCALE: &str = "en";
@@ -62,16 +63,16 @@ impl DateTime {
 
     /**
         Transforms individual date & time values into a new
-        `DateTime` struct, using the universal (UTC) time zone.
+        `DateTime` struct, using the given time zone.
 
         See [`chrono::NaiveDate::from_ymd_opt`] and [`chrono::NaiveTime::from_hms_milli_opt`]
         for additional details and cases where this constructor may return an error.
 
         # Errors
 
-        Returns an error if the date or time values are invalid.
+        Returns an error if the date or time values are invalid or ambiguous.
     */
-    pub fn from_universal_time(values: &DateTimeValues) -> DateTimeResult<Self> {
+    pub fn from_time(values: &DateTimeValues, time_zone: DateTimeZone) -> DateTimeResult<Self> {
         let date = NaiveDate::from_ymd_opt(values.year, values.month, values.day)
             .ok_or(DateTimeError::InvalidDate)?;
 
@@ -83,79 +84,120 @@ impl DateTime {
         )
         .ok_or(DateTimeError::InvalidTime)?;
 
-        let inner = Utc.from_utc_datetime(&NaiveDateTime::new(date, time));
+        let inner = time_zone.from_naive(&NaiveDateTime::new(date, time))?;
 
         Ok(Self { inner })
     }
 
+    /**
+        Transforms individual date & time values into a new
+        `DateTime` struct, using the universal (UTC) time zone.
+
+        See [`DateTime::from_time`] for additional details.
+
+        # Errors
+
+        Returns an error if the date or time values are invalid.
+    */
+    pub fn from_universal_time(values: &DateTimeValues) -> DateTimeResult<Self> {
+        Self::from_time(values, DateTimeZone::Universal)
+    }
+
     /**
         Transforms individual date & time values into a new
         `DateTime` struct, using the current local time zone.
 
-        See [`chrono::NaiveDate::from_ymd_opt`] and [`chrono::NaiveTime::from_hms_milli_opt`]
-        for additional details and cases where this constructor may return an error.
+        See [`DateTime::from_time`] for additional details.
 
         # Errors
 
         Returns an error if the date or time values are invalid or ambiguous.
     */
     pub fn from_local_time(values: &DateTimeValues) -> DateTimeResult<Self> {
-        let date = NaiveDate::from_ymd_opt(values.year, values.month, values.day)
-            .ok_or(DateTimeError::InvalidDate)?;
+        Self::from_time(values, DateTimeZone::Local)
+    }
 
-        let time = NaiveTime::from_hms_milli_opt(
-            values.hour,
-            values.minute,
-            values.second,
-            values.millisecond,
-        )
-        .ok_or(DateTimeError::InvalidTime)?;
+    /**
+        Formats the `DateTime` using the given time zone,
+        the given format string, and the given locale.
 
-        let inner = Local
-            .from_local_datetime(&NaiveDateTime::new(date, time))
-            .single()
-            .ok_or(DateTimeError::Ambiguous)?
-            .with_timezone(&Utc);
+        `format` and `locale` default to `"%Y-%m-%d %H:%M:%S"` and `"en"` respectively.
 
-        Ok(Self { inner })
+        See [`chrono_lc::DateTime::formatl`] for additional details.
+    */
+    #[must_use]
+    pub fn format_string(
+        &self,
+        time_zone: DateTimeZone,
+        format: Option<&str>,
+        locale: Option<&str>,
+    ) -> String {
+        time_zone.format(
+            &self.inner,
+            format.unwrap_or(DEFAULT_FORMAT),
+            locale.unwrap_or(DEFAULT_LOCALE),
+        )
     }
 
     /**
-        Formats the `DateTime` using the universal (UTC) time
+        Formats the `DateTime` using the current local time
         zone, the given format string, and the given locale.
 
-        `format` and `locale` default to `"%Y-%m-%d %H:%M:%S"` and `"en"` respectively.
-
-        See [`chrono_lc::DateTime::formatl`] for additional details.
+        See [`DateTime::format_string`] for additional details.
     */
     #[must_use]
     pub fn format_string_local(&self, format: Option<&str>, locale: Option<&str>) -> String {
-        self.inner
-            .with_timezone(&Local)
-            .formatl(
-                format.unwrap_or(DEFAULT_FORMAT),
-                locale.unwrap_or(DEFAULT_LOCALE),
-            )
-            .to_string()
+        self.format_string(DateTimeZone::Local, format, locale)
     }
 
     /**
         Formats the `DateTime` using the universal (UTC) time
         zone, the given format string, and the given locale.
 
-        `format` and `locale` default to `"%Y-%m-%d %H:%M:%S"` and `"en"` respectively.
-
-        See [`chrono_lc::DateTime::formatl`] for additional details.
+        See [`DateTime::format_string`] for additional details.
     */
     #[must_use]
     pub fn format_string_universal(&self, format: Option<&str>, locale: Option<&str>) -> String {
-        self.inner
-            .with_timezone(&Utc)
-            .formatl(
-                format.unwrap_or(DEFAULT_FORMAT),
-                locale.unwrap_or(DEFAULT_LOCALE),
-            )
-            .to_string()
+        self.format_string(DateTimeZone::Universal, format, locale)
+    }
+
+    /**
+        Parses a time string using the given format string, such as `%d/%m/%Y %H:%M`.
+
+        If the format string contains an offset or a unix timestamp, the time zone is ignored,
+        otherwise the parsed date and time are interpreted in the given time zone. Formats
+        that contain a date but no time are interpreted as midnight on that date.
+
+        See [`chrono::format::strftime`] for the supported format specifiers.
+
+        # Errors
+
+        Returns an error if the input string does not match the format string, if the
+        format string does not describe a full date, or if the result is ambiguous.
+    */
+    pub fn from_format(text: &str, format: &str, time_zone: DateTimeZone) -> DateTimeResult<Self> {
+        let mut parsed = Parsed::new();
+        parse(&mut parsed, text, StrftimeItems::new(format))?;
+
+        if parsed.offset().is_some() {
+            let inner = parsed.to_datetime()?.with_timezone(&Utc);
+            return Ok(Self { inner });
+        }
+        if parsed.timestamp().is_some() {
+            let inner = parsed.to_datetime_with_timezone(&Utc)?;
+            return Ok(Self { inner });
+        }
+
+        let date = parsed.to_naive_date()?;
+        let time = if parsed.hour_div_12().is_none() && parsed.hour_mod_12().is_none() {
+            NaiveTime::MIN
+        } else {
+            parsed.to_naive_time()?
+        };
+
+        let inner = time_zone.from_naive(&NaiveDateTime::new(date, time))?;
+
+        Ok(Self { inner })
     }
 
     /**
@@ -173,13 +215,22 @@ impl DateTime {
         Ok(Self { inner })
     }
 
+    /**
+        Extracts individual date & time values from this
+        `DateTime`, using the given time zone.
+    */
+    #[must_use]
+    pub fn to_time(self, time_zone: DateTimeZone) -> DateTimeValues {
+        time_zone.to_values(&self.inner)
+    }
+
     /**
         Extracts individual date & time values from this
         `DateTime`, using the current local time zone.
     */
     #[must_use]
     pub fn to_local_time(self) -> DateTimeValues {
-        DateTimeValues::from(self.inner.with_timezone(&Local))
+        self.to_time(DateTimeZone::Local)
     }
 
     /**
@@ -188,7 +239,91 @@ impl DateTime {
     */
     #[must_use]
     pub fn to_universal_time(self) -> DateTimeValues {
-        DateTimeValues::from(self.inner.with_timezone(&Utc))
+        self.to_time(DateTimeZone::Universal)
+    }
+
+    /**
+        Adds the given number of seconds, which may be
+        fractional or negative, to this `DateTime`.
+
+        # Errors
+
+        Returns an error if the resulting `DateTime` would be out of range.
+    */
+    pub fn add_seconds(self, seconds: f64) -> DateTimeResult<Self> {
+        if !seconds.is_finite() || seconds.abs() > i64::MAX as f64 / 1_000.0 {
+            return Err(DateTimeError::OutOfRangeUnspecified);
+        }
+        let whole = TimeDelta::try_seconds(seconds.trunc() as i64)
+            .ok_or(DateTimeError::OutOfRangeUnspecified)?;
+        let fract = TimeDelta::nanoseconds((seconds.fract() * 1_000_000_000f64).round() as i64);
+        let inner = self
+            .inner
+            .checked_add_signed(whole + fract)
+            .ok_or(DateTimeError::OutOfRangeUnspecified)?;
+        Ok(Self { inner })
+    }
+
+    /**
+        Adds the given duration to this `DateTime`.
+
+        Years, months, and days are added first, using the calendar of the universal (UTC)
+        time zone, where adding months clamps the day to the last day of the resulting
+        month - adding one month to January 31st results in the last day of February.
+        Hours, minutes, seconds, and milliseconds are then added as exact amounts of time.
+
+        # Errors
+
+        Returns an error if the resulting `DateTime` would be out of range.
+    */
+    pub fn add_duration(self, duration: &DateTimeDuration) -> DateTimeResult<Self> {
+        let out_of_range = || DateTimeError::OutOfRangeUnspecified;
+
+        let months = duration
+            .years
+            .checked_mul(12)
+            .and_then(|months| months.checked_add(duration.months))
+            .ok_or_else(out_of_range)?;
+        let inner = if months.is_negative() {
+            let months = u32::try_from(months.unsigned_abs()).map_err(|_| out_of_range())?;
+            self.inner.checked_sub_months(Months::new(months))
+        } else {
+            let months = u32::try_from(months).map_err(|_| out_of_range())?;
+            self.inner.checked_add_months(Months::new(months))
+        }
+        .ok_or_else(out_of_range)?;
+
+        let days = Days::new(duration.days.unsigned_abs());
+        let inner = if duration.days.is_negative() {
+            inner.checked_sub_days(days)
+        } else {
+            inner.checked_add_days(days)
+        }
+        .ok_or_else(out_of_range)?;
+
+        let delta = [
+            TimeDelta::try_hours(duration.hours),
+            TimeDelta::try_minutes(duration.minutes),
+            TimeDelta::try_seconds(duration.seconds),
+            TimeDelta::try_milliseconds(duration.milliseconds),
+        ]
+        .into_iter()
+        .try_fold(TimeDelta::zero(), |total, delta| total.checked_add(&delta?))
+        .ok_or_else(out_of_range)?;
+        let inner = inner.checked_add_signed(delta).ok_or_else(out_of_range)?;
+
+        Ok(Self { inner })
+    }
+
+    /**
+        Returns the number of seconds from `other` to this `DateTime`, which
+        is fractional when the difference is not a whole number of seconds,
+        and negative when `other` is later than this `DateTime`.
+    */
+    #[must_use]
+    pub fn seconds_since(self, other: Self) -> f64 {
+        let delta = self.inner.signed_duration_since(other.inner);
+        delta.num_seconds() as f64 + f64::from(delta.subsec_nanos()) / 1_000_000_000f64
     }
 
     /**
@@ -228,8 +363,47 @@ impl LuaUserData for DateTime {
                 Ok(matches!(this.cmp(&other), Ordering::Less | Ordering::Equal))
             },
         );
+        // Metamethods to add seconds to and subtract seconds or other DateTime values from a DateTime
+        // NOTE: Adding is commutative, so the DateTime may be on either side of the
+        // operator, and we can not use a meta method which expects it on the left
+        methods.add_meta_function(
+            LuaMetaMethod::Add,
+            |lua, (lhs, rhs): (LuaValue, LuaValue)| {
+                let (this, seconds) = match (&lhs, &rhs) {
+                    (LuaValue::UserData(ud), _) if ud.is::<Self>() => (*ud.borrow::<Self>()?, rhs),
+                    (_, LuaValue::UserData(ud)) if ud.is::<Self>() => (*ud.borrow::<Self>()?, lhs),
+                    _ => return Err(LuaError::runtime("Expected a DateTime to add seconds to")),
+                };
+                let seconds = f64::from_lua(seconds, lua)?;
+                Ok(this.add_seconds(seconds)?)
+            },
+        );
+        methods.add_meta_method(
+            LuaMetaMethod::Sub,
+            |lua, this: &Self, other: LuaValue| match &other {
+                LuaValue::UserData(ud) if ud.is::<Self>() => {
+                    let other = *ud.borrow::<Self>()?;
+                    this.seconds_since(other).into_lua(lua)
+                }
+                _ => {
+                    let seconds = f64::from_lua(other, lua)?;
+                    this.add_seconds(-seconds)?.into_lua(lua)
+                }
+            },
+        );
         // Normal methods
+        methods.add_method("add", |_, this, duration: DateTimeDuration| {
+            Ok(this.add_duration(&duration)?)
+        });
         methods.add_method("toIsoDate", |_, this, ()| Ok(this.to_iso_date()));
+        methods.add_method(
+            "formatTime",
+            |_,
+             this,
+             (time_zone, format, locale): (DateTimeZone, Option<String>, Option<String>)| {
+                Ok(this.format_string(time_zone, format.as_deref(), locale.as_deref()))
+            },
+        );
         methods.add_method(
             "formatUniversalTime",
             |_, this, (format, locale): (Option<String>, Option<String>)| {
@@ -246,5 +420,8 @@ impl LuaUserData for DateTime {
             Ok(this.to_universal_time())
         });
         methods.add_method("toLocalTime", |_, this: &Self, ()| Ok(this.to_local_time()));
+        methods.add_method("toTime", |_, this: &Self, time_zone| {
+            Ok(this.to_time(time_zone))
+        });
     }
 }
@@ -2,13 +2,15 @@
 
 use mlua::prelude::*;
 
-use lune_utils::TableBuilder;
+use lune_utils::{fmt::set_native_value_formatter, TableBuilder};
 
 mod date_time;
 mod result;
+mod time_zone;
 mod values;
 
 pub use self::date_time::DateTime;
+pub use self::time_zone::DateTimeZone;
 
 /**
     Creates the `datetime` standard library module.
@@ -19,12 +21,25 @@ pub use self::date_time::DateTime;
 */
 pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
+        .with_function(
+            "fromFormat",
+            |_, (text, format, time_zone): (String, String, Option<DateTimeZone>)| {
+                Ok(DateTime::from_format(
+                    &text,
+                    &format,
+                    time_zone.unwrap_or(DateTimeZone::Universal),
+                )?)
+            },
+        )?
         .with_function("fromIsoDate", |_, iso_date: String| {
             Ok(DateTime::from_iso_date(iso_date)?)
         })?
         .with_function("fromLocalTime", |_, values| {
             Ok(DateTime::from_local_time(&values)?)
         })?
+        .with_function("fromTime", |_, (values, time_zone)| {
+            Ok(DateTime::from_time(&values, time_zone)?)
+        })?
         .with_function("fromUniversalTime", |_, values| {
             Ok(DateTime::from_universal_time(&values)?)
         })?
@@ -34,3 +49,17 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .with_function("now", |_, ()| Ok(DateTime::now()))?
         .build_readonly()
 }
+
+/**
+    Registers a formatter that shows `DateTime` values as ISO 8601 dates
+    when they are printed, instead of showing only their type name.
+*/
+pub fn register_value_formatter(lua: &Lua) {
+    set_native_value_formatter(lua, "DateTime", |_, value| {
+        let date_time = *value
+            .as_userdata()
+            .ok_or_else(|| LuaError::runtime("Expected a DateTime"))?
+            .borrow::<DateTime>()?;
+        Ok(date_time.to_iso_date())
+    });
+}
@@ -21,6 +21,14 @@ pub enum DateTimeError {
         min: String,
         max: String,
     },
+    #[error(
+        "invalid time zone '{0}', expected \"utc\", \"local\", or an offset such as \"+05:30\""
+    )]
+    InvalidTimeZone(String),
+    #[error(
+        "named time zone '{0}' is not supported, use \"local\" or an offset such as \"+05:30\""
+    )]
+    NamedTimeZone(String),
     #[error(transparent)]
     ParseError(#[from] chrono::ParseError),
 }
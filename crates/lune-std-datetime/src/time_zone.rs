@@ -0,0 +1,135 @@
+use mlua::prelude::*;
+
+use chrono::prelude::*;
+use chrono::DateTime as ChronoDateTime;
+use chrono_lc::LocaleDate;
+
+use crate::result::{DateTimeError, DateTimeResult};
+use crate::values::DateTimeValues;
+
+/**
+    A time zone that dates and times may be converted to and from.
+
+    Only the universal (UTC) time zone, the local time zone, and fixed offsets from
+    UTC are supported, named time zones would need a copy of the time zone database.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateTimeZone {
+    Universal,
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl DateTimeZone {
+    /**
+        Parses a time zone from a string, which may be `"utc"`, `"local"`,
+        or an offset from UTC such as `"+05:30"`, `"-0800"`, or `"+01"`.
+
+        # Errors
+
+        Returns an error if the string is not one of the supported time zones.
+    */
+    pub fn parse(s: &str) -> DateTimeResult<Self> {
+        let invalid = || DateTimeError::InvalidTimeZone(s.to_string());
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.as_str() {
+            "utc" | "gmt" | "z" => return Ok(Self::Universal),
+            "local" => return Ok(Self::Local),
+            // NOTE: Names from the time zone database are always in the
+            // form of Area/Location, see the limitation documented above
+            _ if lower.contains('/') => return Err(DateTimeError::NamedTimeZone(s.to_string())),
+            _ => {}
+        }
+
+        let sign = match lower.as_bytes().first() {
+            Some(b'+') => 1,
+            Some(b'-') => -1,
+            _ => return Err(invalid()),
+        };
+        let digits = lower[1..].replace(':', "");
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let (hours, minutes) = match digits.len() {
+            2 => (&digits[..], "0"),
+            4 => (&digits[..2], &digits[2..]),
+            _ => return Err(invalid()),
+        };
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(Self::Fixed)
+            .ok_or_else(invalid)
+    }
+
+    /**
+        Creates a point in time from a date and time in this time zone.
+
+        # Errors
+
+        Returns an error if the date and time do not exist, or exist twice,
+        in this time zone, such as during daylight saving time transitions.
+    */
+    pub fn from_naive(self, naive: &NaiveDateTime) -> DateTimeResult<ChronoDateTime<Utc>> {
+        let inner = match self {
+            Self::Universal => Utc.from_utc_datetime(naive),
+            Self::Local => Local
+                .from_local_datetime(naive)
+                .single()
+                .ok_or(DateTimeError::Ambiguous)?
+                .with_timezone(&Utc),
+            Self::Fixed(offset) => offset
+                .from_local_datetime(naive)
+                .single()
+                .ok_or(DateTimeError::Ambiguous)?
+                .with_timezone(&Utc),
+        };
+        Ok(inner)
+    }
+
+    /**
+        Extracts individual date & time values from a point in time, in this time zone.
+    */
+    #[must_use]
+    pub fn to_values(self, inner: &ChronoDateTime<Utc>) -> DateTimeValues {
+        match self {
+            Self::Universal => DateTimeValues::from(*inner),
+            Self::Local => DateTimeValues::from(inner.with_timezone(&Local)),
+            Self::Fixed(offset) => DateTimeValues::from(inner.with_timezone(&offset)),
+        }
+    }
+
+    /**
+        Formats a point in time in this time zone, using the given format string and locale.
+    */
+    #[must_use]
+    pub fn format(self, inner: &ChronoDateTime<Utc>, format: &str, locale: &str) -> String {
+        match self {
+            Self::Universal => inner.formatl(format, locale).to_string(),
+            Self::Local => inner
+                .with_timezone(&Local)
+                .formatl(format, locale)
+                .to_string(),
+            Self::Fixed(offset) => inner
+                .with_timezone(&offset)
+                .formatl(format, locale)
+                .to_string(),
+        }
+    }
+}
+
+impl FromLua<'_> for DateTimeZone {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) => Ok(Self::parse(s.to_str()?)?),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "TimeZone",
+                message: Some("time zone must be a string".to_string()),
+            }),
+        }
+    }
+}
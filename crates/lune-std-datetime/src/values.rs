@@ -60,6 +60,47 @@ where
     }
 }
 
+/**
+    An amount of time to add to a point in time, with calendar-aware
+    years, months, and days, and exact hours, minutes, and seconds.
+
+    All values are optional when converting from lua and default to zero,
+    negative values may be used to subtract from a point in time instead.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateTimeDuration {
+    pub years: i64,
+    pub months: i64,
+    pub days: i64,
+    pub hours: i64,
+    pub minutes: i64,
+    pub seconds: i64,
+    pub milliseconds: i64,
+}
+
+impl FromLua<'_> for DateTimeDuration {
+    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+        let LuaValue::Table(value) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "DateTimeDuration",
+                message: Some("value must be a table".to_string()),
+            });
+        };
+        Ok(Self {
+            years: value.get::<_, Option<i64>>("years")?.unwrap_or_default(),
+            months: value.get::<_, Option<i64>>("months")?.unwrap_or_default(),
+            days: value.get::<_, Option<i64>>("days")?.unwrap_or_default(),
+            hours: value.get::<_, Option<i64>>("hours")?.unwrap_or_default(),
+            minutes: value.get::<_, Option<i64>>("minutes")?.unwrap_or_default(),
+            seconds: value.get::<_, Option<i64>>("seconds")?.unwrap_or_default(),
+            milliseconds: value
+                .get::<_, Option<i64>>("milliseconds")?
+                .unwrap_or_default(),
+        })
+    }
+}
+
 /**
     Conversion methods between `DateTimeValues` and plain lua tables
 
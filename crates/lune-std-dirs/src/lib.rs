@@ -0,0 +1,93 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use std::path::{Path, PathBuf};
+
+use directories::{BaseDirs, ProjectDirs};
+use mlua::prelude::*;
+use tokio::fs;
+
+use lune_utils::TableBuilder;
+
+mod options;
+
+use self::options::AppDirsOptions;
+
+/**
+    Creates the `dirs` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("home", |_, ()| base_dir(BaseDirs::home_dir))?
+        .with_function("config", |_, ()| base_dir(BaseDirs::config_dir))?
+        .with_function("cache", |_, ()| base_dir(BaseDirs::cache_dir))?
+        .with_function("data", |_, ()| base_dir(BaseDirs::data_dir))?
+        .with_function("state", |_, ()| {
+            base_dir(|dirs| dirs.state_dir().unwrap_or_else(|| dirs.data_local_dir()))
+        })?
+        .with_async_function("app", dirs_app)?
+        .build_readonly()
+}
+
+/*
+    NOTE: Directories are looked up each time instead of only once, since
+    they depend on environment variables such as XDG_CONFIG_HOME on Linux,
+    which may be changed by the script itself using process.env
+*/
+fn base_dir(f: impl FnOnce(&BaseDirs) -> &Path) -> LuaResult<String> {
+    let dirs = BaseDirs::new().ok_or_else(|| {
+        LuaError::runtime("Failed to find the home directory of the current user")
+    })?;
+    Ok(path_to_string(f(&dirs)))
+}
+
+async fn dirs_app(lua: &Lua, (name, options): (String, AppDirsOptions)) -> LuaResult<LuaTable> {
+    if name.trim().is_empty() {
+        return Err(LuaError::runtime("App name must not be empty"));
+    }
+    let dirs =
+        ProjectDirs::from(&options.qualifier, &options.organization, &name).ok_or_else(|| {
+            LuaError::runtime("Failed to find the home directory of the current user")
+        })?;
+
+    // NOTE: Only Linux has a separate directory for state, other
+    // platforms keep state together with other local app data
+    let paths: [(&str, PathBuf); 4] = [
+        ("config", dirs.config_dir().to_path_buf()),
+        ("cache", dirs.cache_dir().to_path_buf()),
+        ("data", dirs.data_dir().to_path_buf()),
+        (
+            "state",
+            dirs.state_dir()
+                .unwrap_or_else(|| dirs.data_local_dir())
+                .to_path_buf(),
+        ),
+    ];
+
+    if options.create {
+        for (_, path) in &paths {
+            fs::create_dir_all(path).await.map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to create directory '{}' - {e}",
+                    path.display()
+                ))
+            })?;
+        }
+    }
+
+    TableBuilder::new(lua)?
+        .with_values(
+            paths
+                .iter()
+                .map(|(kind, path)| (*kind, path_to_string(path)))
+                .collect(),
+        )?
+        .build_readonly()
+}
+
+fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
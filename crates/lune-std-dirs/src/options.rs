@@ -0,0 +1,53 @@
+use mlua::prelude::*;
+
+/**
+    Options for getting the directories of an app using `dirs.app`.
+*/
+#[derive(Debug, Clone)]
+pub struct AppDirsOptions {
+    pub qualifier: String,
+    pub organization: String,
+    pub create: bool,
+}
+
+impl Default for AppDirsOptions {
+    fn default() -> Self {
+        Self {
+            qualifier: String::new(),
+            organization: String::new(),
+            create: true,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for AppDirsOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let mut this = Self::default();
+        let options = match value {
+            LuaValue::Nil => return Ok(this),
+            LuaValue::Table(t) => t,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "AppDirsOptions",
+                    message: Some(format!(
+                        "Invalid app dirs options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+
+        if let Some(qualifier) = options.get::<_, Option<String>>("qualifier")? {
+            this.qualifier = qualifier;
+        }
+        if let Some(organization) = options.get::<_, Option<String>>("organization")? {
+            this.organization = organization;
+        }
+        if let Some(create) = options.get::<_, Option<bool>>("create")? {
+            this.create = create;
+        }
+
+        Ok(this)
+    }
+}
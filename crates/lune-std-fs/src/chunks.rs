@@ -0,0 +1,104 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+use crate::file::{FsFile, FsOpenMode};
+
+// Chunks are read in pieces of this many bytes, unless another size was given
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+const CHUNK_READER_IMPL_LUA: &str = r"
+return freeze({
+	next = function(...)
+		return reader:next(...)
+	end,
+	close = function(...)
+		return reader:close(...)
+	end,
+})
+";
+
+/**
+    Reads a file in chunks of a fixed size, so that only one chunk at a time needs to be in memory.
+
+    The file is closed once the last chunk has been read, or when closing the reader before that.
+*/
+#[derive(Debug, Clone)]
+pub struct FsChunkReader {
+    file: FsFile,
+    chunk_size: usize,
+    done: Arc<AtomicBool>,
+}
+
+impl FsChunkReader {
+    pub async fn open(lua: &Lua, path: String, chunk_size: Option<usize>) -> LuaResult<Self> {
+        let chunk_size = match chunk_size {
+            Some(0) => {
+                return Err(LuaError::runtime(
+                    "Invalid chunk size - expected a positive integer",
+                ))
+            }
+            Some(size) => size,
+            None => DEFAULT_CHUNK_SIZE,
+        };
+        Ok(Self {
+            file: FsFile::open(lua, path, FsOpenMode::Read).await?,
+            chunk_size,
+            done: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub async fn next(&self) -> LuaResult<Option<Vec<u8>>> {
+        if self.done.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
+        let chunk = self.file.read(Some(self.chunk_size)).await?;
+        if chunk.is_none() {
+            self.close().await?;
+        }
+        Ok(chunk)
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        if self.done.swap(true, Ordering::SeqCst) {
+            Ok(())
+        } else {
+            self.file.close().await
+        }
+    }
+
+    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+
+        let env = TableBuilder::new(lua)?
+            .with_value("reader", self)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+
+        lua.load(CHUNK_READER_IMPL_LUA)
+            .set_name("chunks")
+            .set_environment(env)
+            .eval()
+    }
+}
+
+impl LuaUserData for FsChunkReader {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("next", |lua, this, (): ()| async move {
+            match this.next().await? {
+                None => Ok(LuaValue::Nil),
+                Some(chunk) => lua.create_string(chunk).map(LuaValue::String),
+            }
+        });
+
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+}
@@ -6,6 +6,7 @@ use mlua::prelude::*;
 use tokio::fs;
 
 use super::options::FsWriteOptions;
+use super::root::is_inside_root;
 
 pub struct CopyContents {
     // Vec<(relative depth, path)>
@@ -13,7 +14,11 @@ pub struct CopyContents {
     pub files: Vec<(usize, PathBuf)>,
 }
 
-async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyContents> {
+async fn get_contents_at(
+    root: PathBuf,
+    _: FsWriteOptions,
+    fs_root: Option<&Path>,
+) -> LuaResult<CopyContents> {
     let mut dirs = Vec::new();
     let mut files = Vec::new();
 
@@ -33,13 +38,11 @@ async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyCont
     // when we find any new descendant directories
     // FUTURE: Try to do async reading here concurrently to speed it up a bit
     while let Some((current_depth, current_path)) = queue.pop_front() {
-        let meta = fs::metadata(&current_path).await?;
+        let mut meta = fs::symlink_metadata(&current_path).await?;
         if meta.is_symlink() {
-            return Err(LuaError::RuntimeError(format!(
-                "Symlinks are not yet supported, encountered at path '{}'",
-                current_path.display()
-            )));
-        } else if meta.is_dir() {
+            meta = follow_symlink(&normalized_root, &current_path, fs_root).await?;
+        }
+        if meta.is_dir() {
             // FUTURE: Add an option in FsWriteOptions for max depth and limit it here
             let mut entries = fs::read_dir(&current_path).await?;
             while let Some(entry) = entries.next_entry().await? {
@@ -69,17 +72,61 @@ async fn get_contents_at(root: PathBuf, _: FsWriteOptions) -> LuaResult<CopyCont
     Ok(CopyContents { dirs, files })
 }
 
-async fn ensure_no_dir_exists(path: impl AsRef<Path>) -> LuaResult<()> {
-    let path = path.as_ref();
-    match fs::metadata(&path).await {
-        Ok(meta) if meta.is_dir() => Err(LuaError::RuntimeError(format!(
-            "A directory already exists at the path '{}'",
-            path.display()
+/**
+    Follows a symlink found while copying a directory, which gets copied as whatever
+    it points to, and returns the metadata of that.
+
+    With a filesystem root set, the symlink must point to something inside of it,
+    and symlinks that point to a directory containing them are never followed,
+    since copying them would never end.
+*/
+async fn follow_symlink(
+    dir: &Path,
+    path: &Path,
+    fs_root: Option<&Path>,
+) -> LuaResult<std::fs::Metadata> {
+    let display = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+    let real = fs::canonicalize(path).await.map_err(|e| {
+        LuaError::RuntimeError(format!("Failed to follow symlink at '{display}' - {e}"))
+    })?;
+    if fs_root.is_some_and(|fs_root| !real.starts_with(fs_root)) {
+        return Err(LuaError::RuntimeError(format!(
+            "Symlink at '{display}' points outside of the filesystem root"
+        )));
+    }
+    let parent = match path.parent() {
+        Some(parent) => fs::canonicalize(parent).await?,
+        None => PathBuf::new(),
+    };
+    if parent.starts_with(&real) {
+        return Err(LuaError::RuntimeError(format!(
+            "Symlink at '{display}' points to a directory that contains it"
+        )));
+    }
+    Ok(fs::metadata(&real).await?)
+}
+
+/**
+    Makes sure that writing to the given path would not write outside of the
+    filesystem root, such as through a symlink that already exists at the path.
+*/
+fn ensure_inside_root(path: &Path, fs_root: Option<&Path>) -> LuaResult<()> {
+    match fs_root {
+        Some(fs_root) if !is_inside_root(fs_root, path) => Err(LuaError::RuntimeError(format!(
+            "Path '{}' is outside of the filesystem root",
+            virtual_display(fs_root, path)
         ))),
         _ => Ok(()),
     }
 }
 
+fn virtual_display(fs_root: &Path, path: &Path) -> String {
+    match path.strip_prefix(fs_root) {
+        Ok(relative) => format!("/{}", relative.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
 async fn ensure_no_file_exists(path: impl AsRef<Path>) -> LuaResult<()> {
     let path = path.as_ref();
     match fs::metadata(&path).await {
@@ -91,10 +138,111 @@ async fn ensure_no_file_exists(path: impl AsRef<Path>) -> LuaResult<()> {
     }
 }
 
+/**
+    Gets the absolute path to the given path, with symlinks resolved in all but
+    the last component, which also works for paths that do not exist yet.
+*/
+async fn canonical_path(path: &Path) -> LuaResult<PathBuf> {
+    let path = std::path::absolute(path)?;
+    let mut current = path.as_path();
+    let mut missing = Vec::new();
+    if let (Some(parent), Some(name)) = (current.parent(), current.file_name()) {
+        missing.push(name);
+        current = parent;
+    }
+    loop {
+        match fs::canonicalize(current).await {
+            Ok(mut resolved) => {
+                resolved.extend(missing.iter().rev());
+                return Ok(resolved);
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                match (current.parent(), current.file_name()) {
+                    (Some(parent), Some(name)) => {
+                        missing.push(name);
+                        current = parent;
+                    }
+                    _ => return Err(e.into()),
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/**
+    Makes sure that the target path is not the same as the source path, or inside of it,
+    since copying or moving onto itself would remove the source before it is copied.
+
+    Copying follows a symlink at the source path, while moving moves the symlink itself.
+*/
+async fn ensure_distinct_paths(source: &Path, target: &Path, follow: bool) -> LuaResult<()> {
+    let canonical_source = if follow {
+        fs::canonicalize(source).await?
+    } else {
+        canonical_path(source).await?
+    };
+    let canonical_target = canonical_path(target).await?;
+    if canonical_source == canonical_target {
+        Err(LuaError::RuntimeError(format!(
+            "The paths '{}' and '{}' are the same path",
+            source.display(),
+            target.display()
+        )))
+    } else if canonical_target.starts_with(&canonical_source) {
+        Err(LuaError::RuntimeError(format!(
+            "The path '{}' is inside of the path '{}'",
+            target.display(),
+            source.display()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/**
+    Prepares the target path for copying or moving a directory to it.
+
+    With the merge option, any existing directory at the target is kept as-is.
+    Otherwise, anything at the target path is either removed, if overwriting,
+    or an error is returned, so that nothing is copied on top of it.
+*/
+async fn prepare_dir_target(target: &Path, options: FsWriteOptions) -> LuaResult<()> {
+    let target_meta = match fs::metadata(&target).await {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if target_meta.is_dir() && options.merge {
+        Ok(())
+    } else if !options.overwrite {
+        Err(LuaError::RuntimeError(format!(
+            "A {} already exists at the path '{}'",
+            if target_meta.is_dir() {
+                "directory"
+            } else {
+                "file"
+            },
+            target.display()
+        )))
+    } else if target_meta.is_dir() {
+        fs::remove_dir_all(target).await.into_lua_err()
+    } else {
+        fs::remove_file(target).await.into_lua_err()
+    }
+}
+
+/**
+    Copies a file or directory, including everything inside of the directory.
+
+    With a filesystem root given, everything that is read when copying the
+    directory, and everything that is written to, must be inside of the root.
+*/
 pub async fn copy(
     source: impl AsRef<Path>,
     target: impl AsRef<Path>,
     options: FsWriteOptions,
+    fs_root: Option<&Path>,
 ) -> LuaResult<()> {
     let source = source.as_ref();
     let target = target.as_ref();
@@ -116,51 +264,116 @@ pub async fn copy(
             source.display()
         )));
     }
+    ensure_distinct_paths(source, target, true).await?;
+
+    if is_file {
+        if !options.overwrite {
+            ensure_no_file_exists(target).await?;
+        }
+        fs::copy(source, target).await?;
+        return Ok(());
+    }
 
     // Perform copying:
     //
-    // 1. If we are not allowed to overwrite, make sure nothing exists at the target path
-    // 2. If we are allowed to overwrite, remove any previous entry at the path
+    // 1. Make sure the target path is ready, according to our options
+    // 2. If merging without overwriting, make sure none of the files exist yet,
+    //    so that we don't end up with a partially copied directory on conflicts
     // 3. Write all directories first
     // 4. Write all files
+    // 5. Copy directory permissions last, since they may be read-only
 
-    if !options.overwrite {
-        if is_file {
-            ensure_no_file_exists(target).await?;
-        } else if is_dir {
-            ensure_no_dir_exists(target).await?;
+    let contents = get_contents_at(source.to_path_buf(), options, fs_root).await?;
+    for (_, path) in contents.dirs.iter().chain(&contents.files) {
+        ensure_inside_root(&target.join(path), fs_root)?;
+    }
+
+    prepare_dir_target(target, options).await?;
+    if options.merge && !options.overwrite {
+        for (_, file) in &contents.files {
+            ensure_no_file_exists(target.join(file)).await?;
         }
     }
 
-    if is_file {
-        fs::copy(source, target).await?;
-    } else if is_dir {
-        let contents = get_contents_at(source.to_path_buf(), options).await?;
-
-        if options.overwrite {
-            let (is_dir, is_file) = match fs::metadata(&target).await {
-                Ok(meta) => (meta.is_dir(), meta.is_file()),
-                Err(e) if e.kind() == ErrorKind::NotFound => (false, false),
-                Err(e) => return Err(e.into()),
-            };
-            if is_dir {
-                fs::remove_dir_all(target).await?;
-            } else if is_file {
-                fs::remove_file(target).await?;
-            }
+    fs::create_dir_all(target).await?;
+
+    // FUTURE: Write dirs / files concurrently
+    // to potentially speed these operations up
+    for (_, dir) in &contents.dirs {
+        fs::create_dir_all(target.join(dir)).await?;
+    }
+    for (_, file) in &contents.files {
+        fs::copy(source.join(file), target.join(file)).await?;
+    }
+
+    // NOTE: Files always keep their permissions when copied, and directories
+    // are sorted by depth, so we go deepest first to not lock ourselves out
+    if options.preserve_permissions {
+        for (_, dir) in contents.dirs.iter().rev() {
+            let permissions = fs::metadata(source.join(dir)).await?.permissions();
+            fs::set_permissions(target.join(dir), permissions).await?;
         }
+        let permissions = fs::metadata(source).await?.permissions();
+        fs::set_permissions(target, permissions).await?;
+    }
 
-        fs::create_dir_all(target).await?;
+    Ok(())
+}
+
+/**
+    Moves a file or directory, falling back to copying and then removing
+    the source when it can not be renamed, such as across mount points.
+*/
+pub async fn move_path(
+    source: impl AsRef<Path>,
+    target: impl AsRef<Path>,
+    options: FsWriteOptions,
+    fs_root: Option<&Path>,
+) -> LuaResult<()> {
+    let source = source.as_ref();
+    let target = target.as_ref();
 
-        // FUTURE: Write dirs / files concurrently
-        // to potentially speed these operations up
-        for (_, dir) in &contents.dirs {
-            fs::create_dir_all(target.join(dir)).await?;
+    let source_meta = match fs::metadata(&source).await {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(LuaError::RuntimeError(format!(
+                "No file or directory exists at the path '{}'",
+                source.display()
+            )))
         }
-        for (_, file) in &contents.files {
-            fs::copy(source.join(file), target.join(file)).await?;
+        Err(e) => return Err(e.into()),
+    };
+    ensure_distinct_paths(source, target, false).await?;
+
+    // Merging directories can not be done by renaming, only by copying
+    let target_is_dir = fs::metadata(&target).await.is_ok_and(|meta| meta.is_dir());
+    if options.merge && source_meta.is_dir() && target_is_dir {
+        copy(source, target, options, fs_root).await?;
+        return fs::remove_dir_all(source).await.into_lua_err();
+    }
+
+    if source_meta.is_dir() {
+        prepare_dir_target(target, options).await?;
+    } else if fs::metadata(&target).await.is_ok() {
+        if !options.overwrite {
+            return Err(LuaError::RuntimeError(format!(
+                "A file or directory already exists at the path '{}'",
+                target.display()
+            )));
+        } else if target_is_dir {
+            fs::remove_dir_all(target).await?;
         }
     }
 
-    Ok(())
+    match fs::rename(source, target).await {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy(source, target, options, fs_root).await?;
+            if source_meta.is_dir() {
+                fs::remove_dir_all(source).await.into_lua_err()
+            } else {
+                fs::remove_file(source).await.into_lua_err()
+            }
+        }
+        res => res.into_lua_err(),
+    }
 }
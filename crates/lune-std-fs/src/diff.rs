@@ -0,0 +1,113 @@
+use std::fmt::Write;
+
+use console::style;
+
+use lune_utils::diff::{diff_lines, DiffOp};
+
+// Number of unchanged lines to show around each change
+const CONTEXT_LINES: usize = 3;
+
+/**
+    Creates a colored, unified diff between the old and new contents of a file.
+
+    Returns `None` if the contents are the same.
+*/
+pub fn create_diff(path: &str, old: Option<&[u8]>, new: &[u8]) -> Option<String> {
+    if old == Some(new) {
+        return None;
+    }
+
+    let old_name = if old.is_some() { path } else { "/dev/null" };
+    let mut diff = String::new();
+    writeln!(
+        diff,
+        "{}",
+        style(format!("--- {old_name}")).bold().for_stderr()
+    )
+    .ok();
+    writeln!(diff, "{}", style(format!("+++ {path}")).bold().for_stderr()).ok();
+
+    let (Ok(old), Ok(new)) = (
+        std::str::from_utf8(old.unwrap_or_default()),
+        std::str::from_utf8(new),
+    ) else {
+        writeln!(diff, "Binary contents differ").ok();
+        return Some(diff);
+    };
+
+    let old_lines = old.lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let ops = diff_lines(&old_lines, &new_lines);
+
+    let hunks = hunks(&ops);
+    if hunks.is_empty() {
+        writeln!(diff, "Only line endings or trailing newlines changed").ok();
+    }
+    for hunk in hunks {
+        let (mut old_index, mut new_index) = count_lines(&ops[..hunk.start]);
+        let (old_len, new_len) = count_lines(&ops[hunk.clone()]);
+        let header = format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_index, old_len),
+            hunk_range(new_index, new_len)
+        );
+        writeln!(diff, "{}", style(header).cyan().for_stderr()).ok();
+        for op in &ops[hunk] {
+            match op {
+                DiffOp::Equal => {
+                    writeln!(diff, " {}", old_lines[old_index]).ok();
+                    old_index += 1;
+                    new_index += 1;
+                }
+                DiffOp::Remove => {
+                    let line = format!("-{}", old_lines[old_index]);
+                    writeln!(diff, "{}", style(line).red().for_stderr()).ok();
+                    old_index += 1;
+                }
+                DiffOp::Add => {
+                    let line = format!("+{}", new_lines[new_index]);
+                    writeln!(diff, "{}", style(line).green().for_stderr()).ok();
+                    new_index += 1;
+                }
+            }
+        }
+    }
+
+    Some(diff)
+}
+
+// Groups changes into ranges of operations, including surrounding
+// context, merging any changes with overlapping context together
+fn hunks(ops: &[DiffOp]) -> Vec<std::ops::Range<usize>> {
+    let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+    for (index, op) in ops.iter().enumerate() {
+        if *op == DiffOp::Equal {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + 1 + CONTEXT_LINES).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if last.end >= start => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+    hunks
+}
+
+fn count_lines(ops: &[DiffOp]) -> (usize, usize) {
+    ops.iter().fold((0, 0), |(old, new), op| match op {
+        DiffOp::Equal => (old + 1, new + 1),
+        DiffOp::Remove => (old + 1, new),
+        DiffOp::Add => (old, new + 1),
+    })
+}
+
+// Ranges in hunk headers are one-indexed, except for empty ranges,
+// which instead point at the line right before where they would be
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
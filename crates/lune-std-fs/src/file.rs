@@ -0,0 +1,262 @@
+use std::{io::SeekFrom, str::FromStr, sync::Arc};
+
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader},
+    sync::Mutex,
+};
+
+use lune_utils::TableBuilder;
+
+use crate::root::resolve_path;
+
+// The path is a plain field, since it never changes after the file was opened
+const FILE_HANDLE_IMPL_LUA: &str = r"
+return freeze({
+	path = path,
+	read = function(...)
+		return file:read(...)
+	end,
+	readLine = function(...)
+		return file:readLine(...)
+	end,
+	write = function(...)
+		return file:write(...)
+	end,
+	seek = function(...)
+		return file:seek(...)
+	end,
+	close = function(...)
+		return file:close(...)
+	end,
+})
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsOpenMode {
+    Read,
+    Write,
+    Append,
+    ReadWrite,
+    ReadWriteTruncate,
+    ReadAppend,
+}
+
+impl FsOpenMode {
+    const ALL: [&'static str; 6] = ["r", "w", "a", "r+", "w+", "a+"];
+
+    fn open_options(self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        match self {
+            Self::Read => options.read(true),
+            Self::Write => options.write(true).create(true).truncate(true),
+            Self::Append => options.append(true).create(true),
+            Self::ReadWrite => options.read(true).write(true),
+            Self::ReadWriteTruncate => options.read(true).write(true).create(true).truncate(true),
+            Self::ReadAppend => options.read(true).append(true).create(true),
+        };
+        options
+    }
+}
+
+impl FromStr for FsOpenMode {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "r" => Ok(Self::Read),
+            "w" => Ok(Self::Write),
+            "a" => Ok(Self::Append),
+            "r+" => Ok(Self::ReadWrite),
+            "w+" => Ok(Self::ReadWriteTruncate),
+            "a+" => Ok(Self::ReadAppend),
+            _ => Err(()),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsOpenMode {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::Read),
+            LuaValue::String(s) => {
+                let s = s.to_str()?;
+                s.parse().map_err(|()| LuaError::FromLuaConversionError {
+                    from: "string",
+                    to: "FsOpenMode",
+                    message: Some(format!(
+                        "Invalid file mode '{s}', valid modes are:\n{}",
+                        Self::ALL.join(", ")
+                    )),
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsOpenMode",
+                message: Some(format!(
+                    "Invalid file mode - expected string or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    An open file, read from and written to in chunks instead of all at once.
+
+    Reads are buffered, which is what makes reading lines cheap - the
+    buffer is discarded before any write or seek so that the position
+    of the underlying file always matches what has been read from it.
+*/
+#[derive(Debug, Clone)]
+pub struct FsFile {
+    path: String,
+    inner: Arc<Mutex<Option<BufReader<File>>>>,
+}
+
+impl FsFile {
+    pub async fn open(lua: &Lua, path: String, mode: FsOpenMode) -> LuaResult<Self> {
+        let real_path = resolve_path(lua, &path)?;
+        let file =
+            mode.open_options().open(&real_path).await.map_err(|e| {
+                LuaError::RuntimeError(format!("Failed to open file '{path}' - {e}"))
+            })?;
+        Ok(Self {
+            path,
+            inner: Arc::new(Mutex::new(Some(BufReader::new(file)))),
+        })
+    }
+
+    fn closed_error() -> LuaError {
+        LuaError::runtime("File has already been closed")
+    }
+
+    pub async fn read(&self, len: Option<usize>) -> LuaResult<Option<Vec<u8>>> {
+        let mut guard = self.inner.lock().await;
+        let file = guard.as_mut().ok_or_else(Self::closed_error)?;
+        let mut buf = Vec::new();
+        match len {
+            None => {
+                file.read_to_end(&mut buf).await.into_lua_err()?;
+            }
+            Some(len) => {
+                file.take(len as u64)
+                    .read_to_end(&mut buf)
+                    .await
+                    .into_lua_err()?;
+                if buf.is_empty() && len > 0 {
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(buf))
+    }
+
+    pub async fn read_line(&self) -> LuaResult<Option<Vec<u8>>> {
+        let mut guard = self.inner.lock().await;
+        let file = guard.as_mut().ok_or_else(Self::closed_error)?;
+        let mut line = Vec::new();
+        if file.read_until(b'\n', &mut line).await.into_lua_err()? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+            if line.ends_with(b"\r") {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    pub async fn write(&self, data: &[u8]) -> LuaResult<()> {
+        let mut guard = self.inner.lock().await;
+        let file = guard.as_mut().ok_or_else(Self::closed_error)?;
+        if !file.buffer().is_empty() {
+            file.seek(SeekFrom::Current(0)).await.into_lua_err()?;
+        }
+        let file = file.get_mut();
+        file.write_all(data).await.into_lua_err()?;
+        file.flush().await.into_lua_err()?;
+        Ok(())
+    }
+
+    pub async fn seek(&self, pos: SeekFrom) -> LuaResult<u64> {
+        let mut guard = self.inner.lock().await;
+        let file = guard.as_mut().ok_or_else(Self::closed_error)?;
+        file.seek(pos).await.into_lua_err()
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        let mut guard = self.inner.lock().await;
+        let file = guard.take().ok_or_else(Self::closed_error)?;
+        file.into_inner().flush().await.into_lua_err()
+    }
+
+    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+
+        let env = TableBuilder::new(lua)?
+            .with_value("path", self.path.clone())?
+            .with_value("file", self)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+
+        lua.load(FILE_HANDLE_IMPL_LUA)
+            .set_name("file")
+            .set_environment(env)
+            .eval()
+    }
+}
+
+fn parse_seek_from(whence: Option<String>, offset: Option<i64>) -> LuaResult<SeekFrom> {
+    let offset = offset.unwrap_or(0);
+    match whence.as_deref().unwrap_or("cur") {
+        "set" => u64::try_from(offset).map(SeekFrom::Start).map_err(|_| {
+            LuaError::RuntimeError(format!(
+                "Invalid seek offset {offset} - offsets from the start must not be negative"
+            ))
+        }),
+        "cur" => Ok(SeekFrom::Current(offset)),
+        "end" => Ok(SeekFrom::End(offset)),
+        whence => Err(LuaError::RuntimeError(format!(
+            "Invalid seek position '{whence}', valid positions are:\nset, cur, end"
+        ))),
+    }
+}
+
+impl LuaUserData for FsFile {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read", |lua, this, len: Option<usize>| async move {
+            match this.read(len).await? {
+                None => Ok(LuaValue::Nil),
+                Some(data) => lua.create_string(data).map(LuaValue::String),
+            }
+        });
+
+        methods.add_async_method("readLine", |lua, this, (): ()| async move {
+            match this.read_line().await? {
+                None => Ok(LuaValue::Nil),
+                Some(line) => lua.create_string(line).map(LuaValue::String),
+            }
+        });
+
+        methods.add_async_method("write", |_, this, data: BString| async move {
+            this.write(data.as_bytes()).await
+        });
+
+        methods.add_async_method(
+            "seek",
+            |_, this, (whence, offset): (Option<String>, Option<i64>)| async move {
+                let pos = parse_seek_from(whence, offset)?;
+                this.seek(pos).await
+            },
+        );
+
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+}
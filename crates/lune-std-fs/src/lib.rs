@@ -1,21 +1,43 @@
 #![allow(clippy::cargo_common_metadata)]
 
 use std::io::ErrorKind as IoErrorKind;
-use std::path::PathBuf;
 
 use bstr::{BString, ByteSlice};
+use console::Term;
+use dialoguer::{theme::ColorfulTheme, Confirm};
 use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
 use tokio::fs;
 
 use lune_utils::TableBuilder;
 
+mod chunks;
 mod copy;
+mod diff;
+mod file;
 mod metadata;
 mod options;
+mod permissions;
+mod root;
+mod temp;
+mod watch;
 
-use self::copy::copy;
+use self::chunks::FsChunkReader;
+use self::copy::{copy, move_path};
+use self::diff::create_diff;
+use self::file::{FsFile, FsOpenMode};
 use self::metadata::FsMetadata;
-use self::options::FsWriteOptions;
+use self::options::{FsInteractiveOptions, FsTempOptions, FsWatchOptions, FsWriteOptions};
+use self::permissions::{chmod, chown, FsMode};
+use self::root::{resolve_path, resolve_symlink_path, root_dir};
+use self::temp::{create_temp_dir, create_temp_file};
+use self::watch::watch;
+
+pub use self::root::set_fs_root;
+
+// Setting this environment variable writes changes without
+// confirming them, for running scripts in CI and other automation
+const ASSUME_YES_ENV_VAR: &str = "LUNE_YES";
 
 /**
     Creates the `fs` standard library module.
@@ -27,28 +49,47 @@ use self::options::FsWriteOptions;
 pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_async_function("readFile", fs_read_file)?
+        .with_async_function("readFileChunks", fs_read_file_chunks)?
         .with_async_function("readDir", fs_read_dir)?
         .with_async_function("writeFile", fs_write_file)?
+        .with_async_function("writeFileInteractive", fs_write_file_interactive)?
         .with_async_function("writeDir", fs_write_dir)?
         .with_async_function("removeFile", fs_remove_file)?
         .with_async_function("removeDir", fs_remove_dir)?
         .with_async_function("metadata", fs_metadata)?
         .with_async_function("isFile", fs_is_file)?
         .with_async_function("isDir", fs_is_dir)?
+        .with_async_function("chmod", fs_chmod)?
+        .with_async_function("chown", fs_chown)?
         .with_async_function("move", fs_move)?
         .with_async_function("copy", fs_copy)?
+        .with_async_function("open", fs_open)?
+        .with_function("tempDir", fs_temp_dir)?
+        .with_function("tempFile", fs_temp_file)?
+        .with_function("watch", fs_watch)?
         .build_readonly()
 }
 
 async fn fs_read_file(lua: &Lua, path: String) -> LuaResult<LuaString> {
-    let bytes = fs::read(&path).await.into_lua_err()?;
+    let bytes = fs::read(resolve_path(lua, &path)?).await.into_lua_err()?;
 
     lua.create_string(bytes)
 }
 
-async fn fs_read_dir(_: &Lua, path: String) -> LuaResult<Vec<String>> {
+async fn fs_read_file_chunks(
+    lua: &Lua,
+    (path, chunk_size): (String, Option<usize>),
+) -> LuaResult<LuaTable> {
+    FsChunkReader::open(lua, path, chunk_size)
+        .await?
+        .into_lua_table(lua)
+}
+
+async fn fs_read_dir(lua: &Lua, path: String) -> LuaResult<Vec<String>> {
     let mut dir_strings = Vec::new();
-    let mut dir = fs::read_dir(&path).await.into_lua_err()?;
+    let mut dir = fs::read_dir(resolve_path(lua, &path)?)
+        .await
+        .into_lua_err()?;
     while let Some(dir_entry) = dir.next_entry().await.into_lua_err()? {
         if let Some(dir_name_str) = dir_entry.file_name().to_str() {
             dir_strings.push(dir_name_str.to_owned());
@@ -62,65 +103,161 @@ async fn fs_read_dir(_: &Lua, path: String) -> LuaResult<Vec<String>> {
     Ok(dir_strings)
 }
 
-async fn fs_write_file(_: &Lua, (path, contents): (String, BString)) -> LuaResult<()> {
-    fs::write(&path, contents.as_bytes()).await.into_lua_err()
+async fn fs_write_file(lua: &Lua, (path, contents): (String, BString)) -> LuaResult<()> {
+    fs::write(resolve_path(lua, &path)?, contents.as_bytes())
+        .await
+        .into_lua_err()
+}
+
+async fn fs_write_file_interactive(
+    lua: &Lua,
+    (path, contents, options): (String, BString, FsInteractiveOptions),
+) -> LuaResult<bool> {
+    let real_path = resolve_path(lua, &path)?;
+    let existing = match fs::read(&real_path).await {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == IoErrorKind::NotFound => None,
+        Err(e) => return Err(e.into_lua_err()),
+    };
+    let Some(diff) = create_diff(&path, existing.as_deref(), contents.as_bytes()) else {
+        return Ok(false);
+    };
+
+    let stderr = Term::stderr();
+    stderr.write_str(&diff)?;
+
+    let confirmed = if options.yes || env_is_truthy(ASSUME_YES_ENV_VAR) {
+        true
+    } else if stderr.is_term() {
+        let prompt = format!("Write changes to '{path}'?");
+        lua.spawn_blocking(move || {
+            Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(prompt)
+                .default(false)
+                .interact()
+        })
+        .await
+        .into_lua_err()?
+    } else {
+        return Err(LuaError::RuntimeError(format!(
+            "Can not confirm changes to '{path}' without a terminal\nSet the {ASSUME_YES_ENV_VAR} environment variable to write changes without confirming"
+        )));
+    };
+
+    if confirmed {
+        fs::write(&real_path, contents.as_bytes())
+            .await
+            .into_lua_err()?;
+    }
+    Ok(confirmed)
+}
+
+fn env_is_truthy(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| {
+        let value = value.trim();
+        !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false")
+    })
 }
 
-async fn fs_write_dir(_: &Lua, path: String) -> LuaResult<()> {
-    fs::create_dir_all(&path).await.into_lua_err()
+async fn fs_write_dir(lua: &Lua, path: String) -> LuaResult<()> {
+    fs::create_dir_all(resolve_path(lua, &path)?)
+        .await
+        .into_lua_err()
 }
 
-async fn fs_remove_file(_: &Lua, path: String) -> LuaResult<()> {
-    fs::remove_file(&path).await.into_lua_err()
+async fn fs_remove_file(lua: &Lua, path: String) -> LuaResult<()> {
+    fs::remove_file(resolve_symlink_path(lua, &path)?)
+        .await
+        .into_lua_err()
 }
 
-async fn fs_remove_dir(_: &Lua, path: String) -> LuaResult<()> {
-    fs::remove_dir_all(&path).await.into_lua_err()
+async fn fs_remove_dir(lua: &Lua, path: String) -> LuaResult<()> {
+    fs::remove_dir_all(resolve_path(lua, &path)?)
+        .await
+        .into_lua_err()
 }
 
-async fn fs_metadata(_: &Lua, path: String) -> LuaResult<FsMetadata> {
-    match fs::metadata(path).await {
+async fn fs_metadata(lua: &Lua, path: String) -> LuaResult<FsMetadata> {
+    // NOTE: Symlinks are not followed here, otherwise
+    // the metadata kind could never be a symlink
+    match fs::symlink_metadata(resolve_symlink_path(lua, &path)?).await {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(FsMetadata::not_found()),
         Ok(meta) => Ok(FsMetadata::from(meta)),
         Err(e) => Err(e.into()),
     }
 }
 
-async fn fs_is_file(_: &Lua, path: String) -> LuaResult<bool> {
-    match fs::metadata(path).await {
+async fn fs_is_file(lua: &Lua, path: String) -> LuaResult<bool> {
+    match fs::metadata(resolve_path(lua, &path)?).await {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(false),
         Ok(meta) => Ok(meta.is_file()),
         Err(e) => Err(e.into()),
     }
 }
 
-async fn fs_is_dir(_: &Lua, path: String) -> LuaResult<bool> {
-    match fs::metadata(path).await {
+async fn fs_is_dir(lua: &Lua, path: String) -> LuaResult<bool> {
+    match fs::metadata(resolve_path(lua, &path)?).await {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(false),
         Ok(meta) => Ok(meta.is_dir()),
         Err(e) => Err(e.into()),
     }
 }
 
-async fn fs_move(_: &Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
-    let path_from = PathBuf::from(from);
-    if !path_from.exists() {
-        return Err(LuaError::RuntimeError(format!(
-            "No file or directory exists at the path '{}'",
-            path_from.display()
-        )));
-    }
-    let path_to = PathBuf::from(to);
-    if !options.overwrite && path_to.exists() {
-        return Err(LuaError::RuntimeError(format!(
-            "A file or directory already exists at the path '{}'",
-            path_to.display()
-        )));
-    }
-    fs::rename(path_from, path_to).await.into_lua_err()?;
-    Ok(())
+async fn fs_chmod(lua: &Lua, (path, mode): (String, FsMode)) -> LuaResult<()> {
+    chmod(resolve_path(lua, &path)?, mode).await
+}
+
+async fn fs_chown(
+    lua: &Lua,
+    (path, uid, gid): (String, Option<u32>, Option<u32>),
+) -> LuaResult<()> {
+    chown(resolve_path(lua, &path)?, uid, gid)
+}
+
+async fn fs_move(
+    lua: &Lua,
+    (from, to, options): (String, String, FsWriteOptions),
+) -> LuaResult<()> {
+    move_path(
+        resolve_symlink_path(lua, &from)?,
+        resolve_path(lua, &to)?,
+        options,
+        root_dir(lua)?.as_deref(),
+    )
+    .await
+}
+
+async fn fs_copy(
+    lua: &Lua,
+    (from, to, options): (String, String, FsWriteOptions),
+) -> LuaResult<()> {
+    copy(
+        resolve_path(lua, &from)?,
+        resolve_path(lua, &to)?,
+        options,
+        root_dir(lua)?.as_deref(),
+    )
+    .await
+}
+
+async fn fs_open(lua: &Lua, (path, mode): (String, FsOpenMode)) -> LuaResult<LuaTable> {
+    FsFile::open(lua, path, mode).await?.into_lua_table(lua)
+}
+
+fn fs_temp_dir(lua: &Lua, (prefix, options): (Option<String>, FsTempOptions)) -> LuaResult<String> {
+    create_temp_dir(lua, prefix, options)
+}
+
+fn fs_temp_file(
+    lua: &Lua,
+    (prefix, options): (Option<String>, FsTempOptions),
+) -> LuaResult<String> {
+    create_temp_file(lua, prefix, options)
 }
 
-async fn fs_copy(_: &Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
-    copy(from, to, options).await
+fn fs_watch<'lua>(
+    lua: &'lua Lua,
+    (path, callback, options): (String, LuaFunction<'lua>, FsWatchOptions),
+) -> LuaResult<LuaTable<'lua>> {
+    watch(lua, path, callback, options)
 }
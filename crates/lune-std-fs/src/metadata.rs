@@ -74,20 +74,30 @@ impl<'lua> IntoLua<'lua> for FsMetadataKind {
 #[derive(Debug, Clone)]
 pub struct FsPermissions {
     pub(crate) read_only: bool,
+    pub(crate) mode: Option<u32>,
 }
 
 impl From<StdPermissions> for FsPermissions {
     fn from(value: StdPermissions) -> Self {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(value.mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
         Self {
             read_only: value.readonly(),
+            mode,
         }
     }
 }
 
 impl<'lua> IntoLua<'lua> for FsPermissions {
     fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
-        let tab = lua.create_table_with_capacity(0, 1)?;
+        let tab = lua.create_table_with_capacity(0, 2)?;
         tab.set("readOnly", self.read_only)?;
+        tab.set("mode", self.mode)?;
         tab.set_readonly(true);
         Ok(LuaValue::Table(tab))
     }
@@ -97,6 +107,7 @@ impl<'lua> IntoLua<'lua> for FsPermissions {
 pub struct FsMetadata {
     pub(crate) kind: FsMetadataKind,
     pub(crate) exists: bool,
+    pub(crate) size: Option<u64>,
     pub(crate) created_at: Option<DateTime>,
     pub(crate) modified_at: Option<DateTime>,
     pub(crate) accessed_at: Option<DateTime>,
@@ -108,6 +119,7 @@ impl FsMetadata {
         Self {
             kind: FsMetadataKind::None,
             exists: false,
+            size: None,
             created_at: None,
             modified_at: None,
             accessed_at: None,
@@ -118,9 +130,10 @@ impl FsMetadata {
 
 impl<'lua> IntoLua<'lua> for FsMetadata {
     fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
-        let tab = lua.create_table_with_capacity(0, 6)?;
+        let tab = lua.create_table_with_capacity(0, 7)?;
         tab.set("kind", self.kind)?;
         tab.set("exists", self.exists)?;
+        tab.set("size", self.size)?;
         tab.set("createdAt", self.created_at)?;
         tab.set("modifiedAt", self.modified_at)?;
         tab.set("accessedAt", self.accessed_at)?;
@@ -135,6 +148,7 @@ impl From<StdMetadata> for FsMetadata {
         Self {
             kind: value.file_type().into(),
             exists: true,
+            size: Some(value.len()),
             created_at: system_time_to_timestamp(value.created()),
             modified_at: system_time_to_timestamp(value.modified()),
             accessed_at: system_time_to_timestamp(value.accessed()),
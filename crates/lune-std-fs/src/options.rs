@@ -1,19 +1,28 @@
 use mlua::prelude::*;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct FsWriteOptions {
     pub(crate) overwrite: bool,
+    pub(crate) merge: bool,
+    pub(crate) preserve_permissions: bool,
 }
 
 impl<'lua> FromLua<'lua> for FsWriteOptions {
     fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
         Ok(match value {
-            LuaValue::Nil => Self { overwrite: false },
-            LuaValue::Boolean(b) => Self { overwrite: b },
+            LuaValue::Nil => Self::default(),
+            LuaValue::Boolean(b) => Self {
+                overwrite: b,
+                ..Self::default()
+            },
             LuaValue::Table(t) => {
                 let overwrite: Option<bool> = t.get("overwrite")?;
+                let merge: Option<bool> = t.get("merge")?;
+                let preserve_permissions: Option<bool> = t.get("preservePermissions")?;
                 Self {
                     overwrite: overwrite.unwrap_or(false),
+                    merge: merge.unwrap_or(false),
+                    preserve_permissions: preserve_permissions.unwrap_or(false),
                 }
             }
             _ => {
@@ -29,3 +38,91 @@ impl<'lua> FromLua<'lua> for FsWriteOptions {
         })
     }
 }
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsWatchOptions {
+    pub(crate) recursive: bool,
+}
+
+impl<'lua> FromLua<'lua> for FsWatchOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self { recursive: false },
+            LuaValue::Boolean(b) => Self { recursive: b },
+            LuaValue::Table(t) => {
+                let recursive: Option<bool> = t.get("recursive")?;
+                Self {
+                    recursive: recursive.unwrap_or(false),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWatchOptions",
+                    message: Some(format!(
+                        "Invalid watch options - expected boolean or table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FsInteractiveOptions {
+    pub(crate) yes: bool,
+}
+
+impl<'lua> FromLua<'lua> for FsInteractiveOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self { yes: false },
+            LuaValue::Table(t) => {
+                let yes: Option<bool> = t.get("yes")?;
+                Self {
+                    yes: yes.unwrap_or(false),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsInteractiveOptions",
+                    message: Some(format!(
+                        "Invalid interactive write options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsTempOptions {
+    pub(crate) delete_on_exit: bool,
+}
+
+impl<'lua> FromLua<'lua> for FsTempOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::default(),
+            LuaValue::Table(t) => {
+                let delete_on_exit: Option<bool> = t.get("deleteOnExit")?;
+                Self {
+                    delete_on_exit: delete_on_exit.unwrap_or(false),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsTempOptions",
+                    message: Some(format!(
+                        "Invalid temporary options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
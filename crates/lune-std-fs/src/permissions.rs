@@ -0,0 +1,171 @@
+use std::path::Path;
+
+use mlua::prelude::*;
+use tokio::fs;
+
+// Permission bits that exist in modes, which are the read, write
+// and execute bits for all users, plus setuid, setgid and sticky
+const MODE_MASK: u32 = 0o7777;
+
+/**
+    A change to make to the permissions of a file or directory.
+
+    Modes are either absolute, such as `755`, or symbolic, such as `u+x,go-w`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsMode {
+    Absolute(u32),
+    Symbolic(Vec<FsModeClause>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsModeClause {
+    who: u32,
+    op: char,
+    bits: u32,
+}
+
+impl FsMode {
+    /**
+        Applies this mode to the current mode of a file or directory, giving its new mode.
+    */
+    pub fn apply(&self, current: u32) -> u32 {
+        match self {
+            Self::Absolute(mode) => *mode,
+            Self::Symbolic(clauses) => clauses.iter().fold(current, |mode, clause| {
+                let bits = clause.bits & clause.who;
+                match clause.op {
+                    '+' => mode | bits,
+                    '-' => mode & !bits,
+                    _ => (mode & !clause.who) | bits,
+                }
+            }),
+        }
+    }
+
+    fn parse_symbolic(s: &str) -> Option<Self> {
+        let mut clauses = Vec::new();
+        for clause in s.split(',') {
+            let op_index = clause.find(['+', '-', '='])?;
+            let (who, rest) = clause.split_at(op_index);
+            let mut chars = rest.chars();
+            let op = chars.next()?;
+
+            // NOTE: Not giving any users is the same as giving all of them, we
+            // don't look at the umask here since it is not something that can
+            // be read without also changing it, and scripts want exact changes
+            let who = if who.is_empty() {
+                0o7777
+            } else {
+                who.chars().try_fold(0, |mask, c| {
+                    Some(
+                        mask | match c {
+                            'u' => 0o4700,
+                            'g' => 0o2070,
+                            'o' => 0o1007,
+                            'a' => 0o7777,
+                            _ => return None,
+                        },
+                    )
+                })?
+            };
+            let bits = chars.try_fold(0, |bits, c| {
+                Some(
+                    bits | match c {
+                        'r' => 0o444,
+                        'w' => 0o222,
+                        'x' => 0o111,
+                        's' => 0o6000,
+                        't' => 0o1000,
+                        _ => return None,
+                    },
+                )
+            })?;
+
+            clauses.push(FsModeClause { who, op, bits });
+        }
+        Some(Self::Symbolic(clauses))
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsMode {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let mode = match &value {
+            LuaValue::Integer(_) | LuaValue::Number(_) => {
+                u32::from_lua(value.clone(), lua).ok().map(Self::Absolute)
+            }
+            LuaValue::String(s) => {
+                let s = s.to_str()?.trim();
+                if !s.is_empty() && s.chars().all(|c| c.is_digit(8)) {
+                    u32::from_str_radix(s, 8).ok().map(Self::Absolute)
+                } else {
+                    Self::parse_symbolic(s)
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsMode",
+                    message: Some(format!(
+                        "Invalid mode - expected number or string, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        match mode {
+            Some(Self::Absolute(mode)) if mode > MODE_MASK => None,
+            mode => mode,
+        }
+        .ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "Invalid mode '{}' - expected an octal string such as '755', or a symbolic mode such as 'u+x'",
+                value.to_string().unwrap_or_default()
+            ))
+        })
+    }
+}
+
+/**
+    Changes the permissions of the file or directory at the given path.
+
+    On Windows, files only have a read-only attribute, which is set if the new mode
+    has no write permissions for any users, and all other permissions are ignored.
+*/
+pub async fn chmod(path: impl AsRef<Path>, mode: FsMode) -> LuaResult<()> {
+    let path = path.as_ref();
+    let mut permissions = fs::metadata(path).await.into_lua_err()?.permissions();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let current = permissions.mode() & MODE_MASK;
+        permissions.set_mode(mode.apply(current));
+    }
+    #[cfg(not(unix))]
+    {
+        let current = if permissions.readonly() { 0o444 } else { 0o666 };
+        permissions.set_readonly(mode.apply(current) & 0o222 == 0);
+    }
+
+    fs::set_permissions(path, permissions).await.into_lua_err()
+}
+
+/**
+    Changes the owning user and / or group of the file or directory at the given path.
+
+    Ids that are not given are left unchanged. Errors on platforms without ownership.
+*/
+pub fn chown(path: impl AsRef<Path>, uid: Option<u32>, gid: Option<u32>) -> LuaResult<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::chown(path, uid, gid).into_lua_err()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, uid, gid);
+        Err(LuaError::runtime(
+            "Changing ownership of files is not supported on this platform",
+        ))
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::{Component, Path, PathBuf};
+
+use mlua::prelude::*;
+
+struct FsRoot(PathBuf);
+
+/**
+    Sets a directory that all paths given to the `fs` library are relative to.
+
+    Once set, the `fs` library behaves as if the given directory was the root
+    of the filesystem, and also the current working directory - both `/file.txt`
+    and `file.txt` refer to `file.txt` inside the directory, and paths such as
+    `../file.txt` can not be used to reach anything outside of it, not even
+    through symlinks. Paths returned by the `fs` library are also relative to it.
+
+    By default, no root is set, and paths are used exactly as they are given.
+*/
+pub fn set_fs_root(lua: &Lua, root: impl Into<PathBuf>) {
+    lua.set_app_data(FsRoot(root.into()));
+}
+
+/**
+    Gets the canonical path to the filesystem root, if one is set.
+
+    # Errors
+
+    Errors if a filesystem root is set, but it can not be accessed.
+*/
+pub(crate) fn root_dir(lua: &Lua) -> LuaResult<Option<PathBuf>> {
+    let Some(root) = lua.app_data_ref::<FsRoot>() else {
+        return Ok(None);
+    };
+    // NOTE: The root is canonicalized on every use since it may not exist yet
+    // when it is set, and so that symlinks inside of it can be compared against it
+    let canonical = std::fs::canonicalize(&root.0).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to access filesystem root '{}' - {e}",
+            root.0.display()
+        ))
+    })?;
+    Ok(Some(canonical))
+}
+
+/**
+    Resolves a path given to the `fs` library into the real path that it refers to.
+
+    # Errors
+
+    Errors if a filesystem root is set, and the
+    path refers to something outside of the root.
+*/
+pub(crate) fn resolve_path(lua: &Lua, path: &str) -> LuaResult<PathBuf> {
+    resolve(lua, path, true)
+}
+
+/**
+    Resolves a path given to the `fs` library into the real path that it refers to,
+    for operations that do not follow a symlink at the path, such as removing it.
+
+    # Errors
+
+    Errors if a filesystem root is set, and the
+    path refers to something outside of the root.
+*/
+pub(crate) fn resolve_symlink_path(lua: &Lua, path: &str) -> LuaResult<PathBuf> {
+    resolve(lua, path, false)
+}
+
+fn resolve(lua: &Lua, path: &str, follow_symlink: bool) -> LuaResult<PathBuf> {
+    let Some(root) = root_dir(lua)? else {
+        return Ok(PathBuf::from(path));
+    };
+
+    // Resolve the path without touching the filesystem first, any parent
+    // components stop at the root, the same way that they would at "/"
+    let mut resolved = root.clone();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::ParentDir => {
+                if resolved != root {
+                    resolved.pop();
+                }
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+
+    // Symlinks may still point outside of the root, so we also make sure
+    // that the deepest part of the path that exists is inside of the root,
+    // symlinks that point to nothing are never followed since writing
+    // to one would create whatever it points to, wherever that is
+    let checked = match resolved.parent() {
+        Some(parent) if !follow_symlink && resolved != root => parent,
+        _ => resolved.as_path(),
+    };
+    if is_inside_root(&root, checked) {
+        Ok(resolved)
+    } else {
+        Err(LuaError::RuntimeError(format!(
+            "Path '{path}' is outside of the filesystem root"
+        )))
+    }
+}
+
+/**
+    Checks if the given real path, which does not need to exist yet, is inside of
+    the given canonical root, by resolving the deepest part of it that does exist.
+
+    Any symlink in the path is followed, including one at the path itself.
+*/
+pub(crate) fn is_inside_root(root: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .find(|ancestor| std::fs::symlink_metadata(ancestor).is_ok())
+        .and_then(|ancestor| std::fs::canonicalize(ancestor).ok())
+        .is_some_and(|existing| existing.starts_with(root))
+}
+
+/**
+    Turns a real path back into the path that the `fs` library should return
+    for it, which is relative to the filesystem root, if one is set.
+*/
+pub(crate) fn virtual_path(lua: &Lua, path: &Path) -> String {
+    let Ok(Some(root)) = root_dir(lua) else {
+        return path.to_string_lossy().to_string();
+    };
+    let Ok(relative) = path.strip_prefix(&root) else {
+        return path.to_string_lossy().to_string();
+    };
+    let mut virtual_path = String::new();
+    for component in relative.components() {
+        virtual_path.push('/');
+        virtual_path.push_str(&component.as_os_str().to_string_lossy());
+    }
+    if virtual_path.is_empty() {
+        virtual_path.push('/');
+    }
+    virtual_path
+}
+
+/**
+    Gets the directory that temporary files and directories should be created in.
+
+    This is the `/tmp` directory inside of the filesystem root if one is set,
+    since the temporary directory of the system would be outside of it.
+
+    # Errors
+
+    Errors if a filesystem root is set, and the temporary directory inside of it can not be created.
+*/
+pub(crate) fn temp_dir(lua: &Lua) -> LuaResult<PathBuf> {
+    let Some(root) = root_dir(lua)? else {
+        return Ok(std::env::temp_dir());
+    };
+    let dir = resolve_path(lua, "/tmp")?;
+    std::fs::create_dir_all(&dir).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to create temporary directory inside of filesystem root '{}' - {e}",
+            root.display()
+        ))
+    })?;
+    Ok(dir)
+}
@@ -0,0 +1,82 @@
+use mlua::prelude::*;
+use tempfile::{Builder, TempDir, TempPath};
+
+use crate::options::FsTempOptions;
+use crate::root::{temp_dir, virtual_path};
+
+const DEFAULT_PREFIX: &str = "lune-";
+
+/**
+    Temporary files and directories that should be deleted on exit.
+
+    These are stored in the Lua app data and deleted when dropped, which
+    happens once the Lua state is closed, after the scheduler has shut down.
+*/
+#[derive(Default)]
+struct TempPaths {
+    dirs: Vec<TempDir>,
+    files: Vec<TempPath>,
+}
+
+fn with_temp_paths(lua: &Lua, f: impl FnOnce(&mut TempPaths)) {
+    if lua.app_data_ref::<TempPaths>().is_none() {
+        lua.set_app_data(TempPaths::default());
+    }
+    let mut paths = lua
+        .app_data_mut::<TempPaths>()
+        .expect("Missing temporary paths");
+    f(&mut paths);
+}
+
+fn builder(prefix: Option<&str>) -> Builder<'_, 'static> {
+    let mut builder = Builder::new();
+    builder.prefix(prefix.unwrap_or(DEFAULT_PREFIX));
+    builder
+}
+
+/**
+    Creates a new, uniquely named, directory inside of the temporary directory.
+
+    The temporary directory is inside of the filesystem root, if one is set.
+*/
+pub fn create_temp_dir(
+    lua: &Lua,
+    prefix: Option<String>,
+    options: FsTempOptions,
+) -> LuaResult<String> {
+    let dir = builder(prefix.as_deref())
+        .tempdir_in(temp_dir(lua)?)
+        .map_err(|e| {
+            LuaError::RuntimeError(format!("Failed to create temporary directory - {e}"))
+        })?;
+    let path = virtual_path(lua, dir.path());
+    if options.delete_on_exit {
+        with_temp_paths(lua, |paths| paths.dirs.push(dir));
+    } else {
+        let _ = dir.into_path();
+    }
+    Ok(path)
+}
+
+/**
+    Creates a new, uniquely named, empty file inside of the temporary directory.
+
+    The temporary directory is inside of the filesystem root, if one is set.
+*/
+pub fn create_temp_file(
+    lua: &Lua,
+    prefix: Option<String>,
+    options: FsTempOptions,
+) -> LuaResult<String> {
+    let file = builder(prefix.as_deref())
+        .tempfile_in(temp_dir(lua)?)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to create temporary file - {e}")))?
+        .into_temp_path();
+    let path = virtual_path(lua, &file);
+    if options.delete_on_exit {
+        with_temp_paths(lua, |paths| paths.files.push(file));
+    } else {
+        file.keep().into_lua_err()?;
+    }
+    Ok(path)
+}
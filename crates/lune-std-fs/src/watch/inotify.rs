@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    io,
+    os::fd::{AsFd, AsRawFd, RawFd},
+    path::{Path, PathBuf},
+};
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use tokio::io::unix::AsyncFd;
+
+use super::{list_dir, WatchEvent, WatchEventKind};
+
+const WATCH_FLAGS: AddWatchFlags = AddWatchFlags::IN_CREATE
+    .union(AddWatchFlags::IN_MODIFY)
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_DELETE_SELF)
+    .union(AddWatchFlags::IN_MOVED_FROM)
+    .union(AddWatchFlags::IN_MOVED_TO);
+
+struct InotifyFd(Inotify);
+
+impl AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+/**
+    A watcher using native inotify file system notifications.
+
+    Inotify only watches single directories, so recursive watchers add a
+    watch for every directory inside of the root, including new ones.
+*/
+pub struct Watcher {
+    fd: AsyncFd<InotifyFd>,
+    root: PathBuf,
+    recursive: bool,
+    watches: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new(root: PathBuf, recursive: bool) -> io::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        let mut watcher = Self {
+            fd: AsyncFd::new(InotifyFd(inotify))?,
+            root: root.clone(),
+            recursive,
+            watches: HashMap::new(),
+        };
+        watcher.add_watch(&root)?;
+        if recursive && root.is_dir() {
+            watcher.add_nested_watches(&root, &mut Vec::new());
+        }
+        Ok(watcher)
+    }
+
+    pub async fn next_events(&mut self) -> io::Result<Vec<WatchEvent>> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match guard.try_io(|fd| fd.get_ref().0.read_events().map_err(io::Error::from)) {
+                Ok(Ok(raw_events)) => {
+                    let events = self.handle_events(raw_events);
+                    if !events.is_empty() {
+                        return Ok(events);
+                    }
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_would_block) => {}
+            }
+        }
+    }
+
+    fn add_watch(&mut self, path: &Path) -> io::Result<()> {
+        let wd = self.fd.get_ref().0.add_watch(path, WATCH_FLAGS)?;
+        self.watches.insert(wd, path.to_path_buf());
+        Ok(())
+    }
+
+    // Adds watches for all directories inside of the given one, and creates events for
+    // all entries found, since these may have been created before the watches existed
+    fn add_nested_watches(&mut self, dir: &Path, events: &mut Vec<WatchEvent>) {
+        let mut entries = Vec::new();
+        list_dir(dir, true, &mut entries);
+        for (path, meta) in entries {
+            if meta.is_dir() {
+                self.add_watch(&path).ok();
+            }
+            events.push(WatchEvent::new(WatchEventKind::Create, path));
+        }
+    }
+
+    fn handle_events(&mut self, raw_events: Vec<InotifyEvent>) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        let mut moved_from: HashMap<u32, PathBuf> = HashMap::new();
+        for raw in raw_events {
+            if raw.mask.contains(AddWatchFlags::IN_IGNORED) {
+                self.watches.remove(&raw.wd);
+                continue;
+            }
+            let Some(base) = self.watches.get(&raw.wd) else {
+                continue;
+            };
+            let path = match &raw.name {
+                Some(name) => base.join(name),
+                None => base.clone(),
+            };
+            let is_dir = raw.mask.contains(AddWatchFlags::IN_ISDIR);
+
+            if raw.mask.contains(AddWatchFlags::IN_CREATE) {
+                events.push(WatchEvent::new(WatchEventKind::Create, path.clone()));
+                if self.recursive && is_dir {
+                    self.add_watch(&path).ok();
+                    self.add_nested_watches(&path, &mut events);
+                }
+            } else if raw.mask.contains(AddWatchFlags::IN_MODIFY) {
+                events.push(WatchEvent::new(WatchEventKind::Modify, path));
+            } else if raw.mask.contains(AddWatchFlags::IN_DELETE) {
+                events.push(WatchEvent::new(WatchEventKind::Remove, path));
+            } else if raw.mask.contains(AddWatchFlags::IN_DELETE_SELF) {
+                // NOTE: Directories inside of the root are already
+                // reported as removed by their parent directory
+                if path == self.root {
+                    events.push(WatchEvent::new(WatchEventKind::Remove, path));
+                }
+            } else if raw.mask.contains(AddWatchFlags::IN_MOVED_FROM) {
+                moved_from.insert(raw.cookie, path);
+            } else if raw.mask.contains(AddWatchFlags::IN_MOVED_TO) {
+                if let Some(from) = moved_from.remove(&raw.cookie) {
+                    if is_dir {
+                        self.rename_watches(&from, &path);
+                    }
+                    events.push(WatchEvent {
+                        kind: WatchEventKind::Rename,
+                        path,
+                        from: Some(from),
+                    });
+                } else {
+                    events.push(WatchEvent::new(WatchEventKind::Create, path.clone()));
+                    if self.recursive && is_dir {
+                        self.add_watch(&path).ok();
+                        self.add_nested_watches(&path, &mut events);
+                    }
+                }
+            }
+        }
+
+        // Anything that was moved without being moved to somewhere
+        // else that we are watching is no longer being watched
+        for from in moved_from.into_values() {
+            self.remove_watches(&from);
+            events.push(WatchEvent::new(WatchEventKind::Remove, from));
+        }
+
+        // NOTE: A single write is often reported as several modifications
+        events.dedup();
+        events
+    }
+
+    fn rename_watches(&mut self, from: &Path, to: &Path) {
+        for path in self.watches.values_mut() {
+            if path == from {
+                *path = to.to_path_buf();
+            } else if let Ok(rest) = path.strip_prefix(from) {
+                *path = to.join(rest);
+            }
+        }
+    }
+
+    fn remove_watches(&mut self, under: &Path) {
+        let inotify = &self.fd.get_ref().0;
+        self.watches.retain(|wd, path| {
+            if path.starts_with(under) {
+                inotify.rm_watch(*wd).ok();
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
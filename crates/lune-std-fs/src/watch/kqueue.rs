@@ -0,0 +1,269 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    fs::Metadata,
+    io, mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::{ffi::OsStrExt, fs::MetadataExt},
+    },
+    path::{Path, PathBuf},
+    ptr,
+};
+
+use tokio::io::unix::AsyncFd;
+
+use super::{list_dir, WatchEvent, WatchEventKind};
+
+const MAX_EVENTS: usize = 64;
+
+const VNODE_FLAGS: u32 = libc::NOTE_WRITE
+    | libc::NOTE_EXTEND
+    | libc::NOTE_DELETE
+    | libc::NOTE_RENAME
+    | libc::NOTE_REVOKE;
+
+struct Watch {
+    fd: OwnedFd,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+/**
+    A watcher using native kqueue file system notifications.
+
+    Kqueue only watches files and directories that are kept open, so every
+    one of those is opened, and a directory is listed again whenever it was
+    changed to find out which of its entries were created or removed.
+
+    Entries that were removed and created with the same inode were renamed.
+*/
+pub struct Watcher {
+    kqueue: AsyncFd<OwnedFd>,
+    root: PathBuf,
+    recursive: bool,
+    watches: HashMap<RawFd, Watch>,
+    // Inodes of all entries inside of the root, by their path
+    entries: BTreeMap<PathBuf, u64>,
+}
+
+impl Watcher {
+    pub fn new(root: PathBuf, recursive: bool) -> io::Result<Self> {
+        let meta = std::fs::metadata(&root)?;
+        // SAFETY: Creating a kqueue has no preconditions, and we
+        // take ownership of the returned descriptor, if any
+        let kqueue = unsafe {
+            let fd = libc::kqueue();
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            OwnedFd::from_raw_fd(fd)
+        };
+        let mut watcher = Self {
+            kqueue: AsyncFd::new(kqueue)?,
+            root: root.clone(),
+            recursive,
+            watches: HashMap::new(),
+            entries: BTreeMap::new(),
+        };
+        watcher.add_watch(&root, meta.is_dir())?;
+        if meta.is_dir() {
+            let mut entries = Vec::new();
+            list_dir(&root, recursive, &mut entries);
+            for (path, meta) in entries {
+                watcher.add_entry(path, &meta);
+            }
+        }
+        Ok(watcher)
+    }
+
+    pub async fn next_events(&mut self) -> io::Result<Vec<WatchEvent>> {
+        loop {
+            let mut guard = self.kqueue.readable().await?;
+            match guard.try_io(|kqueue| read_events(kqueue.get_ref())) {
+                Ok(Ok(raw_events)) => {
+                    let events = self.handle_events(&raw_events);
+                    if !events.is_empty() {
+                        return Ok(events);
+                    }
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_would_block) => {}
+            }
+        }
+    }
+
+    fn add_watch(&mut self, path: &Path, is_dir: bool) -> io::Result<()> {
+        let path_c = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: The path is a valid C string, and we take ownership of the
+        // returned descriptor, which is removed from the kqueue once closed
+        unsafe {
+            let fd = libc::open(path_c.as_ptr(), libc::O_EVTONLY | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+            let change = libc::kevent {
+                ident: fd.as_raw_fd() as libc::uintptr_t,
+                filter: libc::EVFILT_VNODE,
+                flags: libc::EV_ADD | libc::EV_CLEAR,
+                fflags: VNODE_FLAGS,
+                data: 0,
+                udata: ptr::null_mut(),
+            };
+            let kqueue = self.kqueue.get_ref().as_raw_fd();
+            let change = ptr::addr_of!(change);
+            if libc::kevent(kqueue, change, 1, ptr::null_mut(), 0, ptr::null()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let watch = Watch {
+                fd,
+                path: path.to_path_buf(),
+                is_dir,
+            };
+            self.watches.insert(watch.fd.as_raw_fd(), watch);
+        }
+        Ok(())
+    }
+
+    // NOTE: Directories are only watched when recursive, but files are always watched,
+    // since we would otherwise not know when files directly inside the root are modified
+    fn add_entry(&mut self, path: PathBuf, meta: &Metadata) {
+        if meta.is_file() || (self.recursive && meta.is_dir()) {
+            self.add_watch(&path, meta.is_dir()).ok();
+        }
+        self.entries.insert(path, meta.ino());
+    }
+
+    // Adds the given entry and, if recursive, all entries inside of it, creating
+    // events for those since these may have been created before being watched
+    fn add_entries(&mut self, path: PathBuf, meta: &Metadata, events: &mut Vec<WatchEvent>) {
+        let is_dir = meta.is_dir();
+        self.add_entry(path.clone(), meta);
+        if self.recursive && is_dir {
+            let mut entries = Vec::new();
+            list_dir(&path, true, &mut entries);
+            for (path, meta) in entries {
+                self.add_entry(path.clone(), &meta);
+                events.push(WatchEvent::new(WatchEventKind::Create, path));
+            }
+        }
+    }
+
+    fn remove_entries(&mut self, under: &Path) {
+        self.entries.retain(|path, _| !path.starts_with(under));
+        self.watches
+            .retain(|_, watch| !watch.path.starts_with(under));
+    }
+
+    fn handle_events(&mut self, raw_events: &[(RawFd, u32)]) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        let mut changed_dirs = Vec::new();
+        for &(fd, fflags) in raw_events {
+            let Some(watch) = self.watches.get(&fd) else {
+                continue;
+            };
+            let path = watch.path.clone();
+            // NOTE: Anything else that is removed or renamed
+            // is reported by the directory that it was inside of
+            if path == self.root
+                && fflags & (libc::NOTE_DELETE | libc::NOTE_RENAME | libc::NOTE_REVOKE) != 0
+            {
+                self.watches.remove(&fd);
+                events.push(WatchEvent::new(WatchEventKind::Remove, path));
+            } else if watch.is_dir {
+                if fflags & libc::NOTE_WRITE != 0 && !changed_dirs.contains(&path) {
+                    changed_dirs.push(path);
+                }
+            } else if fflags & (libc::NOTE_WRITE | libc::NOTE_EXTEND) != 0 {
+                events.push(WatchEvent::new(WatchEventKind::Modify, path));
+            }
+        }
+        self.handle_changed_dirs(&changed_dirs, &mut events);
+
+        // NOTE: A single write is often reported as several modifications
+        events.dedup();
+        events
+    }
+
+    fn handle_changed_dirs(&mut self, dirs: &[PathBuf], events: &mut Vec<WatchEvent>) {
+        let mut removed = Vec::new();
+        let mut created = Vec::new();
+        for dir in dirs {
+            let mut found = Vec::new();
+            list_dir(dir, false, &mut found);
+            for (path, ino) in &self.entries {
+                let exists = found
+                    .iter()
+                    .any(|(p, meta)| p == path && meta.ino() == *ino);
+                if path.parent() == Some(dir.as_path()) && !exists {
+                    removed.push((path.clone(), *ino));
+                }
+            }
+            for (path, meta) in found {
+                if self.entries.get(&path) != Some(&meta.ino()) {
+                    created.push((path, meta));
+                }
+            }
+        }
+        for (path, _) in &removed {
+            self.remove_entries(path);
+        }
+
+        let mut renamed = Vec::new();
+        created.retain(|(path, meta)| {
+            let Some(index) = removed.iter().position(|(_, ino)| *ino == meta.ino()) else {
+                return true;
+            };
+            let (from, _) = removed.swap_remove(index);
+            renamed.push((from, path.clone(), meta.clone()));
+            false
+        });
+
+        // NOTE: Removals go first, since a path may have been replaced by something
+        // else being renamed to it, which should be the last event for that path
+        for (path, _) in removed {
+            events.push(WatchEvent::new(WatchEventKind::Remove, path));
+        }
+        for (from, path, meta) in renamed {
+            events.push(WatchEvent {
+                kind: WatchEventKind::Rename,
+                path: path.clone(),
+                from: Some(from),
+            });
+            self.add_entries(path, &meta, &mut Vec::new());
+        }
+        for (path, meta) in created {
+            events.push(WatchEvent::new(WatchEventKind::Create, path.clone()));
+            self.add_entries(path, &meta, events);
+        }
+    }
+}
+
+fn read_events(kqueue: &OwnedFd) -> io::Result<Vec<(RawFd, u32)>> {
+    let timeout = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: The kernel writes at most as many events as we have room
+    // for, and we return right away instead of waiting for any events
+    unsafe {
+        let mut raw_events: [libc::kevent; MAX_EVENTS] = mem::zeroed();
+        let count = libc::kevent(
+            kqueue.as_raw_fd(),
+            ptr::null(),
+            0,
+            raw_events.as_mut_ptr(),
+            MAX_EVENTS as libc::c_int,
+            ptr::addr_of!(timeout),
+        );
+        match count {
+            count if count < 0 => Err(io::Error::last_os_error()),
+            0 => Err(io::ErrorKind::WouldBlock.into()),
+            count => Ok(raw_events[..count as usize]
+                .iter()
+                .map(|raw| (raw.ident as RawFd, raw.fflags))
+                .collect()),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+use tokio::sync::watch;
+
+use lune_utils::TableBuilder;
+
+use crate::options::FsWatchOptions;
+use crate::root::{resolve_path, virtual_path};
+
+#[cfg(target_os = "linux")]
+mod inotify;
+#[cfg(target_os = "linux")]
+use inotify::Watcher;
+
+#[cfg(target_os = "macos")]
+mod kqueue;
+#[cfg(target_os = "macos")]
+use kqueue::Watcher;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use windows::Watcher;
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod poll;
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+use poll::Watcher;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchEventKind {
+    Create,
+    Modify,
+    Remove,
+    // NOTE: Renames can not be detected when polling
+    #[cfg_attr(
+        not(any(target_os = "linux", target_os = "macos", windows)),
+        allow(dead_code)
+    )]
+    Rename,
+}
+
+impl WatchEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Remove => "remove",
+            Self::Rename => "rename",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub kind: WatchEventKind,
+    pub path: PathBuf,
+    pub from: Option<PathBuf>,
+}
+
+impl WatchEvent {
+    fn new(kind: WatchEventKind, path: PathBuf) -> Self {
+        Self {
+            kind,
+            path,
+            from: None,
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for WatchEvent {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let path_to_string = |path: &Path| virtual_path(lua, path);
+        TableBuilder::new(lua)?
+            .with_value("kind", self.kind.as_str())?
+            .with_value("path", path_to_string(&self.path))?
+            .with_value("from", self.from.as_deref().map(path_to_string))?
+            .build_readonly()?
+            .into_lua(lua)
+    }
+}
+
+/**
+    Starts watching the given path, calling the callback in a new
+    Lua thread for every event until the returned handle is stopped.
+*/
+pub fn watch<'lua>(
+    lua: &'lua Lua,
+    path: String,
+    callback: LuaFunction<'lua>,
+    options: FsWatchOptions,
+) -> LuaResult<LuaTable<'lua>> {
+    let mut watcher = Watcher::new(resolve_path(lua, &path)?, options.recursive)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to watch path '{path}' - {e}")))?;
+
+    let lua_inner: Rc<Lua> = lua
+        .app_data_ref::<Weak<Lua>>()
+        .expect("Missing weak lua ref")
+        .upgrade()
+        .expect("Lua was dropped unexpectedly");
+    let callback_key = lua.create_registry_value(callback)?;
+
+    let (stop_tx, mut stop_rx) = watch::channel(false);
+    lua.spawn_local(async move {
+        loop {
+            // NOTE: If the handle was garbage collected without being stopped, the
+            // sender is dropped and we keep watching forever, same as with net.serve
+            let stopped = async {
+                if stop_rx.wait_for(|stopped| *stopped).await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            };
+            let events = tokio::select! {
+                biased;
+                () = stopped => break,
+                events = watcher.next_events() => events,
+            };
+            // NOTE: Errors are thrown in a new Lua thread, so that they
+            // get reported the same way as errors in the callback would
+            let events = match events {
+                Ok(events) => events,
+                Err(e) => {
+                    let message = format!("Stopped watching path '{path}' - {e}");
+                    if let Ok(error) = lua_inner.globals().get::<_, LuaFunction>("error") {
+                        lua_inner.push_thread_back(error, message).ok();
+                    }
+                    break;
+                }
+            };
+            let callback = lua_inner
+                .registry_value::<LuaFunction>(&callback_key)
+                .expect("Missing watch callback");
+            for event in events {
+                lua_inner.push_thread_back(callback.clone(), event).ok();
+            }
+        }
+        lua_inner.remove_registry_value(callback_key).ok();
+    });
+
+    TableBuilder::new(lua)?
+        .with_function("stop", move |_, (): ()| {
+            stop_tx.send_replace(true);
+            Ok(())
+        })?
+        .build_readonly()
+}
+
+/**
+    Lists the given directory and, if recursive, all directories inside of it.
+
+    Entries that can not be read, such as ones that were removed while
+    listing, are skipped - the caller will receive an event for those.
+*/
+fn list_dir(dir: &Path, recursive: bool, entries: &mut Vec<(PathBuf, std::fs::Metadata)>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let is_dir = meta.is_dir();
+        entries.push((path.clone(), meta));
+        if recursive && is_dir {
+            list_dir(&path, recursive, entries);
+        }
+    }
+}
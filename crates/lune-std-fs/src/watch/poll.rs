@@ -0,0 +1,94 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tokio::time::sleep;
+
+use super::{list_dir, WatchEvent, WatchEventKind};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/**
+    A watcher that polls the file system for changes, for platforms
+    where native file system notifications are not yet supported.
+
+    Renames can not be detected, and are reported as
+    the old path being removed and the new one created.
+*/
+pub struct Watcher {
+    root: PathBuf,
+    recursive: bool,
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+impl Watcher {
+    pub fn new(root: PathBuf, recursive: bool) -> io::Result<Self> {
+        // NOTE: Make sure that the path exists, same as native watchers
+        std::fs::symlink_metadata(&root)?;
+        let entries = snapshot(&root, recursive);
+        Ok(Self {
+            root,
+            recursive,
+            entries,
+        })
+    }
+
+    pub async fn next_events(&mut self) -> io::Result<Vec<WatchEvent>> {
+        loop {
+            sleep(POLL_INTERVAL).await;
+
+            let entries = snapshot(&self.root, self.recursive);
+            let mut events = Vec::new();
+            for (path, entry) in &entries {
+                match self.entries.get(path) {
+                    None => events.push(WatchEvent::new(WatchEventKind::Create, path.clone())),
+                    Some(old) if !entry.is_dir && old != entry => {
+                        events.push(WatchEvent::new(WatchEventKind::Modify, path.clone()));
+                    }
+                    Some(_) => {}
+                }
+            }
+            for path in self.entries.keys() {
+                if !entries.contains_key(path) {
+                    events.push(WatchEvent::new(WatchEventKind::Remove, path.clone()));
+                }
+            }
+            self.entries = entries;
+
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+}
+
+fn snapshot(root: &Path, recursive: bool) -> BTreeMap<PathBuf, Entry> {
+    let mut found = Vec::new();
+    if let Ok(meta) = std::fs::symlink_metadata(root) {
+        if meta.is_dir() {
+            list_dir(root, recursive, &mut found);
+        }
+        found.push((root.to_path_buf(), meta));
+    }
+    found
+        .into_iter()
+        .map(|(path, meta)| {
+            let entry = Entry {
+                is_dir: meta.is_dir(),
+                len: meta.len(),
+                modified: meta.modified().ok(),
+            };
+            (path, entry)
+        })
+        .collect()
+}
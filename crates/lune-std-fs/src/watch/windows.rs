@@ -0,0 +1,308 @@
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io, mem,
+    os::windows::{
+        ffi::OsStringExt,
+        fs::OpenOptionsExt,
+        io::{AsRawHandle, FromRawHandle, OwnedHandle},
+    },
+    path::{Path, PathBuf},
+    ptr, slice,
+    sync::Arc,
+    thread,
+};
+
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use windows_sys::Win32::{
+    Foundation::{FALSE, HANDLE, TRUE, WAIT_OBJECT_0},
+    Storage::FileSystem::{
+        ReadDirectoryChangesW, FILE_ACTION_ADDED, FILE_ACTION_MODIFIED, FILE_ACTION_REMOVED,
+        FILE_ACTION_RENAMED_NEW_NAME, FILE_ACTION_RENAMED_OLD_NAME, FILE_FLAG_BACKUP_SEMANTICS,
+        FILE_FLAG_OVERLAPPED, FILE_LIST_DIRECTORY, FILE_NOTIFY_CHANGE_DIR_NAME,
+        FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_CHANGE_SIZE,
+        FILE_NOTIFY_INFORMATION, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+    },
+    System::{
+        Threading::{CreateEventW, ResetEvent, SetEvent, WaitForMultipleObjects, INFINITE},
+        IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED},
+    },
+};
+
+use super::{list_dir, WatchEvent, WatchEventKind};
+
+// NOTE: Changes to directories on network shares can not be read into larger buffers
+const BUFFER_SIZE: usize = 64 * 1024;
+
+const NOTIFY_FILTER: u32 = FILE_NOTIFY_CHANGE_FILE_NAME
+    | FILE_NOTIFY_CHANGE_DIR_NAME
+    | FILE_NOTIFY_CHANGE_LAST_WRITE
+    | FILE_NOTIFY_CHANGE_SIZE;
+
+type EventSender = UnboundedSender<io::Result<Vec<WatchEvent>>>;
+
+/**
+    A watcher using native `ReadDirectoryChangesW` file system notifications.
+
+    Changes are read on a separate thread, which waits for either the next changes
+    or for the watcher to be dropped. Files are watched by watching the directory
+    that they are inside of, and skipping changes to anything else in it.
+*/
+pub struct Watcher {
+    events: UnboundedReceiver<io::Result<Vec<WatchEvent>>>,
+    stop: Arc<OwnedHandle>,
+}
+
+impl Watcher {
+    pub fn new(root: PathBuf, recursive: bool) -> io::Result<Self> {
+        let is_dir = std::fs::metadata(&root)?.is_dir();
+        let dir = if is_dir {
+            root.clone()
+        } else {
+            root.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        let handle = OpenOptions::new()
+            .access_mode(FILE_LIST_DIRECTORY)
+            .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE)
+            .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED)
+            .open(if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                &dir
+            })?;
+
+        let stop = Arc::new(create_event()?);
+        let (tx, rx) = unbounded_channel();
+        let changes = Changes {
+            root,
+            dir,
+            is_dir,
+            recursive,
+        };
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || changes.read(&handle, &thread_stop, &tx));
+
+        Ok(Self { events: rx, stop })
+    }
+
+    pub async fn next_events(&mut self) -> io::Result<Vec<WatchEvent>> {
+        match self.events.recv().await {
+            Some(events) => events,
+            // NOTE: The thread only stops reading changes after the root was removed,
+            // or on errors, and there will never be any more events after that
+            None => std::future::pending().await,
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // SAFETY: The event is kept open by both the watcher and its thread
+        unsafe { SetEvent(raw(&*self.stop)) };
+    }
+}
+
+struct Changes {
+    root: PathBuf,
+    dir: PathBuf,
+    is_dir: bool,
+    recursive: bool,
+}
+
+impl Changes {
+    fn read(&self, handle: &File, stop: &OwnedHandle, tx: &EventSender) {
+        if let Err(e) = self.read_until_stopped(handle, stop, tx) {
+            // NOTE: Reading changes fails once the directory being watched was removed
+            let events = if self.root.exists() {
+                Err(e)
+            } else {
+                Ok(vec![WatchEvent::new(
+                    WatchEventKind::Remove,
+                    self.root.clone(),
+                )])
+            };
+            tx.send(events).ok();
+        }
+    }
+
+    fn read_until_stopped(
+        &self,
+        handle: &File,
+        stop: &OwnedHandle,
+        tx: &EventSender,
+    ) -> io::Result<()> {
+        let event = create_event()?;
+        // NOTE: Changes are written as entries aligned to 4 bytes, same as our buffer
+        let mut buffer = vec![0u32; BUFFER_SIZE / mem::size_of::<u32>()];
+        loop {
+            // SAFETY: The buffer and the overlapped struct are both kept alive until
+            // reading has either finished or has been cancelled, and waited for
+            let len = unsafe {
+                let mut overlapped: OVERLAPPED = mem::zeroed();
+                overlapped.hEvent = raw(&event);
+                ResetEvent(raw(&event));
+                let started = ReadDirectoryChangesW(
+                    raw(handle),
+                    buffer.as_mut_ptr().cast(),
+                    BUFFER_SIZE as u32,
+                    i32::from(self.is_dir && self.recursive),
+                    NOTIFY_FILTER,
+                    ptr::null_mut(),
+                    ptr::addr_of_mut!(overlapped),
+                    None,
+                );
+                if started == FALSE {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let mut len = 0;
+                let handles = [raw(&event), raw(stop)];
+                if WaitForMultipleObjects(2, handles.as_ptr(), FALSE, INFINITE) != WAIT_OBJECT_0 {
+                    CancelIoEx(raw(handle), ptr::addr_of!(overlapped));
+                    GetOverlappedResult(
+                        raw(handle),
+                        ptr::addr_of!(overlapped),
+                        ptr::addr_of_mut!(len),
+                        TRUE,
+                    );
+                    return Ok(());
+                }
+                let overlapped = ptr::addr_of!(overlapped);
+                if GetOverlappedResult(raw(handle), overlapped, ptr::addr_of_mut!(len), FALSE)
+                    == FALSE
+                {
+                    return Err(io::Error::last_os_error());
+                }
+                len as usize
+            };
+
+            // NOTE: Nothing is read when there were too many changes to fit
+            // in the buffer, and there is no way to know what those were
+            if len == 0 {
+                continue;
+            }
+            let events = self.handle_changes(&buffer, len);
+            if !events.is_empty() && tx.send(Ok(events)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn handle_changes(&self, buffer: &[u32], len: usize) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        let mut renamed_from = None;
+        let mut offset = 0;
+        while offset < len {
+            // SAFETY: The system writes entries within the length it returned, with
+            // the name of each entry stored right after the entry itself, so we only
+            // ever read through raw pointers to the buffer and never past the name
+            let (action, name, next) = unsafe {
+                let info = buffer
+                    .as_ptr()
+                    .add(offset / mem::size_of::<u32>())
+                    .cast::<FILE_NOTIFY_INFORMATION>();
+                let name = slice::from_raw_parts(
+                    ptr::addr_of!((*info).FileName).cast::<u16>(),
+                    (*info).FileNameLength as usize / mem::size_of::<u16>(),
+                );
+                (
+                    (*info).Action,
+                    OsString::from_wide(name),
+                    (*info).NextEntryOffset as usize,
+                )
+            };
+
+            let path = self.dir.join(name);
+            match action {
+                FILE_ACTION_ADDED => self.push_created(path, &mut events),
+                FILE_ACTION_REMOVED => {
+                    events.push(WatchEvent::new(WatchEventKind::Remove, path));
+                }
+                // NOTE: Directories are modified whenever their entries
+                // change, which we already get separate events for
+                FILE_ACTION_MODIFIED if !path.is_dir() => {
+                    events.push(WatchEvent::new(WatchEventKind::Modify, path));
+                }
+                FILE_ACTION_RENAMED_OLD_NAME => renamed_from = Some(path),
+                FILE_ACTION_RENAMED_NEW_NAME => match renamed_from.take() {
+                    Some(from) => events.push(WatchEvent {
+                        kind: WatchEventKind::Rename,
+                        path,
+                        from: Some(from),
+                    }),
+                    None => self.push_created(path, &mut events),
+                },
+                _ => {}
+            }
+
+            if next == 0 {
+                break;
+            }
+            offset += next;
+        }
+
+        // Anything that was renamed without being renamed to somewhere
+        // else that we are watching is no longer being watched
+        if let Some(from) = renamed_from {
+            events.push(WatchEvent::new(WatchEventKind::Remove, from));
+        }
+
+        if !self.is_dir {
+            events = events
+                .into_iter()
+                .filter_map(|event| self.file_event(event))
+                .collect();
+        }
+
+        // NOTE: A single write is often reported as several modifications
+        events.dedup();
+        events
+    }
+
+    // Creates events for all entries inside of created directories, since
+    // these may have been created before we received the changes for them
+    fn push_created(&self, path: PathBuf, events: &mut Vec<WatchEvent>) {
+        events.push(WatchEvent::new(WatchEventKind::Create, path.clone()));
+        if self.recursive && self.is_dir && path.is_dir() {
+            let mut entries = Vec::new();
+            list_dir(&path, true, &mut entries);
+            for (path, _) in entries {
+                events.push(WatchEvent::new(WatchEventKind::Create, path));
+            }
+        }
+    }
+
+    // Keeps only events for the file being watched, where renames
+    // to or from it are the file being created or removed instead
+    fn file_event(&self, event: WatchEvent) -> Option<WatchEvent> {
+        let is_root = event.path == self.root;
+        let from_root = event.from.as_ref() == Some(&self.root);
+        match event.kind {
+            WatchEventKind::Rename if is_root && !from_root => {
+                Some(WatchEvent::new(WatchEventKind::Create, event.path))
+            }
+            WatchEventKind::Rename if from_root && !is_root => {
+                Some(WatchEvent::new(WatchEventKind::Remove, self.root.clone()))
+            }
+            _ if is_root => Some(event),
+            _ => None,
+        }
+    }
+}
+
+fn raw(handle: &impl AsRawHandle) -> HANDLE {
+    handle.as_raw_handle() as HANDLE
+}
+
+fn create_event() -> io::Result<OwnedHandle> {
+    // SAFETY: The event is created without a name or security attributes,
+    // and we take ownership of the returned handle, which is only ever closed once
+    unsafe {
+        let event = CreateEventW(ptr::null(), TRUE, FALSE, ptr::null());
+        if event == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(OwnedHandle::from_raw_handle(event as _))
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    fmt::Debug,
+    io,
+    sync::atomic::{AtomicU32, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+// Short enough that the resulting socket path fits within the limits
+// of all platforms, even when the temporary directory is deeply nested
+const MAX_NAME_LENGTH: usize = 48;
+
+// How often to retry connecting while waiting for a channel to be listened on
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+    A stream connecting two ends of an ipc channel.
+*/
+pub trait IpcStream: AsyncRead + AsyncWrite + Debug + Unpin + Send + 'static {}
+
+impl<T> IpcStream for T where T: AsyncRead + AsyncWrite + Debug + Unpin + Send + 'static {}
+
+pub type BoxedIpcStream = Box<dyn IpcStream>;
+
+/**
+    Checks that the given channel name is valid, meaning that it is not empty,
+    not too long, and only contains ascii letters, digits, `-`, `_` and `.`.
+
+    # Errors
+
+    Errors if the name is not valid, with a message describing why.
+*/
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        Err("channel name must not be empty".to_string())
+    } else if name.len() > MAX_NAME_LENGTH {
+        Err(format!(
+            "channel name must be at most {MAX_NAME_LENGTH} characters long"
+        ))
+    } else if name.starts_with('.') {
+        Err("channel name must not start with '.'".to_string())
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        Err("channel name may only contain letters, digits, '-', '_' and '.'".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/**
+    Generates a new channel name that is unique to this process.
+*/
+pub fn generate_name() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    format!("{}-{count}-{nanos:08x}", std::process::id())
+}
+
+/**
+    Connects to the channel with the given name, retrying until the
+    timeout elapses if nothing is listening on the channel yet.
+*/
+pub async fn connect(name: &str, timeout: Option<Duration>) -> io::Result<BoxedIpcStream> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        match imp::connect(name).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if imp::is_not_listening(&e) => match deadline {
+                Some(deadline) if Instant::now() < deadline => {
+                    tokio::time::sleep(CONNECT_RETRY_INTERVAL).await;
+                }
+                _ => return Err(e),
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub use imp::{bind, IpcListener};
+
+#[cfg(unix)]
+mod imp {
+    use std::{io, path::PathBuf};
+
+    use tokio::net::{UnixListener, UnixStream};
+
+    use super::BoxedIpcStream;
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lune-ipc-{name}.sock"))
+    }
+
+    /**
+        A listener for a channel, backed by a unix domain socket.
+
+        The socket file is removed when the listener is dropped.
+    */
+    #[derive(Debug)]
+    pub struct IpcListener {
+        listener: UnixListener,
+        path: PathBuf,
+    }
+
+    impl IpcListener {
+        pub async fn accept(&mut self) -> io::Result<BoxedIpcStream> {
+            let (stream, _) = self.listener.accept().await?;
+            Ok(Box::new(stream))
+        }
+    }
+
+    impl Drop for IpcListener {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    pub async fn bind(name: &str) -> io::Result<IpcListener> {
+        let path = socket_path(name);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+                if UnixStream::connect(&path).await.is_ok() {
+                    return Err(e);
+                }
+                // NOTE: Nothing is listening on the socket, so it was left behind
+                // by a process that exited without cleaning up, and can be replaced
+                tokio::fs::remove_file(&path).await?;
+                UnixListener::bind(&path)?
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(IpcListener { listener, path })
+    }
+
+    pub async fn connect(name: &str) -> io::Result<BoxedIpcStream> {
+        let stream = UnixStream::connect(socket_path(name)).await?;
+        Ok(Box::new(stream))
+    }
+
+    pub fn is_not_listening(e: &io::Error) -> bool {
+        matches!(
+            e.kind(),
+            io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+        )
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{io, time::Duration};
+
+    use tokio::net::windows::named_pipe::{ClientOptions, NamedPipeServer, ServerOptions};
+
+    use super::BoxedIpcStream;
+
+    const ERROR_FILE_NOT_FOUND: i32 = 2;
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    // How long to wait before retrying when all instances of a pipe are busy
+    const PIPE_BUSY_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn pipe_name(name: &str) -> String {
+        format!(r"\\.\pipe\lune-ipc-{name}")
+    }
+
+    /**
+        A listener for a channel, backed by a named pipe.
+
+        A new instance of the pipe is created after each accepted
+        connection, so that other clients may connect to it.
+    */
+    #[derive(Debug)]
+    pub struct IpcListener {
+        server: NamedPipeServer,
+        name: String,
+    }
+
+    impl IpcListener {
+        pub async fn accept(&mut self) -> io::Result<BoxedIpcStream> {
+            self.server.connect().await?;
+            let next = ServerOptions::new().create(&self.name)?;
+            let connected = std::mem::replace(&mut self.server, next);
+            Ok(Box::new(connected))
+        }
+    }
+
+    #[allow(clippy::unused_async)] // Matches the signature for unix
+    pub async fn bind(name: &str) -> io::Result<IpcListener> {
+        let name = pipe_name(name);
+        let server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&name)
+            .map_err(|e| {
+                if e.raw_os_error() == Some(ERROR_ACCESS_DENIED) {
+                    io::Error::from(io::ErrorKind::AddrInUse)
+                } else {
+                    e
+                }
+            })?;
+        Ok(IpcListener { server, name })
+    }
+
+    pub async fn connect(name: &str) -> io::Result<BoxedIpcStream> {
+        let name = pipe_name(name);
+        loop {
+            match ClientOptions::new().open(&name) {
+                Ok(client) => return Ok(Box::new(client)),
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                    tokio::time::sleep(PIPE_BUSY_INTERVAL).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn is_not_listening(e: &io::Error) -> bool {
+        e.raw_os_error() == Some(ERROR_FILE_NOT_FOUND) || e.kind() == io::ErrorKind::NotFound
+    }
+}
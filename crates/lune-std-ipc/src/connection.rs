@@ -0,0 +1,231 @@
+use std::{
+    future::Future,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use mlua::prelude::*;
+use tokio::{
+    io::{split, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf},
+    sync::{Mutex as AsyncMutex, Notify},
+};
+
+use lune_std_serde::{decode, encode, EncodeDecodeConfig};
+
+use crate::channel::BoxedIpcStream;
+use crate::options::{IpcFormat, IpcOptions};
+
+// Messages are framed using a big-endian length, followed by a
+// single byte for the format of the message, and then the message
+const LENGTH_SIZE: usize = 4;
+
+/**
+    One end of a connection over an ipc channel, from either `ipc.connect` or `IpcServer:accept`.
+
+    Sending and receiving may happen at the same time from different Lua threads,
+    but only one message is ever sent, or received, at once.
+*/
+#[derive(Debug, Clone)]
+pub struct IpcConnection {
+    name: Arc<str>,
+    reader: Arc<AsyncMutex<Option<ReadHalf<BoxedIpcStream>>>>,
+    writer: Arc<AsyncMutex<Option<WriteHalf<BoxedIpcStream>>>>,
+    options: IpcOptions,
+    closed: Arc<AtomicBool>,
+    closed_notify: Arc<Notify>,
+}
+
+impl IpcConnection {
+    pub fn new(name: impl Into<Arc<str>>, stream: BoxedIpcStream, options: IpcOptions) -> Self {
+        let (reader, writer) = split(stream);
+        Self {
+            name: name.into(),
+            reader: Arc::new(AsyncMutex::new(Some(reader))),
+            writer: Arc::new(AsyncMutex::new(Some(writer))),
+            options,
+            closed: Arc::new(AtomicBool::new(false)),
+            closed_notify: Arc::new(Notify::new()),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn mark_closed(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.closed_notify.notify_waiters();
+    }
+
+    /**
+        Runs the given future until it completes, or until the
+        connection is closed - returning `None` if it was closed.
+    */
+    async fn unless_closed<F: Future>(&self, fut: F) -> Option<F::Output> {
+        let closed = self.closed_notify.notified();
+        if self.is_closed() {
+            return None;
+        }
+        tokio::select! {
+            res = fut => Some(res),
+            () = closed => None,
+        }
+    }
+
+    pub async fn send(&self, format: IpcFormat, message: &[u8]) -> LuaResult<()> {
+        let max = self.options.max_message_size;
+        if message.len() > max {
+            return Err(LuaError::RuntimeError(format!(
+                "Failed to send message over ipc channel - message is {} bytes, maximum is {max} bytes",
+                message.len()
+            )));
+        }
+
+        let length = u32::try_from(message.len() + 1).into_lua_err()?;
+        let mut frame = Vec::with_capacity(LENGTH_SIZE + 1 + message.len());
+        frame.extend_from_slice(&length.to_be_bytes());
+        frame.push(format.tag());
+        frame.extend_from_slice(message);
+
+        let res = self
+            .unless_closed(async {
+                let mut writer = self.writer.lock().await;
+                let Some(writer) = writer.as_mut() else {
+                    return Ok(false);
+                };
+                writer.write_all(&frame).await?;
+                writer.flush().await?;
+                Ok::<_, io::Error>(true)
+            })
+            .await;
+
+        match res {
+            Some(Ok(true)) => Ok(()),
+            Some(Ok(false)) | None => {
+                Err(LuaError::runtime("Ipc connection has already been closed"))
+            }
+            Some(Err(e)) => {
+                self.mark_closed();
+                Err(LuaError::RuntimeError(format!(
+                    "Failed to send message over ipc channel - {e}"
+                )))
+            }
+        }
+    }
+
+    pub async fn receive(&self) -> LuaResult<Option<(IpcFormat, Vec<u8>)>> {
+        let max = self.options.max_message_size;
+        let res = self
+            .unless_closed(async {
+                let mut reader = self.reader.lock().await;
+                let Some(reader) = reader.as_mut() else {
+                    return Ok(None);
+                };
+
+                let mut length = [0; LENGTH_SIZE];
+                match reader.read_exact(&mut length).await {
+                    Ok(_) => {}
+                    Err(e) if is_closed_error(&e) => return Ok(None),
+                    Err(e) => return Err(e),
+                }
+
+                let length = u32::from_be_bytes(length) as usize;
+                if length == 0 {
+                    return Err(invalid_data("message is missing its format"));
+                }
+                if length - 1 > max {
+                    return Err(invalid_data(format!(
+                        "message is {} bytes, maximum is {max} bytes",
+                        length - 1
+                    )));
+                }
+
+                let tag = reader.read_u8().await?;
+                let format = IpcFormat::from_tag(tag)
+                    .ok_or_else(|| invalid_data(format!("message has unknown format {tag}")))?;
+
+                let mut message = vec![0; length - 1];
+                reader.read_exact(&mut message).await?;
+                Ok(Some((format, message)))
+            })
+            .await;
+
+        match res {
+            Some(Ok(Some(message))) => Ok(Some(message)),
+            Some(Ok(None)) | None => {
+                self.mark_closed();
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                // NOTE: We can not know where the next message starts after
+                // a partial or invalid message, so the connection is unusable
+                self.mark_closed();
+                Err(LuaError::RuntimeError(format!(
+                    "Failed to receive message over ipc channel - {e}"
+                )))
+            }
+        }
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        self.mark_closed();
+        let writer = self.writer.lock().await.take();
+        let reader = self.reader.lock().await.take();
+        match writer {
+            Some(mut writer) => {
+                // NOTE: Dropping both halves closes the stream, shutting down
+                // first makes sure that anything buffered is sent to the peer
+                let _ = writer.shutdown().await;
+                drop(reader);
+                Ok(())
+            }
+            None => Err(LuaError::runtime("Ipc connection has already been closed")),
+        }
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn is_closed_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset
+    )
+}
+
+impl LuaUserData for IpcConnection {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "IpcConnection");
+        fields.add_field_method_get("name", |_, this| Ok(this.name.to_string()));
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("send", |lua, this, value: LuaValue| async move {
+            if value.is_nil() {
+                return Err(LuaError::runtime(
+                    "Can not send nil over ipc channel, since receiving nil means the connection was closed",
+                ));
+            }
+            let format = this.options.format;
+            let message = encode(value, lua, EncodeDecodeConfig::from(format.encoding()))
+                .context("Failed to encode message for ipc channel")?;
+            this.send(format, message.as_bytes()).await
+        });
+        methods.add_async_method("receive", |lua, this, (): ()| async move {
+            match this.receive().await? {
+                Some((format, message)) => {
+                    decode(message, lua, EncodeDecodeConfig::from(format.encoding()))
+                        .context("Failed to decode message from ipc channel")
+                }
+                None => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+}
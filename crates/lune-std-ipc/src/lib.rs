@@ -0,0 +1,82 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use std::io;
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+mod channel;
+mod connection;
+mod mapping;
+mod options;
+mod server;
+mod shared_buffer;
+
+use self::connection::IpcConnection;
+use self::options::IpcOptions;
+use self::server::IpcServer;
+use self::shared_buffer::SharedBuffer;
+
+/**
+    Creates the `ipc` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("listen", ipc_listen)?
+        .with_async_function("connect", ipc_connect)?
+        .with_function("sharedBuffer", ipc_shared_buffer)?
+        .build_readonly()
+}
+
+fn check_name(name: &str) -> LuaResult<()> {
+    channel::validate_name(name)
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid ipc channel name '{name}' - {e}")))
+}
+
+async fn ipc_listen(
+    _: &Lua,
+    (name, options): (Option<String>, IpcOptions),
+) -> LuaResult<IpcServer> {
+    let name = name.unwrap_or_else(channel::generate_name);
+    check_name(&name)?;
+
+    let listener = channel::bind(&name).await.map_err(|e| {
+        if e.kind() == io::ErrorKind::AddrInUse {
+            LuaError::RuntimeError(format!("Ipc channel '{name}' is already in use"))
+        } else {
+            LuaError::RuntimeError(format!("Failed to listen on ipc channel '{name}' - {e}"))
+        }
+    })?;
+
+    Ok(IpcServer::new(name, listener, options))
+}
+
+async fn ipc_connect(_: &Lua, (name, options): (String, IpcOptions)) -> LuaResult<IpcConnection> {
+    check_name(&name)?;
+
+    let stream = channel::connect(&name, options.timeout)
+        .await
+        .map_err(|e| {
+            LuaError::RuntimeError(format!("Failed to connect to ipc channel '{name}' - {e}"))
+        })?;
+
+    Ok(IpcConnection::new(name, stream, options))
+}
+
+fn ipc_shared_buffer(_: &Lua, (name, size): (String, usize)) -> LuaResult<SharedBuffer> {
+    channel::validate_name(&name).map_err(|e| {
+        LuaError::RuntimeError(format!("Invalid shared buffer name '{name}' - {e}"))
+    })?;
+    if size == 0 || size > shared_buffer::MAX_SIZE {
+        return Err(LuaError::RuntimeError(format!(
+            "Shared buffer size must be between 1 and {} bytes, got {size}",
+            shared_buffer::MAX_SIZE
+        )));
+    }
+    SharedBuffer::open(&name, size)
+}
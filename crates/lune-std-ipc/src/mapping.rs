@@ -0,0 +1,221 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicU32,
+};
+
+/**
+    Gets the path of the file backing the shared memory region with the given name.
+
+    On Linux, regions are stored in `/dev/shm` when it exists, which is always kept
+    in memory - other platforms use the temporary directory, which works the same,
+    but may be written to disk by the operating system when under memory pressure.
+*/
+pub fn region_path(name: &str) -> PathBuf {
+    let file_name = format!("lune-shm-{name}");
+    let shm = Path::new("/dev/shm");
+    if cfg!(target_os = "linux") && shm.is_dir() {
+        shm.join(file_name)
+    } else {
+        std::env::temp_dir().join(file_name)
+    }
+}
+
+/**
+    Opens the file backing a shared memory region of exactly the given length,
+    creating it if it does not exist yet, and returns it along with its length.
+
+    If the file already exists with a different length, it is returned as-is,
+    and the caller should check the length before mapping it into memory.
+*/
+pub fn open_region(path: &Path, len: usize) -> io::Result<(File, usize)> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    let existing = file.metadata()?.len() as usize;
+    if existing == 0 {
+        // NOTE: Any other process creating the region at the same time
+        // sets it to the same length, and new regions are always zeroed
+        file.set_len(len as u64)?;
+        Ok((file, len))
+    } else {
+        Ok((file, existing))
+    }
+}
+
+/**
+    A shared memory region, mapped into the memory of the current process.
+
+    The region is unmapped when this struct is dropped.
+*/
+#[derive(Debug)]
+pub struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+    #[cfg(windows)]
+    handle: windows_sys::Win32::Foundation::HANDLE,
+}
+
+// SAFETY: The mapping is only ever accessed through raw pointers,
+// never through references, so it may be moved between threads
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    /**
+        Gets the atomic at the start of the region.
+
+        # Safety
+
+        The region must be at least 4 bytes long, which is always the case for
+        regions with a header, and the returned reference must not outlive `self`.
+    */
+    pub unsafe fn header_atomic(&self) -> &AtomicU32 {
+        // NOTE: Mappings always start at the beginning of a page, so this is aligned
+        #[allow(clippy::cast_ptr_alignment)]
+        &*self.ptr.cast::<AtomicU32>()
+    }
+
+    /**
+        Copies bytes out of the region, starting at the given offset.
+
+        The caller must make sure that the range is within the region.
+    */
+    pub fn read(&self, offset: usize, out: &mut [u8]) {
+        assert!(offset + out.len() <= self.len, "read out of bounds");
+        // SAFETY: The range was checked above, and other processes may write
+        // to the region at the same time, which is why we only copy using
+        // raw pointers, and never create a reference to the region itself
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.ptr.add(offset), out.as_mut_ptr(), out.len());
+        }
+    }
+
+    /**
+        Copies bytes into the region, starting at the given offset.
+
+        The caller must make sure that the range is within the region.
+    */
+    pub fn write(&self, offset: usize, data: &[u8]) {
+        assert!(offset + data.len() <= self.len, "write out of bounds");
+        // SAFETY: Same as for reading above
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.add(offset), data.len());
+        }
+    }
+
+    /**
+        Sets bytes in the region to the given value, starting at the given offset.
+
+        The caller must make sure that the range is within the region.
+    */
+    pub fn fill(&self, offset: usize, value: u8, count: usize) {
+        assert!(offset + count <= self.len, "fill out of bounds");
+        // SAFETY: Same as for reading above
+        unsafe {
+            std::ptr::write_bytes(self.ptr.add(offset), value, count);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Mapping {
+    pub fn map(file: &File, len: usize) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        // SAFETY: The file descriptor is valid for the duration of this call,
+        // and the mapping stays valid after it is closed, until it is unmapped
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: The pointer and length are exactly what was mapped
+        unsafe {
+            libc::munmap(self.ptr.cast(), self.len);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Mapping {
+    pub fn map(file: &File, len: usize) -> io::Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+
+        use windows_sys::Win32::{
+            Foundation::CloseHandle,
+            System::Memory::{
+                CreateFileMappingW, MapViewOfFile, FILE_MAP_ALL_ACCESS, PAGE_READWRITE,
+            },
+        };
+
+        // SAFETY: The file handle is valid for the duration of this call, and
+        // the mapping handle is only closed once the view has been unmapped
+        unsafe {
+            let handle = CreateFileMappingW(
+                file.as_raw_handle() as _,
+                std::ptr::null(),
+                PAGE_READWRITE,
+                0,
+                0,
+                std::ptr::null(),
+            );
+            if handle == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let view = MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, len);
+            if view.Value.is_null() {
+                let err = io::Error::last_os_error();
+                CloseHandle(handle);
+                return Err(err);
+            }
+
+            Ok(Self {
+                ptr: view.Value.cast(),
+                len,
+                handle,
+            })
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        use windows_sys::Win32::{
+            Foundation::CloseHandle,
+            System::Memory::{UnmapViewOfFile, MEMORY_MAPPED_VIEW_ADDRESS},
+        };
+
+        // SAFETY: The view and handle are exactly what was created when mapping
+        unsafe {
+            UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
+                Value: self.ptr.cast(),
+            });
+            CloseHandle(self.handle);
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use mlua::prelude::*;
+
+use lune_std_serde::EncodeDecodeFormat;
+
+// Large enough for any reasonable message, small enough that a
+// misbehaving peer can not make us allocate an unbounded amount
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+fn invalid_options(message: impl Into<String>) -> LuaError {
+    LuaError::FromLuaConversionError {
+        from: "table",
+        to: "IpcOptions",
+        message: Some(format!("Invalid ipc options - {}", message.into())),
+    }
+}
+
+/**
+    The format that messages are encoded with before being sent.
+
+    The format is sent along with each message, so both ends
+    of a connection do not need to use the same format.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcFormat {
+    Json,
+    MsgPack,
+    Cbor,
+}
+
+impl IpcFormat {
+    pub const fn tag(self) -> u8 {
+        match self {
+            Self::Json => 0,
+            Self::MsgPack => 1,
+            Self::Cbor => 2,
+        }
+    }
+
+    pub const fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Json),
+            1 => Some(Self::MsgPack),
+            2 => Some(Self::Cbor),
+            _ => None,
+        }
+    }
+
+    pub const fn encoding(self) -> EncodeDecodeFormat {
+        match self {
+            Self::Json => EncodeDecodeFormat::Json,
+            Self::MsgPack => EncodeDecodeFormat::MsgPack,
+            Self::Cbor => EncodeDecodeFormat::Cbor,
+        }
+    }
+}
+
+/**
+    Options for listening on and connecting to ipc channels.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct IpcOptions {
+    pub format: IpcFormat,
+    pub max_message_size: usize,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for IpcOptions {
+    fn default() -> Self {
+        Self {
+            format: IpcFormat::Json,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            timeout: None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for IpcOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let options = match value {
+            LuaValue::Nil => return Ok(Self::default()),
+            LuaValue::Table(options) => options,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "IpcOptions",
+                    message: Some(format!(
+                        "Invalid ipc options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+
+        let format = match options.get::<_, Option<String>>("format")?.as_deref() {
+            None | Some("json") => IpcFormat::Json,
+            Some("msgpack") => IpcFormat::MsgPack,
+            Some("cbor") => IpcFormat::Cbor,
+            Some(format) => {
+                return Err(invalid_options(format!(
+                    "format must be 'json', 'msgpack' or 'cbor', got '{format}'"
+                )))
+            }
+        };
+
+        let max_message_size = match options.get::<_, Option<usize>>("maxMessageSize")? {
+            None => DEFAULT_MAX_MESSAGE_SIZE,
+            Some(0) => return Err(invalid_options("maxMessageSize must be a positive integer")),
+            Some(size) => size.min(u32::MAX as usize - 1),
+        };
+
+        let timeout = match options.get::<_, Option<f64>>("timeout")? {
+            None => None,
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(_) => return Err(invalid_options("timeout must be a positive number")),
+        };
+
+        Ok(Self {
+            format,
+            max_message_size,
+            timeout,
+        })
+    }
+}
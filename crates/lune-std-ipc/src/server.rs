@@ -0,0 +1,95 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use mlua::prelude::*;
+use tokio::sync::{Mutex as AsyncMutex, Notify};
+
+use crate::channel::IpcListener;
+use crate::connection::IpcConnection;
+use crate::options::IpcOptions;
+
+/**
+    A server listening for connections on an ipc channel, from `ipc.listen`.
+
+    The channel stops being listened on when the server is closed, or
+    when it is garbage collected, after which the name may be reused.
+*/
+#[derive(Debug, Clone)]
+pub struct IpcServer {
+    name: Arc<str>,
+    listener: Arc<AsyncMutex<Option<IpcListener>>>,
+    options: IpcOptions,
+    closed: Arc<AtomicBool>,
+    closed_notify: Arc<Notify>,
+}
+
+impl IpcServer {
+    pub fn new(name: impl Into<Arc<str>>, listener: IpcListener, options: IpcOptions) -> Self {
+        Self {
+            name: name.into(),
+            listener: Arc::new(AsyncMutex::new(Some(listener))),
+            options,
+            closed: Arc::new(AtomicBool::new(false)),
+            closed_notify: Arc::new(Notify::new()),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    pub async fn accept(&self) -> LuaResult<Option<IpcConnection>> {
+        let closed = self.closed_notify.notified();
+        if self.is_closed() {
+            return Ok(None);
+        }
+
+        let accept = async {
+            let mut listener = self.listener.lock().await;
+            match listener.as_mut() {
+                Some(listener) => listener.accept().await.map(Some),
+                None => Ok(None),
+            }
+        };
+
+        let stream = tokio::select! {
+            res = accept => res.map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to accept connection on ipc channel '{}' - {e}",
+                    self.name
+                ))
+            })?,
+            () = closed => None,
+        };
+
+        Ok(stream.map(|stream| IpcConnection::new(Arc::clone(&self.name), stream, self.options)))
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            return Err(LuaError::runtime("Ipc server has already been closed"));
+        }
+        self.closed_notify.notify_waiters();
+        // NOTE: Dropping the listener stops listening on the channel
+        self.listener.lock().await.take();
+        Ok(())
+    }
+}
+
+impl LuaUserData for IpcServer {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "IpcServer");
+        fields.add_field_method_get("name", |_, this| Ok(this.name.to_string()));
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method(
+            "accept",
+            |_, this, (): ()| async move { this.accept().await },
+        );
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+}
@@ -0,0 +1,374 @@
+use std::{
+    hint::spin_loop,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+
+use crate::mapping::{open_region, region_path, Mapping};
+
+// The header stores the lock, which is the id of the process holding it, or zero,
+// and is large enough to keep the data that follows it aligned to a full cache line
+const HEADER_SIZE: usize = 64;
+
+// Same as the maximum size of a Luau buffer
+pub const MAX_SIZE: usize = 1024 * 1024 * 1024;
+
+// How many times to retry taking the lock before sleeping between attempts,
+// and for how long, which keeps short waits fast without hogging the cpu
+const LOCK_SPIN_ATTEMPTS: u32 = 128;
+const LOCK_SLEEP: Duration = Duration::from_micros(50);
+
+/**
+    A region of memory shared between processes, from `ipc.sharedBuffer`.
+
+    Any process opening a shared buffer with the same name sees the same memory,
+    and may take the lock stored along with it to coordinate access to that memory.
+*/
+#[derive(Debug, Clone)]
+pub struct SharedBuffer {
+    name: Arc<str>,
+    size: usize,
+    mapping: Arc<Mutex<Option<Mapping>>>,
+    locked: Arc<AtomicBool>,
+}
+
+impl SharedBuffer {
+    pub fn open(name: &str, size: usize) -> LuaResult<Self> {
+        let open_error = |e: io::Error| {
+            LuaError::RuntimeError(format!("Failed to open shared buffer '{name}' - {e}"))
+        };
+
+        let len = HEADER_SIZE + size;
+        let (file, existing) = open_region(&region_path(name), len).map_err(open_error)?;
+        if existing != len {
+            return Err(LuaError::RuntimeError(format!(
+                "Shared buffer '{name}' already exists with a size of {} bytes, not {size} bytes",
+                existing.saturating_sub(HEADER_SIZE)
+            )));
+        }
+
+        let mapping = Mapping::map(&file, len).map_err(open_error)?;
+        Ok(Self {
+            name: Arc::from(name),
+            size,
+            mapping: Arc::new(Mutex::new(Some(mapping))),
+            locked: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.mapping.lock().unwrap().is_none()
+    }
+
+    fn with_mapping<T>(&self, f: impl FnOnce(&Mapping) -> T) -> LuaResult<T> {
+        match self.mapping.lock().unwrap().as_ref() {
+            Some(mapping) => Ok(f(mapping)),
+            None => Err(LuaError::runtime("Shared buffer has already been closed")),
+        }
+    }
+
+    /**
+        Checks that the given range is within the buffer, and returns
+        the offset of the range within the mapped memory region.
+    */
+    fn check_range(&self, offset: usize, count: usize) -> LuaResult<usize> {
+        match offset.checked_add(count) {
+            Some(end) if end <= self.size => Ok(HEADER_SIZE + offset),
+            _ => Err(LuaError::RuntimeError(format!(
+                "Offset {offset} and count {count} are out of bounds for shared buffer of size {}",
+                self.size
+            ))),
+        }
+    }
+
+    fn remaining(&self, offset: usize, count: Option<usize>) -> usize {
+        count.unwrap_or_else(|| self.size.saturating_sub(offset))
+    }
+
+    /**
+        Reads the given range into a new vec, checking the range
+        before allocating, so that it never allocates more than the size.
+    */
+    pub fn read_vec(&self, offset: usize, count: usize) -> LuaResult<Vec<u8>> {
+        let start = self.check_range(offset, count)?;
+        let mut bytes = vec![0; count];
+        self.with_mapping(|mapping| mapping.read(start, &mut bytes))?;
+        Ok(bytes)
+    }
+
+    pub fn read(&self, offset: usize, out: &mut [u8]) -> LuaResult<()> {
+        let start = self.check_range(offset, out.len())?;
+        self.with_mapping(|mapping| mapping.read(start, out))
+    }
+
+    pub fn write(&self, offset: usize, data: &[u8]) -> LuaResult<()> {
+        let start = self.check_range(offset, data.len())?;
+        self.with_mapping(|mapping| mapping.write(start, data))
+    }
+
+    pub fn fill(&self, offset: usize, value: u8, count: usize) -> LuaResult<()> {
+        let start = self.check_range(offset, count)?;
+        self.with_mapping(|mapping| mapping.fill(start, value, count))
+    }
+
+    pub fn try_lock(&self) -> LuaResult<bool> {
+        let pid = std::process::id();
+        let acquired = self.with_mapping(|mapping| {
+            // SAFETY: The region always has a header, and the reference does not escape
+            let lock = unsafe { mapping.header_atomic() };
+            match lock.compare_exchange(0, pid, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => true,
+                // NOTE: A process that exits while holding the lock never releases it,
+                // so we take the lock over from it, instead of waiting for it forever
+                Err(owner) => {
+                    !process_exists(owner)
+                        && lock
+                            .compare_exchange(owner, pid, Ordering::Acquire, Ordering::Relaxed)
+                            .is_ok()
+                }
+            }
+        })?;
+        if acquired {
+            self.locked.store(true, Ordering::SeqCst);
+        }
+        Ok(acquired)
+    }
+
+    pub async fn lock(&self, timeout: Option<Duration>) -> LuaResult<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut attempts = 0;
+        while !self.try_lock()? {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            } else if attempts < LOCK_SPIN_ATTEMPTS {
+                attempts += 1;
+                spin_loop();
+            } else {
+                tokio::time::sleep(LOCK_SLEEP).await;
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn unlock(&self) -> LuaResult<()> {
+        if !self.locked.swap(false, Ordering::SeqCst) {
+            return Err(LuaError::runtime(
+                "Shared buffer can not be unlocked, since it was not locked using this shared buffer",
+            ));
+        }
+        self.with_mapping(|mapping| {
+            // SAFETY: Same as for locking above
+            let lock = unsafe { mapping.header_atomic() };
+            lock.store(0, Ordering::Release);
+        })
+    }
+
+    pub fn close(&self) -> LuaResult<()> {
+        if self.locked.load(Ordering::SeqCst) {
+            self.unlock()?;
+        }
+        // NOTE: Dropping the mapping unmaps the memory region
+        match self.mapping.lock().unwrap().take() {
+            Some(_) => Ok(()),
+            None => Err(LuaError::runtime("Shared buffer has already been closed")),
+        }
+    }
+
+    pub fn destroy(&self) -> LuaResult<()> {
+        if !self.is_closed() {
+            self.close()?;
+        }
+        match std::fs::remove_file(region_path(&self.name)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(LuaError::RuntimeError(format!(
+                "Failed to destroy shared buffer '{}' - {e}",
+                self.name
+            ))),
+        }
+    }
+}
+
+/**
+    Checks if a process with the given id is still running.
+
+    Process ids may be reused once a process has exited, so this may also
+    return `true` for a process that only happens to have the same id.
+*/
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: Sending signal zero only checks if the process exists
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+#[cfg(windows)]
+fn process_exists(pid: u32) -> bool {
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, ERROR_INVALID_PARAMETER, STILL_ACTIVE},
+        System::Threading::{GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
+    };
+
+    // SAFETY: The handle is only used while it is open, and closed right after
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle == 0 {
+            // NOTE: Processes that we may not open still exist, only
+            // processes that do not exist give an invalid parameter error
+            let code = io::Error::last_os_error().raw_os_error();
+            return code != i32::try_from(ERROR_INVALID_PARAMETER).ok();
+        }
+        let mut exit_code = 0;
+        let queried = GetExitCodeProcess(handle, std::ptr::addr_of_mut!(exit_code));
+        CloseHandle(handle);
+        queried == 0 || exit_code == STILL_ACTIVE as u32
+    }
+}
+
+/*
+    Adds methods for reading and writing numbers, which work the same as the
+    methods in the built-in `buffer` library, including the little endian order
+*/
+macro_rules! add_number_methods {
+    ($methods:ident, $($read:literal, $write:literal => $ty:ty as $via:ty),* $(,)?) => { $(
+        $methods.add_method($read, |_, this, offset: usize| {
+            let mut bytes = [0; std::mem::size_of::<$ty>()];
+            this.read(offset, &mut bytes)?;
+            Ok(<$ty>::from_le_bytes(bytes) as f64)
+        });
+        $methods.add_method($write, |_, this, (offset, value): (usize, f64)| {
+            this.write(offset, &((value as $via) as $ty).to_le_bytes())
+        });
+    )* };
+}
+
+impl LuaUserData for SharedBuffer {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "SharedBuffer");
+        fields.add_field_method_get("name", |_, this| Ok(this.name.to_string()));
+        fields.add_field_method_get("size", |_, this| Ok(this.size));
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+        fields.add_field_method_get("locked", |_, this| Ok(this.locked.load(Ordering::SeqCst)));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        add_number_methods!(
+            methods,
+            "readi8", "writei8" => i8 as i64,
+            "readu8", "writeu8" => u8 as i64,
+            "readi16", "writei16" => i16 as i64,
+            "readu16", "writeu16" => u16 as i64,
+            "readi32", "writei32" => i32 as i64,
+            "readu32", "writeu32" => u32 as i64,
+            "readf32", "writef32" => f32 as f32,
+            "readf64", "writef64" => f64 as f64,
+        );
+
+        methods.add_method(
+            "readstring",
+            |lua, this, (offset, count): (usize, usize)| {
+                lua.create_string(this.read_vec(offset, count)?)
+            },
+        );
+        methods.add_method(
+            "writestring",
+            |_, this, (offset, value, count): (usize, BString, Option<usize>)| {
+                let count = count.unwrap_or(value.len());
+                if count > value.len() {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Count {count} is larger than the length of the string, {}",
+                        value.len()
+                    )));
+                }
+                this.write(offset, &value.as_bytes()[..count])
+            },
+        );
+        methods.add_method(
+            "read",
+            |lua, this, (offset, count): (Option<usize>, Option<usize>)| {
+                let offset = offset.unwrap_or_default();
+                lua.create_buffer(this.read_vec(offset, this.remaining(offset, count))?)
+            },
+        );
+        methods.add_method("write", |_, this, (offset, data): (usize, BString)| {
+            this.write(offset, data.as_bytes())
+        });
+        methods.add_method(
+            "fill",
+            |_, this, (offset, value, count): (usize, u8, Option<usize>)| {
+                this.fill(offset, value, this.remaining(offset, count))
+            },
+        );
+
+        methods.add_async_method("lock", |_, this, timeout: Option<f64>| async move {
+            let timeout = timeout
+                .map(Duration::try_from_secs_f64)
+                .transpose()
+                .map_err(|_| LuaError::runtime("Timeout must be a non-negative number"))?;
+            this.lock(timeout).await
+        });
+        methods.add_method("tryLock", |_, this, (): ()| this.try_lock());
+        methods.add_method("unlock", |_, this, (): ()| this.unlock());
+        methods.add_method("close", |_, this, (): ()| this.close());
+        methods.add_method("destroy", |_, this, (): ()| this.destroy());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exited_process_id() -> u32 {
+        let mut child = std::process::Command::new(if cfg!(windows) { "cmd" } else { "true" })
+            .args(if cfg!(windows) {
+                &["/C", "exit"][..]
+            } else {
+                &[]
+            })
+            .spawn()
+            .expect("Failed to spawn process");
+        let pid = child.id();
+        child.wait().expect("Failed to wait for process");
+        pid
+    }
+
+    #[test]
+    fn process_exists_current() {
+        assert!(process_exists(std::process::id()));
+    }
+
+    #[test]
+    fn process_exists_exited() {
+        assert!(!process_exists(exited_process_id()));
+    }
+
+    #[test]
+    fn lock_taken_over_from_exited_process() {
+        let name = format!("lune-test-lock-{}", std::process::id());
+        let buffer = SharedBuffer::open(&name, 8).unwrap();
+        let dead = exited_process_id();
+        buffer
+            .with_mapping(|mapping| {
+                // SAFETY: Same as for locking
+                let lock = unsafe { mapping.header_atomic() };
+                lock.store(dead, Ordering::Release);
+            })
+            .unwrap();
+        let taken = buffer.try_lock();
+        buffer.destroy().unwrap();
+        assert!(
+            taken.unwrap(),
+            "Lock held by an exited process should be taken over"
+        );
+    }
+}
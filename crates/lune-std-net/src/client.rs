@@ -1,24 +1,57 @@
-use std::str::FromStr;
+use std::{net::SocketAddr, str::FromStr, sync::Arc};
 
 use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use tokio::{sync::Mutex as AsyncMutex, time::sleep};
 
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING};
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH,
+        CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION, TRANSFER_ENCODING, WWW_AUTHENTICATE,
+    },
+    redirect::Policy as RedirectPolicy,
+    Method, StatusCode,
+};
 
-use lune_std_serde::{decompress, CompressDecompressFormat};
+use encoding_rs::{Encoding, UTF_8};
+
+use lune_std_serde::{
+    decode, decompress, CompressDecompressFormat, EncodeDecodeConfig, EncodeDecodeFormat,
+};
 use lune_utils::TableBuilder;
 
-use super::{config::RequestConfig, util::header_map_to_table};
+use super::{
+    config::{RequestConfig, RequestConfigTls},
+    cookies::CookieJar,
+    hosts::{HostOverrides, PolicyResolver},
+    multipart::MultipartBody,
+    policy::HostPolicy,
+    socks::{SocksBridge, SocksProxy},
+    sse::{SseDecoder, SseEvent},
+    util::header_map_to_table,
+};
 
 const REGISTRY_KEY: &str = "NetClient";
 
+// Same as the default maximum number of redirects in reqwest
+const MAX_REDIRECTS: usize = 10;
+
 pub struct NetClientBuilder {
     builder: reqwest::ClientBuilder,
+    follow_redirects: bool,
+    policy: Option<Arc<HostPolicy>>,
+    overrides: Option<Arc<HostOverrides>>,
+    socks: Option<SocksProxy>,
 }
 
 impl NetClientBuilder {
     pub fn new() -> NetClientBuilder {
         Self {
             builder: reqwest::ClientBuilder::new(),
+            follow_redirects: true,
+            policy: None,
+            overrides: None,
+            socks: None,
         }
     }
 
@@ -37,15 +70,146 @@ impl NetClientBuilder {
         Ok(self)
     }
 
-    pub fn build(self) -> LuaResult<NetClient> {
-        let client = self.builder.build().into_lua_err()?;
-        Ok(NetClient { inner: client })
+    pub fn tls(mut self, tls: &RequestConfigTls) -> LuaResult<Self> {
+        if let Some(ca) = &tls.ca {
+            let certs = reqwest::Certificate::from_pem_bundle(ca).map_err(|e| {
+                LuaError::RuntimeError(format!("Invalid CA certificate bundle - {e}"))
+            })?;
+            if certs.is_empty() {
+                return Err(LuaError::runtime(
+                    "Invalid CA certificate bundle - no certificates found",
+                ));
+            }
+            for cert in certs {
+                self.builder = self.builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(cert) = &tls.cert {
+            // NOTE: The identity must be given as a single buffer containing
+            // both the certificate(s) and the private key, in any order
+            let mut pem = cert.clone();
+            if let Some(key) = &tls.key {
+                pem.push(b'\n');
+                pem.extend_from_slice(key);
+            }
+            let identity = reqwest::Identity::from_pem(&pem).map_err(|e| {
+                LuaError::RuntimeError(format!("Invalid client certificate or key - {e}"))
+            })?;
+            self.builder = self.builder.identity(identity);
+        }
+        if tls.insecure {
+            self.builder = self.builder.danger_accept_invalid_certs(true);
+        }
+        Ok(self)
+    }
+
+    pub fn proxy(mut self, url: &str) -> LuaResult<Self> {
+        // NOTE: SOCKS proxies are not supported by reqwest itself, without
+        // an extra feature, so requests are sent through a local bridge instead
+        if let Some(socks) = SocksProxy::parse(url)? {
+            self.socks = Some(socks);
+            return Ok(self);
+        }
+        let proxy = reqwest::Proxy::all(url)
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid proxy url '{url}' - {e}")))?;
+        self.builder = self.builder.proxy(proxy);
+        Ok(self)
+    }
+
+    pub fn redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    pub fn host_policy(mut self, policy: Option<Arc<HostPolicy>>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn host_overrides(mut self, overrides: Option<Arc<HostOverrides>>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    fn resolver(mut self) -> Self {
+        // NOTE: With a host policy, our own resolver checks every address that hosts
+        // resolve to, and handles host overrides too, since reqwest would otherwise
+        // return the overridden addresses without ever calling our resolver for them
+        if let Some(policy) = &self.policy {
+            self.builder = self.builder.dns_resolver(Arc::new(PolicyResolver {
+                overrides: self.overrides.clone(),
+                policy: Arc::clone(policy),
+            }));
+            return self;
+        }
+        for (host, addrs) in self.overrides.iter().flat_map(|overrides| overrides.iter()) {
+            // NOTE: The port is ignored by reqwest, which always uses the port from the url,
+            // and hostnames are matched exactly, but urls are already lowercased when parsed,
+            // so only the fully qualified form with a trailing dot needs to be added here
+            let addrs = addrs
+                .iter()
+                .map(|addr| SocketAddr::new(*addr, 0))
+                .collect::<Vec<_>>();
+            self.builder = self
+                .builder
+                .resolve_to_addrs(host, &addrs)
+                .resolve_to_addrs(&format!("{host}."), &addrs);
+        }
+        self
+    }
+
+    pub fn build(mut self) -> LuaResult<NetClient> {
+        self = self.resolver();
+
+        let socks = match self.socks.take() {
+            None => None,
+            Some(socks) => {
+                let bridge = SocksBridge::start(socks, self.overrides.clone(), self.policy.clone())
+                    .map_err(|e| {
+                        LuaError::RuntimeError(format!("Failed to start SOCKS proxy - {e}"))
+                    })?;
+                let proxy = reqwest::Proxy::all(bridge.url()).into_lua_err()?;
+                self.builder = self.builder.proxy(proxy);
+                Some(Arc::new(bridge))
+            }
+        };
+
+        // NOTE: Redirects may lead to any host, so with a host policy
+        // every url that is redirected to must also be checked against it
+        let redirect = match (self.follow_redirects, &self.policy) {
+            (false, _) => RedirectPolicy::none(),
+            (true, None) => RedirectPolicy::default(),
+            (true, Some(policy)) => {
+                let policy = Arc::clone(policy);
+                RedirectPolicy::custom(move |attempt| {
+                    if attempt.previous().len() > MAX_REDIRECTS {
+                        attempt.error(format!(
+                            "Too many redirects - exceeded the maximum of {MAX_REDIRECTS}"
+                        ))
+                    } else if let Err(e) = policy.check_url(attempt.url()) {
+                        attempt.error(e)
+                    } else {
+                        attempt.follow()
+                    }
+                })
+            }
+        };
+        let client = self.builder.redirect(redirect).build().into_lua_err()?;
+        Ok(NetClient {
+            inner: client,
+            policy: self.policy,
+            _socks: socks,
+        })
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct NetClient {
     inner: reqwest::Client,
+    policy: Option<Arc<HostPolicy>>,
+    // NOTE: Kept only so that the bridge for a SOCKS proxy
+    // keeps running for as long as the client is being used
+    _socks: Option<Arc<SocksBridge>>,
 }
 
 impl NetClient {
@@ -59,49 +223,79 @@ impl NetClient {
             .expect("Failed to store NetClient in lua registry");
     }
 
-    pub async fn request(&self, config: RequestConfig) -> LuaResult<NetClientResponse> {
-        // Create and send the request
-        let mut request = self.inner.request(config.method, config.url);
-        for (query, values) in config.query {
-            request = request.query(
-                &values
-                    .iter()
-                    .map(|v| (query.as_str(), v))
-                    .collect::<Vec<_>>(),
-            );
-        }
-        for (header, values) in config.headers {
-            for value in values {
-                request = request.header(header.as_str(), value);
+    pub async fn request(
+        &self,
+        config: RequestConfig,
+        mut body: NetClientBody,
+        jar: Option<&CookieJar>,
+    ) -> LuaResult<NetClientResponse> {
+        // Create and send the request, retrying with an increasing delay if necessary
+        let retry = &config.options.retry;
+        let mut delay = retry.delay;
+        let mut attempt = 0;
+        let res = loop {
+            let request_body = body.create().ok_or_else(|| {
+                LuaError::runtime(
+                    "Request body can not be sent again - streamed bodies can not be retried",
+                )
+            })?;
+            let request = self.build_request(&config, request_body)?;
+            self.check_policy(request.url())?;
+            let res = match jar {
+                Some(jar) => self.execute_with_cookies(request, jar).await,
+                None => self.inner.execute(request).await.into_lua_err(),
+            };
+
+            let should_retry = attempt < retry.retries
+                && match &res {
+                    Ok(res) => retry.on_status.contains(&res.status().as_u16()),
+                    Err(e) => retry.on_network_error && is_network_error(e),
+                };
+            if !should_retry {
+                break res?;
             }
-        }
-        let res = request
-            .body(config.body.unwrap_or_default())
-            .send()
-            .await
-            .into_lua_err()?;
+
+            sleep(delay).await;
+            delay = delay.saturating_mul(2);
+            attempt += 1;
+        };
 
         // Extract status, headers
         let res_status = res.status().as_u16();
         let res_status_text = res.status().canonical_reason();
         let res_headers = res.headers().clone();
 
+        let res_content_encoding = res_headers
+            .get(CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+
+        // Streamed responses are read in chunks from lua, and never decompressed
+        if config.options.stream {
+            return Ok(NetClientResponse {
+                ok: (200..300).contains(&res_status),
+                status_code: res_status,
+                status_message: res_status_text.unwrap_or_default().to_string(),
+                headers: res_headers,
+                content_encoding: res_content_encoding,
+                body: NetClientResponseBody::Stream(res),
+                body_decompressed: false,
+            });
+        }
+
         // Read response bytes
         let mut res_bytes = res.bytes().await.into_lua_err()?.to_vec();
         let mut res_decompressed = false;
 
         // Check for extra options, decompression
         if config.options.decompress {
-            let decompress_format = res_headers
-                .iter()
-                .find(|(name, _)| {
-                    name.as_str()
-                        .eq_ignore_ascii_case(CONTENT_ENCODING.as_str())
-                })
-                .and_then(|(_, value)| value.to_str().ok())
-                .and_then(CompressDecompressFormat::detect_from_header_str);
-            if let Some(format) = decompress_format {
-                res_bytes = decompress(res_bytes, format).await?;
+            let decompress_formats = res_content_encoding
+                .as_deref()
+                .and_then(parse_content_encoding);
+            if let Some(formats) = decompress_formats {
+                for format in formats {
+                    res_bytes = decompress(res_bytes, format).await?;
+                }
                 res_decompressed = true;
             }
         }
@@ -111,12 +305,129 @@ impl NetClient {
             status_code: res_status,
             status_message: res_status_text.unwrap_or_default().to_string(),
             headers: res_headers,
-            body: res_bytes,
+            content_encoding: res_content_encoding,
+            body: NetClientResponseBody::Bytes(res_bytes),
             body_decompressed: res_decompressed,
         })
     }
 }
 
+impl NetClient {
+    fn check_policy(&self, url: &reqwest::Url) -> LuaResult<()> {
+        match &self.policy {
+            Some(policy) => Ok(policy.check_url(url)?),
+            None => Ok(()),
+        }
+    }
+
+    fn build_request(
+        &self,
+        config: &RequestConfig,
+        body: reqwest::Body,
+    ) -> LuaResult<reqwest::Request> {
+        let mut request = self
+            .inner
+            .request(config.method.clone(), config.url.as_str());
+        for (query, values) in &config.query {
+            request = request.query(
+                &values
+                    .iter()
+                    .map(|v| (query.as_str(), v))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        for (header, values) in &config.headers {
+            for value in values {
+                request = request.header(header.as_str(), value);
+            }
+        }
+        request.body(body).build().into_lua_err()
+    }
+
+    /**
+        Executes a request, storing and sending cookies using the given cookie jar.
+
+        Redirects are followed manually here, instead of by reqwest, so that any
+        cookies set by redirect responses are stored and sent to the next url.
+        This means that the client must have been created with redirects disabled.
+    */
+    async fn execute_with_cookies(
+        &self,
+        mut request: reqwest::Request,
+        jar: &CookieJar,
+    ) -> LuaResult<reqwest::Response> {
+        let mut headers = request.headers().clone();
+        for _ in 0..=MAX_REDIRECTS {
+            *request.headers_mut() = headers.clone();
+            if let Some(cookies) = jar.header_for(request.url()) {
+                let value = match headers.get(COOKIE).and_then(|v| v.to_str().ok()) {
+                    Some(existing) => format!("{existing}; {cookies}"),
+                    None => cookies,
+                };
+                let value = HeaderValue::from_str(&value).into_lua_err()?;
+                request.headers_mut().insert(COOKIE, value);
+            }
+
+            let url = request.url().clone();
+            let method = request.method().clone();
+            let replay = request.try_clone();
+
+            let res = self.inner.execute(request).await.into_lua_err()?;
+            jar.store_from_headers(&url, res.headers());
+
+            let next_url = res
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| url.join(location).ok());
+            let Some(next_url) = next_url else {
+                return Ok(res);
+            };
+
+            request = match res.status() {
+                // Method and body must be kept, which is not possible for streamed bodies
+                StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => match replay {
+                    Some(replay) => replay,
+                    None => return Ok(res),
+                },
+                // Anything other than GET or HEAD turns into GET, without a body
+                StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND | StatusCode::SEE_OTHER => {
+                    for name in [
+                        CONTENT_TYPE,
+                        CONTENT_LENGTH,
+                        CONTENT_ENCODING,
+                        TRANSFER_ENCODING,
+                    ] {
+                        headers.remove(name);
+                    }
+                    let method = if method == Method::HEAD {
+                        Method::HEAD
+                    } else {
+                        Method::GET
+                    };
+                    reqwest::Request::new(method, next_url.clone())
+                }
+                _ => return Ok(res),
+            };
+
+            // Sensitive headers must not be sent along to other hosts
+            if next_url.host_str() != url.host_str()
+                || next_url.port_or_known_default() != url.port_or_known_default()
+            {
+                for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE] {
+                    headers.remove(name);
+                }
+            }
+
+            self.check_policy(&next_url)?;
+            *request.url_mut() = next_url;
+        }
+        Err(LuaError::RuntimeError(format!(
+            "Too many redirects - exceeded the maximum of {MAX_REDIRECTS}"
+        )))
+    }
+}
+
 impl LuaUserData for NetClient {}
 
 impl FromLua<'_> for NetClient {
@@ -138,18 +449,212 @@ impl From<&Lua> for NetClient {
     }
 }
 
+/**
+    A request body, which may be created more than once if the request is retried.
+*/
+pub enum NetClientBody {
+    Bytes(Vec<u8>),
+    Multipart(MultipartBody),
+    Stream(Option<reqwest::Body>),
+}
+
+impl NetClientBody {
+    fn create(&mut self) -> Option<reqwest::Body> {
+        match self {
+            Self::Bytes(bytes) => Some(reqwest::Body::from(bytes.clone())),
+            Self::Multipart(multipart) => Some(multipart.create_body()),
+            Self::Stream(body) => body.take(),
+        }
+    }
+}
+
+// Encodings are listed in the order they were applied, so they must be decompressed in reverse,
+// and if any one of them is unknown we can not decompress the body back to its original form
+fn parse_content_encoding(header: &str) -> Option<Vec<CompressDecompressFormat>> {
+    let formats = header
+        .split(',')
+        .map(str::trim)
+        .filter(|encoding| !encoding.is_empty() && !encoding.eq_ignore_ascii_case("identity"))
+        .rev()
+        .map(CompressDecompressFormat::detect_from_header_str)
+        .collect::<Option<Vec<_>>>()?;
+    if formats.is_empty() {
+        None
+    } else {
+        Some(formats)
+    }
+}
+
+// Errors when connecting or sending a request may be temporary, but
+// errors such as invalid urls or failing to decode a response are not
+fn is_network_error(err: &LuaError) -> bool {
+    if let LuaError::ExternalError(err) = err {
+        err.downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request())
+    } else {
+        false
+    }
+}
+
+pub enum NetClientResponseBody {
+    Bytes(Vec<u8>),
+    Stream(reqwest::Response),
+}
+
+/**
+    A streamed response body, which may be read either as raw chunks
+    or as server-sent events - any bytes that were received but not yet
+    decoded as events are returned first when reading the next chunk.
+*/
+pub struct NetClientResponseStream {
+    res: reqwest::Response,
+    decoder: SseDecoder,
+}
+
+impl NetClientResponseStream {
+    async fn read_chunk(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        if let Some(buffered) = self.decoder.take_buffered() {
+            return Ok(Some(buffered));
+        }
+        let chunk = self.res.chunk().await.into_lua_err()?;
+        Ok(chunk.map(|c| c.to_vec()))
+    }
+
+    async fn read_event(&mut self) -> LuaResult<Option<SseEvent>> {
+        loop {
+            if let Some(event) = self.decoder.next_event() {
+                return Ok(Some(event));
+            }
+            // NOTE: Any partial event left once the body ends is discarded
+            match self.res.chunk().await.into_lua_err()? {
+                Some(chunk) => self.decoder.push(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
 pub struct NetClientResponse {
     ok: bool,
     status_code: u16,
     status_message: String,
     headers: HeaderMap,
-    body: Vec<u8>,
+    content_encoding: Option<String>,
+    body: NetClientResponseBody,
     body_decompressed: bool,
 }
 
+/**
+    The body of a response, along with what is needed to decompress and decode it,
+    which is shared by the `json`, `text` and `bytes` functions of a response.
+*/
+#[derive(Clone)]
+struct NetClientResponseContent {
+    source: NetClientResponseContentSource,
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    decompressed: bool,
+}
+
+#[derive(Clone)]
+enum NetClientResponseContentSource {
+    Bytes(Arc<Vec<u8>>),
+    Stream(Arc<AsyncMutex<NetClientResponseStream>>),
+}
+
+impl NetClientResponseContent {
+    /**
+        Reads the full body, decompressing it if that was not already done.
+
+        Streamed bodies are read until they end, starting from wherever they
+        were left off, so anything that was already read is not included.
+    */
+    async fn bytes(&self, lua: &Lua) -> LuaResult<Vec<u8>> {
+        let mut bytes = match &self.source {
+            NetClientResponseContentSource::Bytes(bytes) => bytes.to_vec(),
+            NetClientResponseContentSource::Stream(stream) => {
+                let stream = Arc::clone(stream);
+                lua.spawn(async move {
+                    let mut stream = stream.lock().await;
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = stream.read_chunk().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    Ok::<_, LuaError>(bytes)
+                })
+                .await?
+            }
+        };
+        if !self.decompressed {
+            let formats = self
+                .content_encoding
+                .as_deref()
+                .and_then(parse_content_encoding);
+            for format in formats.unwrap_or_default() {
+                bytes = decompress(bytes, format).await?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /**
+        Reads the full body as text, decoding it from the given encoding, or from the
+        charset of the content type if no encoding was given, and otherwise from utf-8.
+    */
+    async fn text(&self, lua: &Lua, encoding: Option<String>) -> LuaResult<String> {
+        let encoding = match encoding {
+            Some(label) => Some(Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+                LuaError::RuntimeError(format!("Unknown text encoding '{label}'"))
+            })?),
+            // NOTE: An unknown charset in the response is not the fault of
+            // the script, so we fall back to utf-8 instead of throwing
+            None => self
+                .content_type
+                .as_deref()
+                .and_then(parse_charset)
+                .and_then(|charset| Encoding::for_label(charset.as_bytes())),
+        };
+        let bytes = self.bytes(lua).await?;
+        let (text, _, _) = encoding.unwrap_or(UTF_8).decode(&bytes);
+        Ok(text.into_owned())
+    }
+}
+
+// Content types may contain parameters, such as "text/html; charset=ISO-8859-1"
+fn parse_charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name.trim().eq_ignore_ascii_case("charset") {
+            Some(value.trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+// Functions on responses may be called using either a colon or a dot,
+// and when using a colon, the first argument is the response table itself
+fn skip_self(mut args: LuaMultiValue) -> LuaMultiValue {
+    if matches!(args.iter().next(), Some(LuaValue::Table(_))) {
+        args.pop_front();
+    }
+    args
+}
+
 impl NetClientResponse {
     pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
-        TableBuilder::new(lua)?
+        let content_type = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+        let mut content = NetClientResponseContent {
+            source: NetClientResponseContentSource::Bytes(Arc::new(Vec::new())),
+            content_type,
+            content_encoding: self.content_encoding.clone(),
+            decompressed: self.body_decompressed,
+        };
+        let builder = TableBuilder::new(lua)?
             .with_value("ok", self.ok)?
             .with_value("statusCode", self.status_code)?
             .with_value("statusMessage", self.status_message)?
@@ -157,7 +662,71 @@ impl NetClientResponse {
                 "headers",
                 header_map_to_table(lua, self.headers, self.body_decompressed)?,
             )?
-            .with_value("body", lua.create_string(&self.body)?)?
+            .with_value("contentEncoding", self.content_encoding)?;
+        let builder = match self.body {
+            NetClientResponseBody::Bytes(bytes) => {
+                let builder = builder.with_value("body", lua.create_string(&bytes)?)?;
+                content.source = NetClientResponseContentSource::Bytes(Arc::new(bytes));
+                builder
+            }
+            NetClientResponseBody::Stream(res) => {
+                let stream = Arc::new(AsyncMutex::new(NetClientResponseStream {
+                    res,
+                    decoder: SseDecoder::default(),
+                }));
+                content.source = NetClientResponseContentSource::Stream(Arc::clone(&stream));
+                let stream_events = Arc::clone(&stream);
+                builder
+                    .with_value("body", "")?
+                    .with_async_function("readChunk", move |lua, (): ()| {
+                        let stream = Arc::clone(&stream);
+                        async move {
+                            // NOTE: We read as a background task to free up resources in lua
+                            let chunk = lua
+                                .spawn(async move { stream.lock().await.read_chunk().await })
+                                .await?;
+                            chunk.map(|c| lua.create_string(&c)).transpose()
+                        }
+                    })?
+                    .with_async_function("readEvent", move |lua, (): ()| {
+                        let stream = Arc::clone(&stream_events);
+                        async move {
+                            lua.spawn(async move { stream.lock().await.read_event().await })
+                                .await
+                        }
+                    })?
+            }
+        };
+
+        let content_json = content.clone();
+        let content_text = content.clone();
+        builder
+            .with_async_function("json", move |lua, args: LuaMultiValue| {
+                let content = content_json.clone();
+                async move {
+                    let () = FromLuaMulti::from_lua_multi(skip_self(args), lua)?;
+                    let text = content.text(lua, None).await?;
+                    decode(
+                        text,
+                        lua,
+                        EncodeDecodeConfig::from(EncodeDecodeFormat::Json),
+                    )
+                }
+            })?
+            .with_async_function("text", move |lua, args: LuaMultiValue| {
+                let content = content_text.clone();
+                async move {
+                    let encoding = FromLuaMulti::from_lua_multi(skip_self(args), lua)?;
+                    content.text(lua, encoding).await
+                }
+            })?
+            .with_async_function("bytes", move |lua, args: LuaMultiValue| {
+                let content = content.clone();
+                async move {
+                    let () = FromLuaMulti::from_lua_multi(skip_self(args), lua)?;
+                    lua.create_buffer(content.bytes(lua).await?)
+                }
+            })?
             .build_readonly()
     }
 }
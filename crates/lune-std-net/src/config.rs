@@ -1,6 +1,8 @@
 use std::{
     collections::HashMap,
-    net::{IpAddr, Ipv4Addr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
 };
 
 use bstr::{BString, ByteSlice};
@@ -10,7 +12,7 @@ use reqwest::Method;
 
 use super::util::table_to_hash_map;
 
-const DEFAULT_IP_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+pub(crate) const DEFAULT_IP_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
 const WEB_SOCKET_UPDGRADE_REQUEST_HANDLER: &str = r#"
 return {
@@ -24,19 +26,161 @@ return {
 
 // Net request config
 
+#[derive(Debug, Clone, Default)]
+pub struct RequestConfigTls {
+    pub ca: Option<Vec<u8>>,
+    pub cert: Option<Vec<u8>>,
+    pub key: Option<Vec<u8>>,
+    pub insecure: bool,
+}
+
+impl<'lua> FromLua<'lua> for RequestConfigTls {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Table(tab) = value {
+            let get_pem = |key: &str| match tab.get::<_, Option<BString>>(key) {
+                Ok(pem) => Ok(pem.map(|p| p.as_bytes().to_owned())),
+                Err(_) => Err(LuaError::RuntimeError(format!(
+                    "Invalid option value for '{key}' in request tls options"
+                ))),
+            };
+            let ca = get_pem("ca")?;
+            let cert = get_pem("cert")?;
+            let key = get_pem("key")?;
+            if key.is_some() && cert.is_none() {
+                return Err(LuaError::RuntimeError(
+                    "Invalid request tls options - 'key' was given without 'cert'".to_string(),
+                ));
+            }
+            let insecure = match tab.get::<_, Option<bool>>("insecure") {
+                Ok(insecure) => Ok(insecure.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'insecure' in request tls options".to_string(),
+                )),
+            }?;
+            Ok(Self {
+                ca,
+                cert,
+                key,
+                insecure,
+            })
+        } else {
+            // Anything else is invalid
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "RequestConfigTls",
+                message: Some(format!(
+                    "Invalid request tls options - expected table, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
+
+// Statuses that are usually temporary, and worth retrying by default
+const DEFAULT_RETRY_STATUSES: [u16; 6] = [408, 429, 500, 502, 503, 504];
+
+#[derive(Debug, Clone)]
+pub struct RequestConfigRetry {
+    pub retries: u32,
+    pub delay: Duration,
+    pub on_network_error: bool,
+    pub on_status: Vec<u16>,
+}
+
+impl RequestConfigRetry {
+    fn from_options_table(tab: &LuaTable, lua: &Lua) -> LuaResult<Self> {
+        let retries = match tab.get::<_, Option<u32>>("retries") {
+            Ok(retries) => Ok(retries.unwrap_or_default()),
+            Err(_) => Err(LuaError::RuntimeError(
+                "Invalid option value for 'retries' in request config options".to_string(),
+            )),
+        }?;
+        let delay = match tab.get::<_, Option<f64>>("retryDelay") {
+            Ok(None) => Ok(Duration::from_secs(1)),
+            Ok(Some(secs)) => Duration::try_from_secs_f64(secs).map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "Invalid option value for 'retryDelay' in request config options - expected a positive number, got {secs}"
+                ))
+            }),
+            Err(_) => Err(LuaError::RuntimeError(
+                "Invalid option value for 'retryDelay' in request config options".to_string(),
+            )),
+        }?;
+        let (on_network_error, on_status) = match tab.get::<_, Option<LuaTable>>("retryOn") {
+            Ok(None) => (true, DEFAULT_RETRY_STATUSES.to_vec()),
+            Ok(Some(on)) => {
+                let mut on_network_error = false;
+                let mut on_status = Vec::new();
+                for value in on.sequence_values::<LuaValue>() {
+                    let value = value?;
+                    if matches!(&value, LuaValue::String(s) if s == "network") {
+                        on_network_error = true;
+                        continue;
+                    }
+                    match u16::from_lua(value.clone(), lua) {
+                        Ok(status) if (100..=999).contains(&status) => on_status.push(status),
+                        _ => {
+                            return Err(LuaError::RuntimeError(format!(
+                                "Invalid value in 'retryOn' in request config options - expected a status code or \"network\", got {}",
+                                value.type_name()
+                            )))
+                        }
+                    }
+                }
+                (on_network_error, on_status)
+            }
+            Err(_) => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid option value for 'retryOn' in request config options".to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            retries,
+            delay,
+            on_network_error,
+            on_status,
+        })
+    }
+}
+
+impl Default for RequestConfigRetry {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            delay: Duration::from_secs(1),
+            on_network_error: true,
+            on_status: DEFAULT_RETRY_STATUSES.to_vec(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RequestConfigOptions {
     pub decompress: bool,
+    pub stream: bool,
+    pub tls: Option<RequestConfigTls>,
+    pub proxy: Option<String>,
+    pub cookies: bool,
+    pub retry: RequestConfigRetry,
 }
 
 impl Default for RequestConfigOptions {
     fn default() -> Self {
-        Self { decompress: true }
+        Self {
+            decompress: true,
+            stream: false,
+            tls: None,
+            proxy: None,
+            cookies: false,
+            retry: RequestConfigRetry::default(),
+        }
     }
 }
 
 impl<'lua> FromLua<'lua> for RequestConfigOptions {
-    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         if let LuaValue::Nil = value {
             // Nil means default options
             Ok(Self::default())
@@ -48,7 +192,37 @@ impl<'lua> FromLua<'lua> for RequestConfigOptions {
                     "Invalid option value for 'decompress' in request config options".to_string(),
                 )),
             }?;
-            Ok(Self { decompress })
+            let stream = match tab.get::<_, Option<bool>>("stream") {
+                Ok(stream) => Ok(stream.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'stream' in request config options".to_string(),
+                )),
+            }?;
+            let tls = match tab.get::<_, LuaValue>("tls")? {
+                LuaValue::Nil => None,
+                value => Some(RequestConfigTls::from_lua(value, lua)?),
+            };
+            let proxy = match tab.get::<_, Option<String>>("proxy") {
+                Ok(proxy) => Ok(proxy),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'proxy' in request config options".to_string(),
+                )),
+            }?;
+            let cookies = match tab.get::<_, Option<bool>>("cookies") {
+                Ok(cookies) => Ok(cookies.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'cookies' in request config options".to_string(),
+                )),
+            }?;
+            let retry = RequestConfigRetry::from_options_table(&tab, lua)?;
+            Ok(Self {
+                decompress,
+                stream,
+                tls,
+                proxy,
+                cookies,
+                retry,
+            })
         } else {
             // Anything else is invalid
             Err(LuaError::FromLuaConversionError {
@@ -64,12 +238,111 @@ impl<'lua> FromLua<'lua> for RequestConfigOptions {
 }
 
 #[derive(Debug, Clone)]
+pub enum RequestConfigMultipartContent {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestConfigMultipartPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub content: RequestConfigMultipartContent,
+}
+
+impl<'lua> FromLua<'lua> for RequestConfigMultipartPart {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Table(tab) = value {
+            let name = match tab.get::<_, Option<String>>("name") {
+                Ok(Some(name)) => Ok(name),
+                _ => Err(LuaError::runtime("Missing 'name' in multipart part")),
+            }?;
+            let get_string = |key: &str| match tab.get::<_, Option<String>>(key) {
+                Ok(value) => Ok(value),
+                Err(_) => Err(LuaError::RuntimeError(format!(
+                    "Invalid value for '{key}' in multipart part '{name}' - expected string"
+                ))),
+            };
+            let filename = get_string("filename")?;
+            let content_type = get_string("contentType")?;
+            let value = match tab.get::<_, Option<BString>>("value") {
+                Ok(value) => Ok(value),
+                Err(_) => Err(LuaError::RuntimeError(format!(
+                    "Invalid value for 'value' in multipart part '{name}' - expected string"
+                ))),
+            }?;
+            let content = match (value, get_string("file")?) {
+                (Some(value), None) => RequestConfigMultipartContent::Bytes(value.into()),
+                (None, Some(file)) => RequestConfigMultipartContent::File(PathBuf::from(file)),
+                (Some(_), Some(_)) => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Multipart part '{name}' must not have both 'value' and 'file'"
+                    )))
+                }
+                (None, None) => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Multipart part '{name}' must have either 'value' or 'file'"
+                    )))
+                }
+            };
+            Ok(Self {
+                name,
+                filename,
+                content_type,
+                content,
+            })
+        } else {
+            // Anything else is invalid
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "RequestConfigMultipartPart",
+                message: Some(format!(
+                    "Invalid multipart part - expected table, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RequestConfigBody {
+    Bytes(Vec<u8>),
+    Producer(LuaRegistryKey),
+    Multipart(Vec<RequestConfigMultipartPart>),
+}
+
+impl RequestConfigBody {
+    fn from_lua_table(tab: &LuaTable) -> LuaResult<Self> {
+        let kind = match tab.get::<_, Option<String>>("kind") {
+            Ok(Some(kind)) => Ok(kind),
+            _ => Err(LuaError::runtime("Missing 'kind' in request body")),
+        }?;
+        match kind.as_str() {
+            "multipart" => {
+                let parts = match tab.get::<_, Vec<RequestConfigMultipartPart>>("parts") {
+                    Ok(parts) => Ok(parts),
+                    Err(e) => Err(LuaError::RuntimeError(format!(
+                        "Invalid 'parts' in multipart request body\n{e}"
+                    ))),
+                }?;
+                Ok(Self::Multipart(parts))
+            }
+            _ => Err(LuaError::RuntimeError(format!(
+                "Invalid request body kind '{kind}'"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct RequestConfig {
     pub url: String,
     pub method: Method,
     pub query: HashMap<String, Vec<String>>,
     pub headers: HashMap<String, Vec<String>>,
-    pub body: Option<Vec<u8>>,
+    pub body: Option<RequestConfigBody>,
     pub options: RequestConfigOptions,
 }
 
@@ -107,10 +380,19 @@ impl FromLua<'_> for RequestConfig {
                 Ok(tab) => table_to_hash_map(tab, "headers")?,
                 Err(_) => HashMap::new(),
             };
-            // Extract body
-            let body = match tab.get::<_, BString>("body") {
-                Ok(config_body) => Some(config_body.as_bytes().to_owned()),
-                Err(_) => None,
+            // Extract body, which may also be a function producing chunks, or
+            // a table describing a body that should be encoded, such as multipart
+            let body = if let LuaValue::Function(f) = tab.get::<_, LuaValue>("body")? {
+                Some(RequestConfigBody::Producer(lua.create_registry_value(f)?))
+            } else if let LuaValue::Table(t) = tab.get::<_, LuaValue>("body")? {
+                Some(RequestConfigBody::from_lua_table(&t)?)
+            } else {
+                match tab.get::<_, BString>("body") {
+                    Ok(config_body) => {
+                        Some(RequestConfigBody::Bytes(config_body.as_bytes().to_owned()))
+                    }
+                    Err(_) => None,
+                }
             };
 
             // Convert method string into proper enum
@@ -158,11 +440,102 @@ impl FromLua<'_> for RequestConfig {
 
 // Net serve config
 
+const DEFAULT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+// NOTE: This is the smallest read buffer that hyper allows, and
+// using anything smaller than it would panic when serving
+const MIN_MAX_HEADER_SIZE: usize = 8192;
+
 #[derive(Debug)]
 pub struct ServeConfig<'a> {
     pub address: IpAddr,
     pub handle_request: LuaFunction<'a>,
     pub handle_web_socket: Option<LuaFunction<'a>>,
+    pub max_concurrent_requests: Option<usize>,
+    pub rate_limit: Option<ServeRateLimit>,
+    pub web_socket_compression: Option<WebSocketCompressionConfig>,
+    pub max_body_size: Option<usize>,
+    pub max_header_size: Option<usize>,
+    pub read_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServeRateLimit {
+    pub requests: u32,
+    pub window: Duration,
+}
+
+impl ServeRateLimit {
+    fn from_lua_value(value: LuaValue) -> LuaResult<Option<Self>> {
+        let tab = match value {
+            LuaValue::Nil => return Ok(None),
+            LuaValue::Table(tab) => tab,
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid option value for 'rateLimit' in serve config - expected a table"
+                        .to_string(),
+                ))
+            }
+        };
+        let requests = match tab.get::<_, Option<u32>>("requests") {
+            Ok(Some(requests)) if requests > 0 => Ok(requests),
+            _ => Err(LuaError::RuntimeError(
+                "Invalid option value for 'requests' in serve rate limit - expected a positive integer"
+                    .to_string(),
+            )),
+        }?;
+        let window = match tab.get::<_, Option<f64>>("window") {
+            Ok(None) => Ok(DEFAULT_RATE_LIMIT_WINDOW),
+            Ok(Some(secs)) => Duration::try_from_secs_f64(secs)
+                .ok()
+                .filter(|window| !window.is_zero())
+                .ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid option value for 'window' in serve rate limit - expected a positive number, got {secs}"
+                    ))
+                }),
+            Err(_) => Err(LuaError::RuntimeError(
+                "Invalid option value for 'window' in serve rate limit".to_string(),
+            )),
+        }?;
+        Ok(Some(Self { requests, window }))
+    }
+}
+
+/**
+    Reads the `maxBodySize`, `maxHeaderSize`, and `readTimeout` options of a serve config.
+*/
+fn serve_size_limits(t: &LuaTable) -> LuaResult<(Option<usize>, Option<usize>, Option<Duration>)> {
+    let max_body_size = match t.get::<_, Option<usize>>("maxBodySize") {
+        Ok(size) => Ok(size),
+        Err(_) => Err(LuaError::RuntimeError(
+            "Invalid option value for 'maxBodySize' in serve config - expected a non-negative integer"
+                .to_string(),
+        )),
+    }?;
+    let max_header_size = match t.get::<_, Option<usize>>("maxHeaderSize") {
+        Ok(None) => Ok(None),
+        Ok(Some(size)) if size >= MIN_MAX_HEADER_SIZE => Ok(Some(size)),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for 'maxHeaderSize' in serve config - expected an integer of at least {MIN_MAX_HEADER_SIZE}"
+        ))),
+    }?;
+    let read_timeout = match t.get::<_, Option<f64>>("readTimeout") {
+        Ok(None) => Ok(None),
+        Ok(Some(secs)) => Duration::try_from_secs_f64(secs)
+            .ok()
+            .filter(|timeout| !timeout.is_zero())
+            .map(Some)
+            .ok_or_else(|| {
+                LuaError::RuntimeError(format!(
+                    "Invalid option value for 'readTimeout' in serve config - expected a positive number, got {secs}"
+                ))
+            }),
+        Err(_) => Err(LuaError::RuntimeError(
+            "Invalid option value for 'readTimeout' in serve config".to_string(),
+        )),
+    }?;
+    Ok((max_body_size, max_header_size, read_timeout))
 }
 
 impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
@@ -173,6 +546,12 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
                 handle_request: f.clone(),
                 handle_web_socket: None,
                 address: DEFAULT_IP_ADDRESS,
+                max_concurrent_requests: None,
+                rate_limit: None,
+                web_socket_compression: None,
+                max_body_size: None,
+                max_header_size: None,
+                read_timeout: None,
             })
         } else if let LuaValue::Table(t) = &value {
             // Table means custom options
@@ -201,6 +580,21 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
                     None => DEFAULT_IP_ADDRESS,
                 };
 
+                let max_concurrent_requests = match t.get::<_, Option<usize>>("maxConcurrentRequests") {
+                    Ok(Some(0)) | Err(_) => Err(LuaError::RuntimeError(
+                        "Invalid option value for 'maxConcurrentRequests' in serve config - expected a positive integer"
+                            .to_string(),
+                    )),
+                    Ok(max) => Ok(max),
+                }?;
+                let rate_limit = ServeRateLimit::from_lua_value(t.get("rateLimit")?)?;
+                let web_socket_compression = WebSocketCompressionConfig::from_lua_value(
+                    t.get("webSocketCompression")?,
+                    "webSocketCompression",
+                    "serve config",
+                )?;
+                let (max_body_size, max_header_size, read_timeout) = serve_size_limits(t)?;
+
                 Ok(Self {
                     address,
                     handle_request: handle_request.unwrap_or_else(|| {
@@ -209,6 +603,12 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
                             .expect("Failed to create default http responder function")
                     }),
                     handle_web_socket,
+                    max_concurrent_requests,
+                    rate_limit,
+                    web_socket_compression,
+                    max_body_size,
+                    max_header_size,
+                    read_timeout,
                 })
             } else {
                 Err(LuaError::FromLuaConversionError {
@@ -229,3 +629,158 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
         }
     }
 }
+
+// Net socket config
+
+const DEFAULT_COMPRESSION_THRESHOLD: usize = 1024;
+const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketCompressionConfig {
+    pub threshold: usize,
+    pub level: u32,
+}
+
+impl Default for WebSocketCompressionConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            level: DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl WebSocketCompressionConfig {
+    fn from_lua_value(value: LuaValue, key: &str, what: &str) -> LuaResult<Option<Self>> {
+        let tab = match value {
+            LuaValue::Nil | LuaValue::Boolean(false) => return Ok(None),
+            LuaValue::Boolean(true) => return Ok(Some(Self::default())),
+            LuaValue::Table(tab) => tab,
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid option value for '{key}' in {what} - expected a boolean or a table"
+                )))
+            }
+        };
+        let threshold = match tab.get::<_, Option<usize>>("threshold") {
+            Ok(threshold) => Ok(threshold.unwrap_or(DEFAULT_COMPRESSION_THRESHOLD)),
+            Err(_) => Err(LuaError::RuntimeError(
+                "Invalid option value for 'threshold' in web socket compression options - expected a non-negative integer"
+                    .to_string(),
+            )),
+        }?;
+        let level = match tab.get::<_, Option<u32>>("level") {
+            Ok(None) => Ok(DEFAULT_COMPRESSION_LEVEL),
+            Ok(Some(level)) if level <= 9 => Ok(level),
+            _ => Err(LuaError::RuntimeError(
+                "Invalid option value for 'level' in web socket compression options - expected an integer between 0 and 9"
+                    .to_string(),
+            )),
+        }?;
+        Ok(Some(Self { threshold, level }))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SocketConfig {
+    pub compression: Option<WebSocketCompressionConfig>,
+}
+
+impl<'lua> FromLua<'lua> for SocketConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Nil = value {
+            // Nil means default options
+            Ok(Self::default())
+        } else if let LuaValue::Table(tab) = value {
+            let compression = WebSocketCompressionConfig::from_lua_value(
+                tab.get("compression")?,
+                "compression",
+                "socket options",
+            )?;
+            Ok(Self { compression })
+        } else {
+            // Anything else is invalid
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SocketConfig",
+                message: Some(format!(
+                    "Invalid socket options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
+
+// Net resolve config
+
+const DEFAULT_RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct ResolveConfig {
+    pub nameserver: Option<SocketAddr>,
+    pub timeout: Duration,
+}
+
+impl Default for ResolveConfig {
+    fn default() -> Self {
+        Self {
+            nameserver: None,
+            timeout: DEFAULT_RESOLVE_TIMEOUT,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ResolveConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Nil = value {
+            // Nil means default options
+            Ok(Self::default())
+        } else if let LuaValue::Table(tab) = value {
+            let nameserver = match tab.get::<_, Option<String>>("nameserver") {
+                Ok(None) => Ok(None),
+                Ok(Some(addr)) => addr
+                    .parse::<SocketAddr>()
+                    .or_else(|_| addr.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+                    .map(Some)
+                    .map_err(|_| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid option value for 'nameserver' in resolve options - \
+                            expected an IP in the form '1.1.1.1' or '1.1.1.1:53', got '{addr}'"
+                        ))
+                    }),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'nameserver' in resolve options".to_string(),
+                )),
+            }?;
+            let timeout = match tab.get::<_, Option<f64>>("timeout") {
+                Ok(None) => Ok(DEFAULT_RESOLVE_TIMEOUT),
+                Ok(Some(secs)) => Duration::try_from_secs_f64(secs)
+                    .ok()
+                    .filter(|timeout| !timeout.is_zero())
+                    .ok_or_else(|| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid option value for 'timeout' in resolve options - expected a positive number, got {secs}"
+                        ))
+                    }),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'timeout' in resolve options".to_string(),
+                )),
+            }?;
+            Ok(Self {
+                nameserver,
+                timeout,
+            })
+        } else {
+            // Anything else is invalid
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ResolveConfig",
+                message: Some(format!(
+                    "Invalid resolve options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
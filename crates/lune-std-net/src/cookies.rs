@@ -0,0 +1,188 @@
+use std::{
+    cmp::Reverse,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use cookie::Cookie;
+use reqwest::{
+    header::{HeaderMap, SET_COOKIE},
+    Url,
+};
+
+#[derive(Debug, Clone)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_matches
+            && path_match(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+    }
+}
+
+/**
+    A simple cookie jar, storing cookies received in responses
+    and giving back the cookies to send along with requests.
+
+    Cloning a cookie jar gives a handle to the same underlying cookies.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<StoredCookie>>>,
+}
+
+impl CookieJar {
+    /**
+        Stores any cookies set in the given response headers, received from the given url.
+    */
+    pub fn store_from_headers(&self, url: &Url, headers: &HeaderMap) {
+        let Some(host) = url.host_str() else {
+            return;
+        };
+        let host = host.to_ascii_lowercase();
+        let now = SystemTime::now();
+
+        let mut cookies = self.cookies.lock().unwrap();
+        for header in headers.get_all(SET_COOKIE) {
+            let Some(parsed) = header.to_str().ok().and_then(|h| Cookie::parse(h).ok()) else {
+                continue;
+            };
+
+            // Cookies may only be set for the host itself, or a parent domain of it
+            let (domain, host_only) = match parsed.domain() {
+                Some(domain) if !domain.is_empty() => {
+                    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_match(&host, &domain) {
+                        continue;
+                    }
+                    (domain, false)
+                }
+                _ => (host.clone(), true),
+            };
+
+            let path = match parsed.path() {
+                Some(path) if path.starts_with('/') => path.to_string(),
+                _ => default_path(url.path()),
+            };
+
+            // Max-Age takes precedence over Expires, and a
+            // cookie that has already expired is a deletion
+            let expires = if let Some(max_age) = parsed.max_age() {
+                let secs = u64::try_from(max_age.whole_seconds()).unwrap_or_default();
+                Some(now + Duration::from_secs(secs))
+            } else {
+                parsed.expires_datetime().map(|dt| {
+                    let secs = u64::try_from(dt.unix_timestamp()).unwrap_or_default();
+                    UNIX_EPOCH + Duration::from_secs(secs)
+                })
+            };
+
+            let cookie = StoredCookie {
+                name: parsed.name().to_string(),
+                value: parsed.value().to_string(),
+                domain,
+                host_only,
+                path,
+                secure: parsed.secure().unwrap_or_default(),
+                expires,
+            };
+
+            cookies.retain(|c| {
+                c.name != cookie.name || c.domain != cookie.domain || c.path != cookie.path
+            });
+            if !cookie.is_expired(now) {
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    /**
+        Gets all of the cookies that should be sent along with a request to the given url.
+
+        Cookies with longer paths are listed first, as recommended by RFC 6265.
+    */
+    pub fn cookies_for(&self, url: &Url) -> Vec<(String, String)> {
+        let now = SystemTime::now();
+
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|c| !c.is_expired(now));
+
+        let mut matching = cookies
+            .iter()
+            .filter(|c| c.matches(url))
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|c| Reverse(c.path.len()));
+        matching
+            .into_iter()
+            .map(|c| (c.name.clone(), c.value.clone()))
+            .collect()
+    }
+
+    /**
+        Creates the value for a `Cookie` header to send along with a request to the given url.
+
+        Returns `None` if there are no cookies to send.
+    */
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let cookies = self.cookies_for(url);
+        if cookies.is_empty() {
+            None
+        } else {
+            let pairs = cookies
+                .into_iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>();
+            Some(pairs.join("; "))
+        }
+    }
+
+    /**
+        Removes all cookies stored in the cookie jar.
+    */
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+}
+
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || request_path
+            .strip_prefix(cookie_path)
+            .is_some_and(|rest| cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(index) => request_path[..index].to_string(),
+    }
+}
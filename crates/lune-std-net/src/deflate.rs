@@ -0,0 +1,676 @@
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use http::HeaderMap;
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+use tokio_tungstenite::{
+    client_async,
+    tungstenite::{
+        client::{uri_mode, IntoClientRequest},
+        protocol::frame::{
+            coding::{Data as OpData, OpCode},
+            Frame,
+        },
+        stream::Mode,
+        Error as WsError, Message as WsMessage,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{
+    config::WebSocketCompressionConfig,
+    hosts::{connect_tcp, HostOverrides},
+    policy::HostPolicy,
+};
+
+pub(crate) const EXTENSIONS_HEADER: &str = "Sec-WebSocket-Extensions";
+
+const EXTENSION_NAME: &str = "permessage-deflate";
+
+// Every compressed message ends with these bytes, which
+// are left out when sending and added back when receiving
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xFF, 0xFF];
+
+// The same as the default message size limit of tungstenite, which only
+// limits the size of compressed messages, and not what they decompress to
+const MAX_DECOMPRESSED_SIZE: usize = 64 << 20;
+
+/**
+    The parameters of the `permessage-deflate` extension that were agreed on
+    by the client and the server during the handshake, as described in RFC 7692:
+
+    <https://datatracker.ietf.org/doc/html/rfc7692>
+
+    Window sizes are never negotiated down, since the compressor that we use
+    always uses the largest window. Smaller windows used by the other side
+    can always be decompressed, so those are accepted without any changes.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DeflateParams {
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl DeflateParams {
+    /**
+        Picks the first offer of the extension in the headers of a
+        client handshake request that the server is able to accept.
+    */
+    pub(crate) fn from_offers(headers: &HeaderMap) -> Option<Self> {
+        parse_extensions(headers)
+            .filter(|(name, _)| name.eq_ignore_ascii_case(EXTENSION_NAME))
+            .find_map(|(_, params)| {
+                let mut accepted = Self::default();
+                for (index, (key, value)) in params.iter().enumerate() {
+                    if params[..index].iter().any(|(other, _)| other == key) {
+                        return None;
+                    }
+                    match (key.as_str(), value.as_deref()) {
+                        ("server_no_context_takeover", None) => {
+                            accepted.server_no_context_takeover = true;
+                        }
+                        ("client_no_context_takeover", None) => {
+                            accepted.client_no_context_takeover = true;
+                        }
+                        ("server_max_window_bits", Some("15")) => {}
+                        ("client_max_window_bits", None) => {}
+                        ("client_max_window_bits", Some(bits)) if is_window_bits(bits) => {}
+                        _ => return None,
+                    }
+                }
+                Some(accepted)
+            })
+    }
+
+    /**
+        Reads the parameters that the server accepted from the headers of
+        its handshake response, if it accepted the extension at all.
+
+        # Errors
+
+        Errors if the server responded with parameters that a client
+        never offers, or with extensions other than `permessage-deflate`.
+    */
+    pub(crate) fn from_response(headers: &HeaderMap) -> LuaResult<Option<Self>> {
+        let mut accepted = None;
+        for (name, params) in parse_extensions(headers) {
+            if !name.eq_ignore_ascii_case(EXTENSION_NAME) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Web socket server accepted the extension '{name}', which was never offered"
+                )));
+            }
+            if accepted.is_some() {
+                return Err(LuaError::runtime(
+                    "Web socket server accepted compression more than once",
+                ));
+            }
+            let mut params_accepted = Self::default();
+            for (key, value) in params {
+                match (key.as_str(), value.as_deref()) {
+                    ("server_no_context_takeover", None) => {
+                        params_accepted.server_no_context_takeover = true;
+                    }
+                    ("client_no_context_takeover", None) => {
+                        params_accepted.client_no_context_takeover = true;
+                    }
+                    ("server_max_window_bits", Some(bits)) if is_window_bits(bits) => {}
+                    _ => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Web socket server accepted compression with the unsupported parameter '{key}'"
+                        )))
+                    }
+                }
+            }
+            accepted = Some(params_accepted);
+        }
+        Ok(accepted)
+    }
+
+    /**
+        Creates the header value that a server sends to accept these parameters.
+    */
+    pub(crate) fn to_header_value(self) -> String {
+        let mut value = EXTENSION_NAME.to_string();
+        if self.server_no_context_takeover {
+            value.push_str("; server_no_context_takeover");
+        }
+        if self.client_no_context_takeover {
+            value.push_str("; client_no_context_takeover");
+        }
+        value
+    }
+}
+
+fn is_window_bits(bits: &str) -> bool {
+    bits.parse::<u8>()
+        .is_ok_and(|bits| (8..=15).contains(&bits))
+}
+
+fn parse_extensions(
+    headers: &HeaderMap,
+) -> impl Iterator<Item = (String, Vec<(String, Option<String>)>)> + '_ {
+    headers
+        .get_all(EXTENSIONS_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|extension| {
+            let mut parts = extension.split(';').map(str::trim);
+            let name = parts.next().filter(|name| !name.is_empty())?;
+            let params = parts
+                .filter(|param| !param.is_empty())
+                .map(|param| match param.split_once('=') {
+                    Some((key, value)) => (
+                        key.trim().to_ascii_lowercase(),
+                        Some(value.trim().trim_matches('"').to_string()),
+                    ),
+                    None => (param.to_ascii_lowercase(), None),
+                })
+                .collect();
+            Some((name.to_string(), params))
+        })
+}
+
+/**
+    Compresses and decompresses messages for one side of a web socket connection.
+*/
+#[derive(Debug)]
+struct DeflateCodec {
+    compress: Compress,
+    decompress: Decompress,
+    compress_no_context_takeover: bool,
+    decompress_no_context_takeover: bool,
+    threshold: usize,
+}
+
+impl DeflateCodec {
+    fn new(params: DeflateParams, config: WebSocketCompressionConfig, is_server: bool) -> Self {
+        let (local, remote) = if is_server {
+            (
+                params.server_no_context_takeover,
+                params.client_no_context_takeover,
+            )
+        } else {
+            (
+                params.client_no_context_takeover,
+                params.server_no_context_takeover,
+            )
+        };
+        Self {
+            compress: Compress::new(Compression::new(config.level), false),
+            decompress: Decompress::new(false),
+            compress_no_context_takeover: local,
+            decompress_no_context_takeover: remote,
+            threshold: config.threshold,
+        }
+    }
+
+    fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+        loop {
+            if output.capacity() - output.len() < 64 {
+                output.reserve(output.capacity().max(1024));
+            }
+            let consumed = usize::try_from(self.compress.total_in() - start).unwrap_or(usize::MAX);
+            self.compress
+                .compress_vec(&data[consumed..], &mut output, FlushCompress::Sync)
+                .map_err(io::Error::other)?;
+            // NOTE: The flush has finished once all of the input was consumed
+            // and there is still room left in the output, otherwise there
+            // may be more compressed data waiting to be written out
+            let consumed = usize::try_from(self.compress.total_in() - start).unwrap_or(usize::MAX);
+            if consumed >= data.len() && output.len() < output.capacity() {
+                break;
+            }
+        }
+        if output.ends_with(&DEFLATE_TRAILER) {
+            output.truncate(output.len() - DEFLATE_TRAILER.len());
+        }
+        if self.compress_no_context_takeover {
+            self.compress.reset();
+        }
+        Ok(output)
+    }
+
+    fn decompress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut input = Vec::with_capacity(data.len() + DEFLATE_TRAILER.len());
+        input.extend_from_slice(data);
+        input.extend_from_slice(&DEFLATE_TRAILER);
+
+        let mut output = Vec::with_capacity(data.len().saturating_mul(2).max(1024));
+        let start = self.decompress.total_in();
+        loop {
+            if output.len() == output.capacity() {
+                if output.len() >= MAX_DECOMPRESSED_SIZE {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Decompressed web socket message is too large",
+                    ));
+                }
+                output.reserve(output.capacity());
+            }
+            let consumed =
+                usize::try_from(self.decompress.total_in() - start).unwrap_or(usize::MAX);
+            let produced = output.len();
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let now_consumed =
+                usize::try_from(self.decompress.total_in() - start).unwrap_or(usize::MAX);
+            if status == Status::StreamEnd {
+                // NOTE: The other side may end the compressed stream at the end of
+                // a message, and then start a new one for the next message
+                self.decompress.reset(false);
+                break;
+            }
+            if now_consumed >= input.len() && output.len() < output.capacity() {
+                break;
+            }
+            if now_consumed == consumed
+                && output.len() == produced
+                && output.len() < output.capacity()
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Received an invalid compressed web socket message",
+                ));
+            }
+        }
+        if self.decompress_no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(output)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MessageStart {
+    compressed: bool,
+    text: bool,
+}
+
+/**
+    Compression state for a web socket connection that offered or
+    accepted the `permessage-deflate` extension, which is shared
+    between the socket itself and the [`DeflateStream`] below it.
+*/
+#[derive(Debug)]
+pub(crate) struct WebSocketDeflate {
+    messages: Mutex<VecDeque<MessageStart>>,
+    codec: Mutex<Option<DeflateCodec>>,
+}
+
+impl WebSocketDeflate {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            messages: Mutex::new(VecDeque::new()),
+            codec: Mutex::new(None),
+        })
+    }
+
+    fn enable(&self, params: DeflateParams, config: WebSocketCompressionConfig, is_server: bool) {
+        *self.codec.lock().unwrap() = Some(DeflateCodec::new(params, config, is_server));
+    }
+
+    /**
+        Creates compression state for the server side
+        of a connection, using the negotiated parameters.
+    */
+    pub(crate) fn server(params: DeflateParams, config: WebSocketCompressionConfig) -> Arc<Self> {
+        let deflate = Self::new();
+        deflate.enable(params, config, true);
+        deflate
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.codec.lock().unwrap().is_some()
+    }
+
+    /**
+        Compresses a message that is about to be sent, if it is large enough.
+
+        # Errors
+
+        Errors if compressing the message fails.
+    */
+    pub(crate) fn compress(&self, msg: WsMessage) -> LuaResult<WsMessage> {
+        let mut codec = self.codec.lock().unwrap();
+        let Some(codec) = codec.as_mut() else {
+            return Ok(msg);
+        };
+        let (data, opcode) = match &msg {
+            WsMessage::Text(text) => (text.as_bytes(), OpData::Text),
+            WsMessage::Binary(bin) => (bin.as_slice(), OpData::Binary),
+            _ => return Ok(msg),
+        };
+        if data.len() < codec.threshold {
+            return Ok(msg);
+        }
+        let compressed = codec.compress(data).into_lua_err()?;
+        let mut frame = Frame::message(compressed, OpCode::Data(opcode), true);
+        frame.header_mut().rsv1 = true;
+        Ok(WsMessage::Frame(frame))
+    }
+
+    /**
+        Decompresses a message that was just received, if it was compressed.
+
+        # Errors
+
+        Errors if the message is compressed but compression was never
+        agreed on, or if it is not valid compressed data or text.
+    */
+    pub(crate) fn decompress(&self, msg: WsMessage) -> LuaResult<WsMessage> {
+        if !matches!(msg, WsMessage::Text(_) | WsMessage::Binary(_)) {
+            return Ok(msg);
+        }
+        let start = self.messages.lock().unwrap().pop_front();
+        let Some(start) = start.filter(|start| start.compressed) else {
+            return Ok(msg);
+        };
+        let mut codec = self.codec.lock().unwrap();
+        let Some(codec) = codec.as_mut() else {
+            return Err(LuaError::runtime(
+                "Received a compressed web socket message, but compression was never agreed on",
+            ));
+        };
+        let decompressed = codec.decompress(&msg.into_data()).into_lua_err()?;
+        if start.text {
+            let text = String::from_utf8(decompressed).map_err(|_| {
+                LuaError::runtime(
+                    "Received a compressed web socket text message with invalid utf-8",
+                )
+            })?;
+            Ok(WsMessage::Text(text))
+        } else {
+            Ok(WsMessage::Binary(decompressed))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ReadState {
+    Handshake { matched: usize },
+    Header { header: [u8; 14], len: usize },
+    Payload { remaining: u64 },
+}
+
+impl ReadState {
+    const fn header() -> Self {
+        Self::Header {
+            header: [0; 14],
+            len: 0,
+        }
+    }
+}
+
+/**
+    A stream that sits below a web socket, and keeps track of which messages
+    it receives are compressed, since tungstenite does not support any
+    extensions and would otherwise reject them.
+
+    The first frame of each compressed message has its compression bit cleared,
+    and is marked as binary, so that tungstenite does not try to read the
+    compressed data as text. Whether each message was compressed, and
+    whether it was text, is then stored in the [`WebSocketDeflate`] state,
+    which is used to decompress the messages in the order they come in.
+*/
+pub(crate) struct DeflateStream<S> {
+    inner: S,
+    state: ReadState,
+    deflate: Arc<WebSocketDeflate>,
+}
+
+impl<S> DeflateStream<S> {
+    /**
+        Wraps a stream for the server side of a connection,
+        which only ever receives frames once upgraded.
+    */
+    pub(crate) fn server(inner: S, deflate: Arc<WebSocketDeflate>) -> Self {
+        Self {
+            inner,
+            state: ReadState::header(),
+            deflate,
+        }
+    }
+
+    /**
+        Wraps a stream for the client side of a connection, which first
+        receives the handshake response from the server, and then frames.
+    */
+    fn client(inner: S, deflate: Arc<WebSocketDeflate>) -> Self {
+        Self {
+            inner,
+            state: ReadState::Handshake { matched: 0 },
+            deflate,
+        }
+    }
+
+    fn process(&mut self, bytes: &mut [u8]) {
+        const HANDSHAKE_END: &[u8] = b"\r\n\r\n";
+
+        let mut index = 0;
+        while index < bytes.len() {
+            match &mut self.state {
+                ReadState::Handshake { matched } => {
+                    if bytes[index] == HANDSHAKE_END[*matched] {
+                        *matched += 1;
+                    } else {
+                        *matched = usize::from(bytes[index] == b'\r');
+                    }
+                    index += 1;
+                    if *matched == HANDSHAKE_END.len() {
+                        self.state = ReadState::header();
+                    }
+                }
+                ReadState::Header { header, len } => {
+                    if *len == 0 {
+                        bytes[index] = rewrite_first_byte(&self.deflate, bytes[index]);
+                    }
+                    header[*len] = bytes[index];
+                    *len += 1;
+                    index += 1;
+                    if let Some(payload_len) = payload_len(&header[..*len]) {
+                        self.state = if payload_len == 0 {
+                            ReadState::header()
+                        } else {
+                            ReadState::Payload {
+                                remaining: payload_len,
+                            }
+                        };
+                    }
+                }
+                ReadState::Payload { remaining } => {
+                    let available = u64::try_from(bytes.len() - index).unwrap_or(u64::MAX);
+                    let skipped = (*remaining).min(available);
+                    *remaining -= skipped;
+                    index += usize::try_from(skipped).unwrap_or(usize::MAX);
+                    if *remaining == 0 {
+                        self.state = ReadState::header();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+    Marks the start of a message if the byte is the first byte of a data frame,
+    and rewrites the byte so that tungstenite accepts it, if it was compressed.
+*/
+fn rewrite_first_byte(deflate: &WebSocketDeflate, byte: u8) -> u8 {
+    const RSV1: u8 = 0x40;
+    const OPCODE: u8 = 0x0F;
+    const OPCODE_TEXT: u8 = 0x1;
+    const OPCODE_BINARY: u8 = 0x2;
+
+    let opcode = byte & OPCODE;
+    if opcode != OPCODE_TEXT && opcode != OPCODE_BINARY {
+        // NOTE: Continuation and control frames are passed along as they are,
+        // tungstenite rejects them if they have the compression bit set
+        return byte;
+    }
+    let compressed = byte & RSV1 != 0;
+    deflate.messages.lock().unwrap().push_back(MessageStart {
+        compressed,
+        text: opcode == OPCODE_TEXT,
+    });
+    if compressed {
+        (byte & !(RSV1 | OPCODE)) | OPCODE_BINARY
+    } else {
+        byte
+    }
+}
+
+/**
+    Gets the length of the payload of a frame, once all of its header has been read.
+*/
+fn payload_len(header: &[u8]) -> Option<u64> {
+    if header.len() < 2 {
+        return None;
+    }
+    let masked = header[1] & 0x80 != 0;
+    let (len_bytes, short_len) = match header[1] & 0x7F {
+        126 => (2, None),
+        127 => (8, None),
+        len => (0, Some(u64::from(len))),
+    };
+    let header_len = 2 + len_bytes + if masked { 4 } else { 0 };
+    if header.len() < header_len {
+        return None;
+    }
+    Some(short_len.unwrap_or_else(|| {
+        header[2..2 + len_bytes]
+            .iter()
+            .fold(0, |len, byte| (len << 8) | u64::from(*byte))
+    }))
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.process(&mut buf.filled_mut()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+pub(crate) type DeflateClientStream = DeflateStream<MaybeTlsStream<TcpStream>>;
+
+/**
+    Connects to a web socket at the given URL, offering to compress messages.
+
+    This does the same as `tokio_tungstenite::connect_async`, except that the
+    [`DeflateStream`] is placed between the TLS stream and the web socket, and
+    that any overridden addresses for the host are connected to instead, and that
+    all addresses of the host are checked against the host policy, if one is given.
+
+    # Errors
+
+    Errors if connecting fails, or if the server accepted compression with invalid parameters.
+*/
+pub(crate) async fn connect_deflate(
+    url: String,
+    config: WebSocketCompressionConfig,
+    overrides: Option<&HostOverrides>,
+    policy: Option<&HostPolicy>,
+) -> LuaResult<(WebSocketStream<DeflateClientStream>, Arc<WebSocketDeflate>)> {
+    let mut request = url.into_client_request().into_lua_err()?;
+    request.headers_mut().insert(
+        EXTENSIONS_HEADER,
+        EXTENSION_NAME.parse().expect("valid header value"),
+    );
+
+    let mode = uri_mode(request.uri()).into_lua_err()?;
+    let host = request
+        .uri()
+        .host()
+        .ok_or_else(|| LuaError::runtime("Web socket URL is missing a host"))?
+        .to_string();
+    let port = request
+        .uri()
+        .port_u16()
+        .unwrap_or(if matches!(mode, Mode::Tls) { 443 } else { 80 });
+
+    let socket = connect_tcp(
+        overrides,
+        policy,
+        host.trim_start_matches('[').trim_end_matches(']'),
+        port,
+    )
+    .await
+    .map_err(WsError::Io)
+    .into_lua_err()?;
+    socket
+        .set_nodelay(true)
+        .map_err(WsError::Io)
+        .into_lua_err()?;
+
+    let stream = match mode {
+        Mode::Plain => MaybeTlsStream::Plain(socket),
+        Mode::Tls => {
+            let mut roots = RootCertStore::empty();
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            let tls_config = ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            let domain = ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']'))
+                .into_lua_err()?
+                .to_owned();
+            let stream = TlsConnector::from(Arc::new(tls_config))
+                .connect(domain, socket)
+                .await
+                .map_err(WsError::Io)
+                .into_lua_err()?;
+            MaybeTlsStream::Rustls(stream)
+        }
+    };
+
+    let deflate = WebSocketDeflate::new();
+    let stream = DeflateStream::client(stream, Arc::clone(&deflate));
+    let (ws, response) = client_async(request, stream).await.into_lua_err()?;
+    if let Some(params) = DeflateParams::from_response(response.headers())? {
+        deflate.enable(params, config, false);
+    }
+
+    Ok((ws, deflate))
+}
@@ -0,0 +1,497 @@
+use std::{
+    cmp::Reverse,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
+
+use mlua::prelude::*;
+use tokio::{
+    fs::read_to_string,
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream, UdpSocket},
+    time::timeout,
+};
+
+use lune_utils::TableBuilder;
+
+use super::{
+    config::ResolveConfig,
+    error::{NetError, NetErrorKind},
+    hosts::HostOverrides,
+};
+
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+const DNS_PORT: u16 = 53;
+
+// Responses over UDP are limited to 512 bytes without EDNS, but
+// some servers send larger responses anyway, so we accept those too
+const MAX_UDP_RESPONSE_SIZE: usize = 65_535;
+
+// Limit for following compression pointers in names, to not loop forever on malformed responses
+const MAX_NAME_POINTERS: usize = 64;
+
+const CLASS_IN: u16 = 1;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+
+/**
+    The kinds of DNS records that can be resolved.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsRecordType {
+    A,
+    Aaaa,
+    Txt,
+    Srv,
+}
+
+impl DnsRecordType {
+    const fn code(self) -> u16 {
+        match self {
+            Self::A => 1,
+            Self::Aaaa => 28,
+            Self::Txt => 16,
+            Self::Srv => 33,
+        }
+    }
+}
+
+impl FromStr for DnsRecordType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "A" => Ok(Self::A),
+            "AAAA" => Ok(Self::Aaaa),
+            "TXT" => Ok(Self::Txt),
+            "SRV" => Ok(Self::Srv),
+            _ => Err(format!(
+                "Invalid record type '{s}' - expected one of 'A', 'AAAA', 'TXT' or 'SRV'"
+            )),
+        }
+    }
+}
+
+/**
+    A single resolved DNS record.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsRecord {
+    Address(IpAddr),
+    Txt(Vec<u8>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+}
+
+impl<'lua> IntoLua<'lua> for DnsRecord {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Self::Address(addr) => addr.to_string().into_lua(lua),
+            Self::Txt(text) => lua.create_string(text)?.into_lua(lua),
+            Self::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => TableBuilder::new(lua)?
+                .with_value("priority", priority)?
+                .with_value("weight", weight)?
+                .with_value("port", port)?
+                .with_value("target", target)?
+                .build_readonly()?
+                .into_lua(lua),
+        }
+    }
+}
+
+/**
+    Resolves records of the given type for the given hostname.
+
+    Addresses are resolved using the host overrides or the system resolver, unless a
+    nameserver is given, while any other records are queried directly from a nameserver.
+*/
+pub async fn resolve(
+    hostname: &str,
+    record_type: DnsRecordType,
+    config: &ResolveConfig,
+    overrides: Option<&HostOverrides>,
+) -> LuaResult<Vec<DnsRecord>> {
+    let hostname = hostname.trim_end_matches('.');
+    if hostname.is_empty() {
+        return Err(LuaError::runtime("Hostname must not be empty"));
+    }
+
+    let matches = |addr: &IpAddr| match record_type {
+        DnsRecordType::A => addr.is_ipv4(),
+        _ => addr.is_ipv6(),
+    };
+
+    match (record_type, config.nameserver) {
+        (DnsRecordType::A | DnsRecordType::Aaaa, None) => {
+            // NOTE: Overridden hosts never fall back to the system resolver,
+            // even if they have no addresses of the type that was asked for
+            if let Some(addrs) = overrides.and_then(|overrides| overrides.get(hostname)) {
+                return Ok(addrs
+                    .iter()
+                    .filter(|addr| matches(addr))
+                    .map(|addr| DnsRecord::Address(*addr))
+                    .collect());
+            }
+            let addrs = timeout(config.timeout, lookup_host((hostname, 0)))
+                .await
+                .map_err(|_| {
+                    NetError::new(
+                        NetErrorKind::Timeout,
+                        format!("Timed out while resolving '{hostname}'"),
+                    )
+                })?
+                .map_err(|e| {
+                    NetError::new(
+                        NetErrorKind::Dns,
+                        format!("Failed to resolve '{hostname}' - {e}"),
+                    )
+                })?;
+            let mut records = Vec::new();
+            for addr in addrs.map(|addr| addr.ip()) {
+                let record = DnsRecord::Address(addr);
+                if matches(&addr) && !records.contains(&record) {
+                    records.push(record);
+                }
+            }
+            Ok(records)
+        }
+        (_, Some(nameserver)) => query(nameserver, hostname, record_type, config).await?,
+        (_, None) => {
+            let mut last_err = None;
+            for nameserver in system_nameservers().await {
+                match query(nameserver, hostname, record_type, config).await {
+                    Err(e) => last_err = Some(e),
+                    Ok(res) => return res,
+                }
+            }
+            Err(last_err.unwrap_or_else(|| {
+                LuaError::runtime(format!(
+                    "No nameservers were found in '{RESOLV_CONF_PATH}' - \
+                    pass a nameserver in the resolve options instead"
+                ))
+            }))
+        }
+    }
+}
+
+async fn system_nameservers() -> Vec<SocketAddr> {
+    let contents = read_to_string(RESOLV_CONF_PATH).await.unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|addr| {
+            // Link-local addresses may contain a zone index, which we do not support
+            let addr = addr.trim().split('%').next()?;
+            addr.parse::<IpAddr>().ok()
+        })
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .collect()
+}
+
+/**
+    Queries the given nameserver for records of the given type.
+
+    The outer error means that the nameserver did not give a usable response, in which case
+    another nameserver may be tried, while the inner result is the answer from the nameserver.
+*/
+async fn query(
+    nameserver: SocketAddr,
+    hostname: &str,
+    record_type: DnsRecordType,
+    config: &ResolveConfig,
+) -> LuaResult<LuaResult<Vec<DnsRecord>>> {
+    let id = rand::random::<u16>();
+    let message = match encode_query(id, hostname, record_type) {
+        Ok(message) => message,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let query_failed = |kind: NetErrorKind, e: String| {
+        LuaError::from(NetError::new(
+            kind,
+            format!("Failed to query nameserver {nameserver} for '{hostname}' - {e}"),
+        ))
+    };
+
+    let response = timeout(config.timeout, async {
+        let response = query_udp(nameserver, id, &message).await?;
+        // Truncated responses must be queried again over TCP to get all records
+        if response.truncated {
+            query_tcp(nameserver, id, &message).await
+        } else {
+            Ok(response)
+        }
+    })
+    .await
+    .map_err(|_| query_failed(NetErrorKind::Timeout, "timed out".to_string()))?
+    .map_err(|e| query_failed(NetErrorKind::Dns, e))?;
+
+    match response.rcode {
+        0 => {}
+        3 => {
+            return Ok(Err(NetError::new(
+                NetErrorKind::Dns,
+                format!("Failed to resolve '{hostname}' - domain does not exist"),
+            )
+            .into()))
+        }
+        code => {
+            let reason = match code {
+                1 => "format error",
+                2 => "server failure",
+                4 => "not implemented",
+                5 => "refused",
+                _ => "unknown error",
+            };
+            return Ok(Err(NetError::new(
+                NetErrorKind::Dns,
+                format!("Failed to resolve '{hostname}' - nameserver {nameserver} responded with {reason} (code {code})"),
+            )
+            .into()));
+        }
+    }
+
+    let mut records = parse_records(&response.message, record_type)
+        .map_err(|e| query_failed(NetErrorKind::Dns, format!("invalid response, {e}")))?;
+    if record_type == DnsRecordType::Srv {
+        records.sort_by_key(|record| match record {
+            DnsRecord::Srv {
+                priority, weight, ..
+            } => (*priority, Reverse(*weight)),
+            _ => (u16::MAX, Reverse(0)),
+        });
+    }
+    Ok(Ok(records))
+}
+
+async fn query_udp(nameserver: SocketAddr, id: u16, message: &[u8]) -> Result<Response, String> {
+    let local: SocketAddr = if nameserver.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await.map_err(|e| e.to_string())?;
+    socket
+        .connect(nameserver)
+        .await
+        .map_err(|e| e.to_string())?;
+    socket.send(message).await.map_err(|e| e.to_string())?;
+
+    let mut buf = vec![0; MAX_UDP_RESPONSE_SIZE];
+    loop {
+        let len = socket.recv(&mut buf).await.map_err(|e| e.to_string())?;
+        // Ignore any stray datagrams that are not a response to our query
+        if let Some(response) = Response::parse(&buf[..len], id) {
+            return Ok(response);
+        }
+    }
+}
+
+async fn query_tcp(nameserver: SocketAddr, id: u16, message: &[u8]) -> Result<Response, String> {
+    let mut stream = TcpStream::connect(nameserver)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let len = u16::try_from(message.len()).map_err(|e| e.to_string())?;
+    let mut framed = len.to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    stream.write_all(&framed).await.map_err(|e| e.to_string())?;
+
+    let len = stream.read_u16().await.map_err(|e| e.to_string())?;
+    let mut buf = vec![0; usize::from(len)];
+    stream
+        .read_exact(&mut buf)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Response::parse(&buf, id).ok_or_else(|| "received an invalid response".to_string())
+}
+
+fn encode_query(id: u16, hostname: &str, record_type: DnsRecordType) -> LuaResult<Vec<u8>> {
+    let invalid = || LuaError::RuntimeError(format!("Invalid hostname '{hostname}'"));
+    if hostname.len() > 253 {
+        return Err(invalid());
+    }
+
+    let mut message = Vec::with_capacity(18 + hostname.len());
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in hostname.split('.') {
+        let len = u8::try_from(label.len())
+            .ok()
+            .filter(|len| (1..=63).contains(len))
+            .ok_or_else(invalid)?;
+        message.push(len);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&record_type.code().to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+struct Response {
+    message: Vec<u8>,
+    truncated: bool,
+    rcode: u8,
+}
+
+impl Response {
+    fn parse(message: &[u8], id: u16) -> Option<Self> {
+        if message.len() < 12 || message[0..2] != id.to_be_bytes() {
+            return None;
+        }
+        let flags = u16::from_be_bytes([message[2], message[3]]);
+        if flags & FLAG_RESPONSE == 0 {
+            return None;
+        }
+        Some(Self {
+            message: message.to_vec(),
+            truncated: flags & FLAG_TRUNCATED != 0,
+            rcode: message[3] & 0x0F,
+        })
+    }
+}
+
+fn parse_records(message: &[u8], record_type: DnsRecordType) -> Result<Vec<DnsRecord>, String> {
+    let mut reader = Reader::new(message);
+    reader.skip(4)?;
+    let question_count = reader.u16()?;
+    let answer_count = reader.u16()?;
+    reader.skip(4)?;
+
+    for _ in 0..question_count {
+        reader.name()?;
+        reader.skip(4)?;
+    }
+
+    // Answers may also contain other records, such as the
+    // CNAME records that lead to the ones we asked for
+    let mut records = Vec::new();
+    for _ in 0..answer_count {
+        reader.name()?;
+        let kind = reader.u16()?;
+        let class = reader.u16()?;
+        reader.skip(4)?;
+        let len = usize::from(reader.u16()?);
+        let start = reader.pos;
+        let data = reader.bytes(len)?;
+        if kind != record_type.code() || class != CLASS_IN {
+            continue;
+        }
+
+        let record = match record_type {
+            DnsRecordType::A => {
+                let octets: [u8; 4] = data.try_into().map_err(|_| "invalid A record")?;
+                DnsRecord::Address(IpAddr::from(octets))
+            }
+            DnsRecordType::Aaaa => {
+                let octets: [u8; 16] = data.try_into().map_err(|_| "invalid AAAA record")?;
+                DnsRecord::Address(IpAddr::from(octets))
+            }
+            DnsRecordType::Txt => {
+                // Long texts are split into several strings, which we join back together
+                let mut text = Vec::new();
+                let mut data = Reader::new(data);
+                while data.pos < data.message.len() {
+                    let len = usize::from(data.u8()?);
+                    text.extend_from_slice(data.bytes(len)?);
+                }
+                DnsRecord::Txt(text)
+            }
+            DnsRecordType::Srv => {
+                let mut data = Reader::new(message);
+                data.pos = start;
+                DnsRecord::Srv {
+                    priority: data.u16()?,
+                    weight: data.u16()?,
+                    port: data.u16()?,
+                    target: data.name()?,
+                }
+            }
+        };
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(message: &'a [u8]) -> Self {
+        Self { message, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .message
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| "unexpected end of message".to_string())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    // Names may end with a pointer to another name earlier in the message,
+    // in which case the reader continues after the pointer and not the name
+    fn name(&mut self) -> Result<String, String> {
+        let mut labels = Vec::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        let mut pointers = 0;
+        loop {
+            let mut label = Reader {
+                message: self.message,
+                pos,
+            };
+            let len = label.u8()?;
+            match len & 0xC0 {
+                0x00 if len == 0 => {
+                    self.pos = end.unwrap_or(label.pos);
+                    return Ok(labels.join("."));
+                }
+                0x00 => {
+                    let bytes = label.bytes(usize::from(len))?;
+                    labels.push(String::from_utf8_lossy(bytes).into_owned());
+                    pos = label.pos;
+                }
+                0xC0 => {
+                    let low = label.u8()?;
+                    end.get_or_insert(label.pos);
+                    pointers += 1;
+                    if pointers > MAX_NAME_POINTERS {
+                        return Err("too many name compression pointers".to_string());
+                    }
+                    pos = usize::from(len & 0x3F) << 8 | usize::from(low);
+                }
+                _ => return Err("invalid name label".to_string()),
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::{error::Error, fmt, io::ErrorKind as IoErrorKind, sync::Arc};
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+// Errors can only be thrown as values from lua, errors returned from rust would
+// be turned into strings, so this catches any error thrown by the wrapped function
+// and throws it again, after turning it into a structured error if it is a network error
+const WRAP_IMPL_LUA: &str = r"
+local f, convert = ...
+return function(...)
+	local ok, result = pcall(f, ...)
+	if ok then
+		return result
+	end
+	error(convert(result), 2)
+end
+";
+
+/**
+    A kind of network error, which stays the same across platforms and
+    versions, so that scripts can decide to retry or fall back based on it.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetErrorKind {
+    Blocked,
+    Dns,
+    Refused,
+    Reset,
+    Tls,
+    Timeout,
+    Other,
+}
+
+impl NetErrorKind {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Blocked => "blocked",
+            Self::Dns => "dns",
+            Self::Refused => "refused",
+            Self::Reset => "reset",
+            Self::Tls => "tls",
+            Self::Timeout => "timeout",
+            Self::Other => "other",
+        }
+    }
+
+    /**
+        Finds the kind of an error by looking through the error and all of its sources.
+    */
+    fn classify(err: &(dyn Error + 'static)) -> Self {
+        // NOTE: Errors from our own redirect policy and resolver are wrapped by reqwest,
+        // and the resolver errors are wrapped in "dns error" errors, so these come first
+        if let Some(net) = find_net_error(err) {
+            return net.kind;
+        }
+        let mut current = Some(err);
+        while let Some(err) = current {
+            if let Some(io) = err.downcast_ref::<std::io::Error>() {
+                match io.kind() {
+                    IoErrorKind::ConnectionRefused => return Self::Refused,
+                    IoErrorKind::ConnectionReset
+                    | IoErrorKind::ConnectionAborted
+                    | IoErrorKind::BrokenPipe
+                    | IoErrorKind::UnexpectedEof => return Self::Reset,
+                    IoErrorKind::TimedOut => return Self::Timeout,
+                    // NOTE: Invalid data while connecting can only come from the
+                    // tls handshake, since nothing else has been read at that point
+                    IoErrorKind::InvalidData if io.get_ref().is_some() => return Self::Tls,
+                    _ => {}
+                }
+                // NOTE: Wrapped errors are skipped by the source of an io error, not returned
+                if let Some(inner) = io.get_ref() {
+                    current = Some(inner);
+                    continue;
+                }
+            }
+            // NOTE: Failed lookups are not given their own error types, neither by
+            // hyper ("dns error") nor by tokio, which is used for websockets
+            let message = err.to_string();
+            if message == "dns error" || message.starts_with("failed to lookup address") {
+                return Self::Dns;
+            }
+            // NOTE: Connections closed by the server before responding are
+            // not io errors in hyper either, but they are resets for us
+            if message.starts_with("connection closed before message completed") {
+                return Self::Reset;
+            }
+            current = err.source();
+        }
+        Self::Other
+    }
+}
+
+/**
+    Finds a [`NetError`] in the given error or any of its sources,
+    including errors wrapped by io errors, which are not their sources.
+*/
+fn find_net_error<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a NetError> {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(net) = err.downcast_ref::<NetError>() {
+            return Some(net);
+        }
+        current = match err
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+        {
+            Some(inner) => Some(inner),
+            None => err.source(),
+        };
+    }
+    None
+}
+
+/**
+    A structured error for network failures, such as failed lookups or refused
+    connections, which is thrown to lua with its kind and message as fields.
+*/
+#[derive(Debug, Clone)]
+pub struct NetError {
+    kind: NetErrorKind,
+    message: String,
+}
+
+impl NetError {
+    pub fn new(kind: NetErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /**
+        Turns an error into a network error, if it is one, otherwise returns the
+        error as it was - errors such as invalid arguments are not network errors.
+    */
+    fn from_lua_error(err: LuaError) -> Result<Self, LuaError> {
+        let mut cause = &err;
+        while let LuaError::CallbackError { cause: inner, .. } = cause {
+            cause = inner.as_ref();
+        }
+        let LuaError::ExternalError(external) = cause else {
+            return Err(err);
+        };
+        let kind = if let Some(e) = find_net_error(external.as_ref()) {
+            return Ok(e.clone());
+        } else if let Some(e) = external.downcast_ref::<reqwest::Error>() {
+            if e.is_builder() {
+                return Err(err);
+            } else if e.is_timeout() {
+                NetErrorKind::Timeout
+            } else {
+                NetErrorKind::classify(e)
+            }
+        } else if let Some(e) = external.downcast_ref::<tokio_tungstenite::tungstenite::Error>() {
+            match e {
+                tokio_tungstenite::tungstenite::Error::Url(_) => return Err(err),
+                tokio_tungstenite::tungstenite::Error::Tls(_) => NetErrorKind::Tls,
+                e => NetErrorKind::classify(e),
+            }
+        } else {
+            return Err(err);
+        };
+        Ok(Self::new(kind, external.to_string()))
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for NetError {}
+
+impl From<NetError> for LuaError {
+    fn from(value: NetError) -> Self {
+        LuaError::ExternalError(Arc::new(value))
+    }
+}
+
+impl LuaUserData for NetError {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("kind", |_, this| Ok(this.kind.as_str()));
+        fields.add_field_method_get("message", |_, this| Ok(this.message.clone()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, (): ()| {
+            Ok(this.message.clone())
+        });
+    }
+}
+
+/**
+    Wraps a function so that any network errors it throws are
+    thrown as structured [`NetError`] values instead of strings.
+*/
+pub fn wrap_net_errors<'lua>(lua: &'lua Lua, f: LuaFunction<'lua>) -> LuaResult<LuaFunction<'lua>> {
+    let convert = lua.create_function(|lua, err: LuaValue| match err {
+        LuaValue::Error(err) => match NetError::from_lua_error(err) {
+            Ok(net_err) => net_err.into_lua(lua),
+            Err(err) => Ok(LuaValue::Error(err)),
+        },
+        value => Ok(value),
+    })?;
+    let env = TableBuilder::new(lua)?
+        .with_value("pcall", lua.globals().get::<_, LuaFunction>("pcall")?)?
+        .with_value("error", lua.globals().get::<_, LuaFunction>("error")?)?
+        .build_readonly()?;
+    lua.load(WRAP_IMPL_LUA)
+        .set_name("net_error")
+        .set_environment(env)
+        .call((f, convert))
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use mlua::prelude::*;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest_hyper::client::connect::dns::Name;
+use tokio::net::{lookup_host, TcpStream};
+
+use super::policy::HostPolicy;
+
+/**
+    Overrides for the addresses that hostnames resolve to, used by the `net` library
+    instead of the system resolver - the same as adding entries to `/etc/hosts`, but
+    only for a single runtime, such as for pointing hostnames at local servers in tests.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostOverrides {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl HostOverrides {
+    /**
+        Creates a new set of host overrides, without any hosts.
+    */
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Adds an address for the given hostname.
+
+        Hostnames may be given more than one address, which
+        are then tried in the same order that they were added.
+
+        # Errors
+
+        Errors if the hostname is empty, or contains a port, path or whitespace.
+    */
+    pub fn insert(&mut self, host: &str, addr: IpAddr) -> Result<(), String> {
+        let normalized = normalize_host(host);
+        if normalized.is_empty()
+            || normalized
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, ':' | '/' | '[' | ']'))
+        {
+            return Err(format!("Invalid hostname '{host}'"));
+        }
+        let addrs = self.hosts.entry(normalized).or_default();
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+        Ok(())
+    }
+
+    /**
+        Parses host overrides from the contents of a file in the same format as `/etc/hosts`.
+
+        Each line contains an address followed by one or more hostnames,
+        separated by whitespace, and anything after a `#` is ignored.
+
+        # Errors
+
+        Errors if any line contains an invalid address or hostname, or no hostnames.
+    */
+    pub fn parse_hosts_file(contents: &str) -> Result<Self, String> {
+        let mut this = Self::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut parts = line.split_whitespace();
+            let Some(addr) = parts.next() else {
+                continue;
+            };
+            let line_number = index + 1;
+            let addr = addr
+                .parse::<IpAddr>()
+                .map_err(|_| format!("Invalid address '{addr}' on line {line_number}"))?;
+            let mut parts = parts.peekable();
+            if parts.peek().is_none() {
+                return Err(format!("Missing hostname on line {line_number}"));
+            }
+            for host in parts {
+                this.insert(host, addr)
+                    .map_err(|e| format!("{e} on line {line_number}"))?;
+            }
+        }
+        Ok(this)
+    }
+
+    /**
+        Adds all hosts from another set of host overrides.
+    */
+    pub fn extend(&mut self, other: Self) {
+        for (host, addrs) in other.hosts {
+            let existing = self.hosts.entry(host).or_default();
+            for addr in addrs {
+                if !existing.contains(&addr) {
+                    existing.push(addr);
+                }
+            }
+        }
+    }
+
+    /**
+        Returns `true` if there are no host overrides.
+    */
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /**
+        Gets the addresses that the given hostname should resolve to, if it is overridden.
+    */
+    #[must_use]
+    pub fn get(&self, host: &str) -> Option<&[IpAddr]> {
+        self.hosts.get(&normalize_host(host)).map(Vec::as_slice)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &[IpAddr])> {
+        self.hosts
+            .iter()
+            .map(|(host, addrs)| (host.as_str(), addrs.as_slice()))
+    }
+
+    pub(crate) fn socket_addrs(&self, host: &str, port: u16) -> Option<Vec<SocketAddr>> {
+        self.get(host).map(|addrs| {
+            addrs
+                .iter()
+                .map(|addr| SocketAddr::new(*addr, port))
+                .collect()
+        })
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[derive(Debug, Clone)]
+struct HostOverridesData(Arc<HostOverrides>);
+
+/**
+    Sets the overrides for the addresses that hostnames resolve to in the `net` library.
+
+    This applies to `net.request`, `net.socket`, sending to hosts using UDP sockets, and to
+    resolving addresses using `net.resolve` - unless a nameserver is given, which is then
+    always queried directly. Host policies still apply to the hostnames themselves.
+
+    The overrides must be set before the `net` library is first required.
+    By default, there are no overrides, and the system resolver is used for all hosts.
+*/
+pub fn set_host_overrides(lua: &Lua, overrides: HostOverrides) {
+    lua.set_app_data(HostOverridesData(Arc::new(overrides)));
+}
+
+/**
+    Gets the host overrides that were set for the given Lua state, if any.
+*/
+pub(crate) fn host_overrides(lua: &Lua) -> Option<Arc<HostOverrides>> {
+    lua.app_data_ref::<HostOverridesData>()
+        .map(|data| Arc::clone(&data.0))
+}
+
+/**
+    Resolves the given host and port, using the overridden addresses of the host, if any.
+*/
+pub(crate) async fn resolve_host(
+    overrides: Option<&HostOverrides>,
+    host: &str,
+    port: u16,
+) -> io::Result<Vec<SocketAddr>> {
+    match overrides.and_then(|overrides| overrides.socket_addrs(host, port)) {
+        Some(addrs) => Ok(addrs),
+        None => Ok(lookup_host((host, port)).await?.collect()),
+    }
+}
+
+/**
+    Resolves the given host and port, the same as [`resolve_host`], and then
+    makes sure that none of the resolved addresses are denied by the host policy.
+*/
+pub(crate) async fn resolve_host_checked(
+    overrides: Option<&HostOverrides>,
+    policy: Option<&HostPolicy>,
+    host: &str,
+    port: u16,
+) -> io::Result<Vec<SocketAddr>> {
+    let addrs = resolve_host(overrides, host, port).await?;
+    if let Some(policy) = policy {
+        policy
+            .check_resolved(host, &addrs, Some(port))
+            .map_err(io::Error::other)?;
+    }
+    Ok(addrs)
+}
+
+/**
+    Connects to the given host and port, using the overridden addresses of the host, if any,
+    and making sure that none of the resolved addresses are denied by the host policy.
+*/
+pub(crate) async fn connect_tcp(
+    overrides: Option<&HostOverrides>,
+    policy: Option<&HostPolicy>,
+    host: &str,
+    port: u16,
+) -> io::Result<TcpStream> {
+    let addrs = resolve_host_checked(overrides, policy, host, port).await?;
+    TcpStream::connect(addrs.as_slice()).await
+}
+
+/**
+    A resolver for reqwest, used instead of its own resolver and overrides when there
+    is a host policy, so that the addresses of all hosts are checked against the policy.
+*/
+pub(crate) struct PolicyResolver {
+    pub overrides: Option<Arc<HostOverrides>>,
+    pub policy: Arc<HostPolicy>,
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let overrides = self.overrides.clone();
+        let policy = Arc::clone(&self.policy);
+        Box::pin(async move {
+            // NOTE: The port is set by reqwest after resolving, and is not known here
+            let addrs = resolve_host(overrides.as_deref(), name.as_str(), 0).await?;
+            policy.check_resolved(name.as_str(), &addrs, None)?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -6,22 +6,48 @@ use mlua_luau_scheduler::LuaSpawnExt;
 
 mod client;
 mod config;
+mod cookies;
+mod deflate;
+mod dns;
+mod error;
+mod hosts;
+mod multipart;
+mod policy;
 mod server;
+mod session;
+mod socks;
+mod sse;
+mod stream;
+mod udp;
 mod util;
 mod websocket;
 
 use lune_utils::TableBuilder;
 
 use self::{
-    client::{NetClient, NetClientBuilder},
-    config::{RequestConfig, ServeConfig},
+    client::{NetClient, NetClientBody, NetClientBuilder},
+    config::{RequestConfig, RequestConfigBody, ResolveConfig, ServeConfig, SocketConfig},
+    deflate::connect_deflate,
+    dns::{resolve, DnsRecordType},
+    error::wrap_net_errors,
+    hosts::{connect_tcp, host_overrides},
+    multipart::create_multipart_body,
+    policy::host_policy,
     server::serve,
+    session::NetSession,
+    stream::create_body_stream,
+    udp::NetUdpSocket,
     util::create_user_agent_header,
     websocket::NetWebSocket,
 };
 
+pub use self::hosts::{set_host_overrides, HostOverrides};
+pub use self::policy::{set_host_policy, HostPolicy};
+
 use lune_std_serde::{decode, encode, EncodeDecodeConfig, EncodeDecodeFormat};
 
+const DEFAULT_ACCEPT_ENCODING: &str = "gzip, br";
+
 /**
     Creates the `net` standard library module.
 
@@ -32,14 +58,28 @@ use lune_std_serde::{decode, encode, EncodeDecodeConfig, EncodeDecodeFormat};
 pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
     NetClientBuilder::new()
         .headers(&[("User-Agent", create_user_agent_header(lua)?)])?
+        .host_policy(host_policy(lua))
+        .host_overrides(host_overrides(lua))
         .build()?
         .into_registry(lua);
     TableBuilder::new(lua)?
         .with_function("jsonEncode", net_json_encode)?
         .with_function("jsonDecode", net_json_decode)?
-        .with_async_function("request", net_request)?
-        .with_async_function("socket", net_socket)?
+        .with_value(
+            "request",
+            wrap_net_errors(lua, lua.create_async_function(net_request)?)?,
+        )?
+        .with_value(
+            "resolve",
+            wrap_net_errors(lua, lua.create_async_function(net_resolve)?)?,
+        )?
+        .with_value(
+            "socket",
+            wrap_net_errors(lua, lua.create_async_function(net_socket)?)?,
+        )?
         .with_async_function("serve", net_serve)?
+        .with_function("session", net_session)?
+        .with_value("udp", create_udp_module(lua)?)?
         .with_function("urlEncode", net_url_encode)?
         .with_function("urlDecode", net_url_decode)?
         .build_readonly()
@@ -59,14 +99,136 @@ fn net_json_decode(lua: &Lua, json: BString) -> LuaResult<LuaValue> {
 }
 
 async fn net_request(lua: &Lua, config: RequestConfig) -> LuaResult<LuaTable> {
-    let client = NetClient::from_registry(lua);
+    let session = if config.options.cookies {
+        Some(NetSession::from_registry(lua)?)
+    } else {
+        None
+    };
+    send_request(lua, config, session.as_ref()).await
+}
+
+async fn send_request<'lua>(
+    lua: &'lua Lua,
+    mut config: RequestConfig,
+    session: Option<&NetSession>,
+) -> LuaResult<LuaTable<'lua>> {
+    // NOTE: Custom tls or proxy options need a separate client, the shared one uses defaults
+    let client = if config.options.tls.is_some() || config.options.proxy.is_some() {
+        let mut builder = NetClientBuilder::new()
+            .headers(&[("User-Agent", create_user_agent_header(lua)?)])?
+            .redirects(session.is_none())
+            .host_policy(host_policy(lua))
+            .host_overrides(host_overrides(lua));
+        if let Some(tls) = &config.options.tls {
+            builder = builder.tls(tls)?;
+        }
+        if let Some(proxy) = &config.options.proxy {
+            builder = builder.proxy(proxy)?;
+        }
+        builder.build()?
+    } else if let Some(session) = session {
+        session.client().clone()
+    } else {
+        NetClient::from_registry(lua)
+    };
+    let jar = session.map(|s| s.jar().clone());
+    // NOTE: Servers only send compressed responses when asked to, but any
+    // accept-encoding header given by the user should take precedence
+    if config.options.decompress
+        && !config.options.stream
+        && !config
+            .headers
+            .keys()
+            .any(|key| key.eq_ignore_ascii_case("accept-encoding"))
+    {
+        config.headers.insert(
+            "accept-encoding".to_string(),
+            vec![DEFAULT_ACCEPT_ENCODING.to_string()],
+        );
+    }
+    // NOTE: Body producers must be driven by lua, so they are set up before spawning
+    let body = match config.body.take() {
+        None => NetClientBody::Bytes(Vec::new()),
+        Some(RequestConfigBody::Bytes(bytes)) => NetClientBody::Bytes(bytes),
+        Some(RequestConfigBody::Producer(key)) => {
+            if config.options.retry.retries > 0 {
+                return Err(LuaError::runtime(
+                    "Request bodies given as functions can not be retried",
+                ));
+            }
+            let producer = lua.registry_value::<LuaFunction>(&key)?;
+            lua.remove_registry_value(key)?;
+            NetClientBody::Stream(Some(create_body_stream(lua, producer)?))
+        }
+        Some(RequestConfigBody::Multipart(parts)) => {
+            let multipart = create_multipart_body(parts).await?;
+            config.headers.retain(|key, _| {
+                !key.eq_ignore_ascii_case("content-type")
+                    && !key.eq_ignore_ascii_case("content-length")
+            });
+            config.headers.insert(
+                "content-type".to_string(),
+                vec![multipart.content_type.clone()],
+            );
+            config.headers.insert(
+                "content-length".to_string(),
+                vec![multipart.content_length.to_string()],
+            );
+            NetClientBody::Multipart(multipart)
+        }
+    };
     // NOTE: We spawn the request as a background task to free up resources in lua
-    let res = lua.spawn(async move { client.request(config).await });
+    let res = lua.spawn(async move { client.request(config, body, jar.as_ref()).await });
     res.await?.into_lua_table(lua)
 }
 
-async fn net_socket(lua: &Lua, url: String) -> LuaResult<LuaTable> {
-    let (ws, _) = tokio_tungstenite::connect_async(url).await.into_lua_err()?;
+async fn net_resolve(
+    lua: &Lua,
+    (hostname, record_type, config): (String, Option<String>, ResolveConfig),
+) -> LuaResult<LuaTable> {
+    let record_type = match record_type {
+        Some(record_type) => record_type.parse().map_err(LuaError::RuntimeError)?,
+        None => DnsRecordType::A,
+    };
+    let overrides = host_overrides(lua);
+    let records = resolve(&hostname, record_type, &config, overrides.as_deref()).await?;
+    lua.create_sequence_from(records)
+}
+
+fn net_session(lua: &Lua, (): ()) -> LuaResult<LuaTable> {
+    NetSession::new(lua)?.into_lua_table(lua)
+}
+
+async fn net_socket(lua: &Lua, (url, config): (String, SocketConfig)) -> LuaResult<LuaTable> {
+    let parsed = reqwest::Url::parse(&url);
+    let policy = host_policy(lua);
+    if let (Some(policy), Ok(parsed)) = (&policy, &parsed) {
+        policy.check_url(parsed)?;
+    }
+    let overrides = host_overrides(lua);
+    if let Some(compression) = config.compression {
+        let (ws, deflate) =
+            connect_deflate(url, compression, overrides.as_deref(), policy.as_deref()).await?;
+        return NetWebSocket::with_deflate(ws, deflate).into_lua_table(lua);
+    }
+    // NOTE: With host overrides or a host policy, hosts are resolved and connected to
+    // by us, and the hostname in the url is then used as usual, such as for certificates
+    let target = parsed.ok().and_then(|parsed| {
+        let port = parsed.port_or_known_default()?;
+        Some((parsed.host_str()?.to_string(), port))
+    });
+    let (ws, _) = match target {
+        Some((host, port)) if overrides.is_some() || policy.is_some() => {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let stream = connect_tcp(overrides.as_deref(), policy.as_deref(), host, port)
+                .await
+                .into_lua_err()?;
+            tokio_tungstenite::client_async_tls(url, stream)
+                .await
+                .into_lua_err()?
+        }
+        _ => tokio_tungstenite::connect_async(url).await.into_lua_err()?,
+    };
     NetWebSocket::new(ws).into_lua_table(lua)
 }
 
@@ -77,6 +239,16 @@ async fn net_serve<'lua>(
     serve(lua, port, config).await
 }
 
+fn create_udp_module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("bind", net_udp_bind)?
+        .build_readonly()
+}
+
+async fn net_udp_bind(lua: &Lua, (port, address): (u16, Option<String>)) -> LuaResult<LuaTable> {
+    NetUdpSocket::bind(port, address).await?.into_lua_table(lua)
+}
+
 fn net_url_encode<'lua>(
     lua: &'lua Lua,
     (lua_string, as_binary): (LuaString<'lua>, Option<bool>),
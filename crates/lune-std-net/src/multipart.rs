@@ -0,0 +1,181 @@
+use std::{collections::VecDeque, io::Error as IoError, path::PathBuf};
+
+use futures_util::stream;
+use mlua::prelude::*;
+use tokio::{
+    fs::{metadata, File},
+    io::AsyncReadExt,
+};
+
+use crate::config::{RequestConfigMultipartContent, RequestConfigMultipartPart};
+
+// Size of each chunk read from files while streaming them in a multipart body
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+const DEFAULT_FILE_CONTENT_TYPE: &str = "application/octet-stream";
+
+/**
+    A multipart/form-data request body, ready to be sent.
+
+    The body may be created more than once, for example when a request is retried,
+    in which case any files will be read again.
+*/
+#[derive(Debug, Clone)]
+pub struct MultipartBody {
+    segments: VecDeque<Segment>,
+    pub content_type: String,
+    pub content_length: u64,
+}
+
+impl MultipartBody {
+    pub fn create_body(&self) -> reqwest::Body {
+        let state = SegmentStream {
+            segments: self.segments.clone(),
+            file: None,
+        };
+        let chunks = stream::try_unfold(state, |mut state| async move {
+            let chunk = state.next_chunk().await?;
+            Ok::<_, IoError>(chunk.map(|chunk| (chunk, state)))
+        });
+        reqwest::Body::wrap_stream(chunks)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Bytes(Vec<u8>),
+    File(PathBuf, u64),
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Bytes(bytes) => bytes.len() as u64,
+            Self::File(_, len) => *len,
+        }
+    }
+}
+
+struct SegmentStream {
+    segments: VecDeque<Segment>,
+    file: Option<File>,
+}
+
+impl SegmentStream {
+    async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, IoError> {
+        loop {
+            if let Some(file) = self.file.as_mut() {
+                let mut chunk = vec![0; FILE_CHUNK_SIZE];
+                let len = file.read(&mut chunk).await?;
+                if len > 0 {
+                    chunk.truncate(len);
+                    return Ok(Some(chunk));
+                }
+                self.file = None;
+            }
+            match self.segments.pop_front() {
+                None => return Ok(None),
+                Some(Segment::Bytes(bytes)) => return Ok(Some(bytes)),
+                Some(Segment::File(path, _)) => self.file = Some(File::open(path).await?),
+            }
+        }
+    }
+}
+
+/**
+    Creates a multipart/form-data request body from the given parts.
+
+    File parts are not read into memory, they are streamed from disk while the request is sent.
+    The length of the body is computed up front, so that it can be sent as the `Content-Length`.
+*/
+pub async fn create_multipart_body(
+    parts: Vec<RequestConfigMultipartPart>,
+) -> LuaResult<MultipartBody> {
+    let boundary = create_boundary();
+
+    let mut segments = VecDeque::new();
+
+    for part in parts {
+        let filename = part.filename.or_else(|| match &part.content {
+            RequestConfigMultipartContent::Bytes(_) => None,
+            RequestConfigMultipartContent::File(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+        });
+
+        let content_type = part.content_type.or_else(|| {
+            filename
+                .as_ref()
+                .map(|_| DEFAULT_FILE_CONTENT_TYPE.to_string())
+        });
+
+        let mut header = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+            escape_quoted(&part.name)
+        );
+        if let Some(filename) = &filename {
+            header.push_str("; filename=\"");
+            header.push_str(&escape_quoted(filename));
+            header.push('"');
+        }
+        header.push_str("\r\n");
+        if let Some(content_type) = content_type {
+            header.push_str("Content-Type: ");
+            header.push_str(&content_type);
+            header.push_str("\r\n");
+        }
+        header.push_str("\r\n");
+        segments.push_back(Segment::Bytes(header.into_bytes()));
+
+        match part.content {
+            RequestConfigMultipartContent::Bytes(bytes) => {
+                segments.push_back(Segment::Bytes(bytes));
+            }
+            RequestConfigMultipartContent::File(path) => {
+                let meta = metadata(&path).await.map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Failed to read file '{}' for multipart part '{}'\n{e}",
+                        path.display(),
+                        part.name
+                    ))
+                })?;
+                if !meta.is_file() {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Path '{}' for multipart part '{}' is not a file",
+                        path.display(),
+                        part.name
+                    )));
+                }
+                segments.push_back(Segment::File(path, meta.len()));
+            }
+        }
+        segments.push_back(Segment::Bytes(b"\r\n".to_vec()));
+    }
+    segments.push_back(Segment::Bytes(format!("--{boundary}--\r\n").into_bytes()));
+
+    let content_length = segments.iter().map(Segment::len).sum();
+
+    Ok(MultipartBody {
+        segments,
+        content_type: format!("multipart/form-data; boundary={boundary}"),
+        content_length,
+    })
+}
+
+fn create_boundary() -> String {
+    format!(
+        "{:016x}-{:016x}-{:016x}-{:016x}",
+        rand::random::<u64>(),
+        rand::random::<u64>(),
+        rand::random::<u64>(),
+        rand::random::<u64>()
+    )
+}
+
+// Quotes and newlines would break out of the quoted header parameter,
+// so they are percent-encoded the same way that browsers encode them
+fn escape_quoted(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
@@ -0,0 +1,267 @@
+use std::{
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+use mlua::prelude::*;
+use reqwest::Url;
+
+use super::error::{NetError, NetErrorKind};
+
+/**
+    A pattern matching one or more hosts, and optionally a single port.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostPattern {
+    Any(Option<u16>),
+    Exact(String, Option<u16>),
+    Subdomains(String, Option<u16>),
+}
+
+impl HostPattern {
+    fn parse(pattern: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("Invalid host pattern '{pattern}' - {reason}");
+
+        let trimmed = pattern.trim();
+        let (host, port) = if let Some(rest) = trimmed.strip_prefix('[') {
+            let (host, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| invalid("missing closing bracket"))?;
+            match rest {
+                "" => (host, None),
+                _ => match rest.strip_prefix(':') {
+                    Some(port) => (host, Some(port)),
+                    None => return Err(invalid("unexpected characters after closing bracket")),
+                },
+            }
+        } else if trimmed.matches(':').count() == 1 {
+            let (host, port) = trimmed.split_once(':').unwrap();
+            (host, Some(port))
+        } else {
+            (trimmed, None)
+        };
+
+        let port = match port {
+            None => None,
+            Some(port) => Some(
+                port.parse::<u16>()
+                    .map_err(|_| invalid("port must be a number between 0 and 65535"))?,
+            ),
+        };
+
+        let host = normalize_host(host);
+        if host.is_empty() {
+            return Err(invalid("host must not be empty"));
+        }
+        if host == "*" {
+            return Ok(Self::Any(port));
+        }
+        if let Some(suffix) = host.strip_prefix("*.") {
+            if suffix.is_empty() || suffix.contains('*') {
+                return Err(invalid("wildcards may only be used as '*' or '*.domain'"));
+            }
+            return Ok(Self::Subdomains(format!(".{suffix}"), port));
+        }
+        if host.contains('*') {
+            return Err(invalid("wildcards may only be used as '*' or '*.domain'"));
+        }
+        if host.contains(':') && host.parse::<Ipv6Addr>().is_err() {
+            return Err(invalid("host is not a valid IPv6 address"));
+        }
+        Ok(Self::Exact(host, port))
+    }
+
+    fn matches(&self, host: &str, port: Option<u16>) -> bool {
+        let (host_matches, pattern_port) = match self {
+            Self::Any(p) => (true, p),
+            Self::Exact(exact, p) => (host == exact, p),
+            Self::Subdomains(suffix, p) => (host.ends_with(suffix.as_str()), p),
+        };
+        host_matches && pattern_port.map_or(true, |p| port == Some(p))
+    }
+
+    /**
+        Checks if this pattern is for the given address. Patterns with
+        a port match any port if the port is not known, unlike in `matches`.
+    */
+    fn matches_addr(&self, addr: IpAddr, port: Option<u16>) -> bool {
+        match self {
+            Self::Exact(exact, p) => {
+                exact.parse::<IpAddr>().ok().map(|ip| ip.to_canonical()) == Some(addr)
+                    && port.zip(*p).map_or(true, |(port, p)| port == p)
+            }
+            Self::Any(_) | Self::Subdomains(..) => false,
+        }
+    }
+}
+
+/**
+    A policy restricting which hosts may be contacted by the `net` library.
+
+    Hosts are given as patterns, which may be:
+
+    - An exact host, such as `example.com` or `127.0.0.1`
+    - A wildcard for all subdomains of a host, such as `*.example.com`,
+      which does not match `example.com` itself
+    - A wildcard for all hosts, `*`
+
+    Any pattern may be followed by a port, such as `localhost:8080`,
+    to only match that port, and IPv6 addresses can be given either
+    as they are or in brackets, such as `[::1]:8080`.
+
+    Hosts that match any denied pattern are never allowed, and if there are
+    any allowed patterns, hosts must also match at least one of those.
+
+    Denied addresses are also checked against the addresses that hosts resolve to,
+    so that `localhost` is not allowed when `127.0.0.1` is denied. Allowed addresses
+    only ever apply to the addresses themselves, and never to hosts resolving to them.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct HostPolicy {
+    allow: Vec<HostPattern>,
+    deny: Vec<HostPattern>,
+}
+
+impl HostPolicy {
+    /**
+        Creates a new host policy from the given allowed and denied host patterns.
+
+        # Errors
+
+        Errors if any of the given patterns are invalid.
+    */
+    pub fn new<A, D>(allow: A, deny: D) -> Result<Self, String>
+    where
+        A: IntoIterator,
+        A::Item: AsRef<str>,
+        D: IntoIterator,
+        D::Item: AsRef<str>,
+    {
+        Ok(Self {
+            allow: parse_patterns(allow)?,
+            deny: parse_patterns(deny)?,
+        })
+    }
+
+    /**
+        Checks if the given host, and optionally port, may be contacted under this policy.
+    */
+    #[must_use]
+    pub fn is_allowed(&self, host: &str, port: Option<u16>) -> bool {
+        let host = normalize_host(host);
+        if self.deny.iter().any(|p| p.matches(&host, port)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|p| p.matches(&host, port))
+    }
+
+    /**
+        Checks if the host of the given url may be contacted under this policy.
+
+        # Errors
+
+        Errors if the host is not allowed, or if the url has no host.
+    */
+    pub(crate) fn check_url(&self, url: &Url) -> Result<(), NetError> {
+        let host = url.host_str().unwrap_or_default();
+        self.check_host(host, url.port_or_known_default())
+    }
+
+    /**
+        Checks if the given host and port may be contacted under this policy.
+
+        # Errors
+
+        Errors if the host is not allowed.
+    */
+    pub(crate) fn check_host(&self, host: &str, port: Option<u16>) -> Result<(), NetError> {
+        if self.is_allowed(host, port) {
+            Ok(())
+        } else {
+            Err(NetError::new(
+                NetErrorKind::Blocked,
+                format!("Host '{host}' is not allowed by the host policy"),
+            ))
+        }
+    }
+
+    /**
+        Checks if the addresses that the given host resolved to may be contacted
+        under this policy, meaning that none of them match any denied pattern.
+
+        The port may not be known, such as when resolving for reqwest, which only
+        sets the port after resolving - denied patterns with a port then match any port.
+
+        # Errors
+
+        Errors if any of the addresses are denied.
+    */
+    pub(crate) fn check_resolved(
+        &self,
+        host: &str,
+        addrs: &[SocketAddr],
+        port: Option<u16>,
+    ) -> Result<(), NetError> {
+        let denied = addrs
+            .iter()
+            .map(|addr| addr.ip().to_canonical())
+            .find(|addr| {
+                self.deny
+                    .iter()
+                    .any(|pattern| pattern.matches_addr(*addr, port))
+            });
+        match denied {
+            None => Ok(()),
+            Some(addr) => Err(NetError::new(
+                NetErrorKind::Blocked,
+                format!(
+                    "Host '{host}' resolved to '{addr}', which is not allowed by the host policy"
+                ),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HostPolicyData(Arc<HostPolicy>);
+
+/**
+    Sets the policy restricting which hosts may be contacted by the `net` library.
+
+    This applies to `net.request`, including any redirects that it follows, as well as to
+    `net.socket` and sending to hosts using UDP sockets - but not to `net.serve`, which
+    only accepts incoming connections, or `net.resolve`, which only contacts DNS servers.
+
+    The policy must be set before the `net` library is first required.
+    By default, no policy is set, and any host may be contacted.
+*/
+pub fn set_host_policy(lua: &Lua, policy: HostPolicy) {
+    lua.set_app_data(HostPolicyData(Arc::new(policy)));
+}
+
+/**
+    Gets the host policy that was set for the given Lua state, if any.
+*/
+pub(crate) fn host_policy(lua: &Lua) -> Option<Arc<HostPolicy>> {
+    lua.app_data_ref::<HostPolicyData>()
+        .map(|data| Arc::clone(&data.0))
+}
+
+fn parse_patterns<I>(patterns: I) -> Result<Vec<HostPattern>, String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    patterns
+        .into_iter()
+        .map(|pattern| HostPattern::parse(pattern.as_ref()))
+        .collect()
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
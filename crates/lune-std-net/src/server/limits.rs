@@ -0,0 +1,239 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    net::IpAddr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::{
+    body::{Body, Incoming},
+    header::{CONNECTION, RETRY_AFTER},
+    Response, StatusCode,
+};
+use tokio::time::timeout;
+
+use mlua::prelude::*;
+
+use crate::config::{ServeConfig, ServeRateLimit};
+
+use super::response::{full_body, ResponseBody};
+
+// Once this many clients are being tracked, clients that have
+// not made any requests for a full window are forgotten about
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/**
+    Limits for a server, checked for every incoming request
+    before any Lua thread is created to handle the request.
+*/
+#[derive(Debug, Clone, Default)]
+pub(super) struct SvcLimits {
+    concurrency: Option<Rc<ConcurrencyLimit>>,
+    rate: Option<Rc<RateLimiter>>,
+    max_body_size: Option<usize>,
+    read_timeout: Option<Duration>,
+}
+
+impl SvcLimits {
+    pub(super) fn new(config: &ServeConfig) -> Self {
+        Self {
+            max_body_size: config.max_body_size,
+            read_timeout: config.read_timeout,
+            concurrency: config.max_concurrent_requests.map(|max| {
+                Rc::new(ConcurrencyLimit {
+                    max,
+                    active: Cell::new(0),
+                })
+            }),
+            rate: config.rate_limit.map(|limit| {
+                Rc::new(RateLimiter {
+                    limit,
+                    clients: RefCell::new(HashMap::new()),
+                })
+            }),
+        }
+    }
+
+    /**
+        Checks if a request from the given address is within the rate limit.
+    */
+    pub(super) fn check_rate(&self, ip: IpAddr) -> Result<(), LimitRejection> {
+        match &self.rate {
+            None => Ok(()),
+            Some(rate) => rate.try_acquire(ip).map_err(LimitRejection::RateLimited),
+        }
+    }
+
+    /**
+        Checks if the declared length of a request body is within the body size limit,
+        so that requests that are too large can be rejected without reading the body.
+    */
+    pub(super) fn check_body_size(&self, body: &Incoming) -> Result<(), LimitRejection> {
+        match (self.max_body_size, body.size_hint().exact()) {
+            (Some(max), Some(len)) if usize::try_from(len).map_or(true, |len| len > max) => {
+                Err(LimitRejection::BodyTooLarge)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /**
+        Reads a full request body, respecting the body size limit and read timeout.
+
+        Returns an outer error if reading the body failed for any other reason,
+        such as the client disconnecting before sending the full body.
+    */
+    pub(super) async fn read_body(
+        &self,
+        body: Incoming,
+    ) -> LuaResult<Result<Vec<u8>, LimitRejection>> {
+        let collected = async {
+            match self.max_body_size {
+                None => body
+                    .collect()
+                    .await
+                    .map(|body| Ok(body.to_bytes().to_vec()))
+                    .into_lua_err(),
+                Some(max) => match Limited::new(body, max).collect().await {
+                    Ok(body) => Ok(Ok(body.to_bytes().to_vec())),
+                    Err(e) if e.is::<LengthLimitError>() => Ok(Err(LimitRejection::BodyTooLarge)),
+                    Err(e) => Err(LuaError::external(e)),
+                },
+            }
+        };
+        match self.read_timeout {
+            None => collected.await,
+            Some(duration) => match timeout(duration, collected).await {
+                Ok(result) => result,
+                Err(_) => Ok(Err(LimitRejection::TimedOut)),
+            },
+        }
+    }
+
+    /**
+        Tries to start handling a request, respecting the concurrency limit.
+
+        Returns a guard that keeps the request counted as active until it is dropped.
+    */
+    pub(super) fn try_start(&self) -> Result<Option<ActiveRequest>, LimitRejection> {
+        match &self.concurrency {
+            None => Ok(None),
+            Some(limit) if limit.active.get() >= limit.max => Err(LimitRejection::Busy),
+            Some(limit) => {
+                limit.active.set(limit.active.get() + 1);
+                Ok(Some(ActiveRequest(Rc::clone(limit))))
+            }
+        }
+    }
+}
+
+/**
+    A request that was rejected because of a limit, and should
+    be responded to without being handled by the server.
+*/
+#[derive(Debug, Clone, Copy)]
+pub(super) enum LimitRejection {
+    RateLimited(Duration),
+    Busy,
+    BodyTooLarge,
+    TimedOut,
+}
+
+impl LimitRejection {
+    pub(super) fn into_response(self) -> Response<ResponseBody> {
+        let status = match self {
+            Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::TimedOut => StatusCode::REQUEST_TIMEOUT,
+        };
+        let reason = status.canonical_reason().unwrap_or_default();
+        let mut response = Response::new(full_body(reason));
+        *response.status_mut() = status;
+        if let Self::RateLimited(retry_after) = self {
+            // NOTE: Retry-After is given in whole seconds, rounding
+            // up makes sure that clients never retry too early
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, secs.max(1).into());
+        }
+        if matches!(self, Self::BodyTooLarge | Self::TimedOut) {
+            // NOTE: The rest of the body was never read, so the connection
+            // can not be reused for any following requests from the client
+            response
+                .headers_mut()
+                .insert(CONNECTION, "close".parse().unwrap());
+        }
+        response
+    }
+}
+
+#[derive(Debug)]
+struct ConcurrencyLimit {
+    max: usize,
+    active: Cell<usize>,
+}
+
+/**
+    Guard for a request that counts towards the concurrency limit.
+*/
+#[derive(Debug)]
+pub(super) struct ActiveRequest(Rc<ConcurrencyLimit>);
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.0.active.set(self.0.active.get() - 1);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/**
+    A token bucket rate limiter, tracking a separate bucket for each client.
+
+    Every bucket holds up to `requests` tokens and is refilled evenly over the
+    window, so clients may send short bursts but never more than the limit.
+*/
+#[derive(Debug)]
+struct RateLimiter {
+    limit: ServeRateLimit,
+    clients: RefCell<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    fn try_acquire(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let capacity = f64::from(self.limit.requests);
+        let refill_per_sec = capacity / self.limit.window.as_secs_f64();
+
+        let mut clients = self.clients.borrow_mut();
+        if clients.len() >= MAX_TRACKED_CLIENTS && !clients.contains_key(&ip) {
+            let window = self.limit.window;
+            clients.retain(|_, bucket| now.duration_since(bucket.updated) < window);
+        }
+
+        let bucket = clients.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / refill_per_sec,
+            ))
+        }
+    }
+}
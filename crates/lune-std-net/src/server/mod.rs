@@ -4,7 +4,7 @@ use std::{
 };
 
 use hyper::server::conn::http1;
-use hyper_util::rt::TokioIo;
+use hyper_util::rt::{TokioIo, TokioTimer};
 use tokio::{net::TcpListener, pin};
 
 use mlua::prelude::*;
@@ -15,12 +15,16 @@ use lune_utils::TableBuilder;
 use super::config::ServeConfig;
 
 mod keys;
+mod limits;
 mod request;
 mod response;
 mod service;
+mod shutdown;
 
 use keys::SvcKeys;
+use limits::SvcLimits;
 use service::Svc;
+use shutdown::{signaled, ServeShutdown, ShutdownSignals};
 
 pub async fn serve<'lua>(
     lua: &'lua Lua,
@@ -39,41 +43,71 @@ pub async fn serve<'lua>(
         (Rc::clone(&rc), rc)
     };
 
+    let limits = SvcLimits::new(&config);
     let keys = SvcKeys::new(lua, config.handle_request, config.handle_web_socket)?;
     let svc = Svc {
         lua: lua_svc,
-        addr,
+        remote_addr: addr,
         keys,
+        limits,
+        web_socket_compression: config.web_socket_compression,
     };
 
-    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let mut http = http1::Builder::new();
+    http.keep_alive(true); // Web sockets need this
+    if let Some(max) = config.max_header_size {
+        http.max_buf_size(max);
+    }
+    if let Some(timeout) = config.read_timeout {
+        // NOTE: Hyper closes the connection when headers are not received
+        // in time, the body read timeout is handled by our own service
+        http.timer(TokioTimer::new()).header_read_timeout(timeout);
+    }
+
+    let (shutdown, signals) = ServeShutdown::new();
+    let shutdown = Rc::new(shutdown);
     lua.spawn_local(async move {
-        let mut shutdown_rx_outer = shutdown_rx.clone();
+        let ShutdownSignals {
+            stop_rx,
+            force_rx,
+            done_tx,
+        } = signals;
+        let mut stop_rx_outer = stop_rx.clone();
         loop {
             // Create futures for accepting new connections and shutting down
-            let fut_shutdown = shutdown_rx_outer.changed();
+            let fut_stop = signaled(&mut stop_rx_outer);
             let fut_accept = async {
-                let stream = match listener.accept().await {
-                    Err(_) => return,
-                    Ok((s, _)) => s,
+                let Ok((stream, remote_addr)) = listener.accept().await else {
+                    return;
                 };
 
                 let io = TokioIo::new(stream);
-                let svc = svc.clone();
-                let mut shutdown_rx_inner = shutdown_rx.clone();
+                let svc = Svc {
+                    remote_addr,
+                    ..svc.clone()
+                };
+                let mut stop_rx_inner = stop_rx.clone();
+                let mut force_rx_inner = force_rx.clone();
+                let done_tx_inner = done_tx.clone();
+                let http = http.clone();
 
                 lua_inner.spawn_local(async move {
-                    let conn = http1::Builder::new()
-                        .keep_alive(true) // Web sockets need this
-                        .serve_connection(io, svc)
-                        .with_upgrades();
+                    // NOTE: The server is only considered fully stopped once every
+                    // connection task, and with it this sender, has been dropped
+                    let _done = done_tx_inner;
+                    let conn = http.serve_connection(io, svc).with_upgrades();
                     // NOTE: Because we need to use keep_alive for websockets, we need to
-                    // also manually poll this future and handle the shutdown signal here
+                    // also manually poll this future and handle the shutdown signal here,
+                    // letting any requests that are currently being handled finish first
                     pin!(conn);
                     tokio::select! {
                         _ = conn.as_mut() => {}
-                        _ = shutdown_rx_inner.changed() => {
+                        () = signaled(&mut stop_rx_inner) => {
                             conn.as_mut().graceful_shutdown();
+                            tokio::select! {
+                                _ = conn.as_mut() => {}
+                                () = signaled(&mut force_rx_inner) => {}
+                            }
                         }
                     }
                 });
@@ -82,14 +116,7 @@ pub async fn serve<'lua>(
             // Wait for either a new connection or a shutdown signal
             tokio::select! {
                 () = fut_accept => {}
-                res = fut_shutdown => {
-                    // NOTE: We will only get a RecvError here if the serve handle is dropped,
-                    // this means lua has garbage collected it and the user does not want
-                    // to manually stop the server using the serve handle. Run forever.
-                    if res.is_ok() {
-                        break;
-                    }
-                }
+                () = fut_stop => break,
             }
         }
     });
@@ -97,9 +124,9 @@ pub async fn serve<'lua>(
     TableBuilder::new(lua)?
         .with_value("ip", addr.ip().to_string())?
         .with_value("port", addr.port())?
-        .with_function("stop", move |_, (): ()| match shutdown_tx.send(true) {
-            Ok(()) => Ok(()),
-            Err(_) => Err(LuaError::runtime("Server already stopped")),
+        .with_async_function("stop", move |_, grace: Option<f64>| {
+            let shutdown = Rc::clone(&shutdown);
+            async move { shutdown.stop(grace).await }
         })?
         .build_readonly()
 }
@@ -1,63 +1,126 @@
-use std::str::FromStr;
+use std::{io::Error as IoError, str::FromStr};
 
 use bstr::{BString, ByteSlice};
-use http_body_util::Full;
+use futures_util::StreamExt;
+use http_body_util::{combinators::BoxBody, BodyExt, Full, StreamBody};
 use hyper::{
-    body::Bytes,
-    header::{HeaderName, HeaderValue},
+    body::{Bytes, Frame},
+    header::{HeaderName, HeaderValue, CACHE_CONTROL, CONTENT_TYPE},
     HeaderMap, Response,
 };
 
 use mlua::prelude::*;
 
+use crate::{
+    sse::encode_event_chunk,
+    stream::{encode_bytes_chunk, spawn_body_producer, BodyChunkEncoder},
+};
+
+pub(super) type ResponseBody = BoxBody<Bytes, IoError>;
+
+/**
+    Creates a response body containing all of the given bytes at once.
+*/
+pub(super) fn full_body(bytes: impl Into<Bytes>) -> ResponseBody {
+    infallible_body(Full::new(bytes.into()))
+}
+
+/**
+    Converts a body that can never error, such as a web socket upgrade response body.
+*/
+pub(super) fn infallible_body(body: Full<Bytes>) -> ResponseBody {
+    body.map_err(|never| match never {}).boxed()
+}
+
 #[derive(Debug, Clone, Copy)]
 pub(super) enum LuaResponseKind {
     PlainText,
     Table,
 }
 
-pub(super) struct LuaResponse {
+pub(super) enum LuaResponseBody<'lua> {
+    Bytes(Vec<u8>),
+    Stream(LuaFunction<'lua>),
+    Events(LuaFunction<'lua>),
+}
+
+pub(super) struct LuaResponse<'lua> {
     pub(super) kind: LuaResponseKind,
     pub(super) status: u16,
     pub(super) headers: HeaderMap,
-    pub(super) body: Option<Vec<u8>>,
+    pub(super) body: Option<LuaResponseBody<'lua>>,
 }
 
-impl LuaResponse {
-    pub(super) fn into_response(self) -> LuaResult<Response<Full<Bytes>>> {
+impl<'lua> LuaResponse<'lua> {
+    pub(super) fn into_response(self, lua: &'lua Lua) -> LuaResult<Response<ResponseBody>> {
         Ok(match self.kind {
-            LuaResponseKind::PlainText => Response::builder()
-                .status(200)
-                .header("Content-Type", "text/plain")
-                .body(Full::new(Bytes::from(self.body.unwrap())))
-                .into_lua_err()?,
+            LuaResponseKind::PlainText => {
+                let Some(LuaResponseBody::Bytes(bytes)) = self.body else {
+                    unreachable!("Plain text responses always have a body")
+                };
+                Response::builder()
+                    .status(200)
+                    .header("Content-Type", "text/plain")
+                    .body(full_body(bytes))
+                    .into_lua_err()?
+            }
             LuaResponseKind::Table => {
+                let mut headers = self.headers;
+                let body = match self.body {
+                    None => full_body(Bytes::new()),
+                    Some(LuaResponseBody::Bytes(bytes)) => full_body(bytes),
+                    Some(LuaResponseBody::Stream(producer)) => {
+                        stream_body(lua, producer, encode_bytes_chunk)?
+                    }
+                    Some(LuaResponseBody::Events(producer)) => {
+                        // NOTE: Proxies and browsers must not buffer or cache event
+                        // streams, but handlers may still override these if needed
+                        headers
+                            .entry(CONTENT_TYPE)
+                            .or_insert(HeaderValue::from_static("text/event-stream"));
+                        headers
+                            .entry(CACHE_CONTROL)
+                            .or_insert(HeaderValue::from_static("no-cache"));
+                        stream_body(lua, producer, encode_event_chunk)?
+                    }
+                };
                 let mut response = Response::builder()
                     .status(self.status)
-                    .body(Full::new(Bytes::from(self.body.unwrap_or_default())))
+                    .body(body)
                     .into_lua_err()?;
-                response.headers_mut().extend(self.headers);
+                response.headers_mut().extend(headers);
                 response
             }
         })
     }
 }
 
-impl FromLua<'_> for LuaResponse {
-    fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
+fn stream_body<'lua>(
+    lua: &'lua Lua,
+    producer: LuaFunction<'lua>,
+    encode: BodyChunkEncoder,
+) -> LuaResult<ResponseBody> {
+    let chunks = spawn_body_producer(lua, producer, "=net.serve.body", encode)?
+        .map(|chunk| chunk.map(|c| Frame::data(Bytes::from(c))));
+    Ok(BodyExt::boxed(StreamBody::new(chunks)))
+}
+
+impl<'lua> FromLua<'lua> for LuaResponse<'lua> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         match value {
             // Plain strings from the handler are plaintext responses
             LuaValue::String(s) => Ok(Self {
                 kind: LuaResponseKind::PlainText,
                 status: 200,
                 headers: HeaderMap::new(),
-                body: Some(s.as_bytes().to_vec()),
+                body: Some(LuaResponseBody::Bytes(s.as_bytes().to_vec())),
             }),
             // Tables are more detailed responses with potential status, headers, body
             LuaValue::Table(t) => {
                 let status: Option<u16> = t.get("status")?;
                 let headers: Option<LuaTable> = t.get("headers")?;
-                let body: Option<BString> = t.get("body")?;
+                let body: LuaValue = t.get("body")?;
+                let events: Option<LuaFunction> = t.get("events")?;
 
                 let mut headers_map = HeaderMap::new();
                 if let Some(headers) = headers {
@@ -69,13 +132,26 @@ impl FromLua<'_> for LuaResponse {
                     }
                 }
 
-                let body_bytes = body.map(|s| s.as_bytes().to_vec());
+                let body = match (body, events) {
+                    (LuaValue::Nil, None) => None,
+                    (LuaValue::Nil, Some(events)) => Some(LuaResponseBody::Events(events)),
+                    (_, Some(_)) => {
+                        return Err(LuaError::runtime(
+                            "Responses may not have both a body and events",
+                        ))
+                    }
+                    (LuaValue::Function(producer), None) => Some(LuaResponseBody::Stream(producer)),
+                    (body, None) => {
+                        let body = BString::from_lua(body, lua)?;
+                        Some(LuaResponseBody::Bytes(body.as_bytes().to_vec()))
+                    }
+                };
 
                 Ok(Self {
                     kind: LuaResponseKind::Table,
                     status: status.unwrap_or(200),
                     headers: headers_map,
-                    body: body_bytes,
+                    body,
                 })
             }
             // Anything else is an error
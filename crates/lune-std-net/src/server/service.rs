@@ -1,65 +1,106 @@
-use std::{future::Future, net::SocketAddr, pin::Pin, rc::Rc};
+use std::{future::Future, net::SocketAddr, pin::Pin, rc::Rc, sync::Arc};
 
-use http_body_util::{BodyExt, Full};
-use hyper::{
-    body::{Bytes, Incoming},
-    service::Service,
-    Request, Response,
+use hyper::{body::Incoming, service::Service, Request, Response};
+use hyper_tungstenite::{
+    is_upgrade_request, tungstenite::protocol::Role, upgrade, WebSocketStream,
 };
-use hyper_tungstenite::{is_upgrade_request, upgrade};
+use hyper_util::rt::TokioIo;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 use mlua::prelude::*;
 use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
 
 use super::{
-    super::websocket::NetWebSocket, keys::SvcKeys, request::LuaRequest, response::LuaResponse,
+    super::{
+        config::WebSocketCompressionConfig,
+        deflate::{DeflateParams, DeflateStream, WebSocketDeflate, EXTENSIONS_HEADER},
+        websocket::NetWebSocket,
+    },
+    keys::SvcKeys,
+    limits::SvcLimits,
+    request::LuaRequest,
+    response::{infallible_body, LuaResponse, ResponseBody},
 };
 
 #[derive(Debug, Clone)]
 pub(super) struct Svc {
     pub(super) lua: Rc<Lua>,
-    pub(super) addr: SocketAddr,
+    pub(super) remote_addr: SocketAddr,
     pub(super) keys: SvcKeys,
+    pub(super) limits: SvcLimits,
+    pub(super) web_socket_compression: Option<WebSocketCompressionConfig>,
 }
 
 impl Service<Request<Incoming>> for Svc {
-    type Response = Response<Full<Bytes>>;
+    type Response = Response<ResponseBody>;
     type Error = LuaError;
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
 
-    fn call(&self, req: Request<Incoming>) -> Self::Future {
+    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
         let lua = self.lua.clone();
-        let addr = self.addr;
+        let addr = self.remote_addr;
         let keys = self.keys;
 
+        // NOTE: Limits are checked before reading the request body or creating
+        // any Lua threads, so that rejecting requests is as cheap as possible
+        if let Err(rejection) = self.limits.check_rate(addr.ip()) {
+            return Box::pin(async move { Ok(rejection.into_response()) });
+        }
+
         if keys.has_websocket_handler() && is_upgrade_request(&req) {
+            let compression = self.web_socket_compression.and_then(|config| {
+                DeflateParams::from_offers(req.headers()).map(|params| (config, params))
+            });
+            let lua_inner = lua.clone();
             Box::pin(async move {
-                let (res, sock) = upgrade(req, None).into_lua_err()?;
-
-                let lua_inner = lua.clone();
-                lua.spawn_local(async move {
-                    let sock = sock.await.unwrap();
-                    let lua_sock = NetWebSocket::new(sock);
-                    let lua_tab = lua_sock.into_lua_table(&lua_inner).unwrap();
+                let Some((config, params)) = compression else {
+                    let (res, sock) = upgrade(req, None).into_lua_err()?;
+                    lua.spawn_local(async move {
+                        let sock = sock.await.unwrap();
+                        handle_websocket(&lua_inner, keys, NetWebSocket::new(sock));
+                    });
+                    return Ok(res.map(infallible_body));
+                };
 
-                    let handler_websocket: LuaFunction =
-                        keys.websocket_handler(&lua_inner).unwrap().unwrap();
+                // NOTE: The upgrade must be taken out of the request before creating the
+                // response, which leaves the upgrade of the websocket returned by it unused
+                let upgraded = hyper::upgrade::on(&mut req);
+                let (mut res, _) = upgrade(req, None).into_lua_err()?;
+                res.headers_mut().insert(
+                    EXTENSIONS_HEADER,
+                    params.to_header_value().parse().into_lua_err()?,
+                );
 
-                    lua_inner
-                        .push_thread_back(handler_websocket, lua_tab)
-                        .unwrap();
+                lua.spawn_local(async move {
+                    let upgraded = upgraded.await.unwrap();
+                    let deflate = WebSocketDeflate::server(params, config);
+                    let stream =
+                        DeflateStream::server(TokioIo::new(upgraded), Arc::clone(&deflate));
+                    let sock = WebSocketStream::from_raw_socket(stream, Role::Server, None).await;
+                    handle_websocket(&lua_inner, keys, NetWebSocket::with_deflate(sock, deflate));
                 });
 
-                Ok(res)
+                Ok(res.map(infallible_body))
             })
         } else {
+            if let Err(rejection) = self.limits.check_body_size(req.body()) {
+                return Box::pin(async move { Ok(rejection.into_response()) });
+            }
+            let active = match self.limits.try_start() {
+                Ok(active) => active,
+                Err(rejection) => return Box::pin(async move { Ok(rejection.into_response()) }),
+            };
+            let limits = self.limits.clone();
             let (head, body) = req.into_parts();
 
             Box::pin(async move {
+                let _active = active;
                 let handler_request: LuaFunction = keys.request_handler(&lua).unwrap();
 
-                let body = body.collect().await.into_lua_err()?;
-                let body = body.to_bytes().to_vec();
+                let body = match limits.read_body(body).await? {
+                    Ok(body) => body,
+                    Err(rejection) => return Ok(rejection.into_response()),
+                };
 
                 let lua_req = LuaRequest {
                     _remote_addr: addr,
@@ -75,8 +116,19 @@ impl Service<Request<Incoming>> for Svc {
                     .get_thread_result(thread_id)
                     .expect("Missing handler thread result")?;
 
-                LuaResponse::from_lua_multi(thread_res, &lua)?.into_response()
+                LuaResponse::from_lua_multi(thread_res, &lua)?.into_response(&lua)
             })
         }
     }
 }
+
+fn handle_websocket<T>(lua: &Lua, keys: SvcKeys, sock: NetWebSocket<T>)
+where
+    T: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let lua_tab = sock.into_lua_table(lua).unwrap();
+
+    let handler_websocket: LuaFunction = keys.websocket_handler(lua).unwrap().unwrap();
+
+    lua.push_thread_back(handler_websocket, lua_tab).unwrap();
+}
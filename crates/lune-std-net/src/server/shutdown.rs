@@ -0,0 +1,100 @@
+use std::{cell::RefCell, future::pending, time::Duration};
+
+use tokio::{
+    sync::{mpsc, watch},
+    time::timeout,
+};
+
+use mlua::prelude::*;
+
+/**
+    Signals used by the server and its connection tasks to know when to shut down.
+*/
+pub(super) struct ShutdownSignals {
+    /// Changes to `true` once the server should stop accepting connections and requests.
+    pub stop_rx: watch::Receiver<bool>,
+    /// Changes to `true` once any remaining connections should be closed immediately.
+    pub force_rx: watch::Receiver<bool>,
+    /// Held by the server and every connection task, dropped once they have finished.
+    pub done_tx: mpsc::Sender<()>,
+}
+
+/**
+    Shutdown state for a server, owned by its serve handle.
+*/
+#[derive(Debug)]
+pub(super) struct ServeShutdown {
+    stop_tx: watch::Sender<bool>,
+    force_tx: watch::Sender<bool>,
+    done_rx: RefCell<Option<mpsc::Receiver<()>>>,
+}
+
+impl ServeShutdown {
+    pub(super) fn new() -> (Self, ShutdownSignals) {
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let (force_tx, force_rx) = watch::channel(false);
+        let (done_tx, done_rx) = mpsc::channel(1);
+        let shutdown = Self {
+            stop_tx,
+            force_tx,
+            done_rx: RefCell::new(Some(done_rx)),
+        };
+        let signals = ShutdownSignals {
+            stop_rx,
+            force_rx,
+            done_tx,
+        };
+        (shutdown, signals)
+    }
+
+    /**
+        Stops the server from accepting new connections and waits for all
+        requests that are currently being handled to finish.
+
+        If a grace period is given, any requests that are still being
+        handled once it has passed will have their connections closed.
+    */
+    pub(super) async fn stop(&self, grace: Option<f64>) -> LuaResult<()> {
+        let grace = grace
+            .map(|secs| {
+                Duration::try_from_secs_f64(secs).map_err(|_| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid grace period for stopping server - expected a positive number, got {secs}"
+                    ))
+                })
+            })
+            .transpose()?;
+
+        let Some(mut done_rx) = self.done_rx.borrow_mut().take() else {
+            return Err(LuaError::runtime("Server already stopped"));
+        };
+
+        self.stop_tx.send_replace(true);
+
+        // NOTE: Receiving only returns None once all senders have been
+        // dropped, meaning that the server and all connections are done
+        let finished = match grace {
+            None => true,
+            Some(grace) => timeout(grace, done_rx.recv()).await.is_ok(),
+        };
+        if !finished {
+            self.force_tx.send_replace(true);
+        }
+        done_rx.recv().await;
+
+        Ok(())
+    }
+}
+
+/**
+    Waits for the given shutdown signal to be set.
+
+    If the serve handle was dropped without the signal being set, meaning that lua
+    has garbage collected it and the user does not want to manually stop the server
+    using the serve handle, this will never resolve and the server will run forever.
+*/
+pub(super) async fn signaled(rx: &mut watch::Receiver<bool>) {
+    if rx.wait_for(|signaled| *signaled).await.is_err() {
+        pending::<()>().await;
+    }
+}
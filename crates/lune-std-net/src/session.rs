@@ -0,0 +1,122 @@
+use mlua::prelude::*;
+
+use reqwest::Url;
+
+use lune_utils::TableBuilder;
+
+use super::{
+    client::{NetClient, NetClientBuilder},
+    config::RequestConfig,
+    cookies::CookieJar,
+    error::wrap_net_errors,
+    hosts::host_overrides,
+    policy::host_policy,
+    send_request,
+    util::create_user_agent_header,
+};
+
+const REGISTRY_KEY: &str = "NetSession";
+
+// Requests made through a session throw the same errors as `net.request` does,
+// which is why they are wrapped, unlike the methods for reading cookies
+const NET_SESSION_IMPL_LUA: &str = r"
+return freeze({
+	request = wrap(function(...)
+		return session:request(...)
+	end),
+	getCookies = function(...)
+		return session:getCookies(...)
+	end,
+	clearCookies = function(...)
+		return session:clearCookies(...)
+	end,
+})
+";
+
+/**
+    A session that stores cookies from responses, and sends them along with later requests.
+*/
+#[derive(Debug, Clone)]
+pub struct NetSession {
+    client: NetClient,
+    jar: CookieJar,
+}
+
+impl NetSession {
+    pub fn new(lua: &Lua) -> LuaResult<Self> {
+        // NOTE: Redirects must be handled by the session client
+        // itself to be able to store cookies from all responses
+        let client = NetClientBuilder::new()
+            .headers(&[("User-Agent", create_user_agent_header(lua)?)])?
+            .redirects(false)
+            .host_policy(host_policy(lua))
+            .host_overrides(host_overrides(lua))
+            .build()?;
+        Ok(Self {
+            client,
+            jar: CookieJar::default(),
+        })
+    }
+
+    /**
+        Gets the shared session used by `net.request` when the `cookies` option is set.
+
+        The session is created the first time this is called.
+    */
+    pub fn from_registry(lua: &Lua) -> LuaResult<Self> {
+        if let LuaValue::UserData(ud) = lua.named_registry_value::<LuaValue>(REGISTRY_KEY)? {
+            Ok(ud.borrow::<Self>()?.clone())
+        } else {
+            let session = Self::new(lua)?;
+            lua.set_named_registry_value(REGISTRY_KEY, session.clone())?;
+            Ok(session)
+        }
+    }
+
+    pub fn client(&self) -> &NetClient {
+        &self.client
+    }
+
+    pub fn jar(&self) -> &CookieJar {
+        &self.jar
+    }
+
+    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+
+        let env = TableBuilder::new(lua)?
+            .with_value("session", self)?
+            .with_value("freeze", table_freeze)?
+            .with_function("wrap", |lua, f: LuaFunction| wrap_net_errors(lua, f))?
+            .build_readonly()?;
+
+        lua.load(NET_SESSION_IMPL_LUA)
+            .set_name("session")
+            .set_environment(env)
+            .eval()
+    }
+}
+
+impl LuaUserData for NetSession {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("request", |lua, this, config: RequestConfig| async move {
+            send_request(lua, config, Some(this)).await
+        });
+
+        methods.add_method("getCookies", |lua, this, url: String| {
+            let url = Url::parse(&url)
+                .map_err(|e| LuaError::RuntimeError(format!("Invalid url '{url}' - {e}")))?;
+            // NOTE: Cookies with longer paths come first, and should take precedence
+            let cookies = this.jar.cookies_for(&url);
+            lua.create_table_from(cookies.into_iter().rev())
+        });
+
+        methods.add_method("clearCookies", |_, this, (): ()| {
+            this.jar.clear();
+            Ok(())
+        });
+    }
+}
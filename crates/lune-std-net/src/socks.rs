@@ -0,0 +1,498 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+use mlua::prelude::*;
+use reqwest::Url;
+use tokio::{
+    io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::{
+    hosts::{resolve_host_checked, HostOverrides},
+    policy::HostPolicy,
+};
+
+// The largest request head that is read from reqwest before it is sent through the proxy
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+// Headers that are only meant for the proxy, or for the connection to it,
+// which are removed from plain http requests before they are sent along
+const PROXY_HEADERS: &[&str] = &["connection", "proxy-authorization", "proxy-connection"];
+
+const SOCKS_VERSION: u8 = 5;
+const SOCKS_AUTH_NONE: u8 = 0;
+const SOCKS_AUTH_PASSWORD: u8 = 2;
+const SOCKS_AUTH_UNACCEPTABLE: u8 = 0xFF;
+const SOCKS_CMD_CONNECT: u8 = 1;
+const SOCKS_ATYP_IPV4: u8 = 1;
+const SOCKS_ATYP_DOMAIN: u8 = 3;
+const SOCKS_ATYP_IPV6: u8 = 4;
+
+/**
+    A SOCKS5 proxy, parsed from a `socks5://` or `socks5h://` url.
+
+    With `socks5h`, hostnames are resolved by the proxy, otherwise they are
+    resolved by us, using any host overrides, and checked against the host policy.
+*/
+#[derive(Debug, Clone)]
+pub(crate) struct SocksProxy {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+    remote_dns: bool,
+}
+
+impl SocksProxy {
+    /**
+        Parses a SOCKS proxy url, returning `None` if the url is not for a SOCKS proxy.
+
+        # Errors
+
+        Errors if the url is for a SOCKS proxy, but is invalid or uses an unsupported version.
+    */
+    pub(crate) fn parse(url: &str) -> LuaResult<Option<Self>> {
+        let invalid =
+            |reason: &str| LuaError::RuntimeError(format!("Invalid proxy url '{url}' - {reason}"));
+
+        let Ok(parsed) = Url::parse(url) else {
+            return Ok(None);
+        };
+        let remote_dns = match parsed.scheme() {
+            "socks5" => false,
+            "socks5h" => true,
+            scheme if scheme.starts_with("socks") => {
+                return Err(invalid("only socks5 and socks5h proxies are supported"))
+            }
+            _ => return Ok(None),
+        };
+
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| invalid("missing host"))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = parsed.port().unwrap_or(1080);
+
+        let credentials = if parsed.username().is_empty() {
+            None
+        } else {
+            let decode = |s: &str| {
+                urlencoding::decode(s)
+                    .map(std::borrow::Cow::into_owned)
+                    .map_err(|_| invalid("username and password must be valid utf-8"))
+            };
+            let username = decode(parsed.username())?;
+            let password = decode(parsed.password().unwrap_or_default())?;
+            if username.len() > 255 || password.len() > 255 {
+                return Err(invalid(
+                    "username and password must be at most 255 bytes long",
+                ));
+            }
+            Some((username, password))
+        };
+
+        Ok(Some(Self {
+            host,
+            port,
+            credentials,
+            remote_dns,
+        }))
+    }
+
+    /**
+        Connects to the given host and port through the proxy.
+    */
+    async fn connect(
+        &self,
+        overrides: Option<&HostOverrides>,
+        policy: Option<&HostPolicy>,
+        host: &str,
+        port: u16,
+    ) -> io::Result<TcpStream> {
+        // NOTE: Overridden hosts are always connected to using their overridden
+        // addresses, even when the proxy would otherwise resolve hostnames itself
+        let overridden = overrides.is_some_and(|overrides| overrides.get(host).is_some());
+        let targets = if let Ok(ip) = host.parse::<IpAddr>() {
+            vec![SocksTarget::Addr(SocketAddr::new(ip, port))]
+        } else if self.remote_dns && !overridden {
+            vec![SocksTarget::Host(host.to_string(), port)]
+        } else {
+            resolve_host_checked(overrides, policy, host, port)
+                .await?
+                .into_iter()
+                .map(SocksTarget::Addr)
+                .collect()
+        };
+
+        let mut last_error = None;
+        for target in targets {
+            match self.connect_target(&target).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No addresses found for '{host}'"),
+            )
+        }))
+    }
+
+    async fn connect_target(&self, target: &SocksTarget) -> io::Result<TcpStream> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        stream.set_nodelay(true)?;
+
+        // Greeting, offering authentication with a password only if we have one
+        let methods: &[u8] = if self.credentials.is_some() {
+            &[SOCKS_AUTH_NONE, SOCKS_AUTH_PASSWORD]
+        } else {
+            &[SOCKS_AUTH_NONE]
+        };
+        let mut greeting = vec![SOCKS_VERSION, u8::try_from(methods.len()).unwrap()];
+        greeting.extend_from_slice(methods);
+        stream.write_all(&greeting).await?;
+
+        let mut chosen = [0; 2];
+        stream.read_exact(&mut chosen).await?;
+        if chosen[0] != SOCKS_VERSION {
+            return Err(socks_error("proxy is not a SOCKS5 proxy"));
+        }
+        match (chosen[1], &self.credentials) {
+            (SOCKS_AUTH_NONE, _) => {}
+            (SOCKS_AUTH_PASSWORD, Some((username, password))) => {
+                authenticate(&mut stream, username, password).await?;
+            }
+            (SOCKS_AUTH_UNACCEPTABLE, _) => {
+                return Err(socks_error(if self.credentials.is_some() {
+                    "proxy did not accept any authentication method"
+                } else {
+                    "proxy requires authentication, but no username and password were given"
+                }))
+            }
+            _ => {
+                return Err(socks_error(
+                    "proxy chose an unsupported authentication method",
+                ))
+            }
+        }
+
+        // Connect request, followed by the reply, which contains an address that we skip
+        let mut request = vec![SOCKS_VERSION, SOCKS_CMD_CONNECT, 0];
+        target.encode(&mut request)?;
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(socks_error("proxy sent an invalid reply"));
+        }
+        if reply[1] != 0 {
+            return Err(socks_error(reply_message(reply[1])));
+        }
+        let skip = match reply[3] {
+            SOCKS_ATYP_IPV4 => 4,
+            SOCKS_ATYP_IPV6 => 16,
+            SOCKS_ATYP_DOMAIN => usize::from(stream.read_u8().await?),
+            _ => return Err(socks_error("proxy sent an invalid address type")),
+        };
+        let mut bound = vec![0; skip + 2];
+        stream.read_exact(&mut bound).await?;
+
+        Ok(stream)
+    }
+}
+
+enum SocksTarget {
+    Host(String, u16),
+    Addr(SocketAddr),
+}
+
+impl SocksTarget {
+    fn encode(&self, out: &mut Vec<u8>) -> io::Result<()> {
+        let port = match self {
+            Self::Addr(SocketAddr::V4(addr)) => {
+                out.push(SOCKS_ATYP_IPV4);
+                out.extend_from_slice(&addr.ip().octets());
+                addr.port()
+            }
+            Self::Addr(SocketAddr::V6(addr)) => {
+                out.push(SOCKS_ATYP_IPV6);
+                out.extend_from_slice(&addr.ip().octets());
+                addr.port()
+            }
+            Self::Host(host, port) => {
+                let len = u8::try_from(host.len())
+                    .map_err(|_| socks_error("hostname is longer than 255 bytes"))?;
+                out.push(SOCKS_ATYP_DOMAIN);
+                out.push(len);
+                out.extend_from_slice(host.as_bytes());
+                *port
+            }
+        };
+        out.extend_from_slice(&port.to_be_bytes());
+        Ok(())
+    }
+}
+
+async fn authenticate(stream: &mut TcpStream, username: &str, password: &str) -> io::Result<()> {
+    // NOTE: Lengths were already checked when parsing the proxy url
+    let mut request = vec![1, u8::try_from(username.len()).unwrap()];
+    request.extend_from_slice(username.as_bytes());
+    request.push(u8::try_from(password.len()).unwrap());
+    request.extend_from_slice(password.as_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] == 0 {
+        Ok(())
+    } else {
+        Err(socks_error(
+            "proxy did not accept the username and password",
+        ))
+    }
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "proxy failed to connect",
+        2 => "connection not allowed by the proxy",
+        3 => "network unreachable from the proxy",
+        4 => "host unreachable from the proxy",
+        5 => "connection refused by the host",
+        6 => "connection to the host timed out",
+        7 => "proxy does not support connecting",
+        8 => "proxy does not support the address type",
+        _ => "proxy failed with an unknown error",
+    }
+}
+
+fn socks_error(message: &str) -> io::Error {
+    io::Error::other(format!("SOCKS proxy error - {message}"))
+}
+
+/**
+    A local HTTP proxy that sends everything it receives through a SOCKS proxy.
+
+    Reqwest only supports SOCKS proxies with an extra feature, which needs
+    dependencies that are not available, so requests are instead sent to this
+    proxy using the HTTP proxy support of reqwest. It listens on a random port
+    on the loopback interface, and stops listening once it is dropped.
+
+    HTTPS requests are tunneled using `CONNECT` requests, as with any other proxy.
+    Plain HTTP requests are sent along once their target has been turned back into
+    a path, and with the connection closed after a single request, so that reqwest
+    never sends a request for another host through the same connection.
+*/
+#[derive(Debug)]
+pub(crate) struct SocksBridge {
+    url: String,
+    task: JoinHandle<()>,
+}
+
+impl SocksBridge {
+    /**
+        Starts listening for requests to send through the given SOCKS proxy.
+
+        # Errors
+
+        Errors if the listener could not be bound.
+    */
+    pub(crate) fn start(
+        proxy: SocksProxy,
+        overrides: Option<Arc<HostOverrides>>,
+        policy: Option<Arc<HostPolicy>>,
+    ) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        listener.set_nonblocking(true)?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let listener = TcpListener::from_std(listener)?;
+
+        let context = Arc::new(BridgeContext {
+            proxy,
+            overrides,
+            policy,
+        });
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let context = Arc::clone(&context);
+                tokio::spawn(async move { context.handle(stream).await });
+            }
+        });
+
+        Ok(Self { url, task })
+    }
+
+    /**
+        The url of the local HTTP proxy, to give to reqwest.
+    */
+    pub(crate) fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for SocksBridge {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct BridgeContext {
+    proxy: SocksProxy,
+    overrides: Option<Arc<HostOverrides>>,
+    policy: Option<Arc<HostPolicy>>,
+}
+
+impl BridgeContext {
+    async fn handle(&self, mut client: TcpStream) {
+        let Ok((head, rest)) = read_head(&mut client).await else {
+            return;
+        };
+        let Some(request) = RequestHead::parse(&head) else {
+            client
+                .write_all(&error_response("invalid request"))
+                .await
+                .ok();
+            return;
+        };
+
+        let (host, port) = &request.destination;
+        let connected = self
+            .proxy
+            .connect(
+                self.overrides.as_deref(),
+                self.policy.as_deref(),
+                host,
+                *port,
+            )
+            .await;
+        let mut upstream = match connected {
+            Ok(upstream) => upstream,
+            Err(e) => {
+                client.write_all(&error_response(&e.to_string())).await.ok();
+                return;
+            }
+        };
+
+        let sent = match request.origin_form() {
+            None => {
+                client
+                    .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                    .await
+            }
+            Some(head) => upstream.write_all(head.as_bytes()).await,
+        };
+        if sent.is_ok() && upstream.write_all(&rest).await.is_ok() {
+            copy_bidirectional(&mut client, &mut upstream).await.ok();
+        }
+    }
+}
+
+/**
+    The head of a request sent to the local proxy by reqwest, which is either
+    a `CONNECT` request for tunneling, or a plain HTTP request with a full url.
+*/
+struct RequestHead<'a> {
+    method: &'a str,
+    version: &'a str,
+    headers: Vec<&'a str>,
+    destination: (String, u16),
+    path: Option<String>,
+}
+
+impl<'a> RequestHead<'a> {
+    fn parse(head: &'a [u8]) -> Option<Self> {
+        let head = std::str::from_utf8(head).ok()?;
+        let mut lines = head.split("\r\n").filter(|line| !line.is_empty());
+        let mut parts = lines.next()?.split(' ');
+        let (method, target, version) = (parts.next()?, parts.next()?, parts.next()?);
+
+        let (destination, path) = if method.eq_ignore_ascii_case("CONNECT") {
+            (split_host_port(target)?, None)
+        } else {
+            let url = Url::parse(target).ok()?;
+            let host = url
+                .host_str()?
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            let mut path = url.path().to_string();
+            if let Some(query) = url.query() {
+                path.push('?');
+                path.push_str(query);
+            }
+            ((host.to_string(), url.port_or_known_default()?), Some(path))
+        };
+
+        Some(Self {
+            method,
+            version,
+            headers: lines.collect(),
+            destination,
+            path,
+        })
+    }
+
+    /**
+        Turns a plain HTTP request back into a request with only a path as its target,
+        as it would have been sent without a proxy, or returns `None` for tunnels.
+    */
+    fn origin_form(&self) -> Option<String> {
+        let path = self.path.as_deref()?;
+        let mut head = format!("{} {path} {}\r\n", self.method, self.version);
+        for header in &self.headers {
+            let name = header.split(':').next().unwrap_or_default().trim();
+            if !PROXY_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h)) {
+                head.push_str(header);
+                head.push_str("\r\n");
+            }
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        Some(head)
+    }
+}
+
+/**
+    Reads the head of a request, returning it along with
+    anything that was read after it, such as part of the body.
+*/
+async fn read_head(stream: &mut TcpStream) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        // NOTE: The end of the head may be split across reads, so we search a bit before
+        let start = buf.len().saturating_sub(3);
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf[start..].windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(start + pos + 4);
+            return Ok((buf, rest));
+        }
+        if buf.len() > MAX_HEAD_SIZE {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+    }
+}
+
+fn split_host_port(target: &str) -> Option<(String, u16)> {
+    let (host, port) = target.rsplit_once(':')?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Some((host.to_string(), port.parse().ok()?))
+}
+
+fn error_response(message: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 502 Bad Gateway\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{message}",
+        message.len()
+    )
+    .into_bytes()
+}
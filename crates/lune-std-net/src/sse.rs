@@ -0,0 +1,287 @@
+use std::{fmt::Write, mem::take, time::Duration};
+
+use bstr::ByteSlice;
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+const DEFAULT_EVENT_TYPE: &str = "message";
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/**
+    A single server-sent event, as described in the HTML specification:
+
+    <https://html.spec.whatwg.org/multipage/server-sent-events.html>
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub data: Option<String>,
+    pub id: Option<String>,
+    pub retry: Option<Duration>,
+    pub comment: Option<String>,
+}
+
+impl SseEvent {
+    /**
+        Encodes the event in the `text/event-stream` format, including the blank line
+        that ends it, so that encoded events may be sent one after another as-is.
+    */
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = String::new();
+        if let Some(comment) = &self.comment {
+            for line in split_lines(comment) {
+                writeln!(encoded, ": {line}").ok();
+            }
+        }
+        if let Some(event) = &self.event {
+            writeln!(encoded, "event: {event}").ok();
+        }
+        if let Some(id) = &self.id {
+            writeln!(encoded, "id: {id}").ok();
+        }
+        if let Some(retry) = self.retry {
+            writeln!(encoded, "retry: {}", retry.as_millis()).ok();
+        }
+        if let Some(data) = &self.data {
+            // NOTE: Lines in data must be sent as separate fields, which
+            // also sends empty data as a single field with an empty value
+            for line in split_lines(data) {
+                writeln!(encoded, "data: {line}").ok();
+            }
+        }
+        encoded.push('\n');
+        encoded.into_bytes()
+    }
+}
+
+fn split_lines(s: &str) -> Vec<String> {
+    s.replace("\r\n", "\n")
+        .split(['\r', '\n'])
+        .map(ToString::to_string)
+        .collect()
+}
+
+impl<'lua> FromLua<'lua> for SseEvent {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            // Plain strings are events with only data
+            LuaValue::String(s) => Ok(Self {
+                data: Some(s.to_str()?.to_string()),
+                ..Self::default()
+            }),
+            LuaValue::Table(tab) => {
+                let single_line = |key: &'static str| {
+                    let value = tab.get::<_, Option<String>>(key).map_err(|_| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid value for '{key}' in server-sent event - expected a string"
+                        ))
+                    })?;
+                    match value {
+                        Some(value) if value.contains(['\r', '\n']) => {
+                            Err(LuaError::RuntimeError(format!(
+                                "Invalid value for '{key}' in server-sent event - must not contain newlines"
+                            )))
+                        }
+                        value => Ok(value),
+                    }
+                };
+                let event = single_line("event")?;
+                let id = single_line("id")?;
+                let data = match tab.get::<_, LuaValue>("data")? {
+                    LuaValue::Nil => None,
+                    LuaValue::String(s) => Some(s.to_str()?.to_string()),
+                    // Numbers and booleans are common enough for dashboards to be allowed as-is
+                    LuaValue::Integer(i) => Some(i.to_string()),
+                    LuaValue::Number(n) => Some(n.to_string()),
+                    LuaValue::Boolean(b) => Some(b.to_string()),
+                    value => {
+                        return Err(LuaError::RuntimeError(format!(
+                        "Invalid value for 'data' in server-sent event - expected a string, got {}",
+                        value.type_name()
+                    )))
+                    }
+                };
+                let retry = match tab.get::<_, Option<f64>>("retry") {
+                    Ok(None) => None,
+                    Ok(Some(secs)) => Some(Duration::try_from_secs_f64(secs).map_err(|_| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid value for 'retry' in server-sent event - expected a positive number, got {secs}"
+                        ))
+                    })?),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid value for 'retry' in server-sent event - expected a number"
+                                .to_string(),
+                        ))
+                    }
+                };
+                let comment = tab.get::<_, Option<String>>("comment").map_err(|_| {
+                    LuaError::RuntimeError(
+                        "Invalid value for 'comment' in server-sent event - expected a string"
+                            .to_string(),
+                    )
+                })?;
+                Ok(Self {
+                    event,
+                    data,
+                    id,
+                    retry,
+                    comment,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServerSentEvent",
+                message: Some(format!(
+                    "Invalid server-sent event - expected string or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for SseEvent {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("event", self.event.as_deref().unwrap_or(DEFAULT_EVENT_TYPE))?
+            .with_value("data", self.data.unwrap_or_default())?
+            .with_value("id", self.id)?
+            .with_value("retry", self.retry.map(|retry| retry.as_secs_f64()))?
+            .build_readonly()?
+            .into_lua(lua)
+    }
+}
+
+/**
+    Encodes a chunk returned by a server-sent event producer.
+*/
+pub fn encode_event_chunk<'lua>(lua: &'lua Lua, chunk: LuaValue<'lua>) -> LuaResult<Vec<u8>> {
+    Ok(SseEvent::from_lua(chunk, lua)?.encode())
+}
+
+/**
+    An incremental decoder for `text/event-stream` bodies.
+
+    Bytes are pushed into the decoder as they are received, and complete
+    events may then be taken out using `next_event`. Any partial lines are
+    kept around until the rest of them has been pushed.
+*/
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    started: bool,
+    skip_line_feed: bool,
+    last_id: Option<String>,
+    pending_event: Option<String>,
+    pending_data: Option<String>,
+    pending_retry: Option<Duration>,
+}
+
+impl SseDecoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        // NOTE: The stream may start with a byte order mark, which should be skipped,
+        // and which may be split across several chunks that we need to wait for
+        if self.started {
+            return;
+        }
+        let prefix = &self.buffer[..self.buffer.len().min(BOM.len())];
+        if prefix.len() == BOM.len() || !BOM.starts_with(prefix) {
+            self.started = true;
+            if self.buffer.starts_with(BOM) {
+                self.buffer.drain(..BOM.len());
+            }
+        }
+    }
+
+    /**
+        Takes out any bytes that have been pushed but not yet decoded.
+    */
+    pub fn take_buffered(&mut self) -> Option<Vec<u8>> {
+        (!self.buffer.is_empty()).then(|| take(&mut self.buffer))
+    }
+
+    /**
+        Decodes the next complete event, if one has been fully received.
+    */
+    pub fn next_event(&mut self) -> Option<SseEvent> {
+        while let Some(line) = self.next_line() {
+            if line.is_empty() {
+                if let Some(event) = self.dispatch() {
+                    return Some(event);
+                }
+                continue;
+            }
+
+            let line = line.to_str_lossy();
+            let (field, value) = match line.split_once(':') {
+                // Lines starting with a colon are comments
+                Some(("", _)) => continue,
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line.as_ref(), ""),
+            };
+            match field {
+                "event" => self.pending_event = Some(value.to_string()),
+                "data" => {
+                    let data = self.pending_data.get_or_insert_with(String::new);
+                    data.push_str(value);
+                    data.push('\n');
+                }
+                "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+                "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                    if let Ok(millis) = value.parse() {
+                        self.pending_retry = Some(Duration::from_millis(millis));
+                    }
+                }
+                // Unknown fields must be ignored
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // Events without any data are not dispatched, but still reset the
+    // event type, the retry field is however not part of the event itself
+    // and we pass it along with the next event that has data, if any
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.pending_event.take();
+        let mut data = self.pending_data.take()?;
+        data.pop();
+        Some(SseEvent {
+            event,
+            data: Some(data),
+            id: self.last_id.clone(),
+            retry: self.pending_retry.take(),
+            comment: None,
+        })
+    }
+
+    // Lines may end with CRLF, LF, or just CR, which means that a CR at the very end
+    // of the buffer may be followed by a LF that has not yet been received
+    fn next_line(&mut self) -> Option<Vec<u8>> {
+        if self.skip_line_feed && !self.buffer.is_empty() {
+            self.skip_line_feed = false;
+            if self.buffer[0] == b'\n' {
+                self.buffer.remove(0);
+            }
+        }
+        let end = self
+            .buffer
+            .iter()
+            .position(|b| matches!(b, b'\r' | b'\n'))?;
+        let terminator_len = match self.buffer.get(end + 1) {
+            Some(b'\n') if self.buffer[end] == b'\r' => 2,
+            None if self.buffer[end] == b'\r' => {
+                self.skip_line_feed = true;
+                1
+            }
+            _ => 1,
+        };
+        let mut line: Vec<u8> = self.buffer.drain(..end + terminator_len).collect();
+        line.truncate(end);
+        Some(line)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    io::Error as IoError,
+    sync::{Arc, Mutex},
+};
+
+use bstr::{BString, ByteSlice};
+use futures_util::{stream, Stream};
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSchedulerExt;
+use tokio::sync::mpsc::{channel, Sender};
+
+use lune_utils::TableBuilder;
+
+// Number of chunks that may be produced before the
+// producer has to wait for them to be sent over the network
+const BODY_STREAM_BUFFER_SIZE: usize = 8;
+
+// Drives a producer function until it returns nil, sending each chunk it returns,
+// and then closes the stream - passing an error along if the producer errored
+const BODY_PRODUCER_IMPL_LUA: &str = r"
+local success, err = pcall(function()
+    while true do
+        local chunk = producer()
+        if chunk == nil then
+            break
+        end
+        send(chunk)
+    end
+end)
+close(if success then nil else tostring(err))
+";
+
+pub type BodyChunkResult = Result<Vec<u8>, IoError>;
+type BodyChunkSender = Arc<Mutex<Option<Sender<BodyChunkResult>>>>;
+
+/**
+    Converts a value returned by a body producer into bytes to send.
+*/
+pub type BodyChunkEncoder = for<'lua> fn(&'lua Lua, LuaValue<'lua>) -> LuaResult<Vec<u8>>;
+
+/**
+    Creates a streaming request body from a Lua producer function.
+
+    The producer is called repeatedly in a new Lua thread on the scheduler,
+    and may yield, until it returns `nil` - each returned chunk is sent as part of the body.
+*/
+pub fn create_body_stream<'lua>(
+    lua: &'lua Lua,
+    producer: LuaFunction<'lua>,
+) -> LuaResult<reqwest::Body> {
+    let chunks = spawn_body_producer(lua, producer, "=net.request.body", encode_bytes_chunk)?;
+    Ok(reqwest::Body::wrap_stream(chunks))
+}
+
+/**
+    Drives a Lua producer function in a new Lua thread on the scheduler,
+    returning a stream of all chunks that it produces, after encoding.
+
+    The producer stops being called once the stream has been dropped.
+*/
+pub fn spawn_body_producer<'lua>(
+    lua: &'lua Lua,
+    producer: LuaFunction<'lua>,
+    name: &'static str,
+    encode: BodyChunkEncoder,
+) -> LuaResult<impl Stream<Item = BodyChunkResult>> {
+    let (tx, mut rx) = channel::<BodyChunkResult>(BODY_STREAM_BUFFER_SIZE);
+    let tx: BodyChunkSender = Arc::new(Mutex::new(Some(tx)));
+
+    let tx_send = Arc::clone(&tx);
+    let send = lua.create_async_function(move |lua, chunk: LuaValue| {
+        let tx = tx_send.lock().unwrap().clone();
+        let chunk = encode(lua, chunk);
+        async move {
+            let tx = tx.ok_or_else(|| LuaError::runtime("Body stream was closed"))?;
+            tx.send(Ok(chunk?))
+                .await
+                .map_err(|_| LuaError::runtime("Body stream was closed"))
+        }
+    })?;
+
+    let tx_close = Arc::clone(&tx);
+    let close = lua.create_async_function(move |_, err: Option<String>| {
+        let tx = tx_close.lock().unwrap().take();
+        async move {
+            if let (Some(tx), Some(err)) = (tx, err) {
+                let err = IoError::other(err);
+                tx.send(Err(err)).await.ok();
+            }
+            Ok(())
+        }
+    })?;
+
+    let env = TableBuilder::new(lua)?
+        .with_value("pcall", lua.globals().get::<_, LuaFunction>("pcall")?)?
+        .with_value("tostring", lua.globals().get::<_, LuaFunction>("tostring")?)?
+        .with_value("producer", producer)?
+        .with_value("send", send)?
+        .with_value("close", close)?
+        .build_readonly()?;
+
+    let thread = lua
+        .load(BODY_PRODUCER_IMPL_LUA)
+        .set_name(name)
+        .set_environment(env)
+        .into_function()?;
+    lua.push_thread_front(thread, ())?;
+
+    Ok(stream::poll_fn(move |cx| rx.poll_recv(cx)))
+}
+
+/**
+    Encodes a chunk returned by a body producer as plain bytes.
+*/
+pub fn encode_bytes_chunk<'lua>(lua: &'lua Lua, chunk: LuaValue<'lua>) -> LuaResult<Vec<u8>> {
+    let chunk = BString::from_lua(chunk, lua)?;
+    Ok(chunk.as_bytes().to_vec())
+}
@@ -0,0 +1,172 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+
+use tokio::{
+    net::{ToSocketAddrs, UdpSocket},
+    sync::Notify,
+};
+
+use lune_utils::TableBuilder;
+
+use super::{
+    config::DEFAULT_IP_ADDRESS,
+    hosts::{host_overrides, resolve_host_checked},
+    policy::host_policy,
+};
+
+// Maximum size of a single datagram, any larger datagrams will be truncated
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+// Sockets are given to Lua as frozen tables with the address and port that they
+// were bound to, and methods that are called with dot syntax instead of colons
+const UDP_SOCKET_IMPL_LUA: &str = r"
+return freeze({
+	address = address,
+	port = port,
+	close = function(...)
+		return socket:close(...)
+	end,
+	sendTo = function(...)
+		return socket:sendTo(...)
+	end,
+	receive = function(...)
+		return socket:receive(...)
+	end,
+})
+";
+
+/**
+    A UDP socket that was bound using `net.udp.bind`.
+
+    Closing the socket drops it, which frees the port once no pending
+    sends or receives are using it anymore, even if the socket is still
+    referenced from Lua.
+*/
+#[derive(Debug, Clone)]
+pub struct NetUdpSocket {
+    inner: Arc<Mutex<Option<Arc<UdpSocket>>>>,
+    closed_notify: Arc<Notify>,
+}
+
+impl NetUdpSocket {
+    pub async fn bind(port: u16, address: Option<String>) -> LuaResult<Self> {
+        let address: IpAddr = match address {
+            Some(addr) => addr.parse().map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "IP address format is incorrect - \
+                    expected an IP in the form '0.0.0.0', got '{addr}'"
+                ))
+            })?,
+            None => DEFAULT_IP_ADDRESS,
+        };
+
+        let socket = UdpSocket::bind(SocketAddr::new(address, port))
+            .await
+            .map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to bind UDP socket to {address}:{port} - {e}"
+                ))
+            })?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Some(Arc::new(socket)))),
+            closed_notify: Arc::new(Notify::new()),
+        })
+    }
+
+    fn socket(&self) -> LuaResult<Arc<UdpSocket>> {
+        match self.inner.lock().unwrap().as_ref() {
+            Some(socket) => Ok(Arc::clone(socket)),
+            None => Err(LuaError::runtime("Socket has already been closed")),
+        }
+    }
+
+    pub async fn send_to(&self, data: &[u8], target: impl ToSocketAddrs) -> LuaResult<usize> {
+        let socket = self.socket()?;
+        socket.send_to(data, target).await.into_lua_err()
+    }
+
+    pub async fn receive(&self) -> LuaResult<Option<(Vec<u8>, SocketAddr)>> {
+        let socket = self.socket()?;
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        tokio::select! {
+            res = socket.recv_from(&mut buf) => {
+                let (len, addr) = res.into_lua_err()?;
+                buf.truncate(len);
+                Ok(Some((buf, addr)))
+            }
+            () = self.closed_notify.notified() => Ok(None),
+        }
+    }
+
+    pub fn close(&self) -> LuaResult<()> {
+        match self.inner.lock().unwrap().take() {
+            Some(_) => {
+                self.closed_notify.notify_waiters();
+                Ok(())
+            }
+            None => Err(LuaError::runtime("Socket has already been closed")),
+        }
+    }
+
+    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+
+        let local_addr = self.socket()?.local_addr().into_lua_err()?;
+
+        let env = TableBuilder::new(lua)?
+            .with_value("socket", self)?
+            .with_value("address", local_addr.ip().to_string())?
+            .with_value("port", local_addr.port())?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+
+        lua.load(UDP_SOCKET_IMPL_LUA)
+            .set_name("udp")
+            .set_environment(env)
+            .eval()
+    }
+}
+
+impl LuaUserData for NetUdpSocket {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("close", |_, this, (): ()| this.close());
+
+        methods.add_async_method(
+            "sendTo",
+            |lua, this, (data, address, port): (BString, String, u16)| async move {
+                let policy = host_policy(lua);
+                if let Some(policy) = &policy {
+                    policy.check_host(&address, Some(port))?;
+                }
+                let overrides = host_overrides(lua);
+                let addrs =
+                    resolve_host_checked(overrides.as_deref(), policy.as_deref(), &address, port)
+                        .await
+                        .into_lua_err()?;
+                // NOTE: Datagrams are only ever sent to the first address
+                this.send_to(data.as_bytes(), addrs.as_slice()).await
+            },
+        );
+
+        methods.add_async_method("receive", |lua, this, (): ()| async move {
+            match this.receive().await? {
+                None => Ok(LuaValue::Nil),
+                Some((data, addr)) => TableBuilder::new(lua)?
+                    .with_value("data", lua.create_string(data)?)?
+                    .with_value("address", addr.ip().to_string())?
+                    .with_value("port", addr.port())?
+                    .build_readonly()
+                    .map(LuaValue::Table),
+            }
+        });
+    }
+}
@@ -25,6 +25,8 @@ use hyper_tungstenite::{
 
 use lune_utils::TableBuilder;
 
+use crate::deflate::WebSocketDeflate;
+
 // Wrapper implementation for compatibility and changing colon syntax to dot syntax
 const WEB_SOCKET_IMPL_LUA: &str = r#"
 return freeze(setmetatable({
@@ -41,6 +43,8 @@ return freeze(setmetatable({
 	__index = function(self, key)
 		if key == "closeCode" then
 			return websocket.closeCode
+		elseif key == "compressed" then
+			return websocket.compressed
 		end
 	end,
 }))
@@ -52,6 +56,7 @@ pub struct NetWebSocket<T> {
     close_code_value: Arc<AtomicU16>,
     read_stream: Arc<AsyncMutex<SplitStream<WebSocketStream<T>>>>,
     write_stream: Arc<AsyncMutex<SplitSink<WebSocketStream<T>, WsMessage>>>,
+    deflate: Option<Arc<WebSocketDeflate>>,
 }
 
 impl<T> Clone for NetWebSocket<T> {
@@ -61,6 +66,7 @@ impl<T> Clone for NetWebSocket<T> {
             close_code_value: Arc::clone(&self.close_code_value),
             read_stream: Arc::clone(&self.read_stream),
             write_stream: Arc::clone(&self.write_stream),
+            deflate: self.deflate.clone(),
         }
     }
 }
@@ -77,9 +83,27 @@ where
             close_code_value: Arc::new(AtomicU16::new(0)),
             read_stream: Arc::new(AsyncMutex::new(read)),
             write_stream: Arc::new(AsyncMutex::new(write)),
+            deflate: None,
+        }
+    }
+
+    /**
+        Creates a web socket that compresses and decompresses its messages using the
+        given state, which must also be used by the `DeflateStream` below the socket.
+    */
+    pub fn with_deflate(value: WebSocketStream<T>, deflate: Arc<WebSocketDeflate>) -> Self {
+        Self {
+            deflate: Some(deflate),
+            ..Self::new(value)
         }
     }
 
+    fn is_compressed(&self) -> bool {
+        self.deflate
+            .as_ref()
+            .is_some_and(|deflate| deflate.is_enabled())
+    }
+
     fn get_close_code(&self) -> Option<u16> {
         if self.close_code_exists.load(Ordering::Relaxed) {
             Some(self.close_code_value.load(Ordering::Relaxed))
@@ -95,12 +119,22 @@ where
 
     pub async fn send(&self, msg: WsMessage) -> LuaResult<()> {
         let mut ws = self.write_stream.lock().await;
+        // NOTE: Messages are compressed while holding the lock, since
+        // the other side decompresses them in the order they were sent
+        let msg = match &self.deflate {
+            Some(deflate) => deflate.compress(msg)?,
+            None => msg,
+        };
         ws.send(msg).await.into_lua_err()
     }
 
     pub async fn next(&self) -> LuaResult<Option<WsMessage>> {
         let mut ws = self.read_stream.lock().await;
-        ws.next().await.transpose().into_lua_err()
+        let msg = ws.next().await.transpose().into_lua_err()?;
+        match (&self.deflate, msg) {
+            (Some(deflate), Some(msg)) => deflate.decompress(msg).map(Some),
+            (_, msg) => Ok(msg),
+        }
     }
 
     pub async fn close(&self, code: Option<u16>) -> LuaResult<()> {
@@ -152,6 +186,7 @@ where
 {
     fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("closeCode", |_, this| Ok(this.get_close_code()));
+        fields.add_field_method_get("compressed", |_, this| Ok(this.is_compressed()));
     }
 
     fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
@@ -0,0 +1,45 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSpawnExt;
+use notify_rust::Notification;
+
+use lune_utils::TableBuilder;
+
+mod options;
+
+use self::options::NotificationOptions;
+
+/**
+    Creates the `notify` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("send", notify_send)?
+        .build_readonly()
+}
+
+async fn notify_send(lua: &Lua, options: NotificationOptions) -> LuaResult<()> {
+    // NOTE: Showing a notification may block for a while on some
+    // platforms, such as when connecting to the session bus on Linux
+    lua.spawn_blocking(move || send_notification(&options))
+        .await
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to send notification - {e}")))
+}
+
+fn send_notification(options: &NotificationOptions) -> Result<(), notify_rust::error::Error> {
+    let mut notification = Notification::new();
+    notification.summary(&options.title);
+    if let Some(body) = &options.body {
+        notification.body(body);
+    }
+    if let Some(icon) = &options.icon {
+        notification.icon(icon);
+    }
+    notification.show()?;
+    Ok(())
+}
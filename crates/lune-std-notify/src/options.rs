@@ -0,0 +1,106 @@
+use mlua::prelude::*;
+
+/**
+    Options for sending a notification using `notify.send`.
+*/
+#[derive(Debug, Clone)]
+pub struct NotificationOptions {
+    pub title: String,
+    pub body: Option<String>,
+    pub icon: Option<String>,
+}
+
+impl<'lua> FromLua<'lua> for NotificationOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(options) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "NotificationOptions",
+                message: Some(format!(
+                    "Invalid notification options - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        let title = match optional_string(&options, "title")? {
+            Some(title) if !title.trim().is_empty() => title,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: "table",
+                    to: "NotificationOptions",
+                    message: Some(String::from(
+                        "Invalid notification options - title must be a non-empty string",
+                    )),
+                })
+            }
+        };
+
+        Ok(Self {
+            title,
+            body: optional_string(&options, "body")?,
+            icon: optional_string(&options, "icon")?,
+        })
+    }
+}
+
+/*
+    NOTE: Getting an Option<String> from the options table would
+    coerce numbers into strings, so we only accept actual strings
+*/
+fn optional_string(options: &LuaTable, key: &str) -> LuaResult<Option<String>> {
+    match options.get(key)? {
+        LuaValue::Nil => Ok(None),
+        LuaValue::String(s) => Ok(Some(s.to_str()?.to_string())),
+        value => Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "NotificationOptions",
+            message: Some(format!(
+                "Invalid notification options - {key} must be a string, got {}",
+                value.type_name()
+            )),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> LuaResult<NotificationOptions> {
+        let lua = Lua::new();
+        let value = lua.load(source).eval::<LuaValue>()?;
+        NotificationOptions::from_lua(value, &lua)
+    }
+
+    #[test]
+    fn parses_all_options() {
+        let options = parse(r#"{ title = "Title", body = "Body", icon = "dialog-information" }"#)
+            .expect("Failed to parse options");
+        assert_eq!(options.title, "Title");
+        assert_eq!(options.body.as_deref(), Some("Body"));
+        assert_eq!(options.icon.as_deref(), Some("dialog-information"));
+    }
+
+    #[test]
+    fn parses_only_title() {
+        let options = parse(r#"{ title = "Title" }"#).expect("Failed to parse options");
+        assert_eq!(options.title, "Title");
+        assert!(options.body.is_none());
+        assert!(options.icon.is_none());
+    }
+
+    #[test]
+    fn rejects_missing_or_empty_title() {
+        assert!(parse("nil").is_err());
+        assert!(parse(r#"{ body = "Body" }"#).is_err());
+        assert!(parse(r#"{ title = "  " }"#).is_err());
+    }
+
+    #[test]
+    fn rejects_numbers_instead_of_strings() {
+        assert!(parse("{ title = 5 }").is_err());
+        assert!(parse(r#"{ title = "Title", body = 5 }"#).is_err());
+        assert!(parse(r#"{ title = "Title", icon = 1.5 }"#).is_err());
+    }
+}
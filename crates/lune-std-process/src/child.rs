@@ -0,0 +1,219 @@
+use std::{io, process::ExitStatus};
+
+use mlua::prelude::*;
+use tokio::{
+    process::Child,
+    sync::{mpsc, oneshot, watch},
+};
+
+use lune_utils::TableBuilder;
+
+use crate::{pty::Pty, reader::ProcessReader, stdin::ProcessStdin};
+
+const SIGNALS: &[&str] = &[
+    "SIGHUP", "SIGINT", "SIGQUIT", "SIGKILL", "SIGUSR1", "SIGUSR2", "SIGTERM",
+];
+
+/**
+    A signal to send to a child process, parsed from its name, such as `SIGTERM`.
+
+    Signals are only sent on Unix - on Windows, any signal terminates the process.
+*/
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Signal(&'static str);
+
+impl Signal {
+    pub fn parse(name: Option<&str>) -> LuaResult<Self> {
+        let Some(name) = name else {
+            return Ok(Self("SIGTERM"));
+        };
+        let upper = name.trim().to_ascii_uppercase();
+        let upper = if upper.starts_with("SIG") {
+            upper
+        } else {
+            format!("SIG{upper}")
+        };
+        SIGNALS
+            .iter()
+            .find(|signal| **signal == upper)
+            .map(|signal| Self(signal))
+            .ok_or_else(|| {
+                LuaError::RuntimeError(format!(
+                    "Invalid signal '{name}', expected one of {}",
+                    SIGNALS
+                        .iter()
+                        .map(|signal| format!("'{signal}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            })
+    }
+
+    #[cfg(unix)]
+    pub fn send(self, child: &mut Child) -> io::Result<()> {
+        let Some(pid) = child.id() else {
+            return Ok(());
+        };
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return Err(io::Error::other("Process id is out of range"));
+        };
+        // NOTE: Signal numbers differ between platforms, so we
+        // only use the names above and let libc give us the numbers
+        let signal = match self.0 {
+            "SIGHUP" => libc::SIGHUP,
+            "SIGINT" => libc::SIGINT,
+            "SIGQUIT" => libc::SIGQUIT,
+            "SIGKILL" => libc::SIGKILL,
+            "SIGUSR1" => libc::SIGUSR1,
+            "SIGUSR2" => libc::SIGUSR2,
+            _ => libc::SIGTERM,
+        };
+        // SAFETY: The pid belongs to our child process, which has not been reaped yet
+        if unsafe { libc::kill(pid, signal) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn send(self, child: &mut Child) -> io::Result<()> {
+        match child.start_kill() {
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => Ok(()),
+            res => res,
+        }
+    }
+}
+
+type KillRequest = (Signal, oneshot::Sender<io::Result<()>>);
+type StatusReceiver = watch::Receiver<Option<Result<ExitStatus, String>>>;
+
+/**
+    A handle for a child process that was created using `process.create`.
+
+    The child process is waited on in the background, and any signals for it
+    are sent from there too, so that it can be killed while also being waited
+    on, and so that its process id is never used after it has been reaped.
+*/
+pub(super) struct ProcessChild {
+    pid: Option<u32>,
+    stdin: Option<ProcessStdin>,
+    stdout: Option<ProcessReader>,
+    stderr: Option<ProcessReader>,
+    status: StatusReceiver,
+    kill: mpsc::UnboundedSender<KillRequest>,
+}
+
+impl ProcessChild {
+    /**
+        Creates a new handle for the given child process, which
+        uses the given pseudo-terminal for its input and output, if any.
+
+        Returns the handle together with a future that must be spawned in the
+        background, which waits for the child process and sends it any signals.
+    */
+    pub fn new(
+        mut child: Child,
+        pty: Option<Pty>,
+    ) -> (Self, impl std::future::Future<Output = ()> + Send) {
+        let (kill_tx, kill_rx) = mpsc::unbounded_channel();
+        let (status_tx, status_rx) = watch::channel(None);
+
+        let (stdin, stdout, stderr) = match pty {
+            Some(pty) => (
+                Some(ProcessStdin::from_pty(pty.input)),
+                Some(ProcessReader::new(pty.output)),
+                None,
+            ),
+            None => (
+                child.stdin.take().map(ProcessStdin::new),
+                child.stdout.take().map(ProcessReader::new),
+                child.stderr.take().map(ProcessReader::new),
+            ),
+        };
+
+        let this = Self {
+            pid: child.id(),
+            stdin,
+            stdout,
+            stderr,
+            status: status_rx,
+            kill: kill_tx,
+        };
+
+        (this, supervise(child, kill_rx, status_tx))
+    }
+
+    async fn kill(&self, signal: Signal) -> LuaResult<()> {
+        let (tx, rx) = oneshot::channel();
+        if self.kill.send((signal, tx)).is_err() {
+            // The child process has already exited
+            return Ok(());
+        }
+        match rx.await {
+            Ok(res) => res.into_lua_err(),
+            Err(_) => Ok(()),
+        }
+    }
+
+    async fn wait(&self) -> LuaResult<ExitStatus> {
+        let mut status = self.status.clone();
+        let status = status
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| LuaError::runtime("Lost track of the child process"))?
+            .clone();
+        match status {
+            Some(Ok(status)) => Ok(status),
+            Some(Err(e)) => Err(LuaError::RuntimeError(format!(
+                "Failed to wait for the child process - {e}"
+            ))),
+            None => unreachable!("status was waited for"),
+        }
+    }
+}
+
+async fn supervise(
+    mut child: Child,
+    mut kill_rx: mpsc::UnboundedReceiver<KillRequest>,
+    status_tx: watch::Sender<Option<Result<ExitStatus, String>>>,
+) {
+    loop {
+        tokio::select! {
+            status = child.wait() => {
+                status_tx.send_replace(Some(status.map_err(|e| e.to_string())));
+                break;
+            }
+            Some((signal, reply)) = kill_rx.recv() => {
+                reply.send(signal.send(&mut child)).ok();
+            }
+        }
+    }
+}
+
+impl LuaUserData for ProcessChild {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("kill", |_, this, signal: Option<String>| async move {
+            let signal = Signal::parse(signal.as_deref())?;
+            this.kill(signal).await
+        });
+        methods.add_async_method("wait", |lua, this, (): ()| async move {
+            let status = this.wait().await?;
+            // NOTE: An exit code is missing if the child process
+            // was terminated by a signal, and we default to 1 then
+            let code = status.code().unwrap_or(1);
+            TableBuilder::new(lua)?
+                .with_value("ok", code == 0)?
+                .with_value("code", code)?
+                .build_readonly()
+        });
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ProcessChild");
+        fields.add_field_method_get("pid", |_, this| Ok(this.pid));
+        fields.add_field_method_get("stdin", |_, this| Ok(this.stdin.clone()));
+        fields.add_field_method_get("stdout", |_, this| Ok(this.stdout.clone()));
+        fields.add_field_method_get("stderr", |_, this| Ok(this.stderr.clone()));
+    }
+}
@@ -0,0 +1,36 @@
+use tokio::process::Command;
+
+/**
+    Makes the given command spawn a child process that is detached from the current
+    process, meaning that it keeps running after the current process has exited.
+
+    On Unix, the child process gets its own session, which means that it has no
+    controlling terminal, and does not get signals such as `SIGHUP` and `SIGINT`
+    that are sent to the current process group when closing the terminal or
+    pressing Ctrl+C. On Windows, it gets its own process group and no console.
+
+    Input and output must be set separately, since the child process can
+    not use any pipes that close once the current process has exited.
+*/
+#[cfg(unix)]
+pub(super) fn detach(command: &mut Command) {
+    // SAFETY: Only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(windows)]
+pub(super) fn detach(command: &mut Command) {
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(super) fn detach(_: &mut Command) {}
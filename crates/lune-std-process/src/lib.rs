@@ -12,16 +12,34 @@ use std::{
 use mlua::prelude::*;
 
 use lune_utils::TableBuilder;
-use mlua_luau_scheduler::{Functions, LuaSpawnExt};
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, LuaSpawnExt};
 use os_str_bytes::RawOsString;
-use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc::unbounded_channel, oneshot};
 
+mod child;
+mod detach;
+mod lock;
+mod on_exit;
 mod options;
+mod pty;
+mod quote;
+mod reader;
+mod stdin;
+mod stream;
+mod supervise;
 mod tee_writer;
 mod wait_for_child;
 
-use self::options::ProcessSpawnOptions;
-use self::wait_for_child::{wait_for_child, WaitForChildResult};
+use self::child::ProcessChild;
+use self::lock::{InstanceLock, SingleInstanceOptions};
+use self::on_exit::process_on_exit;
+use self::options::{default_shell, ProcessSpawnOptions, ProcessSpawnOptionsStdioKind};
+use self::stdin::ProcessStdin;
+use self::stream::{protected_result, OutputCallbacks, OutputSender, OutputStream};
+use self::supervise::{ProcessSupervisor, SuperviseOptions};
+use self::wait_for_child::{wait_for_child, BoxedReader, WaitForChildResult};
+
+pub use self::on_exit::take_exit_callbacks;
 
 use lune_utils::path::get_current_dir;
 
@@ -73,7 +91,13 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .with_value("cwd", cwd_str)?
         .with_value("env", env_tab)?
         .with_value("exit", process_exit)?
+        .with_function("onExit", process_on_exit)?
         .with_async_function("spawn", process_spawn)?
+        .with_function("create", process_create)?
+        .with_function("supervise", process_supervise)?
+        .with_async_function("exec", process_exec)?
+        .with_function("quote", process_quote)?
+        .with_async_function("singleInstance", process_single_instance)?
         .build_readonly()
 }
 
@@ -143,9 +167,71 @@ fn process_env_iter<'lua>(
 
 async fn process_spawn(
     lua: &Lua,
-    (program, args, options): (String, Option<Vec<String>>, ProcessSpawnOptions),
+    (program, args, mut options): (String, Option<Vec<String>>, ProcessSpawnOptions),
 ) -> LuaResult<LuaTable> {
-    let res = lua.spawn(spawn_command(program, args, options)).await?;
+    if options.detached {
+        return Err(LuaError::runtime(
+            "Detached processes can not be waited for\n\
+            Use process.create to create a detached process instead",
+        ));
+    }
+
+    let stdin_callback = take_callback(lua, options.stdio.stdin_callback.take())?;
+    let callbacks = OutputCallbacks {
+        stdout: take_callback(lua, options.stdio.stdout_callback.take())?,
+        stderr: take_callback(lua, options.stdio.stderr_callback.take())?,
+    };
+
+    let (stdin_sender, stdin_receiver) = match stdin_callback {
+        Some(_) => {
+            let (sender, receiver) = oneshot::channel();
+            (Some(sender), Some(receiver))
+        }
+        None => (None, None),
+    };
+    let (output_sender, mut output_receiver) = unbounded_channel();
+    let stdout_sender = callbacks
+        .has(OutputStream::Stdout)
+        .then(|| output_sender.clone());
+    let stderr_sender = callbacks.has(OutputStream::Stderr).then_some(output_sender);
+
+    let task = lua.spawn(spawn_command(
+        program,
+        args,
+        options,
+        stdin_sender,
+        stdout_sender,
+        stderr_sender,
+    ));
+
+    /*
+        If we got a function for writing input, we call it in its own thread
+        with a handle for the input of the child process, once it has spawned,
+        which lets it write input at the same time as we are reading output
+    */
+    let stdin_thread = match (stdin_callback, stdin_receiver) {
+        (Some(callback), Some(receiver)) => match receiver.await {
+            Ok(stdin) => Some(stdin.spawn_callback(lua, callback)?),
+            Err(_) => None,
+        },
+        _ => None,
+    };
+
+    /*
+        Output is sent to us in chunks while the child process is running, and
+        we give each chunk to its callback, waiting for the callback to finish
+        before taking the next one - the channel closes once all output is read,
+        which is immediately if there are no callbacks for any output
+    */
+    while let Some((stream, chunk)) = output_receiver.recv().await {
+        callbacks.call(lua, stream, chunk).await?;
+    }
+    let res = task.await?;
+
+    if let Some(thread_id) = stdin_thread {
+        lua.wait_for_thread(thread_id).await;
+        protected_result(lua, lua.get_thread_result(thread_id))?;
+    }
 
     /*
         NOTE: If an exit code was not given by the child process,
@@ -168,30 +254,172 @@ async fn process_spawn(
         .build_readonly()
 }
 
+async fn process_exec(
+    lua: &Lua,
+    (command, mut options): (String, ProcessSpawnOptions),
+) -> LuaResult<LuaTable> {
+    // NOTE: A specific shell may still be given, but exec always runs
+    // using a shell, since it is meant for things like pipes & redirection
+    if options.shell.is_none() {
+        options.shell = Some(default_shell().ok_or_else(|| {
+            LuaError::runtime("No default shell is available for the current platform")
+        })?);
+    }
+    process_spawn(lua, (command, None, options)).await
+}
+
+fn process_quote(_: &Lua, arg: String) -> LuaResult<String> {
+    Ok(quote::quote(&arg))
+}
+
+async fn process_single_instance(
+    lua: &Lua,
+    (name, options): (String, SingleInstanceOptions),
+) -> LuaResult<InstanceLock> {
+    InstanceLock::acquire(lua, name, options).await
+}
+
+fn process_create(
+    lua: &Lua,
+    (program, args, options): (String, Option<Vec<String>>, ProcessSpawnOptions),
+) -> LuaResult<ProcessChild> {
+    let stdio = &options.stdio;
+    if stdio.stdin.is_some()
+        || stdio.stdin_callback.is_some()
+        || stdio.stdout_callback.is_some()
+        || stdio.stderr_callback.is_some()
+    {
+        return Err(LuaError::runtime(
+            "Input and output callbacks are not supported by process.create\n\
+            Use the stdin, stdout and stderr handles of the child process instead",
+        ));
+    }
+
+    /*
+        Output of the child process is available through reader handles
+        unless it was forwarded or ignored, so inheriting is the same as
+        the default here - there is no output for us to buffer and return
+    */
+    let as_stdio = |kind: ProcessSpawnOptionsStdioKind| match kind {
+        ProcessSpawnOptionsStdioKind::Inherit => Stdio::piped(),
+        kind => kind.as_stdio(),
+    };
+    let stdout = as_stdio(stdio.stdout);
+    let stderr = as_stdio(stdio.stderr);
+    let use_pty = options.pty;
+    let detached = options.detached;
+    if use_pty && detached {
+        return Err(LuaError::runtime(
+            "Detached processes can not use a pty, since they have no terminal",
+        ));
+    }
+
+    let mut command = options.to_command(program, args);
+    let pty = if use_pty {
+        Some(pty::attach(&mut command)?)
+    } else if detached {
+        // NOTE: Pipes would break once we exit, and inheriting our own
+        // stdio would keep the terminal that we are running in busy
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        None
+    } else {
+        command.stdin(Stdio::piped()).stdout(stdout).stderr(stderr);
+        None
+    };
+    let child = command.spawn()?;
+    drop(command);
+
+    let (child, supervisor) = ProcessChild::new(child, pty);
+    lua.spawn(supervisor).detach();
+
+    Ok(child)
+}
+
+fn process_supervise(lua: &Lua, options: SuperviseOptions) -> LuaResult<ProcessSupervisor> {
+    ProcessSupervisor::start(lua, options)
+}
+
+fn take_callback(lua: &Lua, key: Option<LuaRegistryKey>) -> LuaResult<Option<LuaFunction>> {
+    match key {
+        None => Ok(None),
+        Some(key) => {
+            let callback = lua.registry_value(&key)?;
+            lua.remove_registry_value(key)?;
+            Ok(Some(callback))
+        }
+    }
+}
+
 async fn spawn_command(
     program: String,
     args: Option<Vec<String>>,
     mut options: ProcessSpawnOptions,
+    stdin_sender: Option<oneshot::Sender<ProcessStdin>>,
+    stdout_sender: Option<OutputSender>,
+    stderr_sender: Option<OutputSender>,
 ) -> LuaResult<WaitForChildResult> {
     let stdout = options.stdio.stdout;
     let stderr = options.stdio.stderr;
     let stdin = options.stdio.stdin.take();
+    let use_pty = options.pty;
 
-    let mut child = options
-        .into_command(program, args)
-        .stdin(if stdin.is_some() {
-            Stdio::piped()
-        } else {
-            Stdio::null()
-        })
-        .stdout(stdout.as_stdio())
-        .stderr(stderr.as_stdio())
-        .spawn()?;
-
-    if let Some(stdin) = stdin {
-        let mut child_stdin = child.stdin.take().unwrap();
-        child_stdin.write_all(&stdin).await.into_lua_err()?;
+    let mut command = options.to_command(program, args);
+    let pty = if use_pty {
+        Some(pty::attach(&mut command)?)
+    } else {
+        command
+            .stdin(if stdin.is_some() || stdin_sender.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(stdout.as_stdio())
+            .stderr(stderr.as_stdio());
+        None
+    };
+    let mut child = command.spawn()?;
+    // NOTE: The command holds on to the other end of any pseudo-terminal,
+    // and we would never see the end of its output if it was kept around
+    drop(command);
+
+    /*
+        A pseudo-terminal gives us a single stream for all output, which
+        we treat as the output of the child process - there is then no
+        separate error output, so it is always empty and never read
+    */
+    let (child_stdin, stdout_reader, stderr_reader, stderr) = match pty {
+        Some(pty) => (
+            Some(ProcessStdin::from_pty(pty.input)),
+            Some(Box::new(pty.output) as BoxedReader),
+            None,
+            ProcessSpawnOptionsStdioKind::None,
+        ),
+        None => (
+            child.stdin.take().map(ProcessStdin::new),
+            child.stdout.take().map(|s| Box::new(s) as BoxedReader),
+            child.stderr.take().map(|s| Box::new(s) as BoxedReader),
+            stderr,
+        ),
+    };
+
+    if let Some(child_stdin) = child_stdin {
+        if let Some(stdin) = stdin {
+            child_stdin.write(&stdin).await?;
+            child_stdin.close().await?;
+        } else if let Some(sender) = stdin_sender {
+            sender.send(child_stdin).ok();
+        }
     }
 
-    wait_for_child(child, stdout, stderr).await
+    wait_for_child(
+        child,
+        (stdout_reader, stdout),
+        (stderr_reader, stderr),
+        stdout_sender,
+        stderr_sender.filter(|_| stderr != ProcessSpawnOptionsStdioKind::None),
+    )
+    .await
 }
@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use mlua::prelude::*;
+
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+// Locks are kept here instead of in their handles, so that they are
+// held until released even if the handle is garbage collected
+#[derive(Default)]
+struct HeldLocks(HashMap<String, File>);
+
+/**
+    Options for `process.singleInstance`.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct SingleInstanceOptions {
+    wait: bool,
+    timeout: Option<Duration>,
+}
+
+impl<'lua> FromLua<'lua> for SingleInstanceOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let options = match value {
+            LuaValue::Nil => return Ok(Self::default()),
+            LuaValue::Table(t) => t,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SingleInstanceOptions",
+                    message: Some(format!(
+                        "Invalid single instance options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let timeout = match options.get::<_, Option<f64>>("timeout")? {
+            None => None,
+            Some(secs) if secs.is_finite() && secs >= 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(_) => {
+                return Err(LuaError::runtime(
+                    "Invalid timeout - expected a positive number of seconds",
+                ))
+            }
+        };
+        Ok(Self {
+            wait: options
+                .get::<_, Option<bool>>("wait")?
+                .unwrap_or(timeout.is_some()),
+            timeout,
+        })
+    }
+}
+
+/**
+    A handle for a lock that makes sure only a single instance of a program is running.
+*/
+pub(super) struct InstanceLock {
+    name: String,
+    path: PathBuf,
+}
+
+impl InstanceLock {
+    /**
+        Acquires the lock with the given name, waiting for it if wanted.
+    */
+    pub async fn acquire(
+        lua: &Lua,
+        name: String,
+        options: SingleInstanceOptions,
+    ) -> LuaResult<Self> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid instance name '{name}' - names must only contain letters, digits, '-', '_' and '.'"
+            )));
+        }
+        if is_held(lua, &name) {
+            return Err(LuaError::RuntimeError(format!(
+                "Instance '{name}' is already locked by this process"
+            )));
+        }
+
+        let path = std::env::temp_dir().join(format!("lune-{name}.lock"));
+        let started = Instant::now();
+        let file = loop {
+            if let Some(file) = try_lock(&path).map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to lock instance '{name}' at '{}' - {e}",
+                    path.display()
+                ))
+            })? {
+                break file;
+            }
+            let timed_out = options
+                .timeout
+                .is_some_and(|timeout| started.elapsed() >= timeout);
+            if !options.wait || timed_out {
+                let holder = match read_holder(&path) {
+                    Some(pid) => format!(" (process {pid})"),
+                    None => String::new(),
+                };
+                return Err(LuaError::RuntimeError(format!(
+                    "Another instance of '{name}' is already running{holder}"
+                )));
+            }
+            tokio::time::sleep(WAIT_INTERVAL).await;
+        };
+
+        // The process id is only informational, and used for error
+        // messages in other instances, so failing to write it is fine
+        write_holder(&file).ok();
+
+        lua.app_data_mut::<HeldLocks>()
+            .expect("held locks are created above")
+            .0
+            .insert(name.clone(), file);
+
+        Ok(Self { name, path })
+    }
+}
+
+fn is_held(lua: &Lua, name: &str) -> bool {
+    if lua.app_data_ref::<HeldLocks>().is_none() {
+        lua.set_app_data(HeldLocks::default());
+    }
+    lua.app_data_ref::<HeldLocks>()
+        .is_some_and(|locks| locks.0.contains_key(name))
+}
+
+fn write_holder(mut file: &File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    file.flush()
+}
+
+fn read_holder(path: &Path) -> Option<u32> {
+    let mut contents = String::new();
+    File::open(path).ok()?.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/*
+    NOTE: Locks are released by the OS as soon as the file is closed, which
+    also happens if the process crashes, so there are never any stale locks
+    left behind - the lock file itself is not removed when releasing, since
+    another instance may already be waiting to lock that same file
+*/
+#[cfg(unix)]
+fn try_lock(path: &Path) -> io::Result<Option<File>> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+    // SAFETY: The file descriptor is valid for as long as the file is open
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        Ok(Some(file))
+    } else {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            Ok(None)
+        } else {
+            Err(err)
+        }
+    }
+}
+
+#[cfg(windows)]
+fn try_lock(path: &Path) -> io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    // Opening the file without sharing it fails for as long as another process has it open
+    match OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .share_mode(0)
+        .open(path)
+    {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn try_lock(_: &Path) -> io::Result<Option<File>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Instance locks are not supported on this platform",
+    ))
+}
+
+impl LuaUserData for InstanceLock {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "InstanceLock");
+        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+        fields.add_field_method_get("path", |_, this| {
+            Ok(this.path.to_string_lossy().to_string())
+        });
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("release", |lua, this, ()| {
+            if let Some(mut locks) = lua.app_data_mut::<HeldLocks>() {
+                locks.0.remove(&this.name);
+            }
+            Ok(())
+        });
+    }
+}
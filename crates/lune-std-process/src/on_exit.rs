@@ -0,0 +1,32 @@
+use mlua::prelude::*;
+
+#[derive(Default)]
+struct ExitCallbacks(Vec<LuaRegistryKey>);
+
+pub(super) fn process_on_exit(lua: &Lua, callback: LuaFunction) -> LuaResult<()> {
+    let key = lua.create_registry_value(callback)?;
+    let mut callbacks = lua.remove_app_data::<ExitCallbacks>().unwrap_or_default();
+    callbacks.0.push(key);
+    lua.set_app_data(callbacks);
+    Ok(())
+}
+
+/**
+    Takes all of the callbacks added using `process.onExit`, in the order that they were added.
+
+    The runtime should call these once the script has exited, before the exit code takes effect.
+
+    # Errors
+
+    Errors if the callbacks were created using a different Lua state.
+*/
+pub fn take_exit_callbacks(lua: &Lua) -> LuaResult<Vec<LuaFunction>> {
+    let keys = lua.remove_app_data::<ExitCallbacks>().unwrap_or_default().0;
+    keys.into_iter()
+        .map(|key| {
+            let callback = lua.registry_value(&key);
+            lua.remove_registry_value(key)?;
+            callback
+        })
+        .collect()
+}
@@ -8,22 +8,26 @@ use directories::UserDirs;
 use mlua::prelude::*;
 use tokio::process::Command;
 
+use crate::detach::detach;
+
 mod kind;
 mod stdio;
 
 pub(super) use kind::*;
 pub(super) use stdio::*;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Default)]
 pub(super) struct ProcessSpawnOptions {
     pub cwd: Option<PathBuf>,
     pub envs: HashMap<String, String>,
     pub shell: Option<String>,
     pub stdio: ProcessSpawnOptionsStdio,
+    pub pty: bool,
+    pub detached: bool,
 }
 
 impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
-    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         let mut this = Self::default();
         let value = match value {
             LuaValue::Nil => return Ok(this),
@@ -101,13 +105,7 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
         match value.get("shell")? {
             LuaValue::Nil => {}
             LuaValue::String(s) => this.shell = Some(s.to_string_lossy().to_string()),
-            LuaValue::Boolean(true) => {
-                this.shell = match env::consts::FAMILY {
-                    "unix" => Some("/bin/sh".to_string()),
-                    "windows" => Some("powershell".to_string()),
-                    _ => None,
-                };
-            }
+            LuaValue::Boolean(true) => this.shell = default_shell(),
             value => {
                 return Err(LuaError::RuntimeError(format!(
                     "Invalid type for option 'shell' - expected 'true' or 'string', got '{}'",
@@ -116,6 +114,19 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
             }
         }
 
+        /*
+            If we got the pty option, the child process will be given a
+            pseudo-terminal for its input and output, instead of pipes
+        */
+        this.pty = get_bool_option(&value, "pty")?;
+
+        /*
+            If we got the detached option, the child process will keep
+            running after we exit, which is only possible when it is
+            created using process.create - spawning would wait for it
+        */
+        this.detached = get_bool_option(&value, "detached")?;
+
         /*
             If we got options for stdio handling, parse those as well - note that
             we accept a separate "stdin" value here for compatibility with older
@@ -125,9 +136,12 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
         match value.get("stdin")? {
             LuaValue::Nil => {}
             LuaValue::String(s) => this.stdio.stdin = Some(s.as_bytes().to_vec()),
+            LuaValue::Function(f) => {
+                this.stdio.stdin_callback = Some(lua.create_registry_value(f)?);
+            }
             value => {
                 return Err(LuaError::RuntimeError(format!(
-                    "Invalid type for option 'stdin' - expected 'string', got '{}'",
+                    "Invalid type for option 'stdin' - expected 'string' or 'function', got '{}'",
                     value.type_name()
                 )))
             }
@@ -137,12 +151,34 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
     }
 }
 
+fn get_bool_option(options: &LuaTable, name: &str) -> LuaResult<bool> {
+    match options.get(name)? {
+        LuaValue::Nil => Ok(false),
+        LuaValue::Boolean(b) => Ok(b),
+        value => Err(LuaError::RuntimeError(format!(
+            "Invalid type for option '{name}' - expected 'boolean', got '{}'",
+            value.type_name()
+        ))),
+    }
+}
+
+/**
+    Gets the default shell for the current platform, if there is one.
+*/
+pub(super) fn default_shell() -> Option<String> {
+    match env::consts::FAMILY {
+        "unix" => Some("/bin/sh".to_string()),
+        "windows" => Some("powershell".to_string()),
+        _ => None,
+    }
+}
+
 impl ProcessSpawnOptions {
-    pub fn into_command(self, program: impl Into<String>, args: Option<Vec<String>>) -> Command {
+    pub fn to_command(&self, program: impl Into<String>, args: Option<Vec<String>>) -> Command {
         let mut program = program.into();
 
         // Run a shell using the command param if wanted
-        let pargs = match self.shell {
+        let pargs = match &self.shell {
             None => args,
             Some(shell) => {
                 let shell_args = match args {
@@ -165,11 +201,14 @@ impl ProcessSpawnOptions {
         };
 
         // Set dir to run in and env variables
-        if let Some(cwd) = self.cwd {
+        if let Some(cwd) = &self.cwd {
             cmd.current_dir(cwd);
         }
         if !self.envs.is_empty() {
-            cmd.envs(self.envs);
+            cmd.envs(&self.envs);
+        }
+        if self.detached {
+            detach(&mut cmd);
         }
 
         cmd
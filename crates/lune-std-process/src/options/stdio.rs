@@ -2,11 +2,14 @@ use mlua::prelude::*;
 
 use super::kind::ProcessSpawnOptionsStdioKind;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Default)]
 pub struct ProcessSpawnOptionsStdio {
     pub stdout: ProcessSpawnOptionsStdioKind,
     pub stderr: ProcessSpawnOptionsStdioKind,
     pub stdin: Option<Vec<u8>>,
+    pub stdin_callback: Option<LuaRegistryKey>,
+    pub stdout_callback: Option<LuaRegistryKey>,
+    pub stderr_callback: Option<LuaRegistryKey>,
 }
 
 impl From<ProcessSpawnOptionsStdioKind> for ProcessSpawnOptionsStdio {
@@ -29,16 +32,35 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptionsStdio {
             LuaValue::Table(t) => {
                 let mut this = Self::default();
 
-                if let Some(stdin) = t.get("stdin")? {
-                    this.stdin = stdin;
+                /*
+                    Input may also be given as a function, which gets called with
+                    a handle for writing to the input of the child process while
+                    it is running, and the input is closed once the function returns
+                */
+                match t.get("stdin")? {
+                    LuaValue::Function(f) => {
+                        this.stdin_callback = Some(lua.create_registry_value(f)?);
+                    }
+                    value => this.stdin = FromLua::from_lua(value, lua)?,
                 }
 
-                if let Some(stdout) = t.get("stdout")? {
-                    this.stdout = stdout;
+                /*
+                    Output streams may also be given as functions, which get
+                    called with chunks of output as they are being written,
+                    instead of the output being buffered until the process exits
+                */
+                match t.get("stdout")? {
+                    LuaValue::Function(f) => {
+                        this.stdout_callback = Some(lua.create_registry_value(f)?);
+                    }
+                    value => this.stdout = ProcessSpawnOptionsStdioKind::from_lua(value, lua)?,
                 }
 
-                if let Some(stderr) = t.get("stderr")? {
-                    this.stderr = stderr;
+                match t.get("stderr")? {
+                    LuaValue::Function(f) => {
+                        this.stderr_callback = Some(lua.create_registry_value(f)?);
+                    }
+                    value => this.stderr = ProcessSpawnOptionsStdioKind::from_lua(value, lua)?,
                 }
 
                 Ok(this)
@@ -0,0 +1,174 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncRead, ReadBuf},
+    process::Command,
+};
+
+#[cfg(unix)]
+const DEFAULT_COLUMNS: u16 = 80;
+#[cfg(unix)]
+const DEFAULT_ROWS: u16 = 24;
+
+/**
+    A pseudo-terminal that a child process can be attached to, so that
+    it behaves the same as it would when running in a real terminal.
+
+    Output and error output both go to the same terminal, and are read from
+    `output`. Input is written to `input`, and it is echoed back to the output.
+*/
+pub(super) struct Pty {
+    pub input: File,
+    pub output: PtyReader,
+}
+
+/**
+    Reads output from a pseudo-terminal.
+
+    Once the child process has exited, and there are no other processes that
+    use the terminal, reading fails on some platforms instead of giving the end
+    of the output - this reader treats that as the end of the output instead.
+*/
+pub(super) struct PtyReader(File);
+
+impl AsyncRead for PtyReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match Pin::new(&mut self.0).poll_read(cx, buf) {
+            Poll::Ready(Err(e)) if is_closed_error(&e) => Poll::Ready(Ok(())),
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_closed_error(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::EIO)
+}
+
+#[cfg(not(unix))]
+fn is_closed_error(_: &io::Error) -> bool {
+    false
+}
+
+/**
+    Opens a new pseudo-terminal, and attaches the given command to
+    it, which should be spawned before the returned terminal is used.
+
+    The terminal gets the same size as the current terminal,
+    or a default size if there is no current terminal.
+*/
+#[cfg(unix)]
+pub(super) fn attach(command: &mut Command) -> io::Result<Pty> {
+    use std::{
+        ffi::CStr,
+        fs::OpenOptions,
+        os::unix::{fs::OpenOptionsExt, io::FromRawFd},
+        sync::Mutex,
+    };
+
+    // NOTE: ptsname is not thread-safe, it returns a pointer to a static buffer
+    static PTSNAME_LOCK: Mutex<()> = Mutex::new(());
+
+    // SAFETY: The file descriptor is checked, and owned by the file right after opening it
+    let master = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        std::fs::File::from_raw_fd(fd)
+    };
+
+    let master_fd = std::os::unix::io::AsRawFd::as_raw_fd(&master);
+    // SAFETY: The file descriptor is valid, and the name is copied while holding the lock
+    let slave_path = unsafe {
+        if libc::grantpt(master_fd) != 0 || libc::unlockpt(master_fd) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let _guard = PTSNAME_LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let name = libc::ptsname(master_fd);
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        CStr::from_ptr(name).to_string_lossy().to_string()
+    };
+
+    let (columns, rows) = current_size().unwrap_or((DEFAULT_COLUMNS, DEFAULT_ROWS));
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: columns,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: The file descriptor is valid, and the size lives until the call returns
+    if unsafe { libc::ioctl(master_fd, libc::TIOCSWINSZ, &size) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(slave_path)?;
+    command
+        .stdin(slave.try_clone()?)
+        .stdout(slave.try_clone()?)
+        .stderr(slave);
+
+    // Programs check if a terminal supports colors using TERM,
+    // which may not be set if Lune itself is not in a terminal
+    if std::env::var_os("TERM").is_none() {
+        command.env("TERM", "xterm-256color");
+    }
+
+    // SAFETY: Only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            // The child process needs its own session to be able to make
+            // the terminal its controlling terminal, which is what makes
+            // it receive signals for keys such as Ctrl+C from the terminal
+            if libc::setsid() < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let input = File::from_std(master.try_clone()?);
+    let output = PtyReader(File::from_std(master));
+    Ok(Pty { input, output })
+}
+
+#[cfg(not(unix))]
+pub(super) fn attach(_: &mut Command) -> io::Result<Pty> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Pseudo-terminals are not supported on this platform",
+    ))
+}
+
+#[cfg(unix)]
+fn current_size() -> Option<(u16, u16)> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: The size lives until the call returns, and is only read if the call succeeded
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (res == 0 && size.ws_col > 0 && size.ws_row > 0).then_some((size.ws_col, size.ws_row))
+}
@@ -0,0 +1,52 @@
+/**
+    Quotes the given argument so that the default shell for the current
+    platform treats it as a single argument, without interpreting it.
+*/
+pub(super) fn quote(arg: &str) -> String {
+    #[cfg(windows)]
+    return quote_powershell(arg);
+    #[cfg(not(windows))]
+    return quote_posix(arg);
+}
+
+// Characters that never have a special meaning in any POSIX shell,
+// arguments that only contain these do not need to be quoted at all
+#[cfg(not(windows))]
+fn is_safe_posix(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(c, '_' | '-' | '.' | '/' | ',' | ':' | '=' | '+' | '@' | '%')
+}
+
+#[cfg(not(windows))]
+fn quote_posix(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(is_safe_posix) {
+        return arg.to_string();
+    }
+    // NOTE: Nothing is special inside of single quotes, not even backslashes,
+    // so single quotes are the only character that needs any escaping - the
+    // quoted string is ended, an escaped quote added, and a new one started
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(windows)]
+fn quote_powershell(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | '\\' | ':' | ',')
+        })
+    {
+        return arg.to_string();
+    }
+    // NOTE: PowerShell also treats the typographic single quotes as quotes,
+    // so those need to be doubled in the same way as regular single quotes
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('\'');
+    for c in arg.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
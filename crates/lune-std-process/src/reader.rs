@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    sync::Mutex,
+};
+
+const DEFAULT_READ_SIZE: usize = 8 * 1024;
+
+type BoxedReader = BufReader<Box<dyn AsyncRead + Send + Unpin>>;
+
+/**
+    A handle for reading the output of a child process while it is running.
+*/
+#[derive(Clone)]
+pub(super) struct ProcessReader {
+    inner: Arc<Mutex<BoxedReader>>,
+}
+
+impl ProcessReader {
+    pub fn new(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        let reader: Box<dyn AsyncRead + Send + Unpin> = Box::new(reader);
+        Self {
+            inner: Arc::new(Mutex::new(BufReader::new(reader))),
+        }
+    }
+
+    /**
+        Reads at most `size` bytes, waiting until at least one byte is available.
+
+        Returns `None` once all output has been read.
+    */
+    pub async fn read(&self, size: usize) -> LuaResult<Option<Vec<u8>>> {
+        let mut buf = vec![0; size];
+        let n = self.inner.lock().await.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.truncate(n);
+        Ok(Some(buf))
+    }
+
+    /**
+        Reads a single line, without its trailing newline.
+
+        Returns `None` once all output has been read.
+    */
+    pub async fn read_line(&self) -> LuaResult<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        let n = self.inner.lock().await.read_until(b'\n', &mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        if buf.ends_with(b"\n") {
+            buf.pop();
+            if buf.ends_with(b"\r") {
+                buf.pop();
+            }
+        }
+        Ok(Some(buf))
+    }
+
+    pub async fn read_to_end(&self) -> LuaResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.inner.lock().await.read_to_end(&mut buf).await?;
+        Ok(buf)
+    }
+}
+
+impl LuaUserData for ProcessReader {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read", |lua, this, size: Option<usize>| async move {
+            let size = size.unwrap_or(DEFAULT_READ_SIZE);
+            if size == 0 {
+                return Err(LuaError::runtime("Size to read must be greater than zero"));
+            }
+            match this.read(size).await? {
+                Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_async_method("readLine", |lua, this, (): ()| async move {
+            match this.read_line().await? {
+                Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_async_method("readToEnd", |lua, this, (): ()| async move {
+            lua.create_string(this.read_to_end().await?)
+        });
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ProcessReader");
+    }
+}
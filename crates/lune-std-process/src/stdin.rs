@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSchedulerExt;
+use tokio::{
+    fs::File,
+    io::{AsyncWrite, AsyncWriteExt},
+    process::ChildStdin,
+    sync::Mutex,
+};
+
+// The end-of-transmission character, which is what pressing Ctrl+D types, and which
+// makes a terminal give the end of the input to the process that is reading from it
+const PTY_EOF: &[u8] = b"\x04";
+
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+// NOTE: The input is closed as soon as the function returns, even if it errored,
+// since most programs that read their input will not exit until it is closed
+const STDIN_CALLBACK_IMPL_LUA: &str = r"
+local callback, stdin = ...
+local success, err = pcall(callback, stdin)
+stdin:close()
+return success, err
+";
+
+/**
+    A handle for writing to the input of a child process while it is running.
+*/
+#[derive(Clone)]
+pub(super) struct ProcessStdin {
+    inner: Arc<Mutex<Option<BoxedWriter>>>,
+    is_pty: bool,
+}
+
+impl ProcessStdin {
+    pub fn new(stdin: ChildStdin) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Some(Box::new(stdin)))),
+            is_pty: false,
+        }
+    }
+
+    /**
+        Creates a handle for writing to the input of a pseudo-terminal.
+
+        A pseudo-terminal can not be closed without also closing its output,
+        so closing this handle sends the end of the input through the terminal.
+    */
+    pub fn from_pty(input: File) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Some(Box::new(input)))),
+            is_pty: true,
+        }
+    }
+
+    pub async fn write(&self, data: &[u8]) -> LuaResult<()> {
+        let mut guard = self.inner.lock().await;
+        let stdin = guard
+            .as_mut()
+            .ok_or_else(|| LuaError::runtime("Input has already been closed"))?;
+        stdin.write_all(data).await.into_lua_err()?;
+        stdin.flush().await.into_lua_err()
+    }
+
+    pub async fn close(&self) -> LuaResult<()> {
+        let stdin = self.inner.lock().await.take();
+        if let Some(mut stdin) = stdin {
+            if self.is_pty {
+                stdin.write_all(PTY_EOF).await.into_lua_err()?;
+                stdin.flush().await.into_lua_err()?;
+            } else {
+                stdin.shutdown().await.into_lua_err()?;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+        Calls the given function with this handle in a new thread, closing
+        the handle when the function returns, and returns the id of the thread.
+    */
+    pub fn spawn_callback<'lua>(
+        self,
+        lua: &'lua Lua,
+        callback: LuaFunction<'lua>,
+    ) -> LuaResult<mlua_luau_scheduler::ThreadId> {
+        let thread = lua
+            .load(STDIN_CALLBACK_IMPL_LUA)
+            .set_name("=process.spawn")
+            .into_function()?;
+        let thread_id = lua.push_thread_back(thread, (callback, self))?;
+        lua.track_thread(thread_id);
+        Ok(thread_id)
+    }
+}
+
+impl LuaUserData for ProcessStdin {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("write", |_, this, data: LuaString| async move {
+            this.write(data.as_bytes()).await
+        });
+        methods.add_async_method("close", |_, this, (): ()| async move { this.close().await });
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ProcessStdin");
+    }
+}
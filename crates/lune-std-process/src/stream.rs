@@ -0,0 +1,86 @@
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSchedulerExt;
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+pub(super) type OutputSender = UnboundedSender<(OutputStream, Vec<u8>)>;
+
+/**
+    Callbacks for receiving output from a child process as it is being written.
+*/
+#[derive(Debug, Default)]
+pub(super) struct OutputCallbacks<'lua> {
+    pub stdout: Option<LuaFunction<'lua>>,
+    pub stderr: Option<LuaFunction<'lua>>,
+}
+
+impl<'lua> OutputCallbacks<'lua> {
+    pub fn has(&self, stream: OutputStream) -> bool {
+        match stream {
+            OutputStream::Stdout => self.stdout.is_some(),
+            OutputStream::Stderr => self.stderr.is_some(),
+        }
+    }
+
+    /**
+        Calls the callback for the given stream with a chunk of output.
+
+        Callbacks are called in their own thread so that they may yield, and
+        the next chunk is not given to the callback until it has finished.
+    */
+    pub async fn call(
+        &self,
+        lua: &'lua Lua,
+        stream: OutputStream,
+        chunk: Vec<u8>,
+    ) -> LuaResult<()> {
+        let callback = match stream {
+            OutputStream::Stdout => self.stdout.as_ref(),
+            OutputStream::Stderr => self.stderr.as_ref(),
+        };
+        let Some(callback) = callback else {
+            return Ok(());
+        };
+
+        // NOTE: The callback is called using pcall, since errors would otherwise
+        // be reported by the scheduler, in addition to being thrown by spawn
+        let pcall = lua
+            .load("return pcall(...)")
+            .set_name("=process.spawn")
+            .into_function()?;
+        let thread_id =
+            lua.push_thread_back(pcall, (callback.clone(), lua.create_string(chunk)?))?;
+        lua.track_thread(thread_id);
+        lua.wait_for_thread(thread_id).await;
+
+        protected_result(lua, lua.get_thread_result(thread_id))
+    }
+}
+
+/**
+    Turns the result of a thread that called a function using `pcall` back
+    into a normal result, erroring if the function that was called errored.
+*/
+pub(super) fn protected_result(
+    lua: &Lua,
+    result: Option<LuaResult<LuaMultiValue>>,
+) -> LuaResult<()> {
+    let Some(result) = result else {
+        return Ok(());
+    };
+    let mut values = result?.into_iter();
+    match (values.next(), values.next()) {
+        (Some(LuaValue::Boolean(false)), Some(LuaValue::Error(e))) => Err(e),
+        (Some(LuaValue::Boolean(false)), error) => Err(LuaError::RuntimeError(
+            error
+                .and_then(|e| lua.coerce_string(e).ok().flatten())
+                .map_or_else(|| String::from("nil"), |e| e.to_string_lossy().to_string()),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,596 @@
+use std::{
+    cell::{Cell, RefCell},
+    io,
+    process::{ExitStatus, Stdio},
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+use tokio::{
+    process::{Child, Command},
+    sync::watch,
+};
+
+use lune_utils::TableBuilder;
+
+use crate::{
+    child::Signal,
+    options::{ProcessSpawnOptions, ProcessSpawnOptionsStdioKind},
+};
+
+const DEFAULT_BACKOFF_INITIAL: Duration = Duration::from_millis(500);
+const DEFAULT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+const DEFAULT_BACKOFF_FACTOR: f64 = 2.0;
+const DEFAULT_RESET_AFTER: Duration = Duration::from_secs(60);
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/**
+    When a supervised child process should be restarted after it exits.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RestartPolicy {
+    Always,
+    #[default]
+    OnFailure,
+    Never,
+}
+
+impl RestartPolicy {
+    fn should_restart(self, code: i32) -> bool {
+        match self {
+            Self::Always => true,
+            Self::OnFailure => code != 0,
+            Self::Never => false,
+        }
+    }
+}
+
+/**
+    How long to wait before restarting a supervised child process,
+    growing by the given factor for every restart, up to a maximum.
+*/
+#[derive(Debug, Clone, Copy)]
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    factor: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: DEFAULT_BACKOFF_INITIAL,
+            max: DEFAULT_BACKOFF_MAX,
+            factor: DEFAULT_BACKOFF_FACTOR,
+        }
+    }
+}
+
+impl Backoff {
+    fn delay(self, restart: u32) -> Duration {
+        let exponent = i32::try_from(restart.saturating_sub(1)).unwrap_or(i32::MAX);
+        let secs = self.initial.as_secs_f64() * self.factor.powi(exponent);
+        Duration::try_from_secs_f64(secs).map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+fn parse_seconds(value: Option<f64>, name: &str, default: Duration) -> LuaResult<Duration> {
+    match value {
+        None => Ok(default),
+        Some(secs) => Duration::try_from_secs_f64(secs).map_err(|_| {
+            LuaError::RuntimeError(format!(
+                "Invalid value for option '{name}' - expected a positive number of seconds"
+            ))
+        }),
+    }
+}
+
+impl<'lua> FromLua<'lua> for Backoff {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let backoff = match value {
+            LuaValue::Nil => return Ok(Self::default()),
+            LuaValue::Table(t) => t,
+            // NOTE: A single number gives a fixed delay between restarts
+            LuaValue::Integer(_) | LuaValue::Number(_) => {
+                let secs = f64::from_lua(value, lua)?;
+                let delay = parse_seconds(Some(secs), "backoff", DEFAULT_BACKOFF_INITIAL)?;
+                return Ok(Self {
+                    initial: delay,
+                    max: delay,
+                    factor: 1.0,
+                });
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type for option 'backoff' - expected 'number' or 'table', got '{}'",
+                    value.type_name()
+                )))
+            }
+        };
+        let factor = backoff
+            .get::<_, Option<f64>>("factor")?
+            .unwrap_or(DEFAULT_BACKOFF_FACTOR);
+        if !factor.is_finite() || factor < 1.0 {
+            return Err(LuaError::runtime(
+                "Invalid value for option 'backoff.factor' - expected a number of at least 1",
+            ));
+        }
+        Ok(Self {
+            initial: parse_seconds(
+                backoff.get("initial")?,
+                "backoff.initial",
+                DEFAULT_BACKOFF_INITIAL,
+            )?,
+            max: parse_seconds(backoff.get("max")?, "backoff.max", DEFAULT_BACKOFF_MAX)?,
+            factor,
+        })
+    }
+}
+
+/**
+    Options for `process.supervise`.
+
+    Options for spawning the child process are the same as
+    for `process.spawn`, and are read from the same table.
+*/
+pub(super) struct SuperviseOptions {
+    program: String,
+    args: Option<Vec<String>>,
+    restart: RestartPolicy,
+    max_restarts: Option<u32>,
+    backoff: Backoff,
+    reset_after: Duration,
+    spawn: ProcessSpawnOptions,
+}
+
+impl<'lua> FromLua<'lua> for SuperviseOptions {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(options) = &value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SuperviseOptions",
+                message: Some(format!(
+                    "Invalid supervise options - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+
+        /*
+            The command may either be given as a program together with
+            separate args, or as a list of the program followed by its args
+        */
+        let (program, args) = match options.get("cmd")? {
+            LuaValue::String(s) => (s.to_str()?.to_string(), options.get("args")?),
+            LuaValue::Table(t) => {
+                let mut cmd = t
+                    .sequence_values::<String>()
+                    .collect::<LuaResult<Vec<_>>>()
+                    .context("Invalid value for option 'cmd' - expected a list of strings")?;
+                if cmd.is_empty() {
+                    return Err(LuaError::runtime(
+                        "Invalid value for option 'cmd' - expected at least a program to run",
+                    ));
+                }
+                let program = cmd.remove(0);
+                (program, Some(cmd))
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type for option 'cmd' - expected 'string' or 'table', got '{}'",
+                    value.type_name()
+                )))
+            }
+        };
+
+        let restart = match options.get::<_, Option<String>>("restart")?.as_deref() {
+            None => RestartPolicy::default(),
+            Some("always") => RestartPolicy::Always,
+            Some("on-failure") => RestartPolicy::OnFailure,
+            Some("never") => RestartPolicy::Never,
+            Some(other) => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid value for option 'restart' - expected one of 'always', 'on-failure', 'never', got '{other}'"
+                )))
+            }
+        };
+
+        let max_restarts = options
+            .get::<_, Option<u32>>("maxRestarts")
+            .context("Invalid value for option 'maxRestarts' - expected a non-negative integer")?;
+
+        let spawn = ProcessSpawnOptions::from_lua(value.clone(), lua)?;
+        if spawn.detached || spawn.pty {
+            return Err(LuaError::runtime(
+                "Detached processes and pseudo-terminals are not supported by process.supervise",
+            ));
+        }
+        if spawn.stdio.stdin.is_some()
+            || spawn.stdio.stdin_callback.is_some()
+            || spawn.stdio.stdout_callback.is_some()
+            || spawn.stdio.stderr_callback.is_some()
+        {
+            return Err(LuaError::runtime(
+                "Input and output callbacks are not supported by process.supervise",
+            ));
+        }
+
+        Ok(Self {
+            program,
+            args,
+            restart,
+            max_restarts,
+            backoff: options.get("backoff")?,
+            reset_after: parse_seconds(
+                options.get("resetAfter")?,
+                "resetAfter",
+                DEFAULT_RESET_AFTER,
+            )?,
+            spawn,
+        })
+    }
+}
+
+impl SuperviseOptions {
+    /*
+        Supervised child processes run in the background for as long as they
+        need to, so there is nothing for us to buffer their output into, and
+        all output is forwarded unless it is explicitly ignored
+    */
+    fn command(&self) -> Command {
+        let as_stdio = |kind: ProcessSpawnOptionsStdioKind| match kind {
+            ProcessSpawnOptionsStdioKind::None => Stdio::null(),
+            _ => Stdio::inherit(),
+        };
+        let mut command = self.spawn.to_command(&self.program, self.args.clone());
+        command
+            .stdin(Stdio::null())
+            .stdout(as_stdio(self.spawn.stdio.stdout))
+            .stderr(as_stdio(self.spawn.stdio.stderr));
+        command
+    }
+}
+
+/**
+    Why a supervisor stopped supervising its child process.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StopReason {
+    Stopped,
+    Exited,
+    MaxRestarts,
+}
+
+impl StopReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Stopped => "stopped",
+            Self::Exited => "exited",
+            Self::MaxRestarts => "max-restarts",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finished {
+    reason: StopReason,
+    code: i32,
+}
+
+impl<'lua> IntoLua<'lua> for Finished {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("ok", self.code == 0)?
+            .with_value("code", self.code)?
+            .with_value("reason", self.reason.as_str())?
+            .build_readonly()?
+            .into_lua(lua)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SupervisorEvent {
+    Start { pid: Option<u32> },
+    Exit { pid: Option<u32>, code: i32 },
+    Restart { restarts: u32, delay: Duration },
+    Kill { pid: Option<u32>, timeout: Duration },
+    Error { message: String },
+    Stop(Finished),
+}
+
+impl<'lua> IntoLua<'lua> for SupervisorEvent {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let builder = TableBuilder::new(lua)?;
+        let builder = match self {
+            Self::Start { pid } => builder
+                .with_value("kind", "start")?
+                .with_value("pid", pid)?,
+            Self::Exit { pid, code } => builder
+                .with_value("kind", "exit")?
+                .with_value("pid", pid)?
+                .with_value("ok", code == 0)?
+                .with_value("code", code)?,
+            Self::Restart { restarts, delay } => builder
+                .with_value("kind", "restart")?
+                .with_value("restarts", restarts)?
+                .with_value("delay", delay.as_secs_f64())?,
+            Self::Kill { pid, timeout } => builder
+                .with_value("kind", "kill")?
+                .with_value("pid", pid)?
+                .with_value("timeout", timeout.as_secs_f64())?,
+            Self::Error { message } => builder
+                .with_value("kind", "error")?
+                .with_value("message", message)?,
+            Self::Stop(finished) => builder
+                .with_value("kind", "stop")?
+                .with_value("ok", finished.code == 0)?
+                .with_value("code", finished.code)?
+                .with_value("reason", finished.reason.as_str())?,
+        };
+        builder.build_readonly()?.into_lua(lua)
+    }
+}
+
+#[derive(Default)]
+struct SupervisorState {
+    pid: Cell<Option<u32>>,
+    restarts: Cell<u32>,
+    callbacks: RefCell<Vec<LuaRegistryKey>>,
+}
+
+impl SupervisorState {
+    /**
+        Calls every event callback with the given event, each in a new Lua thread.
+    */
+    fn emit(&self, lua: &Lua, event: &SupervisorEvent) {
+        for key in self.callbacks.borrow().iter() {
+            if let Ok(callback) = lua.registry_value::<LuaFunction>(key) {
+                lua.push_thread_back(callback, event.clone()).ok();
+            }
+        }
+    }
+}
+
+/**
+    A request to stop supervising, with the signal to send to the child process,
+    and how long to wait for it to exit before it gets killed.
+*/
+#[derive(Debug, Clone, Copy)]
+struct StopRequest {
+    signal: Signal,
+    timeout: Duration,
+}
+
+type StopSender = watch::Sender<Option<StopRequest>>;
+type StopReceiver = watch::Receiver<Option<StopRequest>>;
+
+/**
+    A handle for a child process that is supervised using `process.supervise`.
+
+    The child process is waited on in the background, and restarted according
+    to the restart policy, until it should no longer be, or until stopped.
+*/
+pub(super) struct ProcessSupervisor {
+    state: Rc<SupervisorState>,
+    stop: StopSender,
+    finished: watch::Receiver<Option<Finished>>,
+}
+
+impl ProcessSupervisor {
+    /**
+        Spawns the child process, and starts supervising it in the background.
+    */
+    pub fn start(lua: &Lua, options: SuperviseOptions) -> LuaResult<Self> {
+        let child = options.command().spawn()?;
+
+        let lua_inner: Rc<Lua> = lua
+            .app_data_ref::<Weak<Lua>>()
+            .expect("Missing weak lua ref")
+            .upgrade()
+            .expect("Lua was dropped unexpectedly");
+
+        // NOTE: The pid is set right away, so that it can be read as soon as
+        // process.supervise returns, before the background task gets to run
+        let state = Rc::new(SupervisorState::default());
+        state.pid.set(child.id());
+        let (stop_tx, stop_rx) = watch::channel(None);
+        let (finished_tx, finished_rx) = watch::channel(None);
+
+        let inner_state = Rc::clone(&state);
+        lua.spawn_local(async move {
+            let finished = supervise(&lua_inner, &inner_state, &options, child, stop_rx).await;
+            finished_tx.send_replace(Some(finished));
+            inner_state.emit(&lua_inner, &SupervisorEvent::Stop(finished));
+            for key in inner_state.callbacks.take() {
+                lua_inner.remove_registry_value(key).ok();
+            }
+        });
+
+        Ok(Self {
+            state,
+            stop: stop_tx,
+            finished: finished_rx,
+        })
+    }
+
+    fn is_running(&self) -> bool {
+        self.finished.borrow().is_none()
+    }
+
+    async fn wait(&self) -> LuaResult<Finished> {
+        let mut finished = self.finished.clone();
+        let finished = *finished
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| LuaError::runtime("Lost track of the supervised process"))?;
+        Ok(finished.expect("finished was waited for"))
+    }
+}
+
+// Resolves once stopping has been requested - if the handle was garbage collected
+// without being stopped, this never resolves, and the child process keeps getting
+// restarted by its restart policy until the script exits and it gets killed
+async fn stop_requested(stop_rx: &mut StopReceiver) -> StopRequest {
+    let request = match stop_rx.wait_for(Option::is_some).await {
+        Ok(request) => *request,
+        Err(_) => std::future::pending().await,
+    };
+    request.expect("stop request was waited for")
+}
+
+/**
+    Sends the stop signal to the child process and waits for it to exit,
+    killing it if it is still running once the stop timeout has passed.
+*/
+async fn stop_child(
+    lua: &Lua,
+    state: &SupervisorState,
+    child: &mut Child,
+    request: StopRequest,
+) -> io::Result<ExitStatus> {
+    request.signal.send(child).ok();
+    if let Ok(status) = tokio::time::timeout(request.timeout, child.wait()).await {
+        return status;
+    }
+    let (pid, timeout) = (child.id(), request.timeout);
+    state.emit(lua, &SupervisorEvent::Kill { pid, timeout });
+    child.start_kill().ok();
+    child.wait().await
+}
+
+fn exit_code(status: &io::Result<ExitStatus>) -> i32 {
+    // NOTE: An exit code is missing if the child process was terminated
+    // by a signal, and we default to 1 then, same as for process.create
+    match status {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(_) => 1,
+    }
+}
+
+async fn supervise(
+    lua: &Lua,
+    state: &SupervisorState,
+    options: &SuperviseOptions,
+    mut child: Child,
+    mut stop_rx: StopReceiver,
+) -> Finished {
+    loop {
+        let pid = child.id();
+        state.pid.set(pid);
+        state.emit(lua, &SupervisorEvent::Start { pid });
+
+        let started = Instant::now();
+        let (status, stopped) = tokio::select! {
+            status = child.wait() => (status, false),
+            request = stop_requested(&mut stop_rx) => {
+                (stop_child(lua, state, &mut child, request).await, true)
+            }
+        };
+
+        // NOTE: A child process that ran for long enough before exiting is
+        // considered stable, and restarting it starts over with the backoff
+        // delay and the maximum number of restarts, as if it was never restarted
+        if started.elapsed() >= options.reset_after {
+            state.restarts.set(0);
+        }
+
+        let code = exit_code(&status);
+        state.pid.set(None);
+        if let Err(e) = status {
+            let message = format!("Failed to wait for '{}' - {e}", options.program);
+            state.emit(lua, &SupervisorEvent::Error { message });
+        }
+        state.emit(lua, &SupervisorEvent::Exit { pid, code });
+
+        let reason = if stopped {
+            StopReason::Stopped
+        } else if options.restart.should_restart(code) {
+            match restart(lua, state, options, &mut stop_rx).await {
+                Ok(restarted) => {
+                    child = restarted;
+                    continue;
+                }
+                Err(reason) => reason,
+            }
+        } else {
+            StopReason::Exited
+        };
+
+        return Finished { reason, code };
+    }
+}
+
+/**
+    Restarts the child process after waiting for the backoff delay,
+    retrying for as long as it fails to spawn, and restarts remain.
+*/
+async fn restart(
+    lua: &Lua,
+    state: &SupervisorState,
+    options: &SuperviseOptions,
+    stop_rx: &mut StopReceiver,
+) -> Result<Child, StopReason> {
+    loop {
+        let restarts = state.restarts.get();
+        if options.max_restarts.is_some_and(|max| restarts >= max) {
+            return Err(StopReason::MaxRestarts);
+        }
+
+        let restarts = restarts + 1;
+        let delay = options.backoff.delay(restarts);
+        state.restarts.set(restarts);
+        state.emit(lua, &SupervisorEvent::Restart { restarts, delay });
+
+        tokio::select! {
+            () = tokio::time::sleep(delay) => {},
+            _ = stop_requested(stop_rx) => return Err(StopReason::Stopped),
+        }
+
+        match options.command().spawn() {
+            Ok(child) => return Ok(child),
+            Err(e) => {
+                let message = format!("Failed to restart '{}' - {e}", options.program);
+                state.emit(lua, &SupervisorEvent::Error { message });
+            }
+        }
+    }
+}
+
+impl LuaUserData for ProcessSupervisor {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "ProcessSupervisor");
+        fields.add_field_method_get("pid", |_, this| Ok(this.state.pid.get()));
+        fields.add_field_method_get("restarts", |_, this| Ok(this.state.restarts.get()));
+        fields.add_field_method_get("running", |_, this| Ok(this.is_running()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("onEvent", |lua, this, callback: LuaFunction| {
+            // NOTE: No more events are sent once supervising has stopped
+            if this.is_running() {
+                let key = lua.create_registry_value(callback)?;
+                this.state.callbacks.borrow_mut().push(key);
+            }
+            Ok(())
+        });
+        methods.add_async_method(
+            "stop",
+            |_, this, (signal, timeout): (Option<String>, Option<f64>)| async move {
+                let signal = Signal::parse(signal.as_deref())?;
+                let timeout = parse_seconds(timeout, "timeout", DEFAULT_STOP_TIMEOUT)?;
+                // NOTE: Only the first request is used, if stopped more than once
+                this.stop.send_if_modified(|current| {
+                    if current.is_some() {
+                        return false;
+                    }
+                    *current = Some(StopRequest { signal, timeout });
+                    true
+                });
+                this.wait().await
+            },
+        );
+        methods.add_async_method("wait", |_, this, (): ()| async move { this.wait().await });
+    }
+}
@@ -7,7 +7,13 @@ use tokio::{
     task,
 };
 
-use super::{options::ProcessSpawnOptionsStdioKind, tee_writer::AsyncTeeWriter};
+use super::{
+    options::ProcessSpawnOptionsStdioKind,
+    stream::{OutputSender, OutputStream},
+    tee_writer::AsyncTeeWriter,
+};
+
+const STREAM_CHUNK_SIZE: usize = 8 * 1024;
 
 #[derive(Debug, Clone)]
 pub(super) struct WaitForChildResult {
@@ -19,12 +25,44 @@ pub(super) struct WaitForChildResult {
 async fn read_with_stdio_kind<R>(
     read_from: Option<R>,
     kind: ProcessSpawnOptionsStdioKind,
+    sender: Option<(OutputSender, OutputStream)>,
 ) -> LuaResult<Vec<u8>>
 where
     R: AsyncRead + Unpin,
 {
+    if let (ProcessSpawnOptionsStdioKind::Default, Some((sender, stream))) = (kind, sender) {
+        let mut read_from = read_from.expect("read_from must be Some when stdio kind is Default");
+
+        let mut buffer = vec![0; STREAM_CHUNK_SIZE];
+        loop {
+            let read = read_from.read(&mut buffer).await.into_lua_err()?;
+            // NOTE: We stop reading if the receiver is gone, which
+            // only happens if a callback errored or spawn was cancelled
+            if read == 0 || sender.send((stream, buffer[..read].to_vec())).is_err() {
+                break;
+            }
+        }
+
+        return Ok(Vec::new());
+    }
+
     Ok(match kind {
-        ProcessSpawnOptionsStdioKind::None | ProcessSpawnOptionsStdioKind::Forward => Vec::new(),
+        ProcessSpawnOptionsStdioKind::None | ProcessSpawnOptionsStdioKind::Forward => {
+            // NOTE: We only get output to read here if the child process writes to a
+            // pseudo-terminal, which must still be read for the child process to not block
+            if let Some(mut read_from) = read_from {
+                if kind == ProcessSpawnOptionsStdioKind::Forward {
+                    io::copy(&mut read_from, &mut io::stdout())
+                        .await
+                        .into_lua_err()?;
+                } else {
+                    io::copy(&mut read_from, &mut io::sink())
+                        .await
+                        .into_lua_err()?;
+                }
+            }
+            Vec::new()
+        }
         ProcessSpawnOptionsStdioKind::Default => {
             let mut read_from =
                 read_from.expect("read_from must be Some when stdio kind is Default");
@@ -49,16 +87,25 @@ where
     })
 }
 
+pub(super) type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+
 pub(super) async fn wait_for_child(
     mut child: Child,
-    stdout_kind: ProcessSpawnOptionsStdioKind,
-    stderr_kind: ProcessSpawnOptionsStdioKind,
+    (stdout_opt, stdout_kind): (Option<BoxedReader>, ProcessSpawnOptionsStdioKind),
+    (stderr_opt, stderr_kind): (Option<BoxedReader>, ProcessSpawnOptionsStdioKind),
+    stdout_sender: Option<OutputSender>,
+    stderr_sender: Option<OutputSender>,
 ) -> LuaResult<WaitForChildResult> {
-    let stdout_opt = child.stdout.take();
-    let stderr_opt = child.stderr.take();
-
-    let stdout_task = task::spawn(read_with_stdio_kind(stdout_opt, stdout_kind));
-    let stderr_task = task::spawn(read_with_stdio_kind(stderr_opt, stderr_kind));
+    let stdout_task = task::spawn(read_with_stdio_kind(
+        stdout_opt,
+        stdout_kind,
+        stdout_sender.map(|sender| (sender, OutputStream::Stdout)),
+    ));
+    let stderr_task = task::spawn(read_with_stdio_kind(
+        stderr_opt,
+        stderr_kind,
+        stderr_sender.map(|sender| (sender, OutputStream::Stderr)),
+    ));
 
     let status = child.wait().await.expect("Child process failed to start");
 
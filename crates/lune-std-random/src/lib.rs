@@ -0,0 +1,71 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+use rand::{rngs::OsRng, Rng, RngCore};
+
+use lune_utils::TableBuilder;
+
+// The largest integer that a lua number can hold without losing precision
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+// The largest buffer that luau can create, which is 1 GiB
+const MAX_BYTES_LEN: i64 = 1 << 30;
+
+/**
+    Creates the `random` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("bytes", random_bytes)?
+        .with_function("int", random_int)?
+        .build_readonly()
+}
+
+/*
+    NOTE: All random values come directly from the random number generator of
+    the operating system, instead of a generator seeded from it, so that there
+    is no state in this process that could ever be used to predict a value
+*/
+fn random_bytes(lua: &Lua, len: LuaNumber) -> LuaResult<LuaAnyUserData> {
+    let len = integer(len, "Length")?;
+    if len < 0 {
+        return Err(LuaError::RuntimeError(format!(
+            "Length must not be negative, got {len}"
+        )));
+    }
+    if len > MAX_BYTES_LEN {
+        return Err(LuaError::RuntimeError(format!(
+            "Length must not be greater than {MAX_BYTES_LEN} (1 GiB), got {len}"
+        )));
+    }
+    let mut bytes = vec![0; usize::try_from(len).into_lua_err()?];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to generate random bytes - {e}")))?;
+    lua.create_buffer(bytes)
+}
+
+fn random_int(_: &Lua, (min, max): (LuaNumber, LuaNumber)) -> LuaResult<i64> {
+    let min = integer(min, "Minimum")?;
+    let max = integer(max, "Maximum")?;
+    if min > max {
+        return Err(LuaError::RuntimeError(format!(
+            "Minimum must not be greater than maximum, got {min} and {max}"
+        )));
+    }
+    Ok(OsRng.gen_range(min..=max))
+}
+
+fn integer(value: LuaNumber, what: &str) -> LuaResult<i64> {
+    if value.fract() != 0.0 || value.abs() > MAX_SAFE_INTEGER {
+        return Err(LuaError::RuntimeError(format!(
+            "{what} must be an integer between -(2^53 - 1) and 2^53 - 1, got {value}"
+        )));
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    Ok(value as i64)
+}
@@ -39,6 +39,21 @@ impl LuaCaptures {
         }
     }
 
+    /**
+        Create a new `LuaCaptures` instance for the first match that
+        starts at or after the given byte offset in a shared text.
+
+        Returns `Some(_)` if captures were found, `None` if no captures were found.
+    */
+    pub fn new_at(pattern: &Regex, text: Arc<String>, start: usize) -> Option<Self> {
+        let inner = LuaCapturesInner::new(text, |owned| pattern.captures_at(owned.as_str(), start));
+        if inner.borrow_dependent().is_some() {
+            Some(Self { inner })
+        } else {
+            None
+        }
+    }
+
     fn captures(&self) -> &Captures {
         self.inner
             .borrow_dependent()
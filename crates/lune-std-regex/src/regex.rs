@@ -22,6 +22,65 @@ impl LuaRegex {
             .map(|inner| Self { inner })
             .map_err(LuaError::external)
     }
+
+    /**
+        Replaces up to `limit` matches in the haystack, or all matches if `limit` is zero.
+
+        The replacer may be a string, which is expanded using capture groups such as `$1`,
+        or a function or table, similar to `string.gsub` in the lua string library.
+        Functions are called with the captures for each match, and tables are indexed
+        using the text of each match, the value returned is then used as-is, or the
+        match is kept as it was if the value is `nil` or `false`.
+    */
+    fn replace(
+        &self,
+        lua: &Lua,
+        haystack: String,
+        replacer: LuaValue,
+        limit: usize,
+    ) -> LuaResult<String> {
+        let replacer = match replacer {
+            LuaValue::Function(_) | LuaValue::Table(_) => replacer,
+            value => {
+                let replacer = String::from_lua(value, lua)?;
+                return Ok(self.inner.replacen(&haystack, limit, replacer).to_string());
+            }
+        };
+
+        let text = Arc::new(haystack);
+        let mut replaced = String::with_capacity(text.len());
+        let mut last_end = 0;
+        for (index, matched) in self.inner.find_iter(&text).enumerate() {
+            if limit > 0 && index >= limit {
+                break;
+            }
+            let value = match &replacer {
+                LuaValue::Function(f) => f.call::<_, LuaValue>(LuaCaptures::new_at(
+                    &self.inner,
+                    Arc::clone(&text),
+                    matched.start(),
+                ))?,
+                LuaValue::Table(t) => t.get::<_, LuaValue>(matched.as_str())?,
+                _ => unreachable!(),
+            };
+            replaced.push_str(&text[last_end..matched.start()]);
+            match value {
+                LuaValue::Nil | LuaValue::Boolean(false) => replaced.push_str(matched.as_str()),
+                value => match lua.coerce_string(value.clone())? {
+                    Some(s) => replaced.push_str(s.to_str()?),
+                    None => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid replacement value (a {})",
+                            value.type_name()
+                        )))
+                    }
+                },
+            }
+            last_end = matched.end();
+        }
+        replaced.push_str(&text[last_end..]);
+        Ok(replaced)
+    }
 }
 
 impl LuaUserData for LuaRegex {
@@ -38,10 +97,28 @@ impl LuaUserData for LuaRegex {
                 .map(|m| LuaMatch::new(Arc::clone(&arc), m)))
         });
 
+        methods.add_method("findAll", |_, this, text: String| {
+            let arc = Arc::new(text);
+            Ok(this
+                .inner
+                .find_iter(&arc)
+                .map(|m| LuaMatch::new(Arc::clone(&arc), m))
+                .collect::<Vec<_>>())
+        });
+
         methods.add_method("captures", |_, this, text: String| {
             Ok(LuaCaptures::new(&this.inner, text))
         });
 
+        methods.add_method("capturesAll", |_, this, text: String| {
+            let arc = Arc::new(text);
+            Ok(this
+                .inner
+                .find_iter(&arc)
+                .filter_map(|m| LuaCaptures::new_at(&this.inner, Arc::clone(&arc), m.start()))
+                .collect::<Vec<_>>())
+        });
+
         methods.add_method("split", |_, this, text: String| {
             Ok(this
                 .inner
@@ -50,18 +127,16 @@ impl LuaUserData for LuaRegex {
                 .collect::<Vec<_>>())
         });
 
-        // TODO: Determine whether it's desirable and / or feasible to support
-        // using a function or table for `replace` like in the lua string library
         methods.add_method(
             "replace",
-            |_, this, (haystack, replacer): (String, String)| {
-                Ok(this.inner.replace(&haystack, replacer).to_string())
+            |lua, this, (haystack, replacer): (String, LuaValue)| {
+                this.replace(lua, haystack, replacer, 1)
             },
         );
         methods.add_method(
             "replaceAll",
-            |_, this, (haystack, replacer): (String, String)| {
-                Ok(this.inner.replace_all(&haystack, replacer).to_string())
+            |lua, this, (haystack, replacer): (String, LuaValue)| {
+                this.replace(lua, haystack, replacer, 0)
             },
         );
 
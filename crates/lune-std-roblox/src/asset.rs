@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use mlua::prelude::*;
+use reqwest::{
+    header::{CONTENT_ENCODING, COOKIE},
+    Client, RequestBuilder, Response, StatusCode,
+};
+use tokio::{fs, time::sleep};
+
+use lune_std_serde::{decompress, CompressDecompressFormat};
+
+const ASSET_DELIVERY_URL: &str = "https://assetdelivery.roblox.com/v1/asset/";
+const OPEN_CLOUD_ASSET_DELIVERY_URL: &str = "https://apis.roblox.com/asset-delivery-api/v1/assetId";
+
+const ROBLOSECURITY_PREFIX: &str = ".ROBLOSECURITY=";
+
+// Roblox rate limits asset downloads fairly aggressively, and the CDN occasionally fails
+// with server errors, so we retry those a couple times before giving up on the download
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/**
+    Options for `roblox.downloadAsset`.
+
+    When neither a cookie nor an api key is given, the cookie
+    from `roblox.getAuthCookie` is used instead, if there is one.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct DownloadAssetOptions {
+    cookie: Option<String>,
+    api_key: Option<String>,
+    version: Option<u64>,
+    path: Option<String>,
+}
+
+impl<'lua> FromLua<'lua> for DownloadAssetOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Nil => return Ok(Self::default()),
+            LuaValue::Table(tab) => tab,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "DownloadAssetOptions",
+                    message: Some(format!(
+                        "Invalid download options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let get_string = |name: &str| {
+            tab.get::<_, Option<String>>(name).map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "Invalid option value for '{name}' in download options - expected a string"
+                ))
+            })
+        };
+        let version = match tab.get::<_, Option<u64>>("version") {
+            Ok(Some(0)) | Err(_) => Err(LuaError::RuntimeError(
+                "Invalid option value for 'version' in download options - expected a positive integer"
+                    .to_string(),
+            )),
+            Ok(version) => Ok(version),
+        }?;
+        let options = Self {
+            cookie: get_string("cookie")?,
+            api_key: get_string("apiKey")?,
+            version,
+            path: get_string("path")?,
+        };
+        if options.cookie.is_some() && options.api_key.is_some() {
+            return Err(LuaError::runtime(
+                "Download options may not have both a cookie and an api key",
+            ));
+        }
+        Ok(options)
+    }
+}
+
+/**
+    Parses an asset id from a number, a string containing a number,
+    or a content url such as `rbxassetid://123` or `https://www.roblox.com/asset/?id=123`.
+*/
+pub fn parse_asset_id(value: &LuaValue) -> LuaResult<u64> {
+    let parsed = match value {
+        LuaValue::Integer(i) => u64::try_from(*i).ok(),
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        LuaValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 && *n < 2f64.powi(53) => {
+            Some(*n as u64)
+        }
+        LuaValue::String(s) => {
+            let s = s.to_str()?.trim();
+            let lower = s.to_ascii_lowercase();
+            let digits = if let Some(rest) = lower.strip_prefix("rbxassetid://") {
+                rest
+            } else if let Some((_, query)) = lower.split_once("id=") {
+                query.split('&').next().unwrap_or_default()
+            } else {
+                &lower
+            };
+            digits.parse().ok()
+        }
+        _ => None,
+    };
+    match parsed {
+        Some(id) if id > 0 => Ok(id),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid asset id '{}' - expected a number, or a string such as 'rbxassetid://123'",
+            value.to_string()?
+        ))),
+    }
+}
+
+/**
+    Downloads the asset with the given id, returning its contents.
+
+    If a path was given in the options, the contents are also written to it.
+*/
+pub async fn download_asset(id: u64, options: DownloadAssetOptions) -> LuaResult<Vec<u8>> {
+    let client = Client::builder()
+        .user_agent(concat!("lune/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .into_lua_err()?;
+
+    let res = if let Some(api_key) = &options.api_key {
+        // Open Cloud responds with the location to download the asset
+        // from, instead of redirecting to it, so we need to request twice
+        let url = match options.version {
+            Some(version) => format!("{OPEN_CLOUD_ASSET_DELIVERY_URL}/{id}/version/{version}"),
+            None => format!("{OPEN_CLOUD_ASSET_DELIVERY_URL}/{id}"),
+        };
+        let res = send_with_retries(id, || client.get(&url).header("x-api-key", api_key)).await?;
+        let body = res.bytes().await.into_lua_err()?;
+        let location = parse_location(&body).ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "Failed to download asset {id} - response did not contain a location"
+            ))
+        })?;
+        send_with_retries(id, || client.get(&location)).await?
+    } else {
+        // The asset delivery api redirects to the cdn, and reqwest will not
+        // send any cookies along when following redirects to other domains
+        let url = match options.version {
+            Some(version) => format!("{ASSET_DELIVERY_URL}?id={id}&version={version}"),
+            None => format!("{ASSET_DELIVERY_URL}?id={id}"),
+        };
+        let cookie = options
+            .cookie
+            .clone()
+            .or_else(rbx_cookie::get_value)
+            .map(|cookie| cookie_header(&cookie));
+        send_with_retries(id, || {
+            let req = client.get(&url);
+            match &cookie {
+                Some(cookie) => req.header(COOKIE, cookie),
+                None => req,
+            }
+        })
+        .await?
+    };
+
+    let content_encoding = res
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string);
+    let mut bytes = res.bytes().await.into_lua_err()?.to_vec();
+    if let Some(content_encoding) = content_encoding {
+        for encoding in content_encoding.split(',').rev() {
+            if let Some(format) = CompressDecompressFormat::detect_from_header_str(encoding.trim())
+            {
+                bytes = decompress(bytes, format).await?;
+            }
+        }
+    }
+
+    if let Some(path) = &options.path {
+        fs::write(path, &bytes).await.map_err(|e| {
+            LuaError::RuntimeError(format!("Failed to save asset {id} to path '{path}' - {e}"))
+        })?;
+    }
+
+    Ok(bytes)
+}
+
+async fn send_with_retries(
+    id: u64,
+    create_request: impl Fn() -> RequestBuilder,
+) -> LuaResult<Response> {
+    let mut attempt = 1;
+    loop {
+        let res = create_request().send().await;
+        let should_retry = attempt < MAX_ATTEMPTS
+            && match &res {
+                Ok(res) => {
+                    res.status() == StatusCode::TOO_MANY_REQUESTS || res.status().is_server_error()
+                }
+                Err(e) => e.is_connect() || e.is_timeout(),
+            };
+        if should_retry {
+            sleep(RETRY_DELAY * attempt).await;
+            attempt += 1;
+            continue;
+        }
+
+        let res = res
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to download asset {id} - {e}")))?;
+        if res.status().is_success() {
+            return Ok(res);
+        }
+
+        let status = res.status();
+        let body = res.bytes().await.unwrap_or_default();
+        let message = parse_error_message(&body).unwrap_or_else(|| {
+            status
+                .canonical_reason()
+                .unwrap_or("Unknown error")
+                .to_string()
+        });
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to download asset {id} - {} {message}",
+            status.as_u16()
+        )));
+    }
+}
+
+fn cookie_header(cookie: &str) -> String {
+    let cookie = cookie.trim();
+    match cookie.strip_prefix(ROBLOSECURITY_PREFIX) {
+        Some(rest) => {
+            let value = rest.split(';').next().unwrap_or_default();
+            format!("{ROBLOSECURITY_PREFIX}{value}")
+        }
+        None => format!("{ROBLOSECURITY_PREFIX}{cookie}"),
+    }
+}
+
+fn parse_location(body: &[u8]) -> Option<String> {
+    let json = serde_json::from_slice::<serde_json::Value>(body).ok()?;
+    json.get("location")?.as_str().map(ToString::to_string)
+}
+
+// Roblox apis respond with errors in one of these forms:
+// { "errors": [{ "code": 0, "message": "..." }] }
+// { "code": "...", "message": "..." }
+fn parse_error_message(body: &[u8]) -> Option<String> {
+    let json = serde_json::from_slice::<serde_json::Value>(body).ok()?;
+    let error = match json.get("errors") {
+        Some(errors) => errors.get(0)?,
+        None => &json,
+    };
+    error.get("message")?.as_str().map(ToString::to_string)
+}
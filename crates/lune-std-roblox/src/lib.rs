@@ -1,12 +1,19 @@
 #![allow(clippy::cargo_common_metadata)]
 
+use std::path::Path;
+
 use mlua::prelude::*;
 use mlua_luau_scheduler::LuaSpawnExt;
 use once_cell::sync::OnceCell;
 
 use lune_roblox::{
     document::{Document, DocumentError, DocumentFormat, DocumentKind},
-    instance::{registry::InstanceRegistry, Instance},
+    instance::{
+        diff::{diff_instances, InstanceDiff},
+        registry::InstanceRegistry,
+        upgrade::{upgrade_instance, InstanceUpgrade},
+        Instance,
+    },
     reflection::Database as ReflectionDatabase,
 };
 
@@ -14,6 +21,12 @@ static REFLECTION_DATABASE: OnceCell<ReflectionDatabase> = OnceCell::new();
 
 use lune_utils::TableBuilder;
 
+mod asset;
+mod scripts;
+
+use self::asset::{download_asset, parse_asset_id, DownloadAssetOptions};
+use self::scripts::{extract_scripts, find_scripts, inject_scripts};
+
 /**
     Creates the `roblox` standard library module.
 
@@ -35,6 +48,12 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .with_async_function("deserializeModel", deserialize_model)?
         .with_async_function("serializePlace", serialize_place)?
         .with_async_function("serializeModel", serialize_model)?
+        .with_async_function("downloadAsset", roblox_download_asset)?
+        .with_function("getScripts", get_scripts)?
+        .with_async_function("extractScripts", roblox_extract_scripts)?
+        .with_async_function("injectScripts", roblox_inject_scripts)?
+        .with_function("diff", diff)?
+        .with_function("upgradeInstances", upgrade_instances)?
         .with_function("getAuthCookie", get_auth_cookie)?
         .with_function("getReflectionDatabase", get_reflection_database)?
         .with_function("implementProperty", implement_property)?
@@ -102,6 +121,109 @@ async fn serialize_model<'lua>(
     lua.create_string(bytes)
 }
 
+async fn roblox_download_asset<'lua>(
+    lua: &'lua Lua,
+    (asset_id, options): (LuaValue<'lua>, DownloadAssetOptions),
+) -> LuaResult<LuaString<'lua>> {
+    let id = parse_asset_id(&asset_id)?;
+    let bytes = download_asset(id, options).await?;
+    lua.create_string(bytes)
+}
+
+// Trees of instances may be given either as a single root instance or as an array of them
+fn instances_from_tree<'lua>(lua: &'lua Lua, tree: LuaValue<'lua>) -> LuaResult<Vec<Instance>> {
+    match &tree {
+        LuaValue::UserData(ud) if ud.is::<Instance>() => Ok(vec![ud.borrow::<Instance>()?.clone()]),
+        _ => Ok(Vec::<LuaUserDataRef<Instance>>::from_lua(tree, lua)?
+            .iter()
+            .map(|i| (*i).clone())
+            .collect()),
+    }
+}
+
+fn get_scripts<'lua>(lua: &'lua Lua, tree: LuaValue<'lua>) -> LuaResult<Vec<Instance>> {
+    let roots = instances_from_tree(lua, tree)?;
+    Ok(find_scripts(&roots)
+        .into_iter()
+        .map(|file| file.instance)
+        .collect())
+}
+
+async fn roblox_extract_scripts<'lua>(
+    lua: &'lua Lua,
+    (tree, dir): (LuaValue<'lua>, String),
+) -> LuaResult<LuaTable<'lua>> {
+    let roots = instances_from_tree(lua, tree)?;
+    let files = find_scripts(&roots);
+    extract_scripts(Path::new(&dir), &files).await?;
+
+    let extracted = lua.create_table()?;
+    for file in files {
+        extracted.push(
+            TableBuilder::new(lua)?
+                .with_value("instance", file.instance)?
+                .with_value("path", Path::new(&dir).join(file.path).to_string_lossy())?
+                .build_readonly()?,
+        )?;
+    }
+    Ok(extracted)
+}
+
+async fn roblox_inject_scripts<'lua>(
+    lua: &'lua Lua,
+    (tree, dir): (LuaValue<'lua>, String),
+) -> LuaResult<Vec<Instance>> {
+    let roots = instances_from_tree(lua, tree)?;
+    let files = find_scripts(&roots);
+    inject_scripts(Path::new(&dir), &files).await
+}
+
+fn diff<'lua>(
+    lua: &'lua Lua,
+    (old, new): (LuaValue<'lua>, LuaValue<'lua>),
+) -> LuaResult<Vec<InstanceDiff>> {
+    let old = instances_from_tree(lua, old)?;
+    let new = instances_from_tree(lua, new)?;
+    Ok(diff_instances(&old, &new))
+}
+
+fn upgrade_instances<'lua>(lua: &'lua Lua, tree: LuaValue<'lua>) -> LuaResult<LuaTable<'lua>> {
+    let instances = instances_from_tree(lua, tree)?;
+
+    let migrated = lua.create_table()?;
+    let failed = lua.create_table()?;
+    let deprecated = lua.create_table()?;
+    for upgrade in instances.iter().flat_map(upgrade_instance) {
+        match upgrade {
+            InstanceUpgrade::Migrated { instance, from, to } => migrated.push(
+                TableBuilder::new(lua)?
+                    .with_value("instance", instance)?
+                    .with_value("from", from)?
+                    .with_value("to", to)?
+                    .build_readonly()?,
+            )?,
+            InstanceUpgrade::Failed {
+                instance,
+                property,
+                message,
+            } => failed.push(
+                TableBuilder::new(lua)?
+                    .with_value("instance", instance)?
+                    .with_value("property", property)?
+                    .with_value("message", message)?
+                    .build_readonly()?,
+            )?,
+            InstanceUpgrade::Deprecated { instance } => deprecated.push(instance)?,
+        }
+    }
+
+    TableBuilder::new(lua)?
+        .with_value("migrated", migrated)?
+        .with_value("failed", failed)?
+        .with_value("deprecated", deprecated)?
+        .build_readonly()
+}
+
 fn get_auth_cookie(_: &Lua, raw: Option<bool>) -> LuaResult<Option<String>> {
     if matches!(raw, Some(true)) {
         Ok(rbx_cookie::get_value())
@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use mlua::prelude::*;
+use rbx_dom_weak::types::Variant as DomValue;
+use tokio::fs;
+
+use lune_roblox::instance::Instance;
+
+// Characters that are not allowed in file names on at least one platform
+const INVALID_FILE_NAME_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/**
+    A script instance, and the path of the file that its source is extracted to.
+
+    The path is relative to the directory that scripts are extracted to.
+*/
+#[derive(Debug, Clone)]
+pub struct ScriptFile {
+    pub instance: Instance,
+    pub path: PathBuf,
+}
+
+fn script_file_suffix(class_name: &str) -> Option<&'static str> {
+    match class_name {
+        "Script" => Some(".server.luau"),
+        "LocalScript" => Some(".client.luau"),
+        "ModuleScript" => Some(".luau"),
+        _ => None,
+    }
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_FILE_NAME_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    // NOTE: Windows does not allow file names ending with dots or spaces,
+    // and names such as "." or ".." would refer to other directories
+    let name = name.trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/**
+    Creates file names for the given siblings, which are unique even on case insensitive
+    file systems, by adding a number to the names of all but the first of any duplicates.
+*/
+fn unique_file_names(siblings: &[Instance]) -> Vec<String> {
+    let mut counts = HashMap::<String, usize>::new();
+    siblings
+        .iter()
+        .map(|sibling| {
+            let name = sanitize_file_name(&sibling.get_name());
+            let count = counts.entry(name.to_lowercase()).or_default();
+            *count += 1;
+            if *count == 1 {
+                name
+            } else {
+                format!("{name} ({count})")
+            }
+        })
+        .collect()
+}
+
+fn find_scripts_in(instances: &[Instance], dir: &Path, files: &mut Vec<ScriptFile>) {
+    for (instance, name) in instances.iter().zip(unique_file_names(instances)) {
+        if let Some(suffix) = script_file_suffix(instance.get_class_name()) {
+            files.push(ScriptFile {
+                instance: instance.clone(),
+                path: dir.join(format!("{name}{suffix}")),
+            });
+        }
+        find_scripts_in(&instance.get_children(), &dir.join(name), files);
+    }
+}
+
+/**
+    Finds all scripts in the given trees of instances, in the order they are in the trees.
+
+    Files for scripts are laid out in directories mirroring the trees, with children of
+    scripts being placed in a directory next to the script, using the same name. Services
+    in places are placed at the top, without a directory for the `DataModel` itself.
+*/
+pub fn find_scripts(roots: &[Instance]) -> Vec<ScriptFile> {
+    let mut files = Vec::new();
+    for root in roots {
+        if root.get_class_name() == "DataModel" {
+            find_scripts_in(&root.get_children(), Path::new(""), &mut files);
+        } else {
+            find_scripts_in(std::slice::from_ref(root), Path::new(""), &mut files);
+        }
+    }
+    files
+}
+
+fn get_source(instance: &Instance) -> String {
+    match instance.get_property("Source") {
+        Some(DomValue::String(source)) => source,
+        _ => String::new(),
+    }
+}
+
+/**
+    Writes the sources of the given scripts to files in the given directory.
+*/
+pub async fn extract_scripts(dir: &Path, files: &[ScriptFile]) -> LuaResult<()> {
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to create directory '{}' - {e}",
+                    parent.display()
+                ))
+            })?;
+        }
+        fs::write(&path, get_source(&file.instance))
+            .await
+            .map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Failed to write script to '{}' - {e}",
+                    path.display()
+                ))
+            })?;
+    }
+    Ok(())
+}
+
+/**
+    Reads the sources of the given scripts from files in the given directory,
+    returning the scripts that had their sources changed.
+
+    Scripts that do not have a file in the directory are left as they are.
+*/
+pub async fn inject_scripts(dir: &Path, files: &[ScriptFile]) -> LuaResult<Vec<Instance>> {
+    let mut changed = Vec::new();
+    for file in files {
+        let path = dir.join(&file.path);
+        let contents = match fs::read(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Failed to read script from '{}' - {e}",
+                    path.display()
+                )))
+            }
+        };
+        let source = String::from_utf8(contents).map_err(|_| {
+            LuaError::RuntimeError(format!(
+                "Failed to read script from '{}' - file is not valid utf-8",
+                path.display()
+            ))
+        })?;
+        if source != get_source(&file.instance) {
+            file.instance
+                .set_property("Source", DomValue::String(source));
+            changed.push(file.instance.clone());
+        }
+    }
+    Ok(changed)
+}
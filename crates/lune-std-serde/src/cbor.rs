@@ -0,0 +1,214 @@
+use mlua::prelude::*;
+
+use ciborium::value::{CanonicalValue, Value as CborValue};
+
+use crate::key_order::{get_key_order, set_key_order};
+
+/*
+    CBOR has a couple of types that can not be represented using plain lua values, so
+    values are converted manually instead of going through serde, which would lose them:
+
+    - Byte strings are decoded as buffers, and buffers as well as
+      strings that are not valid utf-8 are encoded as byte strings
+    - Tagged values are decoded as tables with `tag` and `value` fields, that
+      have a special metatable, which is also used to detect them when encoding
+*/
+const TAG_METATABLE_REGISTRY_KEY: &str = "__lune_serde_cbor_tag";
+
+fn tag_metatable(lua: &Lua) -> LuaResult<LuaTable> {
+    if let Ok(meta) = lua.named_registry_value::<LuaTable>(TAG_METATABLE_REGISTRY_KEY) {
+        return Ok(meta);
+    }
+    let meta = lua.create_table()?;
+    meta.raw_set("__metatable", "The metatable is locked")?;
+    meta.set_readonly(true);
+    lua.set_named_registry_value(TAG_METATABLE_REGISTRY_KEY, meta.clone())?;
+    Ok(meta)
+}
+
+/**
+    Creates a tagged value, which is encoded as a CBOR tag with the given number.
+*/
+pub fn create_tag<'lua>(
+    lua: &'lua Lua,
+    tag: u64,
+    value: LuaValue<'lua>,
+) -> LuaResult<LuaTable<'lua>> {
+    let table = lua.create_table_with_capacity(0, 2)?;
+    table.raw_set("tag", tag)?;
+    table.raw_set("value", value)?;
+    table.set_metatable(Some(tag_metatable(lua)?));
+    Ok(table)
+}
+
+/**
+    Converts a CBOR value into a lua value, optionally
+    recording the order of keys for any maps.
+*/
+pub fn cbor_to_lua(lua: &Lua, value: CborValue, preserve_order: bool) -> LuaResult<LuaValue> {
+    Ok(match value {
+        CborValue::Null => LuaValue::Nil,
+        CborValue::Bool(b) => LuaValue::Boolean(b),
+        CborValue::Integer(i) => LuaValue::Number(i128::from(i) as f64),
+        CborValue::Float(f) => LuaValue::Number(f),
+        CborValue::Text(s) => LuaValue::String(lua.create_string(s)?),
+        CborValue::Bytes(b) => LuaValue::UserData(lua.create_buffer(b)?),
+        CborValue::Tag(tag, value) => {
+            let value = cbor_to_lua(lua, *value, preserve_order)?;
+            LuaValue::Table(create_tag(lua, tag, value)?)
+        }
+        CborValue::Array(array) => {
+            let table = lua.create_table_with_capacity(array.len(), 0)?;
+            // NOTE: Nulls become holes in the table, so we must set values by
+            // their index instead of pushing them, to keep the following ones in place
+            for (index, value) in array.into_iter().enumerate() {
+                table.raw_set(index + 1, cbor_to_lua(lua, value, preserve_order)?)?;
+            }
+            LuaValue::Table(table)
+        }
+        CborValue::Map(map) => {
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            let order = lua.create_table_with_capacity(map.len(), 0)?;
+            for (key, value) in map {
+                let key = cbor_to_lua(lua, key, preserve_order)?;
+                if key.is_nil() {
+                    return Err(LuaError::RuntimeError(
+                        "CBOR map keys can not be null".to_string(),
+                    ));
+                }
+                let value = cbor_to_lua(lua, value, preserve_order)?;
+                table.raw_set(key.clone(), value)?;
+                order.raw_push(key)?;
+            }
+            if preserve_order {
+                set_key_order(lua, &table, order)?;
+            }
+            LuaValue::Table(table)
+        }
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Unsupported CBOR value {value:?}"
+            )))
+        }
+    })
+}
+
+/**
+    Converts a lua value into a CBOR value.
+
+    Keys of maps are sorted in canonical CBOR order, unless the order of keys
+    should be preserved, in which case any recorded order of keys is used first.
+*/
+pub fn lua_to_cbor(lua: &Lua, value: LuaValue, preserve_order: bool) -> LuaResult<CborValue> {
+    let mut visiting = Vec::new();
+    lua_to_cbor_inner(lua, value, preserve_order, &mut visiting)
+}
+
+fn lua_to_cbor_inner<'lua>(
+    lua: &'lua Lua,
+    value: LuaValue<'lua>,
+    preserve_order: bool,
+    visiting: &mut Vec<LuaTable<'lua>>,
+) -> LuaResult<CborValue> {
+    Ok(match value {
+        LuaValue::Nil => CborValue::Null,
+        LuaValue::Boolean(b) => CborValue::Bool(b),
+        LuaValue::Integer(i) => CborValue::Integer(i.into()),
+        LuaValue::Number(n) => number_to_cbor(n),
+        LuaValue::String(s) => match s.to_str() {
+            Ok(s) => CborValue::Text(s.to_string()),
+            Err(_) => CborValue::Bytes(s.as_bytes().to_vec()),
+        },
+        value if value.is_buffer() => {
+            let bytes = lua.unpack::<bstr::BString>(value)?;
+            CborValue::Bytes(bytes.into())
+        }
+        LuaValue::Table(table) => {
+            if visiting.contains(&table) {
+                return Err(LuaError::SerializeError(
+                    "recursive table detected".to_string(),
+                ));
+            }
+            visiting.push(table.clone());
+            let converted = table_to_cbor(lua, &table, preserve_order, visiting)?;
+            visiting.pop();
+            converted
+        }
+        value => {
+            return Err(LuaError::SerializeError(format!(
+                "cannot serialize <{}>",
+                value.type_name()
+            )))
+        }
+    })
+}
+
+fn table_to_cbor<'lua>(
+    lua: &'lua Lua,
+    table: &LuaTable<'lua>,
+    preserve_order: bool,
+    visiting: &mut Vec<LuaTable<'lua>>,
+) -> LuaResult<CborValue> {
+    if table.get_metatable() == Some(tag_metatable(lua)?) {
+        let tag = table.raw_get::<_, u64>("tag")?;
+        let value = table.raw_get::<_, LuaValue>("value")?;
+        let value = lua_to_cbor_inner(lua, value, preserve_order, visiting)?;
+        return Ok(CborValue::Tag(tag, Box::new(value)));
+    }
+
+    let order = if preserve_order {
+        get_key_order(lua, table)?
+    } else {
+        None
+    };
+    let len = table.raw_len();
+    let count = table.clone().pairs::<LuaValue, LuaValue>().count();
+
+    if order.is_none() && len > 0 && len == count {
+        let mut array = Vec::with_capacity(len);
+        for value in table.clone().sequence_values::<LuaValue>() {
+            array.push(lua_to_cbor_inner(lua, value?, preserve_order, visiting)?);
+        }
+        return Ok(CborValue::Array(array));
+    }
+
+    let mut map = Vec::with_capacity(count);
+    let listed = lua.create_table()?;
+    if let Some(order) = order {
+        for key in order.sequence_values::<LuaValue>() {
+            let key = key?;
+            let value = table.raw_get::<_, LuaValue>(key.clone())?;
+            if value.is_nil() || listed.raw_get::<_, bool>(key.clone())? {
+                continue;
+            }
+            listed.raw_set(key.clone(), true)?;
+            map.push((
+                lua_to_cbor_inner(lua, key, preserve_order, visiting)?,
+                lua_to_cbor_inner(lua, value, preserve_order, visiting)?,
+            ));
+        }
+    }
+    let mut rest = Vec::new();
+    for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+        let (key, value) = pair?;
+        if !listed.raw_get::<_, bool>(key.clone())? {
+            rest.push((
+                lua_to_cbor_inner(lua, key, preserve_order, visiting)?,
+                lua_to_cbor_inner(lua, value, preserve_order, visiting)?,
+            ));
+        }
+    }
+    rest.sort_by_cached_key(|(key, _)| CanonicalValue::from(key.clone()));
+    map.extend(rest);
+    Ok(CborValue::Map(map))
+}
+
+fn number_to_cbor(n: f64) -> CborValue {
+    // NOTE: Lua only has one number type, so any number without a fractional
+    // part is encoded as an integer, same as other formats do, which is smaller
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        CborValue::Integer((n as i64).into())
+    } else {
+        CborValue::Float(n)
+    }
+}
@@ -1,8 +1,8 @@
-use std::io::{copy as copy_std, Cursor, Read as _, Write as _};
+use std::io::{copy as copy_std, Cursor, Read as _, Write};
 
 use mlua::prelude::*;
 
-use lz4::{Decoder, EncoderBuilder};
+use lz4::{Decoder, Encoder, EncoderBuilder};
 use tokio::{
     io::{copy, BufReader},
     task::spawn_blocking,
@@ -209,11 +209,7 @@ fn compress_lz4(input: Vec<u8>) -> LuaResult<Vec<u8>> {
     let len = input.get_ref().len() as u32;
     output.write_all(len.to_le_bytes().as_ref())?;
 
-    let mut encoder = EncoderBuilder::new()
-        .level(16)
-        .checksum(lz4::ContentChecksum::ChecksumEnabled)
-        .block_mode(lz4::BlockMode::Independent)
-        .build(output)?;
+    let mut encoder = lz4_encoder(output)?;
 
     copy_std(&mut input, &mut encoder)?;
     let (output, result) = encoder.finish();
@@ -222,6 +218,18 @@ fn compress_lz4(input: Vec<u8>) -> LuaResult<Vec<u8>> {
     Ok(output.into_inner())
 }
 
+/**
+    Creates an lz4 encoder that writes to the given writer, with
+    the same settings for both regular and streaming compression.
+*/
+pub(crate) fn lz4_encoder<W: Write>(writer: W) -> std::io::Result<Encoder<W>> {
+    EncoderBuilder::new()
+        .level(16)
+        .checksum(lz4::ContentChecksum::ChecksumEnabled)
+        .block_mode(lz4::BlockMode::Independent)
+        .build(writer)
+}
+
 fn decompress_lz4(input: Vec<u8>) -> LuaResult<Vec<u8>> {
     let mut input = Cursor::new(input);
 
@@ -0,0 +1,389 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, Read, Write},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use bstr::BString;
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::{Mutex, MutexGuard},
+};
+
+use async_compression::{
+    tokio::write::{
+        BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+    },
+    Level::Best as CompressionQuality,
+    Level::Precise as PreciseCompressionQuality,
+};
+
+use lune_utils::TableBuilder;
+
+use crate::compress_decompress::{lz4_encoder, CompressDecompressFormat};
+
+const STREAM_IMPL_LUA: &str = r"
+return freeze({
+	write = function(...)
+		return stream:write(...)
+	end,
+	finish = function(...)
+		return stream:finish(...)
+	end,
+})
+";
+
+// NOTE: Brotli keeps much more state than the other formats, so it is boxed
+enum Codec {
+    BrotliEncoder(Box<BrotliEncoder<Vec<u8>>>),
+    BrotliDecoder(Box<BrotliDecoder<Vec<u8>>>),
+    GZipEncoder(GzipEncoder<Vec<u8>>),
+    GZipDecoder(GzipDecoder<Vec<u8>>),
+    ZLibEncoder(ZlibEncoder<Vec<u8>>),
+    ZLibDecoder(ZlibDecoder<Vec<u8>>),
+    LZ4Encoder(Lz4Encoder),
+    LZ4Decoder(Box<Lz4Decoder>),
+}
+
+impl Codec {
+    fn writer(&mut self) -> &mut (dyn AsyncWrite + Unpin) {
+        match self {
+            Self::BrotliEncoder(c) => c.as_mut(),
+            Self::BrotliDecoder(c) => c.as_mut(),
+            Self::GZipEncoder(c) => c,
+            Self::GZipDecoder(c) => c,
+            Self::ZLibEncoder(c) => c,
+            Self::ZLibDecoder(c) => c,
+            Self::LZ4Encoder(c) => c,
+            Self::LZ4Decoder(c) => c.as_mut(),
+        }
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(match self {
+            Self::BrotliEncoder(c) => c.get_mut(),
+            Self::BrotliDecoder(c) => c.get_mut(),
+            Self::GZipEncoder(c) => c.get_mut(),
+            Self::GZipDecoder(c) => c.get_mut(),
+            Self::ZLibEncoder(c) => c.get_mut(),
+            Self::ZLibDecoder(c) => c.get_mut(),
+            Self::LZ4Encoder(c) => c.get_mut(),
+            Self::LZ4Decoder(c) => c.get_mut(),
+        })
+    }
+}
+
+/**
+    A stream that compresses or decompresses data in chunks, so
+    that the entire input never needs to be in memory at once.
+
+    Any output that is ready is returned after writing each chunk,
+    and the remaining output is returned once the stream is finished.
+*/
+#[derive(Clone)]
+pub struct CompressDecompressStream {
+    codec: Rc<Mutex<Option<Codec>>>,
+}
+
+impl CompressDecompressStream {
+    /**
+        Creates a new stream that compresses data using the given format.
+
+        # Errors
+
+        Errors when the compressor could not be created.
+    */
+    pub fn compress(format: CompressDecompressFormat, level: Option<i32>) -> LuaResult<Self> {
+        let quality = match level {
+            Some(l) => PreciseCompressionQuality(l),
+            None => CompressionQuality,
+        };
+        let codec = match format {
+            CompressDecompressFormat::Brotli => {
+                Codec::BrotliEncoder(Box::new(BrotliEncoder::with_quality(Vec::new(), quality)))
+            }
+            CompressDecompressFormat::GZip => {
+                Codec::GZipEncoder(GzipEncoder::with_quality(Vec::new(), quality))
+            }
+            CompressDecompressFormat::ZLib => {
+                Codec::ZLibEncoder(ZlibEncoder::with_quality(Vec::new(), quality))
+            }
+            CompressDecompressFormat::LZ4 => Codec::LZ4Encoder(Lz4Encoder::new()?),
+        };
+        Ok(Self::from(codec))
+    }
+
+    /**
+        Creates a new stream that decompresses data using the given format.
+
+        # Errors
+
+        Errors when the decompressor could not be created.
+    */
+    pub fn decompress(format: CompressDecompressFormat) -> LuaResult<Self> {
+        let codec = match format {
+            CompressDecompressFormat::Brotli => {
+                Codec::BrotliDecoder(Box::new(BrotliDecoder::new(Vec::new())))
+            }
+            CompressDecompressFormat::GZip => Codec::GZipDecoder(GzipDecoder::new(Vec::new())),
+            CompressDecompressFormat::ZLib => Codec::ZLibDecoder(ZlibDecoder::new(Vec::new())),
+            CompressDecompressFormat::LZ4 => Codec::LZ4Decoder(Box::new(Lz4Decoder::new()?)),
+        };
+        Ok(Self::from(codec))
+    }
+
+    /**
+        Writes a chunk of data to the stream, returning any output that is ready.
+
+        # Errors
+
+        Errors when the data is invalid, or the stream has already been finished.
+    */
+    pub async fn write(&self, chunk: impl AsRef<[u8]>) -> LuaResult<Vec<u8>> {
+        let mut codec = self.lock()?;
+        let codec = codec.as_mut().ok_or_else(finished_error)?;
+        codec.writer().write_all(chunk.as_ref()).await?;
+        Ok(codec.take_output())
+    }
+
+    /**
+        Finishes the stream, returning all of the remaining output.
+
+        # Errors
+
+        Errors when the data written is incomplete, or the stream has already been finished.
+    */
+    pub async fn finish(&self) -> LuaResult<Vec<u8>> {
+        let mut codec = self.lock()?.take().ok_or_else(finished_error)?;
+        codec.writer().shutdown().await?;
+        Ok(codec.take_output())
+    }
+
+    // NOTE: Waiting for the lock would use up the budget of the tokio task that
+    // runs all lua threads, and streams are only used by one thread at a time anyway
+    fn lock(&self) -> LuaResult<MutexGuard<'_, Option<Codec>>> {
+        self.codec
+            .try_lock()
+            .map_err(|_| LuaError::runtime("Stream is already being written to"))
+    }
+
+    /**
+        Creates a lua table for the stream, with `write` and `finish` functions.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+
+        let env = TableBuilder::new(lua)?
+            .with_value("stream", self)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+
+        lua.load(STREAM_IMPL_LUA)
+            .set_name("compress_stream")
+            .set_environment(env)
+            .eval()
+    }
+}
+
+// NOTE: Data compressed using lz4 starts with the size of the uncompressed data, for
+// compatibility with the lz4 format that was used before - when streaming, the size is
+// not known up front, but it is only ever used as a hint for the size of the output
+const LZ4_SIZE_PREFIX_LEN: usize = 4;
+
+/**
+    A buffer shared between an lz4 encoder or decoder and
+    its stream, since they only give out shared references.
+*/
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<VecDeque<u8>>>);
+
+impl Read for SharedBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/**
+    Compresses data using the lz4 frame format, same as `serde.compress`.
+
+    The lz4 encoder only supports blocking writers, which is fine here,
+    since it only ever writes to memory and never has to wait for anything.
+*/
+struct Lz4Encoder {
+    encoder: Option<lz4::Encoder<SharedBuffer>>,
+    buffer: SharedBuffer,
+    output: Vec<u8>,
+}
+
+impl Lz4Encoder {
+    fn new() -> io::Result<Self> {
+        let buffer = SharedBuffer::default();
+        let encoder = lz4_encoder(buffer.clone())?;
+        let mut this = Self {
+            encoder: Some(encoder),
+            buffer,
+            output: vec![0; LZ4_SIZE_PREFIX_LEN],
+        };
+        this.take_buffer();
+        Ok(this)
+    }
+
+    fn get_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.output
+    }
+
+    fn take_buffer(&mut self) {
+        self.output.extend(self.buffer.0.borrow_mut().drain(..));
+    }
+}
+
+impl AsyncWrite for Lz4Encoder {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(encoder) = this.encoder.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        };
+        let written = encoder.write(buf);
+        this.take_buffer();
+        Poll::Ready(written)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(encoder) = this.encoder.take() else {
+            return Poll::Ready(Ok(()));
+        };
+        let (_, result) = encoder.finish();
+        this.take_buffer();
+        Poll::Ready(result)
+    }
+}
+
+/**
+    Decompresses data using the lz4 frame format, same as `serde.decompress`.
+
+    Chunks are buffered until the decoder reads them, which it does right away,
+    and any output is read from the decoder until it needs more input.
+*/
+struct Lz4Decoder {
+    decoder: Option<lz4::Decoder<SharedBuffer>>,
+    buffer: SharedBuffer,
+    prefix_remaining: usize,
+    output: Vec<u8>,
+}
+
+impl Lz4Decoder {
+    fn new() -> io::Result<Self> {
+        let buffer = SharedBuffer::default();
+        Ok(Self {
+            decoder: Some(lz4::Decoder::new(buffer.clone())?),
+            buffer,
+            prefix_remaining: LZ4_SIZE_PREFIX_LEN,
+            output: Vec::new(),
+        })
+    }
+
+    fn get_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.output
+    }
+}
+
+impl AsyncWrite for Lz4Decoder {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(decoder) = this.decoder.as_mut() else {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        };
+
+        let skipped = this.prefix_remaining.min(buf.len());
+        this.prefix_remaining -= skipped;
+        this.buffer.0.borrow_mut().extend(&buf[skipped..]);
+
+        // NOTE: The decoder reads nothing once it runs out of input, but
+        // keeps its state, so that it can continue once it gets more of it
+        let mut chunk = [0; 8 * 1024];
+        loop {
+            match decoder.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => this.output.extend_from_slice(&chunk[..read]),
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(decoder) = self.get_mut().decoder.take() else {
+            return Poll::Ready(Ok(()));
+        };
+        Poll::Ready(match decoder.finish() {
+            (_, Ok(())) => Ok(()),
+            (_, Err(_)) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "lz4 data is incomplete",
+            )),
+        })
+    }
+}
+
+impl From<Codec> for CompressDecompressStream {
+    fn from(codec: Codec) -> Self {
+        Self {
+            codec: Rc::new(Mutex::new(Some(codec))),
+        }
+    }
+}
+
+impl LuaUserData for CompressDecompressStream {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("write", |lua, this, chunk: BString| async move {
+            lua.create_string(this.write(chunk).await?)
+        });
+
+        methods.add_async_method("finish", |lua, this, (): ()| async move {
+            lua.create_string(this.finish().await?)
+        });
+    }
+}
+
+fn finished_error() -> LuaError {
+    LuaError::runtime("Stream has already been finished")
+}
@@ -1,9 +1,23 @@
+use std::fmt::Write as _;
+
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine as _,
+};
+use bstr::BString;
 use mlua::prelude::*;
 
+use rmpv::Value as MsgPackValue;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use serde_yaml::Value as YamlValue;
 use toml::Value as TomlValue;
 
+use crate::cbor::{cbor_to_lua, lua_to_cbor};
+use crate::key_order::{lua_to_yaml, yaml_to_lua};
+use crate::toml_document::update_document;
+
 // NOTE: These are options for going from other format -> lua ("serializing" lua values)
 const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
     .set_array_metatable(false)
@@ -16,6 +30,19 @@ const LUA_DESERIALIZE_OPTIONS: LuaDeserializeOptions = LuaDeserializeOptions::ne
     .deny_recursive_tables(false)
     .deny_unsupported_types(true);
 
+// NOTE: Url-safe base64 is usually unpadded, such as in JWTs, but both of
+// the base64 formats accept input with or without padding when decoding
+const BASE64_STANDARD: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+const BASE64_URL_SAFE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
 /**
     An encoding and decoding format supported by Lune.
 
@@ -26,6 +53,22 @@ pub enum EncodeDecodeFormat {
     Json,
     Yaml,
     Toml,
+    MsgPack,
+    Cbor,
+    Base64,
+    Base64Url,
+    Hex,
+}
+
+impl EncodeDecodeFormat {
+    /**
+        Returns `true` if this format encodes strings of bytes
+        instead of values, such as `base64` and `hex`.
+    */
+    #[must_use]
+    pub const fn is_bytes(self) -> bool {
+        matches!(self, Self::Base64 | Self::Base64Url | Self::Hex)
+    }
 }
 
 impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
@@ -35,11 +78,16 @@ impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
                 "json" => Ok(Self::Json),
                 "yaml" => Ok(Self::Yaml),
                 "toml" => Ok(Self::Toml),
+                "msgpack" => Ok(Self::MsgPack),
+                "cbor" => Ok(Self::Cbor),
+                "base64" => Ok(Self::Base64),
+                "base64url" => Ok(Self::Base64Url),
+                "hex" => Ok(Self::Hex),
                 kind => Err(LuaError::FromLuaConversionError {
                     from: value.type_name(),
                     to: "EncodeDecodeFormat",
                     message: Some(format!(
-                        "Invalid format '{kind}', valid formats are:  json, yaml, toml"
+                        "Invalid format '{kind}', valid formats are:  json, yaml, toml, msgpack, cbor, base64, base64url, hex"
                     )),
                 }),
             }
@@ -58,10 +106,36 @@ impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
 
     Encoding / decoding in this case is synonymous with serialize / deserialize.
 */
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct EncodeDecodeConfig {
     pub format: EncodeDecodeFormat,
     pub pretty: bool,
+    pub canonical: bool,
+    pub multi_document: bool,
+    pub preserve_order: bool,
+    pub original: Option<String>,
+}
+
+impl EncodeDecodeConfig {
+    /**
+        Creates a configuration for encoding values in canonical form.
+
+        Canonical output is pretty, has all keys sorted, and always ends with a newline,
+        meaning that equal values always encode to the exact same string, which can be
+        stored in files and compared line by line.
+    */
+    #[must_use]
+    pub fn canonical(format: EncodeDecodeFormat) -> Self {
+        Self {
+            format,
+            pretty: true,
+            canonical: true,
+            multi_document: false,
+            preserve_order: false,
+            original: None,
+        }
+    }
 }
 
 impl From<EncodeDecodeFormat> for EncodeDecodeConfig {
@@ -69,6 +143,10 @@ impl From<EncodeDecodeFormat> for EncodeDecodeConfig {
         Self {
             format,
             pretty: false,
+            canonical: false,
+            multi_document: false,
+            preserve_order: false,
+            original: None,
         }
     }
 }
@@ -78,6 +156,10 @@ impl From<(EncodeDecodeFormat, bool)> for EncodeDecodeConfig {
         Self {
             format: value.0,
             pretty: value.1,
+            canonical: false,
+            multi_document: false,
+            preserve_order: false,
+            original: None,
         }
     }
 }
@@ -94,9 +176,10 @@ pub fn encode<'lua>(
     lua: &'lua Lua,
     config: EncodeDecodeConfig,
 ) -> LuaResult<LuaString<'lua>> {
-    let bytes = match config.format {
+    check_options(&config)?;
+    let mut bytes = match config.format {
         EncodeDecodeFormat::Json => {
-            let serialized: JsonValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+            let serialized: JsonValue = from_lua(lua, value, &config)?;
             if config.pretty {
                 serde_json::to_vec_pretty(&serialized).into_lua_err()?
             } else {
@@ -104,21 +187,69 @@ pub fn encode<'lua>(
             }
         }
         EncodeDecodeFormat::Yaml => {
-            let serialized: YamlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+            let documents = if config.multi_document {
+                let LuaValue::Table(documents) = value else {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Expected an array of documents to encode, got {}",
+                        value.type_name()
+                    )));
+                };
+                documents
+                    .sequence_values::<LuaValue>()
+                    .map(|document| from_lua::<YamlValue>(lua, document?, &config))
+                    .collect::<LuaResult<Vec<_>>>()?
+            } else {
+                vec![from_lua::<YamlValue>(lua, value, &config)?]
+            };
+            // NOTE: Serializing more than one value using the same serializer
+            // separates each of them using the yaml document separator, `---`
             let mut writer = Vec::with_capacity(128);
-            serde_yaml::to_writer(&mut writer, &serialized).into_lua_err()?;
+            let mut serializer = serde_yaml::Serializer::new(&mut writer);
+            for document in documents {
+                document.serialize(&mut serializer).into_lua_err()?;
+            }
+            drop(serializer);
             writer
         }
         EncodeDecodeFormat::Toml => {
-            let serialized: TomlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
-            let s = if config.pretty {
+            let serialized: TomlValue = from_lua(lua, value, &config)?;
+            let s = if let Some(original) = &config.original {
+                update_document(original, &serialized)?
+            } else if config.pretty {
                 toml::to_string_pretty(&serialized).into_lua_err()?
             } else {
                 toml::to_string(&serialized).into_lua_err()?
             };
             s.as_bytes().to_vec()
         }
+        EncodeDecodeFormat::MsgPack => {
+            let serialized: MsgPackValue = from_lua(lua, value, &config)?;
+            let mut writer = Vec::with_capacity(128);
+            rmpv::encode::write_value(&mut writer, &serialized).into_lua_err()?;
+            writer
+        }
+        EncodeDecodeFormat::Cbor => {
+            let serialized = lua_to_cbor(lua, value, config.preserve_order)?;
+            let mut writer = Vec::with_capacity(128);
+            ciborium::into_writer(&serialized, &mut writer).into_lua_err()?;
+            writer
+        }
+        EncodeDecodeFormat::Base64 => BASE64_STANDARD.encode(value_bytes(lua, value)?).into(),
+        EncodeDecodeFormat::Base64Url => BASE64_URL_SAFE.encode(value_bytes(lua, value)?).into(),
+        EncodeDecodeFormat::Hex => encode_hex(&value_bytes(lua, value)?).into(),
     };
+    // NOTE: Keys of lua tables are always sorted when deserializing, so
+    // canonical output only needs to make sure that it ends consistently,
+    // which does not apply to binary formats that have no lines to end, or
+    // to encoded bytes, where a newline would be part of what gets decoded
+    let is_binary = config.format.is_bytes()
+        || matches!(
+            config.format,
+            EncodeDecodeFormat::MsgPack | EncodeDecodeFormat::Cbor
+        );
+    if config.canonical && !is_binary && bytes.last() != Some(&b'\n') {
+        bytes.push(b'\n');
+    }
     lua.create_string(bytes)
 }
 
@@ -135,24 +266,168 @@ pub fn decode(
     config: EncodeDecodeConfig,
 ) -> LuaResult<LuaValue> {
     let bytes = bytes.as_ref();
+    check_options(&config)?;
     match config.format {
         EncodeDecodeFormat::Json => {
             let value: JsonValue = serde_json::from_slice(bytes).into_lua_err()?;
-            lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+            to_lua(lua, &value, &config)
         }
         EncodeDecodeFormat::Yaml => {
-            let value: YamlValue = serde_yaml::from_slice(bytes).into_lua_err()?;
-            lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+            if config.multi_document {
+                let documents = serde_yaml::Deserializer::from_slice(bytes)
+                    .map(|document| {
+                        let value = YamlValue::deserialize(document).into_lua_err()?;
+                        to_lua(lua, &value, &config)
+                    })
+                    .collect::<LuaResult<Vec<_>>>()?;
+                lua.create_sequence_from(documents)?.into_lua(lua)
+            } else {
+                let value: YamlValue = serde_yaml::from_slice(bytes).into_lua_err()?;
+                to_lua(lua, &value, &config)
+            }
         }
         EncodeDecodeFormat::Toml => {
             if let Ok(s) = String::from_utf8(bytes.to_vec()) {
                 let value: TomlValue = toml::from_str(&s).into_lua_err()?;
-                lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+                to_lua(lua, &value, &config)
             } else {
                 Err(LuaError::RuntimeError(
                     "TOML must be valid utf-8".to_string(),
                 ))
             }
         }
+        EncodeDecodeFormat::MsgPack => {
+            let mut remaining = bytes;
+            let value = rmpv::decode::read_value(&mut remaining).into_lua_err()?;
+            if remaining.is_empty() {
+                to_lua(lua, &value, &config)
+            } else {
+                Err(LuaError::RuntimeError(format!(
+                    "MessagePack data has {} unexpected bytes after the end of its value",
+                    remaining.len()
+                )))
+            }
+        }
+        EncodeDecodeFormat::Cbor => {
+            let mut remaining = bytes;
+            let value: ciborium::Value = ciborium::from_reader(&mut remaining).into_lua_err()?;
+            if remaining.is_empty() {
+                cbor_to_lua(lua, value, config.preserve_order)
+            } else {
+                Err(LuaError::RuntimeError(format!(
+                    "CBOR data has {} unexpected bytes after the end of its value",
+                    remaining.len()
+                )))
+            }
+        }
+        EncodeDecodeFormat::Base64 | EncodeDecodeFormat::Base64Url => {
+            // NOTE: Base64 is often split into lines, such as in PEM files,
+            // so any whitespace is skipped instead of being an error
+            let trimmed = bytes
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect::<Vec<_>>();
+            let engine = if matches!(config.format, EncodeDecodeFormat::Base64) {
+                BASE64_STANDARD
+            } else {
+                BASE64_URL_SAFE
+            };
+            let decoded = engine
+                .decode(trimmed)
+                .map_err(|e| LuaError::RuntimeError(format!("Failed to decode base64 - {e}")))?;
+            lua.create_string(decoded)?.into_lua(lua)
+        }
+        EncodeDecodeFormat::Hex => lua.create_string(decode_hex(bytes)?)?.into_lua(lua),
+    }
+}
+
+/**
+    Encodes the given bytes as a string of lowercase hex digits.
+*/
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
+            let _ = write!(output, "{b:02x}");
+            output
+        })
+}
+
+/**
+    Decodes a string of hex digits, which may be either lowercase or uppercase.
+
+    # Errors
+
+    Errors if the string contains anything other than pairs of hex digits.
+*/
+pub(crate) fn decode_hex(bytes: &[u8]) -> LuaResult<Vec<u8>> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(LuaError::runtime(
+            "Failed to decode hex - expected an even number of hex digits",
+        ));
+    }
+    if let Some(position) = bytes.iter().position(|b| !b.is_ascii_hexdigit()) {
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to decode hex - invalid hex digit at position {}",
+            position + 1
+        )));
+    }
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|pair| {
+            let hex = std::str::from_utf8(pair).expect("hex digits are valid utf-8");
+            u8::from_str_radix(hex, 16).expect("hex digits are valid")
+        })
+        .collect())
+}
+
+fn value_bytes(lua: &Lua, value: LuaValue) -> LuaResult<BString> {
+    match value {
+        LuaValue::String(_) | LuaValue::UserData(_) => BString::from_lua(value, lua),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Expected a string or buffer to encode, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+fn check_options(config: &EncodeDecodeConfig) -> LuaResult<()> {
+    if config.multi_document && !matches!(config.format, EncodeDecodeFormat::Yaml) {
+        Err(LuaError::RuntimeError(
+            "Multiple documents are only supported for the yaml format".to_string(),
+        ))
+    } else if config.original.is_some() && !matches!(config.format, EncodeDecodeFormat::Toml) {
+        Err(LuaError::RuntimeError(
+            "Updating an original document is only supported for the toml format".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn from_lua<T: DeserializeOwned>(
+    lua: &Lua,
+    value: LuaValue,
+    config: &EncodeDecodeConfig,
+) -> LuaResult<T> {
+    if config.preserve_order {
+        let value = lua_to_yaml(lua, value, LUA_DESERIALIZE_OPTIONS)?;
+        T::deserialize(value).into_lua_err()
+    } else {
+        lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)
+    }
+}
+
+fn to_lua<'lua, T: Serialize>(
+    lua: &'lua Lua,
+    value: &T,
+    config: &EncodeDecodeConfig,
+) -> LuaResult<LuaValue<'lua>> {
+    if config.preserve_order {
+        let value = serde_yaml::to_value(value).into_lua_err()?;
+        yaml_to_lua(lua, value, LUA_SERIALIZE_OPTIONS)
+    } else {
+        lua.to_value_with(value, LUA_SERIALIZE_OPTIONS)
     }
 }
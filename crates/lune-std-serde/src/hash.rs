@@ -1,5 +1,3 @@
-use std::fmt::Write;
-
 use bstr::BString;
 use md5::Md5;
 use mlua::prelude::*;
@@ -9,15 +7,19 @@ use sha1::Sha1;
 use sha2::{Sha224, Sha256, Sha384, Sha512};
 use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
 
+use crate::encode_decode::encode_hex;
+use crate::signature::{constant_time_eq, decode_bytes};
+
 pub struct HashOptions {
     algorithm: HashAlgorithm,
     message: BString,
     secret: Option<BString>,
+    encoding: HashEncoding,
     // seed: Option<BString>,
 }
 
 #[derive(Debug, Clone, Copy)]
-enum HashAlgorithm {
+pub enum HashAlgorithm {
     Md5,
     Sha1,
     // SHA-2 variants
@@ -49,6 +51,7 @@ impl HashAlgorithm {
         Self::Blake3,
     ];
 
+    #[must_use]
     pub const fn name(self) -> &'static str {
         match self {
             Self::Md5 => "md5",
@@ -64,16 +67,61 @@ impl HashAlgorithm {
             Self::Blake3 => "blake3",
         }
     }
+
+    /**
+        Creates a new hasher for this algorithm, which
+        can be given the message to hash in chunks.
+    */
+    #[must_use]
+    pub fn hasher(self) -> Box<dyn digest::DynDigest> {
+        match self {
+            Self::Md5 => Box::<Md5>::default(),
+            Self::Sha1 => Box::<Sha1>::default(),
+            Self::Sha2_224 => Box::<Sha224>::default(),
+            Self::Sha2_256 => Box::<Sha256>::default(),
+            Self::Sha2_384 => Box::<Sha384>::default(),
+            Self::Sha2_512 => Box::<Sha512>::default(),
+            Self::Sha3_224 => Box::<Sha3_224>::default(),
+            Self::Sha3_256 => Box::<Sha3_256>::default(),
+            Self::Sha3_384 => Box::<Sha3_384>::default(),
+            Self::Sha3_512 => Box::<Sha3_512>::default(),
+            Self::Blake3 => Box::<Blake3>::default(),
+        }
+    }
+}
+
+/**
+    The encoding of a computed hash, which is a
+    string of hex digits unless specified otherwise.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashEncoding {
+    #[default]
+    Hex,
+    Binary,
+}
+
+impl HashEncoding {
+    /**
+        Encodes the raw bytes of a computed hash.
+    */
+    #[must_use]
+    pub fn encode(self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Binary => bytes.to_vec(),
+            Self::Hex => encode_hex(bytes).into_bytes(),
+        }
+    }
 }
 
 impl HashOptions {
     /**
         Computes the hash for the `message` using whatever `algorithm` is
-        contained within this struct and returns it as a string of hex digits.
+        contained within this struct and returns it using the `encoding`.
     */
     #[inline]
     #[must_use = "hashing a message is useless without using the resulting hash"]
-    pub fn hash(self) -> String {
+    pub fn hash(self) -> Vec<u8> {
         use digest::Digest;
 
         let message = self.message;
@@ -93,27 +141,22 @@ impl HashOptions {
             HashAlgorithm::Blake3 => Blake3::digest(message).to_vec(),
         };
 
-        // We don't want to return raw binary data generally, since that's not
-        // what most people want a hash for. So we have to make a hex string.
-        bytes
-            .iter()
-            .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
-                let _ = write!(output, "{b:02x}");
-                output
-            })
+        // We don't want to return raw binary data by default, since that's not
+        // what most people want a hash for, so the encoding defaults to hex.
+        self.encoding.encode(&bytes)
     }
 
     /**
         Computes the HMAC for the `message` using whatever `algorithm` and
         `secret` are contained within this struct. The computed value is
-        returned as a string of hex digits.
+        returned using the `encoding`.
 
         # Errors
 
         If the `secret` is not provided or is otherwise invalid.
     */
     #[inline]
-    pub fn hmac(self) -> LuaResult<String> {
+    pub fn hmac(self) -> LuaResult<Vec<u8>> {
         use hmac::{Hmac, Mac, SimpleHmac};
 
         let secret = self
@@ -165,12 +208,37 @@ impl HashOptions {
 
             HashAlgorithm::Blake3 => hmac_no_blocks!(Blake3),
         };
-        Ok(bytes
-            .iter()
-            .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
-                let _ = write!(output, "{b:02x}");
-                output
-            }))
+        Ok(self.encoding.encode(&bytes))
+    }
+
+    /**
+        Creates options for computing the HMAC for the `message` using
+        the `algorithm` and `secret`, with the HMAC returned as raw bytes.
+    */
+    #[must_use]
+    pub fn new_hmac(algorithm: HashAlgorithm, message: BString, secret: BString) -> Self {
+        Self {
+            algorithm,
+            message,
+            secret: Some(secret),
+            encoding: HashEncoding::Binary,
+        }
+    }
+
+    /**
+        Computes the HMAC for the `message` and checks that it matches the expected
+        HMAC, which may be either raw bytes or a string of hex digits, in constant time.
+
+        # Errors
+
+        If the `secret` is not provided or is otherwise invalid, or if the
+        expected HMAC does not have the same length as the computed one.
+    */
+    pub fn verify_hmac(mut self, expected: &[u8]) -> LuaResult<bool> {
+        self.encoding = HashEncoding::Binary;
+        let computed = self.hmac()?;
+        let expected = decode_bytes(expected, computed.len(), "HMAC")?;
+        Ok(constant_time_eq(&computed, &expected))
     }
 }
 
@@ -221,8 +289,45 @@ impl<'lua> FromLua<'lua> for HashAlgorithm {
     }
 }
 
-impl<'lua> FromLuaMulti<'lua> for HashOptions {
-    fn from_lua_multi(mut values: LuaMultiValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+impl<'lua> FromLua<'lua> for HashEncoding {
+    fn from_lua(value: LuaValue<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(str) => match str.to_str()?.to_ascii_lowercase().as_str() {
+                "hex" => Ok(Self::Hex),
+                "binary" => Ok(Self::Binary),
+                str => Err(LuaError::FromLuaConversionError {
+                    from: "string",
+                    to: "HashEncoding",
+                    message: Some(format!(
+                        "Invalid hash encoding '{str}', valid kinds are:\nhex, binary"
+                    )),
+                }),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "HashEncoding",
+                message: None,
+            }),
+        }
+    }
+}
+
+impl HashOptions {
+    /**
+        Parses the options for hashing a message from arguments, which are the
+        algorithm, the message, the secret if `with_secret` is `true`, and
+        finally the optional encoding to use for the computed hash.
+
+        # Errors
+
+        If any of the arguments are missing or invalid.
+    */
+    pub fn from_args<'lua>(
+        mut values: LuaMultiValue<'lua>,
+        lua: &'lua Lua,
+        with_secret: bool,
+    ) -> LuaResult<Self> {
         let algorithm = values
             .pop_front()
             .map(|value| HashAlgorithm::from_lua(value, lua))
@@ -241,10 +346,15 @@ impl<'lua> FromLuaMulti<'lua> for HashOptions {
                 to: "string or buffer",
                 message: Some("Argument #2 missing or nil".to_string()),
             })?;
-        let secret = values
-            .pop_front()
-            .map(|value| BString::from_lua(value, lua))
-            .transpose()?;
+        let secret = if with_secret {
+            values
+                .pop_front()
+                .map(|value| BString::from_lua(value, lua))
+                .transpose()?
+        } else {
+            None
+        };
+        let encoding = HashEncoding::from_lua(values.pop_front().unwrap_or(LuaValue::Nil), lua)?;
         // let seed = values
         //     .pop_front()
         //     .map(|value| BString::from_lua(value, lua))
@@ -254,6 +364,7 @@ impl<'lua> FromLuaMulti<'lua> for HashOptions {
             algorithm,
             message,
             secret,
+            encoding,
             // seed,
         })
     }
@@ -0,0 +1,101 @@
+use bstr::BString;
+use digest::DynDigest;
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+use crate::hash::{HashAlgorithm, HashEncoding};
+
+const STREAM_IMPL_LUA: &str = r"
+return freeze({
+	write = function(...)
+		return stream:write(...)
+	end,
+	finish = function(...)
+		return stream:finish(...)
+	end,
+})
+";
+
+/**
+    A stream that hashes a message in chunks, so that
+    the entire message never needs to be in memory at once.
+*/
+pub struct HashStream {
+    hasher: Option<Box<dyn DynDigest>>,
+}
+
+impl HashStream {
+    /**
+        Creates a new stream that hashes using the given algorithm.
+    */
+    #[must_use]
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            hasher: Some(algorithm.hasher()),
+        }
+    }
+
+    /**
+        Writes a chunk of the message to the stream.
+
+        # Errors
+
+        Errors when the stream has already been finished.
+    */
+    pub fn write(&mut self, chunk: impl AsRef<[u8]>) -> LuaResult<()> {
+        let hasher = self.hasher.as_mut().ok_or_else(finished_error)?;
+        hasher.update(chunk.as_ref());
+        Ok(())
+    }
+
+    /**
+        Finishes the stream, returning the hash of all chunks of the message.
+
+        # Errors
+
+        Errors when the stream has already been finished.
+    */
+    pub fn finish(&mut self, encoding: HashEncoding) -> LuaResult<Vec<u8>> {
+        let hasher = self.hasher.take().ok_or_else(finished_error)?;
+        Ok(encoding.encode(&hasher.finalize()))
+    }
+
+    /**
+        Creates a lua table for the stream, with `write` and `finish` functions.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+
+        let env = TableBuilder::new(lua)?
+            .with_value("stream", self)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+
+        lua.load(STREAM_IMPL_LUA)
+            .set_name("hash_stream")
+            .set_environment(env)
+            .eval()
+    }
+}
+
+impl LuaUserData for HashStream {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("write", |_, this, chunk: BString| this.write(chunk));
+
+        methods.add_method_mut("finish", |lua, this, encoding: HashEncoding| {
+            lua.create_string(this.finish(encoding)?)
+        });
+    }
+}
+
+fn finished_error() -> LuaError {
+    LuaError::runtime("Stream has already been finished")
+}
@@ -0,0 +1,171 @@
+use std::cmp::Ordering;
+
+use mlua::prelude::*;
+
+use serde_yaml::{Mapping as YamlMapping, Value as YamlValue};
+
+/*
+    Lua tables do not keep any order for their keys, so to preserve the order of
+    keys in decoded documents, the keys of each decoded table are stored in a
+    weak-keyed table in the registry, and looked up again when encoding.
+
+    The key order of a table is only used for keys that still exist in the table,
+    any keys that were added after decoding are encoded in sorted order after them.
+*/
+const KEY_ORDERS_REGISTRY_KEY: &str = "__lune_serde_key_orders";
+
+fn key_orders(lua: &Lua) -> LuaResult<LuaTable> {
+    if let Ok(orders) = lua.named_registry_value::<LuaTable>(KEY_ORDERS_REGISTRY_KEY) {
+        return Ok(orders);
+    }
+    let orders = lua.create_table()?;
+    let meta = lua.create_table()?;
+    meta.raw_set("__mode", "k")?;
+    orders.set_metatable(Some(meta));
+    lua.set_named_registry_value(KEY_ORDERS_REGISTRY_KEY, orders.clone())?;
+    Ok(orders)
+}
+
+/**
+    Gets the order of keys that was recorded for the given table, if any.
+*/
+pub fn get_key_order<'lua>(
+    lua: &'lua Lua,
+    table: &LuaTable<'lua>,
+) -> LuaResult<Option<LuaTable<'lua>>> {
+    key_orders(lua)?.raw_get(table.clone())
+}
+
+/**
+    Records the order of keys for the given table, so that it can be used when encoding.
+*/
+pub fn set_key_order<'lua>(
+    lua: &'lua Lua,
+    table: &LuaTable<'lua>,
+    order: LuaTable<'lua>,
+) -> LuaResult<()> {
+    key_orders(lua)?.raw_set(table.clone(), order)
+}
+
+/**
+    Converts a yaml value into a lua value, recording the order
+    of keys for any mappings so that it can be used when encoding.
+*/
+pub fn yaml_to_lua<'lua>(
+    lua: &'lua Lua,
+    value: YamlValue,
+    options: LuaSerializeOptions,
+) -> LuaResult<LuaValue<'lua>> {
+    let orders = key_orders(lua)?;
+    yaml_to_lua_inner(lua, value, options, &orders)
+}
+
+fn yaml_to_lua_inner<'lua>(
+    lua: &'lua Lua,
+    value: YamlValue,
+    options: LuaSerializeOptions,
+    orders: &LuaTable<'lua>,
+) -> LuaResult<LuaValue<'lua>> {
+    match value {
+        YamlValue::Sequence(seq) => {
+            let table = lua.create_table_with_capacity(seq.len(), 0)?;
+            // NOTE: Nulls become holes in the table, so we must set values by
+            // their index instead of pushing them, to keep the following ones in place
+            for (index, value) in seq.into_iter().enumerate() {
+                table.raw_set(index + 1, yaml_to_lua_inner(lua, value, options, orders)?)?;
+            }
+            Ok(LuaValue::Table(table))
+        }
+        YamlValue::Mapping(map) => {
+            let table = lua.create_table_with_capacity(0, map.len())?;
+            let order = lua.create_table_with_capacity(map.len(), 0)?;
+            for (key, value) in map {
+                let key = yaml_to_lua_inner(lua, key, options, orders)?;
+                let value = yaml_to_lua_inner(lua, value, options, orders)?;
+                table.raw_set(key.clone(), value)?;
+                order.raw_push(key)?;
+            }
+            orders.raw_set(table.clone(), order)?;
+            Ok(LuaValue::Table(table))
+        }
+        value => lua.to_value_with(&value, options),
+    }
+}
+
+/**
+    Converts a lua value into a yaml value, using the order of keys
+    that was recorded when decoding for any tables that have one.
+*/
+pub fn lua_to_yaml(
+    lua: &Lua,
+    value: LuaValue,
+    options: LuaDeserializeOptions,
+) -> LuaResult<YamlValue> {
+    let orders = key_orders(lua)?;
+    let mut visiting = Vec::new();
+    lua_to_yaml_inner(lua, value, options, &orders, &mut visiting)
+}
+
+fn lua_to_yaml_inner<'lua>(
+    lua: &'lua Lua,
+    value: LuaValue<'lua>,
+    options: LuaDeserializeOptions,
+    orders: &LuaTable<'lua>,
+    visiting: &mut Vec<LuaTable<'lua>>,
+) -> LuaResult<YamlValue> {
+    let LuaValue::Table(table) = value else {
+        return lua.from_value_with(value, options);
+    };
+
+    if visiting.contains(&table) {
+        return Err(LuaError::SerializeError(
+            "recursive table detected".to_string(),
+        ));
+    }
+    visiting.push(table.clone());
+
+    let order = orders.raw_get::<_, Option<LuaTable>>(table.clone())?;
+    let len = table.raw_len();
+    let count = table.clone().pairs::<LuaValue, LuaValue>().count();
+
+    let converted = if order.is_none() && len > 0 && len == count {
+        let mut seq = Vec::with_capacity(len);
+        for value in table.clone().sequence_values::<LuaValue>() {
+            seq.push(lua_to_yaml_inner(lua, value?, options, orders, visiting)?);
+        }
+        YamlValue::Sequence(seq)
+    } else {
+        let mut map = YamlMapping::with_capacity(count);
+        let listed = lua.create_table()?;
+        if let Some(order) = order {
+            for key in order.sequence_values::<LuaValue>() {
+                let key = key?;
+                let value = table.raw_get::<_, LuaValue>(key.clone())?;
+                if value.is_nil() || listed.raw_get::<_, bool>(key.clone())? {
+                    continue;
+                }
+                listed.raw_set(key.clone(), true)?;
+                map.insert(
+                    lua_to_yaml_inner(lua, key, options, orders, visiting)?,
+                    lua_to_yaml_inner(lua, value, options, orders, visiting)?,
+                );
+            }
+        }
+        let mut rest = Vec::new();
+        for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            if !listed.raw_get::<_, bool>(key.clone())? {
+                rest.push((
+                    lua_to_yaml_inner(lua, key, options, orders, visiting)?,
+                    lua_to_yaml_inner(lua, value, options, orders, visiting)?,
+                ));
+            }
+        }
+        rest.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        map.extend(rest);
+        YamlValue::Mapping(map)
+    };
+
+    visiting.pop();
+    Ok(converted)
+}
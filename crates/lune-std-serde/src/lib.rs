@@ -5,13 +5,22 @@ use mlua::prelude::*;
 
 use lune_utils::TableBuilder;
 
+mod cbor;
 mod compress_decompress;
+mod compress_stream;
 mod encode_decode;
 mod hash;
+mod hash_stream;
+mod key_order;
+mod signature;
+mod toml_document;
 
 pub use self::compress_decompress::{compress, decompress, CompressDecompressFormat};
+pub use self::compress_stream::CompressDecompressStream;
 pub use self::encode_decode::{decode, encode, EncodeDecodeConfig, EncodeDecodeFormat};
-pub use self::hash::HashOptions;
+pub use self::hash::{HashAlgorithm, HashEncoding, HashOptions};
+pub use self::hash_stream::HashStream;
+pub use self::signature::SignatureAlgorithm;
 
 /**
     Creates the `serde` standard library module.
@@ -24,26 +33,76 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_function("encode", serde_encode)?
         .with_function("decode", serde_decode)?
+        .with_function("cborTag", serde_cbor_tag)?
         .with_async_function("compress", serde_compress)?
         .with_async_function("decompress", serde_decompress)?
+        .with_function("compressStream", serde_compress_stream)?
+        .with_function("decompressStream", serde_decompress_stream)?
         .with_function("hash", hash_message)?
         .with_function("hmac", hmac_message)?
+        .with_function("verifyHmac", hmac_verify)?
+        .with_function("hashStream", hash_stream)?
+        .with_function("generateKeypair", generate_keypair)?
+        .with_function("sign", sign_message)?
+        .with_function("verify", verify_signature)?
         .build_readonly()
 }
 
 fn serde_encode<'lua>(
     lua: &'lua Lua,
-    (format, value, pretty): (EncodeDecodeFormat, LuaValue<'lua>, Option<bool>),
+    (format, value, options): (EncodeDecodeFormat, LuaValue<'lua>, LuaValue<'lua>),
 ) -> LuaResult<LuaString<'lua>> {
-    let config = EncodeDecodeConfig::from((format, pretty.unwrap_or_default()));
+    let config = create_config(format, options)?;
     encode(value, lua, config)
 }
 
-fn serde_decode(lua: &Lua, (format, bs): (EncodeDecodeFormat, BString)) -> LuaResult<LuaValue> {
-    let config = EncodeDecodeConfig::from(format);
+fn serde_decode<'lua>(
+    lua: &'lua Lua,
+    (format, bs, options): (EncodeDecodeFormat, BString, LuaValue<'lua>),
+) -> LuaResult<LuaValue<'lua>> {
+    let config = create_config(format, options)?;
     decode(bs, lua, config)
 }
 
+fn serde_cbor_tag<'lua>(
+    lua: &'lua Lua,
+    (tag, value): (u64, LuaValue<'lua>),
+) -> LuaResult<LuaTable<'lua>> {
+    cbor::create_tag(lua, tag, value)
+}
+
+fn create_config(format: EncodeDecodeFormat, options: LuaValue) -> LuaResult<EncodeDecodeConfig> {
+    let mut config = EncodeDecodeConfig::from(format);
+    match options {
+        LuaValue::Nil => {}
+        LuaValue::Boolean(pretty) => config.pretty = pretty,
+        LuaValue::Table(options) => {
+            config.pretty = options
+                .get::<_, Option<bool>>("pretty")?
+                .unwrap_or_default();
+            config.multi_document = options
+                .get::<_, Option<bool>>("multiDocument")?
+                .unwrap_or_default();
+            config.preserve_order = options
+                .get::<_, Option<bool>>("preserveOrder")?
+                .unwrap_or_default();
+            config.original = match options.get::<_, Option<BString>>("original")? {
+                None => None,
+                Some(original) => Some(String::from_utf8(original.into()).map_err(|_| {
+                    LuaError::RuntimeError("Original document must be valid utf-8".to_string())
+                })?),
+            };
+        }
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid options - expected table, boolean or nil, got {}",
+                value.type_name()
+            )))
+        }
+    }
+    Ok(config)
+}
+
 async fn serde_compress(
     lua: &Lua,
     (format, bs, level): (CompressDecompressFormat, BString, Option<i32>),
@@ -60,10 +119,71 @@ async fn serde_decompress(
     lua.create_string(bytes)
 }
 
-fn hash_message(lua: &Lua, options: HashOptions) -> LuaResult<LuaString> {
+fn serde_compress_stream(
+    lua: &Lua,
+    (format, level): (CompressDecompressFormat, Option<i32>),
+) -> LuaResult<LuaTable> {
+    CompressDecompressStream::compress(format, level)?.into_lua_table(lua)
+}
+
+fn serde_decompress_stream(lua: &Lua, format: CompressDecompressFormat) -> LuaResult<LuaTable> {
+    CompressDecompressStream::decompress(format)?.into_lua_table(lua)
+}
+
+fn hash_message<'lua>(lua: &'lua Lua, args: LuaMultiValue<'lua>) -> LuaResult<LuaString<'lua>> {
+    let options = HashOptions::from_args(args, lua, false)?;
     lua.create_string(options.hash())
 }
 
-fn hmac_message(lua: &Lua, options: HashOptions) -> LuaResult<LuaString> {
+fn hmac_message<'lua>(lua: &'lua Lua, args: LuaMultiValue<'lua>) -> LuaResult<LuaString<'lua>> {
+    let options = HashOptions::from_args(args, lua, true)?;
     lua.create_string(options.hmac()?)
 }
+
+fn hash_stream(lua: &Lua, algorithm: HashAlgorithm) -> LuaResult<LuaTable> {
+    HashStream::new(algorithm).into_lua_table(lua)
+}
+
+fn hmac_verify(
+    _: &Lua,
+    (algorithm, message, secret, expected): (HashAlgorithm, BString, BString, BString),
+) -> LuaResult<bool> {
+    HashOptions::new_hmac(algorithm, message, secret).verify_hmac(&expected)
+}
+
+fn generate_keypair(
+    lua: &Lua,
+    (algorithm, encoding): (SignatureAlgorithm, HashEncoding),
+) -> LuaResult<LuaTable> {
+    let (private_key, public_key) = algorithm.generate_keypair();
+    TableBuilder::new(lua)?
+        .with_value(
+            "privateKey",
+            lua.create_string(encoding.encode(&private_key))?,
+        )?
+        .with_value(
+            "publicKey",
+            lua.create_string(encoding.encode(&public_key))?,
+        )?
+        .build()
+}
+
+fn sign_message(
+    lua: &Lua,
+    (algorithm, private_key, message, encoding): (
+        SignatureAlgorithm,
+        BString,
+        BString,
+        HashEncoding,
+    ),
+) -> LuaResult<LuaString> {
+    let signature = algorithm.sign(&private_key, &message)?;
+    lua.create_string(encoding.encode(&signature))
+}
+
+fn verify_signature(
+    _: &Lua,
+    (algorithm, public_key, message, signature): (SignatureAlgorithm, BString, BString, BString),
+) -> LuaResult<bool> {
+    algorithm.verify(&public_key, &message, &signature)
+}
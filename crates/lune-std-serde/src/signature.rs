@@ -0,0 +1,147 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use mlua::prelude::*;
+
+use crate::encode_decode::decode_hex;
+
+/**
+    A signature algorithm supported by the Serde library.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    Ed25519,
+}
+
+impl SignatureAlgorithm {
+    pub const ALL: [Self; 1] = [Self::Ed25519];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ed25519 => "ed25519",
+        }
+    }
+
+    /**
+        Generates a new random private key, returning it along with its public key.
+    */
+    #[must_use]
+    pub fn generate_keypair(self) -> (Vec<u8>, Vec<u8>) {
+        match self {
+            Self::Ed25519 => {
+                let key = SigningKey::from_bytes(&rand::random());
+                (
+                    key.to_bytes().to_vec(),
+                    key.verifying_key().to_bytes().to_vec(),
+                )
+            }
+        }
+    }
+
+    /**
+        Signs the message using the given private key.
+
+        # Errors
+
+        Errors if the private key is invalid.
+    */
+    pub fn sign(self, private_key: &[u8], message: &[u8]) -> LuaResult<Vec<u8>> {
+        match self {
+            Self::Ed25519 => {
+                let key = decode_array::<32>(private_key, "private key")?;
+                let key = SigningKey::from_bytes(&key);
+                Ok(key.sign(message).to_bytes().to_vec())
+            }
+        }
+    }
+
+    /**
+        Verifies that the signature for the message was created
+        using the private key that belongs to the given public key.
+
+        # Errors
+
+        Errors if the public key or the signature is invalid.
+    */
+    pub fn verify(self, public_key: &[u8], message: &[u8], signature: &[u8]) -> LuaResult<bool> {
+        match self {
+            Self::Ed25519 => {
+                let key = decode_array::<32>(public_key, "public key")?;
+                let key = VerifyingKey::from_bytes(&key).map_err(|e| {
+                    LuaError::RuntimeError(format!("Invalid ed25519 public key - {e}"))
+                })?;
+                let signature = decode_array::<64>(signature, "signature")?;
+                let signature = Signature::from_bytes(&signature);
+                Ok(key.verify(message, &signature).is_ok())
+            }
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for SignatureAlgorithm {
+    fn from_lua(value: LuaValue<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(str) = value {
+            let str = str.to_str()?.to_ascii_lowercase();
+            match str.as_str() {
+                "ed25519" => Ok(Self::Ed25519),
+                _ => Err(LuaError::FromLuaConversionError {
+                    from: "string",
+                    to: "SignatureAlgorithm",
+                    message: Some(format!(
+                        "Invalid signature algorithm '{str}', valid kinds are:\n{}",
+                        SignatureAlgorithm::ALL
+                            .into_iter()
+                            .map(SignatureAlgorithm::name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SignatureAlgorithm",
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Decodes bytes of a known length, such as a key or a signature,
+    which may be given either as raw bytes or as a string of hex digits.
+
+    Raw bytes and hex digits always have different lengths, so we can tell them apart.
+*/
+pub(crate) fn decode_bytes(bytes: &[u8], len: usize, what: &str) -> LuaResult<Vec<u8>> {
+    if bytes.len() == len {
+        return Ok(bytes.to_vec());
+    }
+    if bytes.len() != len * 2 {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid {what} - expected {len} bytes, or {} hex digits, got {} bytes",
+            len * 2,
+            bytes.len()
+        )));
+    }
+    if !bytes.iter().all(u8::is_ascii_hexdigit) {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid {what} - contains characters that are not hex digits"
+        )));
+    }
+    decode_hex(bytes)
+}
+
+fn decode_array<const N: usize>(bytes: &[u8], what: &str) -> LuaResult<[u8; N]> {
+    let decoded = decode_bytes(bytes, N, what)?;
+    Ok(decoded
+        .try_into()
+        .expect("decoded bytes have the expected length"))
+}
+
+/**
+    Compares two byte slices in constant time, so that comparing a
+    secret value does not reveal how much of it was guessed correctly.
+*/
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
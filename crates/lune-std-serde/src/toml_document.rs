@@ -0,0 +1,152 @@
+use mlua::prelude::*;
+
+use toml::{Table as TomlTable, Value as TomlValue};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike, Value};
+
+/**
+    Updates the given original toml document so that it contains the given value,
+    keeping comments, formatting, and the order of keys for anything that did not change.
+
+    # Errors
+
+    Errors when the original document is not valid toml, or the value is not a table.
+*/
+pub fn update_document(original: &str, value: &TomlValue) -> LuaResult<String> {
+    let mut document = original.parse::<DocumentMut>().map_err(|e| {
+        LuaError::RuntimeError(format!("Failed to parse original toml document - {e}"))
+    })?;
+    let TomlValue::Table(table) = value else {
+        return Err(LuaError::RuntimeError(format!(
+            "Expected a table to encode as a toml document, got {}",
+            value.type_str()
+        )));
+    };
+    merge_table(document.as_table_mut(), table, false);
+    Ok(document.to_string())
+}
+
+fn merge_table(target: &mut dyn TableLike, source: &TomlTable, inline: bool) {
+    let removed = target
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !source.contains_key(key))
+        .collect::<Vec<_>>();
+    for key in removed {
+        target.remove(&key);
+    }
+
+    for (key, value) in source {
+        if let Some(item) = target.get_mut(key) {
+            merge_item(item, value);
+        } else if inline {
+            target.insert(key, Item::Value(create_value(value)));
+        } else {
+            target.insert(key, create_item(value));
+        }
+    }
+}
+
+fn merge_item(target: &mut Item, source: &TomlValue) {
+    match (target, source) {
+        (Item::Table(target), TomlValue::Table(source)) => merge_table(target, source, false),
+        (Item::ArrayOfTables(target), TomlValue::Array(source)) if is_array_of_tables(source) => {
+            while target.len() > source.len() {
+                target.remove(target.len() - 1);
+            }
+            for (index, source) in source.iter().filter_map(TomlValue::as_table).enumerate() {
+                if let Some(target) = target.get_mut(index) {
+                    merge_table(target, source, false);
+                } else {
+                    target.push(create_table(source));
+                }
+            }
+        }
+        (Item::Value(target), source) => merge_value(target, source),
+        (target, source) => *target = create_item(source),
+    }
+}
+
+fn merge_value(target: &mut Value, source: &TomlValue) {
+    match (&mut *target, source) {
+        (Value::InlineTable(target), TomlValue::Table(source)) => merge_table(target, source, true),
+        (Value::Array(target), TomlValue::Array(source)) => {
+            while target.len() > source.len() {
+                target.remove(target.len() - 1);
+            }
+            for (index, source) in source.iter().enumerate() {
+                if let Some(target) = target.get_mut(index) {
+                    merge_value(target, source);
+                } else {
+                    // NOTE: Added values are formatted like the value before them,
+                    // so that they end up on their own line in multiline arrays
+                    let previous = index
+                        .checked_sub(1)
+                        .and_then(|i| target.get(i))
+                        .map(|value| value.decor().clone())
+                        .filter(|decor| decor.prefix().and_then(|p| p.as_str()) != Some(""));
+                    match previous {
+                        Some(decor) => {
+                            let mut value = create_value(source);
+                            *value.decor_mut() = decor;
+                            target.push_formatted(value);
+                        }
+                        None => target.push(create_value(source)),
+                    }
+                }
+            }
+        }
+        (Value::String(t), TomlValue::String(s)) if t.value() == s => {}
+        (Value::Integer(t), TomlValue::Integer(s)) if t.value() == s => {}
+        (Value::Float(t), TomlValue::Float(s)) if t.value().to_bits() == s.to_bits() => {}
+        (Value::Boolean(t), TomlValue::Boolean(s)) if t.value() == s => {}
+        (Value::Datetime(t), TomlValue::Datetime(s)) if t.value() == s => {}
+        _ => {
+            let decor = target.decor().clone();
+            *target = create_value(source);
+            *target.decor_mut() = decor;
+        }
+    }
+}
+
+fn is_array_of_tables(array: &[TomlValue]) -> bool {
+    !array.is_empty() && array.iter().all(TomlValue::is_table)
+}
+
+fn create_item(source: &TomlValue) -> Item {
+    match source {
+        TomlValue::Table(table) => Item::Table(create_table(table)),
+        TomlValue::Array(array) if is_array_of_tables(array) => Item::ArrayOfTables(
+            array
+                .iter()
+                .filter_map(TomlValue::as_table)
+                .map(create_table)
+                .collect::<ArrayOfTables>(),
+        ),
+        value => Item::Value(create_value(value)),
+    }
+}
+
+fn create_table(source: &TomlTable) -> Table {
+    let mut table = Table::new();
+    for (key, value) in source {
+        table.insert(key, create_item(value));
+    }
+    table
+}
+
+fn create_value(source: &TomlValue) -> Value {
+    match source {
+        TomlValue::String(s) => Value::from(s.as_str()),
+        TomlValue::Integer(i) => Value::from(*i),
+        TomlValue::Float(f) => Value::from(*f),
+        TomlValue::Boolean(b) => Value::from(*b),
+        TomlValue::Datetime(d) => Value::from(*d),
+        TomlValue::Array(array) => Value::Array(array.iter().map(create_value).collect()),
+        TomlValue::Table(table) => Value::InlineTable(
+            table
+                .iter()
+                .map(|(key, value)| (key.as_str(), create_value(value)))
+                .collect(),
+        ),
+    }
+}
@@ -0,0 +1,57 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+use tokio_serial::{available_ports, SerialPortInfo, SerialPortType};
+
+use lune_utils::TableBuilder;
+
+mod options;
+mod port;
+
+use self::options::SerialPortOptions;
+use self::port::SerialPort;
+
+/**
+    Creates the `serial` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("list", serial_list)?
+        .with_function("open", serial_open)?
+        .build_readonly()
+}
+
+fn serial_list(lua: &Lua, (): ()) -> LuaResult<LuaTable> {
+    let ports = available_ports()
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to list serial ports - {e}")))?;
+    let infos = ports
+        .into_iter()
+        .map(|port| create_port_info(lua, port))
+        .collect::<LuaResult<Vec<_>>>()?;
+    lua.create_sequence_from(infos)
+}
+
+fn serial_open(_: &Lua, (path, options): (String, SerialPortOptions)) -> LuaResult<SerialPort> {
+    SerialPort::open(&path, options)
+}
+
+fn create_port_info(lua: &Lua, port: SerialPortInfo) -> LuaResult<LuaTable> {
+    let info = TableBuilder::new(lua)?.with_value("name", port.port_name)?;
+    let info = match port.port_type {
+        SerialPortType::UsbPort(usb) => info
+            .with_value("type", "usb")?
+            .with_value("vid", usb.vid)?
+            .with_value("pid", usb.pid)?
+            .with_value("manufacturer", usb.manufacturer)?
+            .with_value("product", usb.product)?
+            .with_value("serialNumber", usb.serial_number)?,
+        SerialPortType::PciPort => info.with_value("type", "pci")?,
+        SerialPortType::BluetoothPort => info.with_value("type", "bluetooth")?,
+        SerialPortType::Unknown => info.with_value("type", "unknown")?,
+    };
+    info.build_readonly()
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use mlua::prelude::*;
+use tokio_serial::{DataBits, FlowControl, Parity, StopBits};
+
+const DEFAULT_BAUD_RATE: u32 = 9600;
+
+fn invalid_options(message: impl Into<String>) -> LuaError {
+    LuaError::FromLuaConversionError {
+        from: "table",
+        to: "SerialPortOptions",
+        message: Some(format!("Invalid serial port options - {}", message.into())),
+    }
+}
+
+/**
+    Options for opening a serial port using `serial.open`.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct SerialPortOptions {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for SerialPortOptions {
+    fn default() -> Self {
+        Self {
+            baud_rate: DEFAULT_BAUD_RATE,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            timeout: None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for SerialPortOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let options = match value {
+            LuaValue::Nil => return Ok(Self::default()),
+            LuaValue::Table(options) => options,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SerialPortOptions",
+                    message: Some(format!(
+                        "Invalid serial port options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+
+        let baud_rate = match options.get::<_, Option<u32>>("baudRate")? {
+            None => DEFAULT_BAUD_RATE,
+            Some(0) => return Err(invalid_options("baudRate must be a positive integer")),
+            Some(rate) => rate,
+        };
+
+        let data_bits = match options.get::<_, Option<u8>>("dataBits")? {
+            None | Some(8) => DataBits::Eight,
+            Some(7) => DataBits::Seven,
+            Some(6) => DataBits::Six,
+            Some(5) => DataBits::Five,
+            Some(bits) => {
+                return Err(invalid_options(format!(
+                    "dataBits must be 5, 6, 7 or 8, got {bits}"
+                )))
+            }
+        };
+
+        let parity = match options.get::<_, Option<String>>("parity")?.as_deref() {
+            None | Some("none") => Parity::None,
+            Some("odd") => Parity::Odd,
+            Some("even") => Parity::Even,
+            Some(parity) => {
+                return Err(invalid_options(format!(
+                    "parity must be 'none', 'odd' or 'even', got '{parity}'"
+                )))
+            }
+        };
+
+        let stop_bits = match options.get::<_, Option<u8>>("stopBits")? {
+            None | Some(1) => StopBits::One,
+            Some(2) => StopBits::Two,
+            Some(bits) => {
+                return Err(invalid_options(format!(
+                    "stopBits must be 1 or 2, got {bits}"
+                )))
+            }
+        };
+
+        let flow_control = match options.get::<_, Option<String>>("flowControl")?.as_deref() {
+            None | Some("none") => FlowControl::None,
+            Some("software") => FlowControl::Software,
+            Some("hardware") => FlowControl::Hardware,
+            Some(flow) => {
+                return Err(invalid_options(format!(
+                    "flowControl must be 'none', 'software' or 'hardware', got '{flow}'"
+                )))
+            }
+        };
+
+        let timeout = match options.get::<_, Option<f64>>("timeout")? {
+            None => None,
+            Some(secs) if secs.is_finite() && secs > 0.0 => Some(Duration::from_secs_f64(secs)),
+            Some(_) => return Err(invalid_options("timeout must be a positive number")),
+        };
+
+        Ok(Self {
+            baud_rate,
+            data_bits,
+            parity,
+            stop_bits,
+            flow_control,
+            timeout,
+        })
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    future::poll_fn,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use bstr::{BString, ByteSlice};
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::Notify,
+    time::timeout,
+};
+use tokio_serial::{SerialPort as _, SerialPortBuilderExt, SerialStream};
+
+use crate::options::SerialPortOptions;
+
+// Number of bytes to read at most, when no maximum is given to `SerialPort:read`
+const DEFAULT_READ_SIZE: usize = 1024;
+
+// Number of bytes to read at most in a single call to `SerialPort:read`, no matter the
+// maximum given, since reads only return what is available and a buffer is allocated for it
+const MAX_READ_SIZE: usize = 64 * 1024;
+
+/**
+    A serial port that was opened using `serial.open`.
+
+    Reading, writing, and changing settings of the port may all happen at the same
+    time from different Lua threads - the stream is only locked while being polled.
+*/
+#[derive(Debug, Clone)]
+pub struct SerialPort {
+    name: Arc<str>,
+    stream: Arc<Mutex<Option<SerialStream>>>,
+    timeout: Option<Duration>,
+    closed_notify: Arc<Notify>,
+}
+
+impl SerialPort {
+    pub fn open(path: &str, options: SerialPortOptions) -> LuaResult<Self> {
+        let stream = tokio_serial::new(path, options.baud_rate)
+            .data_bits(options.data_bits)
+            .parity(options.parity)
+            .stop_bits(options.stop_bits)
+            .flow_control(options.flow_control)
+            .open_native_async()
+            .map_err(|e| {
+                LuaError::RuntimeError(format!("Failed to open serial port '{path}' - {e}"))
+            })?;
+
+        Ok(Self {
+            name: Arc::from(path),
+            stream: Arc::new(Mutex::new(Some(stream))),
+            timeout: options.timeout,
+            closed_notify: Arc::new(Notify::new()),
+        })
+    }
+
+    fn is_closed(&self) -> bool {
+        self.stream.lock().unwrap().is_none()
+    }
+
+    fn with_stream<T>(&self, f: impl FnOnce(&mut SerialStream) -> LuaResult<T>) -> LuaResult<T> {
+        match self.stream.lock().unwrap().as_mut() {
+            Some(stream) => f(stream),
+            None => Err(LuaError::runtime("Serial port has already been closed")),
+        }
+    }
+
+    /**
+        Polls the stream using the given function until it is ready, the timeout
+        for the port elapses, or the port is closed - returning `None` if it was closed.
+    */
+    async fn poll_stream<T>(
+        &self,
+        mut f: impl FnMut(Pin<&mut SerialStream>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> io::Result<Option<T>> {
+        let closed = self.closed_notify.notified();
+        if self.is_closed() {
+            return Ok(None);
+        }
+
+        let poll = poll_fn(|cx| {
+            let mut stream = self.stream.lock().unwrap();
+            match stream.as_mut() {
+                Some(stream) => f(Pin::new(stream), cx).map_ok(Some),
+                None => Poll::Ready(Ok(None)),
+            }
+        });
+
+        let timed = async {
+            match self.timeout {
+                Some(duration) => timeout(duration, poll)
+                    .await
+                    .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?,
+                None => poll.await,
+            }
+        };
+
+        tokio::select! {
+            res = timed => res,
+            () = closed => Ok(None),
+        }
+    }
+
+    pub async fn read(&self, max_bytes: usize) -> LuaResult<Option<Vec<u8>>> {
+        let mut buf = vec![0; max_bytes];
+        let len = self
+            .poll_stream(|stream, cx| {
+                let mut read_buf = ReadBuf::new(&mut buf);
+                ready!(stream.poll_read(cx, &mut read_buf))?;
+                Poll::Ready(Ok(read_buf.filled().len()))
+            })
+            .await
+            .map_err(|e| {
+                LuaError::RuntimeError(format!("Failed to read from serial port - {e}"))
+            })?;
+
+        Ok(len.map(|len| {
+            buf.truncate(len);
+            buf
+        }))
+    }
+
+    pub async fn write(&self, data: &[u8]) -> LuaResult<()> {
+        let mut written = 0;
+        let res = self
+            .poll_stream(|mut stream, cx| {
+                while written < data.len() {
+                    let n = ready!(stream.as_mut().poll_write(cx, &data[written..]))?;
+                    if n == 0 {
+                        return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                    }
+                    written += n;
+                }
+                stream.poll_flush(cx)
+            })
+            .await
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to write to serial port - {e}")))?;
+
+        match res {
+            Some(()) => Ok(()),
+            None => Err(LuaError::runtime("Serial port has already been closed")),
+        }
+    }
+
+    pub fn close(&self) -> LuaResult<()> {
+        // NOTE: Dropping the stream closes the port
+        let stream = self.stream.lock().unwrap().take();
+        if stream.is_none() {
+            return Err(LuaError::runtime("Serial port has already been closed"));
+        }
+        self.closed_notify.notify_waiters();
+        Ok(())
+    }
+}
+
+fn setting_error(setting: &'static str) -> impl Fn(tokio_serial::Error) -> LuaError {
+    move |e| LuaError::RuntimeError(format!("Failed to set {setting} of serial port - {e}"))
+}
+
+impl LuaUserData for SerialPort {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "SerialPort");
+        fields.add_field_method_get("name", |_, this| Ok(this.name.to_string()));
+        fields.add_field_method_get("baudRate", |_, this| {
+            this.with_stream(|stream| {
+                stream.baud_rate().map_err(|e| {
+                    LuaError::RuntimeError(format!("Failed to get baud rate of serial port - {e}"))
+                })
+            })
+        });
+        fields.add_field_method_get("closed", |_, this| Ok(this.is_closed()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read", |lua, this, max_bytes: Option<usize>| async move {
+            let max_bytes = max_bytes.unwrap_or(DEFAULT_READ_SIZE);
+            if max_bytes == 0 {
+                return Err(LuaError::runtime(
+                    "Maximum number of bytes to read must be positive",
+                ));
+            }
+            match this.read(max_bytes.min(MAX_READ_SIZE)).await? {
+                Some(data) => lua.create_string(data).map(LuaValue::String),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_async_method("write", |_, this, data: BString| async move {
+            this.write(data.as_bytes()).await
+        });
+        methods.add_method("setBaudRate", |_, this, rate: u32| {
+            if rate == 0 {
+                return Err(LuaError::runtime("Baud rate must be a positive integer"));
+            }
+            this.with_stream(|stream| {
+                stream
+                    .set_baud_rate(rate)
+                    .map_err(setting_error("baud rate"))
+            })
+        });
+        methods.add_method("setDtr", |_, this, level: bool| {
+            this.with_stream(|stream| {
+                stream
+                    .write_data_terminal_ready(level)
+                    .map_err(setting_error("DTR"))
+            })
+        });
+        methods.add_method("setRts", |_, this, level: bool| {
+            this.with_stream(|stream| {
+                stream
+                    .write_request_to_send(level)
+                    .map_err(setting_error("RTS"))
+            })
+        });
+        methods.add_method("close", |_, this, (): ()| this.close());
+    }
+}
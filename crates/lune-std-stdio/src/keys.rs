@@ -0,0 +1,197 @@
+use std::io::{self, stdin, IsTerminal};
+
+use console::{Key, Term};
+use mlua::prelude::*;
+
+/**
+    The original terminal mode, from before raw mode was enabled.
+
+    Restores the original mode when dropped, which happens when raw mode
+    is disabled, or when the Lua state is dropped after a script exits.
+*/
+pub(super) struct RawMode(sys::Mode);
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        sys::set_mode(&self.0).ok();
+    }
+}
+
+/**
+    Enables or disables raw mode for the terminal that stdin is connected to.
+*/
+pub(super) fn set_raw_mode(lua: &Lua, enabled: bool) -> LuaResult<()> {
+    if !stdin().is_terminal() {
+        return Err(LuaError::runtime(
+            "Raw mode can only be used when stdin is a terminal",
+        ));
+    }
+    if !enabled {
+        // NOTE: Dropping the original mode restores it
+        lua.remove_app_data::<RawMode>();
+        return Ok(());
+    }
+    if lua.app_data_ref::<RawMode>().is_some() {
+        return Ok(());
+    }
+    let original = sys::enable_raw_mode()
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to enable raw mode - {e}")))?;
+    lua.set_app_data(RawMode(original));
+    Ok(())
+}
+
+/**
+    Checks if raw mode is currently enabled.
+*/
+pub(super) fn is_raw_mode(lua: &Lua) -> bool {
+    lua.app_data_ref::<RawMode>().is_some()
+}
+
+/**
+    Reads a single key press from the terminal, blocking until one is available.
+
+    Returns `None` if stdin is not a terminal. When `raw` is `true`, pressing Ctrl+C
+    is returned as a key - otherwise, it interrupts the process as it usually would.
+*/
+pub(super) fn read_key(raw: bool) -> io::Result<Option<String>> {
+    if !stdin().is_terminal() {
+        return Ok(None);
+    }
+    let term = Term::stdout();
+    let key = if raw {
+        term.read_key_raw()?
+    } else {
+        term.read_key()?
+    };
+    Ok(Some(key_name(key)))
+}
+
+/*
+    NOTE: Keys that type a character are given as that character, and all other
+    keys are given as names, which are always longer than a single character
+*/
+fn key_name(key: Key) -> String {
+    let name = match key {
+        Key::Char(c) if c.is_control() => return control_key_name(c),
+        Key::Char(c) => return c.to_string(),
+        // Alt + a character is sent as escape followed by that character
+        Key::UnknownEscSeq(seq) if seq.len() == 1 && !seq[0].is_control() => {
+            return format!("alt+{}", seq[0]);
+        }
+        Key::ArrowUp => "up",
+        Key::ArrowDown => "down",
+        Key::ArrowLeft => "left",
+        Key::ArrowRight => "right",
+        Key::Enter => "enter",
+        Key::Escape => "escape",
+        Key::Backspace => "backspace",
+        Key::Del => "delete",
+        Key::Insert => "insert",
+        Key::Home => "home",
+        Key::End => "end",
+        Key::PageUp => "pageup",
+        Key::PageDown => "pagedown",
+        Key::Tab => "tab",
+        Key::BackTab => "shift+tab",
+        Key::CtrlC => "ctrl+c",
+        _ => "unknown",
+    };
+    name.to_string()
+}
+
+fn control_key_name(c: char) -> String {
+    let code = c as u32;
+    match char::from_u32(code + 0x60) {
+        Some(letter @ 'a'..='z') => format!("ctrl+{letter}"),
+        _ => String::from("unknown"),
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{io, mem::MaybeUninit, ptr::addr_of, ptr::addr_of_mut};
+
+    pub struct Mode(libc::termios);
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn enable_raw_mode() -> io::Result<Mode> {
+        // SAFETY: The termios struct is initialized by tcgetattr before it is read
+        let mut termios = MaybeUninit::uninit();
+        check(unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) })?;
+        let original = unsafe { termios.assume_init() };
+
+        // Output processing is kept, so that printing newlines still works as usual
+        let mut raw = original;
+        unsafe { libc::cfmakeraw(addr_of_mut!(raw)) };
+        raw.c_oflag = original.c_oflag;
+        check(unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, addr_of!(raw)) })?;
+
+        Ok(Mode(original))
+    }
+
+    pub fn set_mode(mode: &Mode) -> io::Result<()> {
+        check(unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, addr_of!(mode.0)) })
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{io, ptr::addr_of_mut};
+
+    use windows_sys::Win32::System::Console::{
+        GetConsoleMode, GetStdHandle, SetConsoleMode, CONSOLE_MODE, ENABLE_ECHO_INPUT,
+        ENABLE_LINE_INPUT, ENABLE_PROCESSED_INPUT, STD_INPUT_HANDLE,
+    };
+
+    pub struct Mode(CONSOLE_MODE);
+
+    fn check(result: i32) -> io::Result<()> {
+        if result == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn enable_raw_mode() -> io::Result<Mode> {
+        let mut original: CONSOLE_MODE = 0;
+        // SAFETY: The handle is checked by GetConsoleMode, which fails for invalid handles
+        unsafe {
+            let handle = GetStdHandle(STD_INPUT_HANDLE);
+            check(GetConsoleMode(handle, addr_of_mut!(original)))?;
+            let raw = original & !(ENABLE_ECHO_INPUT | ENABLE_LINE_INPUT | ENABLE_PROCESSED_INPUT);
+            check(SetConsoleMode(handle, raw))?;
+        }
+        Ok(Mode(original))
+    }
+
+    pub fn set_mode(mode: &Mode) -> io::Result<()> {
+        // SAFETY: See above
+        unsafe { check(SetConsoleMode(GetStdHandle(STD_INPUT_HANDLE), mode.0)) }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::io;
+
+    pub struct Mode;
+
+    pub fn enable_raw_mode() -> io::Result<Mode> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "raw mode is not supported on this platform",
+        ))
+    }
+
+    pub fn set_mode(_: &Mode) -> io::Result<()> {
+        Ok(())
+    }
+}
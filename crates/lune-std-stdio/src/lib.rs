@@ -1,18 +1,38 @@
 #![allow(clippy::cargo_common_metadata)]
 
-use lune_utils::fmt::{pretty_format_multi_value, ValueFormatConfig};
+use lune_utils::fmt::{pretty_format_multi_value, set_lua_value_formatter, ValueFormatConfig};
 use mlua::prelude::*;
 use mlua_luau_scheduler::LuaSpawnExt;
 
-use tokio::io::{stderr, stdin, stdout, AsyncReadExt, AsyncWriteExt};
+use std::sync::mpsc::{sync_channel, SyncSender};
 
-use lune_utils::TableBuilder;
+use tokio::{
+    io::{stderr, stdin, stdout, AsyncReadExt, AsyncWriteExt},
+    sync::mpsc::unbounded_channel,
+};
 
+use lune_utils::{
+    output::{has_live_output, is_output_captured, write_output, OutputStream},
+    TableBuilder,
+};
+
+mod keys;
+mod progress;
 mod prompt;
+mod resize;
 mod style_and_color;
+mod table;
+mod terminal;
+mod validator;
 
+use self::keys::{is_raw_mode, read_key, set_raw_mode};
+use self::progress::create_progress_table;
 use self::prompt::{prompt, PromptOptions, PromptResult};
-use self::style_and_color::{ColorKind, StyleKind};
+use self::resize::on_resize;
+use self::style_and_color::{ColorKind, ColorLayer, ColorLevel, StyleKind, StyledOptions};
+use self::table::{render_table, TableOptions};
+use self::terminal::{create_link, create_title_sequence, terminal_size, TerminalSize};
+use self::validator::{check_lua_validator_result, InputValidator, PromptValidator};
 
 const FORMAT_CONFIG: ValueFormatConfig = ValueFormatConfig::new()
     .with_max_depth(4)
@@ -29,34 +49,99 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_function("color", stdio_color)?
         .with_function("style", stdio_style)?
+        .with_function("styled", stdio_styled)?
+        .with_function("colorLevel", stdio_color_level)?
         .with_function("format", stdio_format)?
+        .with_function("setFormatter", stdio_set_formatter)?
+        .with_function("table", stdio_table)?
+        .with_function("link", stdio_link)?
+        .with_function("terminalSize", stdio_terminal_size)?
+        .with_function("onResize", stdio_on_resize)?
+        .with_async_function("setTitle", stdio_set_title)?
         .with_async_function("write", stdio_write)?
         .with_async_function("ewrite", stdio_ewrite)?
         .with_async_function("readToEnd", stdio_read_to_end)?
         .with_async_function("prompt", stdio_prompt)?
+        .with_function("setRawMode", stdio_set_raw_mode)?
+        .with_async_function("readKey", stdio_read_key)?
+        .with_value("progress", create_progress_table(lua)?)?
         .build_readonly()
 }
 
 fn stdio_color(lua: &Lua, color: ColorKind) -> LuaResult<LuaValue> {
-    color.ansi_escape_sequence().into_lua(lua)
+    color
+        .ansi_escape_sequence(ColorLevel::detect(), ColorLayer::Foreground)
+        .into_lua(lua)
 }
 
 fn stdio_style(lua: &Lua, style: StyleKind) -> LuaResult<LuaValue> {
     style.ansi_escape_sequence().into_lua(lua)
 }
 
-fn stdio_format(_: &Lua, args: LuaMultiValue) -> LuaResult<String> {
-    Ok(pretty_format_multi_value(&args, &FORMAT_CONFIG))
+fn stdio_styled(_: &Lua, (text, options): (String, StyledOptions)) -> LuaResult<String> {
+    Ok(options.apply(&text, ColorLevel::detect()))
+}
+
+fn stdio_color_level(_: &Lua, (): ()) -> LuaResult<&'static str> {
+    Ok(ColorLevel::detect().name())
+}
+
+fn stdio_format(lua: &Lua, args: LuaMultiValue) -> LuaResult<String> {
+    Ok(pretty_format_multi_value(lua, &args, &FORMAT_CONFIG))
+}
+
+fn stdio_set_formatter(
+    lua: &Lua,
+    (type_name, formatter): (String, Option<LuaFunction>),
+) -> LuaResult<()> {
+    set_lua_value_formatter(lua, &type_name, formatter)
+}
+
+fn stdio_table(lua: &Lua, (rows, options): (LuaTable, TableOptions)) -> LuaResult<String> {
+    render_table(lua, &rows, options)
+}
+
+fn stdio_link(_: &Lua, (text, url): (String, String)) -> LuaResult<String> {
+    Ok(create_link(&text, &url))
+}
+
+fn stdio_terminal_size(_: &Lua, (): ()) -> LuaResult<Option<TerminalSize>> {
+    Ok(terminal_size())
 }
 
-async fn stdio_write(_: &Lua, s: LuaString<'_>) -> LuaResult<()> {
+fn stdio_on_resize<'lua>(lua: &'lua Lua, callback: LuaFunction<'lua>) -> LuaResult<LuaTable<'lua>> {
+    on_resize(lua, callback)
+}
+
+async fn stdio_set_title(_: &Lua, title: String) -> LuaResult<()> {
+    if let Some(sequence) = create_title_sequence(&title) {
+        let mut stdout = stdout();
+        stdout.write_all(sequence.as_bytes()).await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
+
+/*
+    NOTE: Output written while progress bars are shown must be written right away,
+    in between hiding and drawing them again, so it can not be written asynchronously,
+    and neither can output that is captured, since it should stay in the same order
+*/
+
+async fn stdio_write(lua: &Lua, s: LuaString<'_>) -> LuaResult<()> {
+    if has_live_output() || is_output_captured(lua) {
+        return Ok(write_output(lua, OutputStream::Stdout, s.as_bytes())?);
+    }
     let mut stdout = stdout();
     stdout.write_all(s.as_bytes()).await?;
     stdout.flush().await?;
     Ok(())
 }
 
-async fn stdio_ewrite(_: &Lua, s: LuaString<'_>) -> LuaResult<()> {
+async fn stdio_ewrite(lua: &Lua, s: LuaString<'_>) -> LuaResult<()> {
+    if has_live_output() || is_output_captured(lua) {
+        return Ok(write_output(lua, OutputStream::Stderr, s.as_bytes())?);
+    }
     let mut stderr = stderr();
     stderr.write_all(s.as_bytes()).await?;
     stderr.flush().await?;
@@ -78,8 +163,74 @@ async fn stdio_read_to_end(lua: &Lua, (): ()) -> LuaResult<LuaString> {
     lua.create_string(&input)
 }
 
-async fn stdio_prompt(lua: &Lua, options: PromptOptions) -> LuaResult<PromptResult> {
-    lua.spawn_blocking(move || prompt(options))
+fn stdio_set_raw_mode(lua: &Lua, enabled: bool) -> LuaResult<()> {
+    set_raw_mode(lua, enabled)
+}
+
+async fn stdio_read_key(lua: &Lua, (): ()) -> LuaResult<Option<String>> {
+    let raw = is_raw_mode(lua);
+    lua.spawn_blocking(move || read_key(raw))
         .await
         .into_lua_err()
 }
+
+async fn stdio_prompt(lua: &Lua, mut options: PromptOptions) -> LuaResult<PromptResult> {
+    let key = match options.validator.take() {
+        None => {
+            return lua
+                .spawn_blocking(move || prompt(options, None))
+                .await
+                .into_lua_err()
+        }
+        Some(PromptValidator::Native(native)) => {
+            let validator: InputValidator = Box::new(move |input| native.validate(input));
+            return lua
+                .spawn_blocking(move || prompt(options, Some(validator)))
+                .await
+                .into_lua_err();
+        }
+        Some(PromptValidator::Lua(key)) => key,
+    };
+
+    /*
+        Lua validators can not be called from the thread that the prompt runs on,
+        so input is sent back here to be validated, and the prompt thread waits
+        for the result - other Lua threads may keep running in the meantime
+    */
+    let validator_fn = lua.registry_value::<LuaFunction>(&key)?;
+    lua.remove_registry_value(key)?;
+
+    let (tx, mut rx) = unbounded_channel::<(String, SyncSender<Result<(), String>>)>();
+    let validator: InputValidator = Box::new(move |input| {
+        let (result_tx, result_rx) = sync_channel(1);
+        if tx.send((input.to_string(), result_tx)).is_err() {
+            return Ok(());
+        }
+        result_rx.recv().unwrap_or(Ok(()))
+    });
+    let task = lua.spawn_blocking(move || prompt(options, Some(validator)));
+
+    // NOTE: If the validator errors, the input is accepted to finish
+    // the prompt, and the error is then returned instead of the input
+    let mut validator_error = None;
+    while let Some((input, result_tx)) = rx.recv().await {
+        let result = if validator_error.is_some() {
+            Ok(())
+        } else {
+            match validator_fn.call::<_, LuaMultiValue>(input) {
+                Ok(values) => check_lua_validator_result(&values),
+                Err(e) => {
+                    validator_error = Some(e);
+                    Ok(())
+                }
+            }
+        };
+        result_tx.send(result).ok();
+    }
+
+    let result = task.await.into_lua_err()?;
+    match validator_error {
+        Some(e) => Err(e),
+        None => Ok(result),
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    io::{stderr, Write},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use mlua::prelude::*;
+
+use lune_utils::{
+    output::{set_live_output, with_live_output_suspended},
+    TableBuilder,
+};
+
+const TEMPLATE_BAR: &str = "{bar:30.cyan/blue} {pos}/{len} ({eta}) {msg}";
+const TEMPLATE_BAR_BYTES: &str =
+    "{bar:30.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, {eta}) {msg}";
+const TEMPLATE_SPINNER: &str = "{spinner:.cyan} {msg} ({elapsed})";
+
+const SPINNER_TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+static MULTI: OnceLock<MultiProgress> = OnceLock::new();
+
+/**
+    Gets the shared progress bar container, which draws all bars to stderr
+    together, and hides them while any other output is being written.
+*/
+fn multi() -> &'static MultiProgress {
+    MULTI.get_or_init(|| {
+        let multi = MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+        let suspended = multi.clone();
+        set_live_output(Some(Arc::new(move |f| suspended.suspend(f))));
+        multi
+    })
+}
+
+/**
+    Options for creating a progress bar using `stdio.progress.bar`.
+*/
+#[derive(Debug, Clone)]
+pub struct ProgressBarOptions {
+    total: u64,
+    message: Option<String>,
+    bytes: bool,
+}
+
+impl<'lua> FromLua<'lua> for ProgressBarOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(options) = value else {
+            return Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ProgressBarOptions",
+                message: Some(format!(
+                    "Invalid progress bar options - expected table, got {}",
+                    value.type_name()
+                )),
+            });
+        };
+        let Some(total) = options.get::<_, Option<u64>>("total")? else {
+            return Err(LuaError::FromLuaConversionError {
+                from: "table",
+                to: "ProgressBarOptions",
+                message: Some(String::from(
+                    "Invalid progress bar options - total must be a non-negative integer",
+                )),
+            });
+        };
+        Ok(Self {
+            total,
+            message: options.get("message")?,
+            bytes: options.get::<_, Option<bool>>("bytes")?.unwrap_or(false),
+        })
+    }
+}
+
+/**
+    The state of a progress bar or spinner at the time it was finished.
+*/
+#[derive(Debug, Clone, Copy)]
+struct FinishedState {
+    position: u64,
+    total: Option<u64>,
+    elapsed: Duration,
+}
+
+/**
+    A progress bar or spinner, shown on stderr until it is finished.
+
+    When stderr is not a terminal, nothing is shown while in progress,
+    and only the message given when finishing, if any, is written.
+*/
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    finished: Option<FinishedState>,
+}
+
+impl Progress {
+    fn new(bar: ProgressBar, template: &str, message: Option<String>) -> Self {
+        let style = ProgressStyle::with_template(template).expect("Invalid progress template");
+        bar.set_style(style);
+        if let Some(message) = message {
+            bar.set_message(message);
+        }
+        Self {
+            bar: Some(multi().add(bar)),
+            finished: None,
+        }
+    }
+
+    fn bar(&self) -> LuaResult<&ProgressBar> {
+        self.bar
+            .as_ref()
+            .ok_or_else(|| LuaError::runtime("Progress has already been finished"))
+    }
+
+    /*
+        NOTE: Finished bars are always removed, and any message is written as a
+        regular line above the remaining bars instead - bars that are left as-is
+        would be cleared the next time any other output is written anyway
+    */
+    fn finish(&mut self, message: Option<String>) -> LuaResult<()> {
+        let bar = self
+            .bar
+            .take()
+            .ok_or_else(|| LuaError::runtime("Progress has already been finished"))?;
+        self.finished = Some(FinishedState {
+            position: bar.position(),
+            total: bar.length(),
+            elapsed: bar.elapsed(),
+        });
+        bar.finish_and_clear();
+        let Some(message) = message else {
+            return Ok(());
+        };
+        if bar.is_hidden() {
+            with_live_output_suspended(|| {
+                let mut stderr = stderr();
+                writeln!(stderr, "{message}")?;
+                stderr.flush()
+            })?;
+        } else {
+            multi().println(message)?;
+        }
+        Ok(())
+    }
+}
+
+impl LuaUserData for Progress {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "Progress");
+        fields.add_field_method_get("position", |_, this| {
+            Ok(match (&this.bar, this.finished) {
+                (Some(bar), _) => bar.position(),
+                (None, Some(finished)) => finished.position,
+                (None, None) => 0,
+            })
+        });
+        fields.add_field_method_get("total", |_, this| {
+            Ok(match (&this.bar, this.finished) {
+                (Some(bar), _) => bar.length(),
+                (None, Some(finished)) => finished.total,
+                (None, None) => None,
+            })
+        });
+        fields.add_field_method_get("elapsed", |_, this| {
+            Ok(match (&this.bar, this.finished) {
+                (Some(bar), _) => bar.elapsed(),
+                (None, Some(finished)) => finished.elapsed,
+                (None, None) => Duration::ZERO,
+            }
+            .as_secs_f64())
+        });
+        fields.add_field_method_get("finished", |_, this| Ok(this.bar.is_none()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("set", |_, this, position: u64| {
+            this.bar()?.set_position(position);
+            Ok(())
+        });
+        methods.add_method("increment", |_, this, delta: Option<u64>| {
+            this.bar()?.inc(delta.unwrap_or(1));
+            Ok(())
+        });
+        methods.add_method("setTotal", |_, this, total: u64| {
+            this.bar()?.set_length(total);
+            Ok(())
+        });
+        methods.add_method("setMessage", |_, this, message: String| {
+            this.bar()?.set_message(message);
+            Ok(())
+        });
+        methods.add_method_mut("finish", |_, this, message: Option<String>| {
+            this.finish(message)
+        });
+    }
+}
+
+fn progress_bar(_: &Lua, options: ProgressBarOptions) -> LuaResult<Progress> {
+    let template = if options.bytes {
+        TEMPLATE_BAR_BYTES
+    } else {
+        TEMPLATE_BAR
+    };
+    Ok(Progress::new(
+        ProgressBar::new(options.total),
+        template,
+        options.message,
+    ))
+}
+
+fn progress_spinner(_: &Lua, message: Option<String>) -> LuaResult<Progress> {
+    let progress = Progress::new(ProgressBar::new_spinner(), TEMPLATE_SPINNER, message);
+    progress.bar()?.enable_steady_tick(SPINNER_TICK_INTERVAL);
+    Ok(progress)
+}
+
+/**
+    Creates the `stdio.progress` table.
+*/
+pub fn create_progress_table(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("bar", progress_bar)?
+        .with_function("spinner", progress_spinner)?
+        .build_readonly()
+}
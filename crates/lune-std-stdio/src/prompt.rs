@@ -1,18 +1,27 @@
 use std::{fmt, str::FromStr};
 
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Input, MultiSelect, Select};
 use mlua::prelude::*;
 
+use crate::validator::{InputValidator, PromptValidator};
+
 #[derive(Debug, Clone, Copy)]
 pub enum PromptKind {
     Text,
     Confirm,
     Select,
+    FuzzySelect,
     MultiSelect,
 }
 
 impl PromptKind {
-    const ALL: [PromptKind; 4] = [Self::Text, Self::Confirm, Self::Select, Self::MultiSelect];
+    const ALL: [PromptKind; 5] = [
+        Self::Text,
+        Self::Confirm,
+        Self::Select,
+        Self::FuzzySelect,
+        Self::MultiSelect,
+    ];
 }
 
 impl Default for PromptKind {
@@ -28,6 +37,7 @@ impl FromStr for PromptKind {
             "text" => Ok(Self::Text),
             "confirm" => Ok(Self::Confirm),
             "select" => Ok(Self::Select),
+            "fuzzyselect" => Ok(Self::FuzzySelect),
             "multiselect" => Ok(Self::MultiSelect),
             _ => Err(()),
         }
@@ -43,6 +53,7 @@ impl fmt::Display for PromptKind {
                 Self::Text => "Text",
                 Self::Confirm => "Confirm",
                 Self::Select => "Select",
+                Self::FuzzySelect => "FuzzySelect",
                 Self::MultiSelect => "MultiSelect",
             }
         )
@@ -83,6 +94,7 @@ pub struct PromptOptions {
     pub default_string: Option<String>,
     pub default_bool: Option<bool>,
     pub options: Option<Vec<String>>,
+    pub validator: Option<PromptValidator>,
 }
 
 impl<'lua> FromLuaMulti<'lua> for PromptOptions {
@@ -124,11 +136,17 @@ impl<'lua> FromLuaMulti<'lua> for PromptOptions {
                 }
             },
         };
+        // Argument #4 - validator for text prompts (optional)
+        let validator = values
+            .pop_front()
+            .filter(|value| !value.is_nil())
+            .map(|value| PromptValidator::from_lua(value, lua))
+            .transpose()?;
         /*
             Make sure we got the required values for the specific prompt kind:
 
             - "Confirm" requires a message to be present so the user knows what they are confirming
-            - "Select" and "MultiSelect" both require a table of options to choose from
+            - "Select", "FuzzySelect" and "MultiSelect" all require a table of options to choose from
         */
         if matches!(kind, PromptKind::Confirm) && text.is_none() {
             return Err(LuaError::FromLuaConversionError {
@@ -137,13 +155,24 @@ impl<'lua> FromLuaMulti<'lua> for PromptOptions {
                 message: Some("Argument #2 missing or nil".to_string()),
             });
         }
-        if matches!(kind, PromptKind::Select | PromptKind::MultiSelect) && options.is_none() {
+        if matches!(
+            kind,
+            PromptKind::Select | PromptKind::FuzzySelect | PromptKind::MultiSelect
+        ) && options.is_none()
+        {
             return Err(LuaError::FromLuaConversionError {
                 from: "nil",
                 to: "PromptOptions",
                 message: Some("Argument #3 missing or nil".to_string()),
             });
         }
+        if !matches!(kind, PromptKind::Text) && validator.is_some() {
+            return Err(LuaError::FromLuaConversionError {
+                from: "PromptValidator",
+                to: "PromptOptions",
+                message: Some("Argument #4 is only supported for text prompts".to_string()),
+            });
+        }
         // All good, return the prompt options
         Ok(Self {
             kind,
@@ -151,6 +180,7 @@ impl<'lua> FromLuaMulti<'lua> for PromptOptions {
             default_string,
             default_bool,
             options,
+            validator,
         })
     }
 }
@@ -176,16 +206,21 @@ impl<'lua> IntoLua<'lua> for PromptResult {
     }
 }
 
-pub fn prompt(options: PromptOptions) -> LuaResult<PromptResult> {
+pub fn prompt(
+    options: PromptOptions,
+    validator: Option<InputValidator>,
+) -> LuaResult<PromptResult> {
     let theme = ColorfulTheme::default();
     match options.kind {
         PromptKind::Text => {
-            let input: String = Input::with_theme(&theme)
+            let mut input = Input::with_theme(&theme)
                 .allow_empty(true)
                 .with_prompt(options.text.unwrap_or_default())
-                .with_initial_text(options.default_string.unwrap_or_default())
-                .interact_text()
-                .into_lua_err()?;
+                .with_initial_text(options.default_string.unwrap_or_default());
+            if let Some(mut validator) = validator {
+                input = input.validate_with(move |input: &String| validator(input));
+            }
+            let input: String = input.interact_text().into_lua_err()?;
             Ok(PromptResult::String(input))
         }
         PromptKind::Confirm => {
@@ -210,6 +245,17 @@ pub fn prompt(options: PromptOptions) -> LuaResult<PromptResult> {
                 None => PromptResult::None,
             })
         }
+        PromptKind::FuzzySelect => {
+            let chosen = FuzzySelect::with_theme(&theme)
+                .with_prompt(&options.text.unwrap_or_default())
+                .items(&options.options.expect("Missing options in prompt options"))
+                .interact_opt()
+                .into_lua_err()?;
+            Ok(match chosen {
+                Some(idx) => PromptResult::Index(idx + 1),
+                None => PromptResult::None,
+            })
+        }
         PromptKind::MultiSelect => {
             let chosen = MultiSelect::with_theme(&theme)
                 .with_prompt(&options.text.unwrap_or_default())
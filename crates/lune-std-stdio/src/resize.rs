@@ -0,0 +1,124 @@
+use std::rc::{Rc, Weak};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+use tokio::sync::watch;
+
+use lune_utils::TableBuilder;
+
+use crate::terminal::terminal_size;
+
+#[cfg(unix)]
+mod events {
+    use std::io;
+
+    use tokio::signal::unix::{signal, Signal, SignalKind};
+
+    /**
+        Resize events, using the `SIGWINCH` signal that is sent when the terminal is resized.
+    */
+    pub struct ResizeEvents(Signal);
+
+    impl ResizeEvents {
+        pub fn new() -> io::Result<Self> {
+            signal(SignalKind::window_change()).map(Self)
+        }
+
+        pub async fn next(&mut self) -> Option<()> {
+            self.0.recv().await
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod events {
+    use std::{io, time::Duration};
+
+    use tokio::time::{interval, Interval, MissedTickBehavior};
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    /**
+        Resize events, by checking the size of the terminal regularly, since
+        there is no signal for it outside of unix - the size is compared to
+        the previous one by the caller, so this can fire without any change.
+    */
+    pub struct ResizeEvents(Interval);
+
+    impl ResizeEvents {
+        #[allow(clippy::unnecessary_wraps)]
+        pub fn new() -> io::Result<Self> {
+            let mut interval = interval(POLL_INTERVAL);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            Ok(Self(interval))
+        }
+
+        pub async fn next(&mut self) -> Option<()> {
+            self.0.tick().await;
+            Some(())
+        }
+    }
+}
+
+use events::ResizeEvents;
+
+/**
+    Starts listening for the terminal to be resized, calling the callback in
+    a new Lua thread with the new size every time it changes, until the
+    returned handle is stopped.
+*/
+pub fn on_resize<'lua>(lua: &'lua Lua, callback: LuaFunction<'lua>) -> LuaResult<LuaTable<'lua>> {
+    let mut events = ResizeEvents::new().map_err(|e| {
+        LuaError::RuntimeError(format!("Failed to listen for terminal resizes - {e}"))
+    })?;
+
+    let lua_inner: Rc<Lua> = lua
+        .app_data_ref::<Weak<Lua>>()
+        .expect("Missing weak lua ref")
+        .upgrade()
+        .expect("Lua was dropped unexpectedly");
+    let callback_key = lua.create_registry_value(callback)?;
+
+    let (stop_tx, mut stop_rx) = watch::channel(false);
+    lua.spawn_local(async move {
+        let mut last_size = terminal_size();
+        loop {
+            // NOTE: If the handle was garbage collected without being stopped, the
+            // sender is dropped, and the callback keeps getting called on every
+            // resize until the script exits, since it can no longer be stopped
+            let stopped = async {
+                if stop_rx.wait_for(|stopped| *stopped).await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            };
+            tokio::select! {
+                biased;
+                () = stopped => break,
+                event = events.next() => if event.is_none() {
+                    break;
+                },
+            }
+            // NOTE: Terminals may send more than one event for a single resize,
+            // and stdout may not be a terminal at all, so only changes are sent
+            let size = terminal_size();
+            if size == last_size {
+                continue;
+            }
+            last_size = size;
+            if let Some(size) = size {
+                let callback = lua_inner
+                    .registry_value::<LuaFunction>(&callback_key)
+                    .expect("Missing resize callback");
+                lua_inner.push_thread_back(callback, size).ok();
+            }
+        }
+        lua_inner.remove_registry_value(callback_key).ok();
+    });
+
+    TableBuilder::new(lua)?
+        .with_function("stop", move |_, (): ()| {
+            stop_tx.send_replace(true);
+            Ok(())
+        })?
+        .build_readonly()
+}
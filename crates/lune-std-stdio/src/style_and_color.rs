@@ -1,11 +1,82 @@
-use std::str::FromStr;
+use std::{env, str::FromStr};
 
 use mlua::prelude::*;
 
 const ESCAPE_SEQ_RESET: &str = "\x1b[0m";
 
+/**
+    The level of color support of the terminal that output is written to.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorLevel {
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorLevel {
+    /**
+        Detects the level of color support from the environment.
+
+        This is checked every time, instead of only once, so that
+        changing `COLORTERM` or `TERM` takes effect immediately.
+    */
+    pub fn detect() -> Self {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        Self::detect_from(
+            &var("COLORTERM"),
+            &var("TERM"),
+            env::var_os("WT_SESSION").is_some(),
+        )
+    }
+
+    /**
+        Detects the level of color support from the given values of the
+        `COLORTERM` and `TERM` variables, and if running in Windows Terminal.
+    */
+    fn detect_from(colorterm: &str, term: &str, windows_terminal: bool) -> Self {
+        let colorterm = colorterm.to_ascii_lowercase();
+        let term = term.to_ascii_lowercase();
+        if colorterm == "truecolor"
+            || colorterm == "24bit"
+            || term.ends_with("-direct")
+            || windows_terminal
+        {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Basic
+        }
+    }
+
+    /**
+        Returns the name of this color level, as given by `stdio.colorLevel`.
+    */
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Basic => "basic",
+            Self::Ansi256 => "256",
+            Self::TrueColor => "truecolor",
+        }
+    }
+}
+
+/**
+    Where a color is applied - to the text itself, or behind it.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorLayer {
+    Foreground,
+    Background,
+}
+
 /**
     A color kind supported by the `stdio` standard library.
+
+    In addition to the basic named colors, colors may be given as an index into
+    the 256-color palette, or as 24-bit RGB, both of which are downgraded to the
+    closest supported color when the terminal does not support them.
 */
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ColorKind {
@@ -18,10 +89,12 @@ pub enum ColorKind {
     Magenta,
     Cyan,
     White,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
 }
 
 impl ColorKind {
-    pub const ALL: [Self; 9] = [
+    pub const NAMED: [Self; 9] = [
         Self::Reset,
         Self::Black,
         Self::Red,
@@ -34,10 +107,10 @@ impl ColorKind {
     ];
 
     /**
-        Returns the human-friendly name of this color kind.
+        Returns the human-friendly name of this color kind, if it is a named color.
     */
-    pub fn name(self) -> &'static str {
-        match self {
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self {
             Self::Reset => "reset",
             Self::Black => "black",
             Self::Red => "red",
@@ -47,23 +120,54 @@ impl ColorKind {
             Self::Magenta => "magenta",
             Self::Cyan => "cyan",
             Self::White => "white",
-        }
+            Self::Indexed(_) | Self::Rgb(..) => return None,
+        })
     }
 
     /**
-        Returns the ANSI escape sequence for the color kind.
+        Returns the ANSI escape sequence for the color kind, as the closest
+        color that is supported by a terminal with the given color level.
     */
-    pub fn ansi_escape_sequence(self) -> &'static str {
-        match self {
-            Self::Reset => ESCAPE_SEQ_RESET,
-            Self::Black => "\x1b[30m",
-            Self::Red => "\x1b[31m",
-            Self::Green => "\x1b[32m",
-            Self::Yellow => "\x1b[33m",
-            Self::Blue => "\x1b[34m",
-            Self::Magenta => "\x1b[35m",
-            Self::Cyan => "\x1b[36m",
-            Self::White => "\x1b[37m",
+    pub fn ansi_escape_sequence(self, level: ColorLevel, layer: ColorLayer) -> String {
+        let (extended, offset) = match layer {
+            ColorLayer::Foreground => (38, 0),
+            ColorLayer::Background => (48, 10),
+        };
+        let basic = match self {
+            Self::Reset => {
+                return String::from(match layer {
+                    ColorLayer::Foreground => ESCAPE_SEQ_RESET,
+                    ColorLayer::Background => "\x1b[49m",
+                })
+            }
+            Self::Black => 0,
+            Self::Red => 1,
+            Self::Green => 2,
+            Self::Yellow => 3,
+            Self::Blue => 4,
+            Self::Magenta => 5,
+            Self::Cyan => 6,
+            Self::White => 7,
+            Self::Indexed(index) if level >= ColorLevel::Ansi256 => {
+                return format!("\x1b[{extended};5;{index}m");
+            }
+            Self::Indexed(index) if index < 16 => index,
+            Self::Indexed(index) => {
+                let (r, g, b) = ansi256_to_rgb(index);
+                rgb_to_basic(r, g, b)
+            }
+            Self::Rgb(r, g, b) => match level {
+                ColorLevel::TrueColor => return format!("\x1b[{extended};2;{r};{g};{b}m"),
+                ColorLevel::Ansi256 => {
+                    return format!("\x1b[{extended};5;{}m", rgb_to_ansi256(r, g, b));
+                }
+                ColorLevel::Basic => rgb_to_basic(r, g, b),
+            },
+        };
+        if basic < 8 {
+            format!("\x1b[{}m", 30 + offset + basic)
+        } else {
+            format!("\x1b[{}m", 90 + offset + basic - 8)
         }
     }
 }
@@ -71,7 +175,11 @@ impl ColorKind {
 impl FromStr for ColorKind {
     type Err = ();
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s.trim().to_ascii_lowercase().as_str() {
+        let s = s.trim().to_ascii_lowercase();
+        if let Some(hex) = s.strip_prefix('#') {
+            return parse_hex_color(hex).ok_or(());
+        }
+        Ok(match s.as_str() {
             "reset" => Self::Reset,
             "black" => Self::Black,
             "red" => Self::Red,
@@ -90,33 +198,148 @@ impl FromStr for ColorKind {
 
 impl FromLua<'_> for ColorKind {
     fn from_lua(value: LuaValue, _: &Lua) -> LuaResult<Self> {
-        if let LuaValue::String(s) = value {
-            let s = s.to_str()?;
-            match s.parse() {
-                Ok(color) => Ok(color),
-                Err(()) => Err(LuaError::FromLuaConversionError {
-                    from: "string",
-                    to: "ColorKind",
-                    message: Some(format!(
-                        "Invalid color kind '{s}'\nValid kinds are: {}",
-                        Self::ALL
-                            .iter()
-                            .map(|kind| kind.name())
-                            .collect::<Vec<_>>()
-                            .join(", ")
-                    )),
-                }),
+        match value {
+            LuaValue::String(s) => {
+                let s = s.to_str()?;
+                match s.parse() {
+                    Ok(color) => Ok(color),
+                    Err(()) => Err(LuaError::FromLuaConversionError {
+                        from: "string",
+                        to: "ColorKind",
+                        message: Some(format!(
+                            "Invalid color kind '{s}'\nValid kinds are: {}, \
+                            a hex color such as '#ff8800', or a number from 0 to 255",
+                            Self::NAMED
+                                .iter()
+                                .filter_map(|kind| kind.name())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )),
+                    }),
+                }
             }
-        } else {
-            Err(LuaError::FromLuaConversionError {
+            LuaValue::Integer(i) => u8::try_from(i)
+                .map(Self::Indexed)
+                .map_err(|_| invalid_color_index(value.type_name(), &i.to_string())),
+            LuaValue::Number(n) => {
+                if n.fract() == 0.0 && (0.0..=255.0).contains(&n) {
+                    Ok(Self::Indexed(n as u8))
+                } else {
+                    Err(invalid_color_index(value.type_name(), &n.to_string()))
+                }
+            }
+            _ => Err(LuaError::FromLuaConversionError {
                 from: value.type_name(),
                 to: "ColorKind",
                 message: None,
-            })
+            }),
+        }
+    }
+}
+
+fn invalid_color_index(from: &'static str, index: &str) -> LuaError {
+    LuaError::FromLuaConversionError {
+        from,
+        to: "ColorKind",
+        message: Some(format!(
+            "Invalid color index '{index}'\nColor indices must be integers from 0 to 255"
+        )),
+    }
+}
+
+fn parse_hex_color(hex: &str) -> Option<ColorKind> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match hex.len() {
+        // Short hex colors such as '#f80' repeat each digit, as in CSS
+        3 => {
+            let digit = |i: usize| channel(&hex[i..=i].repeat(2));
+            Some(ColorKind::Rgb(digit(0)?, digit(1)?, digit(2)?))
+        }
+        6 => Some(ColorKind::Rgb(
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        _ => None,
+    }
+}
+
+// The colors that most terminals use for the 16 basic colors, based on xterm
+const BASIC_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// The levels of each channel in the 6x6x6 color cube of the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn ansi256_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC_PALETTE[index as usize],
+        16..=231 => {
+            let index = index - 16;
+            (
+                CUBE_LEVELS[(index / 36) as usize],
+                CUBE_LEVELS[(index / 6 % 6) as usize],
+                CUBE_LEVELS[(index % 6) as usize],
+            )
+        }
+        232..=255 => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
         }
     }
 }
 
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // Grays are matched against the grayscale ramp, which has more
+    // levels than the color cube, and pure black and white use the cube
+    if r == g && g == b {
+        return match r {
+            0..=7 => 16,
+            249..=255 => 231,
+            _ => 232 + ((r - 8 + 5) / 10).min(23),
+        };
+    }
+    let level = |c: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| level.abs_diff(c))
+            .map_or(0, |(i, _)| i as u8)
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn rgb_to_basic(r: u8, g: u8, b: u8) -> u8 {
+    let distance = |(pr, pg, pb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    BASIC_PALETTE
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, color)| distance(**color))
+        .map_or(0, |(i, _)| i as u8)
+}
+
 /**
     A style kind supported by the `stdio` standard library.
 */
@@ -193,3 +416,122 @@ impl FromLua<'_> for StyleKind {
         }
     }
 }
+
+/**
+    Options for styling text using `stdio.styled`.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StyledOptions {
+    pub color: Option<ColorKind>,
+    pub background: Option<ColorKind>,
+    pub bold: bool,
+    pub dim: bool,
+}
+
+impl StyledOptions {
+    /**
+        Styles the given text, so that it can be nested inside of other styled text.
+
+        Nested styled text resets all styles when it ends, so the styles of this
+        scope are applied again after every reset sequence inside of the text.
+    */
+    pub fn apply(&self, text: &str, level: ColorLevel) -> String {
+        let mut prefix = String::new();
+        if self.bold {
+            prefix.push_str(StyleKind::Bold.ansi_escape_sequence());
+        }
+        if self.dim {
+            prefix.push_str(StyleKind::Dim.ansi_escape_sequence());
+        }
+        if let Some(color) = self.color {
+            prefix.push_str(&color.ansi_escape_sequence(level, ColorLayer::Foreground));
+        }
+        if let Some(color) = self.background {
+            prefix.push_str(&color.ansi_escape_sequence(level, ColorLayer::Background));
+        }
+        if prefix.is_empty() {
+            return text.to_string();
+        }
+        let inner = text.replace(ESCAPE_SEQ_RESET, &format!("{ESCAPE_SEQ_RESET}{prefix}"));
+        format!("{prefix}{inner}{ESCAPE_SEQ_RESET}")
+    }
+}
+
+impl FromLua<'_> for StyledOptions {
+    fn from_lua(value: LuaValue, lua: &Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            // A single color may be given as a shorthand for only setting the color
+            LuaValue::String(_) | LuaValue::Integer(_) | LuaValue::Number(_) => Ok(Self {
+                color: Some(ColorKind::from_lua(value, lua)?),
+                ..Self::default()
+            }),
+            LuaValue::Table(options) => Ok(Self {
+                color: options.get("color")?,
+                background: options.get("background")?,
+                bold: options.get::<_, Option<bool>>("bold")?.unwrap_or_default(),
+                dim: options.get::<_, Option<bool>>("dim")?.unwrap_or_default(),
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "StyledOptions",
+                message: Some(format!(
+                    "Invalid style options - expected table or color, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(s: &str) -> ColorKind {
+        s.parse().expect("valid color")
+    }
+
+    fn foreground(color: ColorKind, level: ColorLevel) -> String {
+        color.ansi_escape_sequence(level, ColorLayer::Foreground)
+    }
+
+    #[test]
+    fn detects_color_level() {
+        let detect = |colorterm, term| ColorLevel::detect_from(colorterm, term, false);
+        assert_eq!(detect("truecolor", ""), ColorLevel::TrueColor);
+        assert_eq!(detect("24BIT", "xterm"), ColorLevel::TrueColor);
+        assert_eq!(detect("", "xterm-direct"), ColorLevel::TrueColor);
+        assert_eq!(detect("", "xterm-256color"), ColorLevel::Ansi256);
+        assert_eq!(detect("", "xterm"), ColorLevel::Basic);
+        assert_eq!(detect("", ""), ColorLevel::Basic);
+        assert_eq!(ColorLevel::detect_from("", "", true), ColorLevel::TrueColor);
+    }
+
+    #[test]
+    fn truecolor() {
+        let level = ColorLevel::TrueColor;
+        assert_eq!(foreground(color("#ff8800"), level), "\x1b[38;2;255;136;0m");
+        assert_eq!(foreground(color("#F80"), level), "\x1b[38;2;255;136;0m");
+        assert_eq!(foreground(ColorKind::Indexed(208), level), "\x1b[38;5;208m");
+        assert_eq!(foreground(color("red"), level), "\x1b[31m");
+    }
+
+    #[test]
+    fn downgrades_to_256_colors() {
+        let level = ColorLevel::Ansi256;
+        assert_eq!(foreground(color("#ff8800"), level), "\x1b[38;5;208m");
+        assert_eq!(foreground(color("#808080"), level), "\x1b[38;5;244m");
+        assert_eq!(foreground(ColorKind::Indexed(208), level), "\x1b[38;5;208m");
+    }
+
+    #[test]
+    fn downgrades_to_basic_colors() {
+        let level = ColorLevel::Basic;
+        assert_eq!(foreground(color("#ff0000"), level), "\x1b[91m");
+        assert_eq!(foreground(color("#000000"), level), "\x1b[30m");
+        assert_eq!(foreground(ColorKind::Indexed(1), level), "\x1b[31m");
+        assert_eq!(foreground(ColorKind::Indexed(9), level), "\x1b[91m");
+        assert_eq!(foreground(ColorKind::Indexed(196), level), "\x1b[91m");
+    }
+}
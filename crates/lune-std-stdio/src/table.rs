@@ -0,0 +1,269 @@
+use std::collections::BTreeSet;
+
+use console::{measure_text_width, pad_str, truncate_str, Alignment};
+use mlua::prelude::*;
+
+use lune_utils::fmt::{pretty_format_value, ValueFormatConfig};
+
+const FORMAT_CONFIG: ValueFormatConfig = ValueFormatConfig::new()
+    .with_max_depth(1)
+    .with_colors_enabled(false);
+
+const TRUNCATION_TAIL: &str = "…";
+
+// Characters used for borders, in order: horizontal, vertical,
+// and then the left, middle, and right junctions for each line
+const HORIZONTAL: &str = "─";
+const VERTICAL: &str = "│";
+const TOP: [&str; 3] = ["┌", "┬", "┐"];
+const MIDDLE: [&str; 3] = ["├", "┼", "┤"];
+const BOTTOM: [&str; 3] = ["└", "┴", "┘"];
+
+// Spacing between columns when there are no borders
+const COLUMN_GAP: &str = "  ";
+
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    columns: Option<Vec<String>>,
+    max_width: Option<usize>,
+    borders: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            columns: None,
+            max_width: None,
+            borders: true,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for TableOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Nil = value {
+            // Nil means default options
+            Ok(Self::default())
+        } else if let LuaValue::Table(tab) = value {
+            let columns = match tab.get::<_, Option<Vec<String>>>("columns") {
+                Ok(columns) => Ok(columns),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'columns' in table options - expected a list of strings"
+                        .to_string(),
+                )),
+            }?;
+            let max_width = match tab.get::<_, Option<usize>>("maxWidth") {
+                Ok(Some(0)) | Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'maxWidth' in table options - expected a positive integer"
+                        .to_string(),
+                )),
+                Ok(max_width) => Ok(max_width),
+            }?;
+            let borders = match tab.get::<_, Option<bool>>("borders") {
+                Ok(borders) => Ok(borders.unwrap_or(true)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'borders' in table options".to_string(),
+                )),
+            }?;
+            Ok(Self {
+                columns,
+                max_width,
+                borders,
+            })
+        } else {
+            // Anything else is invalid
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "TableOptions",
+                message: Some(format!(
+                    "Invalid table options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
+
+/**
+    Renders the given rows as an aligned table.
+
+    Rows may either be lists of cells, or dictionaries keyed by column name.
+    Widths are measured ignoring any ANSI escape sequences, so cells may be
+    styled, and cells containing newlines are rendered over several lines.
+*/
+pub fn render_table(lua: &Lua, rows: &LuaTable, options: TableOptions) -> LuaResult<String> {
+    let rows = rows
+        .clone()
+        .sequence_values::<LuaValue>()
+        .enumerate()
+        .map(|(index, row)| match row? {
+            LuaValue::Table(row) => Ok(row),
+            value => Err(LuaError::RuntimeError(format!(
+                "Invalid row at index {} in table - expected a table, got {}",
+                index + 1,
+                value.type_name()
+            ))),
+        })
+        .collect::<LuaResult<Vec<_>>>()?;
+
+    // Dictionary rows need column names to know the order to
+    // render in, if none were given we use all keys, sorted
+    let columns = if let Some(columns) = options.columns {
+        Some(columns)
+    } else {
+        let mut keys = BTreeSet::new();
+        for row in &rows {
+            if row.raw_len() == 0 {
+                for pair in row.clone().pairs::<LuaValue, LuaValue>() {
+                    let (key, _) = pair?;
+                    keys.insert(format_cell(lua, &key));
+                }
+            }
+        }
+        (!keys.is_empty()).then(|| keys.into_iter().collect())
+    };
+
+    let mut cells: Vec<Vec<String>> = Vec::new();
+    for row in &rows {
+        let row_cells = if row.raw_len() > 0 {
+            (1..=row.raw_len())
+                .map(|index| row.raw_get(index).map(|value| format_cell(lua, &value)))
+                .collect::<LuaResult<Vec<_>>>()?
+        } else {
+            columns
+                .iter()
+                .flatten()
+                .map(|column| {
+                    row.raw_get(column.as_str())
+                        .map(|value| format_cell(lua, &value))
+                })
+                .collect::<LuaResult<Vec<_>>>()?
+        };
+        cells.push(row_cells);
+    }
+
+    let column_count = cells
+        .iter()
+        .map(Vec::len)
+        .chain(columns.as_ref().map(Vec::len))
+        .max()
+        .unwrap_or_default();
+    if column_count == 0 {
+        return Ok(String::new());
+    }
+
+    let mut widths = vec![0; column_count];
+    for row in columns.iter().chain(&cells) {
+        for (index, cell) in row.iter().enumerate() {
+            for line in cell.lines() {
+                widths[index] = widths[index].max(measure_text_width(line));
+            }
+        }
+    }
+
+    if let Some(max_width) = options.max_width {
+        let overhead = if options.borders {
+            3 * column_count + 1
+        } else {
+            COLUMN_GAP.len() * (column_count - 1)
+        };
+        shrink_widths(&mut widths, max_width.saturating_sub(overhead));
+    }
+
+    let mut lines = Vec::new();
+    if options.borders {
+        lines.push(border_line(&widths, TOP));
+    }
+    if let Some(columns) = &columns {
+        render_row(&mut lines, columns, &widths, options.borders);
+        if options.borders {
+            lines.push(border_line(&widths, MIDDLE));
+        } else {
+            let underlines = widths.iter().map(|w| HORIZONTAL.repeat(*w));
+            lines.push(underlines.collect::<Vec<_>>().join(COLUMN_GAP));
+        }
+    }
+    for row in &cells {
+        render_row(&mut lines, row, &widths, options.borders);
+    }
+    if options.borders {
+        lines.push(border_line(&widths, BOTTOM));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn format_cell(lua: &Lua, value: &LuaValue) -> String {
+    match value {
+        LuaValue::Nil => String::new(),
+        LuaValue::String(s) => s.to_string_lossy().replace("\r\n", "\n"),
+        value => pretty_format_value(lua, value, &FORMAT_CONFIG),
+    }
+}
+
+// Repeatedly shrinks the widest column until all of the columns fit, but
+// never shrinks any column below a single character, since it would vanish
+fn shrink_widths(widths: &mut [usize], available: usize) {
+    let mut total: usize = widths.iter().sum();
+    while total > available {
+        let (widest, width) = widths
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by_key(|(_, width)| *width)
+            .expect("Table has at least one column");
+        if width <= 1 {
+            break;
+        }
+        widths[widest] -= 1;
+        total -= 1;
+    }
+}
+
+fn render_row(lines: &mut Vec<String>, row: &[String], widths: &[usize], borders: bool) {
+    let cell_lines = widths
+        .iter()
+        .enumerate()
+        .map(|(index, width)| {
+            let cell = row.get(index).map_or("", String::as_str);
+            cell.split('\n')
+                .map(|line| {
+                    // NOTE: Truncating keeps any trailing ANSI escape sequences, so
+                    // that styles that were reset at the end of the cell stay reset
+                    if measure_text_width(line) > *width {
+                        let line = truncate_str(line, *width, TRUNCATION_TAIL);
+                        pad_str(&line, *width, Alignment::Left, None).into_owned()
+                    } else {
+                        pad_str(line, *width, Alignment::Left, None).into_owned()
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let height = cell_lines.iter().map(Vec::len).max().unwrap_or(1);
+    for line_index in 0..height {
+        let parts = cell_lines.iter().zip(widths).map(|(lines, width)| {
+            lines
+                .get(line_index)
+                .cloned()
+                .unwrap_or_else(|| " ".repeat(*width))
+        });
+        let line = if borders {
+            let inner = parts.collect::<Vec<_>>().join(&format!(" {VERTICAL} "));
+            format!("{VERTICAL} {inner} {VERTICAL}")
+        } else {
+            let joined = parts.collect::<Vec<_>>().join(COLUMN_GAP);
+            joined.trim_end().to_string()
+        };
+        lines.push(line);
+    }
+}
+
+fn border_line(widths: &[usize], [left, middle, right]: [&str; 3]) -> String {
+    let segments = widths
+        .iter()
+        .map(|width| HORIZONTAL.repeat(width + 2))
+        .collect::<Vec<_>>();
+    format!("{left}{}{right}", segments.join(middle))
+}
@@ -0,0 +1,114 @@
+use std::env::var;
+
+use console::{colors_enabled, Term};
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+const OSC: &str = "\x1b]";
+const ST: &str = "\x1b\\";
+const BEL: &str = "\x07";
+
+/**
+    Checks if hyperlinks are supported by the terminal that stdout is connected to.
+
+    Terminals that do not support hyperlinks will usually print the escape sequences as-is,
+    so this is conservative and only returns `true` for terminals that are known to support them.
+    The `FORCE_HYPERLINK` environment variable may be set to `1` or `0` to override detection.
+*/
+fn hyperlinks_supported() -> bool {
+    if let Ok(force) = var("FORCE_HYPERLINK") {
+        return !force.is_empty() && force != "0";
+    }
+
+    if !Term::stdout().is_term() || !colors_enabled() {
+        return false;
+    }
+
+    let env_is = |name: &str, values: &[&str]| {
+        var(name).is_ok_and(|value| values.iter().any(|v| value.eq_ignore_ascii_case(v)))
+    };
+
+    // NOTE: VTE based terminals (GNOME Terminal, Tilix, ...)
+    // support hyperlinks starting from version 0.50
+    let vte_supported = var("VTE_VERSION")
+        .ok()
+        .and_then(|version| version.parse::<u32>().ok())
+        .is_some_and(|version| version >= 5000);
+
+    vte_supported
+        || var("WT_SESSION").is_ok()
+        || var("KONSOLE_VERSION").is_ok()
+        || var("DOMTERM").is_ok()
+        || env_is(
+            "TERM_PROGRAM",
+            &["iTerm.app", "WezTerm", "vscode", "Hyper", "ghostty"],
+        )
+        || env_is("TERM", &["xterm-kitty", "alacritty", "xterm-ghostty"])
+}
+
+// Control characters would end the escape sequence early, or start new
+// ones, so they are stripped from anything embedded into a sequence
+fn strip_control(s: &str) -> String {
+    s.chars().filter(|c| !c.is_control()).collect()
+}
+
+/**
+    Creates a hyperlink with the given text, pointing to the given url.
+
+    Falls back to the text followed by the url in parentheses if
+    hyperlinks are not supported, or just the url if both are the same.
+*/
+pub fn create_link(text: &str, url: &str) -> String {
+    if hyperlinks_supported() {
+        let url = strip_control(url);
+        format!("{OSC}8;;{url}{ST}{text}{OSC}8;;{ST}")
+    } else if text.is_empty() || text == url {
+        url.to_string()
+    } else {
+        format!("{text} ({url})")
+    }
+}
+
+/**
+    Creates an escape sequence that sets the title of the terminal.
+
+    Returns `None` if stdout is not connected to a terminal.
+*/
+pub fn create_title_sequence(title: &str) -> Option<String> {
+    if Term::stdout().is_term() {
+        let title = strip_control(title);
+        Some(format!("{OSC}0;{title}{BEL}"))
+    } else {
+        None
+    }
+}
+
+/**
+    The size of a terminal, in columns and rows of characters.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalSize {
+    pub columns: u16,
+    pub rows: u16,
+}
+
+impl<'lua> IntoLua<'lua> for TerminalSize {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("columns", self.columns)?
+            .with_value("rows", self.rows)?
+            .build_readonly()?
+            .into_lua(lua)
+    }
+}
+
+/**
+    Gets the size of the terminal that stdout is connected to.
+
+    Returns `None` if stdout is not connected to a terminal.
+*/
+pub fn terminal_size() -> Option<TerminalSize> {
+    let (rows, columns) = Term::stdout().size_checked()?;
+    Some(TerminalSize { columns, rows })
+}
@@ -0,0 +1,198 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use mlua::prelude::*;
+use regex::Regex;
+use url::Url;
+
+/**
+    A function that validates input for a text prompt, returning a message to show if invalid.
+*/
+pub type InputValidator = Box<dyn FnMut(&str) -> Result<(), String> + Send>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ValidatorKind {
+    Number,
+    Integer,
+    Path,
+    File,
+    Directory,
+    Url,
+}
+
+impl ValidatorKind {
+    const ALL: [ValidatorKind; 6] = [
+        Self::Number,
+        Self::Integer,
+        Self::Path,
+        Self::File,
+        Self::Directory,
+        Self::Url,
+    ];
+}
+
+impl FromStr for ValidatorKind {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "number" => Ok(Self::Number),
+            "integer" => Ok(Self::Integer),
+            "path" => Ok(Self::Path),
+            "file" => Ok(Self::File),
+            "directory" => Ok(Self::Directory),
+            "url" => Ok(Self::Url),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for ValidatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Number => "number",
+                Self::Integer => "integer",
+                Self::Path => "path",
+                Self::File => "file",
+                Self::Directory => "directory",
+                Self::Url => "url",
+            }
+        )
+    }
+}
+
+/**
+    A validator that does not need Lua, and may run on any thread.
+*/
+#[derive(Debug, Clone)]
+pub enum NativeValidator {
+    Builtin(ValidatorKind),
+    Pattern {
+        regex: Regex,
+        message: Option<String>,
+    },
+}
+
+impl NativeValidator {
+    pub fn validate(&self, input: &str) -> Result<(), String> {
+        let valid = match self {
+            Self::Builtin(kind) => {
+                let trimmed = input.trim();
+                match kind {
+                    ValidatorKind::Number => trimmed.parse::<f64>().is_ok_and(f64::is_finite),
+                    ValidatorKind::Integer => trimmed.parse::<i64>().is_ok(),
+                    ValidatorKind::Path => !input.is_empty() && Path::new(input).exists(),
+                    ValidatorKind::File => !input.is_empty() && Path::new(input).is_file(),
+                    ValidatorKind::Directory => !input.is_empty() && Path::new(input).is_dir(),
+                    ValidatorKind::Url => Url::parse(trimmed).is_ok_and(|url| url.has_host()),
+                }
+            }
+            Self::Pattern { regex, .. } => regex.is_match(input),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(self.message())
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::Builtin(kind) => match kind {
+                ValidatorKind::Number => "Please enter a number",
+                ValidatorKind::Integer => "Please enter a whole number",
+                ValidatorKind::Path => "No file or directory exists at this path",
+                ValidatorKind::File => "No file exists at this path",
+                ValidatorKind::Directory => "No directory exists at this path",
+                ValidatorKind::Url => "Please enter a valid URL, such as https://example.com",
+            }
+            .to_string(),
+            Self::Pattern { regex, message } => message
+                .clone()
+                .unwrap_or_else(|| format!("Input must match the pattern {}", regex.as_str())),
+        }
+    }
+}
+
+/**
+    A validator for text prompts, given as argument #4 to `stdio.prompt`.
+
+    Lua validators are stored in the registry, since prompts
+    run on a separate thread and can not hold onto Lua values.
+*/
+pub enum PromptValidator {
+    Native(NativeValidator),
+    Lua(LuaRegistryKey),
+}
+
+impl<'lua> FromLua<'lua> for PromptValidator {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => {
+                let s = s.to_str()?;
+                let kind = s.parse().map_err(|()| LuaError::FromLuaConversionError {
+                    from: "string",
+                    to: "PromptValidator",
+                    message: Some(format!(
+                        "Invalid prompt validator '{s}', valid validators are:\n{}",
+                        ValidatorKind::ALL
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                })?;
+                Ok(Self::Native(NativeValidator::Builtin(kind)))
+            }
+            LuaValue::Table(tab) => {
+                let pattern = match tab.get::<_, Option<String>>("pattern") {
+                    Ok(Some(pattern)) => Ok(pattern),
+                    Ok(None) | Err(_) => Err(LuaError::RuntimeError(
+                        "Invalid option value for 'pattern' in prompt validator - expected a string"
+                            .to_string(),
+                    )),
+                }?;
+                let regex = Regex::new(&pattern).map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid option value for 'pattern' in prompt validator - {e}"
+                    ))
+                })?;
+                let message = match tab.get::<_, Option<String>>("message") {
+                    Ok(message) => Ok(message),
+                    Err(_) => Err(LuaError::RuntimeError(
+                        "Invalid option value for 'message' in prompt validator - expected a string"
+                            .to_string(),
+                    )),
+                }?;
+                Ok(Self::Native(NativeValidator::Pattern { regex, message }))
+            }
+            LuaValue::Function(f) => Ok(Self::Lua(lua.create_registry_value(f)?)),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "PromptValidator",
+                message: Some(format!(
+                    "Invalid prompt validator - expected string, table, or function, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Checks the values returned by a Lua validator function.
+
+    Validators return `true` for valid input, or `false`
+    and an optional message to show for invalid input.
+*/
+pub fn check_lua_validator_result(values: &LuaMultiValue) -> Result<(), String> {
+    let mut values = values.iter();
+    match values.next() {
+        Some(LuaValue::Nil | LuaValue::Boolean(false)) | None => Err(match values.next() {
+            Some(LuaValue::String(message)) => message.to_string_lossy().to_string(),
+            _ => "Invalid input".to_string(),
+        }),
+        Some(_) => Ok(()),
+    }
+}
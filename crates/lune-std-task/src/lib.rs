@@ -3,12 +3,34 @@
 use std::time::Duration;
 
 use mlua::prelude::*;
-use mlua_luau_scheduler::Functions;
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, QueueStats, TaskKind};
 
-use tokio::time::{sleep, Instant};
+use tokio::{
+    task::yield_now,
+    time::{sleep, Instant},
+};
 
 use lune_utils::TableBuilder;
 
+// Waits shorter than this are finished by spinning instead of sleeping
+// when in low latency mode, since OS timers are not precise enough
+const LOW_LATENCY_SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+struct LowLatencyMode(bool);
+
+/**
+    Enables or disables low latency mode for `task.wait`.
+
+    When enabled, waits will sleep for as long as the OS timer can be trusted,
+    and then spin, yielding to other tasks, for the last couple of milliseconds.
+    This makes short waits such as `task.wait(0.001)` accurate, at the cost of higher CPU usage.
+
+    Low latency mode is disabled by default.
+*/
+pub fn set_low_latency_mode(lua: &Lua, enabled: bool) {
+    lua.set_app_data(LowLatencyMode(enabled));
+}
+
 /**
     Creates the `task` standard library module.
 
@@ -33,15 +55,48 @@ pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
         .set_environment(task_delay_env)
         .into_function()?;
 
+    // Create wait all functions, with and without settled results
+    let task_wait_all = create_wait_all(lua, &fns, false)?;
+    let task_wait_all_settled = create_wait_all(lua, &fns, true)?;
+
     TableBuilder::new(lua)?
         .with_value("cancel", fns.cancel)?
         .with_value("defer", fns.defer)?
         .with_value("delay", task_delay)?
+        .with_value("onIdle", fns.idle)?
         .with_value("spawn", fns.spawn)?
+        .with_function("stats", stats)?
         .with_value("wait", task_wait)?
+        .with_value("waitAll", task_wait_all)?
+        .with_value("waitAllSettled", task_wait_all_settled)?
         .build_readonly()
 }
 
+fn create_wait_all<'lua>(
+    lua: &'lua Lua,
+    fns: &Functions<'lua>,
+    settled: bool,
+) -> LuaResult<LuaFunction<'lua>> {
+    let coroutine = lua.globals().get::<_, LuaTable>("coroutine")?;
+    let env = TableBuilder::new(lua)?
+        .with_value("settled", settled)?
+        .with_value("error", lua.globals().get::<_, LuaFunction>("error")?)?
+        .with_value("pcall", lua.globals().get::<_, LuaFunction>("pcall")?)?
+        .with_value("typeof", lua.globals().get::<_, LuaFunction>("typeof")?)?
+        .with_value("running", coroutine.get::<_, LuaFunction>("running")?)?
+        .with_value("yield", coroutine.get::<_, LuaFunction>("yield")?)?
+        .with_value("spawn", fns.spawn.clone())?
+        .build_readonly()?;
+    lua.load(WAIT_ALL_IMPL_LUA)
+        .set_name(if settled {
+            "task.waitAllSettled"
+        } else {
+            "task.waitAll"
+        })
+        .set_environment(env)
+        .into_function()
+}
+
 const DELAY_IMPL_LUA: &str = r"
 return defer(function(...)
     wait(select(1, ...))
@@ -49,12 +104,106 @@ return defer(function(...)
 end, ...)
 ";
 
-async fn wait(_: &Lua, secs: Option<f64>) -> LuaResult<f64> {
+// Spawns all of the given functions, and yields until every one of them has either
+// completed or errored - the waiting thread is resumed by whichever task finishes last
+const WAIT_ALL_IMPL_LUA: &str = r#"
+local tasks = ...
+if typeof(tasks) ~= "table" then
+    error(`Expected a table of functions, got {typeof(tasks)}`, 2)
+end
+for index, fn in tasks do
+    if typeof(fn) ~= "function" then
+        error(`Expected a function at index {index}, got {typeof(fn)}`, 2)
+    end
+end
+
+local count = #tasks
+local remaining = count
+local results = {}
+local waiter = nil
+
+local function settle(index, success, ...)
+    if success then
+        results[index] = { success = true, values = { ... } }
+    else
+        results[index] = { success = false, error = ... }
+    end
+    remaining -= 1
+    if remaining == 0 and waiter ~= nil then
+        spawn(waiter)
+    end
+end
+
+for index = 1, count do
+    spawn(function()
+        settle(index, pcall(tasks[index]))
+    end)
+end
+
+if remaining > 0 then
+    waiter = running()
+    yield()
+end
+
+if settled then
+    return results
+end
+
+local values = {}
+for index, result in results do
+    if not result.success then
+        error(result.error, 0)
+    end
+    values[index] = result.values
+end
+return values
+"#;
+
+fn stats(lua: &Lua, (): ()) -> LuaResult<LuaTable> {
+    let mut builder = TableBuilder::new(lua)?;
+    for kind in TaskKind::ALL {
+        let stats = queue_stats_to_table(lua, lua.queue_stats(kind))?;
+        builder = builder.with_value(kind.name(), stats)?;
+    }
+    builder.build_readonly()
+}
+
+fn queue_stats_to_table(lua: &Lua, stats: QueueStats) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_value("count", stats.count)?
+        .with_value("average", stats.average.as_secs_f64())?
+        .with_value("p50", stats.p50.as_secs_f64())?
+        .with_value("p90", stats.p90.as_secs_f64())?
+        .with_value("p99", stats.p99.as_secs_f64())?
+        .with_value("max", stats.max.as_secs_f64())?
+        .build_readonly()
+}
+
+async fn wait(lua: &Lua, secs: Option<f64>) -> LuaResult<f64> {
     let duration = Duration::from_secs_f64(secs.unwrap_or_default());
+    let low_latency = lua
+        .app_data_ref::<LowLatencyMode>()
+        .is_some_and(|mode| mode.0);
 
     let before = Instant::now();
-    sleep(duration).await;
+    if low_latency {
+        sleep_precise(duration).await;
+    } else {
+        sleep(duration).await;
+    }
     let after = Instant::now();
 
     Ok((after - before).as_secs_f64())
 }
+
+async fn sleep_precise(duration: Duration) {
+    let deadline = Instant::now() + duration;
+    if let Some(sleep_duration) = duration.checked_sub(LOW_LATENCY_SPIN_THRESHOLD) {
+        sleep(sleep_duration).await;
+    }
+    // NOTE: Waking up right away would keep the scheduler busy until the deadline, and it
+    // would never get to yield to tokio, so timers, I/O and other tasks would have to wait
+    while Instant::now() < deadline {
+        yield_now().await;
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::HashSet;
+
+use mlua::prelude::*;
+
+/**
+    Checks if two values are deeply equal.
+
+    Tables are equal if they have the same keys, with deeply equal values, regardless
+    of their metatables. Keys are compared the same way as Lua does when indexing.
+    All other values are compared using `==`, meaning that `__eq` metamethods are
+    respected, except for `NaN`, which is equal to itself here.
+*/
+pub fn deep_equals(a: &LuaValue, b: &LuaValue) -> LuaResult<bool> {
+    let mut visited = HashSet::new();
+    deep_equals_inner(a, b, &mut visited)
+}
+
+fn deep_equals_inner(
+    a: &LuaValue,
+    b: &LuaValue,
+    visited: &mut HashSet<(usize, usize)>,
+) -> LuaResult<bool> {
+    match (a, b) {
+        (LuaValue::Number(a), LuaValue::Number(b)) if a.is_nan() && b.is_nan() => Ok(true),
+        (LuaValue::Table(a), LuaValue::Table(b)) => {
+            // NOTE: Tables that we are already comparing further up are assumed
+            // to be equal, which is what makes comparing recursive tables possible
+            let pair = (a.to_pointer() as usize, b.to_pointer() as usize);
+            if a == b || !visited.insert(pair) {
+                return Ok(true);
+            }
+            let mut len = 0;
+            for pair in a.clone().pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                let other = b.raw_get::<_, LuaValue>(key)?;
+                if !deep_equals_inner(&value, &other, visited)? {
+                    return Ok(false);
+                }
+                len += 1;
+            }
+            let other_len = b.clone().pairs::<LuaValue, LuaValue>().count();
+            Ok(len == other_len)
+        }
+        (a, b) => a.equals(b),
+    }
+}
@@ -0,0 +1,296 @@
+use mlua::prelude::*;
+
+use crate::{
+    equality::deep_equals,
+    matchers::call_matcher,
+    message::{failure, format_value, format_value_diff},
+    protected::call_protected,
+    snapshot::match_snapshot,
+    spy::{get_calls, is_spy},
+};
+
+/**
+    An expectation for a value, created using `test.expect`.
+
+    The value being tested is stored as the user value of the userdata.
+*/
+pub struct Expectation {
+    negated: bool,
+}
+
+impl Expectation {
+    pub fn create<'lua>(lua: &'lua Lua, value: LuaValue<'lua>) -> LuaResult<LuaAnyUserData<'lua>> {
+        Self::create_with(lua, value, false)
+    }
+
+    fn create_with<'lua>(
+        lua: &'lua Lua,
+        value: LuaValue<'lua>,
+        negated: bool,
+    ) -> LuaResult<LuaAnyUserData<'lua>> {
+        let ud = lua.create_userdata(Self { negated })?;
+        ud.set_user_value(value)?;
+        Ok(ud)
+    }
+
+    pub fn get<'lua>(ud: &LuaAnyUserData<'lua>) -> LuaResult<(LuaValue<'lua>, bool)> {
+        let negated = ud.borrow::<Self>()?.negated;
+        Ok((ud.user_value()?, negated))
+    }
+}
+
+/**
+    Checks the result of a matcher, erroring with the given message if it failed.
+
+    Messages are given if the matcher was negated, so that they can describe the failure.
+*/
+pub fn check(
+    lua: &Lua,
+    pass: bool,
+    negated: bool,
+    message: impl FnOnce(bool) -> String,
+) -> LuaResult<()> {
+    if pass == negated {
+        Err(failure(lua, message(negated)))
+    } else {
+        Ok(())
+    }
+}
+
+fn spy_calls<'lua>(received: &LuaValue<'lua>) -> LuaResult<Vec<LuaTable<'lua>>> {
+    match received {
+        LuaValue::Table(t) if is_spy(t) => get_calls(t),
+        value => Err(LuaError::RuntimeError(format!(
+            "Expected a spy created using test.spy or test.spyOn, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+impl LuaUserData for Expectation {
+    #[allow(clippy::too_many_lines)]
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_function(
+            "toBe",
+            |lua, (this, expected): (LuaAnyUserData, LuaValue)| {
+                let (received, negated) = Expectation::get(&this)?;
+                check(lua, received == expected, negated, |negated| {
+                    if negated {
+                        format!(
+                            "Expected values not to be the same\n\nReceived: {}",
+                            format_value(lua, &received)
+                        )
+                    } else if deep_equals(&received, &expected).unwrap_or_default() {
+                        format!(
+                            "Expected values to be the same, but they are only equal\n\
+                            Use toEqual to compare the contents of tables instead\n\n\
+                            Received: {}",
+                            format_value(lua, &received)
+                        )
+                    } else {
+                        format!(
+                            "Expected values to be the same\n\n{}",
+                            format_value_diff(lua, &expected, &received)
+                        )
+                    }
+                })
+            },
+        );
+
+        methods.add_function(
+            "toEqual",
+            |lua, (this, expected): (LuaAnyUserData, LuaValue)| {
+                let (received, negated) = Expectation::get(&this)?;
+                let pass = deep_equals(&received, &expected)?;
+                check(lua, pass, negated, |negated| {
+                    if negated {
+                        format!(
+                            "Expected values not to be equal\n\nReceived: {}",
+                            format_value(lua, &received)
+                        )
+                    } else {
+                        format!(
+                            "Expected values to be equal\n\n{}",
+                            format_value_diff(lua, &expected, &received)
+                        )
+                    }
+                })
+            },
+        );
+
+        methods.add_function("toBeNil", |lua, this: LuaAnyUserData| {
+            let (received, negated) = Expectation::get(&this)?;
+            check(lua, received.is_nil(), negated, |negated| {
+                let not = if negated { " not" } else { "" };
+                format!(
+                    "Expected value{not} to be nil\n\nReceived: {}",
+                    format_value(lua, &received)
+                )
+            })
+        });
+
+        methods.add_function("toBeTruthy", |lua, this: LuaAnyUserData| {
+            let (received, negated) = Expectation::get(&this)?;
+            let pass = !matches!(received, LuaValue::Nil | LuaValue::Boolean(false));
+            check(lua, pass, negated, |negated| {
+                let not = if negated { " not" } else { "" };
+                format!(
+                    "Expected value{not} to be truthy\n\nReceived: {}",
+                    format_value(lua, &received)
+                )
+            })
+        });
+
+        methods.add_function("toBeFalsy", |lua, this: LuaAnyUserData| {
+            let (received, negated) = Expectation::get(&this)?;
+            let pass = matches!(received, LuaValue::Nil | LuaValue::Boolean(false));
+            check(lua, pass, negated, |negated| {
+                let not = if negated { " not" } else { "" };
+                format!(
+                    "Expected value{not} to be falsy\n\nReceived: {}",
+                    format_value(lua, &received)
+                )
+            })
+        });
+
+        methods.add_function(
+            "toBeCloseTo",
+            |lua, (this, expected, digits): (LuaAnyUserData, f64, Option<i32>)| {
+                let (received, negated) = Expectation::get(&this)?;
+                let digits = digits.unwrap_or(2);
+                let pass = match received.as_number() {
+                    Some(number) => (number - expected).abs() < 10f64.powi(-digits) / 2.0,
+                    None => false,
+                };
+                check(lua, pass, negated, |negated| {
+                    let not = if negated { " not" } else { "" };
+                    format!(
+                        "Expected value{not} to be close to {expected} ({digits} digits)\n\nReceived: {}",
+                        format_value(lua, &received)
+                    )
+                })
+            },
+        );
+
+        methods.add_async_function(
+            "toThrow",
+            |lua, (this, expected): (LuaAnyUserData, Option<String>)| async move {
+                let (received, negated) = Expectation::get(&this)?;
+                let LuaValue::Function(f) = received else {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Expected a function to call for toThrow, got {}",
+                        received.type_name()
+                    )));
+                };
+                let message = call_protected(lua, f, ()).await?.err();
+                let pass = match (&message, &expected) {
+                    (Some(message), Some(expected)) => message.contains(expected.as_str()),
+                    (message, _) => message.is_some(),
+                };
+                check(lua, pass, negated, |negated| {
+                    let not = if negated { " not" } else { "" };
+                    let expectation = match &expected {
+                        Some(expected) => format!(
+                            "Expected function{not} to throw an error containing '{expected}'"
+                        ),
+                        None => format!("Expected function{not} to throw an error"),
+                    };
+                    match &message {
+                        Some(message) => format!("{expectation}\n\nReceived error: {message}"),
+                        None => format!("{expectation}, but it did not throw"),
+                    }
+                })
+            },
+        );
+
+        methods.add_function("toHaveBeenCalled", |lua, this: LuaAnyUserData| {
+            let (received, negated) = Expectation::get(&this)?;
+            let calls = spy_calls(&received)?;
+            check(lua, !calls.is_empty(), negated, |negated| {
+                let not = if negated { " not" } else { "" };
+                format!(
+                    "Expected spy{not} to have been called\n\nCalls: {}",
+                    calls.len()
+                )
+            })
+        });
+
+        methods.add_function(
+            "toHaveBeenCalledTimes",
+            |lua, (this, expected): (LuaAnyUserData, usize)| {
+                let (received, negated) = Expectation::get(&this)?;
+                let calls = spy_calls(&received)?;
+                check(lua, calls.len() == expected, negated, |negated| {
+                    let not = if negated { " not" } else { "" };
+                    format!(
+                        "Expected spy{not} to have been called {expected} time(s)\n\nCalls: {}",
+                        calls.len()
+                    )
+                })
+            },
+        );
+
+        methods.add_function(
+            "toHaveBeenCalledWith",
+            |lua, (this, args): (LuaAnyUserData, LuaMultiValue)| {
+                let (received, negated) = Expectation::get(&this)?;
+                let calls = spy_calls(&received)?;
+
+                let expected = lua.create_sequence_from(args)?;
+                expected.raw_set("n", expected.raw_len())?;
+                let expected = LuaValue::Table(expected);
+
+                let mut pass = false;
+                for call in &calls {
+                    if deep_equals(&LuaValue::Table(call.clone()), &expected)? {
+                        pass = true;
+                        break;
+                    }
+                }
+                check(lua, pass, negated, |negated| {
+                    let not = if negated { " not" } else { "" };
+                    let calls = lua
+                        .create_sequence_from(calls)
+                        .map(|calls| format_value(lua, &LuaValue::Table(calls)))
+                        .unwrap_or_default();
+                    format!(
+                        "Expected spy{not} to have been called with {}\n\nCalls: {calls}",
+                        format_value(lua, &expected)
+                    )
+                })
+            },
+        );
+
+        methods.add_function(
+            "toMatchSnapshot",
+            |lua, (this, name): (LuaAnyUserData, Option<String>)| {
+                let (received, negated) = Expectation::get(&this)?;
+                if negated {
+                    return Err(LuaError::runtime(
+                        "Snapshot expectations can not be negated",
+                    ));
+                }
+                match_snapshot(lua, received, name)
+            },
+        );
+
+        // NOTE: Methods above take priority over this, so custom matchers
+        // are only looked up for names that are not built-in matchers
+        methods.add_meta_function(
+            LuaMetaMethod::Index,
+            |lua, (_, name): (LuaAnyUserData, String)| {
+                lua.create_function(move |lua, (this, args): (LuaAnyUserData, LuaMultiValue)| {
+                    call_matcher(lua, &name, &this, args)
+                })
+            },
+        );
+    }
+
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "Expectation");
+        fields.add_field_function_get("never", |lua, this| {
+            let (received, negated) = Expectation::get(&this)?;
+            Expectation::create_with(lua, received, !negated)
+        });
+    }
+}
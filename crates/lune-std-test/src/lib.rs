@@ -0,0 +1,64 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+mod equality;
+mod expect;
+mod matchers;
+mod message;
+mod property;
+mod protected;
+mod snapshot;
+mod spy;
+
+use self::equality::deep_equals;
+use self::expect::Expectation;
+use self::message::{failure, format_value_diff};
+
+pub use self::property::set_test_seed;
+pub use self::snapshot::set_update_snapshots;
+pub use self::spy::set_test_mode;
+
+/**
+    Creates the `test` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("expect", expect)?
+        .with_function("assertEq", assert_eq)?
+        .with_function("extend", matchers::extend)?
+        .with_function("spy", spy::create)?
+        .with_function("spyOn", spy::spy_on)?
+        .with_function("restoreAll", spy::restore_all)?
+        .with_value("gen", property::create_generators(lua)?)?
+        .with_async_function("check", property::check)?
+        .build_readonly()
+}
+
+fn expect<'lua>(lua: &'lua Lua, value: LuaValue<'lua>) -> LuaResult<LuaAnyUserData<'lua>> {
+    Expectation::create(lua, value)
+}
+
+fn assert_eq<'lua>(
+    lua: &'lua Lua,
+    (actual, expected, message): (LuaValue<'lua>, LuaValue<'lua>, Option<String>),
+) -> LuaResult<()> {
+    if deep_equals(&actual, &expected)? {
+        Ok(())
+    } else {
+        let message = message.unwrap_or_else(|| String::from("Expected values to be equal"));
+        Err(failure(
+            lua,
+            format!(
+                "{message}\n\n{}",
+                format_value_diff(lua, &expected, &actual)
+            ),
+        ))
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use mlua::prelude::*;
+
+use crate::expect::{check, Expectation};
+
+// Names that are already used by expectations, and that can not
+// be used for custom matchers, since they would never be called
+const BUILTIN_MATCHERS: &[&str] = &[
+    "never",
+    "toBe",
+    "toEqual",
+    "toBeNil",
+    "toBeTruthy",
+    "toBeFalsy",
+    "toBeCloseTo",
+    "toThrow",
+    "toHaveBeenCalled",
+    "toHaveBeenCalledTimes",
+    "toHaveBeenCalledWith",
+    "toMatchSnapshot",
+];
+
+#[derive(Default)]
+struct CustomMatchers(HashMap<String, LuaRegistryKey>);
+
+/**
+    Registers custom matchers from a table of matcher names to functions.
+
+    Matchers are called with the received value followed by any arguments given
+    to the matcher, and return if the value passed, plus an optional message.
+*/
+pub fn extend<'lua>(lua: &'lua Lua, matchers: LuaTable<'lua>) -> LuaResult<()> {
+    let mut added = Vec::new();
+    for pair in matchers.pairs::<String, LuaFunction>() {
+        let (name, matcher) = pair?;
+        if BUILTIN_MATCHERS.contains(&name.as_str()) {
+            return Err(LuaError::RuntimeError(format!(
+                "Matcher '{name}' is a built-in matcher and can not be replaced"
+            )));
+        }
+        added.push((name, lua.create_registry_value(matcher)?));
+    }
+
+    let mut custom = lua.remove_app_data::<CustomMatchers>().unwrap_or_default();
+    for (name, key) in added {
+        if let Some(old) = custom.0.insert(name, key) {
+            lua.remove_registry_value(old)?;
+        }
+    }
+    lua.set_app_data(custom);
+
+    Ok(())
+}
+
+/**
+    Calls the custom matcher with the given name for an expectation.
+*/
+pub fn call_matcher<'lua>(
+    lua: &'lua Lua,
+    name: &str,
+    this: &LuaAnyUserData<'lua>,
+    args: LuaMultiValue<'lua>,
+) -> LuaResult<()> {
+    let matcher = lua
+        .app_data_ref::<CustomMatchers>()
+        .and_then(|custom| {
+            custom
+                .0
+                .get(name)
+                .map(|key| lua.registry_value::<LuaFunction>(key))
+        })
+        .transpose()?
+        .ok_or_else(|| LuaError::RuntimeError(format!("Unknown matcher '{name}'")))?;
+
+    let (received, negated) = Expectation::get(this)?;
+    let mut matcher_args = args.into_vec();
+    matcher_args.insert(0, received);
+
+    let (pass, message) =
+        matcher.call::<_, (bool, Option<String>)>(LuaMultiValue::from_vec(matcher_args))?;
+    check(lua, pass, negated, |negated| {
+        message.unwrap_or_else(|| {
+            let not = if negated { " not" } else { "" };
+            format!("Expected value{not} to pass matcher '{name}'")
+        })
+    })
+}
@@ -0,0 +1,115 @@
+use std::fmt::Write as _;
+
+use console::style;
+use mlua::prelude::*;
+
+use lune_utils::{
+    diff::{diff_lines, DiffOp},
+    fmt::{pretty_format_value, ValueFormatConfig},
+};
+
+// Tables in failure messages are formatted deeper than when printing,
+// since any differences may be nested deep inside of the values
+const FORMAT_CONFIG: ValueFormatConfig = ValueFormatConfig::new().with_max_depth(16);
+
+const ASYNC_POLL_CHUNK_NAME: &str = "__mlua_async_poll";
+
+/**
+    Creates an error for a failed expectation, including the location
+    of the lua function that called the current native function.
+*/
+pub fn failure(lua: &Lua, message: impl AsRef<str>) -> LuaError {
+    // NOTE: Async functions are polled from within a lua chunk created by mlua,
+    // which we skip past so that the location is where the function was called
+    let location = (1..)
+        .map_while(|level| lua.inspect_stack(level))
+        .find(|info| {
+            let source = info.source().short_src.unwrap_or_default();
+            !source.contains(ASYNC_POLL_CHUNK_NAME)
+        })
+        .and_then(|info| {
+            let line = info.curr_line();
+            let source = info.source().short_src?.to_string();
+            (line > 0).then(|| format!("{source}:{line}: "))
+        });
+    LuaError::RuntimeError(format!(
+        "{}{}",
+        location.unwrap_or_default(),
+        message.as_ref()
+    ))
+}
+
+/**
+    Formats a value for a failure message.
+*/
+pub fn format_value(lua: &Lua, value: &LuaValue) -> String {
+    match value {
+        // NOTE: Strings are printed as they are when not inside of tables, but
+        // here we need them to be quoted so that "1" and 1 can be told apart
+        LuaValue::String(s) => format!("{:?}", s.to_string_lossy()),
+        value => pretty_format_value(lua, value, &FORMAT_CONFIG),
+    }
+}
+
+/**
+    Formats the difference between an expected and a received value for a failure message.
+*/
+pub fn format_value_diff(lua: &Lua, expected: &LuaValue, received: &LuaValue) -> String {
+    format_text_diff(&format_value(lua, expected), &format_value(lua, received))
+}
+
+/**
+    Formats the difference between an expected and a received text for a failure message.
+
+    Values that fit on a single line are shown as they are, anything longer
+    is shown as a colored diff of the lines, including lines that are equal.
+*/
+pub fn format_text_diff(expected: &str, received: &str) -> String {
+    let (expected, received) = (expected.trim_end(), received.trim_end());
+    if !expected.contains('\n') && !received.contains('\n') {
+        return format!("Expected: {expected}\nReceived: {received}");
+    }
+
+    let expected_lines = expected.lines().collect::<Vec<_>>();
+    let received_lines = received.lines().collect::<Vec<_>>();
+
+    let mut diff = format!(
+        "{}\n{}\n",
+        style("- Expected").red().for_stderr(),
+        style("+ Received").green().for_stderr()
+    );
+    let (mut expected_index, mut received_index) = (0, 0);
+    for op in diff_lines(&expected_lines, &received_lines) {
+        match op {
+            DiffOp::Equal => {
+                write!(diff, "\n  {}", expected_lines[expected_index]).ok();
+                expected_index += 1;
+                received_index += 1;
+            }
+            DiffOp::Remove => {
+                let line = format!("- {}", expected_lines[expected_index]);
+                write!(diff, "\n{}", style(line).red().for_stderr()).ok();
+                expected_index += 1;
+            }
+            DiffOp::Add => {
+                let line = format!("+ {}", received_lines[received_index]);
+                write!(diff, "\n{}", style(line).green().for_stderr()).ok();
+                received_index += 1;
+            }
+        }
+    }
+    diff
+}
+
+/**
+    Gets the message of an error, without any of the tracebacks or
+    other context that may have been added while it was propagating.
+*/
+pub fn error_message(error: &LuaError) -> String {
+    match error {
+        LuaError::RuntimeError(message) => message.clone(),
+        LuaError::CallbackError { cause, .. } => error_message(cause),
+        LuaError::WithContext { cause, .. } => error_message(cause),
+        error => error.to_string(),
+    }
+}
@@ -0,0 +1,592 @@
+use std::rc::Rc;
+
+use mlua::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use lune_utils::TableBuilder;
+
+use crate::{
+    message::{failure, format_value},
+    protected::call_protected,
+};
+
+const DEFAULT_RUNS: usize = 100;
+const DEFAULT_MAX_SHRINKS: usize = 1000;
+
+const DEFAULT_NUMBER_RANGE: f64 = 1000.0;
+const DEFAULT_INTEGER_RANGE: i64 = 1000;
+const DEFAULT_STRING_LENGTH: usize = 32;
+const DEFAULT_TABLE_LENGTH: usize = 16;
+
+// Sequences are shrunk by removing each of their items, but only
+// up to this many, so that shrinking long sequences stays fast
+const MAX_REMOVAL_CANDIDATES: usize = 32;
+
+struct TestSeed(u64);
+
+/**
+    Sets the seed to use for all property checks that were not given a seed.
+
+    When not set, which is the default, each property check uses a random seed.
+*/
+pub fn set_test_seed(lua: &Lua, seed: u64) {
+    lua.set_app_data(TestSeed(seed));
+}
+
+/**
+    Creates the `test.gen` table of functions for creating generators.
+*/
+pub fn create_generators(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("boolean", |_, (): ()| Ok(Generator::new(Kind::Boolean)))?
+        .with_function("integer", |_, (min, max): (Option<i64>, Option<i64>)| {
+            let min = min.unwrap_or(-DEFAULT_INTEGER_RANGE);
+            let max = max.unwrap_or(DEFAULT_INTEGER_RANGE);
+            check_range(min, max)?;
+            Ok(Generator::new(Kind::Integer { min, max }))
+        })?
+        .with_function("number", |_, (min, max): (Option<f64>, Option<f64>)| {
+            let min = min.unwrap_or(-DEFAULT_NUMBER_RANGE);
+            let max = max.unwrap_or(DEFAULT_NUMBER_RANGE);
+            if !min.is_finite() || !max.is_finite() {
+                return Err(LuaError::runtime("Invalid range - numbers must be finite"));
+            }
+            check_range(min, max)?;
+            Ok(Generator::new(Kind::Number { min, max }))
+        })?
+        .with_function(
+            "string",
+            |_, (min_len, max_len): (Option<usize>, Option<usize>)| {
+                let min_len = min_len.unwrap_or(0);
+                let max_len = max_len.unwrap_or(DEFAULT_STRING_LENGTH.max(min_len));
+                check_range(min_len, max_len)?;
+                Ok(Generator::new(Kind::String { min_len, max_len }))
+            },
+        )?
+        .with_function("constant", |lua, value: LuaValue| {
+            Ok(Generator::new(Kind::Constant(
+                lua.create_registry_value(value)?,
+            )))
+        })?
+        .with_function("oneOf", |lua, generators: LuaMultiValue| {
+            let generators = generators
+                .into_iter()
+                .map(|value| Generator::from_lua(value, lua))
+                .collect::<LuaResult<Vec<_>>>()?;
+            if generators.is_empty() {
+                return Err(LuaError::runtime(
+                    "Expected at least one generator to choose from",
+                ));
+            }
+            let generators = generators.iter().map(|g| Rc::clone(&g.0)).collect();
+            Ok(Generator::new(Kind::OneOf(generators)))
+        })?
+        .with_function("optional", |_, generator: Generator| {
+            Ok(Generator::new(Kind::Optional(generator.0)))
+        })?
+        .with_function(
+            "array",
+            |_, (item, min_len, max_len): (Generator, Option<usize>, Option<usize>)| {
+                let min_len = min_len.unwrap_or(0);
+                let max_len = max_len.unwrap_or(DEFAULT_TABLE_LENGTH.max(min_len));
+                check_range(min_len, max_len)?;
+                Ok(Generator::new(Kind::Array {
+                    item: item.0,
+                    min_len,
+                    max_len,
+                }))
+            },
+        )?
+        .with_function(
+            "dictionary",
+            |_, (key, value, max_len): (Generator, Generator, Option<usize>)| {
+                Ok(Generator::new(Kind::Dictionary {
+                    key: key.0,
+                    value: value.0,
+                    max_len: max_len.unwrap_or(DEFAULT_TABLE_LENGTH),
+                }))
+            },
+        )?
+        .with_function("record", |_, shape: LuaTable| {
+            let mut fields = shape
+                .pairs::<String, Generator>()
+                .map(|pair| pair.map(|(key, generator)| (key, generator.0)))
+                .collect::<LuaResult<Vec<_>>>()?;
+            // NOTE: Fields are sorted so that the same seed
+            // always generates the same values for the record
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            Ok(Generator::new(Kind::Record(fields)))
+        })?
+        .with_function("map", |lua, (generator, f): (Generator, LuaFunction)| {
+            Ok(Generator::new(Kind::Map(
+                generator.0,
+                lua.create_registry_value(f)?,
+            )))
+        })?
+        .build_readonly()
+}
+
+fn check_range<T: PartialOrd>(min: T, max: T) -> LuaResult<()> {
+    if min > max {
+        Err(LuaError::runtime(
+            "Invalid range - min must not be greater than max",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/**
+    The different kinds of generators, and the options they were created with.
+*/
+enum Kind {
+    Boolean,
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    Number {
+        min: f64,
+        max: f64,
+    },
+    String {
+        min_len: usize,
+        max_len: usize,
+    },
+    Constant(LuaRegistryKey),
+    OneOf(Vec<Rc<Kind>>),
+    Optional(Rc<Kind>),
+    Array {
+        item: Rc<Kind>,
+        min_len: usize,
+        max_len: usize,
+    },
+    Dictionary {
+        key: Rc<Kind>,
+        value: Rc<Kind>,
+        max_len: usize,
+    },
+    Record(Vec<(String, Rc<Kind>)>),
+    Map(Rc<Kind>, LuaRegistryKey),
+}
+
+/**
+    A generator of random values, created using the functions in `test.gen`.
+*/
+#[derive(Clone)]
+pub struct Generator(Rc<Kind>);
+
+impl Generator {
+    fn new(kind: Kind) -> Self {
+        Self(Rc::new(kind))
+    }
+}
+
+impl<'lua> FromLua<'lua> for Generator {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::UserData(ud) => Ok(ud.borrow::<Self>()?.clone()),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Generator",
+                message: Some(format!(
+                    "Invalid generator - expected a generator from test.gen, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+impl LuaUserData for Generator {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_meta_field(LuaMetaMethod::Type, "Generator");
+    }
+}
+
+/**
+    A generated value, kept in a form that can be shrunk.
+
+    The shape of a sample always matches the generator that created it,
+    and it is only turned into a Lua value when calling the property.
+*/
+#[derive(Debug, Clone, PartialEq)]
+enum Sample {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    Number(f64),
+    String(Vec<u8>),
+    Choice(usize, Box<Sample>),
+    Optional(Option<Box<Sample>>),
+    Sequence(Vec<Sample>),
+    Entries(Vec<(Sample, Sample)>),
+}
+
+impl Kind {
+    fn generate(&self, rng: &mut StdRng) -> Sample {
+        // NOTE: Edge cases such as the bounds of ranges find a lot of bugs,
+        // so they are picked more often than they would be by chance
+        let edge = rng.gen_ratio(1, 8);
+        match self {
+            Self::Boolean => Sample::Boolean(rng.gen()),
+            Self::Integer { min, max } => Sample::Integer(if edge {
+                [*min, *max, 0.clamp(*min, *max)][rng.gen_range(0..3)]
+            } else {
+                rng.gen_range(*min..=*max)
+            }),
+            Self::Number { min, max } => Sample::Number(if edge {
+                [*min, *max, 0.0f64.clamp(*min, *max)][rng.gen_range(0..3)]
+            } else {
+                rng.gen_range(*min..=*max)
+            }),
+            Self::String { min_len, max_len } => {
+                let len = rng.gen_range(*min_len..=*max_len);
+                Sample::String((0..len).map(|_| rng.gen_range(b' '..=b'~')).collect())
+            }
+            Self::Constant(_) => Sample::Nil,
+            Self::OneOf(kinds) => {
+                let index = rng.gen_range(0..kinds.len());
+                Sample::Choice(index, Box::new(kinds[index].generate(rng)))
+            }
+            Self::Optional(kind) => Sample::Optional(if rng.gen_ratio(1, 4) {
+                None
+            } else {
+                Some(Box::new(kind.generate(rng)))
+            }),
+            Self::Array {
+                item,
+                min_len,
+                max_len,
+            } => {
+                let len = rng.gen_range(*min_len..=*max_len);
+                Sample::Sequence((0..len).map(|_| item.generate(rng)).collect())
+            }
+            Self::Dictionary {
+                key,
+                value,
+                max_len,
+            } => {
+                let len = rng.gen_range(0..=*max_len);
+                Sample::Entries(
+                    (0..len)
+                        .map(|_| (key.generate(rng), value.generate(rng)))
+                        .collect(),
+                )
+            }
+            Self::Record(fields) => {
+                Sample::Sequence(fields.iter().map(|(_, kind)| kind.generate(rng)).collect())
+            }
+            Self::Map(kind, _) => kind.generate(rng),
+        }
+    }
+
+    /**
+        Gives smaller versions of a sample, with the simplest ones first.
+    */
+    fn shrink(&self, sample: &Sample) -> Vec<Sample> {
+        match (self, sample) {
+            (Self::Boolean, Sample::Boolean(true)) => vec![Sample::Boolean(false)],
+            (Self::Integer { min, max }, Sample::Integer(value)) => {
+                let target = 0.clamp(*min, *max);
+                let mut candidates = Vec::new();
+                for candidate in [
+                    target,
+                    target + (value - target) / 2,
+                    value - (value - target).signum(),
+                ] {
+                    if candidate != *value && !candidates.contains(&Sample::Integer(candidate)) {
+                        candidates.push(Sample::Integer(candidate));
+                    }
+                }
+                candidates
+            }
+            (Self::Number { min, max }, Sample::Number(value)) => {
+                let target = 0.0f64.clamp(*min, *max);
+                let mut candidates = Vec::new();
+                #[allow(clippy::float_cmp)]
+                for candidate in [target, value.trunc(), target + (value - target) / 2.0] {
+                    if candidate != *value
+                        && (candidate - value).abs() > f64::EPSILON
+                        && (*min..=*max).contains(&candidate)
+                        && !candidates.contains(&Sample::Number(candidate))
+                    {
+                        candidates.push(Sample::Number(candidate));
+                    }
+                }
+                candidates
+            }
+            (Self::String { min_len, .. }, Sample::String(bytes)) => {
+                shrink_sequence(bytes, *min_len, |byte| {
+                    if *byte == b'a' {
+                        Vec::new()
+                    } else {
+                        vec![b'a']
+                    }
+                })
+                .into_iter()
+                .map(Sample::String)
+                .collect()
+            }
+            (Self::OneOf(kinds), Sample::Choice(index, inner)) => kinds[*index]
+                .shrink(inner)
+                .into_iter()
+                .map(|inner| Sample::Choice(*index, Box::new(inner)))
+                .collect(),
+            (Self::Optional(kind), Sample::Optional(Some(inner))) => {
+                let mut candidates = vec![Sample::Optional(None)];
+                candidates.extend(
+                    kind.shrink(inner)
+                        .into_iter()
+                        .map(|inner| Sample::Optional(Some(Box::new(inner)))),
+                );
+                candidates
+            }
+            (Self::Array { item, min_len, .. }, Sample::Sequence(items)) => {
+                shrink_sequence(items, *min_len, |sample| item.shrink(sample))
+                    .into_iter()
+                    .map(Sample::Sequence)
+                    .collect()
+            }
+            (Self::Dictionary { key, value, .. }, Sample::Entries(entries)) => {
+                shrink_sequence(entries, 0, |(k, v)| {
+                    let values = value.shrink(v).into_iter().map(|v| (k.clone(), v));
+                    let keys = key.shrink(k).into_iter().map(|k| (k, v.clone()));
+                    values.chain(keys).collect()
+                })
+                .into_iter()
+                .map(Sample::Entries)
+                .collect()
+            }
+            (Self::Record(fields), Sample::Sequence(values)) => {
+                shrink_each(values, |index, sample| fields[index].1.shrink(sample))
+                    .into_iter()
+                    .map(Sample::Sequence)
+                    .collect()
+            }
+            (Self::Map(kind, _), sample) => kind.shrink(sample),
+            _ => Vec::new(),
+        }
+    }
+
+    fn to_lua<'lua>(&self, lua: &'lua Lua, sample: &Sample) -> LuaResult<LuaValue<'lua>> {
+        Ok(match (self, sample) {
+            (Self::Boolean, Sample::Boolean(b)) => LuaValue::Boolean(*b),
+            (Self::Integer { .. }, Sample::Integer(i)) => i.into_lua(lua)?,
+            (Self::Number { .. }, Sample::Number(n)) => LuaValue::Number(*n),
+            (Self::String { .. }, Sample::String(s)) => LuaValue::String(lua.create_string(s)?),
+            (Self::Constant(key), _) => lua.registry_value(key)?,
+            (Self::OneOf(kinds), Sample::Choice(index, inner)) => {
+                kinds[*index].to_lua(lua, inner)?
+            }
+            (Self::Optional(kind), Sample::Optional(Some(inner))) => kind.to_lua(lua, inner)?,
+            (Self::Array { item, .. }, Sample::Sequence(items)) => {
+                let table = lua.create_table_with_capacity(items.len(), 0)?;
+                for sample in items {
+                    table.raw_push(item.to_lua(lua, sample)?)?;
+                }
+                LuaValue::Table(table)
+            }
+            (Self::Dictionary { key, value, .. }, Sample::Entries(entries)) => {
+                let table = lua.create_table()?;
+                for (k, v) in entries {
+                    let k = key.to_lua(lua, k)?;
+                    if !k.is_nil() {
+                        table.raw_set(k, value.to_lua(lua, v)?)?;
+                    }
+                }
+                LuaValue::Table(table)
+            }
+            (Self::Record(fields), Sample::Sequence(values)) => {
+                let table = lua.create_table_with_capacity(0, fields.len())?;
+                for ((name, kind), sample) in fields.iter().zip(values) {
+                    table.raw_set(name.as_str(), kind.to_lua(lua, sample)?)?;
+                }
+                LuaValue::Table(table)
+            }
+            (Self::Map(kind, key), sample) => {
+                let f = lua.registry_value::<LuaFunction>(key)?;
+                f.call(kind.to_lua(lua, sample)?)?
+            }
+            _ => LuaValue::Nil,
+        })
+    }
+}
+
+fn shrink_sequence<T: Clone>(
+    items: &[T],
+    min_len: usize,
+    shrink_item: impl Fn(&T) -> Vec<T>,
+) -> Vec<Vec<T>> {
+    let mut candidates = Vec::new();
+    if items.len() > min_len {
+        candidates.push(items[..min_len].to_vec());
+        let half = items.len() / 2;
+        if half > min_len {
+            candidates.push(items[..half].to_vec());
+            candidates.push(items[items.len() - half..].to_vec());
+        }
+        for index in 0..items.len().min(MAX_REMOVAL_CANDIDATES) {
+            let mut candidate = items.to_vec();
+            candidate.remove(index);
+            candidates.push(candidate);
+        }
+    }
+    candidates.extend(shrink_each(items, |_, item| shrink_item(item)));
+    candidates
+}
+
+fn shrink_each<T: Clone>(items: &[T], shrink_item: impl Fn(usize, &T) -> Vec<T>) -> Vec<Vec<T>> {
+    let mut candidates = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        for shrunk in shrink_item(index, item) {
+            let mut candidate = items.to_vec();
+            candidate[index] = shrunk;
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+pub struct CheckOptions {
+    runs: usize,
+    max_shrinks: usize,
+    seed: Option<u64>,
+}
+
+impl<'lua> FromLua<'lua> for CheckOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self {
+                runs: DEFAULT_RUNS,
+                max_shrinks: DEFAULT_MAX_SHRINKS,
+                seed: None,
+            }),
+            LuaValue::Table(t) => Ok(Self {
+                runs: t.get::<_, Option<usize>>("runs")?.unwrap_or(DEFAULT_RUNS),
+                max_shrinks: t
+                    .get::<_, Option<usize>>("maxShrinks")?
+                    .unwrap_or(DEFAULT_MAX_SHRINKS),
+                seed: t.get("seed")?,
+            }),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CheckOptions",
+                message: Some(format!(
+                    "Invalid check options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Checks that a property holds for many randomly generated values, erroring
+    with the smallest failing values that could be found if it does not.
+
+    Properties fail if they throw an error or return `false`.
+*/
+pub async fn check<'lua>(
+    lua: &'lua Lua,
+    (generators, property, options): (LuaValue<'lua>, LuaFunction<'lua>, CheckOptions),
+) -> LuaResult<()> {
+    let kinds = match generators {
+        LuaValue::UserData(ud) => vec![ud.borrow::<Generator>()?.0.clone()],
+        LuaValue::Table(t) => t
+            .sequence_values::<Generator>()
+            .map(|g| g.map(|g| g.0))
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Expected a generator or a list of generators, got {}",
+                value.type_name()
+            )))
+        }
+    };
+
+    let seed = options
+        .seed
+        .or_else(|| lua.app_data_ref::<TestSeed>().map(|seed| seed.0))
+        .unwrap_or_else(|| u64::from(rand::random::<u32>()));
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for run in 1..=options.runs {
+        let samples = kinds
+            .iter()
+            .map(|kind| kind.generate(&mut rng))
+            .collect::<Vec<_>>();
+        let Some(error) = run_property(lua, &property, &kinds, &samples).await? else {
+            continue;
+        };
+
+        // Shrink the failing samples for as long as we can find smaller ones that still fail
+        let original = format_samples(lua, &kinds, &samples)?;
+        let (mut current, mut error) = (samples, error);
+        let (mut steps, mut attempts) = (0, 0);
+        'shrinking: while attempts < options.max_shrinks {
+            for candidate in shrink_each(&current, |index, sample| kinds[index].shrink(sample)) {
+                attempts += 1;
+                if let Some(candidate_error) =
+                    run_property(lua, &property, &kinds, &candidate).await?
+                {
+                    current = candidate;
+                    error = candidate_error;
+                    steps += 1;
+                    continue 'shrinking;
+                }
+                if attempts >= options.max_shrinks {
+                    break;
+                }
+            }
+            break;
+        }
+
+        let shrunk = if steps > 0 {
+            format!("\nShrunk {steps} time(s) from: {original}")
+        } else {
+            String::new()
+        };
+        return Err(failure(
+            lua,
+            format!(
+                "Property failed after {run} run(s) with seed {seed}\n\n\
+                Counterexample: {}{shrunk}\n\n\
+                Error: {error}\n\n\
+                Replay using {{ seed = {seed} }} or `lune test --seed {seed}`",
+                format_samples(lua, &kinds, &current)?
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn run_property<'lua>(
+    lua: &'lua Lua,
+    property: &LuaFunction<'lua>,
+    kinds: &[Rc<Kind>],
+    samples: &[Sample],
+) -> LuaResult<Option<String>> {
+    let args = kinds
+        .iter()
+        .zip(samples)
+        .map(|(kind, sample)| kind.to_lua(lua, sample))
+        .collect::<LuaResult<Vec<_>>>()?;
+    match call_protected(lua, property.clone(), LuaMultiValue::from_vec(args)).await? {
+        Err(error) => Ok(Some(error)),
+        Ok(values) => match values.into_iter().next() {
+            Some(LuaValue::Boolean(false)) => Ok(Some(String::from("Property returned false"))),
+            _ => Ok(None),
+        },
+    }
+}
+
+fn format_samples(lua: &Lua, kinds: &[Rc<Kind>], samples: &[Sample]) -> LuaResult<String> {
+    let values = kinds
+        .iter()
+        .zip(samples)
+        .map(|(kind, sample)| Ok(format_value(lua, &kind.to_lua(lua, sample)?)))
+        .collect::<LuaResult<Vec<_>>>()?;
+    Ok(values.join(", "))
+}
@@ -0,0 +1,41 @@
+use mlua::prelude::*;
+use mlua_luau_scheduler::LuaSchedulerExt;
+
+use crate::message::{error_message, format_value};
+
+/**
+    Calls a function that may yield, such as one that uses `task.wait`,
+    giving back either its return values or the message of its error.
+
+    The function is called using `pcall` in a new thread, so that any error
+    does not get reported by the scheduler as an uncaught error.
+*/
+pub async fn call_protected<'lua>(
+    lua: &'lua Lua,
+    f: LuaFunction<'lua>,
+    args: impl IntoLuaMulti<'lua>,
+) -> LuaResult<Result<LuaMultiValue<'lua>, String>> {
+    let pcall = lua
+        .load("return pcall(...)")
+        .set_name("=test")
+        .into_function()?;
+    let mut pcall_args = args.into_lua_multi(lua)?;
+    pcall_args.push_front(LuaValue::Function(f));
+    let thread_id = lua.push_thread_back(pcall, pcall_args)?;
+    lua.track_thread(thread_id);
+    lua.wait_for_thread(thread_id).await;
+
+    let mut values = match lua.get_thread_result(thread_id) {
+        Some(result) => result?,
+        None => return Ok(Ok(LuaMultiValue::new())),
+    };
+    match values.pop_front() {
+        Some(LuaValue::Boolean(false)) => Ok(Err(match values.pop_front() {
+            Some(LuaValue::Error(e)) => error_message(&e),
+            Some(LuaValue::String(s)) => s.to_string_lossy().to_string(),
+            Some(value) => format_value(lua, &value),
+            None => String::from("nil"),
+        })),
+        _ => Ok(Ok(values)),
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+
+use lune_std_serde::{encode, EncodeDecodeConfig, EncodeDecodeFormat};
+use lune_utils::path::clean_path_and_make_absolute;
+
+use crate::message::format_text_diff;
+
+const SNAPSHOT_EXTENSION: &str = "snap.json";
+
+#[derive(Debug, Default)]
+struct SnapshotFile {
+    entries: BTreeMap<String, JsonValue>,
+    counts: HashMap<String, usize>,
+}
+
+#[derive(Debug, Default)]
+struct Snapshots {
+    files: HashMap<PathBuf, SnapshotFile>,
+}
+
+struct UpdateSnapshots(bool);
+
+/**
+    Enables or disables updating of snapshots that do not match.
+
+    When disabled, which is the default, snapshots that do not match will error.
+    Snapshots that do not exist yet are always written, regardless of this setting.
+*/
+pub fn set_update_snapshots(lua: &Lua, enabled: bool) {
+    lua.set_app_data(UpdateSnapshots(enabled));
+}
+
+/**
+    Matches the given value against a snapshot stored beside the calling script.
+
+    Snapshots are named using the given name, or `snapshot` if no name was given,
+    followed by the number of times that the name has been used by the script.
+*/
+pub fn match_snapshot(lua: &Lua, value: LuaValue, name: Option<String>) -> LuaResult<()> {
+    // NOTE: Level 1 is the lua function that called us, which
+    // is the script that the snapshot should be stored beside
+    let source = lua
+        .inspect_stack(1)
+        .and_then(|info| info.source().source.map(|source| source.to_string()))
+        .ok_or_else(|| LuaError::runtime("Failed to get the script that is using snapshots"))?;
+    let path = clean_path_and_make_absolute(format!("{source}.{SNAPSHOT_EXTENSION}"));
+
+    let received = encode(
+        value,
+        lua,
+        EncodeDecodeConfig::canonical(EncodeDecodeFormat::Json),
+    )
+    .map_err(|e| LuaError::RuntimeError(format!("Failed to serialize snapshot - {e}")))?;
+    let received = serde_json::from_slice::<JsonValue>(received.as_bytes()).into_lua_err()?;
+
+    let update = lua
+        .app_data_ref::<UpdateSnapshots>()
+        .is_some_and(|update| update.0);
+    if lua.app_data_ref::<Snapshots>().is_none() {
+        lua.set_app_data(Snapshots::default());
+    }
+    let mut snapshots = lua.app_data_mut::<Snapshots>().expect("Missing snapshots");
+    let file = match snapshots.files.entry(path.clone()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(read_snapshot_file(&path)?),
+    };
+
+    let name = name.unwrap_or_else(|| "snapshot".to_string());
+    let count = file.counts.entry(name.clone()).or_default();
+    *count += 1;
+    let key = format!("{name} {count}");
+
+    match file.entries.get(&key) {
+        Some(expected) if *expected == received => Ok(()),
+        Some(expected) if !update => Err(LuaError::RuntimeError(format!(
+            "Snapshot '{key}' does not match\n\n{}\n\n\
+            Run tests with --update-snapshots to update it",
+            format_text_diff(&format_json(expected), &format_json(&received)),
+        ))),
+        _ => {
+            file.entries.insert(key, received);
+            write_snapshot_file(&path, &file.entries)
+        }
+    }
+}
+
+fn format_json(value: &JsonValue) -> String {
+    let mut formatted = serde_json::to_string_pretty(value).unwrap_or_default();
+    formatted.push('\n');
+    formatted
+}
+
+fn read_snapshot_file(path: &Path) -> LuaResult<SnapshotFile> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(SnapshotFile::default()),
+        Err(e) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Failed to read snapshots from '{}' - {e}",
+                path.display()
+            )))
+        }
+    };
+    let entries = serde_json::from_slice(&contents).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to read snapshots from '{}' - {e}",
+            path.display()
+        ))
+    })?;
+    Ok(SnapshotFile {
+        entries,
+        counts: HashMap::new(),
+    })
+}
+
+fn write_snapshot_file(path: &Path, entries: &BTreeMap<String, JsonValue>) -> LuaResult<()> {
+    let mut contents = serde_json::to_string_pretty(entries).into_lua_err()?;
+    contents.push('\n');
+    fs::write(path, contents).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to write snapshots to '{}' - {e}",
+            path.display()
+        ))
+    })
+}
@@ -0,0 +1,173 @@
+use mlua::prelude::*;
+
+// NOTE: Spies are implemented in Luau and called using __call, so
+// that functions being spied on can yield, such as async builtins
+const SPY_IMPL_LUA: &str = r"
+local implementation, restore = ...
+local methods = {}
+
+function methods.returns(self, ...)
+	local values = table.pack(...)
+	implementation = function()
+		return table.unpack(values, 1, values.n)
+	end
+	return self
+end
+
+function methods.implement(self, f)
+	implementation = f
+	return self
+end
+
+function methods.reset(self)
+	table.clear(self.calls)
+	return self
+end
+
+function methods.restore(self)
+	if restore ~= nil then
+		restore()
+		restore = nil
+	end
+end
+
+return setmetatable({ calls = {} }, {
+	__type = 'Spy',
+	__index = methods,
+	__call = function(self, ...)
+		table.insert(self.calls, table.pack(...))
+		if implementation ~= nil then
+			return implementation(...)
+		end
+		return
+	end,
+})
+";
+
+struct TestMode(bool);
+
+/**
+    Enables or disables test mode, which is used when running `lune test`.
+
+    In test mode, spies can replace functions in tables that are read-only,
+    such as the tables of functions for the built-in standard libraries.
+*/
+pub fn set_test_mode(lua: &Lua, enabled: bool) {
+    lua.set_app_data(TestMode(enabled));
+}
+
+#[derive(Default)]
+struct ActiveSpies(Vec<LuaRegistryKey>);
+
+/**
+    Creates a new spy, which records its calls and then calls the given implementation.
+*/
+pub fn create<'lua>(
+    lua: &'lua Lua,
+    implementation: Option<LuaFunction<'lua>>,
+) -> LuaResult<LuaTable<'lua>> {
+    lua.load(SPY_IMPL_LUA)
+        .set_name("spy")
+        .call((implementation, LuaValue::Nil))
+}
+
+/**
+    Replaces the function at the given key in a table with a spy that calls the original.
+
+    The original function is put back when the spy is restored, either using
+    `spy:restore()`, `test.restoreAll()`, or when the test file finishes.
+*/
+pub fn spy_on<'lua>(
+    lua: &'lua Lua,
+    (target, key): (LuaTable<'lua>, String),
+) -> LuaResult<LuaTable<'lua>> {
+    // NOTE: Functions that are already being spied on can be spied on again,
+    // which is why restoring all spies needs to happen in reverse order
+    let original = match target.raw_get::<_, LuaValue>(key.as_str())? {
+        LuaValue::Function(f) => LuaValue::Function(f),
+        LuaValue::Table(t) if is_spy(&t) => LuaValue::Table(t),
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Expected '{key}' to be a function to spy on, got {}",
+                value.type_name()
+            )))
+        }
+    };
+
+    let test_mode = lua.app_data_ref::<TestMode>().is_some_and(|mode| mode.0);
+    if target.is_readonly() && !test_mode {
+        return Err(LuaError::RuntimeError(format!(
+            "Can not spy on '{key}' since its table is read-only\n\
+            Functions in read-only tables, such as built-in libraries, can only be spied on using `lune test`"
+        )));
+    }
+
+    let target_key = lua.create_registry_value(target.clone())?;
+    let original_key = lua.create_registry_value(original.clone())?;
+    let restore_key = key.clone();
+    let restore = lua.create_function(move |lua, (): ()| {
+        let target = lua.registry_value::<LuaTable>(&target_key)?;
+        let original = lua.registry_value::<LuaValue>(&original_key)?;
+        set_field(&target, &restore_key, original)
+    })?;
+
+    let spy: LuaTable = lua
+        .load(SPY_IMPL_LUA)
+        .set_name("spy")
+        .call((original, restore))?;
+    set_field(&target, &key, spy.clone())?;
+
+    let mut active = lua.remove_app_data::<ActiveSpies>().unwrap_or_default();
+    active.0.push(lua.create_registry_value(spy.clone())?);
+    lua.set_app_data(active);
+
+    Ok(spy)
+}
+
+/**
+    Restores all spies created using `spyOn` that have not been restored yet.
+*/
+pub fn restore_all(lua: &Lua, (): ()) -> LuaResult<()> {
+    let Some(active) = lua.remove_app_data::<ActiveSpies>() else {
+        return Ok(());
+    };
+    // NOTE: Spies are restored in reverse order, so that if the same function was
+    // spied on more than once, the function that is put back is the original one
+    for key in active.0.into_iter().rev() {
+        let spy = lua.registry_value::<LuaTable>(&key)?;
+        lua.remove_registry_value(key)?;
+        spy.call_method::<_, ()>("restore", ())?;
+    }
+    Ok(())
+}
+
+/**
+    Checks if the given table is a spy.
+*/
+pub fn is_spy(table: &LuaTable) -> bool {
+    table
+        .get_metatable()
+        .and_then(|meta| meta.raw_get::<_, LuaString>("__type").ok())
+        .is_some_and(|name| name == "Spy")
+}
+
+/**
+    Gets the arguments of all calls that have been made to a spy.
+*/
+pub fn get_calls<'lua>(spy: &LuaTable<'lua>) -> LuaResult<Vec<LuaTable<'lua>>> {
+    spy.raw_get::<_, LuaTable>("calls")?
+        .sequence_values()
+        .collect()
+}
+
+fn set_field<'lua>(table: &LuaTable<'lua>, key: &str, value: impl IntoLua<'lua>) -> LuaResult<()> {
+    let readonly = table.is_readonly();
+    if readonly {
+        table.set_readonly(false);
+    }
+    let result = table.raw_set(key, value);
+    if readonly {
+        table.set_readonly(true);
+    }
+    result
+}
@@ -0,0 +1,99 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use std::rc::{Rc, Weak};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
+use tokio::sync::{mpsc::unbounded_channel, watch};
+
+use lune_utils::TableBuilder;
+
+mod options;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod sni;
+#[cfg(all(unix, not(target_os = "macos")))]
+use sni::TrayIcon;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use windows::TrayIcon;
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+mod unsupported;
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+use unsupported::TrayIcon;
+
+use self::options::TrayOptions;
+
+/**
+    Creates the `tray` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("create", tray_create)?
+        .build_readonly()
+}
+
+/**
+    Creates a tray icon, calling the callback in a new Lua thread with the id
+    of the menu item every time one is clicked, until the icon is removed.
+*/
+async fn tray_create<'lua>(
+    lua: &'lua Lua,
+    (options, callback): (TrayOptions, LuaFunction<'lua>),
+) -> LuaResult<LuaTable<'lua>> {
+    let (event_tx, mut event_rx) = unbounded_channel::<String>();
+    let icon = TrayIcon::spawn(options, event_tx)
+        .await
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to create tray icon - {e}")))?;
+
+    let lua_inner: Rc<Lua> = lua
+        .app_data_ref::<Weak<Lua>>()
+        .expect("Missing weak lua ref")
+        .upgrade()
+        .expect("Lua was dropped unexpectedly");
+    let callback_key = lua.create_registry_value(callback)?;
+
+    let (remove_tx, mut remove_rx) = watch::channel(false);
+    lua.spawn_local(async move {
+        loop {
+            // NOTE: If the handle was garbage collected without being removed, the
+            // sender is dropped, and the icon keeps showing and sending events to
+            // its callbacks until the script exits and the icon thread is torn down
+            let removed = async {
+                if remove_rx.wait_for(|removed| *removed).await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            };
+            // NOTE: Events stop arriving if the icon was removed by the
+            // platform, such as when the tray host shuts down on Linux
+            let id = tokio::select! {
+                biased;
+                () = removed => break,
+                id = event_rx.recv() => match id {
+                    Some(id) => id,
+                    None => break,
+                },
+            };
+            let callback = lua_inner
+                .registry_value::<LuaFunction>(&callback_key)
+                .expect("Missing tray callback");
+            lua_inner.push_thread_back(callback, id).ok();
+        }
+        icon.remove();
+        lua_inner.remove_registry_value(callback_key).ok();
+    });
+
+    TableBuilder::new(lua)?
+        .with_function("remove", move |_, (): ()| {
+            remove_tx.send_replace(true);
+            Ok(())
+        })?
+        .build_readonly()
+}
@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use mlua::prelude::*;
+
+/**
+    An item in the menu of a tray icon.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayMenuItem {
+    Item {
+        id: String,
+        label: String,
+        enabled: bool,
+    },
+    Separator,
+}
+
+impl<'lua> FromLua<'lua> for TrayMenuItem {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let item = match value {
+            LuaValue::String(s) if s.to_str()? == "separator" => return Ok(Self::Separator),
+            LuaValue::Table(t) => t,
+            _ => {
+                return Err(invalid_options(format!(
+                    "menu items must be tables or \"separator\", got {}",
+                    value.type_name()
+                )))
+            }
+        };
+
+        let id = match item.get::<_, Option<String>>("id")? {
+            Some(id) if !id.is_empty() => id,
+            _ => return Err(invalid_options("menu items must have a non-empty id")),
+        };
+        let label = item
+            .get::<_, Option<String>>("label")?
+            .unwrap_or_else(|| id.clone());
+        let enabled = item.get::<_, Option<bool>>("enabled")?.unwrap_or(true);
+
+        Ok(Self::Item { id, label, enabled })
+    }
+}
+
+/**
+    Options for creating a tray icon using `tray.create`.
+*/
+#[derive(Debug, Clone)]
+pub struct TrayOptions {
+    pub title: String,
+    pub tooltip: Option<String>,
+    pub icon: Option<String>,
+    pub menu: Vec<TrayMenuItem>,
+}
+
+impl<'lua> FromLua<'lua> for TrayOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let LuaValue::Table(options) = value else {
+            return Err(invalid_options(format!(
+                "expected table, got {}",
+                value.type_name()
+            )));
+        };
+
+        let title = match options.get::<_, Option<String>>("title")? {
+            Some(title) if !title.trim().is_empty() => title,
+            _ => return Err(invalid_options("title must be a non-empty string")),
+        };
+        let menu = options
+            .get::<_, Option<Vec<TrayMenuItem>>>("menu")?
+            .unwrap_or_default();
+
+        let mut ids = HashSet::new();
+        for item in &menu {
+            if let TrayMenuItem::Item { id, .. } = item {
+                if !ids.insert(id.as_str()) {
+                    return Err(invalid_options(format!(
+                        "menu item id '{id}' is used more than once"
+                    )));
+                }
+            }
+        }
+
+        Ok(Self {
+            title,
+            tooltip: options.get("tooltip")?,
+            icon: options.get("icon")?,
+            menu,
+        })
+    }
+}
+
+fn invalid_options(message: impl AsRef<str>) -> LuaError {
+    LuaError::FromLuaConversionError {
+        from: "table",
+        to: "TrayOptions",
+        message: Some(format!("Invalid tray options - {}", message.as_ref())),
+    }
+}
@@ -0,0 +1,85 @@
+use ksni::{menu::StandardItem, Handle, MenuItem, ToolTip, Tray, TrayMethods};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::options::{TrayMenuItem, TrayOptions};
+
+/**
+    A tray icon using the `StatusNotifierItem` D-Bus protocol, which is
+    supported by most desktop environments on Linux and other systems.
+*/
+pub struct TrayIcon {
+    handle: Handle<SniTray>,
+}
+
+impl TrayIcon {
+    pub async fn spawn(
+        options: TrayOptions,
+        events: UnboundedSender<String>,
+    ) -> Result<Self, String> {
+        let tray = SniTray { options, events };
+        let handle = tray.spawn().await.map_err(|e| e.to_string())?;
+        Ok(Self { handle })
+    }
+
+    pub fn remove(&self) {
+        // NOTE: This sends the shutdown request right away,
+        // there is no need to wait for it to be completed
+        drop(self.handle.shutdown());
+    }
+}
+
+struct SniTray {
+    options: TrayOptions,
+    events: UnboundedSender<String>,
+}
+
+impl Tray for SniTray {
+    // Clicking the icon itself shows the menu, the same as on other platforms
+    const MENU_ON_ACTIVATE: bool = true;
+
+    fn id(&self) -> String {
+        String::from("lune")
+    }
+
+    fn title(&self) -> String {
+        self.options.title.clone()
+    }
+
+    fn icon_name(&self) -> String {
+        self.options.icon.clone().unwrap_or_default()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self
+                .options
+                .tooltip
+                .clone()
+                .unwrap_or_else(|| self.options.title.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        self.options
+            .menu
+            .iter()
+            .map(|item| match item {
+                TrayMenuItem::Separator => MenuItem::Separator,
+                TrayMenuItem::Item { id, label, enabled } => {
+                    let id = id.clone();
+                    StandardItem {
+                        // NOTE: Underscores mark access keys, and must be doubled to be shown
+                        label: label.replace('_', "__"),
+                        enabled: *enabled,
+                        activate: Box::new(move |tray: &mut Self| {
+                            tray.events.send(id.clone()).ok();
+                        }),
+                        ..Default::default()
+                    }
+                    .into()
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,29 @@
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::options::TrayOptions;
+
+/**
+    A tray icon on a platform where tray icons are not supported.
+
+    On macOS, the menu bar can only be used from the main thread while it runs the
+    native event loop, which would leave no room for the Lua scheduler to run on.
+*/
+pub struct TrayIcon;
+
+impl TrayIcon {
+    #[allow(clippy::unused_async)]
+    pub async fn spawn(_: TrayOptions, _: UnboundedSender<String>) -> Result<Self, String> {
+        if cfg!(target_os = "macos") {
+            Err(String::from(
+                "tray icons are not supported on macOS, since the menu bar \
+                can only be used from the main thread of the process",
+            ))
+        } else {
+            Err(String::from(
+                "tray icons are not supported on this platform",
+            ))
+        }
+    }
+
+    pub fn remove(&self) {}
+}
@@ -0,0 +1,241 @@
+use std::{
+    iter::once,
+    mem::{size_of, zeroed},
+    ptr::{addr_of, addr_of_mut, null},
+    thread,
+};
+
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
+use windows_sys::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, POINT, WPARAM},
+    System::LibraryLoader::GetModuleHandleW,
+    UI::{
+        Shell::{
+            Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NOTIFYICONDATAW,
+        },
+        WindowsAndMessaging::{
+            AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu,
+            DestroyWindow, DispatchMessageW, GetCursorPos, GetMessageW, GetWindowLongPtrW,
+            LoadIconW, LoadImageW, PostMessageW, PostQuitMessage, RegisterClassW,
+            SetForegroundWindow, SetWindowLongPtrW, TrackPopupMenu, TranslateMessage,
+            GWLP_USERDATA, HICON, HMENU, IDI_APPLICATION, IMAGE_ICON, LR_DEFAULTSIZE,
+            LR_LOADFROMFILE, MF_GRAYED, MF_SEPARATOR, MF_STRING, MSG, TPM_BOTTOMALIGN,
+            TPM_RIGHTBUTTON, WM_APP, WM_CLOSE, WM_COMMAND, WM_DESTROY, WM_LBUTTONUP, WM_NULL,
+            WM_RBUTTONUP, WNDCLASSW,
+        },
+    },
+};
+
+use crate::options::{TrayMenuItem, TrayOptions};
+
+const WM_TRAY_ICON: u32 = WM_APP + 1;
+
+/**
+    A tray icon in the Windows notification area.
+
+    The icon and its menu belong to a hidden window, which gets a thread of its
+    own to run the message loop on, so that the Lua scheduler is not blocked.
+*/
+pub struct TrayIcon {
+    hwnd: HWND,
+}
+
+impl TrayIcon {
+    pub async fn spawn(
+        options: TrayOptions,
+        events: UnboundedSender<String>,
+    ) -> Result<Self, String> {
+        // NOTE: Creating the window happens on its own thread, and we wait for
+        // it asynchronously, so that the Lua scheduler is never blocked by it
+        let (init_tx, init_rx) = oneshot::channel();
+        thread::spawn(move || {
+            // SAFETY: The window, menu and icon are only used on this thread,
+            // and the message loop runs until the window has been destroyed
+            unsafe { run(options, events, init_tx) };
+        });
+        let hwnd = init_rx
+            .await
+            .map_err(|_| String::from("tray icon thread exited unexpectedly"))??;
+        Ok(Self { hwnd })
+    }
+
+    pub fn remove(&self) {
+        // SAFETY: Posting to a window that was already destroyed simply fails
+        unsafe { PostMessageW(self.hwnd, WM_CLOSE, 0, 0) };
+    }
+}
+
+struct WindowState {
+    menu: HMENU,
+    ids: Vec<String>,
+    events: UnboundedSender<String>,
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(once(0)).collect()
+}
+
+unsafe fn run(
+    options: TrayOptions,
+    events: UnboundedSender<String>,
+    init_tx: oneshot::Sender<Result<HWND, String>>,
+) {
+    let instance = GetModuleHandleW(null());
+    let class_name = wide("LuneTrayIcon");
+
+    // NOTE: Registering the class again, for the next icon, fails and that is fine
+    let mut class: WNDCLASSW = zeroed();
+    class.lpfnWndProc = Some(window_proc);
+    class.hInstance = instance;
+    class.lpszClassName = class_name.as_ptr();
+    RegisterClassW(addr_of!(class));
+
+    let hwnd = CreateWindowExW(
+        0,
+        class_name.as_ptr(),
+        class_name.as_ptr(),
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        instance,
+        null(),
+    );
+    if hwnd == 0 {
+        init_tx
+            .send(Err(std::io::Error::last_os_error().to_string()))
+            .ok();
+        return;
+    }
+
+    // NOTE: Menu item identifiers start at 1, since 0 means that nothing was chosen
+    let menu = CreatePopupMenu();
+    let mut ids = Vec::new();
+    for item in &options.menu {
+        match item {
+            TrayMenuItem::Separator => {
+                AppendMenuW(menu, MF_SEPARATOR, 0, null());
+            }
+            TrayMenuItem::Item { id, label, enabled } => {
+                ids.push(id.clone());
+                let flags = if *enabled {
+                    MF_STRING
+                } else {
+                    MF_STRING | MF_GRAYED
+                };
+                // Ampersands mark access keys, and must be doubled to be shown
+                let label = wide(&label.replace('&', "&&"));
+                AppendMenuW(menu, flags, ids.len(), label.as_ptr());
+            }
+        }
+    }
+    let state = Box::into_raw(Box::new(WindowState { menu, ids, events }));
+    SetWindowLongPtrW(hwnd, GWLP_USERDATA, state as isize);
+
+    let icon: HICON = match &options.icon {
+        Some(path) => LoadImageW(
+            0,
+            wide(path).as_ptr(),
+            IMAGE_ICON,
+            0,
+            0,
+            LR_LOADFROMFILE | LR_DEFAULTSIZE,
+        ),
+        None => 0,
+    };
+    let icon = if icon == 0 {
+        LoadIconW(0, IDI_APPLICATION)
+    } else {
+        icon
+    };
+
+    let mut data: NOTIFYICONDATAW = zeroed();
+    data.cbSize = size_of::<NOTIFYICONDATAW>() as u32;
+    data.hWnd = hwnd;
+    data.uID = 1;
+    data.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
+    data.uCallbackMessage = WM_TRAY_ICON;
+    data.hIcon = icon;
+    let tooltip = options.tooltip.as_deref().unwrap_or(&options.title);
+    let tooltip = tooltip.encode_utf16().take(data.szTip.len() - 1);
+    for (dst, src) in data.szTip.iter_mut().zip(tooltip) {
+        *dst = src;
+    }
+
+    if Shell_NotifyIconW(NIM_ADD, addr_of!(data)) == 0 {
+        DestroyWindow(hwnd);
+        DestroyMenu(menu);
+        drop(Box::from_raw(state));
+        init_tx
+            .send(Err(String::from("the notification area is not available")))
+            .ok();
+        return;
+    }
+    init_tx.send(Ok(hwnd)).ok();
+
+    let mut msg: MSG = zeroed();
+    while GetMessageW(addr_of_mut!(msg), 0, 0, 0) > 0 {
+        TranslateMessage(addr_of!(msg));
+        DispatchMessageW(addr_of!(msg));
+    }
+
+    // NOTE: The window has been destroyed once the message loop ends,
+    // so the state is no longer used by the window procedure either
+    Shell_NotifyIconW(NIM_DELETE, addr_of!(data));
+    DestroyMenu(menu);
+    drop(Box::from_raw(state));
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    // NOTE: The state is set right after the window is created, and
+    // freed only after the window is destroyed, so it is always valid
+    let state = (GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WindowState).as_ref();
+    match (msg, state) {
+        (WM_TRAY_ICON, Some(state)) => {
+            let event = (lparam & 0xFFFF) as u32;
+            if event == WM_LBUTTONUP || event == WM_RBUTTONUP {
+                // NOTE: The window must be in the foreground for the menu to close
+                // when clicking outside of it, and the message afterwards is needed
+                // for the menu to work correctly when opened more than once
+                let mut point: POINT = zeroed();
+                GetCursorPos(addr_of_mut!(point));
+                SetForegroundWindow(hwnd);
+                TrackPopupMenu(
+                    state.menu,
+                    TPM_RIGHTBUTTON | TPM_BOTTOMALIGN,
+                    point.x,
+                    point.y,
+                    0,
+                    hwnd,
+                    null(),
+                );
+                PostMessageW(hwnd, WM_NULL, 0, 0);
+            }
+            0
+        }
+        (WM_COMMAND, Some(state)) => {
+            let index = wparam & 0xFFFF;
+            if let Some(id) = index.checked_sub(1).and_then(|i| state.ids.get(i)) {
+                state.events.send(id.clone()).ok();
+            }
+            0
+        }
+        (WM_CLOSE, _) => {
+            DestroyWindow(hwnd);
+            0
+        }
+        (WM_DESTROY, _) => {
+            PostQuitMessage(0);
+            0
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
@@ -0,0 +1,34 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+
+use lune_utils::{
+    units::{parse_duration, parse_size},
+    TableBuilder,
+};
+
+/**
+    Creates the `units` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("parseDuration", units_parse_duration)?
+        .with_function("parseSize", units_parse_size)?
+        .build_readonly()
+}
+
+fn units_parse_duration(_: &Lua, s: String) -> LuaResult<f64> {
+    parse_duration(&s)
+        .map(|duration| duration.as_secs_f64())
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid duration '{s}' - {e}")))
+}
+
+fn units_parse_size(_: &Lua, s: String) -> LuaResult<f64> {
+    parse_size(&s)
+        .map(|bytes| bytes as f64)
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid size '{s}' - {e}")))
+}
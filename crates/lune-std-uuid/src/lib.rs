@@ -0,0 +1,61 @@
+#![allow(clippy::cargo_common_metadata)]
+
+use mlua::prelude::*;
+use uuid::{Uuid, Variant};
+
+use lune_std_datetime::DateTime;
+use lune_utils::TableBuilder;
+
+/**
+    Creates the `uuid` standard library module.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn module(lua: &Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("v4", |_, ()| Ok(Uuid::new_v4().to_string()))?
+        .with_function("v7", |_, ()| Ok(Uuid::now_v7().to_string()))?
+        .with_function("parse", uuid_parse)?
+        .with_function("isValid", |_, value: LuaString| {
+            Ok(parse_str(&value).is_ok())
+        })?
+        .build_readonly()
+}
+
+fn uuid_parse<'lua>(lua: &'lua Lua, value: LuaString<'lua>) -> LuaResult<LuaTable<'lua>> {
+    let uuid = parse_str(&value)?;
+
+    let variant = match uuid.get_variant() {
+        Variant::NCS => "ncs",
+        Variant::RFC4122 => "rfc4122",
+        Variant::Microsoft => "microsoft",
+        _ => "future",
+    };
+
+    // NOTE: Only time-based uuids have a timestamp, for all other
+    // versions the timestamp field is left out of the table entirely
+    let timestamp = match uuid.get_timestamp() {
+        Some(timestamp) => {
+            let (secs, nanos) = timestamp.to_unix();
+            #[allow(clippy::cast_precision_loss)]
+            let secs = secs as f64 + f64::from(nanos) / 1_000_000_000.0;
+            Some(DateTime::from_unix_timestamp_float(secs).into_lua_err()?)
+        }
+        None => None,
+    };
+
+    TableBuilder::new(lua)?
+        .with_value("uuid", uuid.to_string())?
+        .with_value("version", uuid.get_version_num())?
+        .with_value("variant", variant)?
+        .with_value("timestamp", timestamp)?
+        .build_readonly()
+}
+
+fn parse_str(value: &LuaString) -> LuaResult<Uuid> {
+    let value = value.to_str()?;
+    Uuid::try_parse(value)
+        .map_err(|e| LuaError::RuntimeError(format!("Invalid uuid '{value}' - {e}")))
+}
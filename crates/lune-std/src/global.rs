@@ -8,6 +8,7 @@ use mlua::prelude::*;
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum LuneStandardGlobal {
     GTable,
+    Lune,
     Print,
     Require,
     Version,
@@ -20,6 +21,7 @@ impl LuneStandardGlobal {
     */
     pub const ALL: &'static [Self] = &[
         Self::GTable,
+        Self::Lune,
         Self::Print,
         Self::Require,
         Self::Version,
@@ -33,6 +35,7 @@ impl LuneStandardGlobal {
     pub fn name(&self) -> &'static str {
         match self {
             Self::GTable => "_G",
+            Self::Lune => "lune",
             Self::Print => "print",
             Self::Require => "require",
             Self::Version => "_VERSION",
@@ -52,6 +55,7 @@ impl LuneStandardGlobal {
     pub fn create<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
         let res = match self {
             Self::GTable => crate::globals::g_table::create(lua),
+            Self::Lune => crate::globals::lune::create(lua),
             Self::Print => crate::globals::print::create(lua),
             Self::Require => crate::globals::require::create(lua),
             Self::Version => crate::globals::version::create(lua),
@@ -73,6 +77,7 @@ impl FromStr for LuneStandardGlobal {
         let low = s.trim().to_ascii_lowercase();
         Ok(match low.as_str() {
             "_g" => Self::GTable,
+            "lune" => Self::Lune,
             "print" => Self::Print,
             "require" => Self::Require,
             "_version" => Self::Version,
@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+use mlua::prelude::*;
+
+use lune_utils::TableBuilder;
+
+use crate::library::LuneStandardLibrary;
+
+use super::require::{register_lua_resolver, require_library};
+
+use super::version::runtime_version;
+
+/*
+    Names of features that are not the name of a library member, mapped
+    to the library member that the feature is provided by.
+
+    Any other features are checked by looking up the member directly, so
+    `net.socket` is available if the `net` library has a `socket` member.
+*/
+const FEATURE_ALIASES: &[(&str, &str)] = &[("net.websocket", "net.socket")];
+
+pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
+    TableBuilder::new(lua)?
+        .with_value("version", runtime_version(lua))?
+        .with_function("hasFeature", has_feature)?
+        .with_function("registerResolver", register_resolver)?
+        .build_readonly()?
+        .into_lua(lua)
+}
+
+fn has_feature(lua: &Lua, feature: String) -> LuaResult<bool> {
+    let feature = feature.trim();
+    let feature = FEATURE_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(feature))
+        .map_or(feature, |(_, target)| target);
+
+    let mut path = feature.split('.');
+    let Some(library) = path
+        .next()
+        .and_then(|name| LuneStandardLibrary::from_str(name).ok())
+    else {
+        return Ok(false);
+    };
+
+    // NOTE: Libraries are only ever compiled in or not, so they can be checked
+    // without loading them, and members are checked using the same library
+    // that `require` returns, which is loaded once and then cached
+    let mut path = path.peekable();
+    if path.peek().is_none() {
+        return Ok(true);
+    }
+
+    let mut value = require_library(lua, library)?
+        .into_iter()
+        .next()
+        .unwrap_or(LuaNil);
+    for key in path {
+        value = match value {
+            LuaValue::Table(table) => table.raw_get(key)?,
+            _ => return Ok(false),
+        };
+    }
+    Ok(!value.is_nil())
+}
+
+fn register_resolver(lua: &Lua, (prefix, resolver): (String, LuaFunction)) -> LuaResult<()> {
+    register_lua_resolver(lua, prefix, resolver)
+}
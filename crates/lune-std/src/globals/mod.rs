@@ -1,4 +1,5 @@
 pub mod g_table;
+pub mod lune;
 pub mod print;
 pub mod require;
 pub mod version;
@@ -1,6 +1,7 @@
-use std::io::Write;
-
-use lune_utils::fmt::{pretty_format_multi_value, ValueFormatConfig};
+use lune_utils::{
+    fmt::{pretty_format_multi_value, ValueFormatConfig},
+    output::{write_output, OutputStream},
+};
 use mlua::prelude::*;
 
 const FORMAT_CONFIG: ValueFormatConfig = ValueFormatConfig::new()
@@ -8,11 +9,12 @@ const FORMAT_CONFIG: ValueFormatConfig = ValueFormatConfig::new()
     .with_colors_enabled(true);
 
 pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
-    let f = lua.create_function(|_, args: LuaMultiValue| {
-        let formatted = format!("{}\n", pretty_format_multi_value(&args, &FORMAT_CONFIG));
-        let mut stdout = std::io::stdout();
-        stdout.write_all(formatted.as_bytes())?;
-        stdout.flush()?;
+    let f = lua.create_function(|lua, args: LuaMultiValue| {
+        let formatted = format!(
+            "{}\n",
+            pretty_format_multi_value(lua, &args, &FORMAT_CONFIG)
+        );
+        write_output(lua, OutputStream::Stdout, formatted.as_bytes())?;
         Ok(())
     })?;
     f.into_lua(lua)
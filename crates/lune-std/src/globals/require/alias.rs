@@ -1,15 +1,19 @@
+use std::path::PathBuf;
+
 use mlua::prelude::*;
 
-use lune_utils::path::{clean_path_and_make_absolute, diff_path, get_current_dir};
+use lune_utils::path::{clean_path, clean_path_and_make_absolute, diff_path, get_current_dir};
 
 use crate::luaurc::LuauRc;
 
 use super::context::*;
 
+const SELF_ALIAS: &str = "self";
+
 pub(super) async fn require<'lua, 'ctx>(
     lua: &'lua Lua,
     ctx: &'ctx RequireContext,
-    source: &str,
+    caller: &RequireCaller,
     alias: &str,
     path: &str,
 ) -> LuaResult<LuaMultiValue<'lua>>
@@ -18,16 +22,31 @@ where
 {
     let alias = alias.to_ascii_lowercase();
 
-    let parent = clean_path_and_make_absolute(source)
+    let parent = clean_path_and_make_absolute(&caller.source)
         .parent()
         .expect("how did a root path end up here..")
         .to_path_buf();
 
+    // The self alias always refers to the directory of the calling
+    // file, and can not be overridden by any .luaurc file
+    if alias == SELF_ALIAS {
+        return require_aliased(lua, ctx, caller, &alias, parent, path).await;
+    }
+
     // Try to gather the first luaurc and / or error we
     // encounter to display better error messages to users
     let mut first_luaurc = None;
     let mut first_error = None;
-    let predicate = |rc: &LuauRc| {
+    let predicate = |result: &Result<LuauRc, String>| {
+        let rc = match result {
+            Ok(rc) => rc,
+            Err(e) => {
+                if first_error.is_none() {
+                    first_error.replace(e.clone());
+                }
+                return false;
+            }
+        };
         if first_luaurc.is_none() {
             first_luaurc.replace(rc.clone());
         }
@@ -62,12 +81,31 @@ where
             }
         })?;
 
+    let aliased = luaurc.find_alias(&alias).unwrap();
+    require_aliased(lua, ctx, caller, &alias, aliased, path).await
+}
+
+async fn require_aliased<'lua, 'ctx>(
+    lua: &'lua Lua,
+    ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
+    alias: &str,
+    aliased: PathBuf,
+    path: &str,
+) -> LuaResult<LuaMultiValue<'lua>>
+where
+    'lua: 'ctx,
+{
     // We now have our aliased path, our path require function just needs it
     // in a slightly different format with both absolute + relative to cwd
-    let abs_path = luaurc.find_alias(&alias).unwrap().join(path);
+    let abs_path = if path.is_empty() {
+        aliased
+    } else {
+        clean_path(aliased.join(path))
+    };
     let rel_path = diff_path(&abs_path, get_current_dir()).ok_or_else(|| {
         LuaError::runtime(format!("failed to find relative path for alias '{alias}'"))
     })?;
 
-    super::path::require_abs_rel(lua, ctx, abs_path, rel_path).await
+    super::path::require_abs_rel(lua, ctx, caller, abs_path, rel_path).await
 }
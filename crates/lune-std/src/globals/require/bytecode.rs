@@ -0,0 +1,217 @@
+use std::{
+    ffi::OsStr,
+    fs::FileTimes,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use mlua::prelude::*;
+use mlua::{ChunkMode, Compiler as LuaCompiler};
+
+use tokio::{
+    fs::{create_dir_all, read, remove_file, rename, write},
+    task::spawn_blocking,
+};
+
+use super::coverage::{is_coverage_enabled, track_coverage};
+
+// Bumped whenever the way that bytecode is compiled or stored changes,
+// so that cached bytecode from older versions of Lune is never used
+const CACHE_FORMAT: &str = concat!("lune-bytecode-1-", env!("CARGO_PKG_VERSION"));
+
+const CACHE_EXTENSION: &str = "luauc";
+
+// Maximum total size of all cached bytecode, unless changed using `set_bytecode_cache_max_size`
+const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+struct BytecodeCache(PathBuf);
+
+struct BytecodeCacheMaxSize(u64);
+
+/**
+    Enables caching of compiled bytecode for required modules, in the given directory.
+
+    Modules are cached by the hash of their contents, meaning that a module which
+    has not changed since it was last required - even in a previous run - will not be
+    compiled again. Modules that are modified will be compiled and cached as usual.
+
+    Reading from or writing to the cache never causes a `require` to fail, if anything
+    goes wrong with a cached module it will be compiled from its source code instead.
+
+    Once the cache grows larger than its maximum size, 64 MiB by default, the modules
+    that were least recently used are removed from it, see [`set_bytecode_cache_max_size`].
+*/
+pub fn set_bytecode_cache_dir(lua: &Lua, dir: impl Into<PathBuf>) {
+    lua.set_app_data(BytecodeCache(dir.into()));
+}
+
+/**
+    Sets the maximum total size, in bytes, of all bytecode in the bytecode cache.
+
+    The size is checked whenever a module is added to the cache, removing
+    the modules that were least recently used until it fits again.
+*/
+pub fn set_bytecode_cache_max_size(lua: &Lua, max_size: u64) {
+    lua.set_app_data(BytecodeCacheMaxSize(max_size));
+}
+
+/**
+    Loads the given module contents into a function, using the
+    bytecode cache if one has been enabled using [`set_bytecode_cache_dir`].
+
+    The bytecode cache is skipped while coverage is enabled, and the
+    loaded function is instead tracked for coverage, see [`track_coverage`].
+*/
+pub(super) async fn load_function<'lua>(
+    lua: &'lua Lua,
+    name: String,
+    contents: Vec<u8>,
+) -> LuaResult<LuaFunction<'lua>> {
+    if is_coverage_enabled(lua) {
+        let function = lua.load(contents).set_name(name.clone()).into_function()?;
+        track_coverage(lua, name, &function)?;
+        return Ok(function);
+    }
+
+    let Some(dir) = lua.app_data_ref::<BytecodeCache>().map(|c| c.0.clone()) else {
+        return lua.load(contents).set_name(name).into_function();
+    };
+
+    let hash = {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(CACHE_FORMAT.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&contents);
+        hasher.finalize().to_hex()
+    };
+    let path = dir.join(hash.as_str()).with_extension(CACHE_EXTENSION);
+
+    // Try to use any existing bytecode first, but if it turns out to be invalid,
+    // for example if the file was corrupted, we fall through and overwrite it
+    if let Ok(cached) = read(&path).await {
+        if let Some(bytecode) = verify_cached(&cached) {
+            let loaded = lua
+                .load(bytecode)
+                .set_name(name.clone())
+                .set_mode(ChunkMode::Binary)
+                .into_function();
+            if let Ok(function) = loaded {
+                spawn_blocking(move || mark_used(&path)).await.ok();
+                return Ok(function);
+            }
+        }
+    }
+
+    // NOTE: The default compiler options are the same as the ones Luau
+    // uses when loading source code, so the resulting functions are identical
+    let bytecode = LuaCompiler::default().compile(&contents);
+
+    // Bytecode starting with a zero byte contains a syntax error instead of a
+    // compiled module, loading the source will give a proper error message for it
+    if bytecode.first() == Some(&0) {
+        return lua.load(contents).set_name(name).into_function();
+    }
+
+    let function = lua
+        .load(&bytecode)
+        .set_name(name)
+        .set_mode(ChunkMode::Binary)
+        .into_function()?;
+
+    let max_size = lua
+        .app_data_ref::<BytecodeCacheMaxSize>()
+        .map_or(DEFAULT_MAX_SIZE, |m| m.0);
+    write_atomic(&dir, path.clone(), bytecode).await;
+
+    // NOTE: Listing the cache takes many small filesystem operations, which
+    // are all done at once on a blocking thread instead of one at a time
+    spawn_blocking(move || evict_entries(&dir, &path, max_size))
+        .await
+        .ok();
+
+    Ok(function)
+}
+
+// Luau does not fully validate bytecode while loading it, so cached files start
+// with a checksum of the bytecode, making sure that they are never loaded if
+// they were truncated or otherwise modified after being written to the cache
+fn verify_cached(cached: &[u8]) -> Option<&[u8]> {
+    if cached.len() <= blake3::OUT_LEN {
+        return None;
+    }
+    let (checksum, bytecode) = cached.split_at(blake3::OUT_LEN);
+    (blake3::hash(bytecode).as_bytes() == checksum).then_some(bytecode)
+}
+
+// Writes to a temporary file first and then renames it, so that
+// other processes reading from the cache never see partial files
+async fn write_atomic(dir: &Path, path: PathBuf, bytecode: Vec<u8>) {
+    let temp_path = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut contents = blake3::hash(&bytecode).as_bytes().to_vec();
+    contents.extend(bytecode);
+    let written = async {
+        create_dir_all(dir).await?;
+        write(&temp_path, contents).await?;
+        rename(&temp_path, &path).await
+    }
+    .await;
+    if written.is_err() {
+        remove_file(&temp_path).await.ok();
+    }
+}
+
+// Entries are never written again once cached, so the access time is what tells us
+// when they were last used, and we set it ourselves since it is often not updated
+fn mark_used(path: &Path) {
+    if let Ok(file) = std::fs::File::options().write(true).open(path) {
+        file.set_times(FileTimes::new().set_accessed(SystemTime::now()))
+            .ok();
+    }
+}
+
+// Removes the least recently used entries, other than the one that was just written,
+// until all of the entries left in the cache fit within its maximum size
+fn evict_entries(dir: &Path, written: &Path, max_size: u64) {
+    let Ok(dir_entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries = Vec::new();
+    let mut total_size = 0;
+    for entry in dir_entries.flatten() {
+        let path = entry.path();
+        if path.extension() != Some(OsStr::new(CACHE_EXTENSION)) {
+            continue;
+        }
+        // NOTE: File times are often less precise than the current time, so the
+        // entry that we just wrote may seem to be as old as some other entries
+        if path == written {
+            total_size += entry.metadata().map_or(0, |meta| meta.len());
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        let used_at = meta.accessed().or_else(|_| meta.modified());
+        total_size += meta.len();
+        entries.push((used_at.unwrap_or(SystemTime::UNIX_EPOCH), meta.len(), path));
+    }
+    if total_size <= max_size {
+        return;
+    }
+    entries.sort();
+    for (_, size, path) in entries {
+        if total_size <= max_size {
+            break;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total_size = total_size.saturating_sub(size);
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
+    cell::Cell,
     collections::HashMap,
+    future::Future,
     path::{Path, PathBuf},
+    rc::Rc,
     sync::Arc,
 };
 
+use futures_util::StreamExt;
 use mlua::prelude::*;
-use mlua_luau_scheduler::LuaSchedulerExt;
+use mlua_luau_scheduler::{LuaSchedulerExt, LuaSpawnExt};
 
 use tokio::{
     fs::read,
@@ -19,6 +23,33 @@ use lune_utils::path::{clean_path, clean_path_and_make_absolute};
 
 use crate::library::LuneStandardLibrary;
 
+use super::bytecode;
+
+/**
+    The location of a `require` call, used for diagnostics.
+*/
+#[derive(Debug, Clone)]
+pub(super) struct RequireCaller {
+    pub source: String,
+    pub line: Option<usize>,
+}
+
+impl RequireCaller {
+    fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{line}", self.source),
+            None => self.source.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingRequire {
+    sender: Sender<()>,
+    rel_path: PathBuf,
+    caller: RequireCaller,
+}
+
 /**
     Context containing cached results for all `require` operations.
 
@@ -29,7 +60,8 @@ use crate::library::LuneStandardLibrary;
 pub(super) struct RequireContext {
     libraries: Arc<AsyncMutex<HashMap<LuneStandardLibrary, LuaResult<LuaRegistryKey>>>>,
     results: Arc<AsyncMutex<HashMap<PathBuf, LuaResult<LuaRegistryKey>>>>,
-    pending: Arc<AsyncMutex<HashMap<PathBuf, Sender<()>>>>,
+    pending: Arc<AsyncMutex<HashMap<PathBuf, PendingRequire>>>,
+    blocking: Rc<Cell<usize>>,
 }
 
 impl RequireContext {
@@ -45,9 +77,30 @@ impl RequireContext {
             libraries: Arc::new(AsyncMutex::new(HashMap::new())),
             results: Arc::new(AsyncMutex::new(HashMap::new())),
             pending: Arc::new(AsyncMutex::new(HashMap::new())),
+            blocking: Rc::new(Cell::new(0)),
         }
     }
 
+    /**
+        Checks if a `require` is currently blocking the scheduler, meaning
+        that modules must be loaded without going through the scheduler.
+    */
+    pub fn is_blocking(&self) -> bool {
+        self.blocking.get() > 0
+    }
+
+    /**
+        Runs the given future to completion while blocking the scheduler.
+
+        This is used for `require` calls made from contexts that can not yield, such as metamethods.
+    */
+    pub fn block_on<F: Future>(&self, lua: &Lua, fut: F) -> F::Output {
+        self.blocking.set(self.blocking.get() + 1);
+        let output = lua.block_on(fut);
+        self.blocking.set(self.blocking.get() - 1);
+        output
+    }
+
     /**
         Resolves the given `source` and `path` into require paths
         to use, based on the current require context settings.
@@ -60,10 +113,17 @@ impl RequireContext {
         source: impl AsRef<str>,
         path: impl AsRef<str>,
     ) -> LuaResult<(PathBuf, PathBuf)> {
-        let path = PathBuf::from(source.as_ref())
-            .parent()
-            .ok_or_else(|| LuaError::runtime("Failed to get parent path of source"))?
-            .join(path.as_ref());
+        // NOTE: Virtual modules do not exist on disk, so any paths
+        // required from them are relative to the current directory
+        let source = source.as_ref();
+        let path = if source.starts_with("@virtual/") {
+            PathBuf::from(path.as_ref())
+        } else {
+            PathBuf::from(source)
+                .parent()
+                .ok_or_else(|| LuaError::runtime("Failed to get parent path of source"))?
+                .join(path.as_ref())
+        };
 
         let abs_path = clean_path_and_make_absolute(&path);
         let rel_path = clean_path(path);
@@ -95,6 +155,56 @@ impl RequireContext {
         Ok(is_pending)
     }
 
+    /**
+        Checks if requiring the given pending path from the given caller would create a
+        cycle, where the pending path is (indirectly) waiting for the caller to finish.
+
+        Returns an error message describing the full chain of requires if so.
+    */
+    pub fn find_cycle(
+        &self,
+        caller: &RequireCaller,
+        abs_path: impl AsRef<Path>,
+        rel_path: impl AsRef<Path>,
+    ) -> Option<String> {
+        let abs_path = abs_path.as_ref();
+        let pending = self
+            .pending
+            .try_lock()
+            .expect("RequireContext may not be used from multiple threads");
+
+        // Walk from the caller towards the root of the require chain, every
+        // pending module stores the caller that first started requiring it
+        let mut chain = vec![(caller, rel_path.as_ref())];
+        let mut current = clean_path_and_make_absolute(&caller.source);
+        while current != abs_path {
+            let next = pending.get(&current)?;
+            if chain.len() > pending.len() {
+                return None;
+            }
+            chain.push((&next.caller, &next.rel_path));
+            current = clean_path_and_make_absolute(&next.caller.source);
+        }
+        chain.reverse();
+
+        let mut modules = vec![chain[0].0.source.clone()];
+        modules.extend(chain.iter().map(|(_, path)| path.display().to_string()));
+        let requires = chain
+            .iter()
+            .map(|(caller, path)| {
+                format!("    {} requires '{}'", caller.location(), path.display())
+            })
+            .collect::<Vec<_>>();
+
+        Some(format!(
+            "Circular require detected: {}\n{}\n\
+            Consider moving one of these requires into the function that uses it, \
+            so that it runs after the module has finished loading",
+            modules.join(" -> "),
+            requires.join("\n")
+        ))
+    }
+
     /**
         Gets the resulting value from the require cache.
 
@@ -139,10 +249,10 @@ impl RequireContext {
                 .pending
                 .try_lock()
                 .expect("RequireContext may not be used from multiple threads");
-            let thread_id = pending
+            let pending_require = pending
                 .get(abs_path.as_ref())
                 .expect("Path is not currently pending require");
-            thread_id.subscribe()
+            pending_require.sender.subscribe()
         };
 
         thread_recv.recv().await.into_lua_err()?;
@@ -155,22 +265,43 @@ impl RequireContext {
         lua: &'lua Lua,
         abs_path: impl AsRef<Path>,
         rel_path: impl AsRef<Path>,
+        contents: Option<Vec<u8>>,
     ) -> LuaResult<LuaRegistryKey> {
         let abs_path = abs_path.as_ref();
         let rel_path = rel_path.as_ref();
 
-        // Read the file at the given path, try to parse and
-        // load it into a new lua thread that we can schedule
-        let file_contents = read(&abs_path).await?;
-        let file_thread = lua
-            .load(file_contents)
-            .set_name(rel_path.to_string_lossy().to_string());
-
-        // Schedule the thread to run, wait for it to finish running
-        let thread_id = lua.push_thread_back(file_thread, ())?;
-        lua.track_thread(thread_id);
-        lua.wait_for_thread(thread_id).await;
-        let thread_res = lua.get_thread_result(thread_id).unwrap();
+        // Read the file at the given path, unless we were given its contents,
+        // try to parse and load it into a new lua thread that we can schedule
+        let file_contents = match contents {
+            Some(contents) => contents,
+            None => read(&abs_path).await?,
+        };
+        let file_thread =
+            bytecode::load_function(lua, rel_path.to_string_lossy().to_string(), file_contents)
+                .await?;
+
+        // Schedule the thread to run, wait for it to finish running - if the scheduler
+        // is currently blocked by another require, run the thread here directly instead
+        let thread_res = if self.is_blocking() {
+            let thread = lua.create_thread(file_thread)?;
+            let res = thread
+                .clone()
+                .into_async::<_, LuaMultiValue>(())
+                .next()
+                .await;
+            if thread.status() == LuaThreadStatus::Resumable {
+                return Err(LuaError::runtime(format!(
+                    "Module '{}' yielded to the scheduler while being required from a context that can not yield",
+                    rel_path.display()
+                )));
+            }
+            res.unwrap_or_else(|| Ok(LuaMultiValue::new()))
+        } else {
+            let thread_id = lua.push_thread_back(file_thread, ())?;
+            lua.track_thread(thread_id);
+            lua.wait_for_thread(thread_id).await;
+            lua.get_thread_result(thread_id).unwrap()
+        };
 
         // Return the result of the thread, storing any lua value(s) in the registry
         match thread_res {
@@ -187,12 +318,17 @@ impl RequireContext {
 
     /**
         Loads (requires) the file at the given path.
+
+        If `contents` are given, those are loaded instead of reading the file, and
+        the given path is only used as the key for caching and in error messages.
     */
     pub async fn load_with_caching<'lua>(
         &self,
         lua: &'lua Lua,
+        caller: &RequireCaller,
         abs_path: impl AsRef<Path>,
         rel_path: impl AsRef<Path>,
+        contents: Option<Vec<u8>>,
     ) -> LuaResult<LuaMultiValue<'lua>> {
         let abs_path = abs_path.as_ref();
         let rel_path = rel_path.as_ref();
@@ -202,10 +338,17 @@ impl RequireContext {
         self.pending
             .try_lock()
             .expect("RequireContext may not be used from multiple threads")
-            .insert(abs_path.to_path_buf(), broadcast_tx);
+            .insert(
+                abs_path.to_path_buf(),
+                PendingRequire {
+                    sender: broadcast_tx,
+                    rel_path: rel_path.to_path_buf(),
+                    caller: caller.clone(),
+                },
+            );
 
         // Try to load at this abs path
-        let load_res = self.load(lua, abs_path, rel_path).await;
+        let load_res = self.load(lua, abs_path, rel_path, contents).await;
         let load_val = match &load_res {
             Err(e) => Err(e.clone()),
             Ok(k) => {
@@ -227,13 +370,13 @@ impl RequireContext {
         // Remove the pending thread id from the require context,
         // broadcast a message to let any listeners know that this
         // path has now finished the require process and is cached
-        let broadcast_tx = self
+        let pending_require = self
             .pending
             .try_lock()
             .expect("RequireContext may not be used from multiple threads")
             .remove(abs_path)
             .expect("Pending require broadcaster was unexpectedly removed");
-        broadcast_tx.send(()).ok();
+        pending_require.sender.send(()).ok();
 
         load_val
     }
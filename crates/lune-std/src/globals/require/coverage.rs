@@ -0,0 +1,87 @@
+use mlua::prelude::*;
+use mlua::Compiler as LuaCompiler;
+
+#[derive(Default)]
+struct CoverageFunctions(Vec<(String, LuaRegistryKey)>);
+
+/**
+    Line coverage for a single function that has been run.
+*/
+#[derive(Debug, Clone)]
+pub struct FunctionCoverage {
+    /// The name of the chunk that the function was loaded from.
+    pub chunk: String,
+    /// The name of the function, if it has one.
+    pub function: Option<String>,
+    /// The line that the function was defined on.
+    pub line_defined: i32,
+    /// The number of times each line has run, indexed by line, or `-1` for lines without any code.
+    pub hits: Vec<i32>,
+}
+
+/**
+    Enables line coverage for all code that is loaded from now on, including required modules.
+
+    Compiled bytecode can not be cached while coverage is enabled, since
+    cached bytecode is compiled without the instructions that record coverage.
+*/
+pub fn enable_coverage(lua: &Lua) {
+    lua.set_compiler(LuaCompiler::default().set_coverage_level(1));
+    lua.set_app_data(CoverageFunctions::default());
+}
+
+pub(super) fn is_coverage_enabled(lua: &Lua) -> bool {
+    lua.app_data_ref::<CoverageFunctions>().is_some()
+}
+
+/**
+    Tracks coverage for the given chunk function and all functions defined inside of it.
+
+    Does nothing if coverage has not been enabled using [`enable_coverage`].
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn track_coverage(
+    lua: &Lua,
+    chunk: impl Into<String>,
+    function: &LuaFunction,
+) -> LuaResult<()> {
+    if !is_coverage_enabled(lua) {
+        return Ok(());
+    }
+    let key = lua.create_registry_value(function.clone())?;
+    if let Some(mut functions) = lua.app_data_mut::<CoverageFunctions>() {
+        functions.0.push((chunk.into(), key));
+    }
+    Ok(())
+}
+
+/**
+    Collects the current line coverage for all tracked chunks.
+
+    Hit counts are totals since each chunk was loaded,
+    and functions that have never run are not included.
+*/
+#[must_use]
+pub fn collect_coverage(lua: &Lua) -> Vec<FunctionCoverage> {
+    let Some(functions) = lua.app_data_ref::<CoverageFunctions>() else {
+        return Vec::new();
+    };
+    let mut coverage = Vec::new();
+    for (chunk, key) in &functions.0 {
+        let Ok(function) = lua.registry_value::<LuaFunction>(key) else {
+            continue;
+        };
+        function.coverage(|info| {
+            coverage.push(FunctionCoverage {
+                chunk: chunk.clone(),
+                function: info.function,
+                line_defined: info.line_defined,
+                hits: info.hits,
+            });
+        });
+    }
+    coverage
+}
@@ -1,5 +1,7 @@
 use mlua::prelude::*;
 
+use crate::library::LuneStandardLibrary;
+
 use super::context::*;
 
 pub(super) fn require<'lua, 'ctx>(
@@ -12,3 +14,20 @@ where
 {
     ctx.load_library(lua, name)
 }
+
+/**
+    Loads the given library the same way that `require` does, using
+    the same cache, so that it is only ever created once.
+
+    Without the `require` global, there is no cache, and the library is created directly.
+*/
+pub(crate) fn require_library(
+    lua: &Lua,
+    library: LuneStandardLibrary,
+) -> LuaResult<LuaMultiValue<'_>> {
+    let context = lua.app_data_ref::<RequireContext>().map(|ctx| ctx.clone());
+    match context {
+        Some(context) => context.load_library(lua, library.name()),
+        None => library.module(lua),
+    }
+}
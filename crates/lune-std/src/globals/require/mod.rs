@@ -3,14 +3,29 @@ use mlua::prelude::*;
 use lune_utils::TableBuilder;
 
 mod context;
-use context::RequireContext;
+use context::{RequireCaller, RequireContext};
 
 mod alias;
+mod bytecode;
+mod coverage;
 mod library;
 mod path;
+mod resolver;
+mod virtual_module;
+
+pub use bytecode::{set_bytecode_cache_dir, set_bytecode_cache_max_size};
+pub use coverage::{collect_coverage, enable_coverage, track_coverage, FunctionCoverage};
+pub(crate) use library::require_library;
+pub(crate) use resolver::register_lua_resolver;
+pub use resolver::{register_require_resolver, ResolvedModule};
+pub use virtual_module::{register_virtual_module, VirtualModule};
 
 const REQUIRE_IMPL: &str = r"
-return require(source(), ...)
+if isyieldable() then
+    return require(source(), line(), ...)
+else
+    return requireBlocking(source(), line(), ...)
+end
 ";
 
 pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
@@ -33,6 +48,10 @@ pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
         1. The current c / rust function
         2. The wrapper lua chunk defined above
         3. The lua chunk we are require-ing from
+
+        Requiring from a context that can not yield, such as a metamethod, also can't
+        use the async require function at all - in that case we instead block and
+        drive the required module to completion from a normal (blocking) function
     */
 
     let require_fn = lua.create_async_function(require)?;
@@ -47,10 +66,23 @@ pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
             Some(source) => lua.create_string(source.as_bytes()),
         },
     })?;
+    let get_line_fn = lua.create_function(move |lua, (): ()| {
+        let line = lua.inspect_stack(2).map(|info| info.curr_line());
+        Ok(line.and_then(|line| usize::try_from(line).ok()))
+    })?;
+
+    let require_blocking_fn = lua.create_function(require_blocking)?;
+    let is_yieldable_fn = lua
+        .globals()
+        .get::<_, LuaTable>("coroutine")?
+        .get::<_, LuaFunction>("isyieldable")?;
 
     let require_env = TableBuilder::new(lua)?
         .with_value("source", get_source_fn)?
+        .with_value("line", get_line_fn)?
         .with_value("require", require_fn)?
+        .with_value("requireBlocking", require_blocking_fn)?
+        .with_value("isyieldable", is_yieldable_fn)?
         .build_readonly()?;
 
     lua.load(REQUIRE_IMPL)
@@ -60,9 +92,20 @@ pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
         .into_lua(lua)
 }
 
+fn require_blocking<'lua>(
+    lua: &'lua Lua,
+    (source, line, path): (LuaString<'lua>, Option<usize>, LuaString<'lua>),
+) -> LuaResult<LuaMultiValue<'lua>> {
+    let context = lua
+        .app_data_ref::<RequireContext>()
+        .expect("Failed to get RequireContext from app data")
+        .clone();
+    context.block_on(lua, require(lua, (source, line, path)))
+}
+
 async fn require<'lua>(
     lua: &'lua Lua,
-    (source, path): (LuaString<'lua>, LuaString<'lua>),
+    (source, line, path): (LuaString<'lua>, Option<usize>, LuaString<'lua>),
 ) -> LuaResult<LuaMultiValue<'lua>> {
     let source = source
         .to_str()
@@ -79,15 +122,18 @@ async fn require<'lua>(
     let context = lua
         .app_data_ref()
         .expect("Failed to get RequireContext from app data");
+    let caller = RequireCaller { source, line };
 
     if let Some(builtin_name) = path.strip_prefix("@lune/").map(str::to_ascii_lowercase) {
         library::require(lua, &context, &builtin_name)
+    } else if let Some(resolved) = resolver::require(lua, &context, &caller, &path).await? {
+        Ok(resolved)
+    } else if let Some(virtual_name) = path.strip_prefix("@virtual/") {
+        virtual_module::require(lua, &context, &caller, virtual_name).await
     } else if let Some(aliased_path) = path.strip_prefix('@') {
-        let (alias, path) = aliased_path.split_once('/').ok_or(LuaError::runtime(
-            "Require with custom alias must contain '/' delimiter",
-        ))?;
-        alias::require(lua, &context, &source, alias, path).await
+        let (alias, path) = aliased_path.split_once('/').unwrap_or((aliased_path, ""));
+        alias::require(lua, &context, &caller, alias, path).await
     } else {
-        path::require(lua, &context, &source, &path).await
+        path::require(lua, &context, &caller, &path).await
     }
 }
@@ -8,19 +8,20 @@ use super::context::*;
 pub(super) async fn require<'lua, 'ctx>(
     lua: &'lua Lua,
     ctx: &'ctx RequireContext,
-    source: &str,
+    caller: &RequireCaller,
     path: &str,
 ) -> LuaResult<LuaMultiValue<'lua>>
 where
     'lua: 'ctx,
 {
-    let (abs_path, rel_path) = RequireContext::resolve_paths(source, path)?;
-    require_abs_rel(lua, ctx, abs_path, rel_path).await
+    let (abs_path, rel_path) = RequireContext::resolve_paths(&caller.source, path)?;
+    require_abs_rel(lua, ctx, caller, abs_path, rel_path).await
 }
 
 pub(super) async fn require_abs_rel<'lua, 'ctx>(
     lua: &'lua Lua,
     ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
     abs_path: PathBuf, // Absolute to filesystem
     rel_path: PathBuf, // Relative to CWD (for displaying)
 ) -> LuaResult<LuaMultiValue<'lua>>
@@ -28,7 +29,7 @@ where
     'lua: 'ctx,
 {
     // 1. Try to require the exact path
-    match require_inner(lua, ctx, &abs_path, &rel_path).await {
+    match require_inner(lua, ctx, caller, &abs_path, &rel_path).await {
         Ok(res) => return Ok(res),
         Err(err) => {
             if !is_file_not_found_error(&err) {
@@ -43,6 +44,7 @@ where
         match require_inner(
             lua,
             ctx,
+            caller,
             &append_extension(&abs_path, extension),
             &append_extension(&rel_path, extension),
         )
@@ -68,6 +70,7 @@ where
         match require_inner(
             lua,
             ctx,
+            caller,
             &append_extension(&abs_init, extension),
             &append_extension(&rel_init, extension),
         )
@@ -92,9 +95,42 @@ where
 async fn require_inner<'lua, 'ctx>(
     lua: &'lua Lua,
     ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
     abs_path: impl AsRef<Path>,
     rel_path: impl AsRef<Path>,
 ) -> LuaResult<LuaMultiValue<'lua>>
+where
+    'lua: 'ctx,
+{
+    require_cached(lua, ctx, caller, abs_path, rel_path, None).await
+}
+
+/**
+    Requires a module with the given contents, instead of reading them from
+    a file, using the given paths for caching and displaying in errors.
+*/
+pub(super) async fn require_with_contents<'lua, 'ctx>(
+    lua: &'lua Lua,
+    ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
+    abs_path: PathBuf,
+    rel_path: PathBuf,
+    contents: Vec<u8>,
+) -> LuaResult<LuaMultiValue<'lua>>
+where
+    'lua: 'ctx,
+{
+    require_cached(lua, ctx, caller, abs_path, rel_path, Some(contents)).await
+}
+
+async fn require_cached<'lua, 'ctx>(
+    lua: &'lua Lua,
+    ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
+    abs_path: impl AsRef<Path>,
+    rel_path: impl AsRef<Path>,
+    contents: Option<Vec<u8>>,
+) -> LuaResult<LuaMultiValue<'lua>>
 where
     'lua: 'ctx,
 {
@@ -104,9 +140,23 @@ where
     if ctx.is_cached(abs_path)? {
         ctx.get_from_cache(lua, abs_path)
     } else if ctx.is_pending(abs_path)? {
+        // NOTE: Waiting for a module that is (indirectly) waiting for
+        // this same require to finish would never complete, so error
+        if let Some(cycle) = ctx.find_cycle(caller, abs_path, rel_path) {
+            return Err(LuaError::runtime(cycle));
+        }
+        // NOTE: A pending require is driven by the scheduler, which
+        // can not make progress while it is blocked by another require
+        if ctx.is_blocking() {
+            return Err(LuaError::runtime(format!(
+                "Module '{}' is already being required by another thread, and can not be waited for from a context that can not yield",
+                rel_path.display()
+            )));
+        }
         ctx.wait_for_cache(lua, &abs_path).await
     } else {
-        ctx.load_with_caching(lua, &abs_path, &rel_path).await
+        ctx.load_with_caching(lua, caller, &abs_path, &rel_path, contents)
+            .await
     }
 }
 
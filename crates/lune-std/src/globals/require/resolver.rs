@@ -0,0 +1,257 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use mlua::prelude::*;
+
+use lune_utils::path::clean_path_and_make_absolute;
+
+use super::context::*;
+
+type RustResolver = dyn Fn(&Lua, &str, &str) -> LuaResult<Option<ResolvedModule>>;
+
+/**
+    A module resolved by a require resolver, see [`register_require_resolver`].
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedModule {
+    /// The name of the module, which is used to cache it, and as the base for any relative
+    /// requires made from it - modules that resolve to the same name are only loaded once.
+    pub name: String,
+    /// Luau source code for the module, which runs the first time that the module is required.
+    pub source: String,
+}
+
+#[derive(Clone)]
+enum Resolver {
+    Lua(Rc<LuaRegistryKey>),
+    Rust(Rc<RustResolver>),
+}
+
+#[derive(Default)]
+struct RequireResolvers {
+    resolvers: Vec<(String, Resolver)>,
+    // Require paths that have already been resolved, and the names they resolved to
+    resolved: HashMap<String, String>,
+    names: HashSet<String>,
+}
+
+fn check_prefix(prefix: &str) -> LuaResult<()> {
+    if prefix.is_empty() {
+        Err(LuaError::runtime(
+            "Require resolver prefix must not be empty",
+        ))
+    } else if prefix.starts_with("@lune/") {
+        Err(LuaError::runtime(
+            "Require resolver prefix must not start with '@lune/', since built-in libraries can not be replaced",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn register(lua: &Lua, prefix: String, resolver: Resolver) -> LuaResult<()> {
+    check_prefix(&prefix)?;
+    if let Some(mut resolvers) = lua.app_data_mut::<RequireResolvers>() {
+        resolvers.resolvers.push((prefix, resolver));
+        return Ok(());
+    }
+    lua.set_app_data(RequireResolvers {
+        resolvers: vec![(prefix, resolver)],
+        ..Default::default()
+    });
+    Ok(())
+}
+
+/**
+    Registers a require resolver for all require paths that start with the given prefix.
+
+    The resolver is called with the path being required, and the source of the
+    script requiring it, and may return `None` to let the next resolver that
+    matches the path, or the default behavior of `require`, handle the path.
+
+    Resolvers are tried in the order they were registered, before aliases, virtual
+    modules and relative paths, and every path is only resolved once - requiring
+    the same path again returns the cached result, without calling any resolvers.
+
+    Relative requires made from resolved modules are joined with the name of the module,
+    and must also be handled by a resolver, since resolved modules do not exist on disk.
+
+    # Errors
+
+    Errors if the prefix is empty, or if it starts with `@lune/`.
+*/
+pub fn register_require_resolver<F>(
+    lua: &Lua,
+    prefix: impl Into<String>,
+    resolver: F,
+) -> LuaResult<()>
+where
+    F: Fn(&Lua, &str, &str) -> LuaResult<Option<ResolvedModule>> + 'static,
+{
+    register(lua, prefix.into(), Resolver::Rust(Rc::new(resolver)))
+}
+
+/**
+    Registers a require resolver implemented in Lua, see [`register_require_resolver`].
+*/
+pub(crate) fn register_lua_resolver(
+    lua: &Lua,
+    prefix: String,
+    resolver: LuaFunction,
+) -> LuaResult<()> {
+    let key = lua.create_registry_value(resolver)?;
+    register(lua, prefix, Resolver::Lua(Rc::new(key)))
+}
+
+/**
+    Joins a relative path onto the name of a resolved module, the
+    same way that relative paths are joined onto the path of a file.
+*/
+fn join_relative(name: &str, path: &str) -> String {
+    let mut segments = name.split('/').collect::<Vec<_>>();
+    segments.pop();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+fn is_relative(path: &str) -> bool {
+    path.starts_with("./") || path.starts_with("../")
+}
+
+async fn resolve(
+    lua: &Lua,
+    resolvers: Vec<(String, Resolver)>,
+    path: &str,
+    from: &str,
+) -> LuaResult<Option<ResolvedModule>> {
+    for (prefix, resolver) in resolvers {
+        let resolved = match resolver {
+            Resolver::Rust(resolver) => resolver(lua, path, from),
+            Resolver::Lua(key) => {
+                let resolver = lua.registry_value::<LuaFunction>(&key)?;
+                resolver
+                    .call_async::<_, (Option<String>, Option<String>)>((path, from))
+                    .await
+                    .map(|(source, name)| {
+                        source.map(|source| ResolvedModule {
+                            name: name.unwrap_or_else(|| path.to_string()),
+                            source,
+                        })
+                    })
+            }
+        };
+        let resolved = resolved.map_err(|e| {
+            e.context(format!(
+                "require resolver for '{prefix}' failed to resolve '{path}'"
+            ))
+        })?;
+        if let Some(resolved) = resolved {
+            if resolved.name.is_empty() {
+                return Err(LuaError::runtime(format!(
+                    "require resolver for '{prefix}' resolved '{path}' to a module with an empty name"
+                )));
+            }
+            return Ok(Some(resolved));
+        }
+    }
+    Ok(None)
+}
+
+/**
+    Tries to require the given path using registered require resolvers.
+
+    Returns `None` if no resolver handled the path, and it should be required as usual.
+*/
+pub(super) async fn require<'lua, 'ctx>(
+    lua: &'lua Lua,
+    ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
+    path: &str,
+) -> LuaResult<Option<LuaMultiValue<'lua>>>
+where
+    'lua: 'ctx,
+{
+    let (path, joined, matching, cached) = {
+        let Some(resolvers) = lua.app_data_ref::<RequireResolvers>() else {
+            return Ok(None);
+        };
+        let joined = is_relative(path) && resolvers.names.contains(&caller.source);
+        let path = if joined {
+            join_relative(&caller.source, path)
+        } else {
+            path.to_string()
+        };
+        let matching = resolvers
+            .resolvers
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let cached = resolvers.resolved.get(&path).cloned();
+        (path, joined, matching, cached)
+    };
+
+    // NOTE: Modules that were already resolved are always cached
+    // or pending, so the contents we pass here are never used
+    if let Some(name) = cached {
+        return require_resolved(lua, ctx, caller, name, Vec::new())
+            .await
+            .map(Some);
+    }
+
+    let resolved = resolve(lua, matching, &path, &caller.source).await?;
+    let Some(resolved) = resolved else {
+        if joined {
+            return Err(LuaError::runtime(format!(
+                "No require resolver handled the path '{path}', required from the resolved module '{}'",
+                caller.source
+            )));
+        }
+        return Ok(None);
+    };
+
+    if let Some(mut resolvers) = lua.app_data_mut::<RequireResolvers>() {
+        resolvers.resolved.insert(path, resolved.name.clone());
+        resolvers.names.insert(resolved.name.clone());
+    }
+
+    require_resolved(
+        lua,
+        ctx,
+        caller,
+        resolved.name,
+        resolved.source.into_bytes(),
+    )
+    .await
+    .map(Some)
+}
+
+async fn require_resolved<'lua, 'ctx>(
+    lua: &'lua Lua,
+    ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
+    name: String,
+    contents: Vec<u8>,
+) -> LuaResult<LuaMultiValue<'lua>>
+where
+    'lua: 'ctx,
+{
+    // NOTE: Resolved modules are cached by name, using the same
+    // approach as virtual modules, which also do not exist on disk
+    let rel_path = PathBuf::from(name);
+    let abs_path = clean_path_and_make_absolute(&rel_path);
+    super::path::require_with_contents(lua, ctx, caller, abs_path, rel_path, contents).await
+}
@@ -0,0 +1,116 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use mlua::prelude::*;
+
+use lune_utils::path::clean_path_and_make_absolute;
+
+use super::context::*;
+
+const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(false)
+    .serialize_unit_to_null(false);
+
+/**
+    A module that can be required from Lua using `require("@virtual/name")`.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum VirtualModule {
+    /// Luau source code, which runs the first time that the module is required.
+    Source(String),
+    /// A prebuilt value, such as a table of configuration values, which is returned as-is.
+    Value(serde_json::Value),
+}
+
+enum VirtualModuleEntry {
+    Source(String),
+    Value(LuaRegistryKey),
+}
+
+struct VirtualModules(BTreeMap<String, VirtualModuleEntry>);
+
+/**
+    Registers a virtual module with the given name, which can then
+    be required from Lua using `require("@virtual/name")`.
+
+    Registering a module with the same name as an existing
+    module replaces it, unless it has already been required.
+
+    # Errors
+
+    Errors if the name is empty, or if a prebuilt value could not be converted into a Lua value.
+*/
+pub fn register_virtual_module(
+    lua: &Lua,
+    name: impl Into<String>,
+    module: VirtualModule,
+) -> LuaResult<()> {
+    let name = name.into();
+    if name.is_empty() {
+        return Err(LuaError::runtime("Virtual module name must not be empty"));
+    }
+
+    let entry = match module {
+        VirtualModule::Source(source) => VirtualModuleEntry::Source(source),
+        VirtualModule::Value(value) => {
+            let value = lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)?;
+            VirtualModuleEntry::Value(lua.create_registry_value(value)?)
+        }
+    };
+
+    let existing = lua.app_data_mut::<VirtualModules>();
+    if let Some(mut modules) = existing {
+        modules.0.insert(name, entry);
+    } else {
+        lua.set_app_data(VirtualModules(BTreeMap::from([(name, entry)])));
+    }
+
+    Ok(())
+}
+
+pub(super) async fn require<'lua, 'ctx>(
+    lua: &'lua Lua,
+    ctx: &'ctx RequireContext,
+    caller: &RequireCaller,
+    name: &str,
+) -> LuaResult<LuaMultiValue<'lua>>
+where
+    'lua: 'ctx,
+{
+    let source = {
+        let modules = lua.app_data_ref::<VirtualModules>();
+        match modules.as_ref().and_then(|modules| modules.0.get(name)) {
+            Some(VirtualModuleEntry::Source(source)) => source.clone(),
+            Some(VirtualModuleEntry::Value(key)) => {
+                let value = lua.registry_value::<LuaValue>(key)?;
+                return Ok(LuaMultiValue::from_vec(vec![value]));
+            }
+            None => {
+                let known = modules
+                    .as_ref()
+                    .map(|modules| modules.0.keys().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                return Err(LuaError::runtime(if known.is_empty() {
+                    format!("failed to find virtual module '{name}' (no virtual modules)")
+                } else {
+                    format!(
+                        "failed to find virtual module '{name}' - known virtual modules:\n{}",
+                        known
+                            .iter()
+                            .map(|name| format!("    {name}"))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )
+                }));
+            }
+        }
+    };
+
+    // NOTE: Virtual modules are cached just like files, using a path that can
+    // never exist, and that stays the same when made absolute in the require
+    // context - this also means that require cycle detection works as usual
+    let rel_path = PathBuf::from(format!("@virtual/{name}"));
+    let abs_path = clean_path_and_make_absolute(&rel_path);
+    super::path::require_with_contents(lua, ctx, caller, abs_path, rel_path, source.into_bytes())
+        .await
+}
@@ -7,12 +7,18 @@ struct Version(String);
 impl LuaUserData for Version {}
 
 pub fn create(lua: &Lua) -> LuaResult<LuaValue> {
-    let v = match lua.app_data_ref::<Version>() {
+    let s = get_version_string(runtime_version(lua));
+    lua.create_string(s)?.into_lua(lua)
+}
+
+/**
+    Gets the version of the Lune runtime, such as `0.8.7`, without any other information.
+*/
+pub fn runtime_version(lua: &Lua) -> String {
+    match lua.app_data_ref::<Version>() {
         Some(v) => v.0.to_string(),
         None => env!("CARGO_PKG_VERSION").to_string(),
-    };
-    let s = get_version_string(v);
-    lua.create_string(s)?.into_lua(lua)
+    }
 }
 
 /**
@@ -8,9 +8,32 @@ mod library;
 mod luaurc;
 
 pub use self::global::LuneStandardGlobal;
+pub use self::globals::require::{
+    collect_coverage, enable_coverage, register_require_resolver, register_virtual_module,
+    set_bytecode_cache_dir, set_bytecode_cache_max_size, track_coverage, FunctionCoverage,
+    ResolvedModule, VirtualModule,
+};
 pub use self::globals::version::set_global_version;
 pub use self::library::LuneStandardLibrary;
 
+#[cfg(feature = "args")]
+pub use lune_std_args::ArgsParser;
+
+#[cfg(feature = "fs")]
+pub use lune_std_fs::set_fs_root;
+
+#[cfg(feature = "net")]
+pub use lune_std_net::{set_host_overrides, set_host_policy, HostOverrides, HostPolicy};
+
+#[cfg(feature = "process")]
+pub use lune_std_process::take_exit_callbacks;
+
+#[cfg(feature = "task")]
+pub use lune_std_task::set_low_latency_mode;
+
+#[cfg(feature = "test")]
+pub use lune_std_test::{set_test_mode, set_test_seed, set_update_snapshots};
+
 /**
     Injects all standard globals into the given Lua state / VM.
 
@@ -25,5 +48,7 @@ pub fn inject_globals(lua: &Lua) -> LuaResult<()> {
     for global in LuneStandardGlobal::ALL {
         lua.globals().set(global.name(), global.create(lua)?)?;
     }
+    #[cfg(feature = "datetime")]
+    lune_std_datetime::register_value_formatter(lua);
     Ok(())
 }
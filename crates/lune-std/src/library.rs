@@ -18,6 +18,17 @@ pub enum LuneStandardLibrary {
     #[cfg(feature = "serde")]    Serde,
     #[cfg(feature = "stdio")]    Stdio,
     #[cfg(feature = "roblox")]   Roblox,
+    #[cfg(feature = "test")]     Test,
+    #[cfg(feature = "args")]     Args,
+    #[cfg(feature = "config")]   Config,
+    #[cfg(feature = "dirs")]     Dirs,
+    #[cfg(feature = "units")]    Units,
+    #[cfg(feature = "notify")]   Notify,
+    #[cfg(feature = "tray")]     Tray,
+    #[cfg(feature = "serial")]   Serial,
+    #[cfg(feature = "uuid")]     Uuid,
+    #[cfg(feature = "random")]   Random,
+    #[cfg(feature = "ipc")]      Ipc,
 }
 
 impl LuneStandardLibrary {
@@ -36,6 +47,17 @@ impl LuneStandardLibrary {
         #[cfg(feature = "serde")]    Self::Serde,
         #[cfg(feature = "stdio")]    Self::Stdio,
         #[cfg(feature = "roblox")]   Self::Roblox,
+        #[cfg(feature = "test")]     Self::Test,
+        #[cfg(feature = "args")]     Self::Args,
+        #[cfg(feature = "config")]   Self::Config,
+        #[cfg(feature = "dirs")]     Self::Dirs,
+        #[cfg(feature = "units")]    Self::Units,
+        #[cfg(feature = "notify")]   Self::Notify,
+        #[cfg(feature = "tray")]     Self::Tray,
+        #[cfg(feature = "serial")]   Self::Serial,
+        #[cfg(feature = "uuid")]     Self::Uuid,
+        #[cfg(feature = "random")]   Self::Random,
+        #[cfg(feature = "ipc")]      Self::Ipc,
     ];
 
     /**
@@ -56,6 +78,17 @@ impl LuneStandardLibrary {
             #[cfg(feature = "serde")]    Self::Serde    => "serde",
             #[cfg(feature = "stdio")]    Self::Stdio    => "stdio",
             #[cfg(feature = "roblox")]   Self::Roblox   => "roblox",
+            #[cfg(feature = "test")]     Self::Test     => "test",
+            #[cfg(feature = "args")]     Self::Args     => "args",
+            #[cfg(feature = "config")]   Self::Config   => "config",
+            #[cfg(feature = "dirs")]     Self::Dirs     => "dirs",
+            #[cfg(feature = "units")]    Self::Units    => "units",
+            #[cfg(feature = "notify")]   Self::Notify   => "notify",
+            #[cfg(feature = "tray")]     Self::Tray     => "tray",
+            #[cfg(feature = "serial")]   Self::Serial   => "serial",
+            #[cfg(feature = "uuid")]     Self::Uuid     => "uuid",
+            #[cfg(feature = "random")]   Self::Random   => "random",
+            #[cfg(feature = "ipc")]      Self::Ipc      => "ipc",
 
             _ => unreachable!("no standard library enabled"),
         }
@@ -82,6 +115,17 @@ impl LuneStandardLibrary {
             #[cfg(feature = "serde")]    Self::Serde    => lune_std_serde::module(lua),
             #[cfg(feature = "stdio")]    Self::Stdio    => lune_std_stdio::module(lua),
             #[cfg(feature = "roblox")]   Self::Roblox   => lune_std_roblox::module(lua),
+            #[cfg(feature = "test")]     Self::Test     => lune_std_test::module(lua),
+            #[cfg(feature = "args")]     Self::Args     => lune_std_args::module(lua),
+            #[cfg(feature = "config")]   Self::Config   => lune_std_config::module(lua),
+            #[cfg(feature = "dirs")]     Self::Dirs     => lune_std_dirs::module(lua),
+            #[cfg(feature = "units")]    Self::Units    => lune_std_units::module(lua),
+            #[cfg(feature = "notify")]   Self::Notify   => lune_std_notify::module(lua),
+            #[cfg(feature = "tray")]     Self::Tray     => lune_std_tray::module(lua),
+            #[cfg(feature = "serial")]   Self::Serial   => lune_std_serial::module(lua),
+            #[cfg(feature = "uuid")]     Self::Uuid     => lune_std_uuid::module(lua),
+            #[cfg(feature = "random")]   Self::Random   => lune_std_random::module(lua),
+            #[cfg(feature = "ipc")]      Self::Ipc      => lune_std_ipc::module(lua),
 
             _ => unreachable!("no standard library enabled"),
         };
@@ -111,6 +155,17 @@ impl FromStr for LuneStandardLibrary {
             #[cfg(feature = "serde")]    "serde"    => Self::Serde,
             #[cfg(feature = "stdio")]    "stdio"    => Self::Stdio,
             #[cfg(feature = "roblox")]   "roblox"   => Self::Roblox,
+            #[cfg(feature = "test")]     "test"     => Self::Test,
+            #[cfg(feature = "args")]     "args"     => Self::Args,
+            #[cfg(feature = "config")]   "config"   => Self::Config,
+            #[cfg(feature = "dirs")]     "dirs"     => Self::Dirs,
+            #[cfg(feature = "units")]    "units"    => Self::Units,
+            #[cfg(feature = "notify")]   "notify"   => Self::Notify,
+            #[cfg(feature = "tray")]     "tray"     => Self::Tray,
+            #[cfg(feature = "serial")]   "serial"   => Self::Serial,
+            #[cfg(feature = "uuid")]     "uuid"     => Self::Uuid,
+            #[cfg(feature = "random")]   "random"   => Self::Random,
+            #[cfg(feature = "ipc")]      "ipc"      => Self::Ipc,
 
             _ => {
                 return Err(format!(
@@ -54,34 +54,45 @@ impl LuauRc {
     /**
         Reads a `.luaurc` file from the given directory.
 
-        If the file does not exist, or if it is invalid, this function returns `None`.
+        Comments and trailing commas are allowed, the same as in
+        other tooling that reads `.luaurc` files, such as `luau-lsp`.
+
+        If the file does not exist, this function returns `None`, and
+        if it exists but is invalid, this function returns an error.
     */
-    pub async fn read(dir: impl AsRef<Path>) -> Option<Self> {
+    pub async fn read(dir: impl AsRef<Path>) -> Option<Result<Self, String>> {
         let dir = clean_path_and_make_absolute(dir);
         let path = dir.join(LUAURC_FILE);
         let bytes = read(&path).await.ok()?;
-        let config = serde_json::from_slice(&bytes).ok()?;
-        Some(Self {
-            dir: dir.into(),
-            config,
-        })
+        let contents = String::from_utf8_lossy(&bytes);
+        let result = serde_json::from_str(&strip_comments_and_trailing_commas(&contents))
+            .map(|config| Self {
+                dir: dir.into(),
+                config,
+            })
+            .map_err(|e| format!("{}: {e}", path.display()));
+        Some(result)
     }
 
     /**
         Reads a `.luaurc` file from the given directory, and then recursively searches
         for a `.luaurc` file in the parent directories if a predicate is not satisfied.
 
-        If no `.luaurc` file exists, or if they are invalid, this function returns `None`.
+        Invalid `.luaurc` files are also given to the predicate, but are never returned.
+
+        If no `.luaurc` file satisfies the predicate, this function returns `None`.
     */
     pub async fn read_recursive(
         dir: impl AsRef<Path>,
-        mut predicate: impl FnMut(&Self) -> bool,
+        mut predicate: impl FnMut(&Result<Self, String>) -> bool,
     ) -> Option<Self> {
         let mut current = clean_path_and_make_absolute(dir);
         loop {
-            if let Some(rc) = Self::read(&current).await {
-                if predicate(&rc) {
-                    return Some(rc);
+            if let Some(result) = Self::read(&current).await {
+                if predicate(&result) {
+                    if let Ok(rc) = result {
+                        return Some(rc);
+                    }
                 }
             }
             if let Some(parent) = current.parent() {
@@ -162,3 +173,64 @@ fn is_valid_alias_key(alias: impl AsRef<str>) -> bool {
 fn is_valid_alias_char(c: char) -> bool {
     c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'
 }
+
+/**
+    Strips line comments, block comments, and trailing commas
+    from the given JSON contents, leaving strings untouched.
+*/
+fn strip_comments_and_trailing_commas(contents: &str) -> String {
+    let mut stripped = String::with_capacity(contents.len());
+    let mut pending_comma = None;
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                stripped.extend(pending_comma.take());
+                stripped.push(c);
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    stripped.push(c);
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        stripped.push(c);
+                        break;
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some('*')) => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            ',' => {
+                stripped.extend(pending_comma.replace(c));
+            }
+            '}' | ']' => {
+                pending_comma = None;
+                stripped.push(c);
+            }
+            c if c.is_whitespace() => stripped.push(c),
+            c => {
+                stripped.extend(pending_comma.take());
+                stripped.push(c);
+            }
+        }
+    }
+
+    stripped.extend(pending_comma);
+    stripped
+}
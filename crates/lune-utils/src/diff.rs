@@ -0,0 +1,80 @@
+// Diffing uses a table of this many cells at most, anything larger
+// than this is shown as all of the old lines being replaced at once
+const MAX_TABLE_SIZE: usize = 4_000_000;
+
+/**
+    An operation in a diff between two lists of lines.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    /// The next line is the same in both lists.
+    Equal,
+    /// The next line of the old list was removed.
+    Remove,
+    /// The next line of the new list was added.
+    Add,
+}
+
+/**
+    Diffs the given lines, returning the operations to turn the old lines into the new ones.
+
+    Common lines at the start and end are skipped before diffing the lines in between,
+    which keeps the common case of small changes to large files fast.
+*/
+#[must_use]
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![DiffOp::Equal; prefix];
+    if (old_mid.len() + 1).saturating_mul(new_mid.len() + 1) > MAX_TABLE_SIZE {
+        ops.extend(std::iter::repeat_n(DiffOp::Remove, old_mid.len()));
+        ops.extend(std::iter::repeat_n(DiffOp::Add, new_mid.len()));
+    } else {
+        ops.extend(diff_lcs(old_mid, new_mid));
+    }
+    ops.extend(std::iter::repeat_n(DiffOp::Equal, suffix));
+    ops
+}
+
+// Diffs using the longest common subsequence of lines, where
+// table[i][j] is the length of it for old[i..] and new[j..]
+fn diff_lcs(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let width = new.len() + 1;
+    let mut table = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(old.len() + new.len());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            ops.push(DiffOp::Equal);
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            ops.push(DiffOp::Remove);
+            i += 1;
+        } else {
+            ops.push(DiffOp::Add);
+            j += 1;
+        }
+    }
+    ops.extend(std::iter::repeat_n(DiffOp::Remove, old.len() - i));
+    ops.extend(std::iter::repeat_n(DiffOp::Add, new.len() - j));
+    ops
+}
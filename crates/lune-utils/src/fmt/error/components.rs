@@ -6,6 +6,7 @@ use console::style;
 use mlua::prelude::*;
 use once_cell::sync::Lazy;
 
+use super::source_map::remap_message;
 use super::StackTrace;
 
 static STYLED_STACK_BEGIN: Lazy<String> = Lazy::new(|| {
@@ -147,6 +148,12 @@ impl From<LuaError> for ErrorComponents {
             None
         };
 
+        // Messages may start with a location in a generated chunk, same as stack traces
+        let messages = messages
+            .into_iter()
+            .map(|message| remap_message(&message).unwrap_or(message))
+            .collect();
+
         ErrorComponents { messages, trace }
     }
 }
@@ -1,8 +1,10 @@
 mod components;
+mod source_map;
 mod stack_trace;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::components::ErrorComponents;
+pub use self::source_map::SourceMap;
 pub use self::stack_trace::{StackTrace, StackTraceLine, StackTraceSource};
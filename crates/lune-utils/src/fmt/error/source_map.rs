@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use once_cell::sync::Lazy;
+
+const HEADER: &str = "-- Lines in this file belong to the following modules:";
+const LINE_PREFIX: &str = "--   ";
+
+// NOTE: This is global and not per Lua state, since errors are formatted without access to one
+static SOURCE_MAPS: Lazy<Mutex<HashMap<String, Arc<SourceMap>>>> = Lazy::new(Mutex::default);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SourceMapping {
+    first_line: usize,
+    last_line: usize,
+    path: String,
+}
+
+/**
+    A mapping from ranges of lines in a generated chunk,
+    such as a bundle, to the files that they came from.
+
+    Source maps are written as a comment block at the start of the
+    generated file, where each line is a range of lines and a path:
+
+    ```plaintext
+    -- Lines in this file belong to the following modules:
+    --   10-24 main.luau
+    --   30-41 lib/util.luau
+    --
+    ```
+
+    Once registered for a chunk name, any errors and stack traces that are
+    formatted using [`ErrorComponents`](super::ErrorComponents) will point
+    at the original files and lines instead of the generated chunk.
+*/
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    mappings: Vec<SourceMapping>,
+}
+
+impl SourceMap {
+    /**
+        Creates a new, empty, source map.
+    */
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+        Adds a mapping for the given range of lines, inclusive, to the file at the given path.
+
+        Line numbers start at 1, and the first line in the range maps to line 1 in the file.
+    */
+    pub fn add(&mut self, first_line: usize, last_line: usize, path: impl Into<String>) {
+        self.mappings.push(SourceMapping {
+            first_line,
+            last_line,
+            path: path.into(),
+        });
+    }
+
+    /**
+        Returns `true` if the source map has no mappings.
+    */
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    /**
+        Parses a source map from the comment block at the start of the given source code.
+
+        Returns `None` if the source code does not start with a source map.
+    */
+    #[must_use]
+    pub fn from_source(source: &str) -> Option<Self> {
+        let lines = source
+            .lines()
+            // NOTE: Shebang lines may have been replaced with empty lines to keep line numbers
+            .skip_while(|line| line.starts_with("#!") || line.is_empty())
+            .take_while(|line| line.starts_with("--"))
+            .skip_while(|line| *line != HEADER)
+            .skip(1);
+
+        let mut map = Self::new();
+        for line in lines {
+            let Some(mapping) = line.strip_prefix(LINE_PREFIX) else {
+                break;
+            };
+            let (range, path) = mapping.split_once(' ')?;
+            let (first, last) = range.split_once('-')?;
+            map.add(first.parse().ok()?, last.parse().ok()?, path);
+        }
+
+        if map.is_empty() {
+            None
+        } else {
+            Some(map)
+        }
+    }
+
+    /**
+        Looks up the original path and line number for the given line in the generated chunk.
+    */
+    #[must_use]
+    pub fn lookup(&self, line: usize) -> Option<(&str, usize)> {
+        self.mappings
+            .iter()
+            .find(|m| (m.first_line..=m.last_line).contains(&line))
+            .map(|m| (m.path.as_str(), line - m.first_line + 1))
+    }
+
+    /**
+        Registers the source map for the chunk with the given name,
+        replacing any source map previously registered for it.
+    */
+    pub fn register(self, chunk_name: impl Into<String>) {
+        SOURCE_MAPS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(chunk_name.into(), Arc::new(self));
+    }
+}
+
+impl fmt::Display for SourceMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for mapping in &self.mappings {
+            writeln!(
+                f,
+                "{LINE_PREFIX}{}-{} {}",
+                mapping.first_line, mapping.last_line, mapping.path
+            )?;
+        }
+        writeln!(f, "--")
+    }
+}
+
+/**
+    Looks up the original path and line number for a line in
+    the chunk with the given name, if it has a source map.
+*/
+pub(crate) fn remap_location(chunk_name: &str, line: usize) -> Option<(String, usize)> {
+    let map = SOURCE_MAPS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(chunk_name)
+        .cloned()?;
+    map.lookup(line)
+        .map(|(path, line)| (path.to_string(), line))
+}
+
+/**
+    Checks if a line in the chunk with the given name has a source map, but is not
+    part of any of the original files, such as the loader that a bundle wraps modules in.
+*/
+pub(crate) fn is_generated_location(chunk_name: &str, line: usize) -> bool {
+    SOURCE_MAPS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(chunk_name)
+        .is_some_and(|map| map.lookup(line).is_none())
+}
+
+/**
+    Remaps the location at the start of an error message,
+    such as `[string "chunk"]:12: message`, if it has a source map.
+*/
+pub(crate) fn remap_message(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("[string \"")?;
+    let (chunk_name, rest) = rest.split_once("\"]:")?;
+    let (line, rest) = rest.split_once(':')?;
+    let (path, line) = remap_location(chunk_name, line.parse().ok()?)?;
+    Some(format!("[string \"{path}\"]:{line}:{rest}"))
+}
@@ -1,6 +1,8 @@
 use std::fmt;
 use std::str::FromStr;
 
+use super::source_map::{is_generated_location, remap_location};
+
 fn parse_path(s: &str) -> Option<(&str, &str)> {
     let path = s.strip_prefix("[string \"")?;
     let (path, after) = path.split_once("\"]:")?;
@@ -48,6 +50,7 @@ pub struct StackTraceLine {
     path: Option<String>,
     line_number: Option<usize>,
     function_name: Option<String>,
+    generated: bool,
 }
 
 impl StackTraceLine {
@@ -95,16 +98,25 @@ impl FromStr for StackTraceLine {
                 path: None,
                 line_number: None,
                 function_name,
+                generated: false,
             })
         } else if let Some((path, after)) = parse_path(s) {
             let (line_number, after) = parse_line_number(after);
             let function_name = parse_function_name(after).map(ToString::to_string);
 
+            // Lines in generated chunks, such as bundles, should point at the original files
+            let generated = line_number.is_some_and(|l| is_generated_location(path, l));
+            let (path, line_number) = match line_number.and_then(|l| remap_location(path, l)) {
+                Some((path, line_number)) => (path, Some(line_number)),
+                None => (path.to_string(), line_number),
+            };
+
             Ok(Self {
                 source: StackTraceSource::Lua,
-                path: Some(path.to_string()),
+                path: Some(path),
                 line_number,
                 function_name,
+                generated,
             })
         } else {
             Err(String::from("unknown format"))
@@ -164,7 +176,10 @@ impl FromStr for StackTrace {
                     Some(line.parse())
                 }
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Result<Vec<StackTraceLine>, _>>()?;
+        // NOTE: Lines that are not part of any original file, such as the loader
+        // in a bundle, are left out since they would only point at generated code
+        let lines = lines.into_iter().filter(|line| !line.generated).collect();
         Ok(StackTrace { lines })
     }
 }
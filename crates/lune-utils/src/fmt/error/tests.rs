@@ -83,3 +83,122 @@ mod error_components {
         assert_eq!(line_2, "Script 'chunk_name', Line 1");
     }
 }
+
+// Tests for remapping errors in generated chunks to their original files
+mod source_maps {
+    use super::*;
+
+    use crate::fmt::SourceMap;
+
+    const GENERATED: &str = "\
+-- Bundled by Lune from main.luau
+--
+-- Lines in this file belong to the following modules:
+--   7-9 lib/util.luau
+--   11-11 main.luau
+--
+local function f()
+    error(\"oh no, a bundled error\")
+end
+
+f()
+";
+
+    fn new_generated_result(chunk_name: &str) -> LuaResult<()> {
+        SourceMap::from_source(GENERATED)
+            .unwrap()
+            .register(chunk_name);
+        Lua::new().load(GENERATED).set_name(chunk_name).exec()
+    }
+
+    const GENERATED_WRAPPED: &str = "\
+-- Lines in this file belong to the following modules:
+--   8-8 main.luau
+--
+local function load(loader)
+    return loader()
+end
+load(function()
+    error(\"oh no, a wrapped error\")
+end)
+";
+
+    #[test]
+    fn parse_and_display() {
+        let map = SourceMap::from_source(GENERATED).unwrap();
+
+        assert_eq!(map.lookup(6), None);
+        assert_eq!(map.lookup(7), Some(("lib/util.luau", 1)));
+        assert_eq!(map.lookup(8), Some(("lib/util.luau", 2)));
+        assert_eq!(map.lookup(10), None);
+        assert_eq!(map.lookup(11), Some(("main.luau", 1)));
+        assert_eq!(SourceMap::from_source(&map.to_string()), Some(map));
+        assert_eq!(SourceMap::from_source("-- just a comment\nprint()"), None);
+    }
+
+    #[test]
+    fn message() {
+        let lua_error = new_generated_result("source_map_message").unwrap_err();
+        let components = ErrorComponents::from(lua_error);
+
+        assert_eq!(
+            components.messages()[0],
+            "[string \"lib/util.luau\"]:2: oh no, a bundled error"
+        );
+    }
+
+    #[test]
+    fn stack_lines() {
+        let lua_error = new_generated_result("source_map_stack_lines").unwrap_err();
+        let components = ErrorComponents::from(lua_error);
+
+        let lines = components
+            .trace()
+            .unwrap()
+            .lines()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[lines.len() - 3..],
+            [
+                "Script '[C]' - function 'error'",
+                "Script 'lib/util.luau', Line 2",
+                "Script 'main.luau', Line 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_lines_without_generated_code() {
+        SourceMap::from_source(GENERATED_WRAPPED)
+            .unwrap()
+            .register("source_map_generated_code");
+        let lua_error = Lua::new()
+            .load(GENERATED_WRAPPED)
+            .set_name("source_map_generated_code")
+            .exec()
+            .unwrap_err();
+        let components = ErrorComponents::from(lua_error);
+
+        let lines = components
+            .trace()
+            .unwrap()
+            .lines()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[lines.len() - 2..],
+            [
+                "Script '[C]' - function 'error'",
+                "Script 'main.luau', Line 1",
+            ]
+        );
+        assert!(!lines
+            .iter()
+            .any(|l| l.contains("source_map_generated_code")));
+    }
+}
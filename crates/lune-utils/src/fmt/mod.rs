@@ -2,6 +2,9 @@ mod error;
 mod label;
 mod value;
 
-pub use self::error::{ErrorComponents, StackTrace, StackTraceLine, StackTraceSource};
+pub use self::error::{ErrorComponents, SourceMap, StackTrace, StackTraceLine, StackTraceSource};
 pub use self::label::Label;
-pub use self::value::{pretty_format_multi_value, pretty_format_value, ValueFormatConfig};
+pub use self::value::{
+    pretty_format_multi_value, pretty_format_value, set_lua_value_formatter,
+    set_native_value_formatter, NativeValueFormatter, ValueFormatConfig,
+};
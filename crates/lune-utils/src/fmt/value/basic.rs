@@ -3,11 +3,13 @@ use mlua::prelude::*;
 use crate::fmt::ErrorComponents;
 
 use super::{
+    config::ValueFormatConfig,
+    formatters::format_with_formatter,
     metamethods::{
         call_table_tostring_metamethod, call_userdata_tostring_metamethod,
         get_table_type_metavalue, get_userdata_type_metavalue,
     },
-    style::{COLOR_CYAN, COLOR_GREEN, COLOR_MAGENTA, COLOR_YELLOW},
+    style::{styled, COLOR_CYAN, COLOR_GREEN, COLOR_MAGENTA, COLOR_YELLOW},
 };
 
 const STRING_REPLACEMENTS: &[(&str, &str)] =
@@ -39,49 +41,59 @@ pub(crate) fn lua_value_as_plain_string_key(value: &LuaValue) -> Option<String>
 
     This does not recursively format tables.
 */
-pub(crate) fn format_value_styled(value: &LuaValue, prefer_plain: bool) -> String {
+pub(crate) fn format_value_styled(
+    lua: &Lua,
+    value: &LuaValue,
+    config: &ValueFormatConfig,
+    prefer_plain: bool,
+) -> String {
     match value {
-        LuaValue::Nil => COLOR_YELLOW.apply_to("nil").to_string(),
-        LuaValue::Boolean(true) => COLOR_YELLOW.apply_to("true").to_string(),
-        LuaValue::Boolean(false) => COLOR_YELLOW.apply_to("false").to_string(),
-        LuaValue::Number(n) => COLOR_CYAN.apply_to(n).to_string(),
-        LuaValue::Integer(i) => COLOR_CYAN.apply_to(i).to_string(),
+        LuaValue::Nil => styled(&COLOR_YELLOW, config, "nil").to_string(),
+        LuaValue::Boolean(true) => styled(&COLOR_YELLOW, config, "true").to_string(),
+        LuaValue::Boolean(false) => styled(&COLOR_YELLOW, config, "false").to_string(),
+        LuaValue::Number(n) => styled(&COLOR_CYAN, config, n).to_string(),
+        LuaValue::Integer(i) => styled(&COLOR_CYAN, config, i).to_string(),
         LuaValue::String(s) if prefer_plain => s.to_string_lossy().to_string(),
-        LuaValue::String(s) => COLOR_GREEN
-            .apply_to({
-                let mut s = s.to_string_lossy().to_string();
-                for (from, to) in STRING_REPLACEMENTS {
-                    s = s.replace(from, to);
-                }
-                format!(r#""{s}""#)
-            })
-            .to_string(),
-        LuaValue::Vector(_) => COLOR_MAGENTA.apply_to("<vector>").to_string(),
-        LuaValue::Thread(_) => COLOR_MAGENTA.apply_to("<thread>").to_string(),
-        LuaValue::Function(_) => COLOR_MAGENTA.apply_to("<function>").to_string(),
-        LuaValue::LightUserData(_) => COLOR_MAGENTA.apply_to("<pointer>").to_string(),
+        LuaValue::String(s) => styled(&COLOR_GREEN, config, {
+            let mut s = s.to_string_lossy().to_string();
+            for (from, to) in STRING_REPLACEMENTS {
+                s = s.replace(from, to);
+            }
+            format!(r#""{s}""#)
+        })
+        .to_string(),
+        LuaValue::Vector(_) => styled(&COLOR_MAGENTA, config, "<vector>").to_string(),
+        LuaValue::Thread(_) => styled(&COLOR_MAGENTA, config, "<thread>").to_string(),
+        LuaValue::Function(_) => styled(&COLOR_MAGENTA, config, "<function>").to_string(),
+        LuaValue::LightUserData(_) => styled(&COLOR_MAGENTA, config, "<pointer>").to_string(),
         LuaValue::UserData(u) => {
-            let formatted = format_typename_and_tostringed(
-                "userdata",
-                get_userdata_type_metavalue(u),
-                call_userdata_tostring_metamethod(u),
-            );
-            COLOR_MAGENTA.apply_to(formatted).to_string()
+            let typename = if value.is_buffer() {
+                Some("buffer".to_string())
+            } else {
+                get_userdata_type_metavalue(u)
+            };
+            let tostringed = typename
+                .as_deref()
+                .and_then(|typename| format_with_formatter(lua, typename, value))
+                .or_else(|| call_userdata_tostring_metamethod(u));
+            let formatted = format_typename_and_tostringed("userdata", typename, tostringed);
+            styled(&COLOR_MAGENTA, config, formatted).to_string()
         }
         LuaValue::Table(t) => {
-            let formatted = format_typename_and_tostringed(
-                "table",
-                get_table_type_metavalue(t),
-                call_table_tostring_metamethod(t),
-            );
-            COLOR_MAGENTA.apply_to(formatted).to_string()
+            let typename = get_table_type_metavalue(t);
+            let tostringed = typename
+                .as_deref()
+                .and_then(|typename| format_with_formatter(lua, typename, value))
+                .or_else(|| call_table_tostring_metamethod(t));
+            let formatted = format_typename_and_tostringed("table", typename, tostringed);
+            styled(&COLOR_MAGENTA, config, formatted).to_string()
         }
-        LuaValue::Error(e) => COLOR_MAGENTA
-            .apply_to(format!(
-                "<LuaError(\n{})>",
-                ErrorComponents::from(e.clone())
-            ))
-            .to_string(),
+        LuaValue::Error(e) => styled(
+            &COLOR_MAGENTA,
+            config,
+            format!("<LuaError(\n{})>", ErrorComponents::from(e.clone())),
+        )
+        .to_string(),
     }
 }
 
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt::Write as _};
+
+use bstr::BString;
+use mlua::prelude::*;
+
+// Maximum number of bytes to show when formatting a buffer
+const MAX_BUFFER_PREVIEW_LEN: usize = 16;
+
+/**
+    A native function that formats a value of a specific type into a string.
+*/
+pub type NativeValueFormatter = for<'lua> fn(&'lua Lua, &LuaValue<'lua>) -> LuaResult<String>;
+
+enum ValueFormatter {
+    Native(NativeValueFormatter),
+    Lua(LuaRegistryKey),
+}
+
+/**
+    Formatters for values, keyed by their type name.
+
+    Type names are the same as the ones shown by the pretty-printer - `buffer`
+    for buffers, and the `__type` metatable field for userdata and tables.
+*/
+#[derive(Default)]
+struct ValueFormatters {
+    formatters: HashMap<String, ValueFormatter>,
+}
+
+fn set_value_formatter(lua: &Lua, type_name: &str, formatter: Option<ValueFormatter>) {
+    if lua.app_data_ref::<ValueFormatters>().is_none() {
+        lua.set_app_data(ValueFormatters::default());
+    }
+    let mut formatters = lua
+        .app_data_mut::<ValueFormatters>()
+        .expect("Missing value formatters");
+    let previous = match formatter {
+        Some(formatter) => formatters
+            .formatters
+            .insert(type_name.to_string(), formatter),
+        None => formatters.formatters.remove(type_name),
+    };
+    if let Some(ValueFormatter::Lua(key)) = previous {
+        drop(formatters);
+        lua.remove_registry_value(key).ok();
+    }
+}
+
+/**
+    Sets a native formatter for values with the given type name.
+
+    This replaces any previous formatter for the type,
+    including formatters that were set from Lua.
+*/
+pub fn set_native_value_formatter(lua: &Lua, type_name: &str, formatter: NativeValueFormatter) {
+    set_value_formatter(lua, type_name, Some(ValueFormatter::Native(formatter)));
+}
+
+/**
+    Sets a Lua function as the formatter for values with the given type name,
+    or removes the current formatter for the type if no function is given.
+
+    # Errors
+
+    Errors when out of memory.
+*/
+pub fn set_lua_value_formatter(
+    lua: &Lua,
+    type_name: &str,
+    formatter: Option<LuaFunction>,
+) -> LuaResult<()> {
+    let formatter = match formatter {
+        Some(f) => Some(ValueFormatter::Lua(lua.create_registry_value(f)?)),
+        None => None,
+    };
+    set_value_formatter(lua, type_name, formatter);
+    Ok(())
+}
+
+/**
+    Checks if there is a formatter for values with the given type name.
+*/
+pub(crate) fn has_formatter(lua: &Lua, type_name: &str) -> bool {
+    lua.app_data_ref::<ValueFormatters>()
+        .is_some_and(|formatters| formatters.formatters.contains_key(type_name))
+}
+
+/**
+    Formats the given value using the formatter for its type, if any.
+
+    Returns `None` if there is no formatter for the type, or if the formatter errored.
+*/
+pub(crate) fn format_with_formatter(
+    lua: &Lua,
+    type_name: &str,
+    value: &LuaValue,
+) -> Option<String> {
+    // NOTE: We must not hold onto the app data while calling the formatter,
+    // since Lua formatters may set formatters or format values themselves
+    enum Found<'lua> {
+        Native(NativeValueFormatter),
+        Lua(LuaFunction<'lua>),
+    }
+    let found = match lua
+        .app_data_ref::<ValueFormatters>()
+        .as_ref()
+        .and_then(|formatters| formatters.formatters.get(type_name))
+    {
+        Some(ValueFormatter::Native(f)) => Found::Native(*f),
+        Some(ValueFormatter::Lua(key)) => Found::Lua(lua.registry_value(key).ok()?),
+        None if value.is_buffer() => Found::Native(format_buffer),
+        None => return None,
+    };
+    match found {
+        Found::Native(f) => f(lua, value).ok(),
+        Found::Lua(f) => f.call(value.clone()).ok(),
+    }
+}
+
+/**
+    Formats a buffer as its length and a preview of its first bytes in hex.
+*/
+fn format_buffer<'lua>(lua: &'lua Lua, value: &LuaValue<'lua>) -> LuaResult<String> {
+    let bytes = BString::from_lua(value.clone(), lua)?;
+    let mut formatted = match bytes.len() {
+        1 => "1 byte".to_string(),
+        len => format!("{len} bytes"),
+    };
+    for (index, byte) in bytes.iter().take(MAX_BUFFER_PREVIEW_LEN).enumerate() {
+        let separator = if index == 0 { ": " } else { " " };
+        write!(formatted, "{separator}{byte:02x}").ok();
+    }
+    if bytes.len() > MAX_BUFFER_PREVIEW_LEN {
+        formatted.push_str(" ...");
+    }
+    Ok(formatted)
+}
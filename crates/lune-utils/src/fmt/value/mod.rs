@@ -1,14 +1,11 @@
-use std::{
-    collections::HashSet,
-    sync::{Arc, Mutex},
-};
+use std::collections::HashSet;
 
-use console::{colors_enabled as get_colors_enabled, set_colors_enabled};
+use console::colors_enabled as get_colors_enabled;
 use mlua::prelude::*;
-use once_cell::sync::Lazy;
 
 mod basic;
 mod config;
+mod formatters;
 mod metamethods;
 mod recursive;
 mod style;
@@ -16,27 +13,28 @@ mod style;
 use self::recursive::format_value_recursive;
 
 pub use self::config::ValueFormatConfig;
+pub use self::formatters::{
+    set_lua_value_formatter, set_native_value_formatter, NativeValueFormatter,
+};
 
-// NOTE: Since the setting for colors being enabled is global,
-// and these functions may be called in parallel, we use this global
-// lock to make sure that we don't mess up the colors for other threads.
-static COLORS_LOCK: Lazy<Arc<Mutex<()>>> = Lazy::new(|| Arc::new(Mutex::new(())));
+// NOTE: The setting for colors being enabled is global, so instead of changing it
+// while formatting, which would need a lock that is then held while formatters
+// written in Lua run, and those may format values themselves, we resolve it once
+// per call and pass it along in the config that is given to all styling.
+fn resolve_config(config: &ValueFormatConfig) -> ValueFormatConfig {
+    config.with_colors_enabled(config.colors_enabled && get_colors_enabled())
+}
 
 /**
     Formats a Lua value into a pretty string using the given config.
 */
 #[must_use]
 #[allow(clippy::missing_panics_doc)]
-pub fn pretty_format_value(value: &LuaValue, config: &ValueFormatConfig) -> String {
-    let _guard = COLORS_LOCK.lock().unwrap();
-
-    let were_colors_enabled = get_colors_enabled();
-    set_colors_enabled(were_colors_enabled && config.colors_enabled);
+pub fn pretty_format_value(lua: &Lua, value: &LuaValue, config: &ValueFormatConfig) -> String {
+    let config = &resolve_config(config);
 
     let mut visited = HashSet::new();
-    let res = format_value_recursive(value, config, &mut visited, 0);
-
-    set_colors_enabled(were_colors_enabled);
+    let res = format_value_recursive(lua, value, config, &mut visited, 0);
     res.expect("using fmt for writing into strings should never fail")
 }
 
@@ -47,19 +45,18 @@ pub fn pretty_format_value(value: &LuaValue, config: &ValueFormatConfig) -> Stri
 */
 #[must_use]
 #[allow(clippy::missing_panics_doc)]
-pub fn pretty_format_multi_value(values: &LuaMultiValue, config: &ValueFormatConfig) -> String {
-    let _guard = COLORS_LOCK.lock().unwrap();
-
-    let were_colors_enabled = get_colors_enabled();
-    set_colors_enabled(were_colors_enabled && config.colors_enabled);
+pub fn pretty_format_multi_value(
+    lua: &Lua,
+    values: &LuaMultiValue,
+    config: &ValueFormatConfig,
+) -> String {
+    let config = &resolve_config(config);
 
     let mut visited = HashSet::new();
     let res = values
         .into_iter()
-        .map(|value| format_value_recursive(value, config, &mut visited, 0))
+        .map(|value| format_value_recursive(lua, value, config, &mut visited, 0))
         .collect::<Result<Vec<_>, _>>();
-
-    set_colors_enabled(were_colors_enabled);
     res.expect("using fmt for writing into strings should never fail")
         .join(" ")
 }
@@ -7,7 +7,9 @@ use mlua::prelude::*;
 use super::{
     basic::{format_value_styled, lua_value_as_plain_string_key},
     config::ValueFormatConfig,
-    style::STYLE_DIM,
+    formatters::has_formatter,
+    metamethods::get_table_type_metavalue,
+    style::dim,
 };
 
 const INDENT: &str = "    ";
@@ -34,10 +36,14 @@ impl From<&LuaTable<'_>> for LuaValueId {
     Formats the given value, recursively formatting tables
     up to the maximum depth specified in the config.
 
+    Tables with a formatter for their type are not formatted recursively,
+    and are instead formatted the same way as userdata would be.
+
     NOTE: We return a result here but it's really just to make handling
     of the `write!` calls easier. Writing into a string should never fail.
 */
 pub(crate) fn format_value_recursive(
+    lua: &Lua,
     value: &LuaValue,
     config: &ValueFormatConfig,
     visited: &mut HashSet<LuaValueId>,
@@ -45,13 +51,20 @@ pub(crate) fn format_value_recursive(
 ) -> Result<String, fmt::Error> {
     let mut buffer = String::new();
 
-    if let LuaValue::Table(ref t) = value {
+    let has_table_formatter = match value {
+        LuaValue::Table(t) => {
+            get_table_type_metavalue(t).is_some_and(|typename| has_formatter(lua, &typename))
+        }
+        _ => false,
+    };
+
+    if let (LuaValue::Table(ref t), false) = (value, has_table_formatter) {
         if depth >= config.max_depth {
-            write!(buffer, "{}", STYLE_DIM.apply_to("{ ... }"))?;
+            write!(buffer, "{}", dim(config, "{ ... }"))?;
         } else if !visited.insert(LuaValueId::from(t)) {
-            write!(buffer, "{}", STYLE_DIM.apply_to("{ recursive }"))?;
+            write!(buffer, "{}", dim(config, "{ recursive }"))?;
         } else {
-            write!(buffer, "{}", STYLE_DIM.apply_to("{"))?;
+            write!(buffer, "{}", dim(config, "{"))?;
 
             let mut values = t
                 .clone()
@@ -67,28 +80,32 @@ pub(crate) fn format_value_recursive(
                 .all(|(i, (key, _))| key.as_integer().is_some_and(|x| x == (i as i32) + 1));
 
             let formatted_values = if is_array {
-                format_array(values, config, visited, depth)?
+                format_array(lua, values, config, visited, depth)?
             } else {
-                format_table(values, config, visited, depth)?
+                format_table(lua, values, config, visited, depth)?
             };
 
             visited.remove(&LuaValueId::from(t));
 
             if is_empty {
-                write!(buffer, " {}", STYLE_DIM.apply_to("}"))?;
+                write!(buffer, " {}", dim(config, "}"))?;
             } else {
                 write!(
                     buffer,
                     "\n{}\n{}{}",
                     formatted_values.join("\n"),
                     INDENT.repeat(depth),
-                    STYLE_DIM.apply_to("}")
+                    dim(config, "}")
                 )?;
             }
         }
     } else {
         let prefer_plain = depth == 0;
-        write!(buffer, "{}", format_value_styled(value, prefer_plain))?;
+        write!(
+            buffer,
+            "{}",
+            format_value_styled(lua, value, config, prefer_plain)
+        )?;
     }
 
     Ok(buffer)
@@ -114,6 +131,7 @@ fn sort_for_formatting(values: &mut [(LuaValue, LuaValue)]) {
 }
 
 fn format_array(
+    lua: &Lua,
     values: Vec<(LuaValue, LuaValue)>,
     config: &ValueFormatConfig,
     visited: &mut HashSet<LuaValueId>,
@@ -125,14 +143,15 @@ fn format_array(
             Ok(format!(
                 "{}{}{}",
                 INDENT.repeat(1 + depth),
-                format_value_recursive(&value, config, visited, depth + 1)?,
-                STYLE_DIM.apply_to(","),
+                format_value_recursive(lua, &value, config, visited, depth + 1)?,
+                dim(config, ","),
             ))
         })
         .collect()
 }
 
 fn format_table(
+    lua: &Lua,
     values: Vec<(LuaValue, LuaValue)>,
     config: &ValueFormatConfig,
     visited: &mut HashSet<LuaValueId>,
@@ -145,20 +164,20 @@ fn format_table(
                 Ok(format!(
                     "{}{plain_key} {} {}{}",
                     INDENT.repeat(1 + depth),
-                    STYLE_DIM.apply_to("="),
-                    format_value_recursive(&value, config, visited, depth + 1)?,
-                    STYLE_DIM.apply_to(","),
+                    dim(config, "="),
+                    format_value_recursive(lua, &value, config, visited, depth + 1)?,
+                    dim(config, ","),
                 ))
             } else {
                 Ok(format!(
                     "{}{}{}{} {} {}{}",
                     INDENT.repeat(1 + depth),
-                    STYLE_DIM.apply_to("["),
-                    format_value_recursive(&key, config, visited, depth + 1)?,
-                    STYLE_DIM.apply_to("]"),
-                    STYLE_DIM.apply_to("="),
-                    format_value_recursive(&value, config, visited, depth + 1)?,
-                    STYLE_DIM.apply_to(","),
+                    dim(config, "["),
+                    format_value_recursive(lua, &key, config, visited, depth + 1)?,
+                    dim(config, "]"),
+                    dim(config, "="),
+                    format_value_recursive(lua, &value, config, visited, depth + 1)?,
+                    dim(config, ","),
                 ))
             }
         })
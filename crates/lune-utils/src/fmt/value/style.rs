@@ -1,9 +1,28 @@
-use console::Style;
+use console::{Style, StyledObject};
 use once_cell::sync::Lazy;
 
+use super::config::ValueFormatConfig;
+
 pub static COLOR_GREEN: Lazy<Style> = Lazy::new(|| Style::new().green());
 pub static COLOR_YELLOW: Lazy<Style> = Lazy::new(|| Style::new().yellow());
 pub static COLOR_MAGENTA: Lazy<Style> = Lazy::new(|| Style::new().magenta());
 pub static COLOR_CYAN: Lazy<Style> = Lazy::new(|| Style::new().cyan());
 
 pub static STYLE_DIM: Lazy<Style> = Lazy::new(|| Style::new().dim());
+
+/**
+    Applies the given style to a value, only if colors are enabled in the given config.
+
+    NOTE: This does not use the global setting for colors, since changing that
+    for a single call would also change it for all other threads formatting values.
+*/
+pub fn styled<D>(style: &Style, config: &ValueFormatConfig, value: D) -> StyledObject<D> {
+    style.apply_to(value).force_styling(config.colors_enabled)
+}
+
+/**
+    Applies the dim style to a value, only if colors are enabled in the given config.
+*/
+pub fn dim<D>(config: &ValueFormatConfig, value: D) -> StyledObject<D> {
+    styled(&STYLE_DIM, config, value)
+}
@@ -3,8 +3,12 @@
 mod table_builder;
 mod version_string;
 
+pub mod diff;
 pub mod fmt;
+pub mod output;
 pub mod path;
+pub mod units;
+pub mod warnings;
 
 pub use self::table_builder::TableBuilder;
 pub use self::version_string::get_version_string;
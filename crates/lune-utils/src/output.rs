@@ -0,0 +1,161 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use mlua::prelude::*;
+
+/**
+    A function that hides output which is redrawn in place, such as progress
+    bars, while the given function runs, and then draws it again.
+*/
+pub type LiveOutputSuspender = Arc<dyn Fn(&mut dyn FnMut()) + Send + Sync>;
+
+// NOTE: This is global and not per Lua state, since there is only one terminal to draw to
+static LIVE_OUTPUT: Mutex<Option<LiveOutputSuspender>> = Mutex::new(None);
+
+fn current_suspender() -> Option<LiveOutputSuspender> {
+    LIVE_OUTPUT
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone()
+}
+
+/**
+    Sets the function used to hide output which is redrawn in place, such as
+    progress bars, while other output is written, or removes it if `None`.
+*/
+pub fn set_live_output(suspender: Option<LiveOutputSuspender>) {
+    *LIVE_OUTPUT
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner) = suspender;
+}
+
+/**
+    Checks if there is any output which is redrawn in place, such as progress bars.
+
+    When this returns `false`, output can be written without using `with_live_output_suspended`.
+*/
+#[must_use]
+pub fn has_live_output() -> bool {
+    current_suspender().is_some()
+}
+
+/**
+    Runs the given function, which writes output, while any output which
+    is redrawn in place, such as progress bars, is hidden - so that the
+    written output does not get mixed up with it.
+*/
+pub fn with_live_output_suspended<R>(f: impl FnOnce() -> R) -> R {
+    let Some(suspender) = current_suspender() else {
+        return f();
+    };
+    let mut f = Some(f);
+    let mut result = None;
+    suspender(&mut || {
+        if let Some(f) = f.take() {
+            result = Some(f());
+        }
+    });
+    // NOTE: A suspender that does not call the function would
+    // lose the output, so it is written afterwards instead
+    match (result, f) {
+        (Some(result), _) => result,
+        (None, Some(f)) => f(),
+        (None, None) => unreachable!("function was called without returning a result"),
+    }
+}
+
+/**
+    A standard output stream that output can be written to.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/**
+    A chunk of output, and the stream that it was written to.
+*/
+pub type OutputChunk = (OutputStream, Vec<u8>);
+
+/**
+    Output written by built-in libraries of a Lua state, captured using
+    [`set_captured_output`] instead of being written to stdout and stderr.
+
+    This is a cheap handle to the same buffer, so it may be cloned and
+    read from while the Lua state keeps writing to it.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CapturedOutput(Arc<Mutex<Vec<OutputChunk>>>);
+
+impl CapturedOutput {
+    fn push(&self, stream: OutputStream, bytes: &[u8]) {
+        let mut chunks = self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match chunks.last_mut() {
+            Some((last, chunk)) if *last == stream => chunk.extend_from_slice(bytes),
+            _ => chunks.push((stream, bytes.to_vec())),
+        }
+    }
+
+    /**
+        Takes all of the output captured so far, in the order that it was
+        written in, with consecutive writes to the same stream joined together.
+    */
+    #[must_use]
+    pub fn take(&self) -> Vec<OutputChunk> {
+        std::mem::take(
+            &mut *self
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+        )
+    }
+}
+
+/**
+    Captures all output written using [`write_output`] for the given Lua state into
+    the given buffer, instead of writing it to stdout and stderr, or stops capturing it.
+*/
+pub fn set_captured_output(lua: &Lua, output: Option<CapturedOutput>) {
+    match output {
+        Some(output) => lua.set_app_data(output),
+        None => lua.remove_app_data::<CapturedOutput>(),
+    };
+}
+
+/**
+    Writes output from a built-in library of the given Lua state to stdout or stderr,
+    around any output which is redrawn in place, or to its captured output if set.
+
+    # Errors
+
+    Errors if writing to stdout or stderr fails.
+*/
+pub fn write_output(lua: &Lua, stream: OutputStream, bytes: &[u8]) -> io::Result<()> {
+    if let Some(output) = lua.app_data_ref::<CapturedOutput>() {
+        output.push(stream, bytes);
+        return Ok(());
+    }
+    with_live_output_suspended(|| match stream {
+        OutputStream::Stdout => write_flushed(io::stdout(), bytes),
+        OutputStream::Stderr => write_flushed(io::stderr(), bytes),
+    })
+}
+
+/**
+    Checks if output written using [`write_output`] for the given Lua state is captured.
+*/
+#[must_use]
+pub fn is_output_captured(lua: &Lua) -> bool {
+    lua.app_data_ref::<CapturedOutput>().is_some()
+}
+
+fn write_flushed(mut out: impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(bytes)?;
+    out.flush()
+}
@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+// Fractions with more digits than this can not be represented exactly anyway
+const MAX_FRACTION_DIGITS: usize = 18;
+
+fn duration_unit_nanos(unit: &str) -> Option<u128> {
+    Some(match unit {
+        "ns" | "nsec" | "nanosecond" | "nanoseconds" => 1,
+        "us" | "µs" | "μs" | "usec" | "microsecond" | "microseconds" => 1_000,
+        "ms" | "msec" | "millisecond" | "milliseconds" => 1_000_000,
+        "s" | "sec" | "secs" | "second" | "seconds" => NANOS_PER_SEC,
+        "m" | "min" | "mins" | "minute" | "minutes" => 60 * NANOS_PER_SEC,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60 * NANOS_PER_SEC,
+        "d" | "day" | "days" => 24 * 60 * 60 * NANOS_PER_SEC,
+        "w" | "wk" | "week" | "weeks" => 7 * 24 * 60 * 60 * NANOS_PER_SEC,
+        _ => return None,
+    })
+}
+
+fn size_unit_bytes(unit: &str) -> Option<u128> {
+    Some(match unit {
+        "" | "b" | "byte" | "bytes" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "pb" => 1_000_000_000_000_000,
+        "eb" => 1_000_000_000_000_000_000,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "p" | "pib" => 1 << 50,
+        "e" | "eib" => 1 << 60,
+        _ => return None,
+    })
+}
+
+/**
+    A number written in decimal, kept as digits so that it can be scaled without losing precision.
+*/
+struct Decimal<'a> {
+    whole: &'a str,
+    fraction: &'a str,
+}
+
+impl Decimal<'_> {
+    /**
+        Multiplies the number by the given scale, rounding down, or returns `None` if it overflows.
+    */
+    fn scale(&self, scale: u128) -> Option<u128> {
+        let whole = if self.whole.is_empty() {
+            0
+        } else {
+            self.whole.parse::<u128>().ok()?.checked_mul(scale)?
+        };
+        let fraction = &self.fraction[..self.fraction.len().min(MAX_FRACTION_DIGITS)];
+        let fraction = if fraction.is_empty() {
+            0
+        } else {
+            let digits = u32::try_from(fraction.len()).ok()?;
+            fraction.parse::<u128>().ok()?.checked_mul(scale)? / 10u128.pow(digits)
+        };
+        whole.checked_add(fraction)
+    }
+}
+
+/**
+    Splits a number, such as `1` or `1.5`, off of the start of the given string.
+*/
+fn split_decimal(s: &str) -> Option<(Decimal<'_>, &str)> {
+    let whole_len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (whole, rest) = s.split_at(whole_len);
+    let (fraction, rest) = match rest.strip_prefix('.') {
+        Some(rest) => {
+            let fraction_len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            rest.split_at(fraction_len)
+        }
+        None => ("", rest),
+    };
+    if whole.is_empty() && fraction.is_empty() {
+        None
+    } else {
+        Some((Decimal { whole, fraction }, rest))
+    }
+}
+
+/**
+    Splits a unit, such as `ms` or `MiB`, off of the start of the given
+    string, skipping any whitespace between it and the number before it.
+*/
+fn split_unit(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let unit_len = s.find(|c: char| !c.is_alphabetic()).unwrap_or(s.len());
+    s.split_at(unit_len)
+}
+
+fn nanos_to_duration(nanos: u128) -> Option<Duration> {
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+    let subsec_nanos = u32::try_from(nanos % NANOS_PER_SEC).ok()?;
+    Some(Duration::new(secs, subsec_nanos))
+}
+
+/**
+    Parses a human-friendly duration, such as `1h30m`, `2 days` or `1:30:00`.
+
+    Durations are written as numbers followed by units, which may be separated
+    by whitespace, commas, or `and`, as in `1 hour and 30 minutes`. The supported
+    units, in both short and long forms, are nanoseconds (`ns`), microseconds (`us`),
+    milliseconds (`ms`), seconds (`s`), minutes (`m`), hours (`h`), days (`d`)
+    and weeks (`w`). Months and years are not supported, since their lengths vary.
+
+    Durations may also be written as `hh:mm:ss` or `mm:ss`, or as a plain number of seconds.
+
+    # Errors
+
+    Errors if the duration is empty, could not be parsed, or is too large.
+    The error describes what is wrong with the duration, such as `unknown unit 'x'`.
+*/
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return Err(String::from("duration is empty"));
+    }
+    if trimmed.contains(':') {
+        return parse_clock(trimmed)
+            .ok_or_else(|| String::from("expected hours, minutes and seconds, such as '1:30:00'"));
+    }
+
+    let mut total: u128 = 0;
+    let mut rest = trimmed;
+    let mut first = true;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if let Some(after) = rest.strip_prefix("and") {
+            if !first && after.starts_with(char::is_whitespace) {
+                rest = after.trim_start();
+            }
+        }
+        if rest.is_empty() {
+            break;
+        }
+
+        let Some((number, after)) = split_decimal(rest) else {
+            return Err(format!("expected a number, got '{rest}'"));
+        };
+        let (unit, after) = split_unit(after);
+        let scale = if unit.is_empty() {
+            // A plain number on its own is a number of seconds
+            if first && after.trim().is_empty() {
+                NANOS_PER_SEC
+            } else {
+                return Err(String::from("missing unit after number"));
+            }
+        } else {
+            duration_unit_nanos(&unit.to_lowercase()).ok_or_else(|| {
+                format!("unknown unit '{unit}', expected one of ns, us, ms, s, m, h, d, w")
+            })?
+        };
+
+        total = number
+            .scale(scale)
+            .and_then(|nanos| total.checked_add(nanos))
+            .ok_or_else(|| String::from("duration is too large"))?;
+        rest = after;
+        first = false;
+    }
+
+    nanos_to_duration(total).ok_or_else(|| String::from("duration is too large"))
+}
+
+fn parse_clock(s: &str) -> Option<Duration> {
+    let parts = s.split(':').map(str::trim).collect::<Vec<_>>();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [minutes, seconds] => ("0", *minutes, *seconds),
+        [hours, minutes, seconds] => (*hours, *minutes, *seconds),
+        _ => return None,
+    };
+
+    let all_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(hours) || !all_digits(minutes) {
+        return None;
+    }
+    let (seconds, rest) = split_decimal(seconds)?;
+    if !rest.is_empty() || seconds.whole.is_empty() {
+        return None;
+    }
+
+    // Only the first part may be larger than its usual range, as in '90:00'
+    let hours = hours.parse::<u128>().ok()?;
+    let minutes = minutes.parse::<u128>().ok()?;
+    if parts.len() == 3 && minutes >= 60 {
+        return None;
+    }
+    let seconds = seconds.scale(NANOS_PER_SEC)?;
+    if seconds >= 60 * NANOS_PER_SEC {
+        return None;
+    }
+
+    let nanos = hours
+        .checked_mul(60 * 60 * NANOS_PER_SEC)?
+        .checked_add(minutes.checked_mul(60 * NANOS_PER_SEC)?)?
+        .checked_add(seconds)?;
+    nanos_to_duration(nanos)
+}
+
+/**
+    Parses a human-friendly size, such as `512MiB`, `1.5 GB` or `64k`, into a number of bytes.
+
+    Units are case-insensitive, and may be either decimal (`KB`, `MB`, `GB`, `TB`, `PB`, `EB`),
+    which are powers of 1000, or binary (`KiB`, `MiB`, `GiB`, `TiB`, `PiB`, `EiB`), which are
+    powers of 1024. Single letters (`K`, `M`, `G`, `T`, `P`, `E`) are binary, the same as in most
+    command line tools. A plain number, or a number followed by `B`, is a number of bytes.
+
+    Fractions of bytes are rounded down.
+
+    # Errors
+
+    Errors if the size is empty, could not be parsed, or does not fit in 64 bits.
+    The error describes what is wrong with the size, such as `unknown unit 'x'`.
+*/
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return Err(String::from("size is empty"));
+    }
+
+    let Some((number, after)) = split_decimal(trimmed) else {
+        return Err(String::from("expected a number"));
+    };
+    let (unit, after) = split_unit(after);
+    if !after.trim().is_empty() {
+        return Err(format!("unexpected '{}'", after.trim()));
+    }
+    let scale = size_unit_bytes(&unit.to_lowercase()).ok_or_else(|| {
+        format!("unknown unit '{unit}', expected one of B, KB, MB, GB, TB, KiB, MiB, GiB, TiB")
+    })?;
+
+    number
+        .scale(scale)
+        .and_then(|bytes| u64::try_from(bytes).ok())
+        .ok_or_else(|| String::from("size is too large"))
+}
@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use mlua::prelude::*;
+
+use crate::{
+    fmt::Label,
+    output::{write_output, OutputStream},
+};
+
+const ASYNC_POLL_CHUNK_NAME: &str = "__mlua_async_poll";
+
+#[derive(Debug, Default)]
+struct Warnings {
+    disabled: bool,
+    emitted: HashSet<String>,
+}
+
+/**
+    Enables or disables warnings emitted using [`emit_warning`] for the given Lua state.
+
+    Warnings are enabled by default.
+*/
+pub fn set_warnings_enabled(lua: &Lua, enabled: bool) {
+    match lua.app_data_mut::<Warnings>() {
+        Some(mut warnings) => warnings.disabled = !enabled,
+        None => {
+            lua.set_app_data(Warnings {
+                disabled: !enabled,
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/**
+    Emits a warning, such as for a deprecated argument or behavior of a
+    built-in library, from the native function that is currently running.
+
+    Each warning is only emitted once for each location in the lua code that called
+    the native function, so that warnings in loops do not flood the output, and is
+    written to stderr in the same style as the `warn` global.
+
+    Does nothing if warnings have been disabled using [`set_warnings_enabled`].
+*/
+pub fn emit_warning(lua: &Lua, message: impl AsRef<str>) {
+    let message = message.as_ref();
+
+    // NOTE: Async functions are polled from within a lua chunk created by mlua,
+    // which we skip past so that the location is where the function was called
+    let location = (1..)
+        .map_while(|level| lua.inspect_stack(level))
+        .find(|info| {
+            let source = info.source().short_src.unwrap_or_default();
+            !source.contains(ASYNC_POLL_CHUNK_NAME)
+        })
+        .and_then(|info| {
+            let line = info.curr_line();
+            let source = info.source().short_src?.to_string();
+            // NOTE: Sources of chunks with names are formatted as [string "name"]
+            let source = source
+                .strip_prefix("[string \"")
+                .and_then(|s| s.strip_suffix("\"]"))
+                .unwrap_or(&source);
+            (line > 0).then(|| format!("{source}:{line}"))
+        });
+
+    let site = format!("{}\0{message}", location.as_deref().unwrap_or_default());
+    let is_new_site = if let Some(mut warnings) = lua.app_data_mut::<Warnings>() {
+        !warnings.disabled && warnings.emitted.insert(site)
+    } else {
+        lua.set_app_data(Warnings {
+            disabled: false,
+            emitted: HashSet::from([site]),
+        });
+        true
+    };
+    if !is_new_site {
+        return;
+    }
+
+    let formatted = match location {
+        Some(location) => format!("{}\n{message}\n    at {location}\n", Label::Warn),
+        None => format!("{}\n{message}\n", Label::Warn),
+    };
+    write_output(lua, OutputStream::Stderr, formatted.as_bytes()).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::{set_captured_output, CapturedOutput};
+
+    use super::*;
+
+    fn run_captured(lua: &Lua, source: &str) -> String {
+        let output = CapturedOutput::default();
+        set_captured_output(lua, Some(output.clone()));
+        lua.load(source)
+            .set_name("warnings")
+            .exec()
+            .expect("Failed to run chunk");
+        set_captured_output(lua, None);
+        output
+            .take()
+            .into_iter()
+            .map(|(_, bytes)| String::from_utf8(bytes).expect("Output is not utf-8"))
+            .collect()
+    }
+
+    fn create_lua() -> Lua {
+        let lua = Lua::new();
+        let deprecated = lua
+            .create_function(|lua, (): ()| {
+                emit_warning(lua, "deprecated is deprecated");
+                Ok(())
+            })
+            .unwrap();
+        lua.globals().set("deprecated", deprecated).unwrap();
+        lua
+    }
+
+    #[test]
+    fn warns_once_per_call_site() {
+        let lua = create_lua();
+        let output = run_captured(
+            &lua,
+            "for _ = 1, 3 do\n\tdeprecated()\nend\ndeprecated()\ndeprecated()",
+        );
+        assert_eq!(output.matches("deprecated is deprecated").count(), 3);
+        assert_eq!(output.matches("at warnings:2\n").count(), 1);
+        assert_eq!(output.matches("at warnings:4\n").count(), 1);
+        assert_eq!(output.matches("at warnings:5\n").count(), 1);
+    }
+
+    #[test]
+    fn warns_once_per_call_site_across_chunks() {
+        let lua = create_lua();
+        let first = run_captured(&lua, "deprecated()");
+        let second = run_captured(&lua, "deprecated()");
+        assert!(first.contains("deprecated is deprecated"));
+        assert!(second.is_empty());
+    }
+
+    #[test]
+    fn does_not_warn_when_disabled() {
+        let lua = create_lua();
+        set_warnings_enabled(&lua, false);
+        assert!(run_captured(&lua, "deprecated()").is_empty());
+        set_warnings_enabled(&lua, true);
+        assert!(run_captured(&lua, "deprecated()").contains("deprecated is deprecated"));
+    }
+}
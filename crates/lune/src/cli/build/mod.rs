@@ -1,12 +1,17 @@
-use std::{path::PathBuf, process::ExitCode};
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
 use console::style;
-use tokio::fs;
 
 use crate::standalone::metadata::Metadata;
 
+use super::bundle::{modules::BundledModules, output::render_bundle};
+use super::utils::files::strip_shebang;
+
 mod base_exe;
 mod files;
 mod result;
@@ -16,7 +21,7 @@ use self::base_exe::get_or_download_base_executable;
 use self::files::{remove_source_file_ext, write_executable_file_to};
 use self::target::BuildTarget;
 
-/// Build a standalone executable
+/// Build a standalone executable, including all of the local modules that the input file requires
 #[derive(Debug, Clone, Parser)]
 pub struct BuildCommand {
     /// The path to the input file
@@ -52,12 +57,7 @@ impl BuildCommand {
             bail!("output path cannot be the same as input path, please specify a different output path");
         }
 
-        // Try to read the given input file
-        // FUTURE: We should try and resolve a full require file graph using the input
-        // path here instead, see the notes in the `standalone` module for more details
-        let source_code = fs::read(&self.input)
-            .await
-            .context("failed to read input file")?;
+        let source_code = read_source_code(&self.input).await?;
 
         // Derive the base executable path based on the arguments provided
         let base_exe_path = get_or_download_base_executable(target).await?;
@@ -67,7 +67,9 @@ impl BuildCommand {
             "Compiling standalone binary from {}",
             style(self.input.display()).green()
         );
-        let patched_bin = Metadata::create_env_patched_bin(base_exe_path, source_code)
+        // NOTE: We skip the extension here to remove it from stack traces, same as `lune run`
+        let script_name = self.input.with_extension("").display().to_string();
+        let patched_bin = Metadata::create_env_patched_bin(base_exe_path, script_name, source_code)
             .await
             .context("failed to create patched binary")?;
 
@@ -81,3 +83,134 @@ impl BuildCommand {
         Ok(ExitCode::SUCCESS)
     }
 }
+
+/**
+    Reads the source code to compile from the given input file, bundling it together
+    with all of the local modules that it requires, since there are no files to require
+    from once compiled.
+*/
+async fn read_source_code(input: &Path) -> Result<Vec<u8>> {
+    let bundle = BundledModules::discover(input)
+        .await
+        .context("failed to read input file")?;
+    for skipped in &bundle.skipped {
+        eprintln!("{} {skipped}", style("Skipped require").yellow());
+    }
+    let source_code = if bundle.modules.len() == 1 {
+        bundle.entry().source.clone()
+    } else {
+        // NOTE: The bundle starts with a source map, which is kept in the
+        // standalone binary so that errors point at the original modules
+        let required = bundle.modules.len() - 1;
+        println!(
+            "Bundling {required} required {} into standalone binary",
+            if required == 1 { "module" } else { "modules" }
+        );
+        render_bundle(&bundle)
+    };
+    Ok(strip_shebang(source_code.into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use lune::Runtime;
+    use tokio::fs;
+
+    use super::*;
+
+    async fn write_files(dir: &Path, files: &[(&str, &str)]) -> Result<()> {
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).await?;
+            fs::write(path, contents).await?;
+        }
+        Ok(())
+    }
+
+    /**
+        Compiles the given input file into a standalone binary, using an empty file as
+        the base executable, then runs the embedded script and returns any of its errors.
+    */
+    async fn compile_and_run(dir: &Path, input: &str) -> Result<String> {
+        let base_exe = dir.join("base");
+        fs::write(&base_exe, "").await?;
+        let source_code = read_source_code(&dir.join(input)).await?;
+        let patched_bin = Metadata::create_env_patched_bin(base_exe, input, source_code).await?;
+
+        let meta = Metadata::from_bytes(patched_bin)?;
+        if let Some(source_map) = meta.source_map {
+            source_map.register(&meta.name);
+        }
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_inner = Arc::clone(&errors);
+        Runtime::new()
+            .with_error_callback(move |e| errors_inner.lock().unwrap().push(e.to_string()))
+            .run(&meta.name, meta.bytecode)
+            .await?;
+        let errors = errors.lock().unwrap().join("\n");
+        Ok(errors)
+    }
+
+    #[tokio::test]
+    async fn bundles_required_modules() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lune-build-bundle-{}", std::process::id()));
+        write_files(
+            &dir,
+            &[
+                (
+                    "main.luau",
+                    "#!/usr/bin/env lune\n\
+                    local util = require(\"./lib/util\")\n\
+                    local fs = require(\"@lune/fs\")\n\
+                    assert(util.double(2) == 4)\n\
+                    assert(type(fs.readFile) == \"function\")\n\
+                    util.fail()\n",
+                ),
+                (
+                    "lib/util.luau",
+                    "local other = require(\"./other\")\n\
+                    return {\n\
+                        double = other.double,\n\
+                        fail = function()\n\
+                            error(\"failed in util\")\n\
+                        end,\n\
+                    }\n",
+                ),
+                (
+                    "lib/other.luau",
+                    "return { double = function(n) return n * 2 end }\n",
+                ),
+            ],
+        )
+        .await?;
+
+        let error = compile_and_run(&dir, "main.luau").await;
+        fs::remove_dir_all(&dir).await?;
+
+        // NOTE: Errors should point at the original files, and not at the bundle
+        let error = error?;
+        let util = Path::new("lib").join("util.luau");
+        assert!(
+            error.contains(&format!("{}\"]:5: failed in util", util.display())),
+            "error should point at the required module, got:\n{error}"
+        );
+        assert!(
+            error.contains("main.luau', Line 6"),
+            "stack trace should point at the entry file, got:\n{error}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn does_not_bundle_single_files() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lune-build-single-{}", std::process::id()));
+        write_files(&dir, &[("main.luau", "#!/usr/bin/env lune\nreturn 1\n")]).await?;
+
+        let source_code = read_source_code(&dir.join("main.luau")).await;
+        fs::remove_dir_all(&dir).await?;
+        assert_eq!(source_code?, b"\nreturn 1\n");
+        Ok(())
+    }
+}
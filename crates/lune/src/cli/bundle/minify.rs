@@ -0,0 +1,564 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use full_moon::{
+    ast::{
+        punctuated::Punctuated, Block, Call, Expression, Field, FunctionArgs, FunctionBody,
+        FunctionCall, Index, LastStmt, Parameter, Prefix, Stmt, Suffix, TableConstructor, Var,
+    },
+    node::Node,
+    tokenizer::{Token, TokenReference, TokenType},
+    LuaVersion,
+};
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "export", "false", "for",
+    "function", "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true",
+    "type", "typeof", "until", "while",
+];
+
+/**
+    Options for making the source code of bundled modules smaller and less readable.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct MinifyOptions {
+    /// Strip comments and any whitespace that is not needed.
+    pub strip_whitespace: bool,
+    /// Rename local variables, functions and parameters to short names.
+    pub mangle: bool,
+    /// Names that should never be renamed, or used as new names.
+    pub keep: HashSet<String>,
+}
+
+impl MinifyOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.strip_whitespace || self.mangle
+    }
+}
+
+/**
+    Minifies the given Luau source code.
+
+    Line breaks are always kept, even when stripping whitespace, so that
+    every line of the minified source is the same line in the original,
+    which lets errors still point at the correct lines in the original files.
+*/
+pub fn minify(source: &str, options: &MinifyOptions) -> Result<String> {
+    let ast = full_moon::parse_fallible(source, LuaVersion::luau())
+        .into_result()
+        .map_err(|errors| {
+            let messages = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+            anyhow!("failed to parse module\n{}", messages.join("\n"))
+        })?;
+
+    let renames = if options.mangle {
+        let mut mangler = Mangler::new(&ast, &options.keep);
+        mangler.block(ast.nodes());
+        mangler.renames
+    } else {
+        HashMap::new()
+    };
+
+    let mut output = Output {
+        contents: String::with_capacity(source.len()),
+        strip_whitespace: options.strip_whitespace,
+        pending_newlines: 0,
+        last_was_number: false,
+    };
+    // NOTE: Tokens of nodes with delimiters, such as parentheses, are not
+    // always iterated in the same order as in the source, so they are sorted
+    let mut tokens = ast.nodes().tokens().chain([ast.eof()]).collect::<Vec<_>>();
+    tokens.sort_by_key(|token| token.token().start_position().bytes());
+    for token in tokens {
+        for trivia in token.leading_trivia() {
+            output.trivia(trivia);
+        }
+        if let Some(name) = renames.get(&token.token().start_position().bytes()) {
+            output.push(name, false);
+        } else {
+            let is_number = matches!(token.token_type(), TokenType::Number { .. });
+            output.push(&token.token().to_string(), is_number);
+        }
+        for trivia in token.trailing_trivia() {
+            output.trivia(trivia);
+        }
+    }
+    output.flush_newlines();
+
+    Ok(output.contents)
+}
+
+/**
+    Walks through the AST, keeping track of which locals are in scope,
+    to find new names for every local and all of the references to it.
+
+    Every declaration gets its own unique name, which also makes sure that
+    no new name can ever shadow another local, or any global that is used.
+*/
+struct Mangler<'a> {
+    keep: &'a HashSet<String>,
+    taken: HashSet<String>,
+    scopes: Vec<HashMap<String, String>>,
+    renames: HashMap<usize, String>,
+    next_name: usize,
+}
+
+impl<'a> Mangler<'a> {
+    fn new(ast: &full_moon::ast::Ast, keep: &'a HashSet<String>) -> Self {
+        let taken = ast
+            .nodes()
+            .tokens()
+            .filter(|token| matches!(token.token_type(), TokenType::Identifier { .. }))
+            .map(|token| token.token().to_string())
+            .chain(keep.iter().cloned())
+            .chain(KEYWORDS.iter().map(ToString::to_string))
+            .collect();
+        Self {
+            keep,
+            taken,
+            scopes: Vec::new(),
+            renames: HashMap::new(),
+            next_name: 0,
+        }
+    }
+
+    fn generate_name(&mut self) -> String {
+        const FIRST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_";
+        const REST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ_0123456789";
+        loop {
+            let mut n = self.next_name;
+            self.next_name += 1;
+            let mut name = String::from(FIRST[n % FIRST.len()] as char);
+            n /= FIRST.len();
+            while n > 0 {
+                n -= 1;
+                name.push(REST[n % REST.len()] as char);
+                n /= REST.len();
+            }
+            if !self.taken.contains(&name) {
+                return name;
+            }
+        }
+    }
+
+    fn declare(&mut self, token: &TokenReference) {
+        let name = token.token().to_string();
+        // NOTE: The implicit self parameter of methods can
+        // not be renamed, so any other self is also kept
+        let new_name = if name == "self" || self.keep.contains(&name) {
+            name.clone()
+        } else {
+            self.generate_name()
+        };
+        self.renames
+            .insert(token.token().start_position().bytes(), new_name.clone());
+        self.scopes
+            .last_mut()
+            .expect("locals are always declared in a scope")
+            .insert(name, new_name);
+    }
+
+    fn declare_kept(&mut self, token: &TokenReference) {
+        let name = token.token().to_string();
+        self.scopes
+            .last_mut()
+            .expect("locals are always declared in a scope")
+            .insert(name.clone(), name);
+    }
+
+    fn reference(&mut self, token: &TokenReference) {
+        let name = token.token().to_string();
+        let new_name = self.scopes.iter().rev().find_map(|scope| scope.get(&name));
+        if let Some(new_name) = new_name {
+            self.renames
+                .insert(token.token().start_position().bytes(), new_name.clone());
+        }
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scoped(|this| this.block_contents(block));
+    }
+
+    fn block_contents(&mut self, block: &Block) {
+        for stmt in block.stmts() {
+            self.stmt(stmt);
+        }
+        if let Some(LastStmt::Return(ret)) = block.last_stmt() {
+            self.expressions(ret.returns());
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assignment(assignment) => {
+                for var in assignment.variables() {
+                    self.var(var);
+                }
+                self.expressions(assignment.expressions());
+            }
+            Stmt::CompoundAssignment(assignment) => {
+                self.var(assignment.lhs());
+                self.expression(assignment.rhs());
+            }
+            Stmt::Do(stmt) => self.block(stmt.block()),
+            Stmt::FunctionCall(call) => self.function_call(call),
+            Stmt::FunctionDeclaration(declaration) => {
+                if let Some(name) = declaration.name().names().iter().next() {
+                    self.reference(name);
+                }
+                self.function_body(declaration.body());
+            }
+            Stmt::GenericFor(stmt) => {
+                self.expressions(stmt.expressions());
+                self.scoped(|this| {
+                    for name in stmt.names() {
+                        this.declare(name);
+                    }
+                    this.block_contents(stmt.block());
+                });
+            }
+            Stmt::If(stmt) => self.scoped(|this| {
+                if let Some(binding) = stmt.binding() {
+                    this.declare_kept(binding.name());
+                }
+                this.expression(stmt.condition());
+                this.block(stmt.block());
+                for else_if in stmt.else_if().into_iter().flatten() {
+                    if let Some(binding) = else_if.binding() {
+                        this.declare_kept(binding.name());
+                    }
+                    this.expression(else_if.condition());
+                    this.block(else_if.block());
+                }
+                if let Some(block) = stmt.else_block() {
+                    this.block(block);
+                }
+            }),
+            Stmt::LocalAssignment(assignment) => {
+                self.expressions(assignment.expressions());
+                for name in assignment.names() {
+                    self.declare(name);
+                }
+            }
+            Stmt::ConstAssignment(assignment) => {
+                self.expressions(assignment.expressions());
+                for name in assignment.names() {
+                    self.declare(name);
+                }
+            }
+            Stmt::LocalFunction(function) => {
+                self.declare(function.name());
+                self.function_body(function.body());
+            }
+            Stmt::ConstFunction(function) => {
+                self.declare(function.name());
+                self.function_body(function.body());
+            }
+            Stmt::NumericFor(stmt) => {
+                self.expression(stmt.start());
+                self.expression(stmt.end());
+                if let Some(step) = stmt.step() {
+                    self.expression(step);
+                }
+                self.scoped(|this| {
+                    this.declare(stmt.index_variable());
+                    this.block_contents(stmt.block());
+                });
+            }
+            Stmt::Repeat(stmt) => self.scoped(|this| {
+                // NOTE: Locals declared in the body of the loop are still in scope for the condition
+                this.block_contents(stmt.block());
+                this.expression(stmt.until());
+            }),
+            Stmt::While(stmt) => {
+                self.expression(stmt.condition());
+                self.block(stmt.block());
+            }
+            _ => {}
+        }
+    }
+
+    fn function_body(&mut self, body: &FunctionBody) {
+        self.scoped(|this| {
+            for parameter in body.parameters() {
+                if let Parameter::Name(name) = parameter {
+                    this.declare(name);
+                }
+            }
+            this.block_contents(body.block());
+        });
+    }
+
+    fn function_call(&mut self, call: &FunctionCall) {
+        self.prefix(call.prefix());
+        for suffix in call.suffixes() {
+            self.suffix(suffix);
+        }
+    }
+
+    fn expressions(&mut self, expressions: &Punctuated<Expression>) {
+        for expression in expressions {
+            self.expression(expression);
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::BinaryOperator { lhs, rhs, .. } => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            Expression::Parentheses { expression, .. }
+            | Expression::UnaryOperator { expression, .. }
+            | Expression::TypeAssertion { expression, .. } => self.expression(expression),
+            Expression::Function(function) => self.function_body(function.body()),
+            Expression::FunctionCall(call) => self.function_call(call),
+            Expression::IfExpression(expression) => self.scoped(|this| {
+                if let Some(binding) = expression.binding() {
+                    this.declare_kept(binding.name());
+                }
+                this.expression(expression.condition());
+                this.expression(expression.if_expression());
+                for else_if in expression.else_if_expressions().into_iter().flatten() {
+                    if let Some(binding) = else_if.binding() {
+                        this.declare_kept(binding.name());
+                    }
+                    this.expression(else_if.condition());
+                    this.expression(else_if.expression());
+                }
+                this.expression(expression.else_expression());
+            }),
+            Expression::InterpolatedString(string) => {
+                for expression in string.expressions() {
+                    self.expression(expression);
+                }
+            }
+            Expression::TableConstructor(table) => self.table_constructor(table),
+            Expression::Var(var) => self.var(var),
+            _ => {}
+        }
+    }
+
+    fn table_constructor(&mut self, table: &TableConstructor) {
+        for field in table.fields() {
+            match field {
+                Field::ExpressionKey { key, value, .. } => {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                Field::NameKey { value, .. } => self.expression(value),
+                Field::NoKey(value) => self.expression(value),
+                _ => {}
+            }
+        }
+    }
+
+    fn var(&mut self, var: &Var) {
+        match var {
+            Var::Name(name) => self.reference(name),
+            Var::Expression(expression) => {
+                self.prefix(expression.prefix());
+                for suffix in expression.suffixes() {
+                    self.suffix(suffix);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn prefix(&mut self, prefix: &Prefix) {
+        match prefix {
+            Prefix::Name(name) => self.reference(name),
+            Prefix::Expression(expression) => self.expression(expression),
+            _ => {}
+        }
+    }
+
+    fn suffix(&mut self, suffix: &Suffix) {
+        match suffix {
+            Suffix::Call(Call::AnonymousCall(args)) => self.function_args(args),
+            Suffix::Call(Call::MethodCall(call)) => self.function_args(call.args()),
+            Suffix::Index(Index::Brackets { expression, .. }) => self.expression(expression),
+            _ => {}
+        }
+    }
+
+    fn function_args(&mut self, args: &FunctionArgs) {
+        match args {
+            FunctionArgs::Parentheses { arguments, .. } => self.expressions(arguments),
+            FunctionArgs::TableConstructor(table) => self.table_constructor(table),
+            _ => {}
+        }
+    }
+}
+
+struct Output {
+    contents: String,
+    strip_whitespace: bool,
+    pending_newlines: usize,
+    last_was_number: bool,
+}
+
+impl Output {
+    fn trivia(&mut self, trivia: &Token) {
+        let text = trivia.to_string();
+        if !self.strip_whitespace || matches!(trivia.token_type(), TokenType::Shebang { .. }) {
+            self.contents.push_str(&text);
+        } else {
+            self.pending_newlines += text.matches('\n').count();
+        }
+    }
+
+    fn push(&mut self, text: &str, is_number: bool) {
+        if self.pending_newlines > 0 {
+            self.flush_newlines();
+        } else if self.strip_whitespace && self.needs_separator(text) {
+            self.contents.push(' ');
+        }
+        self.contents.push_str(text);
+        self.last_was_number = is_number;
+    }
+
+    fn flush_newlines(&mut self) {
+        for _ in 0..self.pending_newlines {
+            self.contents.push('\n');
+        }
+        self.pending_newlines = 0;
+    }
+
+    /**
+        Checks if the given text would be read as part of the last
+        token, or form a different token, if there was no space between.
+    */
+    fn needs_separator(&self, text: &str) -> bool {
+        const JOINING_SYMBOLS: &str = "-.=<>~:/[";
+        let (Some(last), Some(next)) = (self.contents.chars().last(), text.chars().next()) else {
+            return false;
+        };
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        (is_word(last) && is_word(next))
+            || (self.last_was_number && next == '.')
+            || (JOINING_SYMBOLS.contains(last) && JOINING_SYMBOLS.contains(next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mlua::prelude::*;
+
+    use super::*;
+
+    fn strip(source: &str) -> String {
+        let options = MinifyOptions {
+            strip_whitespace: true,
+            ..MinifyOptions::default()
+        };
+        minify(source, &options).unwrap()
+    }
+
+    fn mangle(source: &str, keep: &[&str]) -> String {
+        let options = MinifyOptions {
+            mangle: true,
+            keep: keep.iter().map(ToString::to_string).collect(),
+            ..MinifyOptions::default()
+        };
+        minify(source, &options).unwrap()
+    }
+
+    fn eval(source: &str) -> String {
+        Lua::new().load(source).eval::<String>().unwrap()
+    }
+
+    const PROGRAM: &str = r#"
+-- Adds up some numbers, with a few locals that shadow each other
+local total = 0
+local function add(value: number)
+    local total = total + value -- this is a different total
+    return total
+end
+for i = 1, 3 do
+    local value = add(i)
+    total += value
+end
+local list = { total, 1 .. 2, 10 - -1, [[long
+string]] }
+local self = { name = "kept" }
+function self:describe() return self.name end
+return `{table.concat(list, ",")} {self:describe()} {#list}`
+"#;
+
+    #[test]
+    fn strip_whitespace_keeps_lines() {
+        let minified = strip("local  a = 1 -- comment\n\n--[[ block\ncomment ]]\nreturn a  +  a\n");
+        assert_eq!(minified, "local a=1\n\n\n\nreturn a+a\n");
+    }
+
+    #[test]
+    fn strip_whitespace_separates_tokens() {
+        assert_eq!(strip("return 1 .. 2"), "return 1 ..2");
+        assert_eq!(strip("return 1 - -1"), "return 1- -1");
+        assert_eq!(
+            strip("local t = {} t [ [[a]] ] = 1"),
+            "local t={}t[ [[a]]]=1"
+        );
+    }
+
+    #[test]
+    fn strip_whitespace_keeps_shebang() {
+        assert_eq!(
+            strip("#!/usr/bin/env lune\nprint( 1 )"),
+            "#!/usr/bin/env lune\nprint(1)"
+        );
+    }
+
+    #[test]
+    fn mangle_renames_locals_only() {
+        let mangled = mangle("local value = 1\nprint(value, value.field, math.pi)", &[]);
+        assert_eq!(mangled, "local a = 1\nprint(a, a.field, math.pi)");
+    }
+
+    #[test]
+    fn mangle_avoids_existing_names() {
+        let mangled = mangle("local first = 1\nreturn a + first", &[]);
+        assert_eq!(mangled, "local b = 1\nreturn a + b");
+    }
+
+    #[test]
+    fn mangle_keeps_names() {
+        let mangled = mangle("local kept, other = 1, 2\nreturn kept + other", &["kept"]);
+        assert_eq!(mangled, "local kept, a = 1, 2\nreturn kept + a");
+    }
+
+    #[test]
+    fn mangle_scopes() {
+        let mangled = mangle("local x = 1\ndo local x = x + 1 end\nreturn x", &[]);
+        assert_eq!(mangled, "local a = 1\ndo local b = a + 1 end\nreturn a");
+    }
+
+    #[test]
+    fn minified_programs_behave_the_same() {
+        let expected = eval(PROGRAM);
+        assert_eq!(expected, "11,12,11,long\nstring kept 4");
+        assert_eq!(eval(&strip(PROGRAM)), expected);
+        assert_eq!(eval(&mangle(PROGRAM, &[])), expected);
+        let options = MinifyOptions {
+            strip_whitespace: true,
+            mangle: true,
+            ..MinifyOptions::default()
+        };
+        let minified = minify(PROGRAM, &options).unwrap();
+        assert_eq!(minified.lines().count(), PROGRAM.lines().count());
+        assert_eq!(eval(&minified), expected);
+    }
+
+    #[test]
+    fn invalid_syntax() {
+        assert!(minify("local = 1", &MinifyOptions::default()).is_err());
+    }
+}
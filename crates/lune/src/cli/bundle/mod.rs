@@ -0,0 +1,91 @@
+use std::{collections::HashSet, path::PathBuf, process::ExitCode};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use console::style;
+use tokio::fs;
+
+mod minify;
+pub(crate) mod modules;
+pub(crate) mod output;
+mod requires;
+
+use self::minify::{minify, MinifyOptions};
+use self::modules::BundledModules;
+use self::output::render_bundle;
+
+/// Bundle a script and all of the modules it requires into a single file
+#[derive(Debug, Clone, Parser)]
+pub struct BundleCommand {
+    /// The path to the entry file
+    pub input: PathBuf,
+
+    /// The path to the output file - defaults to the
+    /// input file path with a `.bundle.luau` extension
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Strip comments and whitespace from bundled modules
+    #[clap(long)]
+    pub minify: bool,
+
+    /// Rename local variables in bundled modules to short names
+    #[clap(long)]
+    pub mangle: bool,
+
+    /// Names that should never be renamed when mangling, separated by commas
+    #[clap(long, value_delimiter = ',', requires = "mangle")]
+    pub keep: Vec<String>,
+}
+
+impl BundleCommand {
+    pub async fn run(self) -> Result<ExitCode> {
+        let output_path = self
+            .output
+            .clone()
+            .unwrap_or_else(|| self.input.with_extension("bundle.luau"));
+        if output_path == self.input {
+            bail!("output path cannot be the same as input path");
+        }
+
+        // Find all of the modules that the input file requires, making sure to warn about
+        // any that could not be bundled so that they don't fail unexpectedly at runtime
+        let mut bundle = BundledModules::discover(&self.input)
+            .await
+            .context("failed to bundle input file")?;
+        for skipped in &bundle.skipped {
+            eprintln!("{} {skipped}", style("Skipped require").yellow());
+        }
+
+        println!(
+            "Bundling {} {} from {}",
+            bundle.modules.len(),
+            if bundle.modules.len() == 1 {
+                "module"
+            } else {
+                "modules"
+            },
+            style(self.input.display()).green()
+        );
+
+        let options = MinifyOptions {
+            strip_whitespace: self.minify,
+            mangle: self.mangle,
+            keep: self.keep.iter().cloned().collect::<HashSet<_>>(),
+        };
+        if options.is_enabled() {
+            for module in &mut bundle.modules {
+                module.source = minify(&module.source, &options)
+                    .with_context(|| format!("failed to minify {}", module.path.display()))?;
+            }
+        }
+        let contents = render_bundle(&bundle);
+
+        println!("Writing bundle to {}", style(output_path.display()).blue());
+        fs::write(&output_path, contents)
+            .await
+            .context("failed to write bundle")?;
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
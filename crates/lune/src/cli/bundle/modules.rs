@@ -0,0 +1,165 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use tokio::fs;
+
+use lune_utils::path::{clean_path_and_make_absolute, diff_path};
+
+use super::requires::find_requires;
+
+/**
+    A module that is included in a bundle.
+*/
+#[derive(Debug, Clone)]
+pub struct BundledModule {
+    /// The name of the module, which is its path relative to the entry module.
+    pub name: String,
+    /// The path to the module on disk, used for error messages.
+    pub path: PathBuf,
+    /// The source code of the module.
+    pub source: String,
+    /// The paths that the module requires, mapped to the names of their bundled modules.
+    pub requires: BTreeMap<String, String>,
+}
+
+/**
+    All of the modules that are included in a bundle, in the order that they
+    were found in, starting with the entry module and then its requires.
+*/
+#[derive(Debug, Clone)]
+pub struct BundledModules {
+    pub modules: Vec<BundledModule>,
+    /// Requires that could not be bundled, which are left as-is, along with the reason why.
+    pub skipped: Vec<String>,
+}
+
+impl BundledModules {
+    /**
+        Finds the entry module at the given path, and all local
+        modules that it requires, both directly and indirectly.
+    */
+    pub async fn discover(entry: impl AsRef<Path>) -> Result<Self> {
+        let entry = clean_path_and_make_absolute(entry.as_ref());
+        let root = entry
+            .parent()
+            .context("entry file has no parent directory")?
+            .to_path_buf();
+
+        let mut names = HashMap::new();
+        let mut queue = VecDeque::from([entry.clone()]);
+        let mut modules = Vec::new();
+        let mut skipped = Vec::new();
+
+        names.insert(entry.clone(), module_name(&root, &entry));
+        while let Some(path) = queue.pop_front() {
+            let display = diff_path(&path, lune_utils::path::get_current_dir())
+                .unwrap_or_else(|| path.clone());
+            let bytes = fs::read(&path)
+                .await
+                .with_context(|| format!("failed to read {}", display.display()))?;
+            let source = String::from_utf8(bytes)
+                .with_context(|| format!("{} is not valid utf-8", display.display()))?;
+
+            let mut requires = BTreeMap::new();
+            for call in find_requires(&source) {
+                let location = format!("{}:{}", display.display(), call.line);
+                let Some(required) = call.path else {
+                    skipped.push(format!(
+                        "{location} - the path is not a string literal, so it will be required at runtime"
+                    ));
+                    continue;
+                };
+                if required.starts_with('@') {
+                    // NOTE: Built-in libraries, virtual modules and aliases are
+                    // resolved by the runtime that the bundle is eventually run with
+                    if !required.starts_with("@lune/") {
+                        skipped.push(format!(
+                            "{location} - '{required}' is not a relative path, so it will be required at runtime"
+                        ));
+                    }
+                    continue;
+                }
+                if requires.contains_key(&required) {
+                    continue;
+                }
+
+                let base = path.parent().unwrap_or(&root);
+                let Some(resolved) = resolve_module_path(&base.join(&required)).await else {
+                    bail!(
+                        "{} could not be resolved - no file exists at the path '{}'",
+                        style(&location).yellow(),
+                        required
+                    );
+                };
+                let name = names.entry(resolved.clone()).or_insert_with(|| {
+                    queue.push_back(resolved.clone());
+                    module_name(&root, &resolved)
+                });
+                requires.insert(required, name.clone());
+            }
+
+            modules.push(BundledModule {
+                name: names[&path].clone(),
+                path: display,
+                source,
+                requires,
+            });
+        }
+
+        Ok(Self { modules, skipped })
+    }
+
+    /**
+        Gets the entry module, which is always the first module.
+    */
+    pub fn entry(&self) -> &BundledModule {
+        &self.modules[0]
+    }
+}
+
+/**
+    Resolves a required path to a file, the same way that `require` does:
+
+    1. The exact path
+    2. The path with an added `luau` or `lua` extension
+    3. An `init` file with a `luau` or `lua` extension in a directory at the path
+*/
+async fn resolve_module_path(path: &Path) -> Option<PathBuf> {
+    let path = clean_path_and_make_absolute(path);
+    let init = path.join("init");
+    let candidates = [
+        path.clone(),
+        append_extension(&path, "luau"),
+        append_extension(&path, "lua"),
+        append_extension(&init, "luau"),
+        append_extension(&init, "lua"),
+    ];
+    for candidate in candidates {
+        if fs::metadata(&candidate).await.is_ok_and(|m| m.is_file()) {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    match path.extension() {
+        None => path.with_extension(extension),
+        Some(existing) => {
+            path.with_extension(format!("{}.{extension}", existing.to_string_lossy()))
+        }
+    }
+}
+
+fn module_name(root: &Path, path: &Path) -> String {
+    let relative = diff_path(path, root).unwrap_or_else(|| path.to_path_buf());
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use lune_utils::fmt::SourceMap;
+
+use super::modules::{BundledModule, BundledModules};
+
+// Defines and loads bundled modules - each module gets its own `require`
+// function which loads the bundled modules it requires, and falls back
+// to the global `require` for anything that was not bundled, such as
+// built-in libraries, so that modules behave the same as when unbundled
+const PRELUDE: &str = r"local __lune_bundle = (function()
+	local globalRequire = require
+	local modules = {}
+	local results = {}
+	local loading = {}
+	local bundle = {}
+
+	function bundle.load(name, ...)
+		local result = results[name]
+		if result == nil then
+			if loading[name] then
+				error(`Circular require detected for bundled module '{name}'`, 3)
+			end
+			loading[name] = true
+			local module = modules[name]
+			result = table.pack(module.loader(module.require, ...))
+			loading[name] = nil
+			results[name] = result
+		end
+		return table.unpack(result, 1, result.n)
+	end
+
+	function bundle.define(name, requires, loader)
+		modules[name] = {
+			loader = loader,
+			require = function(path, ...)
+				local required = requires[path]
+				if required ~= nil then
+					return bundle.load(required)
+				end
+				return globalRequire(path, ...)
+			end,
+		}
+	end
+
+	return bundle
+end)()
+";
+
+const BUNDLE_NAME: &str = "__lune_bundle";
+
+/**
+    Renders all of the given modules into a single Luau source file.
+
+    The file starts with a source map, listing which lines in it belong to
+    each of the original modules, so that errors can point at the original files.
+*/
+pub fn render_bundle(bundle: &BundledModules) -> String {
+    let entry = bundle.entry();
+    let (shebang, entry_source) = split_shebang(&entry.source);
+
+    let modules = bundle
+        .modules
+        .iter()
+        .map(|module| {
+            let source = if module.name == entry.name {
+                entry_source
+            } else {
+                split_shebang(&module.source).1
+            };
+            (module, render_define(module, source))
+        })
+        .collect::<Vec<_>>();
+
+    // NOTE: The header has one line per module, so the line numbers
+    // of everything after it are known before the header is written
+    let header_lines = 4 + modules.len() + usize::from(shebang.is_some());
+    let mut line = header_lines + count_lines(PRELUDE) + 2;
+    let mut header = String::new();
+    if let Some(shebang) = shebang {
+        header.push_str(shebang);
+        header.push('\n');
+    }
+    writeln!(header, "-- Bundled by Lune from {}", entry.path.display()).unwrap();
+    header.push_str("--\n");
+
+    let mut map = SourceMap::new();
+    let mut body = String::new();
+    for (module, (define, body_offset)) in &modules {
+        let first = line + body_offset;
+        let last = first + count_lines(split_shebang(&module.source).1).saturating_sub(1);
+        map.add(first, last, module.path.display().to_string());
+        line += count_lines(define) + 1;
+        body.push_str(define);
+        body.push_str("\n\n");
+    }
+    write!(header, "{map}").unwrap();
+
+    format!(
+        "{header}{PRELUDE}\n{body}return {BUNDLE_NAME}.load({:?}, ...)\n",
+        entry.name
+    )
+}
+
+/**
+    Renders the definition of a single module, returning it along with the
+    offset of the line that its source starts on, relative to the definition.
+*/
+fn render_define(module: &BundledModule, source: &str) -> (String, usize) {
+    let mut define = String::new();
+    writeln!(define, "{BUNDLE_NAME}.define({:?}, {{", module.name).unwrap();
+    for (path, name) in &module.requires {
+        writeln!(define, "\t[{path:?}] = {name:?},").unwrap();
+    }
+    define.push_str("}, function(require, ...)\n");
+    let offset = count_lines(&define);
+    define.push_str(source);
+    if !source.ends_with('\n') {
+        define.push('\n');
+    }
+    define.push_str("end)");
+    (define, offset)
+}
+
+/**
+    Splits a shebang line off of the given source, replacing
+    it with an empty line so that line numbers stay the same.
+*/
+fn split_shebang(source: &str) -> (Option<&str>, &str) {
+    if source.starts_with("#!") {
+        let end = source.find('\n').unwrap_or(source.len());
+        (Some(&source[..end]), &source[end..])
+    } else {
+        (None, source)
+    }
+}
+
+fn count_lines(s: &str) -> usize {
+    s.lines().count()
+}
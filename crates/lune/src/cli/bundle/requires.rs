@@ -0,0 +1,314 @@
+/**
+    A call to `require` that was found in the source code of a module.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequireCall {
+    /// The path given to `require`, or `None` if it is not a string literal.
+    pub path: Option<String>,
+    /// The line that the call is on, starting at 1.
+    pub line: usize,
+}
+
+/**
+    Finds all calls to `require` in the given Luau source code.
+
+    This is not a full parser, but it does skip comments and strings, so that
+    any text that only looks like a call to `require` is not included.
+*/
+pub fn find_requires(source: &str) -> Vec<RequireCall> {
+    let mut scanner = Scanner::new(source);
+    let mut calls = Vec::new();
+    let mut previous = None;
+
+    while let Some(c) = scanner.peek() {
+        match c {
+            b'-' if scanner.peek_at(1) == Some(b'-') => {
+                scanner.skip_comment();
+                continue;
+            }
+            b'"' | b'\'' | b'`' => {
+                scanner.skip_string(c);
+            }
+            b'[' if scanner.long_bracket_level().is_some() => {
+                scanner.skip_long_bracket();
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let line = scanner.line;
+                let word = scanner.read_word();
+                // NOTE: Fields and methods that happen to be called require,
+                // such as `module.require(...)`, are not calls to the global
+                let is_field = matches!(previous, Some(b'.' | b':'));
+                if word == "require" && !is_field {
+                    calls.extend(scanner.read_require_call(line));
+                }
+                previous = Some(b'a');
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                scanner.advance();
+                continue;
+            }
+            _ => {
+                scanner.advance();
+            }
+        }
+        previous = Some(c);
+    }
+
+    calls
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            bytes: source.as_bytes(),
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn advance(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        if c == b'\n' {
+            self.line += 1;
+        }
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.advance();
+        }
+    }
+
+    fn read_word(&mut self) -> &'a str {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_')
+        {
+            self.advance();
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default()
+    }
+
+    /**
+        Checks if a long bracket, such as `[[` or `[==[`, starts at the current
+        position, and returns the number of equals signs in it if it does.
+    */
+    fn long_bracket_level(&self) -> Option<usize> {
+        if self.peek() != Some(b'[') {
+            return None;
+        }
+        let mut level = 0;
+        while self.peek_at(level + 1) == Some(b'=') {
+            level += 1;
+        }
+        (self.peek_at(level + 1) == Some(b'[')).then_some(level)
+    }
+
+    fn skip_long_bracket(&mut self) {
+        let Some(level) = self.long_bracket_level() else {
+            return;
+        };
+        for _ in 0..level + 2 {
+            self.advance();
+        }
+        while let Some(c) = self.advance() {
+            if c == b']'
+                && (0..level).all(|i| self.peek_at(i) == Some(b'='))
+                && self.peek_at(level) == Some(b']')
+            {
+                for _ in 0..=level {
+                    self.advance();
+                }
+                return;
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        self.advance();
+        self.advance();
+        if self.long_bracket_level().is_some() {
+            self.skip_long_bracket();
+        } else {
+            while self.peek().is_some_and(|c| c != b'\n') {
+                self.advance();
+            }
+        }
+    }
+
+    /**
+        Skips past a quoted string, returning its contents if it
+        is a simple string without any interpolation or escapes
+        other than the most common ones.
+    */
+    fn skip_string(&mut self, quote: u8) -> Option<String> {
+        self.advance();
+        let mut contents = Some(Vec::new());
+        while let Some(c) = self.advance() {
+            match c {
+                c if c == quote => return contents.and_then(|c| String::from_utf8(c).ok()),
+                b'\n' if quote != b'`' => return None,
+                b'\\' => {
+                    let escaped = match self.advance() {
+                        Some(b'n') => Some(b'\n'),
+                        Some(b't') => Some(b'\t'),
+                        Some(c @ (b'\\' | b'"' | b'\'' | b'`' | b'{')) => Some(c),
+                        _ => None,
+                    };
+                    match (contents.as_mut(), escaped) {
+                        (Some(contents), Some(escaped)) => contents.push(escaped),
+                        _ => contents = None,
+                    }
+                }
+                b'{' if quote == b'`' => {
+                    contents = None;
+                    self.skip_interpolation();
+                }
+                c => {
+                    if let Some(contents) = contents.as_mut() {
+                        contents.push(c);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn skip_interpolation(&mut self) {
+        let mut depth = 1;
+        while let Some(c) = self.peek() {
+            match c {
+                b'"' | b'\'' | b'`' => {
+                    self.skip_string(c);
+                    continue;
+                }
+                b'{' => depth += 1,
+                b'}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance();
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /**
+        Reads a call to `require`, right after the name, returning
+        `None` if the name is not followed by any call arguments.
+    */
+    fn read_require_call(&mut self, line: usize) -> Option<RequireCall> {
+        self.skip_whitespace();
+        let path = match self.peek()? {
+            c @ (b'"' | b'\'') => self.skip_string(c),
+            b'(' => {
+                self.advance();
+                self.skip_whitespace();
+                let path = match self.peek() {
+                    Some(c @ (b'"' | b'\'' | b'`')) => self.skip_string(c),
+                    _ => None,
+                };
+                self.skip_whitespace();
+                // NOTE: Calls such as require("a" .. "b") are not string literals
+                path.filter(|_| self.peek() == Some(b')'))
+            }
+            _ => return None,
+        };
+        Some(RequireCall { path, line })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(source: &str) -> Vec<Option<String>> {
+        find_requires(source).into_iter().map(|c| c.path).collect()
+    }
+
+    fn path(path: &str) -> Option<String> {
+        Some(path.to_string())
+    }
+
+    #[test]
+    fn finds_calls_and_lines() {
+        let source = "local a = require(\"./a\")\n\nlocal b = require './b'\nrequire \"./c\"";
+        assert_eq!(
+            find_requires(source),
+            [
+                RequireCall {
+                    path: path("./a"),
+                    line: 1
+                },
+                RequireCall {
+                    path: path("./b"),
+                    line: 3
+                },
+                RequireCall {
+                    path: path("./c"),
+                    line: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_strings() {
+        let source = r#"
+            -- require("./line")
+            --[[ require("./block") ]]
+            --[==[ require("./level") ]] ]==]
+            local s = "require('./string')"
+            local l = [[require("./long")]]
+            local i = `require("{name}")`
+            require("./real")
+        "#;
+        assert_eq!(paths(source), [path("./real")]);
+    }
+
+    #[test]
+    fn skips_fields_and_methods() {
+        let source = "module.require('./a')\nmodule:require('./b')\nmy_require('./c')";
+        assert_eq!(paths(source), Vec::<Option<String>>::new());
+    }
+
+    #[test]
+    fn non_literal_paths() {
+        let source = "require(name)\nrequire('./a' .. b)\nrequire(`./{c}`)\nrequire(`./d`)";
+        assert_eq!(paths(source), [None, None, None, path("./d")]);
+    }
+
+    #[test]
+    fn escapes_in_paths() {
+        let source = r#"require("./a\\b")
+require('./it\'s')
+require("./\q")"#;
+        assert_eq!(paths(source), [path("./a\\b"), path("./it's"), None]);
+    }
+
+    #[test]
+    fn name_without_call() {
+        assert_eq!(paths("local r = require\nr('./a')"), Vec::new());
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    fs::{create_dir_all, write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use console::style;
+use tokio::fs::{read as read_to_vec, read_dir};
+
+use lune::{FuzzEvent, FuzzOptions, Runtime};
+
+use super::utils::{
+    config::LuneConfig,
+    files::{discover_script_path_including_lune_dirs, strip_shebang},
+};
+
+const CRASHES_DIR_NAME: &str = "crashes";
+
+/// Fuzz a script by calling its exported fuzz function with generated inputs
+#[derive(Debug, Clone, Parser)]
+pub struct FuzzCommand {
+    /// Directory to load inputs from and save new inputs to, defaults to the script path with a `.corpus` extension
+    #[clap(long)]
+    corpus: Option<PathBuf>,
+    /// Maximum number of inputs to run, runs until a crash is found by default
+    #[clap(long)]
+    runs: Option<u64>,
+    /// Maximum number of seconds to run for, runs until a crash is found by default
+    #[clap(long)]
+    max_time: Option<u64>,
+    /// Maximum length of generated inputs, in bytes
+    #[clap(long, default_value_t = 4096)]
+    max_len: usize,
+    /// Seed for generating inputs, to replay a previous run
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Script name or full path to the file to fuzz
+    script_path: String,
+}
+
+impl FuzzCommand {
+    pub async fn run(self) -> Result<ExitCode> {
+        let file_path = discover_script_path_including_lune_dirs(&self.script_path)?;
+        let file_contents = read_to_vec(&file_path).await?;
+        // NOTE: We skip the extension here to remove it from stack traces
+        let file_display_name = file_path.with_extension("").display().to_string();
+
+        let corpus_dir = self
+            .corpus
+            .clone()
+            .unwrap_or_else(|| file_path.with_extension("corpus"));
+        let corpus = read_corpus(&corpus_dir).await?;
+        println!(
+            "Loaded {} input(s) from {}",
+            corpus.len(),
+            style(corpus_dir.display()).cyan()
+        );
+
+        let options = FuzzOptions {
+            corpus,
+            runs: self.runs,
+            max_duration: self.max_time.map(Duration::from_secs),
+            max_len: self.max_len,
+            seed: self.seed,
+        };
+
+        // NOTE: New inputs are saved as soon as they are found, so
+        // that they are kept even if fuzzing is interrupted later on
+        let save_dir = corpus_dir.clone();
+        let on_event = move |event: FuzzEvent| match event {
+            FuzzEvent::NewInput { input, stats } => {
+                if let Err(e) = save_input(&save_dir, input) {
+                    eprintln!("Failed to save input to corpus - {e}");
+                }
+                println!(
+                    "{} runs: {}, corpus: {}, features: {}",
+                    style("NEW").green().bold(),
+                    stats.runs,
+                    stats.corpus,
+                    stats.features
+                );
+            }
+            FuzzEvent::Pulse { stats } => {
+                println!(
+                    "{} runs: {}, corpus: {}, features: {}",
+                    style("PULSE").dim(),
+                    stats.runs,
+                    stats.corpus,
+                    stats.features
+                );
+            }
+        };
+
+        let config = LuneConfig::read().await?;
+        let mut rt = config.apply(Runtime::new());
+        let report = match rt
+            .fuzz(
+                &file_display_name,
+                strip_shebang(file_contents),
+                options,
+                on_event,
+            )
+            .await
+        {
+            Ok(report) => report,
+            Err(err) => {
+                eprintln!("{err}");
+                return Ok(ExitCode::FAILURE);
+            }
+        };
+
+        println!(
+            "\nFinished after {} runs with {} input(s) in corpus and {} features (seed {})",
+            report.stats.runs, report.stats.corpus, report.stats.features, report.seed
+        );
+
+        let Some(crash) = report.crash else {
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        let crash_path = save_input(&corpus_dir.join(CRASHES_DIR_NAME), &crash.input)
+            .context("Failed to save crashing input")?;
+        eprintln!(
+            "\n{} Found an input that throws an error, saved to {}\n\n{}",
+            style("CRASH").red().bold(),
+            style(crash_path.display()).cyan(),
+            crash.error
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+async fn read_corpus(dir: &Path) -> Result<Vec<Vec<u8>>> {
+    let mut entries = match read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read corpus {}", dir.display()))
+        }
+    };
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    // Sorted so that the same corpus and seed always give the same run
+    paths.sort();
+    let mut corpus = Vec::with_capacity(paths.len());
+    for path in paths {
+        let input = read_to_vec(&path)
+            .await
+            .with_context(|| format!("Failed to read corpus input {}", path.display()))?;
+        corpus.push(input);
+    }
+    Ok(corpus)
+}
+
+// Inputs are named by the hash of their contents, which
+// means that saving the same input twice does nothing
+fn save_input(dir: &Path, input: &[u8]) -> std::io::Result<PathBuf> {
+    create_dir_all(dir)?;
+    let hash = blake3::hash(input).to_hex();
+    let path = dir.join(&hash.as_str()[..16]);
+    if !path.exists() {
+        write(&path, input)?;
+    }
+    Ok(path)
+}
@@ -4,14 +4,20 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 
 pub(crate) mod build;
+pub(crate) mod bundle;
+pub(crate) mod fuzz;
 pub(crate) mod list;
 pub(crate) mod repl;
 pub(crate) mod run;
 pub(crate) mod setup;
+pub(crate) mod test;
 pub(crate) mod utils;
 
+use self::{setup::TYPEDEFS_DIR, utils::typedefs::dump_typedefs};
+
 pub use self::{
-    build::BuildCommand, list::ListCommand, repl::ReplCommand, run::RunCommand, setup::SetupCommand,
+    build::BuildCommand, bundle::BundleCommand, fuzz::FuzzCommand, list::ListCommand,
+    repl::ReplCommand, run::RunCommand, setup::SetupCommand, test::TestCommand,
 };
 
 #[derive(Debug, Clone, Subcommand)]
@@ -20,7 +26,10 @@ pub enum CliSubcommand {
     List(ListCommand),
     Setup(SetupCommand),
     Build(BuildCommand),
+    Bundle(BundleCommand),
     Repl(ReplCommand),
+    Test(TestCommand),
+    Fuzz(FuzzCommand),
 }
 
 impl Default for CliSubcommand {
@@ -35,6 +44,9 @@ impl Default for CliSubcommand {
 pub struct Cli {
     #[clap(subcommand)]
     subcommand: Option<CliSubcommand>,
+    /// Print the API of all builtin libraries as JSON, including their doc comments
+    #[clap(long)]
+    print_typedefs: bool,
 }
 
 impl Cli {
@@ -43,12 +55,21 @@ impl Cli {
     }
 
     pub async fn run(self) -> Result<ExitCode> {
+        if self.print_typedefs {
+            let dump = dump_typedefs(&TYPEDEFS_DIR);
+            println!("{}", serde_json::to_string_pretty(&dump)?);
+            return Ok(ExitCode::SUCCESS);
+        }
+
         match self.subcommand.unwrap_or_default() {
             CliSubcommand::Run(cmd) => cmd.run().await,
             CliSubcommand::List(cmd) => cmd.run().await,
             CliSubcommand::Setup(cmd) => cmd.run().await,
             CliSubcommand::Build(cmd) => cmd.run().await,
+            CliSubcommand::Bundle(cmd) => cmd.run().await,
             CliSubcommand::Repl(cmd) => cmd.run().await,
+            CliSubcommand::Test(cmd) => cmd.run().await,
+            CliSubcommand::Fuzz(cmd) => cmd.run().await,
         }
     }
 }
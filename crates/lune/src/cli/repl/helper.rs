@@ -0,0 +1,232 @@
+use std::{collections::BTreeSet, rc::Rc};
+
+use mlua::prelude::*;
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    validate::{ValidationContext, ValidationResult, Validator},
+    Context, Helper,
+};
+
+use crate::cli::setup::TYPEDEFS_DIR;
+
+#[rustfmt::skip]
+const KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "do", "else", "elseif", "end", "false", "for", "function",
+    "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+// How many `__index` metatables to follow when finding members of a value,
+// which covers classes that inherit from other classes, without looping forever
+const MAX_INDEX_DEPTH: usize = 8;
+
+/**
+    Helper for the REPL editor, which keeps editing input until it is a complete
+    chunk of Luau code, and completes globals, their members, and built-in libraries.
+*/
+pub struct ReplHelper {
+    lua: Rc<Lua>,
+}
+
+impl ReplHelper {
+    pub fn new(lua: Rc<Lua>) -> Self {
+        Self { lua }
+    }
+
+    /**
+        Completes the global, or member of a global, at the end of the given input,
+        such as `pri` or `fs.read` - members of tables and userdata are found using
+        raw access only, so that completing never runs any Luau code.
+    */
+    fn complete_members(&self, input: &str) -> (usize, Vec<Pair>) {
+        let expression = trailing_expression(input);
+        let separator = expression.rfind(['.', ':']);
+        let partial = separator.map_or(expression, |index| &expression[index + 1..]);
+        let start = input.len() - partial.len();
+
+        let mut value = LuaValue::Table(self.lua.globals());
+        if let Some(index) = separator {
+            for segment in expression[..index].split('.') {
+                match member(&value, segment) {
+                    Some(found) => value = found,
+                    None => return (start, Vec::new()),
+                }
+            }
+        }
+
+        let methods_only = separator.is_some_and(|index| expression[index..].starts_with(':'));
+        let mut names = BTreeSet::new();
+        for table in member_tables(&value) {
+            for (key, member) in table.pairs::<LuaValue, LuaValue>().flatten() {
+                let LuaValue::String(key) = key else {
+                    continue;
+                };
+                let Ok(key) = key.to_str() else {
+                    continue;
+                };
+                if is_identifier(key)
+                    && key.starts_with(partial)
+                    && (!methods_only || matches!(member, LuaValue::Function(_)))
+                {
+                    names.insert(key.to_string());
+                }
+            }
+        }
+        if separator.is_none() {
+            names.extend(
+                KEYWORDS
+                    .iter()
+                    .filter(|keyword| keyword.starts_with(partial))
+                    .map(ToString::to_string),
+            );
+        }
+
+        (start, names.into_iter().map(pair).collect())
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let input = &line[..pos];
+        Ok(complete_require(input).unwrap_or_else(|| self.complete_members(input)))
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        // NOTE: Compiling the input does not run it, and any other syntax
+        // errors are left for when it runs, so that they are reported as usual
+        let incomplete = matches!(
+            self.lua.load(ctx.input()).into_function(),
+            Err(LuaError::SyntaxError {
+                incomplete_input: true,
+                ..
+            })
+        );
+        Ok(if incomplete {
+            ValidationResult::Incomplete
+        } else {
+            ValidationResult::Valid(None)
+        })
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/**
+    Completes the names of built-in libraries in a string
+    given to `require`, such as `require("@lune/f`.
+*/
+fn complete_require(input: &str) -> Option<(usize, Vec<Pair>)> {
+    let call = input.rfind("require(")?;
+    let mut chars = input[call + "require(".len()..].chars();
+    let quote = chars.next().filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let partial = chars.as_str();
+    if partial.contains(quote) {
+        return None;
+    }
+
+    let libraries = TYPEDEFS_DIR
+        .files()
+        .filter_map(|file| file.path().file_stem()?.to_str())
+        .map(|name| format!("@lune/{name}"))
+        .filter(|path| path.starts_with(partial))
+        .collect::<BTreeSet<_>>();
+    Some((
+        input.len() - partial.len(),
+        libraries.into_iter().map(pair).collect(),
+    ))
+}
+
+/**
+    Finds the expression at the end of the given input, made up of
+    names separated by `.` or `:`, such as `fs.read` or `socket:se`.
+*/
+fn trailing_expression(input: &str) -> &str {
+    let start = input
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':'))
+        .last()
+        .map_or(input.len(), |(index, _)| index);
+    let expression = &input[start..];
+    // NOTE: Concatenation without any spaces, such as `a..b`, starts a new expression
+    expression
+        .rfind("..")
+        .map_or(expression, |index| &expression[index + 2..])
+}
+
+/**
+    Gets the tables that members of the given value may be found in, which is the
+    value itself for tables, followed by the tables in any `__index` metatables.
+*/
+fn member_tables<'lua>(value: &LuaValue<'lua>) -> Vec<LuaTable<'lua>> {
+    let mut next = match value {
+        LuaValue::Table(table) => Some(table.clone()),
+        LuaValue::UserData(data) => data
+            .get_metatable()
+            .and_then(|meta| meta.get::<LuaValue>("__index"))
+            .ok()
+            .and_then(into_table),
+        _ => None,
+    };
+    let mut tables = Vec::new();
+    while let Some(table) = next.take() {
+        if tables.len() == MAX_INDEX_DEPTH {
+            break;
+        }
+        next = table
+            .get_metatable()
+            .and_then(|meta| meta.raw_get::<_, LuaValue>("__index").ok())
+            .and_then(into_table);
+        tables.push(table);
+    }
+    tables
+}
+
+fn member<'lua>(value: &LuaValue<'lua>, name: &str) -> Option<LuaValue<'lua>> {
+    if !is_identifier(name) {
+        return None;
+    }
+    member_tables(value)
+        .into_iter()
+        .map(|table| table.raw_get::<_, LuaValue>(name))
+        .find_map(|found| found.ok().filter(|found| !found.is_nil()))
+}
+
+fn into_table(value: LuaValue) -> Option<LuaTable> {
+    match value {
+        LuaValue::Table(table) => Some(table),
+        _ => None,
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+fn pair(name: String) -> Pair {
+    Pair {
+        display: name.clone(),
+        replacement: name,
+    }
+}
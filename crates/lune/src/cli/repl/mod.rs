@@ -0,0 +1,117 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use anyhow::Result;
+use clap::Parser;
+use console::style;
+use directories::UserDirs;
+use rustyline::{error::ReadlineError, history::FileHistory, CompletionType, Config, Editor};
+
+use lune::Runtime;
+
+use super::utils::config::LuneConfig;
+
+mod helper;
+
+use self::helper::ReplHelper;
+
+const MESSAGE_WELCOME: &str = concat!("Lune v", env!("CARGO_PKG_VERSION"));
+const MESSAGE_INTERRUPT: &str = "Interrupt: ^C again to exit";
+
+// How many entries to keep in the history file - every entry is a
+// complete chunk of input, which may also span more than one line
+const HISTORY_SIZE: usize = 1000;
+
+type ReplEditor = Editor<ReplHelper, FileHistory>;
+
+/// Launch an interactive REPL (default)
+#[derive(Debug, Clone, Default, Parser)]
+pub struct ReplCommand {}
+
+impl ReplCommand {
+    pub async fn run(self) -> Result<ExitCode> {
+        println!("{MESSAGE_WELCOME}");
+
+        let mut lune_instance = LuneConfig::read().await?.apply(Runtime::new());
+
+        let config = Config::builder()
+            .max_history_size(HISTORY_SIZE)?
+            .history_ignore_dups(true)?
+            .completion_type(CompletionType::List)
+            .build();
+        let mut repl = ReplEditor::with_config(config)?;
+        repl.set_helper(Some(ReplHelper::new(lune_instance.lua())));
+
+        // NOTE: The REPL is still usable without any history, so failing to
+        // find, load or save the history file should not stop it from running
+        let mut history_file_path =
+            UserDirs::new().map(|dirs| dirs.home_dir().join(".lune_history"));
+        if let Some(path) = history_file_path.as_ref().filter(|path| path.exists()) {
+            if let Err(e) = repl.load_history(path) {
+                eprintln!("{} {e}", style("Failed to load REPL history -").yellow());
+            }
+        }
+
+        let mut interrupt_counter = 0;
+
+        loop {
+            // NOTE: Input is only given back once it is a complete chunk of code,
+            // the editor keeps going on new lines for incomplete input such as
+            // unclosed blocks, so every history entry is a whole chunk of code
+            let source_code = match repl.readline("> ") {
+                Ok(code) => {
+                    interrupt_counter = 0;
+                    code
+                }
+
+                Err(ReadlineError::Eof) => break,
+                Err(ReadlineError::Interrupted) => {
+                    interrupt_counter += 1;
+
+                    // NOTE: We actually want the user to do ^C twice to exit,
+                    // and if we get an interrupt we should continue to the next
+                    // readline loop iteration so we don't run input code twice
+                    if interrupt_counter == 1 {
+                        println!("{MESSAGE_INTERRUPT}");
+                        continue;
+                    }
+
+                    break;
+                }
+
+                Err(err) => {
+                    eprintln!("REPL ERROR: {err}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+
+            if source_code.trim().is_empty() {
+                continue;
+            }
+
+            repl.add_history_entry(&source_code)?;
+            save_history(&mut repl, &mut history_file_path);
+
+            // TODO: Preserve context here somehow?
+            if let Err(err) = lune_instance.run("REPL", &source_code).await {
+                eprintln!("{err}");
+            }
+        }
+
+        save_history(&mut repl, &mut history_file_path);
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/**
+    Saves the history of the given editor, if there is a history file, and stops
+    using the history file if saving fails, so that the error is only printed once.
+*/
+fn save_history(repl: &mut ReplEditor, history_file_path: &mut Option<PathBuf>) {
+    if let Some(path) = history_file_path.as_ref() {
+        if let Err(e) = repl.save_history(path) {
+            eprintln!("{} {e}", style("Failed to save REPL history -").yellow());
+            *history_file_path = None;
+        }
+    }
+}
@@ -1,19 +1,52 @@
-use std::process::ExitCode;
+use std::{path::PathBuf, process::ExitCode};
 
 use anyhow::{Context, Result};
 use clap::Parser;
+use directories::UserDirs;
 use tokio::{
     fs::read as read_to_vec,
     io::{stdin, AsyncReadExt as _},
 };
 
 use lune::Runtime;
+use mlua_luau_scheduler::TaskKind;
 
-use super::utils::files::{discover_script_path_including_lune_dirs, strip_shebang};
+use super::utils::{
+    config::LuneConfig,
+    files::{discover_script_path_including_lune_dirs, strip_shebang},
+};
 
 /// Run a script
 #[derive(Debug, Clone, Parser)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct RunCommand {
+    /// Only allow the net library to contact hosts matching this pattern, such as "*.example.com"
+    #[clap(long = "allow-host", value_name = "PATTERN")]
+    allow_hosts: Vec<String>,
+    /// Never allow the net library to contact hosts matching this pattern, or hosts resolving to a matching address
+    #[clap(long = "deny-host", value_name = "PATTERN")]
+    deny_hosts: Vec<String>,
+    /// Resolve a hostname to the given address in the net library, instead of using DNS
+    #[clap(long = "resolve", value_name = "HOST=ADDRESS")]
+    resolve: Vec<String>,
+    /// File in the same format as /etc/hosts, with addresses that hostnames should resolve to
+    #[clap(long, value_name = "PATH")]
+    hosts_file: Option<PathBuf>,
+    /// Directory that all paths given to the fs library are relative to, as if it was the root
+    #[clap(long, value_name = "DIR")]
+    fs_root: Option<PathBuf>,
+    /// Make short waits more accurate, at the cost of higher CPU usage
+    #[clap(long)]
+    low_latency: bool,
+    /// Compile all required modules from scratch instead of using cached bytecode
+    #[clap(long)]
+    no_bytecode_cache: bool,
+    /// Do not print warnings from built-in libraries, such as for deprecated arguments
+    #[clap(long)]
+    no_warnings: bool,
+    /// Print time spent by threads in the scheduler queues once the script completes
+    #[clap(long)]
+    scheduler_stats: bool,
     /// Script name or full path to the file to run
     script_path: String,
     /// Arguments to pass to the script, stored in process.args
@@ -41,10 +74,34 @@ impl RunCommand {
         };
 
         // Create a new lune object with all globals & run the script
-        let result = Runtime::new()
+        let config = LuneConfig::read()
+            .await?
+            .with_hosts(&self.allow_hosts, &self.deny_hosts)?
+            .with_host_overrides(&self.resolve, self.hosts_file.as_deref())
+            .await?;
+        let mut rt = Runtime::new()
             .with_args(self.script_args)
+            .with_main_function(true)
+            .with_low_latency(self.low_latency)
+            .with_warnings(!self.no_warnings);
+        if !self.no_bytecode_cache {
+            if let Some(dir) = bytecode_cache_dir() {
+                rt = rt.with_bytecode_cache(dir);
+            }
+        }
+        if let Some(root) = self.fs_root {
+            rt = rt.with_fs_root(root);
+        }
+        let mut rt = config.apply(rt);
+        let result = rt
             .run(&script_display_name, strip_shebang(script_contents))
             .await;
+        if self.scheduler_stats {
+            eprintln!("Scheduler queue stats:");
+            for kind in TaskKind::ALL {
+                eprintln!("  {kind:<8} {}", rt.queue_stats(kind));
+            }
+        }
         Ok(match result {
             Err(err) => {
                 eprintln!("{err}");
@@ -54,3 +111,30 @@ impl RunCommand {
         })
     }
 }
+
+pub(crate) fn bytecode_cache_dir() -> Option<PathBuf> {
+    let home_dir = UserDirs::new()?.home_dir().to_path_buf();
+    Some(home_dir.join(".lune").join(".bytecode"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn low_latency() -> Result<()> {
+        let script = format!(
+            "{}/../../tests/task/wait_low_latency.luau",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let command = RunCommand::try_parse_from([
+            "run",
+            "--low-latency",
+            "--no-bytecode-cache",
+            script.as_str(),
+        ])?;
+        assert!(command.low_latency);
+        assert_eq!(command.run().await?, ExitCode::SUCCESS);
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::BTreeMap,
+    io::{stderr, stdout, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, available_parallelism},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use console::style;
+use tokio::{
+    fs::{read as read_to_vec, read_dir},
+    runtime::Builder as AsyncRuntimeBuilder,
+    sync::mpsc::unbounded_channel,
+};
+
+use lune::Runtime;
+use lune_utils::output::{CapturedOutput, OutputChunk, OutputStream};
+
+use super::{
+    run::bytecode_cache_dir,
+    utils::{config::LuneConfig, files::strip_shebang},
+};
+
+const TEST_FILE_SUFFIXES: &[&str] = &[".test.luau", ".test.lua", ".spec.luau", ".spec.lua"];
+
+// Threads spawned by the standard library get much smaller stacks than
+// the main thread by default, which deeply recursive scripts may need
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Run test files
+#[derive(Debug, Clone, Parser)]
+pub struct TestCommand {
+    /// Update snapshots that no longer match, instead of failing
+    #[clap(long)]
+    update_snapshots: bool,
+    /// Seed to use for property checks that were not given a seed, to replay a failure
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Only allow the net library to contact hosts matching this pattern, such as "*.example.com"
+    #[clap(long = "allow-host", value_name = "PATTERN")]
+    allow_hosts: Vec<String>,
+    /// Never allow the net library to contact hosts matching this pattern, or hosts resolving to a matching address
+    #[clap(long = "deny-host", value_name = "PATTERN")]
+    deny_hosts: Vec<String>,
+    /// Resolve a hostname to the given address in the net library, instead of using DNS
+    #[clap(long = "resolve", value_name = "HOST=ADDRESS")]
+    resolve: Vec<String>,
+    /// File in the same format as /etc/hosts, with addresses that hostnames should resolve to
+    #[clap(long, value_name = "PATH")]
+    hosts_file: Option<PathBuf>,
+    /// Directory that all paths given to the fs library are relative to, as if it was the root
+    #[clap(long, value_name = "DIR")]
+    fs_root: Option<PathBuf>,
+    /// Compile all required modules from scratch instead of using cached bytecode
+    #[clap(long)]
+    no_bytecode_cache: bool,
+    /// Do not print warnings from built-in libraries, such as for deprecated arguments
+    #[clap(long)]
+    no_warnings: bool,
+    /// Number of test files to run at the same time, defaults to the number of CPU cores
+    #[clap(long, short)]
+    jobs: Option<usize>,
+    /// Test files, or directories to search for test files in, defaults to the current directory
+    paths: Vec<PathBuf>,
+}
+
+impl TestCommand {
+    pub async fn run(self) -> Result<ExitCode> {
+        let paths = if self.paths.is_empty() {
+            vec![PathBuf::from(".")]
+        } else {
+            self.paths.clone()
+        };
+
+        let mut files = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                find_test_files(&path, &mut files).await?;
+            } else {
+                files.push(path);
+            }
+        }
+        for file in &mut files {
+            if let Ok(stripped) = file.strip_prefix(".") {
+                *file = stripped.to_path_buf();
+            }
+        }
+        files.sort();
+        files.dedup();
+
+        if files.is_empty() {
+            eprintln!("No test files were found");
+            return Ok(ExitCode::FAILURE);
+        }
+
+        let config = LuneConfig::read()
+            .await?
+            .with_hosts(&self.allow_hosts, &self.deny_hosts)?
+            .with_host_overrides(&self.resolve, self.hosts_file.as_deref())
+            .await?;
+        let jobs = self
+            .jobs
+            .unwrap_or_else(|| available_parallelism().map_or(1, NonZeroUsize::get))
+            .clamp(1, files.len());
+
+        let start = Instant::now();
+        let mut failed = 0;
+        self.run_files(&files, &config, jobs, |file, result| {
+            failed += usize::from(!result.report(file));
+        })
+        .await?;
+
+        println!(
+            "\n{} passed, {} failed, {} total ({:.2}s)",
+            files.len() - failed,
+            failed,
+            files.len(),
+            start.elapsed().as_secs_f64()
+        );
+
+        Ok(if failed > 0 {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+
+    /**
+        Runs the given files using the given number of worker threads,
+        and calls `report` with the result of each file once it has
+        finished, in the same order that the files were given in.
+    */
+    async fn run_files(
+        &self,
+        files: &[PathBuf],
+        config: &LuneConfig,
+        jobs: usize,
+        mut report: impl FnMut(&Path, TestResult),
+    ) -> Result<()> {
+        // NOTE: Lune runtimes can not be sent between threads, so each worker thread
+        // gets its own async runtime, and creates a new Lune runtime for each file,
+        // which also isolates the files from each other - each one gets its own VM
+        let files = Arc::<[PathBuf]>::from(files);
+        let next_index = Arc::new(AtomicUsize::new(0));
+        let (tx, mut rx) = unbounded_channel();
+        let mut workers = Vec::with_capacity(jobs);
+        for n in 0..jobs {
+            let async_rt = AsyncRuntimeBuilder::new_current_thread()
+                .enable_all()
+                .build()
+                .context("Failed to create async runtime for tests")?;
+            let this = self.clone();
+            let config = config.clone();
+            let files = Arc::clone(&files);
+            let next_index = Arc::clone(&next_index);
+            let tx = tx.clone();
+            let worker = thread::Builder::new()
+                .name(format!("lune-test-{n}"))
+                .stack_size(WORKER_STACK_SIZE)
+                .spawn(move || loop {
+                    let index = next_index.fetch_add(1, Ordering::SeqCst);
+                    let Some(file) = files.get(index) else {
+                        break;
+                    };
+                    let result = async_rt.block_on(this.run_file(file, config.clone()));
+                    if tx.send((index, result)).is_err() {
+                        break;
+                    }
+                })
+                .context("Failed to spawn test worker thread")?;
+            workers.push(worker);
+        }
+        drop(tx);
+
+        // Files finish in any order, but we report them in the order that they
+        // were found in, so that the output is the same regardless of timing
+        let mut pending = BTreeMap::new();
+        let mut reported = 0;
+        while let Some((index, result)) = rx.recv().await {
+            pending.insert(index, result?);
+            while let Some(result) = pending.remove(&reported) {
+                report(&files[reported], result);
+                reported += 1;
+            }
+        }
+        for worker in workers {
+            worker.join().ok();
+        }
+        for file in &files[reported..] {
+            report(file, TestResult::crashed());
+        }
+        Ok(())
+    }
+
+    async fn run_file(&self, file: &Path, config: LuneConfig) -> Result<TestResult> {
+        let contents = read_to_vec(file)
+            .await
+            .with_context(|| format!("Failed to read test file {}", file.display()))?;
+        // NOTE: We skip the extension here to remove it from stack traces,
+        // this is also what snapshot files are named after, plus their own one
+        let name = file.with_extension("").display().to_string();
+
+        // NOTE: Files run at the same time, so their output is captured and written
+        // once each file has finished, instead of being interleaved with other files
+        let output = CapturedOutput::default();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let errors_inner = Arc::clone(&errors);
+        let mut rt = Runtime::new()
+            .with_test_mode(true)
+            .with_captured_output(output.clone())
+            .with_update_snapshots(self.update_snapshots)
+            .with_warnings(!self.no_warnings)
+            .with_error_callback(move |e| {
+                errors_inner
+                    .lock()
+                    .expect("Failed to lock test errors")
+                    .push(e.to_string());
+            });
+        if let Some(seed) = self.seed {
+            rt = rt.with_test_seed(seed);
+        }
+        if !self.no_bytecode_cache {
+            if let Some(dir) = bytecode_cache_dir() {
+                rt = rt.with_bytecode_cache(dir);
+            }
+        }
+        if let Some(root) = &self.fs_root {
+            rt = rt.with_fs_root(root);
+        }
+        let mut rt = config.apply(rt);
+
+        let start = Instant::now();
+        let passed = match rt.run(&name, strip_shebang(contents)).await {
+            Ok(code) => code == ExitCode::SUCCESS,
+            Err(err) => {
+                errors
+                    .lock()
+                    .expect("Failed to lock test errors")
+                    .push(err.to_string());
+                false
+            }
+        };
+        let duration = start.elapsed();
+
+        let errors = errors.lock().expect("Failed to lock test errors").clone();
+        Ok(TestResult {
+            passed,
+            output: output.take(),
+            errors,
+            duration: Some(duration),
+        })
+    }
+}
+
+struct TestResult {
+    passed: bool,
+    output: Vec<OutputChunk>,
+    errors: Vec<String>,
+    duration: Option<Duration>,
+}
+
+impl TestResult {
+    fn crashed() -> Self {
+        Self {
+            passed: false,
+            output: Vec::new(),
+            errors: vec!["Test worker crashed while running this file".to_string()],
+            duration: None,
+        }
+    }
+
+    fn report(&self, file: &Path) -> bool {
+        let status = if self.passed {
+            style("PASS").green().bold()
+        } else {
+            style("FAIL").red().bold()
+        };
+        match self.duration {
+            Some(duration) => println!(
+                "{status} {} {}",
+                file.display(),
+                style(format!("({:.2}s)", duration.as_secs_f64())).dim()
+            ),
+            None => println!("{status} {}", file.display()),
+        }
+        for (stream, bytes) in &self.output {
+            let written = match stream {
+                OutputStream::Stdout => stdout().write_all(bytes).and_then(|()| stdout().flush()),
+                OutputStream::Stderr => stderr().write_all(bytes).and_then(|()| stderr().flush()),
+            };
+            written.ok();
+        }
+        for error in &self.errors {
+            eprintln!("{error}");
+        }
+        self.passed
+    }
+}
+
+fn is_test_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| TEST_FILE_SUFFIXES.iter().any(|s| name.ends_with(s)))
+}
+
+async fn find_test_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = read_dir(&dir)
+            .await
+            .with_context(|| format!("Failed to read directory {}", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            // NOTE: Hidden directories such as .git are skipped, since
+            // they may be very large and should never contain any tests
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if entry.file_type().await?.is_dir() {
+                if !is_hidden {
+                    dirs.push(path);
+                }
+            } else if is_test_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn parallel_files_keep_their_own_output() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("lune-test-runner-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await?;
+
+        // Each file writes its output in several parts and yields in between,
+        // so that output from files running at the same time would interleave
+        let mut files = Vec::new();
+        for n in 0..8 {
+            let file = dir.join(format!("file{n}.test.luau"));
+            let failure = if n == 3 { "error(\"failed\")" } else { "" };
+            let source = format!(
+                "local stdio = require(\"@lune/stdio\")\n\
+                local task = require(\"@lune/task\")\n\
+                for i = 1, 5 do\n\
+                    stdio.write(`{n}:{{i}} `)\n\
+                    task.wait(0.01)\n\
+                end\n\
+                stdio.ewrite(\"stderr {n}\\n\")\n\
+                print(\"done {n}\")\n\
+                {failure}\n"
+            );
+            tokio::fs::write(&file, source).await?;
+            files.push(file);
+        }
+
+        let command = TestCommand::try_parse_from(["test", "--no-bytecode-cache"])?;
+        let mut results = Vec::new();
+        let ran = command
+            .run_files(&files, &LuneConfig::default(), 4, |file, result| {
+                results.push((file.to_path_buf(), result));
+            })
+            .await;
+        tokio::fs::remove_dir_all(&dir).await?;
+        ran?;
+
+        assert_eq!(results.len(), files.len());
+        for (n, (file, result)) in results.iter().enumerate() {
+            assert_eq!(
+                file, &files[n],
+                "results should be in the same order as files"
+            );
+            assert_eq!(result.passed, n != 3);
+            assert_eq!(result.errors.is_empty(), n != 3);
+            let expected = vec![
+                (
+                    OutputStream::Stdout,
+                    format!("{n}:1 {n}:2 {n}:3 {n}:4 {n}:5 "),
+                ),
+                (OutputStream::Stderr, format!("stderr {n}\n")),
+                (OutputStream::Stdout, format!("done {n}\n")),
+            ];
+            let output = result
+                .output
+                .iter()
+                .map(|(stream, bytes)| (*stream, String::from_utf8_lossy(bytes).into_owned()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                output,
+                expected,
+                "output of {} should not interleave",
+                file.display()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::BTreeMap,
+    io::ErrorKind,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Error, Result};
+use serde::Deserialize;
+use tokio::fs::read_to_string;
+
+use lune::{HostOverrides, HostPolicy, Runtime, VirtualModule};
+use lune_utils::path::get_current_dir;
+
+const LUNE_CONFIG_FILE_NAME: &str = "lune.toml";
+
+#[derive(Debug, Default, Deserialize)]
+struct LuneConfigFile {
+    #[serde(default)]
+    modules: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    net: LuneConfigNet,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct LuneConfigNet {
+    #[serde(default)]
+    allow_hosts: Vec<String>,
+    #[serde(default)]
+    deny_hosts: Vec<String>,
+    #[serde(default)]
+    resolve: BTreeMap<String, toml::Value>,
+    hosts_file: Option<PathBuf>,
+}
+
+/**
+    Configuration for the Lune CLI, read from a `lune.toml` file in the current directory.
+
+    Virtual modules are given in a `modules` table, where each value is either
+    a string of Luau source code, or a table that will be required as-is:
+
+    ```toml
+    [modules]
+    greeting = "return 'Hello, world!'"
+
+    [modules.config]
+    debug = true
+    ```
+
+    Hosts that `@lune/net` may contact are given in a `net` table, using the
+    same patterns as the `--allow-host` and `--deny-host` CLI options:
+
+    ```toml
+    [net]
+    allow-hosts = ["api.example.com", "*.github.com"]
+    deny-hosts = ["internal.github.com"]
+    ```
+
+    Addresses that hostnames should resolve to may also be given in the `net` table,
+    either directly or in a file in the same format as `/etc/hosts`, the same as the
+    `--resolve` and `--hosts-file` CLI options:
+
+    ```toml
+    [net]
+    hosts-file = "tests/hosts"
+
+    [net.resolve]
+    "api.example.com" = "127.0.0.1"
+    "cdn.example.com" = ["127.0.0.1", "::1"]
+    ```
+*/
+#[derive(Debug, Default, Clone)]
+pub struct LuneConfig {
+    modules: Vec<(String, VirtualModule)>,
+    allow_hosts: Vec<String>,
+    deny_hosts: Vec<String>,
+    host_policy: Option<HostPolicy>,
+    host_overrides: HostOverrides,
+}
+
+impl LuneConfig {
+    /**
+        Reads the configuration file in the current directory, if one exists.
+
+        # Errors
+
+        Errors if the configuration file exists but could not be read or is invalid.
+    */
+    pub async fn read() -> Result<Self> {
+        let path = get_current_dir().join(LUNE_CONFIG_FILE_NAME);
+        match read_to_string(&path).await {
+            Ok(contents) => {
+                let (config, hosts_file) = Self::parse(&contents)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                config.with_host_overrides(&[], hosts_file.as_deref()).await
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<(Self, Option<PathBuf>)> {
+        let file: LuneConfigFile = toml::from_str(contents)?;
+        let mut modules = Vec::new();
+        for (name, value) in file.modules {
+            let module = match value {
+                toml::Value::String(source) => VirtualModule::Source(source),
+                toml::Value::Table(table) => VirtualModule::Value(serde_json::to_value(table)?),
+                _ => bail!(
+                    "Virtual module '{name}' must be either a string of source code or a table"
+                ),
+            };
+            modules.push((name, module));
+        }
+        let mut host_overrides = HostOverrides::new();
+        for (host, value) in file.net.resolve {
+            let addrs = match value {
+                toml::Value::String(addr) => vec![addr],
+                toml::Value::Array(addrs) => addrs
+                    .into_iter()
+                    .map(|addr| match addr {
+                        toml::Value::String(addr) => Ok(addr),
+                        _ => bail!("Addresses for host '{host}' must be strings"),
+                    })
+                    .collect::<Result<_>>()?,
+                _ => bail!("Address for host '{host}' must be a string or a list of strings"),
+            };
+            for addr in addrs {
+                host_overrides
+                    .insert(&host, parse_address(&addr)?)
+                    .map_err(Error::msg)?;
+            }
+        }
+        let mut config = Self {
+            modules,
+            allow_hosts: file.net.allow_hosts,
+            deny_hosts: file.net.deny_hosts,
+            host_policy: None,
+            host_overrides,
+        };
+        config.update_host_policy()?;
+        Ok((config, file.net.hosts_file))
+    }
+
+    /**
+        Adds allowed and denied host patterns, such as those given as CLI
+        options, on top of any patterns from the configuration file.
+
+        # Errors
+
+        Errors if any of the given patterns are invalid.
+    */
+    pub fn with_hosts(mut self, allow: &[String], deny: &[String]) -> Result<Self> {
+        self.allow_hosts.extend_from_slice(allow);
+        self.deny_hosts.extend_from_slice(deny);
+        self.update_host_policy()?;
+        Ok(self)
+    }
+
+    /**
+        Adds addresses that hostnames should resolve to, such as those given as CLI options,
+        on top of any addresses from the configuration file. Each given value must be in the
+        form `HOST=ADDRESS`, and the hosts file must be in the same format as `/etc/hosts`.
+
+        # Errors
+
+        Errors if any of the given values are invalid, or if
+        the hosts file could not be read or is invalid.
+    */
+    pub async fn with_host_overrides(
+        mut self,
+        resolve: &[String],
+        hosts_file: Option<&Path>,
+    ) -> Result<Self> {
+        if let Some(path) = hosts_file {
+            let contents = read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read hosts file {}", path.display()))?;
+            let overrides = HostOverrides::parse_hosts_file(&contents)
+                .map_err(Error::msg)
+                .with_context(|| format!("Failed to parse hosts file {}", path.display()))?;
+            self.host_overrides.extend(overrides);
+        }
+        for value in resolve {
+            let Some((host, addr)) = value.split_once('=') else {
+                bail!("Invalid value '{value}' for --resolve, expected HOST=ADDRESS");
+            };
+            self.host_overrides
+                .insert(host, parse_address(addr)?)
+                .map_err(Error::msg)?;
+        }
+        Ok(self)
+    }
+
+    fn update_host_policy(&mut self) -> Result<()> {
+        self.host_policy = if self.allow_hosts.is_empty() && self.deny_hosts.is_empty() {
+            None
+        } else {
+            Some(HostPolicy::new(&self.allow_hosts, &self.deny_hosts).map_err(Error::msg)?)
+        };
+        Ok(())
+    }
+
+    /**
+        Applies this configuration to the given runtime.
+    */
+    pub fn apply(self, mut runtime: Runtime) -> Runtime {
+        for (name, module) in self.modules {
+            runtime = runtime.with_virtual_module(name, module);
+        }
+        if let Some(policy) = self.host_policy {
+            runtime = runtime.with_host_policy(policy);
+        }
+        if !self.host_overrides.is_empty() {
+            runtime = runtime.with_host_overrides(self.host_overrides);
+        }
+        runtime
+    }
+}
+
+fn parse_address(addr: &str) -> Result<IpAddr> {
+    addr.trim()
+        .parse()
+        .with_context(|| format!("Invalid address '{addr}', expected an IPv4 or IPv6 address"))
+}
@@ -1,2 +1,4 @@
+pub mod config;
 pub mod files;
 pub mod listing;
+pub mod typedefs;
@@ -0,0 +1,883 @@
+use std::collections::HashMap;
+
+use include_dir::Dir;
+use serde::Serialize;
+
+/**
+    The full API surface of all builtin libraries, as described by their type definitions.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiDump {
+    pub version: &'static str,
+    pub modules: Vec<ApiModule>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiModule {
+    pub name: String,
+    pub class: Option<String>,
+    pub description: Option<String>,
+    pub classes: Vec<ApiClass>,
+    pub types: Vec<ApiType>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApiClass {
+    pub name: String,
+    pub description: Option<String>,
+    pub properties: Vec<ApiProperty>,
+    pub functions: Vec<ApiFunction>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiProperty {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiFunction {
+    pub name: String,
+    pub method: bool,
+    pub signature: String,
+    pub description: Option<String>,
+    pub params: Vec<ApiParam>,
+    pub returns: Vec<ApiReturn>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiParam {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiReturn {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiType {
+    pub name: String,
+    pub generics: Option<String>,
+    pub kind: &'static str,
+    pub within: Option<String>,
+    pub definition: String,
+    pub description: Option<String>,
+}
+
+/**
+    Creates a dump of the API surface of all builtin libraries in the given typedefs directory.
+*/
+pub fn dump_typedefs(dir: &Dir<'_>) -> ApiDump {
+    let mut modules = dir
+        .find("*.luau")
+        .unwrap()
+        .filter_map(|entry| entry.as_file())
+        .map(|file| {
+            let name = file.path().file_stem().unwrap().to_string_lossy();
+            let source = String::from_utf8_lossy(file.contents());
+            parse_module(&name, &source)
+        })
+        .collect::<Vec<_>>();
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    ApiDump {
+        version: env!("CARGO_PKG_VERSION"),
+        modules,
+    }
+}
+
+/**
+    A parsed doc comment, such as `--[=[ @within Fs ... ]=]`.
+*/
+#[derive(Debug, Default)]
+struct DocComment {
+    description: Option<String>,
+    tags: Vec<(String, String)>,
+}
+
+impl DocComment {
+    fn parse(lines: &[&str]) -> Self {
+        let mut description = Vec::new();
+        let mut tags = Vec::new();
+        let mut in_code_block = false;
+        for line in lines {
+            // Doc comments are indented by a single tab
+            let line = line.strip_prefix('\t').unwrap_or(line);
+            if line.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+            }
+            let trimmed = line.trim();
+            if let Some(tag) = trimmed.strip_prefix('@').filter(|_| !in_code_block) {
+                let (name, value) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                tags.push((name.to_string(), value.trim().to_string()));
+            } else {
+                description.push(line.trim_end());
+            }
+        }
+
+        let description = description.join("\n").trim().to_string();
+        Self {
+            description: Some(description).filter(|d| !d.is_empty()),
+            tags,
+        }
+    }
+
+    fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn tags_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn plain_tags(&self) -> Vec<String> {
+        self.tags_named("tag").map(str::to_string).collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ModuleParser {
+    classes: Vec<ApiClass>,
+    types: Vec<ApiType>,
+    // Names of local tables, such as `fs`, mapped to the names of their classes, such as `Fs`
+    class_tables: HashMap<String, String>,
+    returned: Option<String>,
+}
+
+impl ModuleParser {
+    fn class_mut(&mut self, name: &str) -> &mut ApiClass {
+        if let Some(index) = self.classes.iter().position(|class| class.name == name) {
+            return &mut self.classes[index];
+        }
+        self.classes.push(ApiClass {
+            name: name.to_string(),
+            ..ApiClass::default()
+        });
+        self.classes.last_mut().unwrap()
+    }
+
+    /**
+        Finds the class that a declaration on the given local table belongs to, and the
+        name that the declaration should have - tables that are not classes themselves,
+        such as `progress` in `progress.bar`, are kept as part of the name.
+    */
+    fn owner_of(&self, doc: &DocComment, table: &str, name: &str) -> (String, String) {
+        match (doc.tag("within"), self.class_tables.get(table)) {
+            (_, Some(class)) => (class.clone(), name.to_string()),
+            (Some(within), None) if within == table => (within.to_string(), name.to_string()),
+            (Some(within), None) => (within.to_string(), format!("{table}.{name}")),
+            (None, None) => (table.to_string(), name.to_string()),
+        }
+    }
+
+    fn declare_class(&mut self, doc: &DocComment, table: &str) {
+        let Some(class) = doc.tag("class").map(str::to_string) else {
+            return;
+        };
+        self.class_tables.insert(table.to_string(), class.clone());
+        let description = doc.description.clone();
+        let entry = self.class_mut(&class);
+        if entry.description.is_none() {
+            entry.description = description;
+        }
+    }
+
+    /**
+        Declares the fields of a table that is written out in full on multiple lines,
+        such as `local DateTime = { unixTimestamp = (nil :: any) :: number }`, which
+        may be documented using `---` comments on the lines before each field.
+    */
+    fn declare_fields(&mut self, table: &str, source: &str) {
+        let class = self
+            .class_tables
+            .get(table)
+            .cloned()
+            .unwrap_or_else(|| table.to_string());
+
+        let mut comments = Vec::new();
+        let mut properties = Vec::new();
+        for line in source.lines().skip(1) {
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix("---") {
+                comments.push(comment.trim());
+                continue;
+            }
+            let Some((name, value)) = line.trim_end_matches(',').split_once('=') else {
+                comments.clear();
+                continue;
+            };
+            let name = name.trim();
+            let value = value.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                comments.clear();
+                continue;
+            }
+            let kind = match value.rsplit_once("::") {
+                Some((_, kind)) => Some(kind.trim().to_string()),
+                None if value.parse::<f64>().is_ok() => Some(String::from("number")),
+                None if value.starts_with(['"', '\'']) => Some(String::from("string")),
+                None if value == "true" || value == "false" => Some(String::from("boolean")),
+                None => None,
+            };
+            properties.push(ApiProperty {
+                name: name.to_string(),
+                kind,
+                description: Some(comments.join(" ")).filter(|d| !d.is_empty()),
+                tags: Vec::new(),
+            });
+            comments.clear();
+        }
+
+        if !properties.is_empty() {
+            self.class_mut(&class).properties.extend(properties);
+        }
+    }
+
+    fn declare_type(&mut self, doc: &DocComment, source: &str) {
+        // Generic types may have defaults, such as `Generator<T = any>`,
+        // so the definition starts at the first `=` outside of any generics
+        let mut depth = 0i32;
+        let Some(split) = source.find(|c: char| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+            c == '=' && depth == 0
+        }) else {
+            return;
+        };
+        let (head, definition) = (&source[..split], &source[split + 1..]);
+        let head = head.trim().trim_start_matches("export type").trim();
+        let (name, generics) = match head.find('<') {
+            Some(open) => (&head[..open], Some(head[open..].to_string())),
+            None => (head, None),
+        };
+        let kind = if doc.tag("interface").is_some() {
+            "interface"
+        } else {
+            "type"
+        };
+        self.types.push(ApiType {
+            name: name.to_string(),
+            generics,
+            kind,
+            within: doc.tag("within").map(str::to_string),
+            definition: definition.trim().to_string(),
+            description: doc.description.clone(),
+        });
+    }
+
+    fn declare_function(&mut self, doc: &DocComment, declaration: &str) {
+        let signature = declaration.trim_start_matches("function").trim();
+        let Some(open) = signature.find(['(', '<']) else {
+            return;
+        };
+        let path = &signature[..open];
+        let (table, name, method) = match path.split_once([':', '.']) {
+            Some((table, name)) => (table, name, path.contains(':')),
+            None => ("", path, false),
+        };
+
+        let (params, returns) = parse_signature(signature);
+        let method = method || params.first().is_some_and(|(name, _)| name == "self");
+        let param_docs = doc
+            .tags_named("param")
+            .map(parse_param_tag)
+            .collect::<HashMap<_, _>>();
+        let params = params
+            .into_iter()
+            .filter(|(name, _)| name != "self")
+            .map(|(name, kind)| ApiParam {
+                description: param_docs.get(name.as_str()).cloned().flatten(),
+                name,
+                kind,
+            })
+            .collect();
+
+        let mut return_docs = doc.tags_named("return").map(parse_return_tag);
+        let returns = returns
+            .map(|kind| ApiReturn {
+                description: return_docs.next().and_then(|(_, description)| description),
+                kind: Some(kind),
+            })
+            .into_iter()
+            .chain(return_docs.map(|(kind, description)| ApiReturn { kind, description }))
+            .collect();
+
+        let (class, name) = self.owner_of(doc, table, name);
+        self.class_mut(&class).functions.push(ApiFunction {
+            name,
+            method,
+            signature: signature.to_string(),
+            description: doc.description.clone(),
+            params,
+            returns,
+            tags: doc.plain_tags(),
+        });
+    }
+
+    fn declare_function_value(&mut self, doc: &DocComment, declaration: &str) {
+        // Functions with complex types, such as overloads, are written
+        // as `local name: Type = function(...)` with a `@function` tag
+        let Some(name) = doc.tag("function") else {
+            return;
+        };
+        let kind = declaration
+            .split_once(':')
+            .and_then(|(_, rest)| rest.split_once('='))
+            .map(|(kind, _)| kind.trim().to_string());
+        let Some(class) = doc.tag("within") else {
+            return;
+        };
+        let param_docs = doc.tags_named("param").map(parse_param_tag);
+        let function = ApiFunction {
+            name: name.to_string(),
+            method: false,
+            signature: kind.clone().unwrap_or_default(),
+            description: doc.description.clone(),
+            params: param_docs
+                .map(|(name, description)| ApiParam {
+                    name: name.to_string(),
+                    kind: None,
+                    description,
+                })
+                .collect(),
+            returns: Vec::new(),
+            tags: doc.plain_tags(),
+        };
+        self.class_mut(class).functions.push(function);
+    }
+
+    fn declare_property(&mut self, doc: &DocComment, declaration: &str) {
+        let Some((path, value)) = declaration.split_once('=') else {
+            return;
+        };
+        let Some((table, name)) = path.trim().split_once('.') else {
+            return;
+        };
+
+        // Props are documented using `@prop name type`, but fall back to the type in the
+        // assignment for undocumented props, which is written as `(nil :: any) :: Type`
+        let typed = value.contains("::");
+        if doc.tag("prop").is_none() && !typed {
+            // Undocumented assignments of other values, such as `stdio.prompt = prompt`,
+            // only re-export something that has already been documented elsewhere
+            return;
+        }
+        let (name, kind) = match doc.tag("prop") {
+            Some(prop) => match prop.split_once(char::is_whitespace) {
+                Some((name, kind)) => (name.to_string(), Some(kind.trim().to_string())),
+                None => (prop.to_string(), None),
+            },
+            None => (
+                name.to_string(),
+                value
+                    .rsplit_once("::")
+                    .map(|(_, kind)| kind.trim().to_string()),
+            ),
+        };
+
+        let (class, name) = self.owner_of(doc, table, &name);
+        self.class_mut(&class).properties.push(ApiProperty {
+            name,
+            kind,
+            description: doc.description.clone(),
+            tags: doc.plain_tags(),
+        });
+    }
+}
+
+fn parse_module(name: &str, source: &str) -> ApiModule {
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut parser = ModuleParser::default();
+    let mut doc: Option<DocComment> = None;
+
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+
+        if line.trim_start().starts_with("--[=[") {
+            let start = index;
+            while index < lines.len() && !lines[index].trim_start().starts_with("]=]") {
+                index += 1;
+            }
+            doc = Some(DocComment::parse(&lines[start..index.min(lines.len())]));
+            index += 1;
+            continue;
+        }
+
+        // Declarations are only ever at the top level of typedef files
+        if line.is_empty() || line.starts_with(char::is_whitespace) || line.starts_with("--") {
+            continue;
+        }
+
+        let (declaration, source, consumed) = read_declaration(&lines[index - 1..]);
+        index += consumed - 1;
+
+        let doc = doc.take().unwrap_or_default();
+        if declaration.starts_with("export type ") {
+            parser.declare_type(&doc, &source);
+        } else if declaration.starts_with("function ") {
+            parser.declare_function(&doc, &declaration);
+        } else if let Some(local) = declaration.strip_prefix("local ") {
+            let table = local
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default();
+            if local.contains(": ") && local.contains("= function") {
+                parser.declare_function_value(&doc, &declaration);
+            } else {
+                parser.declare_class(&doc, table);
+                if source.contains('\n') && local.contains("= {") {
+                    parser.declare_fields(table, &source);
+                }
+            }
+        } else if let Some(returned) = declaration.strip_prefix("return ") {
+            parser.returned = Some(returned.trim().to_string());
+        } else if declaration.contains('=') && !declaration.starts_with("type ") {
+            parser.declare_property(&doc, &declaration);
+        }
+    }
+
+    // Classes without any documentation or members are internal helpers, such as for overloads
+    parser.classes.retain(|class| {
+        class.description.is_some() || !class.functions.is_empty() || !class.properties.is_empty()
+    });
+
+    let class = parser
+        .returned
+        .as_ref()
+        .and_then(|table| parser.class_tables.get(table))
+        .cloned();
+    let description = class.as_ref().and_then(|class| {
+        parser
+            .classes
+            .iter()
+            .find(|c| &c.name == class)
+            .and_then(|c| c.description.clone())
+    });
+
+    ApiModule {
+        name: name.to_string(),
+        class,
+        description,
+        classes: parser.classes,
+        types: parser.types,
+    }
+}
+
+/**
+    Reads a single declaration starting at the first of the given lines, returning it
+    both as a single line and as it was written, and the number of lines that it spans.
+
+    Declarations end once all brackets are closed, and the next line does not continue
+    the declaration - such as for union types with each member on a separate line.
+*/
+fn read_declaration(lines: &[&str]) -> (String, String, usize) {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut consumed = 0;
+    for line in lines {
+        consumed += 1;
+        let code = line.split("--").next().unwrap_or_default();
+        depth += bracket_depth(code);
+        parts.push(code.trim());
+
+        if depth <= 0 {
+            let continues = code.trim_end().ends_with('=')
+                || lines.get(consumed).is_some_and(|next| {
+                    let next = next.trim_start();
+                    next.starts_with('|') || next.starts_with('&')
+                });
+            if !continues {
+                break;
+            }
+        }
+    }
+
+    let mut declaration = parts
+        .iter()
+        .filter(|part| !part.is_empty())
+        .fold(String::new(), |mut acc, part| {
+            let joins = acc.ends_with(['(', '{', '<', '[']) || part.starts_with([')', '}']);
+            if !acc.is_empty() && !joins {
+                acc.push(' ');
+            }
+            acc.push_str(part);
+            acc
+        })
+        .replace(",)", ")")
+        .replace(",}", "}");
+
+    // Function bodies are never part of the declaration
+    if declaration.starts_with("function ") {
+        if let Some(stripped) = declaration.strip_suffix(" end") {
+            declaration = stripped.to_string();
+        }
+    }
+
+    (declaration, lines[..consumed].join("\n"), consumed)
+}
+
+fn bracket_depth(code: &str) -> i32 {
+    let mut depth = 0;
+    let mut chars = code.chars().peekable();
+    let mut in_string = None;
+    while let Some(c) = chars.next() {
+        match (in_string, c) {
+            (Some(quote), _) if c == quote => in_string = None,
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '"' | '\'' | '`') => in_string = Some(c),
+            (None, '(' | '{' | '[') => depth += 1,
+            (None, ')' | '}' | ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/**
+    Splits the given text at all commas that are not nested inside of brackets.
+*/
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '{' | '[' | '<' => depth += 1,
+            // Arrows in function types are not closing brackets
+            '>' if previous == '-' => {}
+            ')' | '}' | ']' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    parts.push(text[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/**
+    Parses the parameters and return type of a function signature, such
+    as `fs.readFile(path: string): string`, skipping any generic types.
+*/
+fn parse_signature(signature: &str) -> (Vec<(String, Option<String>)>, Option<String>) {
+    let Some(open) = signature.find('(') else {
+        return (Vec::new(), None);
+    };
+
+    let mut depth = 0;
+    let mut close = signature.len();
+    for (i, c) in signature[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = open + i;
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let params = split_top_level(&signature[open + 1..close.min(signature.len())])
+        .into_iter()
+        .map(|param| match param.split_once(':') {
+            Some((name, kind)) => (name.trim().to_string(), Some(kind.trim().to_string())),
+            None => (param.to_string(), None),
+        })
+        .collect();
+
+    let returns = signature
+        .get(close + 1..)
+        .and_then(|rest| rest.trim().strip_prefix(':'))
+        .map(|kind| kind.trim().to_string())
+        .filter(|kind| !kind.is_empty());
+
+    (params, returns)
+}
+
+/**
+    Parses a `@param` tag, which may be written as either
+    `@param name description` or `@param name -- description`.
+*/
+fn parse_param_tag(tag: &str) -> (&str, Option<String>) {
+    let (name, description) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let description = description.trim().trim_start_matches("--").trim();
+    (
+        name,
+        Some(description.to_string()).filter(|d| !d.is_empty()),
+    )
+}
+
+/**
+    Parses a `@return` tag, which may be written as either
+    `@return description` or `@return type -- description`.
+*/
+fn parse_return_tag(tag: &str) -> (Option<String>, Option<String>) {
+    match tag.split_once("--") {
+        Some((kind, description)) => (
+            Some(kind.trim().to_string()).filter(|k| !k.is_empty()),
+            Some(description.trim().to_string()).filter(|d| !d.is_empty()),
+        ),
+        None => (None, Some(tag.trim().to_string()).filter(|d| !d.is_empty())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cli::setup::TYPEDEFS_DIR;
+
+    const MODULE: &str = r"
+--[=[
+	@interface Options
+	@within Example
+
+	Options for `example.run`.
+]=]
+export type Options<T = any> = {
+	value: T,
+}
+
+--[=[
+	@class Example
+
+	Built-in library for examples
+
+	```lua
+	@notatag
+	```
+]=]
+local example = {}
+
+--[=[
+	@within Example
+	@tag Must Use
+
+	Runs the example.
+
+	@param options The options to run with
+	@param count -- How many times to run
+	@return The result of the last run
+]=]
+function example.run<T>(
+	options: Options<T>,
+	count: number?
+): (boolean, string)
+	return nil :: any
+end
+
+--[=[
+	@within Example
+	@prop version string
+
+	The version of the example library.
+]=]
+example.version = (nil :: any) :: string
+
+example.untyped = example.run
+
+return example
+";
+
+    #[test]
+    fn doc_comment_tags_and_code_blocks() {
+        let doc = DocComment::parse(&[
+            "\t@within Fs",
+            "\t@tag A",
+            "\t@tag B",
+            "",
+            "\tDescription",
+            "\t```lua",
+            "\t@notatag",
+            "\t```",
+        ]);
+        assert_eq!(doc.tag("within"), Some("Fs"));
+        assert_eq!(doc.plain_tags(), ["A", "B"]);
+        assert_eq!(
+            doc.description.as_deref(),
+            Some("Description\n```lua\n@notatag\n```")
+        );
+        assert!(DocComment::parse(&["\t@within Fs"]).description.is_none());
+    }
+
+    #[test]
+    fn signatures() {
+        let (params, returns) =
+            parse_signature("fs.copy(from: string, opts: { a: number, b: (x: T) -> () }?): ()");
+        assert_eq!(
+            params,
+            [
+                (String::from("from"), Some(String::from("string"))),
+                (
+                    String::from("opts"),
+                    Some(String::from("{ a: number, b: (x: T) -> () }?"))
+                ),
+            ]
+        );
+        assert_eq!(returns.as_deref(), Some("()"));
+        assert_eq!(
+            parse_signature("f(self, ...)"),
+            (
+                vec![(String::from("self"), None), (String::from("..."), None)],
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn param_and_return_tags() {
+        assert_eq!(
+            parse_param_tag("path The path"),
+            ("path", Some(String::from("The path")))
+        );
+        assert_eq!(
+            parse_param_tag("path -- The path"),
+            ("path", Some(String::from("The path")))
+        );
+        assert_eq!(parse_param_tag("path"), ("path", None));
+        assert_eq!(
+            parse_return_tag("string -- The contents"),
+            (
+                Some(String::from("string")),
+                Some(String::from("The contents"))
+            )
+        );
+        assert_eq!(
+            parse_return_tag("The contents"),
+            (None, Some(String::from("The contents")))
+        );
+    }
+
+    #[test]
+    fn multiline_declarations() {
+        let lines = [
+            "export type Kind =",
+            "\t| \"a\"",
+            "\t| \"b\"",
+            "",
+            "local x = 1",
+        ];
+        let (declaration, source, consumed) = read_declaration(&lines);
+        assert_eq!(declaration, "export type Kind = | \"a\" | \"b\"");
+        assert_eq!(source, lines[..3].join("\n"));
+        assert_eq!(consumed, 3);
+    }
+
+    #[test]
+    fn module() {
+        let module = parse_module("example", MODULE);
+        assert_eq!(module.class.as_deref(), Some("Example"));
+        assert_eq!(
+            module.description.as_deref(),
+            Some("Built-in library for examples\n\n```lua\n@notatag\n```")
+        );
+
+        let [ty] = module.types.as_slice() else {
+            panic!("expected a single type, got {:?}", module.types);
+        };
+        assert_eq!(ty.name, "Options");
+        assert_eq!(ty.generics.as_deref(), Some("<T = any>"));
+        assert_eq!(ty.kind, "interface");
+        assert_eq!(ty.within.as_deref(), Some("Example"));
+        assert_eq!(ty.definition, "{\n\tvalue: T,\n}");
+
+        let [class] = module.classes.as_slice() else {
+            panic!("expected a single class, got {:?}", module.classes);
+        };
+        let [function] = class.functions.as_slice() else {
+            panic!("expected a single function, got {:?}", class.functions);
+        };
+        assert_eq!(function.name, "run");
+        assert!(!function.method);
+        assert_eq!(
+            function.signature,
+            "example.run<T>(options: Options<T>, count: number?): (boolean, string)"
+        );
+        assert_eq!(function.tags, ["Must Use"]);
+        assert_eq!(function.params[0].kind.as_deref(), Some("Options<T>"));
+        assert_eq!(
+            function.params[0].description.as_deref(),
+            Some("The options to run with")
+        );
+        assert_eq!(
+            function.params[1].description.as_deref(),
+            Some("How many times to run")
+        );
+        assert_eq!(
+            function.returns[0].kind.as_deref(),
+            Some("(boolean, string)")
+        );
+        assert_eq!(
+            function.returns[0].description.as_deref(),
+            Some("The result of the last run")
+        );
+
+        let [property] = class.properties.as_slice() else {
+            panic!("expected a single property, got {:?}", class.properties);
+        };
+        assert_eq!(property.name, "version");
+        assert_eq!(property.kind.as_deref(), Some("string"));
+    }
+
+    #[test]
+    fn builtin_typedefs() {
+        let dump = dump_typedefs(&TYPEDEFS_DIR);
+        let fs = dump
+            .modules
+            .iter()
+            .find(|module| module.name == "fs")
+            .expect("missing fs module");
+        assert_eq!(fs.class.as_deref(), Some("FS"));
+
+        let class = fs.classes.iter().find(|c| c.name == "FS").unwrap();
+        let read_file = class
+            .functions
+            .iter()
+            .find(|f| f.name == "readFile")
+            .expect("missing fs.readFile");
+        assert_eq!(read_file.params[0].name, "path");
+        assert!(read_file.description.is_some());
+        assert!(fs.types.iter().any(|t| t.name == "File"));
+
+        // Every builtin library should be documented
+        for module in &dump.modules {
+            assert!(
+                module.description.is_some(),
+                "{} has no description",
+                module.name
+            );
+        }
+    }
+}
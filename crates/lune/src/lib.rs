@@ -9,4 +9,57 @@ pub use lune_roblox as roblox;
 #[cfg(test)]
 mod tests;
 
-pub use crate::rt::{Runtime, RuntimeError, RuntimeResult};
+pub use crate::rt::{enable_crash_reports, Runtime, RuntimeError, RuntimeResult};
+
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+pub use crate::rt::{FuzzCrash, FuzzEvent, FuzzOptions, FuzzReport, FuzzStats};
+
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+pub use lune_std::{ResolvedModule, VirtualModule};
+
+#[cfg(feature = "std-net")]
+pub use lune_std::{HostOverrides, HostPolicy};
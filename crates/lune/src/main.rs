@@ -19,6 +19,8 @@ async fn main() -> ExitCode {
         .with_level(true)
         .init();
 
+    lune::enable_crash_reports();
+
     if let Some(bin) = standalone::check().await {
         return standalone::run(bin).await.unwrap();
     }
@@ -0,0 +1,186 @@
+use std::{
+    backtrace::Backtrace,
+    cell::RefCell,
+    fmt::Write as _,
+    fs,
+    panic::{self, PanicHookInfo},
+    path::PathBuf,
+    rc::{Rc, Weak},
+    sync::Once,
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::SchedulerState;
+
+const ISSUES_URL: &str = "https://github.com/lune-org/lune/issues";
+
+// Maximum number of Lua stack frames to include, any
+// deeper frames are most likely from infinite recursion
+const MAX_LUA_FRAMES: usize = 64;
+
+struct ActiveRuntime {
+    lua: Weak<Lua>,
+    scheduler: SchedulerState,
+}
+
+thread_local! {
+    static ACTIVE_RUNTIMES: RefCell<Vec<ActiveRuntime>> = const { RefCell::new(Vec::new()) };
+}
+
+/**
+    Marks a runtime as running on the current thread, until the returned guard
+    is dropped, so that crash reports can include information about it.
+*/
+pub(crate) fn enter_runtime(lua: &Rc<Lua>, scheduler: SchedulerState) -> ActiveRuntimeGuard {
+    ACTIVE_RUNTIMES.with_borrow_mut(|runtimes| {
+        runtimes.push(ActiveRuntime {
+            lua: Rc::downgrade(lua),
+            scheduler,
+        });
+    });
+    ActiveRuntimeGuard { _private: () }
+}
+
+pub(crate) struct ActiveRuntimeGuard {
+    _private: (),
+}
+
+impl Drop for ActiveRuntimeGuard {
+    fn drop(&mut self) {
+        // NOTE: This may be dropped while unwinding from a panic,
+        // so we must not panic again here, which would abort
+        let _ = ACTIVE_RUNTIMES.try_with(|runtimes| {
+            if let Ok(mut runtimes) = runtimes.try_borrow_mut() {
+                runtimes.pop();
+            }
+        });
+    }
+}
+
+/**
+    Enables crash reports for the current process.
+
+    Whenever a panic happens, such as from a bug in a built-in library or the
+    scheduler, a crash report is written to a file in the temporary directory,
+    and a short message pointing at it is printed after the panic message.
+
+    Panics inside of scheduler tasks are fatal too, and the scheduler resumes
+    them once it is done running, so those also end the process with a report.
+
+    Crash reports contain the panic message, the Rust backtrace, the traceback
+    of the Lua thread that was running, the state of the scheduler, and the version
+    of Lune, which is everything that is usually needed to track down the bug.
+
+    Calling this more than once has no effect.
+*/
+pub fn enable_crash_reports() {
+    static ENABLED: Once = Once::new();
+    ENABLED.call_once(|| {
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous_hook(info);
+            let report = create_report(info);
+            match write_report(&report) {
+                Ok(path) => eprintln!(
+                    "\nLune panicked unexpectedly, and a crash report was written to {}\
+                    \nPlease include it when reporting this bug at {ISSUES_URL}",
+                    path.display()
+                ),
+                Err(e) => eprintln!(
+                    "\nLune panicked unexpectedly, and writing a crash report failed: {e}"
+                ),
+            }
+        }));
+    });
+}
+
+fn create_report(info: &PanicHookInfo) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(report, "Lune crash report\n");
+    let _ = writeln!(report, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        report,
+        "Platform: {}-{}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(
+        report,
+        "Thread: {}",
+        thread::current().name().unwrap_or("<unnamed>")
+    );
+
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "<unknown>".to_string());
+    let _ = writeln!(report, "\nPanic:\n{message}");
+    if let Some(location) = info.location() {
+        let _ = writeln!(report, "at {location}");
+    }
+
+    // NOTE: The thread local may already be destroyed or borrowed if we are
+    // panicking during thread shutdown or while entering a runtime, and
+    // panicking again from within the panic hook would abort the process
+    let _ = ACTIVE_RUNTIMES.try_with(|runtimes| {
+        let Ok(runtimes) = runtimes.try_borrow() else {
+            return;
+        };
+        let Some(runtime) = runtimes.last() else {
+            let _ = writeln!(report, "\nNo runtime was running on this thread");
+            return;
+        };
+        let _ = writeln!(report, "\nLua traceback:");
+        match runtime.lua.upgrade() {
+            Some(lua) => write_lua_traceback(&mut report, &lua),
+            None => {
+                let _ = writeln!(report, "<runtime was dropped>");
+            }
+        }
+        let _ = writeln!(report, "\nScheduler:\n{}", runtime.scheduler);
+    });
+
+    let _ = writeln!(report, "\nRust backtrace:\n{}", Backtrace::force_capture());
+
+    report
+}
+
+fn write_lua_traceback(report: &mut String, lua: &Lua) {
+    let mut frames = 0;
+    for info in (0..).map_while(|level| lua.inspect_stack(level)) {
+        if frames == MAX_LUA_FRAMES {
+            let _ = writeln!(report, "...");
+            break;
+        }
+        let source = info.source();
+        let source = source.short_src.as_deref().unwrap_or("?");
+        let line = info.curr_line();
+        let name = info.names().name.map(|name| name.to_string());
+        let _ = match (line > 0, name) {
+            (true, Some(name)) => writeln!(report, "{source}:{line} function {name}"),
+            (true, None) => writeln!(report, "{source}:{line}"),
+            (false, Some(name)) => writeln!(report, "{source} function {name}"),
+            (false, None) => writeln!(report, "{source}"),
+        };
+        frames += 1;
+    }
+    if frames == 0 {
+        let _ = writeln!(report, "<no Lua thread was running>");
+    }
+}
+
+fn write_report(report: &str) -> std::io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let path =
+        std::env::temp_dir().join(format!("lune-crash-{timestamp}-{}.txt", std::process::id()));
+    fs::write(&path, report)?;
+    Ok(path)
+}
@@ -0,0 +1,339 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use lune_std::FunctionCoverage;
+
+// Bytes that commonly have special meaning in parsers, which random
+// bytes would only rarely hit, such as delimiters and escape characters
+const INTERESTING_BYTES: &[u8] = &[
+    0x00, 0x01, 0x7F, 0x80, 0xFF, b'\n', b'\r', b'\t', b' ', b'"', b'\'', b'\\', b'{', b'}', b'[',
+    b']', b'(', b')', b'<', b'>', b',', b':', b';', b'=', b'-', b'+', b'.', b'0', b'9', b'e',
+];
+
+const MAX_MUTATIONS: usize = 4;
+
+/**
+    Options for fuzzing a script using [`Runtime::fuzz`](crate::Runtime::fuzz).
+*/
+#[derive(Debug, Clone)]
+pub struct FuzzOptions {
+    /// Inputs to start from, such as a corpus saved by a previous run.
+    pub corpus: Vec<Vec<u8>>,
+    /// The maximum number of inputs to run, or `None` to run until a crash is found.
+    pub runs: Option<u64>,
+    /// The maximum amount of time to run for, or `None` to run until a crash is found.
+    pub max_duration: Option<Duration>,
+    /// The maximum length of generated inputs, in bytes.
+    pub max_len: usize,
+    /// The seed for generating inputs, or `None` to use a random seed.
+    pub seed: Option<u64>,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            corpus: Vec::new(),
+            runs: None,
+            max_duration: None,
+            max_len: 4096,
+            seed: None,
+        }
+    }
+}
+
+/**
+    An event that happened while fuzzing, given to the callback for [`Runtime::fuzz`](crate::Runtime::fuzz).
+*/
+#[derive(Debug, Clone, Copy)]
+pub enum FuzzEvent<'a> {
+    /// A generated input reached code that no previous input had, and was added to the corpus.
+    NewInput { input: &'a [u8], stats: FuzzStats },
+    /// Sent periodically, with statistics for the run so far.
+    Pulse { stats: FuzzStats },
+}
+
+/**
+    Statistics for a fuzzing run.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzStats {
+    /// The number of inputs that have been run.
+    pub runs: u64,
+    /// The number of inputs in the corpus.
+    pub corpus: usize,
+    /// The number of distinct coverage features that have been reached.
+    pub features: usize,
+}
+
+/**
+    An input that made the fuzzed function throw an error.
+*/
+#[derive(Debug, Clone)]
+pub struct FuzzCrash {
+    pub input: Vec<u8>,
+    pub error: String,
+}
+
+/**
+    The result of a fuzzing run.
+*/
+#[derive(Debug, Clone)]
+pub struct FuzzReport {
+    /// The seed that was used for generating inputs, which can be used to replay the run.
+    pub seed: u64,
+    /// Statistics for the whole run.
+    pub stats: FuzzStats,
+    /// The input that caused a crash, if one was found.
+    pub crash: Option<FuzzCrash>,
+}
+
+// A line that ran some number of times during a single run, where the number
+// of times is bucketed, so that loops running a few more times are not new
+type Feature = (usize, i32, usize, u8);
+
+pub(crate) struct Fuzzer {
+    options: FuzzOptions,
+    seed: u64,
+    rng: StdRng,
+    started: Instant,
+    next_pulse: u64,
+    runs: u64,
+    pending: VecDeque<Vec<u8>>,
+    current: Option<(Vec<u8>, bool)>,
+    corpus: Vec<Vec<u8>>,
+    features: HashSet<Feature>,
+    chunks: HashMap<String, usize>,
+    hits: HashMap<(usize, i32), Vec<i32>>,
+    crash: Option<FuzzCrash>,
+    on_event: Box<dyn FnMut(FuzzEvent)>,
+}
+
+impl Fuzzer {
+    pub fn new(mut options: FuzzOptions, on_event: Box<dyn FnMut(FuzzEvent)>) -> Self {
+        let seed = options.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut pending = VecDeque::from(std::mem::take(&mut options.corpus));
+        if pending.is_empty() {
+            pending.push_back(Vec::new());
+        }
+        Self {
+            options,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            started: Instant::now(),
+            next_pulse: 1,
+            runs: 0,
+            pending,
+            current: None,
+            corpus: Vec::new(),
+            features: HashSet::new(),
+            chunks: HashMap::new(),
+            hits: HashMap::new(),
+            crash: None,
+            on_event,
+        }
+    }
+
+    /**
+        Records the coverage so far without adding any features,
+        so that loading the script itself does not count as coverage.
+    */
+    pub fn prime(&mut self, coverage: Vec<FunctionCoverage>) {
+        self.diff(coverage);
+    }
+
+    /**
+        Gets the next input to run, or `None` if fuzzing should stop.
+    */
+    pub fn next_input(&mut self) -> Option<Vec<u8>> {
+        let reached_runs = self.options.runs.is_some_and(|runs| self.runs >= runs);
+        let reached_time = self
+            .options
+            .max_duration
+            .is_some_and(|max| self.started.elapsed() >= max);
+        if self.crash.is_some() || reached_runs || reached_time {
+            return None;
+        }
+        let (input, generated) = match self.pending.pop_front() {
+            Some(input) => (input, false),
+            None => (self.generate(), true),
+        };
+        self.current = Some((input.clone(), generated));
+        Some(input)
+    }
+
+    /**
+        Reports the coverage and result of running the current input.
+    */
+    pub fn report(&mut self, coverage: Vec<FunctionCoverage>, error: Option<String>) {
+        let Some((input, generated)) = self.current.take() else {
+            return;
+        };
+        self.runs += 1;
+
+        let new_features = self.diff(coverage);
+        let is_new = !new_features.is_empty();
+        self.features.extend(new_features);
+
+        if let Some(error) = error {
+            self.crash = Some(FuzzCrash { input, error });
+            return;
+        }
+
+        if is_new {
+            if generated {
+                let stats = self.stats();
+                (self.on_event)(FuzzEvent::NewInput {
+                    input: &input,
+                    stats: FuzzStats {
+                        corpus: stats.corpus + 1,
+                        ..stats
+                    },
+                });
+            }
+            self.corpus.push(input);
+        }
+
+        if self.runs >= self.next_pulse {
+            self.next_pulse *= 2;
+            let stats = self.stats();
+            (self.on_event)(FuzzEvent::Pulse { stats });
+        }
+    }
+
+    pub fn finish(&mut self) -> FuzzReport {
+        FuzzReport {
+            seed: self.seed,
+            stats: self.stats(),
+            crash: self.crash.take(),
+        }
+    }
+
+    fn stats(&self) -> FuzzStats {
+        FuzzStats {
+            runs: self.runs,
+            corpus: self.corpus.len(),
+            features: self.features.len(),
+        }
+    }
+
+    // Compares the total hit counts with the ones from the previous
+    // run, and returns the features that have not been reached before
+    fn diff(&mut self, coverage: Vec<FunctionCoverage>) -> Vec<Feature> {
+        let mut found = Vec::new();
+        for function in coverage {
+            let next_id = self.chunks.len();
+            let chunk = *self.chunks.entry(function.chunk).or_insert(next_id);
+            let previous = self
+                .hits
+                .insert((chunk, function.line_defined), function.hits.clone())
+                .unwrap_or_default();
+            for (line, &hits) in function.hits.iter().enumerate() {
+                let before = previous.get(line).copied().unwrap_or(0).max(0);
+                let delta = hits - before;
+                if delta > 0 {
+                    let feature = (chunk, function.line_defined, line, bucket(delta));
+                    if !self.features.contains(&feature) {
+                        found.push(feature);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn generate(&mut self) -> Vec<u8> {
+        let mut input = if self.corpus.is_empty() {
+            Vec::new()
+        } else {
+            self.corpus[self.rng.gen_range(0..self.corpus.len())].clone()
+        };
+        for _ in 0..self.rng.gen_range(1..=MAX_MUTATIONS) {
+            self.mutate(&mut input);
+        }
+        input.truncate(self.options.max_len);
+        input
+    }
+
+    fn mutate(&mut self, input: &mut Vec<u8>) {
+        let rng = &mut self.rng;
+        let can_grow = input.len() < self.options.max_len;
+        if input.is_empty() {
+            let byte = random_byte(rng);
+            input.push(byte);
+            return;
+        }
+        match rng.gen_range(0..8) {
+            // Flip a single bit
+            0 => {
+                let index = rng.gen_range(0..input.len());
+                input[index] ^= 1 << rng.gen_range(0..8);
+            }
+            // Replace a byte
+            1 => {
+                let index = rng.gen_range(0..input.len());
+                input[index] = random_byte(rng);
+            }
+            // Insert a byte
+            2 if can_grow => {
+                let index = rng.gen_range(0..=input.len());
+                let byte = random_byte(rng);
+                input.insert(index, byte);
+            }
+            // Remove a range of bytes
+            3 | 4 => {
+                let start = rng.gen_range(0..input.len());
+                let end = rng.gen_range(start..=input.len().min(start + 8));
+                input.drain(start..end.max(start + 1));
+            }
+            // Duplicate a range of bytes
+            5 if can_grow => {
+                let start = rng.gen_range(0..input.len());
+                let end = rng.gen_range(start + 1..=input.len().min(start + 16));
+                let range = input[start..end].to_vec();
+                let index = rng.gen_range(0..=input.len());
+                input.splice(index..index, range);
+            }
+            // Splice with another input from the corpus
+            6 if !self.corpus.is_empty() => {
+                let other = &self.corpus[rng.gen_range(0..self.corpus.len())];
+                if !other.is_empty() {
+                    let split = rng.gen_range(0..=input.len());
+                    let other_split = rng.gen_range(0..other.len());
+                    input.truncate(split);
+                    input.extend_from_slice(&other[other_split..]);
+                }
+            }
+            // Swap two bytes
+            _ => {
+                let a = rng.gen_range(0..input.len());
+                let b = rng.gen_range(0..input.len());
+                input.swap(a, b);
+            }
+        }
+    }
+}
+
+fn random_byte(rng: &mut StdRng) -> u8 {
+    if rng.gen_bool(0.5) {
+        INTERESTING_BYTES[rng.gen_range(0..INTERESTING_BYTES.len())]
+    } else {
+        rng.gen()
+    }
+}
+
+fn bucket(hits: i32) -> u8 {
+    match hits {
+        1 => 0,
+        2 => 1,
+        3 => 2,
+        4..=7 => 3,
+        8..=15 => 4,
+        16..=31 => 5,
+        32..=127 => 6,
+        _ => 7,
+    }
+}
@@ -1,5 +1,55 @@
+mod crash;
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+mod fuzz;
 mod result;
 mod runtime;
 
+pub use self::crash::enable_crash_reports;
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+pub use self::fuzz::{FuzzCrash, FuzzEvent, FuzzOptions, FuzzReport, FuzzStats};
 pub use self::result::{RuntimeError, RuntimeResult};
 pub use self::runtime::Runtime;
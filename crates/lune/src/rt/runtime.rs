@@ -1,6 +1,8 @@
 #![allow(clippy::missing_panics_doc)]
 
 use std::{
+    cell::RefCell,
+    path::PathBuf,
     process::ExitCode,
     rc::Rc,
     sync::{
@@ -10,11 +12,86 @@ use std::{
 };
 
 use mlua::prelude::*;
-use mlua_luau_scheduler::{Functions, Scheduler};
+use mlua_luau_scheduler::{Functions, LuaSchedulerExt, QueueStats, Scheduler, TaskKind, ThreadId};
 use self_cell::self_cell;
 
+use lune_utils::fmt::SourceMap;
+
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+use lune_std::{ResolvedModule, VirtualModule};
+
+use super::crash::enter_runtime;
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+use super::fuzz::{FuzzEvent, FuzzOptions, FuzzReport, Fuzzer};
 use super::{RuntimeError, RuntimeResult};
 
+// Runs the script, and then calls its exported fuzz function with inputs until
+// the fuzzer runs out of them - errors thrown while loading the script are
+// returned instead of thrown, so that they are not reported twice
+const FUZZ_IMPL_LUA: &str = r#"
+local script, nextInput, report = ...
+local function handler(err)
+    return if type(err) == "string" then debug.traceback(err, 2) else err
+end
+return pcall(function()
+    local exports = script()
+    if type(exports) ~= "table" or type(exports.fuzz) ~= "function" then
+        error("Fuzzed scripts must return a table with a fuzz function")
+    end
+    while true do
+        local input = nextInput()
+        if input == nil then
+            break
+        end
+        report(xpcall(exports.fuzz, handler, input))
+    end
+end)
+"#;
+
 // NOTE: We need to use self_cell to create a self-referential
 // struct storing both the Lua VM and the scheduler. The scheduler
 // needs to be created at the same time so that we can also create
@@ -45,16 +122,27 @@ impl RuntimeInner {
 
             // Inject all the globals that are enabled
             #[cfg(any(
+                feature = "std-args",
+                feature = "std-config",
                 feature = "std-datetime",
+                feature = "std-dirs",
                 feature = "std-fs",
+                feature = "std-ipc",
                 feature = "std-luau",
                 feature = "std-net",
+                feature = "std-notify",
                 feature = "std-process",
+                feature = "std-random",
                 feature = "std-regex",
                 feature = "std-roblox",
                 feature = "std-serde",
+                feature = "std-serial",
                 feature = "std-stdio",
                 feature = "std-task",
+                feature = "std-test",
+                feature = "std-tray",
+                feature = "std-units",
+                feature = "std-uuid",
             ))]
             {
                 lune_std::set_global_version(lua, env!("CARGO_PKG_VERSION"));
@@ -67,16 +155,27 @@ impl RuntimeInner {
             // _G table needs to be injected again after sandboxing,
             // otherwise it will be read-only and completely unusable
             #[cfg(any(
+                feature = "std-args",
+                feature = "std-config",
                 feature = "std-datetime",
+                feature = "std-dirs",
                 feature = "std-fs",
+                feature = "std-ipc",
                 feature = "std-luau",
                 feature = "std-net",
+                feature = "std-notify",
                 feature = "std-process",
+                feature = "std-random",
                 feature = "std-regex",
                 feature = "std-roblox",
                 feature = "std-serde",
+                feature = "std-serial",
                 feature = "std-stdio",
                 feature = "std-task",
+                feature = "std-test",
+                feature = "std-tray",
+                feature = "std-units",
+                feature = "std-uuid",
             ))]
             {
                 let g_table = lune_std::LuneStandardGlobal::GTable;
@@ -96,11 +195,15 @@ impl RuntimeInner {
     }
 }
 
+type ErrorCallback = Arc<dyn Fn(RuntimeError) + Send + Sync>;
+
 /**
     A Lune runtime.
 */
 pub struct Runtime {
     inner: RuntimeInner,
+    error_callback: Option<ErrorCallback>,
+    main_function: bool,
 }
 
 impl Runtime {
@@ -114,6 +217,8 @@ impl Runtime {
     pub fn new() -> Self {
         Self {
             inner: RuntimeInner::create().expect("Failed to create runtime"),
+            error_callback: None,
+            main_function: false,
         }
     }
 
@@ -131,6 +236,358 @@ impl Runtime {
         self
     }
 
+    /**
+        Sets a callback to call with any errors that scripts run into,
+        instead of printing them to stderr, which is the default.
+    */
+    #[must_use]
+    pub fn with_error_callback(
+        mut self,
+        callback: impl Fn(RuntimeError) + Send + Sync + 'static,
+    ) -> Self {
+        self.error_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /**
+        Sets the number of Lua threads that the scheduler will run per batch.
+
+        Batching can reduce overhead for workloads with lots of small threads, such as `net.serve`.
+
+        See [`Scheduler::set_batch_size`] for more information.
+    */
+    #[must_use]
+    pub fn with_batch_size(self, size: usize) -> Self {
+        self.inner.scheduler().set_batch_size(size);
+        self
+    }
+
+    /**
+        Enables or disables low latency mode for `task.wait`.
+
+        Low latency mode makes short waits more accurate, at the cost of higher CPU usage.
+
+        Has no effect if the `std-task` feature is not enabled.
+    */
+    #[must_use]
+    pub fn with_low_latency(self, enabled: bool) -> Self {
+        #[cfg(feature = "std-task")]
+        lune_std::set_low_latency_mode(self.inner.lua(), enabled);
+        #[cfg(not(feature = "std-task"))]
+        let _ = enabled;
+        self
+    }
+
+    /**
+        Enables or disables calling a `main` function exported by scripts.
+
+        When enabled, scripts may return a table with a `main` function, which is called
+        once the script has finished running, in the same initial task. It is given the
+        script arguments, or the parsed arguments if the table also has an args parser
+        in its `args` field. A number returned from it is used as the exit code, unless
+        `process.exit` was called, or any thread errored while running the script.
+
+        Disabled by default, since this is only meant for the entry script given to `lune run`.
+    */
+    #[must_use]
+    pub fn with_main_function(mut self, enabled: bool) -> Self {
+        self.main_function = enabled;
+        self
+    }
+
+    /**
+        Enables or disables warnings from built-in libraries, such as for deprecated arguments.
+
+        Warnings are enabled by default, and each one is only written once per calling location.
+    */
+    #[must_use]
+    pub fn with_warnings(self, enabled: bool) -> Self {
+        lune_utils::warnings::set_warnings_enabled(self.inner.lua(), enabled);
+        self
+    }
+
+    /**
+        Captures output written by built-in libraries, such as from `print` and `stdio.write`,
+        into the given buffer instead of writing it to stdout and stderr.
+
+        Output that is written directly by child processes is not captured.
+    */
+    #[must_use]
+    pub fn with_captured_output(self, output: lune_utils::output::CapturedOutput) -> Self {
+        lune_utils::output::set_captured_output(self.inner.lua(), Some(output));
+        self
+    }
+
+    /**
+        Enables or disables updating of snapshots that no longer match in `@lune/test`.
+
+        Has no effect if the `std-test` feature is not enabled.
+    */
+    #[must_use]
+    pub fn with_update_snapshots(self, enabled: bool) -> Self {
+        #[cfg(feature = "std-test")]
+        lune_std::set_update_snapshots(self.inner.lua(), enabled);
+        #[cfg(not(feature = "std-test"))]
+        let _ = enabled;
+        self
+    }
+
+    /**
+        Enables or disables test mode for `@lune/test`, which is used when running tests.
+
+        In test mode, spies may replace functions in read-only tables, such as built-in libraries.
+
+        Has no effect if the `std-test` feature is not enabled.
+    */
+    #[must_use]
+    pub fn with_test_mode(self, enabled: bool) -> Self {
+        #[cfg(feature = "std-test")]
+        lune_std::set_test_mode(self.inner.lua(), enabled);
+        #[cfg(not(feature = "std-test"))]
+        let _ = enabled;
+        self
+    }
+
+    /**
+        Sets a directory that all paths given to `@lune/fs` are relative to, as if it was
+        the root of the filesystem, so that scripts can not read or write anything outside of it.
+
+        This is useful for tooling that should only operate on a staging directory, and for
+        tests that should not be able to escape their sandbox. Other libraries, such as
+        `@lune/process`, are not restricted by this, and `require` is not affected by it.
+
+        Has no effect if the `std-fs` feature is not enabled.
+    */
+    #[must_use]
+    pub fn with_fs_root(self, root: impl Into<PathBuf>) -> Self {
+        #[cfg(feature = "std-fs")]
+        lune_std::set_fs_root(self.inner.lua(), root);
+        #[cfg(not(feature = "std-fs"))]
+        let _ = root;
+        self
+    }
+
+    /**
+        Sets the policy restricting which hosts `@lune/net` may contact, such as for
+        running third-party scripts that should only be able to reach a few known hosts.
+
+        Requests to hosts that are not allowed, including through redirects, fail with
+        an error instead of connecting. Incoming connections to `net.serve` are not affected.
+
+        See [`lune_std::HostPolicy`] for the supported host patterns.
+    */
+    #[cfg(feature = "std-net")]
+    #[must_use]
+    pub fn with_host_policy(self, policy: lune_std::HostPolicy) -> Self {
+        lune_std::set_host_policy(self.inner.lua(), policy);
+        self
+    }
+
+    /**
+        Overrides the addresses that hostnames resolve to in `@lune/net`, such as for
+        integration tests that should reach local servers using production hostnames,
+        without changing the system resolver or `/etc/hosts`.
+
+        Overrides apply to requests, web sockets, UDP sockets and `net.resolve`, unless
+        a nameserver is given to it. Other hosts are resolved using the system resolver.
+    */
+    #[cfg(feature = "std-net")]
+    #[must_use]
+    pub fn with_host_overrides(self, overrides: lune_std::HostOverrides) -> Self {
+        lune_std::set_host_overrides(self.inner.lua(), overrides);
+        self
+    }
+
+    /**
+        Sets the seed to use for property checks in `@lune/test` that were not given a seed.
+
+        Has no effect if the `std-test` feature is not enabled.
+    */
+    #[must_use]
+    pub fn with_test_seed(self, seed: u64) -> Self {
+        #[cfg(feature = "std-test")]
+        lune_std::set_test_seed(self.inner.lua(), seed);
+        #[cfg(not(feature = "std-test"))]
+        let _ = seed;
+        self
+    }
+
+    /**
+        Registers a virtual module, which scripts can require using `require("@virtual/name")`.
+
+        Modules may be either Luau source code, which runs the first time that
+        the module is required, or a prebuilt value such as a table of configuration.
+
+        # Panics
+
+        Panics if the module name is empty.
+    */
+    #[cfg(any(
+        feature = "std-args",
+        feature = "std-config",
+        feature = "std-datetime",
+        feature = "std-dirs",
+        feature = "std-fs",
+        feature = "std-ipc",
+        feature = "std-luau",
+        feature = "std-net",
+        feature = "std-notify",
+        feature = "std-process",
+        feature = "std-random",
+        feature = "std-regex",
+        feature = "std-roblox",
+        feature = "std-serde",
+        feature = "std-serial",
+        feature = "std-stdio",
+        feature = "std-task",
+        feature = "std-test",
+        feature = "std-tray",
+        feature = "std-units",
+        feature = "std-uuid",
+    ))]
+    #[must_use]
+    pub fn with_virtual_module(self, name: impl Into<String>, module: VirtualModule) -> Self {
+        lune_std::register_virtual_module(self.inner.lua(), name, module)
+            .expect("Failed to register virtual module");
+        self
+    }
+
+    /**
+        Registers a require resolver, which handles all require paths that start with the given prefix.
+
+        The resolver is given the path being required and the source of the script requiring it,
+        and may return `None` to let the next matching resolver, or the default behavior of
+        `require`, handle the path. Every path is only resolved once, and then cached.
+
+        # Panics
+
+        Panics if the prefix is empty, or if it starts with `@lune/`.
+    */
+    #[cfg(any(
+        feature = "std-args",
+        feature = "std-config",
+        feature = "std-datetime",
+        feature = "std-dirs",
+        feature = "std-fs",
+        feature = "std-ipc",
+        feature = "std-luau",
+        feature = "std-net",
+        feature = "std-notify",
+        feature = "std-process",
+        feature = "std-random",
+        feature = "std-regex",
+        feature = "std-roblox",
+        feature = "std-serde",
+        feature = "std-serial",
+        feature = "std-stdio",
+        feature = "std-task",
+        feature = "std-test",
+        feature = "std-tray",
+        feature = "std-units",
+        feature = "std-uuid",
+    ))]
+    #[must_use]
+    pub fn with_require_resolver<F>(self, prefix: impl Into<String>, resolver: F) -> Self
+    where
+        F: Fn(&Lua, &str, &str) -> LuaResult<Option<ResolvedModule>> + 'static,
+    {
+        lune_std::register_require_resolver(self.inner.lua(), prefix, resolver)
+            .expect("Failed to register require resolver");
+        self
+    }
+
+    /**
+        Enables caching of compiled bytecode for required modules, in the given directory.
+
+        Modules that have not changed since they were last required, even by
+        a different runtime or process, will not need to be compiled again.
+    */
+    #[cfg(any(
+        feature = "std-args",
+        feature = "std-config",
+        feature = "std-datetime",
+        feature = "std-dirs",
+        feature = "std-fs",
+        feature = "std-ipc",
+        feature = "std-luau",
+        feature = "std-net",
+        feature = "std-notify",
+        feature = "std-process",
+        feature = "std-random",
+        feature = "std-regex",
+        feature = "std-roblox",
+        feature = "std-serde",
+        feature = "std-serial",
+        feature = "std-stdio",
+        feature = "std-task",
+        feature = "std-test",
+        feature = "std-tray",
+        feature = "std-units",
+        feature = "std-uuid",
+    ))]
+    #[must_use]
+    pub fn with_bytecode_cache(self, dir: impl Into<PathBuf>) -> Self {
+        lune_std::set_bytecode_cache_dir(self.inner.lua(), dir);
+        self
+    }
+
+    /**
+        Sets the maximum total size, in bytes, of the bytecode cache enabled using
+        [`Runtime::with_bytecode_cache`], which is 64 MiB by default.
+
+        Modules that were least recently used are removed once the cache grows larger.
+    */
+    #[cfg(any(
+        feature = "std-args",
+        feature = "std-config",
+        feature = "std-datetime",
+        feature = "std-dirs",
+        feature = "std-fs",
+        feature = "std-ipc",
+        feature = "std-luau",
+        feature = "std-net",
+        feature = "std-notify",
+        feature = "std-process",
+        feature = "std-random",
+        feature = "std-regex",
+        feature = "std-roblox",
+        feature = "std-serde",
+        feature = "std-serial",
+        feature = "std-stdio",
+        feature = "std-task",
+        feature = "std-test",
+        feature = "std-tray",
+        feature = "std-units",
+        feature = "std-uuid",
+    ))]
+    #[must_use]
+    pub fn with_bytecode_cache_max_size(self, max_size: u64) -> Self {
+        lune_std::set_bytecode_cache_max_size(self.inner.lua(), max_size);
+        self
+    }
+
+    /**
+        Gets statistics about the time that Lua threads of the given kind
+        have spent waiting in the scheduler queue, across all runs so far.
+
+        See [`Scheduler::queue_stats`] for more information.
+    */
+    #[must_use]
+    pub fn queue_stats(&self, kind: TaskKind) -> QueueStats {
+        self.inner.scheduler().queue_stats(kind)
+    }
+
+    /**
+        Gets the Luau VM that scripts in this runtime run in.
+
+        Globals set by scripts are kept in the VM between runs, and may be
+        inspected using it afterwards, such as for completions in a REPL.
+    */
+    #[must_use]
+    pub fn lua(&self) -> Rc<Lua> {
+        Rc::clone(self.inner.borrow_owner())
+    }
+
     /**
         Runs a Lune script inside of the current runtime.
 
@@ -147,33 +604,289 @@ impl Runtime {
     ) -> RuntimeResult<ExitCode> {
         let lua = self.inner.lua();
         let sched = self.inner.scheduler();
+        let _active = enter_runtime(self.inner.borrow_owner(), sched.state());
 
         // Add error callback to format errors nicely + store status
+        let report_error = {
+            let callback = self.error_callback.clone();
+            move |e: RuntimeError| match &callback {
+                Some(callback) => callback(e),
+                None => eprintln!("{e}"),
+            }
+        };
         let got_any_error = Arc::new(AtomicBool::new(false));
         let got_any_inner = Arc::clone(&got_any_error);
+        let report_inner = report_error.clone();
         self.inner.scheduler().set_error_callback(move |e| {
             got_any_inner.store(true, Ordering::SeqCst);
-            eprintln!("{}", RuntimeError::from(e));
+            report_inner(RuntimeError::from(e));
         });
 
+        // Generated scripts, such as bundles, may contain a source map
+        // that lets errors point at the files they were generated from
+        let source_map = std::str::from_utf8(script_contents.as_ref())
+            .ok()
+            .and_then(SourceMap::from_source);
+        if let Some(map) = source_map {
+            map.register(script_name.as_ref());
+        }
+
         // Load our "main" thread
         let main = lua
             .load(script_contents.as_ref())
-            .set_name(script_name.as_ref());
+            .set_name(script_name.as_ref())
+            .into_function()?;
 
-        // Run it on our scheduler until it and any other spawned threads complete
-        sched.push_thread_back(main, ())?;
+        // Run it on our scheduler until it and any other spawned threads complete,
+        // with a separate thread that calls any exported main function once it is done
+        let main_id = sched.push_thread_back(main, ())?;
+        let call_main_id = if self.main_function {
+            let call_main =
+                lua.create_async_function(move |lua, ()| call_main_function(lua, main_id))?;
+            Some(sched.push_thread_back(call_main, ())?)
+        } else {
+            None
+        };
         sched.run().await;
 
-        // Return the exit code - default to FAILURE if we got any errors
-        let exit_code = sched.get_exit_code().unwrap_or({
+        // NOTE: Results are always taken, so that they are not kept between runs
+        let _ = sched.get_thread_result(main_id);
+
+        // A number returned from an exported main function is used as the exit code
+        let main_exit_code = match call_main_id.and_then(|id| sched.get_thread_result(id)) {
+            Some(Ok(values)) => match values.into_iter().next() {
+                None | Some(LuaValue::Nil) => None,
+                Some(value) => match u8::from_lua(value, lua) {
+                    Ok(code) => Some(ExitCode::from(code)),
+                    Err(e) => {
+                        report_error(RuntimeError::from(LuaError::runtime(format!(
+                            "Main function must return an exit code between 0 and 255\n{e}"
+                        ))));
+                        Some(ExitCode::FAILURE)
+                    }
+                },
+            },
+            _ => None,
+        };
+
+        // Return the exit code - default to FAILURE if we got any errors,
+        // even if the main function returned a different exit code
+        let exit_code = sched.take_exit_code().unwrap_or({
             if got_any_error.load(Ordering::SeqCst) {
                 ExitCode::FAILURE
             } else {
-                ExitCode::SUCCESS
+                main_exit_code.unwrap_or(ExitCode::SUCCESS)
             }
         });
 
+        #[cfg(feature = "std-process")]
+        let exit_code = run_exit_callbacks(lua, sched, &got_any_error, exit_code).await?;
+
         Ok(exit_code)
     }
+
+    /**
+        Fuzzes a Lune script inside of the current runtime.
+
+        The script must return a table with a `fuzz` function, which is called
+        repeatedly with generated inputs as buffers. Inputs are generated by mutating
+        earlier inputs that reached new code, using line coverage as feedback, until
+        an input makes the function throw an error, or a limit in the options is reached.
+
+        Enables coverage for the runtime, see [`lune_std::enable_coverage`].
+
+        # Errors
+
+        This function will return an error if the script fails to load,
+        or if it does not return a table with a `fuzz` function.
+    */
+    #[cfg(any(
+        feature = "std-args",
+        feature = "std-config",
+        feature = "std-datetime",
+        feature = "std-dirs",
+        feature = "std-fs",
+        feature = "std-ipc",
+        feature = "std-luau",
+        feature = "std-net",
+        feature = "std-notify",
+        feature = "std-process",
+        feature = "std-random",
+        feature = "std-regex",
+        feature = "std-roblox",
+        feature = "std-serde",
+        feature = "std-serial",
+        feature = "std-stdio",
+        feature = "std-task",
+        feature = "std-test",
+        feature = "std-tray",
+        feature = "std-units",
+        feature = "std-uuid",
+    ))]
+    pub async fn fuzz(
+        &mut self,
+        script_name: impl AsRef<str>,
+        script_contents: impl AsRef<[u8]>,
+        options: FuzzOptions,
+        on_event: impl FnMut(FuzzEvent) + 'static,
+    ) -> RuntimeResult<FuzzReport> {
+        let lua = self.inner.lua();
+        let sched = self.inner.scheduler();
+
+        let callback = self.error_callback.clone();
+        self.inner.scheduler().set_error_callback(move |e| {
+            let e = RuntimeError::from(e);
+            match &callback {
+                Some(callback) => callback(e),
+                None => eprintln!("{e}"),
+            }
+        });
+
+        lune_std::enable_coverage(lua);
+        let script = lua
+            .load(script_contents.as_ref())
+            .set_name(script_name.as_ref())
+            .into_function()?;
+        lune_std::track_coverage(lua, script_name.as_ref(), &script)?;
+
+        let fuzzer = Rc::new(RefCell::new(Fuzzer::new(options, Box::new(on_event))));
+        let primed = Rc::new(RefCell::new(false));
+
+        let next_fuzzer = Rc::clone(&fuzzer);
+        let next_input = lua.create_function(move |lua, (): ()| {
+            let mut fuzzer = next_fuzzer.borrow_mut();
+            if !primed.replace(true) {
+                fuzzer.prime(lune_std::collect_coverage(lua));
+            }
+            match fuzzer.next_input() {
+                Some(input) => Ok(LuaValue::UserData(lua.create_buffer(input)?)),
+                None => Ok(LuaValue::Nil),
+            }
+        })?;
+
+        let report_fuzzer = Rc::clone(&fuzzer);
+        let report = lua.create_function(move |lua, (ok, err): (bool, LuaValue)| {
+            let error = if ok {
+                None
+            } else {
+                Some(match err {
+                    LuaValue::Error(e) => RuntimeError::from(e).to_string(),
+                    LuaValue::String(s) => s.to_string_lossy().to_string(),
+                    value => format!("{value:?}"),
+                })
+            };
+            report_fuzzer
+                .borrow_mut()
+                .report(lune_std::collect_coverage(lua), error);
+            Ok(())
+        })?;
+
+        let main = lua.load(FUZZ_IMPL_LUA).set_name("fuzz").into_function()?;
+        let main_id = sched.push_thread_back(main, (script, next_input, report))?;
+        sched.run().await;
+
+        if let Some(Ok(values)) = sched.get_thread_result(main_id) {
+            let mut values = values.into_iter();
+            if let (Some(LuaValue::Boolean(false)), Some(err)) = (values.next(), values.next()) {
+                return Err(match err {
+                    LuaValue::Error(e) => RuntimeError::from(e),
+                    value => RuntimeError::from(LuaError::runtime(
+                        lua.coerce_string(value)?.map_or_else(
+                            || "Unknown error".to_string(),
+                            |s| s.to_string_lossy().to_string(),
+                        ),
+                    )),
+                });
+            }
+        }
+
+        let report = fuzzer.borrow_mut().finish();
+        Ok(report)
+    }
+}
+
+/**
+    Runs callbacks added using `process.onExit` on the scheduler, until they and any
+    threads they spawn have completed, and returns the exit code that should be used.
+
+    The exit code stays the same, unless a callback errors, which makes it
+    FAILURE, or a callback calls `process.exit` to set a different one.
+*/
+#[cfg(feature = "std-process")]
+async fn run_exit_callbacks(
+    lua: &Lua,
+    sched: &Scheduler<'_>,
+    got_any_error: &AtomicBool,
+    exit_code: ExitCode,
+) -> RuntimeResult<ExitCode> {
+    let callbacks = lune_std::take_exit_callbacks(lua)?;
+    if callbacks.is_empty() {
+        return Ok(exit_code);
+    }
+
+    got_any_error.store(false, Ordering::SeqCst);
+    let ids = callbacks
+        .into_iter()
+        .map(|callback| sched.push_thread_back(callback, ()))
+        .collect::<LuaResult<Vec<_>>>()?;
+    sched.run().await;
+    for id in ids {
+        let _ = sched.get_thread_result(id);
+    }
+
+    // Callbacks added while exiting are never called, and should not be left for the next run
+    lune_std::take_exit_callbacks(lua)?;
+
+    Ok(sched.take_exit_code().unwrap_or({
+        if got_any_error.load(Ordering::SeqCst) {
+            ExitCode::FAILURE
+        } else {
+            exit_code
+        }
+    }))
+}
+
+/**
+    Waits for the script thread with the given id to complete, and if it returned
+    a table with a `main` function, calls that in a new thread and returns its results.
+
+    The main function is given the script arguments, or the arguments parsed by
+    the args parser in the `args` field of the returned table, if there is one.
+*/
+async fn call_main_function(lua: &Lua, script_id: ThreadId) -> LuaResult<LuaMultiValue> {
+    lua.wait_for_thread(script_id).await;
+    let Some(Ok(values)) = lua.get_thread_result(script_id) else {
+        return Ok(LuaMultiValue::new());
+    };
+    let Some(LuaValue::Table(exports)) = values.into_iter().next() else {
+        return Ok(LuaMultiValue::new());
+    };
+    let LuaValue::Function(main) = exports.get("main")? else {
+        return Ok(LuaMultiValue::new());
+    };
+
+    let args = match exports.get::<_, LuaValue>("args")? {
+        #[cfg(feature = "std-args")]
+        LuaValue::UserData(parser) if parser.is::<lune_std::ArgsParser>() => {
+            parser.call_async_method("parse", ()).await?
+        }
+        _ => {
+            let args = lua
+                .app_data_ref::<Vec<String>>()
+                .map(|args| args.clone())
+                .unwrap_or_default();
+            LuaValue::Table(lua.create_sequence_from(args)?)
+        }
+    };
+
+    // NOTE: The main function is pushed to the front, so that it
+    // runs before any threads that the script itself deferred
+    let main_id = lua.push_thread_front(main, args)?;
+    lua.track_thread(main_id);
+    lua.wait_for_thread(main_id).await;
+    match lua.get_thread_result(main_id) {
+        Some(Ok(values)) => Ok(values),
+        // NOTE: Errors in the main function were already reported by the scheduler
+        _ => Ok(LuaMultiValue::new()),
+    }
 }
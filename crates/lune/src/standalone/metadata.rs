@@ -5,6 +5,8 @@ use mlua::Compiler as LuaCompiler;
 use once_cell::sync::Lazy;
 use tokio::fs;
 
+use lune_utils::fmt::SourceMap;
+
 pub static CURRENT_EXE: Lazy<PathBuf> =
     Lazy::new(|| env::current_exe().expect("failed to get current exe"));
 const MAGIC: &[u8; 8] = b"cr3sc3nt";
@@ -30,6 +32,8 @@ const MAGIC: &[u8; 8] = b"cr3sc3nt";
 #[derive(Debug, Clone)]
 pub struct Metadata {
     pub bytecode: Vec<u8>,
+    pub name: String,
+    pub source_map: Option<SourceMap>,
 }
 
 impl Metadata {
@@ -46,10 +50,14 @@ impl Metadata {
     }
 
     /**
-        Creates a patched standalone binary from the given script contents.
+        Creates a patched standalone binary from the given script name and contents.
+
+        The name is used for the script in stack traces, along with any source map
+        in the script contents, since comments are not kept when compiling it.
     */
     pub async fn create_env_patched_bin(
         base_exe_path: PathBuf,
+        script_name: impl Into<String>,
         script_contents: impl Into<Vec<u8>>,
     ) -> Result<Vec<u8>> {
         let compiler = LuaCompiler::new()
@@ -60,10 +68,18 @@ impl Metadata {
         let mut patched_bin = fs::read(base_exe_path).await?;
 
         // Compile luau input into bytecode
-        let bytecode = compiler.compile(script_contents.into());
+        let script_contents = script_contents.into();
+        let source_map = std::str::from_utf8(&script_contents)
+            .ok()
+            .and_then(SourceMap::from_source);
+        let bytecode = compiler.compile(script_contents);
 
         // Append the bytecode / metadata to the end
-        let meta = Self { bytecode };
+        let meta = Self {
+            bytecode,
+            name: script_name.into(),
+            source_map,
+        };
         patched_bin.extend_from_slice(&meta.to_bytes());
 
         Ok(patched_bin)
@@ -74,28 +90,53 @@ impl Metadata {
     */
     pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self> {
         let bytes = bytes.as_ref();
-        if bytes.len() < 16 || !bytes.ends_with(MAGIC) {
+        if bytes.len() < 32 || !bytes.ends_with(MAGIC) {
             bail!("not a standalone binary")
         }
 
-        // Extract bytecode size
-        let bytecode_size_bytes = &bytes[bytes.len() - 16..bytes.len() - 8];
-        let bytecode_size =
-            usize::try_from(u64::from_be_bytes(bytecode_size_bytes.try_into().unwrap()))?;
-
-        // Extract bytecode
-        let bytecode = bytes[bytes.len() - 16 - bytecode_size..].to_vec();
-
-        Ok(Self { bytecode })
+        // Extract sizes of the bytecode, name, and source map
+        let read_size = |offset: usize| {
+            let end = bytes.len() - 8 - offset * 8;
+            let size_bytes: [u8; 8] = bytes[end - 8..end].try_into().unwrap();
+            usize::try_from(u64::from_be_bytes(size_bytes))
+        };
+        let source_map_size = read_size(0)?;
+        let name_size = read_size(1)?;
+        let bytecode_size = read_size(2)?;
+
+        // Extract bytecode, name, and source map, which come right before the sizes
+        let source_map_end = bytes.len() - 32;
+        let name_end = source_map_end - source_map_size;
+        let bytecode_end = name_end - name_size;
+        let bytecode = bytes[bytecode_end - bytecode_size..bytecode_end].to_vec();
+        let name = String::from_utf8(bytes[bytecode_end..name_end].to_vec())?;
+        let source_map = std::str::from_utf8(&bytes[name_end..source_map_end])
+            .ok()
+            .and_then(SourceMap::from_source);
+
+        Ok(Self {
+            bytecode,
+            name,
+            source_map,
+        })
     }
 
     /**
         Writes the metadata chunk to a byte vector, to later bet read using `from_bytes`.
     */
     pub fn to_bytes(&self) -> Vec<u8> {
+        let source_map = self
+            .source_map
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
         let mut bytes = Vec::new();
         bytes.extend_from_slice(&self.bytecode);
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(source_map.as_bytes());
         bytes.extend_from_slice(&(self.bytecode.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.name.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&(source_map.len() as u64).to_be_bytes());
         bytes.extend_from_slice(MAGIC);
         bytes
     }
@@ -29,9 +29,14 @@ pub async fn run(patched_bin: impl AsRef<[u8]>) -> Result<ExitCode> {
     let args = env::args().skip(1).collect::<Vec<_>>();
     let meta = Metadata::from_bytes(patched_bin).expect("must be a standalone binary");
 
+    // NOTE: Bytecode has no comments, so any source map is stored separately
+    if let Some(source_map) = meta.source_map {
+        source_map.register(&meta.name);
+    }
+
     let result = Runtime::new()
         .with_args(args)
-        .run("STANDALONE", meta.bytecode)
+        .run(&meta.name, meta.bytecode)
         .await;
 
     Ok(match result {
@@ -13,62 +13,84 @@ use crate::Runtime;
 
 const ARGS: &[&str] = &["Foo", "Bar"];
 
+/**
+    Runs the test script with the given name, from inside of the tests directory,
+    after giving the runtime to the configure function for any test specific setup.
+*/
+async fn run_test_with(name: &str, configure: impl FnOnce(Runtime) -> Runtime) -> Result<ExitCode> {
+    // We need to change the current directory to the workspace root since
+    // we are in a sub-crate and tests would run relative to the sub-crate
+    let workspace_dir_str = format!("{}/../../", env!("CARGO_MANIFEST_DIR"));
+    let workspace_dir = clean_path_and_make_absolute(PathBuf::from(workspace_dir_str));
+    set_current_dir(&workspace_dir)?;
+
+    // Disable styling for stdout and stderr since
+    // some tests rely on output not being styled
+    set_colors_enabled(false);
+    set_colors_enabled_stderr(false);
+
+    // The rest of the test logic can continue as normal
+    let full_name = format!("{}/tests/{}.luau", workspace_dir.display(), name);
+    let script = read_to_string(&full_name).await?;
+    let mut lune = configure(
+        Runtime::new().with_args(ARGS.iter().map(ToString::to_string).collect::<Vec<_>>()),
+    );
+    let script_name = full_name
+        .trim_end_matches(".luau")
+        .trim_end_matches(".lua")
+        .to_string();
+    let exit_code = lune.run(&script_name, &script).await?;
+    Ok(exit_code)
+}
+
 macro_rules! create_tests {
-    ($($name:ident: $value:expr,)*) => { $(
+    ($($name:ident: $value:expr,)*) => {
+        create_tests! { $($name: $value => |lune| lune,)* }
+    };
+    ($($name:ident: $value:expr => $configure:expr,)*) => { $(
         #[tokio::test(flavor = "multi_thread")]
         async fn $name() -> Result<ExitCode> {
-            // We need to change the current directory to the workspace root since
-            // we are in a sub-crate and tests would run relative to the sub-crate
-            let workspace_dir_str = format!("{}/../../", env!("CARGO_MANIFEST_DIR"));
-            let workspace_dir = clean_path_and_make_absolute(PathBuf::from(workspace_dir_str));
-            set_current_dir(&workspace_dir)?;
-
-            // Disable styling for stdout and stderr since
-            // some tests rely on output not being styled
-            set_colors_enabled(false);
-            set_colors_enabled_stderr(false);
-
-            // The rest of the test logic can continue as normal
-            let full_name = format!("{}/tests/{}.luau", workspace_dir.display(), $value);
-            let script = read_to_string(&full_name).await?;
-            let mut lune = Runtime::new().with_args(
-                ARGS
-                    .clone()
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<_>>()
-            );
-            let script_name = full_name
-				.trim_end_matches(".luau")
-				.trim_end_matches(".lua")
-				.to_string();
-            let exit_code = lune.run(&script_name, &script).await?;
-            Ok(exit_code)
+            run_test_with($value, $configure).await
         }
-    )* }
+    )* };
 }
 
 #[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
     feature = "std-datetime",
+    feature = "std-dirs",
     feature = "std-fs",
+    feature = "std-ipc",
     feature = "std-luau",
     feature = "std-net",
+    feature = "std-notify",
     feature = "std-process",
+    feature = "std-random",
     feature = "std-regex",
     feature = "std-roblox",
     feature = "std-serde",
+    feature = "std-serial",
     feature = "std-stdio",
     feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
 ))]
 create_tests! {
     require_aliases: "require/tests/aliases",
     require_async: "require/tests/async",
+    require_async_blocking: "require/tests/async_blocking",
     require_async_concurrent: "require/tests/async_concurrent",
     require_async_sequential: "require/tests/async_sequential",
     require_builtins: "require/tests/builtins",
     require_children: "require/tests/children",
+    require_circular: "require/tests/circular",
     require_init: "require/tests/init",
     require_invalid: "require/tests/invalid",
+    require_luaurc: "require/tests/luaurc/aliased",
+    require_luaurc_invalid: "require/tests/luaurc/invalid/aliased",
     require_multi_ext: "require/tests/multi_ext",
     require_nested: "require/tests/nested",
     require_parents: "require/tests/parents",
@@ -79,33 +101,156 @@ create_tests! {
     global_version: "globals/_VERSION",
     global_coroutine: "globals/coroutine",
     global_error: "globals/error",
+    global_lune: "globals/lune",
     global_pcall: "globals/pcall",
     global_type: "globals/type",
     global_typeof: "globals/typeof",
     global_warn: "globals/warn",
 }
 
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+#[tokio::test(flavor = "multi_thread")]
+async fn require_virtual() -> Result<ExitCode> {
+    // NOTE: Virtual modules are only given to this test, and the one with source code
+    // requires a module from the tests directory, relative to the current directory
+    const GREETING_SOURCE: &str = r#"
+local config = require("@virtual/config")
+return {
+    greeting = `Hello, {config.name}!`,
+    module = require("./tests/require/tests/modules/module"),
+}
+"#;
+    run_test_with("require/tests/virtual", |lune| {
+        lune.with_virtual_module(
+            "config",
+            crate::VirtualModule::Value(serde_json::json!({
+                "name": "Lune",
+                "list": [1, 2, 3],
+                "nested": { "enabled": true },
+            })),
+        )
+        .with_virtual_module(
+            "greeting",
+            crate::VirtualModule::Source(GREETING_SOURCE.to_string()),
+        )
+    })
+    .await
+}
+
+#[cfg(any(
+    feature = "std-args",
+    feature = "std-config",
+    feature = "std-datetime",
+    feature = "std-dirs",
+    feature = "std-fs",
+    feature = "std-ipc",
+    feature = "std-luau",
+    feature = "std-net",
+    feature = "std-notify",
+    feature = "std-process",
+    feature = "std-random",
+    feature = "std-regex",
+    feature = "std-roblox",
+    feature = "std-serde",
+    feature = "std-serial",
+    feature = "std-stdio",
+    feature = "std-task",
+    feature = "std-test",
+    feature = "std-tray",
+    feature = "std-units",
+    feature = "std-uuid",
+))]
+#[tokio::test(flavor = "multi_thread")]
+async fn require_resolvers() -> Result<ExitCode> {
+    // NOTE: Resolvers registered from Rust are only given to this test,
+    // the rest of the resolvers being tested are registered from Lua
+    run_test_with("require/tests/resolvers", |lune| {
+        lune.with_require_resolver("rust://", |_, path, _| {
+            Ok(Some(crate::ResolvedModule {
+                name: path.to_string(),
+                source: format!("return {path:?}"),
+            }))
+        })
+    })
+    .await
+}
+
+#[cfg(feature = "std-args")]
+create_tests! {
+    args_help: "args/help",
+    args_parse: "args/parse",
+}
+
+#[cfg(feature = "std-config")]
+create_tests! {
+    config_load: "config/load",
+}
+
 #[cfg(feature = "std-datetime")]
 create_tests! {
+    datetime_arithmetic: "datetime/arithmetic",
     datetime_format_local_time: "datetime/formatLocalTime",
     datetime_format_universal_time: "datetime/formatUniversalTime",
+    datetime_from_format: "datetime/fromFormat",
     datetime_from_iso_date: "datetime/fromIsoDate",
     datetime_from_local_time: "datetime/fromLocalTime",
     datetime_from_universal_time: "datetime/fromUniversalTime",
     datetime_from_unix_timestamp: "datetime/fromUnixTimestamp",
     datetime_now: "datetime/now",
+    datetime_time_zones: "datetime/timeZones",
     datetime_to_iso_date: "datetime/toIsoDate",
     datetime_to_local_time: "datetime/toLocalTime",
     datetime_to_universal_time: "datetime/toUniversalTime",
 }
 
+#[cfg(feature = "std-dirs")]
+create_tests! {
+    dirs_app: "dirs/app",
+    dirs_base: "dirs/base",
+}
+
 #[cfg(feature = "std-fs")]
 create_tests! {
+    fs_chunks: "fs/chunks",
     fs_files: "fs/files",
+    fs_interactive: "fs/interactive",
     fs_copy: "fs/copy",
     fs_dirs: "fs/dirs",
     fs_metadata: "fs/metadata",
     fs_move: "fs/move",
+    fs_open: "fs/open",
+    fs_permissions: "fs/permissions",
+    fs_temp: "fs/temp",
+    fs_watch: "fs/watch",
+}
+
+#[cfg(feature = "std-ipc")]
+create_tests! {
+    ipc_channels: "ipc/channels",
+    ipc_messages: "ipc/messages",
+    ipc_shared_buffer: "ipc/sharedBuffer",
 }
 
 #[cfg(feature = "std-luau")]
@@ -120,35 +265,83 @@ create_tests! {
 create_tests! {
     net_request_codes: "net/request/codes",
     net_request_compression: "net/request/compression",
+    net_request_cookies: "net/request/cookies",
+    net_request_decoding: "net/request/decoding",
+    net_request_encoding: "net/request/encoding",
+    net_request_errors: "net/request/errors",
     net_request_methods: "net/request/methods",
+    net_request_multipart: "net/request/multipart",
+    net_request_proxy: "net/request/proxy",
     net_request_query: "net/request/query",
     net_request_redirect: "net/request/redirect",
+    net_request_retry: "net/request/retry",
+    net_request_stream: "net/request/stream",
+    net_request_tls: "net/request/tls",
+    net_resolve: "net/resolve",
     net_url_encode: "net/url/encode",
     net_url_decode: "net/url/decode",
+    net_serve_body_limits: "net/serve/bodyLimits",
+    net_serve_limits: "net/serve/limits",
     net_serve_requests: "net/serve/requests",
+    net_serve_shutdown: "net/serve/shutdown",
+    net_serve_sse: "net/serve/sse",
     net_serve_websockets: "net/serve/websockets",
     net_socket_basic: "net/socket/basic",
+    net_socket_compression: "net/socket/compression",
     net_socket_wss: "net/socket/wss",
     net_socket_wss_rw: "net/socket/wss_rw",
+    net_udp_basic: "net/udp/basic",
 }
 
 #[cfg(feature = "std-process")]
 create_tests! {
     process_args: "process/args",
+    process_create: "process/create",
     process_cwd: "process/cwd",
+    process_detached: "process/detached",
     process_env: "process/env",
+    process_exec: "process/exec",
     process_exit: "process/exit",
+    process_on_exit: "process/on_exit",
+    process_single_instance: "process/single_instance",
     process_spawn_async: "process/spawn/async",
     process_spawn_basic: "process/spawn/basic",
     process_spawn_cwd: "process/spawn/cwd",
     process_spawn_no_panic: "process/spawn/no_panic",
+    process_spawn_pty: "process/spawn/pty",
     process_spawn_shell: "process/spawn/shell",
     process_spawn_stdin: "process/spawn/stdin",
+    process_spawn_stdin_handle: "process/spawn/stdin_handle",
     process_spawn_stdio: "process/spawn/stdio",
+    process_spawn_stream: "process/spawn/stream",
+    process_supervise: "process/supervise",
+}
+
+#[cfg(feature = "std-process")]
+create_tests! {
+    process_main: "process/main" => with_main_function,
+}
+
+#[cfg(all(feature = "std-args", feature = "std-process"))]
+create_tests! {
+    process_main_args: "process/main_args" => with_main_function,
+}
+
+// Exported main functions are only called for entry scripts, the same as when using `lune run`
+#[cfg(feature = "std-process")]
+fn with_main_function(lune: Runtime) -> Runtime {
+    lune.with_main_function(true)
+}
+
+#[cfg(feature = "std-random")]
+create_tests! {
+    random_bytes: "random/bytes",
+    random_int: "random/int",
 }
 
 #[cfg(feature = "std-regex")]
 create_tests! {
+    regex_find_all: "regex/findAll",
     regex_general: "regex/general",
     regex_metamethods: "regex/metamethods",
     regex_replace: "regex/replace",
@@ -192,6 +385,7 @@ create_tests! {
 
     roblox_instance_classes_data_model: "roblox/instance/classes/DataModel",
     roblox_instance_classes_workspace: "roblox/instance/classes/Workspace",
+    roblox_instance_classes_keyframe_sequence: "roblox/instance/classes/KeyframeSequence",
     roblox_instance_classes_terrain: "roblox/instance/classes/Terrain",
 
     roblox_instance_custom_async: "roblox/instance/custom/async",
@@ -215,7 +409,11 @@ create_tests! {
     roblox_instance_methods_is_ancestor_of: "roblox/instance/methods/IsAncestorOf",
     roblox_instance_methods_is_descendant_of: "roblox/instance/methods/IsDescendantOf",
 
+    roblox_misc_diff: "roblox/misc/diff",
+    roblox_misc_download_asset: "roblox/misc/downloadAsset",
+    roblox_misc_scripts: "roblox/misc/scripts",
     roblox_misc_typeof: "roblox/misc/typeof",
+    roblox_misc_upgrade_instances: "roblox/misc/upgradeInstances",
 
     roblox_reflection_class: "roblox/reflection/class",
     roblox_reflection_database: "roblox/reflection/database",
@@ -225,23 +423,49 @@ create_tests! {
 
 #[cfg(feature = "std-serde")]
 create_tests! {
+    serde_base64_roundtrip: "serde/base64/roundtrip",
+    serde_cbor_encode: "serde/cbor/encode",
+    serde_cbor_roundtrip: "serde/cbor/roundtrip",
     serde_compression_files: "serde/compression/files",
     serde_compression_roundtrip: "serde/compression/roundtrip",
+    serde_compression_stream: "serde/compression/stream",
+    serde_hex_roundtrip: "serde/hex/roundtrip",
     serde_json_decode: "serde/json/decode",
     serde_json_encode: "serde/json/encode",
+    serde_msgpack_encode: "serde/msgpack/encode",
+    serde_msgpack_roundtrip: "serde/msgpack/roundtrip",
     serde_toml_decode: "serde/toml/decode",
     serde_toml_encode: "serde/toml/encode",
+    serde_toml_update: "serde/toml/update",
+    serde_yaml_decode: "serde/yaml/decode",
+    serde_yaml_encode: "serde/yaml/encode",
     serde_hashing_hash: "serde/hashing/hash",
     serde_hashing_hmac: "serde/hashing/hmac",
+    serde_hashing_stream: "serde/hashing/stream",
+    serde_signing_ed25519: "serde/signing/ed25519",
+    serde_signing_hmac: "serde/signing/hmac",
+}
+
+#[cfg(feature = "std-serial")]
+create_tests! {
+    serial_list: "serial/list",
+    serial_open: "serial/open",
 }
 
 #[cfg(feature = "std-stdio")]
 create_tests! {
     stdio_format: "stdio/format",
+    stdio_formatters: "stdio/formatters",
     stdio_color: "stdio/color",
     stdio_style: "stdio/style",
+    stdio_styled: "stdio/styled",
+    stdio_table: "stdio/table",
     stdio_write: "stdio/write",
     stdio_ewrite: "stdio/ewrite",
+    stdio_link: "stdio/link",
+    stdio_progress: "stdio/progress",
+    stdio_terminal: "stdio/terminal",
+    stdio_validators: "stdio/validators",
 }
 
 #[cfg(feature = "std-task")]
@@ -249,6 +473,425 @@ create_tests! {
     task_cancel: "task/cancel",
     task_defer: "task/defer",
     task_delay: "task/delay",
+    task_on_idle: "task/on_idle",
     task_spawn: "task/spawn",
+    task_stats: "task/stats",
     task_wait: "task/wait",
+    task_wait_all: "task/wait_all",
+}
+
+#[cfg(feature = "std-test")]
+create_tests! {
+    test_expect: "test/expect" => with_test_mode,
+    test_properties: "test/properties" => with_test_mode,
+    test_snapshots: "test/snapshots" => with_test_mode,
+    test_spies: "test/spies" => with_test_mode,
+}
+
+// Scripts for the test library run in test mode, the same as when using `lune test`
+#[cfg(feature = "std-test")]
+fn with_test_mode(lune: Runtime) -> Runtime {
+    lune.with_test_mode(true)
+}
+
+#[cfg(feature = "std-notify")]
+create_tests! {
+    notify_send: "notify/send",
+}
+
+#[cfg(feature = "std-tray")]
+create_tests! {
+    tray_create: "tray/create",
+}
+
+#[cfg(feature = "std-units")]
+create_tests! {
+    units_duration: "units/duration",
+    units_size: "units/size",
+}
+
+#[cfg(feature = "std-uuid")]
+create_tests! {
+    uuid_generate: "uuid/generate",
+    uuid_parse: "uuid/parse",
+}
+
+#[cfg(feature = "std")]
+#[tokio::test(flavor = "multi_thread")]
+async fn fuzz_finds_crash() -> Result<()> {
+    let full_name = format!(
+        "{}/../../tests/fuzz/target.luau",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let script = read_to_string(&full_name).await?;
+
+    // Each new prefix reaches a new line, so coverage should lead the fuzzer
+    // to the crash in far fewer runs than random inputs would need
+    let found = std::rc::Rc::new(std::cell::Cell::new(0));
+    let found_inner = std::rc::Rc::clone(&found);
+    let report = Runtime::new()
+        .fuzz(
+            "tests/fuzz/target",
+            &script,
+            crate::FuzzOptions {
+                runs: Some(100_000),
+                seed: Some(1),
+                ..Default::default()
+            },
+            move |event| {
+                if let crate::FuzzEvent::NewInput { .. } = event {
+                    found_inner.set(found_inner.get() + 1);
+                }
+            },
+        )
+        .await?;
+
+    let crash = report.crash.expect("fuzzer should find the crash");
+    assert!(crash.input.starts_with(b"[!]"));
+    assert!(crash.error.contains("Found the crash"));
+    assert!(found.get() >= 2, "inputs reaching new code should be found");
+    assert!(report.stats.runs < 100_000);
+
+    // Scripts without a fuzz function should error instead of running
+    let result = Runtime::new()
+        .fuzz(
+            "missing",
+            "return {}",
+            crate::FuzzOptions::default(),
+            |_| {},
+        )
+        .await;
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[cfg(feature = "std-task")]
+#[tokio::test(flavor = "multi_thread")]
+async fn process_main_exit_code() -> Result<()> {
+    // Errors in other threads should not be hidden by the exit code from main
+    let source = "local task = require('@lune/task')\n\
+        task.spawn(error, 'spawned thread error')\n\
+        return { main = function() return 0 end }\n";
+    let mut rt = Runtime::new()
+        .with_main_function(true)
+        .with_error_callback(|_| {});
+    assert_eq!(rt.run("main_errors", source).await?, ExitCode::FAILURE);
+
+    // Exported main functions should not be called unless enabled
+    let source = "return { main = function() error('main should not be called') end }";
+    let mut rt = Runtime::new();
+    assert_eq!(rt.run("main_disabled", source).await?, ExitCode::SUCCESS);
+
+    Ok(())
+}
+
+#[cfg(feature = "std-task")]
+#[tokio::test(flavor = "multi_thread")]
+async fn task_wait_low_latency() -> Result<ExitCode> {
+    run_test_with("task/wait_low_latency", |lune| lune.with_low_latency(true)).await
+}
+
+#[cfg(feature = "std-task")]
+#[tokio::test]
+async fn task_wait_low_latency_yields() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // NOTE: This runs on a single threaded runtime, where other tasks only get
+    // to run when the scheduler yields to tokio, which it must also do when spinning
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_task = tokio::spawn({
+        let counter = Arc::clone(&counter);
+        async move {
+            loop {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+
+    let mut rt = Runtime::new().with_low_latency(true);
+    let lua = rt.lua();
+    let get_counter = lua.create_function(move |_, ()| Ok(counter.load(Ordering::SeqCst)))?;
+    lua.globals().set("getCounter", get_counter)?;
+    drop(lua);
+
+    let source = "local before = getCounter()\n\
+        require('@lune/task').wait(0.001)\n\
+        assert(getCounter() > before, 'other tasks should run while spinning')\n";
+    let exit_code = rt.run("low_latency_yields", source).await;
+    counter_task.abort();
+
+    assert_eq!(exit_code?, ExitCode::SUCCESS);
+    Ok(())
+}
+
+#[cfg(feature = "std-fs")]
+#[tokio::test(flavor = "multi_thread")]
+async fn fs_root() -> Result<()> {
+    // The root is created outside of the workspace, with symlinks
+    // inside of it that point back out, which should not be followed,
+    // also when nested inside of directories that are copied or moved
+    let root = std::env::temp_dir().join(format!("lune-fs-root-{}", std::process::id()));
+    let outside = root.with_extension("outside");
+    std::fs::create_dir_all(&root)?;
+    std::fs::create_dir_all(&outside)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::symlink;
+        symlink(&outside, root.join("escape"))?;
+        std::fs::write(outside.join("secret.txt"), "secret")?;
+        std::fs::create_dir_all(root.join("linked"))?;
+        symlink(outside.join("secret.txt"), root.join("linked/secret.txt"))?;
+        std::fs::create_dir_all(root.join("dst"))?;
+        symlink(&outside, root.join("dst/sub"))?;
+        std::fs::create_dir_all(root.join("internal_target"))?;
+        std::fs::write(root.join("internal_target/file.txt"), "internal")?;
+        std::fs::create_dir_all(root.join("internal"))?;
+        symlink(root.join("internal_target"), root.join("internal/link"))?;
+        std::fs::create_dir_all(root.join("cycle"))?;
+        symlink(root.join("cycle"), root.join("cycle/self"))?;
+    }
+
+    let exit_code = run_test_with("fs/root", |lune| lune.with_fs_root(&root)).await;
+
+    let written_outside = outside.join("file.txt").exists() || outside.join("x.txt").exists();
+    std::fs::remove_dir_all(&root)?;
+    std::fs::remove_dir_all(&outside)?;
+
+    assert_eq!(exit_code?, ExitCode::SUCCESS);
+    assert!(
+        !written_outside,
+        "nothing should be written outside of the root"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "std-net")]
+#[tokio::test(flavor = "multi_thread")]
+async fn net_host_policy() -> Result<ExitCode> {
+    let policy =
+        crate::HostPolicy::new(["127.0.0.1:8100", "*.example.com"], ["blocked.example.com"])
+            .map_err(anyhow::Error::msg)?;
+    run_test_with("net/policy", |lune| lune.with_host_policy(policy)).await
+}
+
+#[cfg(feature = "std-net")]
+#[tokio::test(flavor = "multi_thread")]
+async fn net_host_policy_addresses() -> Result<ExitCode> {
+    let policy = crate::HostPolicy::new(Vec::<String>::new(), ["127.0.0.1", "::1"])
+        .map_err(anyhow::Error::msg)?;
+    let overrides = crate::HostOverrides::parse_hosts_file("127.0.0.1 internal.example.com\n")
+        .map_err(anyhow::Error::msg)?;
+
+    run_test_with("net/policyAddresses", |lune| {
+        lune.with_host_policy(policy).with_host_overrides(overrides)
+    })
+    .await
+}
+
+#[cfg(feature = "std-net")]
+#[tokio::test(flavor = "multi_thread")]
+async fn net_host_overrides() -> Result<ExitCode> {
+    let overrides = crate::HostOverrides::parse_hosts_file(
+        "# Local fixtures\n127.0.0.1  api.example.com\n::1 ipv6.example.com # IPv6 only\n",
+    )
+    .map_err(anyhow::Error::msg)?;
+
+    run_test_with("net/hosts", |lune| lune.with_host_overrides(overrides)).await
+}
+
+/**
+    A minimal SOCKS5 proxy for testing, which connects to localhost for any
+    target it is asked to connect to, and records the targets it was sent.
+
+    Username and password authentication is used if the client offers it,
+    and only the credentials `lune` and `secret` are accepted.
+*/
+#[cfg(feature = "std-net")]
+async fn serve_socks_proxy(
+    port: u16,
+    targets: std::sync::Arc<std::sync::Mutex<Vec<(u8, String, u16)>>>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    use tokio::io::{copy_bidirectional, AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn handle(
+        mut client: TcpStream,
+        targets: &std::sync::Mutex<Vec<(u8, String, u16)>>,
+    ) -> std::io::Result<()> {
+        let mut head = [0; 2];
+        client.read_exact(&mut head).await?;
+        let mut methods = vec![0; usize::from(head[1])];
+        client.read_exact(&mut methods).await?;
+
+        if methods.contains(&2) {
+            client.write_all(&[5, 2]).await?;
+            let field = |len: u8| vec![0; usize::from(len)];
+            let mut version = [0; 2];
+            client.read_exact(&mut version).await?;
+            let mut username = field(version[1]);
+            client.read_exact(&mut username).await?;
+            let mut password = field(client.read_u8().await?);
+            client.read_exact(&mut password).await?;
+            if username != b"lune" || password != b"secret" {
+                return client.write_all(&[1, 1]).await;
+            }
+            client.write_all(&[1, 0]).await?;
+        } else {
+            client.write_all(&[5, 0]).await?;
+        }
+
+        let mut request = [0; 4];
+        client.read_exact(&mut request).await?;
+        let host = match request[3] {
+            1 => {
+                let mut addr = [0; 4];
+                client.read_exact(&mut addr).await?;
+                std::net::Ipv4Addr::from(addr).to_string()
+            }
+            3 => {
+                let mut name = vec![0; usize::from(client.read_u8().await?)];
+                client.read_exact(&mut name).await?;
+                String::from_utf8_lossy(&name).into_owned()
+            }
+            _ => {
+                let mut addr = [0; 16];
+                client.read_exact(&mut addr).await?;
+                std::net::Ipv6Addr::from(addr).to_string()
+            }
+        };
+        let port = client.read_u16().await?;
+        targets.lock().unwrap().push((request[3], host, port));
+
+        let mut upstream = TcpStream::connect(("127.0.0.1", port)).await?;
+        client.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).await?;
+        copy_bidirectional(&mut client, &mut upstream).await?;
+        Ok(())
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    Ok(tokio::spawn(async move {
+        while let Ok((client, _)) = listener.accept().await {
+            let targets = std::sync::Arc::clone(&targets);
+            tokio::spawn(async move { handle(client, &targets).await.ok() });
+        }
+    }))
+}
+
+#[cfg(feature = "std-net")]
+#[tokio::test(flavor = "multi_thread")]
+async fn net_request_socks() -> Result<()> {
+    let targets = std::sync::Arc::default();
+    let proxy = serve_socks_proxy(8104, std::sync::Arc::clone(&targets)).await?;
+    let exit_code = run_test_with("net/request/socks", |lune| lune).await;
+    proxy.abort();
+
+    assert_eq!(exit_code?, ExitCode::SUCCESS);
+
+    // Hostnames should only be sent to the proxy with socks5h,
+    // including for tunneled HTTPS requests, with socks5 they
+    // should instead be resolved to addresses before being sent
+    let targets = targets.lock().unwrap();
+    let with_hostnames = targets
+        .iter()
+        .filter(|(kind, host, port)| *kind == 3 && host == "lune.invalid" && *port == 8105);
+    assert_eq!(with_hostnames.count(), 3, "unexpected targets {targets:?}");
+    assert!(
+        targets
+            .iter()
+            .all(|(kind, host, _)| *kind == 3 || host != "localhost"),
+        "unexpected targets {targets:?}"
+    );
+    Ok(())
+}
+
+/**
+    Runs one of the bytecode cache tests, which are the only tests that use
+    the bytecode cache, each one with its own cache directory and maximum size.
+*/
+#[cfg(feature = "std-fs")]
+async fn run_bytecode_cache_test(name: &str, cache_dir: &str, max_size: u64) -> Result<ExitCode> {
+    // NOTE: Tests run from inside of the workspace root, same as the cache directory
+    run_test_with(&format!("require/tests/{name}"), |lune| {
+        lune.with_bytecode_cache(PathBuf::from("bin").join(cache_dir))
+            .with_bytecode_cache_max_size(max_size)
+    })
+    .await
+}
+
+#[cfg(feature = "std-fs")]
+#[tokio::test(flavor = "multi_thread")]
+async fn require_bytecode_cache() -> Result<ExitCode> {
+    run_bytecode_cache_test("bytecode_cache", "bytecode-cache", 64 * 1024 * 1024).await
+}
+
+#[cfg(feature = "std-fs")]
+#[tokio::test(flavor = "multi_thread")]
+async fn require_bytecode_cache_eviction() -> Result<ExitCode> {
+    run_bytecode_cache_test(
+        "bytecode_cache_eviction",
+        "bytecode-cache-eviction",
+        16 * 1024,
+    )
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crash_report_on_panic() -> Result<()> {
+    use futures_util::FutureExt;
+    use mlua::prelude::*;
+
+    crate::enable_crash_reports();
+
+    // Other tests in this process may also write crash reports,
+    // so we find ours using a message that is unique to this run
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_nanos();
+    let message = format!("Forced panic for crash report {nanos}");
+
+    let mut rt = Runtime::new();
+    let lua = rt.lua();
+    let message_inner = message.clone();
+    let force_panic = lua.create_function(move |_, ()| -> LuaResult<()> {
+        panic!("{message_inner}");
+    })?;
+    lua.globals().set("forcePanic", force_panic)?;
+    drop(lua);
+
+    // The scheduler should treat the panic as fatal and resume it, instead of
+    // dropping it together with the thread and continuing as if nothing happened
+    let source = "local function inner()\n\tforcePanic()\nend\ninner()\nprint('unreachable')\n";
+    let result = std::panic::AssertUnwindSafe(rt.run("crash", source))
+        .catch_unwind()
+        .await;
+    let payload = result.expect_err("panic should be resumed by the scheduler");
+    assert_eq!(payload.downcast_ref::<String>(), Some(&message));
+
+    let suffix = format!("-{}.txt", std::process::id());
+    let mut report = None;
+    for entry in std::fs::read_dir(std::env::temp_dir())? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !name.starts_with("lune-crash-") || !name.ends_with(&suffix) {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        if contents.contains(&message) {
+            std::fs::remove_file(&path)?;
+            report = Some(contents);
+            break;
+        }
+    }
+
+    let report = report.expect("crash report should have been written");
+    assert!(report.contains(concat!("Version: ", env!("CARGO_PKG_VERSION"))));
+    assert!(report.contains("Lua traceback:\n[C]\n[string \"crash\"]:2"));
+    assert!(report.contains("[string \"crash\"]:4"));
+    assert!(report.contains("Scheduler:\nstatus Running"));
+    assert!(report.contains("Rust backtrace:"));
+
+    Ok(())
 }
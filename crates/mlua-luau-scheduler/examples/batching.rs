@@ -0,0 +1,59 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+#![allow(clippy::cargo_common_metadata)]
+
+use std::time::Duration;
+
+use async_io::{block_on, Timer};
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/batching.luau");
+
+const BATCH_SIZE: usize = 64;
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("spawn", fns.spawn)?;
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_, duration: f64| async move {
+            Timer::after(Duration::from_secs_f64(duration)).await;
+            Ok(())
+        })?,
+    )?;
+
+    // Run threads in batches instead of running each one in its own task
+    sched.set_batch_size(BATCH_SIZE);
+
+    // Load the main script into the scheduler, and keep track of the thread we spawn
+    let main = lua.load(MAIN_SCRIPT);
+    let id = sched.push_thread_front(main, ())?;
+
+    // Run until completion
+    block_on(sched.run());
+
+    // All of the threads should have completed, even when batched
+    let res = sched.get_thread_result(id).unwrap().unwrap();
+    let completed = usize::from_lua_multi(res, &lua)?;
+    assert_eq!(completed, 10_000);
+
+    Ok(())
+}
+
+#[test]
+fn test_batching() -> LuaResult<()> {
+    main()
+}
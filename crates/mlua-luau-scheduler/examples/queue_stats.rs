@@ -0,0 +1,58 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+#![allow(clippy::cargo_common_metadata)]
+
+use async_io::block_on;
+
+use mlua::prelude::*;
+use mlua_luau_scheduler::{Functions, Scheduler, TaskKind};
+
+const MAIN_SCRIPT: &str = include_str!("./lua/queue_stats.luau");
+
+pub fn main() -> LuaResult<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_target(false)
+        .without_time()
+        .init();
+
+    // Set up persistent Lua environment
+    let lua = Lua::new();
+    let sched = Scheduler::new(&lua);
+    let fns = Functions::new(&lua)?;
+
+    lua.globals().set("defer", fns.defer)?;
+    lua.globals().set("idle", fns.idle)?;
+
+    // Load the main script into the scheduler
+    let main = lua.load(MAIN_SCRIPT);
+    sched.push_thread_front(main, ())?;
+
+    // Nothing has been resumed yet, so there should be no stats
+    assert_eq!(sched.queue_stats(TaskKind::Spawned).count, 0);
+
+    // Run until completion
+    block_on(sched.run());
+
+    // Every thread should have been recorded once it left its queue
+    for (kind, count) in [
+        (TaskKind::Spawned, 1),
+        (TaskKind::Deferred, 100),
+        (TaskKind::Idle, 1),
+    ] {
+        let stats = sched.queue_stats(kind);
+        println!("{kind:<8} {stats}");
+        assert_eq!(stats.count, count);
+        assert!(stats.p50 <= stats.p90);
+        assert!(stats.p90 <= stats.p99);
+        assert!(stats.p99 <= stats.max);
+        assert!(stats.average <= stats.max);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_queue_stats() -> LuaResult<()> {
+    main()
+}
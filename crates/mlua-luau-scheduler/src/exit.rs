@@ -25,6 +25,10 @@ impl Exit {
         self.code.get()
     }
 
+    pub fn take(&self) -> Option<ExitCode> {
+        self.code.take()
+    }
+
     pub async fn listen(&self) {
         self.event.listen().await;
     }
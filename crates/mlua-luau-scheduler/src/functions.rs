@@ -7,12 +7,12 @@ use mlua::prelude::*;
 
 use crate::{
     error_callback::ThreadErrorCallback,
-    queue::{DeferredThreadQueue, SpawnedThreadQueue},
+    queue::{DeferredThreadQueue, IdleThreadQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
     scheduler::Scheduler,
     thread_id::ThreadId,
     traits::LuaSchedulerExt,
-    util::{is_poll_pending, LuaThreadOrFunction, ThreadResult},
+    util::{is_poll_pending, is_thread_active, LuaThreadOrFunction, ThreadResult},
 };
 
 const ERR_METADATA_NOT_ATTACHED: &str = "\
@@ -21,6 +21,8 @@ Lua state does not have scheduler metadata attached!\
 \nScheduler functions must always be created from within an active scheduler.\
 ";
 
+const ERR_RESUME_ACTIVE: &str = "cannot resume non-suspended coroutine";
+
 const EXIT_IMPL_LUA: &str = r"
 exit(...)
 yield()
@@ -69,6 +71,12 @@ pub struct Functions<'lua> {
         Does not resume instantly, only adds to the queue.
     */
     pub defer: LuaFunction<'lua>,
+    /**
+        Schedules a function / thread to run once the scheduler is idle.
+
+        Does not resume instantly, only adds to the idle queue.
+    */
+    pub idle: LuaFunction<'lua>,
     /**
         Cancels a function / thread, removing it from the queue.
     */
@@ -102,6 +110,10 @@ impl<'lua> Functions<'lua> {
             .app_data_ref::<DeferredThreadQueue>()
             .expect(ERR_METADATA_NOT_ATTACHED)
             .clone();
+        let idle_queue = lua
+            .app_data_ref::<IdleThreadQueue>()
+            .expect(ERR_METADATA_NOT_ATTACHED)
+            .clone();
         let error_callback = lua
             .app_data_ref::<ThreadErrorCallback>()
             .expect(ERR_METADATA_NOT_ATTACHED)
@@ -111,11 +123,21 @@ impl<'lua> Functions<'lua> {
             .expect(ERR_METADATA_NOT_ATTACHED)
             .clone();
 
+        let status = lua
+            .globals()
+            .get::<_, LuaTable>("coroutine")?
+            .get::<_, LuaFunction>("status")?;
+
         let resume_queue = defer_queue.clone();
         let resume_map = result_map.clone();
+        let resume_status_key = lua.create_registry_value(status.clone())?;
         let resume =
             lua.create_function(move |lua, (thread, args): (LuaThread, LuaMultiValue)| {
                 let _span = tracing::trace_span!("Scheduler::fn_resume").entered();
+                let status: LuaFunction = lua.registry_value(&resume_status_key)?;
+                if is_thread_active(&status, &thread)? {
+                    return (false, ERR_RESUME_ACTIVE).into_lua_multi(lua);
+                }
                 match thread.resume::<_, LuaMultiValue>(args.clone()) {
                     Ok(v) => {
                         if v.get(0).is_some_and(is_poll_pending) {
@@ -165,9 +187,17 @@ impl<'lua> Functions<'lua> {
             .into_function()?;
 
         let spawn_map = result_map.clone();
+        let spawn_status_key = lua.create_registry_value(status)?;
         let spawn = lua.create_function(
             move |lua, (tof, args): (LuaThreadOrFunction, LuaMultiValue)| {
                 let _span = tracing::trace_span!("Scheduler::fn_spawn").entered();
+                // NOTE: Only existing threads may be active, new ones never are
+                if let LuaThreadOrFunction::Thread(thread) = &tof {
+                    let status: LuaFunction = lua.registry_value(&spawn_status_key)?;
+                    if is_thread_active(&status, thread)? {
+                        return Err(LuaError::runtime(ERR_RESUME_ACTIVE));
+                    }
+                }
                 let thread = tof.into_thread(lua)?;
                 if thread.status() == LuaThreadStatus::Resumable {
                     // NOTE: We need to resume the thread once instantly for correct behavior,
@@ -213,6 +243,17 @@ impl<'lua> Functions<'lua> {
             },
         )?;
 
+        let idle = lua.create_function(
+            move |lua, (tof, args): (LuaThreadOrFunction, LuaMultiValue)| {
+                let _span = tracing::trace_span!("Scheduler::fn_idle").entered();
+                let thread = tof.into_thread(lua)?;
+                if thread.status() == LuaThreadStatus::Resumable {
+                    idle_queue.push_item(lua, &thread, args)?;
+                }
+                Ok(thread)
+            },
+        )?;
+
         let close = lua
             .globals()
             .get::<_, LuaTable>("coroutine")?
@@ -255,6 +296,7 @@ impl<'lua> Functions<'lua> {
             wrap,
             spawn,
             defer,
+            idle,
             cancel,
             exit,
         })
@@ -6,6 +6,8 @@ mod functions;
 mod queue;
 mod result_map;
 mod scheduler;
+mod state;
+mod stats;
 mod status;
 mod thread_id;
 mod traits;
@@ -13,6 +15,8 @@ mod util;
 
 pub use functions::Functions;
 pub use scheduler::Scheduler;
+pub use state::SchedulerState;
+pub use stats::{QueueStats, TaskKind};
 pub use status::Status;
 pub use thread_id::ThreadId;
 pub use traits::{IntoLuaThread, LuaSchedulerExt, LuaSpawnExt};
@@ -6,7 +6,12 @@ use event_listener::Event;
 use futures_lite::{Future, FutureExt};
 use mlua::prelude::*;
 
-use crate::{traits::IntoLuaThread, util::ThreadWithArgs, ThreadId};
+use crate::{
+    stats::{QueueStats, QueueStatsRecorder},
+    traits::IntoLuaThread,
+    util::ThreadWithArgs,
+    ThreadId,
+};
 
 /**
     Queue for storing [`LuaThread`]s with associated arguments.
@@ -18,13 +23,19 @@ use crate::{traits::IntoLuaThread, util::ThreadWithArgs, ThreadId};
 pub(crate) struct ThreadQueue {
     queue: Rc<ConcurrentQueue<ThreadWithArgs>>,
     event: Rc<Event>,
+    stats: QueueStatsRecorder,
 }
 
 impl ThreadQueue {
     pub fn new() -> Self {
         let queue = Rc::new(ConcurrentQueue::unbounded());
         let event = Rc::new(Event::new());
-        Self { queue, event }
+        let stats = QueueStatsRecorder::default();
+        Self {
+            queue,
+            event,
+            stats,
+        }
     }
 
     pub fn push_item<'lua>(
@@ -54,7 +65,10 @@ impl ThreadQueue {
     where
         'lua: 'outer,
     {
-        self.queue.try_iter().map(|stored| stored.into_inner(lua))
+        self.queue.try_iter().map(|stored| {
+            self.stats.record(stored.queued_at());
+            stored.into_inner(lua)
+        })
     }
 
     #[inline]
@@ -73,6 +87,15 @@ impl ThreadQueue {
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        self.stats.snapshot()
+    }
 }
 
 /**
@@ -99,6 +122,18 @@ impl DeferredThreadQueue {
     }
 }
 
+/**
+    Alias for [`ThreadQueue`], providing a newtype to store in Lua app data.
+*/
+#[derive(Debug, Clone, Deref, DerefMut)]
+pub(crate) struct IdleThreadQueue(ThreadQueue);
+
+impl IdleThreadQueue {
+    pub fn new() -> Self {
+        Self(ThreadQueue::new())
+    }
+}
+
 pub type LocalBoxFuture<'fut> = Pin<Box<dyn Future<Output = ()> + 'fut>>;
 
 /**
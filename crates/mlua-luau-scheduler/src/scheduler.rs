@@ -1,14 +1,18 @@
 #![allow(clippy::module_name_repetitions)]
 
 use std::{
-    cell::Cell,
+    any::Any,
+    cell::{Cell, RefCell},
+    mem,
+    panic::{resume_unwind, AssertUnwindSafe},
     process::ExitCode,
     rc::{Rc, Weak as WeakRc},
     sync::{Arc, Weak as WeakArc},
     thread::panicking,
 };
 
-use futures_lite::prelude::*;
+use futures_lite::{future, prelude::*};
+use futures_util::stream::FuturesUnordered;
 use mlua::prelude::*;
 
 use async_executor::{Executor, LocalExecutor};
@@ -17,8 +21,10 @@ use tracing::{debug, instrument, trace, trace_span, Instrument};
 use crate::{
     error_callback::ThreadErrorCallback,
     exit::Exit,
-    queue::{DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
+    queue::{DeferredThreadQueue, FuturesQueue, IdleThreadQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
+    state::SchedulerState,
+    stats::{QueueStats, TaskKind},
     status::Status,
     thread_id::ThreadId,
     traits::IntoLuaThread,
@@ -36,10 +42,16 @@ Lua state scheduler metadata was unexpectedly removed!\
 \nThis should never happen, and is likely a bug in the scheduler.\
 ";
 
+type PanicPayload = Box<dyn Any + Send>;
+
 const ERR_SET_CALLBACK_WHEN_RUNNING: &str = "\
 Cannot set error callback when scheduler is running!\
 ";
 
+const ERR_SET_BATCH_SIZE_WHEN_RUNNING: &str = "\
+Cannot set batch size when scheduler is running!\
+";
+
 /**
     A scheduler for running Lua threads and async tasks.
 */
@@ -48,9 +60,11 @@ pub struct Scheduler<'lua> {
     lua: &'lua Lua,
     queue_spawn: SpawnedThreadQueue,
     queue_defer: DeferredThreadQueue,
+    queue_idle: IdleThreadQueue,
     error_callback: ThreadErrorCallback,
     result_map: ThreadResultMap,
     status: Rc<Cell<Status>>,
+    batch_size: Rc<Cell<usize>>,
     exit: Exit,
 }
 
@@ -68,6 +82,7 @@ impl<'lua> Scheduler<'lua> {
     pub fn new(lua: &'lua Lua) -> Scheduler<'lua> {
         let queue_spawn = SpawnedThreadQueue::new();
         let queue_defer = DeferredThreadQueue::new();
+        let queue_idle = IdleThreadQueue::new();
         let error_callback = ThreadErrorCallback::default();
         let result_map = ThreadResultMap::new();
         let exit = Exit::new();
@@ -80,6 +95,10 @@ impl<'lua> Scheduler<'lua> {
             lua.app_data_ref::<DeferredThreadQueue>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
         );
+        assert!(
+            lua.app_data_ref::<IdleThreadQueue>().is_none(),
+            "{ERR_METADATA_ALREADY_ATTACHED}"
+        );
         assert!(
             lua.app_data_ref::<ThreadErrorCallback>().is_none(),
             "{ERR_METADATA_ALREADY_ATTACHED}"
@@ -95,19 +114,23 @@ impl<'lua> Scheduler<'lua> {
 
         lua.set_app_data(queue_spawn.clone());
         lua.set_app_data(queue_defer.clone());
+        lua.set_app_data(queue_idle.clone());
         lua.set_app_data(error_callback.clone());
         lua.set_app_data(result_map.clone());
         lua.set_app_data(exit.clone());
 
         let status = Rc::new(Cell::new(Status::NotStarted));
+        let batch_size = Rc::new(Cell::new(1));
 
         Scheduler {
             lua,
             queue_spawn,
             queue_defer,
+            queue_idle,
             error_callback,
             result_map,
             status,
+            batch_size,
             exit,
         }
     }
@@ -128,6 +151,21 @@ impl<'lua> Scheduler<'lua> {
         self.status.get()
     }
 
+    /**
+        Returns a handle for inspecting the current state of this scheduler.
+
+        See [`SchedulerState`] for more information.
+    */
+    #[must_use]
+    pub fn state(&self) -> SchedulerState {
+        SchedulerState {
+            status: Rc::clone(&self.status),
+            queue_spawn: self.queue_spawn.clone(),
+            queue_defer: self.queue_defer.clone(),
+            queue_idle: self.queue_idle.clone(),
+        }
+    }
+
     /**
         Sets the error callback for this scheduler.
 
@@ -164,6 +202,31 @@ impl<'lua> Scheduler<'lua> {
         self.error_callback.clear();
     }
 
+    /**
+        Sets the batch size for this scheduler.
+
+        When greater than one, Lua threads that are ready to run are grouped into batches
+        of up to this size, and each batch is driven by a single task on the executor,
+        instead of each thread being driven by a task of its own.
+
+        This reduces executor overhead for workloads that run lots of small threads
+        at once, such as servers that spawn a new thread for every request.
+
+        Defaults to one, meaning that threads are not batched.
+
+        # Panics
+
+        Panics if the scheduler is currently running, or if the given batch size is zero.
+    */
+    pub fn set_batch_size(&self, size: usize) {
+        assert!(
+            !self.status().is_running(),
+            "{ERR_SET_BATCH_SIZE_WHEN_RUNNING}"
+        );
+        assert!(size > 0, "Batch size must be greater than zero");
+        self.batch_size.set(size);
+    }
+
     /**
         Gets the exit code for this scheduler, if one has been set.
     */
@@ -172,6 +235,17 @@ impl<'lua> Scheduler<'lua> {
         self.exit.get()
     }
 
+    /**
+        Takes the exit code out of this scheduler, if one has been set.
+
+        This makes it possible to run the scheduler again after it
+        was stopped by setting an exit code, such as to run cleanup code.
+    */
+    #[must_use]
+    pub fn take_exit_code(&self) -> Option<ExitCode> {
+        self.exit.take()
+    }
+
     /**
         Sets the exit code for this scheduler.
 
@@ -233,6 +307,49 @@ impl<'lua> Scheduler<'lua> {
         Ok(id)
     }
 
+    /**
+        Schedules a chunk / function / thread to run once the scheduler is idle.
+
+        The scheduler is idle when there are no spawned or deferred threads
+        waiting to run, and no futures that are immediately ready to make progress.
+
+        Idle threads run only once, and are guaranteed to be resumed in the order that they were pushed.
+
+        # Returns
+
+        Returns a [`ThreadId`] that can be used to retrieve the result of the thread.
+
+        Note that the result may not be available until [`Scheduler::run`] completes.
+
+        # Errors
+
+        Errors when out of memory.
+    */
+    pub fn push_thread_idle(
+        &self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        let id = self.queue_idle.push_item(self.lua, thread, args)?;
+        self.result_map.track(id);
+        Ok(id)
+    }
+
+    /**
+        Gets statistics about the time that Lua threads of the given kind
+        have spent waiting in their queue before being resumed.
+
+        This may be used to tell apart slow Lua code from a backlog in the scheduler itself.
+    */
+    #[must_use]
+    pub fn queue_stats(&self, kind: TaskKind) -> QueueStats {
+        match kind {
+            TaskKind::Spawned => self.queue_spawn.stats(),
+            TaskKind::Deferred => self.queue_defer.stats(),
+            TaskKind::Idle => self.queue_idle.stats(),
+        }
+    }
+
     /**
         Gets the tracked result for the [`LuaThread`] with the given [`ThreadId`].
 
@@ -270,6 +387,10 @@ impl<'lua> Scheduler<'lua> {
         # Panics
 
         Panics if the given Lua state already has a scheduler attached to it.
+
+        Also panics if any Lua thread or future panicked while running, which is a fatal
+        error - the scheduler stops running anything else, and the panic is resumed once
+        it has been cleaned up, instead of being silently dropped with the detached task.
     */
     #[allow(clippy::too_many_lines)]
     #[instrument(level = "debug", name = "Scheduler::run", skip(self))]
@@ -288,6 +409,7 @@ impl<'lua> Scheduler<'lua> {
         let local_exec = LocalExecutor::new();
         let main_exec = Arc::new(Executor::new());
         let fut_queue = Rc::new(FuturesQueue::new());
+        let panic: Rc<RefCell<Option<PanicPayload>>> = Rc::new(RefCell::new(None));
 
         /*
             Store the main executor and queue in Lua, so that they may be used with LuaSchedulerExt.
@@ -317,12 +439,15 @@ impl<'lua> Scheduler<'lua> {
             4. A new thread-local future is available to run on the local executor
             5. Task(s) scheduled on the Lua executor have made progress and should be polled again
 
+            If none of the above are immediately ready, and there are Lua threads waiting on the idle queue,
+            the idle threads are run instead of waiting - they will never run while there is other work to do.
+
             This ordering is vital to ensure that we don't accidentally exit the main loop
             when there are new Lua threads to enqueue and potentially more work to be done.
         */
         let fut = async {
             let result_map = self.result_map.clone();
-            let process_thread = |thread: LuaThread<'lua>, args| {
+            let create_thread_fut = |thread: LuaThread<'lua>, args| {
                 // NOTE: Thread may have been cancelled from Lua
                 // before we got here, so we need to check it again
                 if thread.status() == LuaThreadStatus::Resumable {
@@ -356,8 +481,33 @@ impl<'lua> Scheduler<'lua> {
                             }
                         }
                     };
-                    // Spawn it on the executor
-                    local_exec.spawn(fut).detach();
+                    Some(fut)
+                } else {
+                    None
+                }
+            };
+
+            // Spawn threads on the executor, either directly or in batches
+            let batch_size = self.batch_size.get();
+            let spawn_batch = |batch: Vec<_>| {
+                let mut futs = batch.into_iter().collect::<FuturesUnordered<_>>();
+                let fut = async move { while futs.next().await.is_some() {} };
+                local_exec
+                    .spawn(catch_panic(fut, Rc::clone(&panic)))
+                    .detach();
+            };
+            let process_thread = |thread: LuaThread<'lua>, args, batch: &mut Vec<_>| {
+                if let Some(fut) = create_thread_fut(thread, args) {
+                    if batch_size > 1 {
+                        batch.push(fut);
+                        if batch.len() >= batch_size {
+                            spawn_batch(mem::take(batch));
+                        }
+                    } else {
+                        local_exec
+                            .spawn(catch_panic(fut, Rc::clone(&panic)))
+                            .detach();
+                    }
                 }
             };
 
@@ -380,18 +530,34 @@ impl<'lua> Scheduler<'lua> {
                 };
 
                 // 1 + 2 + 3 + 4 + 5
-                fut_exit
+                let fut_any = fut_exit
                     .or(fut_spawn)
                     .or(fut_defer)
                     .or(fut_futs)
-                    .or(fut_tick.instrument(span_tick.or_current()))
-                    .await;
+                    .or(fut_tick.instrument(span_tick.or_current()));
+
+                // Idle - run idle threads if nothing else is immediately ready
+                let mut batch = Vec::new();
+                let mut num_idle = 0;
+                if self.queue_idle.is_empty() {
+                    fut_any.await;
+                } else if future::poll_once(fut_any).await.is_none() {
+                    let _span = trace_span!("Scheduler::drain_idle").entered();
+                    for (thread, args) in self.queue_idle.drain_items(self.lua) {
+                        process_thread(thread, args, &mut batch);
+                        num_idle += 1;
+                    }
+                }
 
                 // Check if we should exit
                 if self.exit.get().is_some() {
                     debug!("exit signal received");
                     break;
                 }
+                if panic.borrow().is_some() {
+                    debug!("fatal error, a task panicked");
+                    break;
+                }
 
                 // Process spawned threads first, then deferred threads, then futures
                 let mut num_spawned = 0;
@@ -400,21 +566,26 @@ impl<'lua> Scheduler<'lua> {
                 {
                     let _span = trace_span!("Scheduler::drain_spawned").entered();
                     for (thread, args) in self.queue_spawn.drain_items(self.lua) {
-                        process_thread(thread, args);
+                        process_thread(thread, args, &mut batch);
                         num_spawned += 1;
                     }
                 }
                 {
                     let _span = trace_span!("Scheduler::drain_deferred").entered();
                     for (thread, args) in self.queue_defer.drain_items(self.lua) {
-                        process_thread(thread, args);
+                        process_thread(thread, args, &mut batch);
                         num_deferred += 1;
                     }
                 }
+                if !batch.is_empty() {
+                    spawn_batch(batch);
+                }
                 {
                     let _span = trace_span!("Scheduler::drain_futures").entered();
                     for fut in fut_queue.drain_items() {
-                        local_exec.spawn(fut).detach();
+                        local_exec
+                            .spawn(catch_panic(fut, Rc::clone(&panic)))
+                            .detach();
                         num_futures += 1;
                     }
                 }
@@ -423,12 +594,14 @@ impl<'lua> Scheduler<'lua> {
                 // above, and there are no remaining tasks to run later
                 let completed = local_exec.is_empty()
                     && self.queue_spawn.is_empty()
-                    && self.queue_defer.is_empty();
+                    && self.queue_defer.is_empty()
+                    && self.queue_idle.is_empty();
                 trace!(
                     futures_spawned = num_futures,
                     futures_processed = num_processed,
                     lua_threads_spawned = num_spawned,
                     lua_threads_deferred = num_deferred,
+                    lua_threads_idle = num_idle,
                     "loop"
                 );
                 if completed {
@@ -449,6 +622,23 @@ impl<'lua> Scheduler<'lua> {
         self.lua
             .remove_app_data::<WeakRc<FuturesQueue>>()
             .expect(ERR_METADATA_REMOVED);
+
+        // Resume any panic from a task, now that we have been cleaned up
+        if let Some(payload) = panic.take() {
+            resume_unwind(payload);
+        }
+    }
+}
+
+/**
+    Runs the given future, storing its panic payload, if it panics,
+    so that the scheduler can stop and resume unwinding with it.
+
+    Only the first panic is stored, since that is usually the cause of any later ones.
+*/
+async fn catch_panic(fut: impl Future<Output = ()>, panic: Rc<RefCell<Option<PanicPayload>>>) {
+    if let Err(payload) = AssertUnwindSafe(fut).catch_unwind().await {
+        panic.borrow_mut().get_or_insert(payload);
     }
 }
 
@@ -459,6 +649,7 @@ impl Drop for Scheduler<'_> {
             // this may abort the program instead of safely unwinding
             self.lua.remove_app_data::<SpawnedThreadQueue>();
             self.lua.remove_app_data::<DeferredThreadQueue>();
+            self.lua.remove_app_data::<IdleThreadQueue>();
             self.lua.remove_app_data::<ThreadErrorCallback>();
             self.lua.remove_app_data::<ThreadResultMap>();
             self.lua.remove_app_data::<Exit>();
@@ -470,6 +661,9 @@ impl Drop for Scheduler<'_> {
             self.lua
                 .remove_app_data::<DeferredThreadQueue>()
                 .expect(ERR_METADATA_REMOVED);
+            self.lua
+                .remove_app_data::<IdleThreadQueue>()
+                .expect(ERR_METADATA_REMOVED);
             self.lua
                 .remove_app_data::<ThreadErrorCallback>()
                 .expect(ERR_METADATA_REMOVED);
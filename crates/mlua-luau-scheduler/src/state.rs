@@ -0,0 +1,56 @@
+use std::{cell::Cell, fmt, rc::Rc};
+
+use crate::{
+    queue::{DeferredThreadQueue, IdleThreadQueue, SpawnedThreadQueue},
+    stats::TaskKind,
+    status::Status,
+};
+
+/**
+    A handle for inspecting the current state of a [`Scheduler`](crate::Scheduler).
+
+    The handle does not borrow the scheduler or its Lua state, and
+    inspecting it never panics, so it may be used to get information
+    about a scheduler from anywhere, even while handling a panic.
+*/
+#[derive(Debug, Clone)]
+pub struct SchedulerState {
+    pub(crate) status: Rc<Cell<Status>>,
+    pub(crate) queue_spawn: SpawnedThreadQueue,
+    pub(crate) queue_defer: DeferredThreadQueue,
+    pub(crate) queue_idle: IdleThreadQueue,
+}
+
+impl SchedulerState {
+    /**
+        Returns the current status of the scheduler.
+    */
+    #[must_use]
+    pub fn status(&self) -> Status {
+        self.status.get()
+    }
+
+    /**
+        Returns the number of Lua threads of the given kind
+        that are currently waiting in the scheduler queue.
+    */
+    #[must_use]
+    pub fn queued(&self, kind: TaskKind) -> usize {
+        match kind {
+            TaskKind::Spawned => self.queue_spawn.len(),
+            TaskKind::Deferred => self.queue_defer.len(),
+            TaskKind::Idle => self.queue_idle.len(),
+        }
+    }
+}
+
+impl fmt::Display for SchedulerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status {:?}, queued", self.status())?;
+        for (index, kind) in TaskKind::ALL.into_iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            write!(f, "{separator} {} {kind}", self.queued(kind))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+// Number of recent samples kept around for computing percentiles,
+// older samples are still counted towards the total and average
+const MAX_SAMPLES: usize = 1024;
+
+/**
+    The different kinds of Lua threads that may be waiting in a [`Scheduler`](crate::Scheduler) queue.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskKind {
+    /// Threads pushed to the front of the scheduler, using `spawn`.
+    Spawned,
+    /// Threads pushed to the back of the scheduler, using `defer` or after yielding.
+    Deferred,
+    /// Threads waiting for the scheduler to become idle.
+    Idle,
+}
+
+impl TaskKind {
+    /**
+        All of the task kinds, in the order they are processed by the scheduler.
+    */
+    pub const ALL: [TaskKind; 3] = [Self::Spawned, Self::Deferred, Self::Idle];
+
+    /**
+        Returns the lowercase name of this task kind.
+    */
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Spawned => "spawned",
+            Self::Deferred => "deferred",
+            Self::Idle => "idle",
+        }
+    }
+}
+
+impl fmt::Display for TaskKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/**
+    Statistics about the time that Lua threads spent waiting in a scheduler queue,
+    from being pushed to the queue until the scheduler started resuming them.
+
+    Percentiles are computed from the most recent threads only, while
+    the count, average, and maximum include every thread ever queued.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// The total number of threads that have left the queue.
+    pub count: u64,
+    /// The average time spent in the queue.
+    pub average: Duration,
+    /// The median time spent in the queue.
+    pub p50: Duration,
+    /// The 90th percentile of time spent in the queue.
+    pub p90: Duration,
+    /// The 99th percentile of time spent in the queue.
+    pub p99: Duration,
+    /// The longest time spent in the queue.
+    pub max: Duration,
+}
+
+impl fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "count {}, avg {:?}, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+            self.count, self.average, self.p50, self.p90, self.p99, self.max
+        )
+    }
+}
+
+/**
+    Recorder for the time that items spend in a queue.
+
+    Cloning gives a handle to the same underlying recorder.
+*/
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QueueStatsRecorder {
+    inner: Rc<RefCell<QueueStatsInner>>,
+}
+
+#[derive(Debug, Default)]
+struct QueueStatsInner {
+    count: u64,
+    total: Duration,
+    max: Duration,
+    samples: VecDeque<Duration>,
+}
+
+impl QueueStatsRecorder {
+    pub fn record(&self, queued_at: Instant) {
+        let elapsed = queued_at.elapsed();
+        let mut inner = self.inner.borrow_mut();
+        inner.count += 1;
+        inner.total += elapsed;
+        inner.max = inner.max.max(elapsed);
+        if inner.samples.len() >= MAX_SAMPLES {
+            inner.samples.pop_front();
+        }
+        inner.samples.push_back(elapsed);
+    }
+
+    pub fn snapshot(&self) -> QueueStats {
+        let inner = self.inner.borrow();
+        if inner.count == 0 {
+            return QueueStats::default();
+        }
+
+        let mut sorted = inner.samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let percentile = |p: usize| sorted[((sorted.len() * p).div_ceil(100)).max(1) - 1];
+
+        QueueStats {
+            count: inner.count,
+            average: Duration::from_nanos(
+                u64::try_from(inner.total.as_nanos() / u128::from(inner.count)).unwrap_or(u64::MAX),
+            ),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: inner.max,
+        }
+    }
+}
@@ -6,14 +6,16 @@ use std::{
 };
 
 use async_executor::{Executor, Task};
+use futures_lite::future;
 use mlua::prelude::*;
 use tracing::trace;
 
 use crate::{
     exit::Exit,
-    queue::{DeferredThreadQueue, FuturesQueue, SpawnedThreadQueue},
+    queue::{DeferredThreadQueue, FuturesQueue, IdleThreadQueue, SpawnedThreadQueue},
     result_map::ThreadResultMap,
     scheduler::Scheduler,
+    stats::{QueueStats, TaskKind},
     thread_id::ThreadId,
 };
 
@@ -114,6 +116,21 @@ pub trait LuaSchedulerExt<'lua> {
         args: impl IntoLuaMulti<'lua>,
     ) -> LuaResult<ThreadId>;
 
+    /**
+        Pushes a lua thread to the **idle** queue of the current scheduler.
+
+        See [`Scheduler::push_thread_idle`] for more information.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn push_thread_idle(
+        &'lua self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId>;
+
     /**
         Registers the given thread to be tracked within the current scheduler.
 
@@ -142,6 +159,17 @@ pub trait LuaSchedulerExt<'lua> {
         Panics if called outside of a running [`Scheduler`].
     */
     fn wait_for_thread(&'lua self, id: ThreadId) -> impl Future<Output = ()>;
+
+    /**
+        Gets queue latency statistics for the given kind of thread.
+
+        See [`Scheduler::queue_stats`] for more information.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn queue_stats(&'lua self, kind: TaskKind) -> QueueStats;
 }
 
 /**
@@ -280,6 +308,24 @@ pub trait LuaSpawnExt<'lua> {
     where
         F: FnOnce() -> T + Send + 'static,
         T: Send + 'static;
+
+    /**
+        Blocks the current thread until the given future completes, and returns its output.
+
+        Background tasks spawned using [`LuaSpawnExt::spawn`] will keep making progress
+        while blocked, but Lua threads and thread-local futures will not run until the
+        given future completes, so the future must not wait for any of those.
+
+        This is useful when a future must be driven to completion from a context where
+        Lua can not yield, such as inside of a metamethod.
+
+        # Panics
+
+        Panics if called outside of a running [`Scheduler`].
+    */
+    fn block_on<F>(&self, fut: F) -> F::Output
+    where
+        F: Future;
 }
 
 impl<'lua> LuaSchedulerExt<'lua> for Lua {
@@ -312,6 +358,17 @@ impl<'lua> LuaSchedulerExt<'lua> for Lua {
         queue.push_item(self, thread, args)
     }
 
+    fn push_thread_idle(
+        &'lua self,
+        thread: impl IntoLuaThread<'lua>,
+        args: impl IntoLuaMulti<'lua>,
+    ) -> LuaResult<ThreadId> {
+        let queue = self
+            .app_data_ref::<IdleThreadQueue>()
+            .expect("lua threads can only be pushed from within an active scheduler");
+        queue.push_item(self, thread, args)
+    }
+
     fn track_thread(&'lua self, id: ThreadId) {
         let map = self
             .app_data_ref::<ThreadResultMap>()
@@ -332,6 +389,21 @@ impl<'lua> LuaSchedulerExt<'lua> for Lua {
             .expect("lua threads results can only be retrieved from within an active scheduler");
         async move { map.listen(id).await }
     }
+
+    fn queue_stats(&'lua self, kind: TaskKind) -> QueueStats {
+        const ERR: &str = "queue stats can only be retrieved from within an active scheduler";
+        match kind {
+            TaskKind::Spawned => self
+                .app_data_ref::<SpawnedThreadQueue>()
+                .expect(ERR)
+                .stats(),
+            TaskKind::Deferred => self
+                .app_data_ref::<DeferredThreadQueue>()
+                .expect(ERR)
+                .stats(),
+            TaskKind::Idle => self.app_data_ref::<IdleThreadQueue>().expect(ERR).stats(),
+        }
+    }
 }
 
 impl<'lua> LuaSpawnExt<'lua> for Lua {
@@ -375,4 +447,17 @@ impl<'lua> LuaSpawnExt<'lua> for Lua {
         trace!("spawning blocking task on executor");
         exec.spawn(blocking::unblock(f))
     }
+
+    fn block_on<F>(&self, fut: F) -> F::Output
+    where
+        F: Future,
+    {
+        let exec = self
+            .app_data_ref::<WeakArc<Executor>>()
+            .expect("futures can only be blocked on within an active scheduler")
+            .upgrade()
+            .expect("executor was dropped");
+        trace!("blocking on future");
+        future::block_on(exec.run(fut))
+    }
 }
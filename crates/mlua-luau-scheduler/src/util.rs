@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use futures_lite::StreamExt;
 use mlua::prelude::*;
 use tracing::instrument;
@@ -30,6 +32,18 @@ pub(crate) async fn run_until_yield<'lua>(
     stream.next().await
 }
 
+/**
+    Checks if the given thread is active, meaning that it is either the running
+    thread, or a thread that resumed the running thread and is waiting for it.
+
+    Active threads have a resumable status in mlua, same as suspended threads, but resuming
+    them corrupts their stack instead of erroring, so this uses `coroutine.status` instead.
+*/
+pub(crate) fn is_thread_active(status: &LuaFunction, thread: &LuaThread) -> LuaResult<bool> {
+    let status = status.call::<_, LuaString>(thread)?;
+    Ok(matches!(status.as_bytes(), b"running" | b"normal"))
+}
+
 /**
     Checks if the given [`LuaValue`] is the async `POLL_PENDING` constant.
 */
@@ -80,6 +94,7 @@ impl ThreadResult {
 pub(crate) struct ThreadWithArgs {
     key_thread: LuaRegistryKey,
     key_args: LuaRegistryKey,
+    queued_at: Instant,
 }
 
 impl ThreadWithArgs {
@@ -96,9 +111,14 @@ impl ThreadWithArgs {
         Ok(Self {
             key_thread,
             key_args,
+            queued_at: Instant::now(),
         })
     }
 
+    pub fn queued_at(&self) -> Instant {
+        self.queued_at
+    }
+
     pub fn into_inner(self, lua: &Lua) -> (LuaThread<'_>, LuaMultiValue<'_>) {
         let thread = lua.registry_value(&self.key_thread).unwrap();
         let argsv = lua.registry_value(&self.key_args).unwrap();